@@ -0,0 +1,53 @@
+//! Emulation speed of a game streaming its cartridge, as GBA Video carts do: a synthetic
+//! 32MB ROM copies itself to EWRAM in a tight LDM/STM loop.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use emu::{cartridge_header::CartridgeHeader, cpu::asm::assemble_arm, gba::Gba, test_rom::TestRom};
+
+const ROM_SIZE: usize = 32 * 1024 * 1024;
+
+fn streaming_gba() -> Gba {
+    let mut bios = [0; 0x4000];
+    bios[..4].copy_from_slice(
+        &assemble_arm("MOV PC, #0x08000000", 0)
+            .unwrap()
+            .to_le_bytes(),
+    );
+
+    let mut rom = (0..=u8::MAX).cycle().take(ROM_SIZE).collect::<Vec<u8>>();
+    // The strings of the header must be valid.
+    rom[0xA0..0xBD].fill(0);
+
+    let program = [
+        "MOV R0, #0x08000000",
+        "MOV R1, #0x02000000",
+        // Start of the loop.
+        "LDMIA R0!, {R2-R9}",
+        "STMIA R1, {R2-R9}",
+        "B 0x08000008",
+    ]
+    .iter()
+    .zip((0x0800_0000..).step_by(4))
+    .map(|(line, address)| assemble_arm(line, address).unwrap())
+    .collect::<Vec<_>>();
+    let rom = TestRom::from_bytes(rom).words(0, &program).build();
+
+    Gba::new(CartridgeHeader::new(&rom).unwrap(), bios, rom)
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut gba = streaming_gba();
+    let output = gba.frame_output();
+
+    c.bench_function("rom_streaming_frame", |b| {
+        b.iter(|| {
+            let frame = output.frame_count();
+            while output.frame_count() == frame {
+                gba.step();
+            }
+        });
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,131 @@
+//! Writes of save files that a crash or a power loss can't leave half done.
+//!
+//! The new content goes to a temporary file next to the target, renamed over it once
+//! complete: the target always holds either the old content or the new one. The
+//! previous versions are kept as numbered backups, `game.sav.1` being the latest:
+//! ```text
+//! default.sav      written now
+//! default.sav.1    the previous write
+//! default.sav.2    the one before
+//! ```
+
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Backups kept by default, enough to go back past a save the game itself corrupted.
+pub const DEFAULT_BACKUPS: usize = 2;
+
+/// Path of the backup `generation` of `path`, 1 for the latest.
+#[must_use]
+pub fn backup_path(path: &Path, generation: usize) -> PathBuf {
+    with_suffix(path, &generation.to_string())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".");
+    name.push(suffix);
+
+    PathBuf::from(name)
+}
+
+/// Replaces the content of `path` with `data`, keeping its previous `backups` versions.
+///
+/// # Errors
+/// It fails if the temporary file can't be written or renamed, `path` is then left as
+/// it was. A backup that can't be rotated fails the write before `path` is replaced.
+pub fn write(path: &Path, data: &[u8], backups: usize) -> io::Result<()> {
+    let temporary = with_suffix(path, "tmp");
+    let result = write_synced(&temporary, data)
+        .and_then(|()| rotate_backups(path, backups))
+        .and_then(|()| fs::rename(&temporary, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+
+    result
+}
+
+fn write_synced(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(data)?;
+
+    // The rename must not reach the disk before the content.
+    file.sync_all()
+}
+
+/// Shifts the backups of `path` by a generation, the oldest dropped, and copies `path`
+/// as the latest. Copied rather than renamed, `path` never goes missing.
+fn rotate_backups(path: &Path, backups: usize) -> io::Result<()> {
+    if backups == 0 || !path.is_file() {
+        return Ok(());
+    }
+
+    for generation in (1..backups).rev() {
+        let older = backup_path(path, generation);
+        if older.is_file() {
+            fs::rename(&older, backup_path(path, generation + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+
+    Ok(())
+}
+
+/// Paths of the backups of `path` that exist, the latest first.
+#[must_use]
+pub fn existing_backups(path: &Path) -> Vec<PathBuf> {
+    let mut backups = Vec::new();
+    loop {
+        let backup = backup_path(path, backups.len() + 1);
+        if !backup.is_file() {
+            return backups;
+        }
+        backups.push(backup);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_the_backups() {
+        let dir = std::env::temp_dir().join("clementine-atomic-file");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.sav");
+
+        for content in 1..=4 {
+            write(&path, &[content], 2).unwrap();
+        }
+
+        assert_eq!(fs::read(&path).unwrap(), [4]);
+        assert_eq!(fs::read(backup_path(&path, 1)).unwrap(), [3]);
+        assert_eq!(fs::read(backup_path(&path, 2)).unwrap(), [2]);
+        assert_eq!(existing_backups(&path).len(), 2);
+        assert!(!with_suffix(&path, "tmp").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_write_keeps_the_file() {
+        let dir = std::env::temp_dir().join("clementine-atomic-file-failed");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.sav");
+        write(&path, &[1], 1).unwrap();
+
+        // A directory where the temporary file goes can't be replaced by a file.
+        fs::create_dir(with_suffix(&path, "tmp")).unwrap();
+        assert!(write(&path, &[2], 1).is_err());
+        assert_eq!(fs::read(&path).unwrap(), [1]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
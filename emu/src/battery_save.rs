@@ -0,0 +1,215 @@
+//! Battery save files (`.sav`) in the layouts written by the common emulators and flash
+//! carts, to import the saves users already have and export ones the other tools read.
+//!
+//! - mGBA and VBA write the backup memory as is, sometimes followed by a small footer
+//!   with the state of the cartridge's real-time clock.
+//! - EZ-Flash and some other flash carts pad the memory with 0xFF up to a power of two,
+//!   for example a 512 bytes EEPROM in an 8 `KBytes` file.
+//! - Some older tools store EEPROM saves with the bytes of each 64-bit word reversed.
+//!   That order can't be told apart from the content, it's only chosen explicitly.
+
+use std::fmt;
+
+/// Longest footer appended after the memory, mGBA and VBA write 16 or 20 bytes of clock.
+const MAX_FOOTER: usize = 0x40;
+
+/// Size of the unit EEPROMs are read and written by.
+const EEPROM_WORD: usize = 8;
+
+/// How the backup memory is laid out in a save file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveLayout {
+    /// Exactly the content of the memory, the format exported by default.
+    Raw,
+    /// The memory followed by 0xFF up to `len` bytes.
+    Padded { len: usize },
+    /// EEPROM content with the bytes of every 64-bit word reversed.
+    EepromSwapped,
+}
+
+impl fmt::Display for SaveLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Raw => write!(f, "raw"),
+            Self::Padded { len } => write!(f, "padded to {len} bytes"),
+            Self::EepromSwapped => write!(f, "EEPROM, byte-swapped words"),
+        }
+    }
+}
+
+/// Backup memory read from a save file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ImportedSave {
+    /// Content of the memory, exactly as large as the memory.
+    pub data: Vec<u8>,
+    /// Layout the file was found in.
+    pub layout: SaveLayout,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BatterySaveError {
+    /// The cartridge has no backup memory to import the save into.
+    NoBackupMemory,
+    /// The size of the file doesn't match any layout of a memory of `capacity` bytes.
+    UnexpectedSize { len: usize, capacity: usize },
+}
+
+impl fmt::Display for BatterySaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoBackupMemory => write!(f, "the cartridge has no backup memory"),
+            Self::UnexpectedSize { len, capacity } => write!(
+                f,
+                "a save file of {len} bytes doesn't fit a backup memory of {capacity} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BatterySaveError {}
+
+/// Reads the content of a memory of `capacity` bytes from `file`, telling the layout
+/// from the size of the file.
+///
+/// A file smaller than the memory by a power of two, such as a 64 `KBytes` Flash save
+/// for a chip detected as 128 `KBytes`, fills the start of the memory and the rest
+/// reads as erased.
+///
+/// # Errors
+/// It fails if the size of `file` matches no layout.
+pub fn import(file: &[u8], capacity: usize) -> Result<ImportedSave, BatterySaveError> {
+    let len = file.len();
+    let unexpected = BatterySaveError::UnexpectedSize { len, capacity };
+
+    let layout = if len == capacity || (len > capacity && len - capacity <= MAX_FOOTER) {
+        SaveLayout::Raw
+    } else if len > capacity && len.is_power_of_two() {
+        SaveLayout::Padded { len }
+    } else if len < capacity && len.is_power_of_two() && capacity.is_multiple_of(len) {
+        let mut data = vec![0xFF; capacity];
+        data[..len].copy_from_slice(file);
+
+        return Ok(ImportedSave {
+            data,
+            layout: SaveLayout::Raw,
+        });
+    } else {
+        return Err(unexpected);
+    };
+
+    Ok(ImportedSave {
+        data: file[..capacity].to_vec(),
+        layout,
+    })
+}
+
+/// Reads an EEPROM save in the given `layout`, for the byte order that [`import`] can't
+/// detect.
+///
+/// # Errors
+/// It fails if the size of `file` matches no layout.
+pub fn import_as(
+    file: &[u8],
+    capacity: usize,
+    layout: SaveLayout,
+) -> Result<ImportedSave, BatterySaveError> {
+    let mut imported = import(file, capacity)?;
+
+    if layout == SaveLayout::EepromSwapped {
+        swap_eeprom_words(&mut imported.data);
+    }
+    imported.layout = layout;
+
+    Ok(imported)
+}
+
+/// Writes the content of the memory in `layout`. Padding shorter than the memory
+/// leaves it as is.
+#[must_use]
+pub fn export(data: &[u8], layout: SaveLayout) -> Vec<u8> {
+    let mut file = data.to_vec();
+
+    match layout {
+        SaveLayout::Raw => {}
+        SaveLayout::Padded { len } => {
+            if len > file.len() {
+                file.resize(len, 0xFF);
+            }
+        }
+        SaveLayout::EepromSwapped => swap_eeprom_words(&mut file),
+    }
+
+    file
+}
+
+fn swap_eeprom_words(data: &mut [u8]) {
+    for word in data.chunks_exact_mut(EEPROM_WORD) {
+        word.reverse();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| u8::try_from(i % 251).unwrap()).collect()
+    }
+
+    #[test]
+    fn detects_raw_and_footer() {
+        let data = pattern(0x1_0000);
+        let imported = import(&data, 0x1_0000).unwrap();
+        assert_eq!(imported.layout, SaveLayout::Raw);
+        assert_eq!(imported.data, data);
+
+        // mGBA appends the clock of the cartridge after the memory.
+        let mut file = data.clone();
+        file.extend_from_slice(&[0x12; 16]);
+        let imported = import(&file, 0x1_0000).unwrap();
+        assert_eq!(imported.layout, SaveLayout::Raw);
+        assert_eq!(imported.data, data);
+    }
+
+    #[test]
+    fn detects_padding() {
+        let data = pattern(0x200);
+        let file = export(&data, SaveLayout::Padded { len: 0x2000 });
+        assert_eq!(file.len(), 0x2000);
+        assert!(file[0x200..].iter().all(|&b| b == 0xFF));
+
+        let imported = import(&file, 0x200).unwrap();
+        assert_eq!(imported.layout, SaveLayout::Padded { len: 0x2000 });
+        assert_eq!(imported.data, data);
+    }
+
+    #[test]
+    fn smaller_save_fills_start_of_memory() {
+        let data = pattern(0x1_0000);
+        let imported = import(&data, 0x2_0000).unwrap();
+        assert_eq!(&imported.data[..0x1_0000], &data[..]);
+        assert!(imported.data[0x1_0000..].iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn rejects_unexpected_size() {
+        assert_eq!(
+            import(&[0; 1000], 0x1_0000),
+            Err(BatterySaveError::UnexpectedSize {
+                len: 1000,
+                capacity: 0x1_0000
+            })
+        );
+        assert!(import(&[], 0x200).is_err());
+    }
+
+    #[test]
+    fn eeprom_words_round_trip() {
+        let data = pattern(0x200);
+        let file = export(&data, SaveLayout::EepromSwapped);
+        assert_eq!(&file[..8], &[7, 6, 5, 4, 3, 2, 1, 0]);
+
+        let imported = import_as(&file, 0x200, SaveLayout::EepromSwapped).unwrap();
+        assert_eq!(imported.data, data);
+    }
+}
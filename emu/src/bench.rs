@@ -0,0 +1,278 @@
+//! Benchmark: runs a game as fast as possible for a number of frames and reports the
+//! speed reached and where the time went, to track performance regressions.
+//!
+//! ```no_run
+//! use emu::{bench, cartridge_header::CartridgeHeader, gba::Gba};
+//!
+//! let bios = std::fs::read("gba_bios.bin").unwrap().try_into().unwrap();
+//! let rom = std::fs::read("game.gba").unwrap();
+//! let mut gba = Gba::new(CartridgeHeader::new(&rom).unwrap(), bios, rom);
+//!
+//! println!("{}", bench::run(&mut gba, 600));
+//! ```
+//!
+//! The components of the bus step every cycle, for a few nanoseconds each: timing all
+//! of them would take longer than running them. Only one cycle every [`SAMPLE_PERIOD`]
+//! is timed, the breakdown is an estimate the sampling barely slows down. The CPU gets
+//! the rest of the time, with the frame handling of [`Gba`] around it.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{self, FRAME_RATE},
+    frame_guard::FrameOverrun,
+    gba::Gba,
+};
+
+/// Bus cycles between two timed ones.
+pub const SAMPLE_PERIOD: u32 = 1024;
+
+/// Empty spans measured to know what measuring costs, see [`BusProfile::overhead`].
+const CALIBRATION_SPANS: u32 = 10_000;
+
+/// Parts of the emulator timed apart, the components in the order the bus steps them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Subsystem {
+    Interrupts,
+    Lcd,
+    Sound,
+    Dma,
+    Timers,
+    Serial,
+    Keypad,
+    /// The backup memory and the add-on hardware of the cartridge.
+    Cartridge,
+    Cpu,
+}
+
+impl Subsystem {
+    /// The components of the bus, as indexed in [`BusProfile`].
+    const COMPONENTS: [Self; 8] = [
+        Self::Interrupts,
+        Self::Lcd,
+        Self::Sound,
+        Self::Dma,
+        Self::Timers,
+        Self::Serial,
+        Self::Keypad,
+        Self::Cartridge,
+    ];
+}
+
+impl fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Interrupts => "interrupts",
+            Self::Lcd => "LCD",
+            Self::Sound => "sound",
+            Self::Dma => "DMA",
+            Self::Timers => "timers",
+            Self::Serial => "serial",
+            Self::Keypad => "keypad",
+            Self::Cartridge => "cartridge",
+            Self::Cpu => "CPU",
+        })
+    }
+}
+
+/// Time the bus spent in each of its components on the timed cycles, see
+/// [`Bus::set_profiling`](crate::bus::Bus::set_profiling).
+pub(crate) struct BusProfile {
+    /// Cycles left before the next timed one.
+    countdown: u32,
+    timed_cycles: u64,
+    components: [Duration; Subsystem::COMPONENTS.len()],
+    /// What measuring an empty span takes, removed from every measure.
+    overhead: Duration,
+}
+
+impl BusProfile {
+    pub(crate) fn new() -> Self {
+        let overhead = (0..CALIBRATION_SPANS)
+            .map(|_| Instant::now().elapsed())
+            .sum::<Duration>()
+            / CALIBRATION_SPANS;
+
+        Self {
+            countdown: 0,
+            timed_cycles: 0,
+            components: Default::default(),
+            overhead,
+        }
+    }
+
+    /// Whether this cycle is timed.
+    pub(crate) const fn sample(&mut self) -> bool {
+        if self.countdown == 0 {
+            self.countdown = SAMPLE_PERIOD - 1;
+            self.timed_cycles += 1;
+            true
+        } else {
+            self.countdown -= 1;
+            false
+        }
+    }
+
+    /// Adds the time since `start` to the component at `index` of
+    /// [`Bus::components_mut`](crate::bus::Bus), the cartridge coming last.
+    pub(crate) fn record(&mut self, index: usize, start: Instant) {
+        self.components[index] += start.elapsed().saturating_sub(self.overhead);
+    }
+}
+
+pub struct BenchReport {
+    /// Frames completed.
+    pub frames: u64,
+    pub elapsed: Duration,
+    pub instructions: u64,
+    /// Bus cycles emulated, see [`crate::clock`].
+    pub cycles: u128,
+    /// Estimated time spent in each subsystem, longest first.
+    pub subsystems: Vec<(Subsystem, Duration)>,
+    /// Why the run stopped before the frames requested.
+    pub overrun: Option<FrameOverrun>,
+}
+
+impl BenchReport {
+    #[must_use]
+    pub fn frames_per_second(&self) -> f64 {
+        per_second(self.frames as f64, self.elapsed)
+    }
+
+    #[must_use]
+    pub fn instructions_per_second(&self) -> f64 {
+        per_second(self.instructions as f64, self.elapsed)
+    }
+
+    /// How many times faster than the hardware the game ran.
+    #[must_use]
+    pub fn speed(&self) -> f64 {
+        self.frames_per_second() / FRAME_RATE
+    }
+}
+
+fn per_second(count: f64, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        0.0
+    } else {
+        count / elapsed.as_secs_f64()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} frames in {:.2} s: {:.1} frames/s ({:.2}x), {:.2} M instructions/s, {:.2} s emulated",
+            self.frames,
+            self.elapsed.as_secs_f64(),
+            self.frames_per_second(),
+            self.speed(),
+            self.instructions_per_second() / 1e6,
+            clock::cycles_to_duration(self.cycles).as_secs_f64(),
+        )?;
+        for (subsystem, time) in &self.subsystems {
+            let share = if self.elapsed.is_zero() {
+                0.0
+            } else {
+                time.as_secs_f64() / self.elapsed.as_secs_f64() * 100.0
+            };
+            writeln!(
+                f,
+                "  {subsystem:<10} {share:5.1}% {:8.3} s",
+                time.as_secs_f64()
+            )?;
+        }
+        if let Some(overrun) = &self.overrun {
+            writeln!(f, "stopped early: {overrun}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs `gba` for `frames` frames as fast as possible, stopping early if it gets stuck.
+#[must_use]
+pub fn run(gba: &mut Gba, frames: u64) -> BenchReport {
+    let start_instructions = gba.cpu.instructions();
+    let start_cycles = gba.cpu.bus.cycles();
+    gba.cpu.bus.set_profiling(true);
+
+    let start = Instant::now();
+    let mut completed = 0;
+    let mut overrun = None;
+    while completed < frames {
+        match gba.run_frame() {
+            Ok(_) => completed += 1,
+            Err(stuck) => {
+                overrun = Some(stuck);
+                break;
+            }
+        }
+    }
+    let elapsed = start.elapsed();
+
+    let profile = gba.cpu.bus.take_profile();
+    let cycles = gba.cpu.bus.cycles() - start_cycles;
+
+    BenchReport {
+        frames: completed,
+        elapsed,
+        instructions: gba.cpu.instructions() - start_instructions,
+        cycles,
+        subsystems: profile.map_or_else(Vec::new, |profile| breakdown(&profile, cycles, elapsed)),
+        overrun,
+    }
+}
+
+/// The timed cycles scaled to every cycle run, the CPU taking what's left of `elapsed`.
+fn breakdown(profile: &BusProfile, cycles: u128, elapsed: Duration) -> Vec<(Subsystem, Duration)> {
+    let scale = if profile.timed_cycles == 0 {
+        0.0
+    } else {
+        cycles as f64 / profile.timed_cycles as f64
+    };
+    let mut subsystems = Subsystem::COMPONENTS
+        .into_iter()
+        .zip(profile.components)
+        .map(|(subsystem, time)| (subsystem, time.mul_f64(scale).min(elapsed)))
+        .collect::<Vec<_>>();
+    let components = subsystems.iter().map(|(_, time)| *time).sum::<Duration>();
+    subsystems.push((Subsystem::Cpu, elapsed.saturating_sub(components)));
+    subsystems.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    subsystems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_cycle_timed_every_period() {
+        let mut profile = BusProfile::new();
+        let timed = (0..SAMPLE_PERIOD * 3).filter(|_| profile.sample()).count();
+        assert_eq!(timed, 3);
+        assert_eq!(profile.timed_cycles, 3);
+    }
+
+    #[test]
+    fn breakdown_scales_the_timed_cycles() {
+        let mut profile = BusProfile::new();
+        profile.timed_cycles = 2;
+        profile.components[1] = Duration::from_millis(3);
+        profile.components[2] = Duration::from_millis(1);
+
+        let subsystems = breakdown(&profile, 2 * 1024, Duration::from_secs(10));
+        assert_eq!(subsystems.len(), 9);
+        assert_eq!(subsystems[0], (Subsystem::Cpu, Duration::from_millis(5904)));
+        assert_eq!(subsystems[1], (Subsystem::Lcd, Duration::from_millis(3072)));
+        assert_eq!(
+            subsystems[2],
+            (Subsystem::Sound, Duration::from_millis(1024))
+        );
+    }
+}
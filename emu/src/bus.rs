@@ -1,20 +1,92 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
 
 use logger::log;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+use crate::bench::BusProfile;
 use crate::bitwise::Bits;
-use crate::cpu::hardware::dma::{Dma, Registers};
+use crate::cheats::Cheat;
+use crate::config::Overclock;
+use crate::cpu::hardware::component::{HardwareComponent, StepOutput};
+use crate::cpu::hardware::debug_print::{DebugOutput, DebugPrint};
+use crate::cpu::hardware::dma::{self, BusMasters, Dma};
+use crate::cpu::hardware::flash::FlashTiming;
 use crate::cpu::hardware::get_unmasked_address;
 use crate::cpu::hardware::internal_memory::InternalMemory;
-use crate::cpu::hardware::interrupt_control::InterruptControl;
-use crate::cpu::hardware::keypad::Keypad;
+use crate::cpu::hardware::interrupt_control::{InterruptControl, IrqType};
+use crate::cpu::hardware::keypad::{KeySampling, Keypad, KeypadInput};
 use crate::cpu::hardware::lcd::Lcd;
-use crate::cpu::hardware::serial::Serial;
+use crate::cpu::hardware::prefetch::Prefetch;
+use crate::cpu::hardware::serial::link_cable::LinkTransport;
+use crate::cpu::hardware::serial::session::WirelessTransport;
+use crate::cpu::hardware::serial::uart::UartHost;
+use crate::cpu::hardware::serial::{Serial, SerialPeripheral};
+use crate::cpu::hardware::sound::filter::FilterSettings;
+use crate::cpu::hardware::sound::mixer::StereoSample;
 use crate::cpu::hardware::sound::Sound;
 use crate::cpu::hardware::timers::Timers;
+use crate::events::Event;
+#[cfg(feature = "debug-hooks")]
+use crate::io_trace::IoTraceWriter;
+use crate::io_trace::{IoAccess, IoAccessKind};
+use crate::memory_edit::{self, EditValue, MemoryEditError};
+use crate::memory_map::{
+    io_register, is_unmapped, VramAddr, BG_PALETTE_START, OAM_START, PALETTE_SIZE,
+};
+#[cfg(feature = "debug-hooks")]
+use crate::memory_map::{IO_START, LCD_REGISTERS_END};
+#[cfg(feature = "serde")]
+use crate::save_state::Section;
+
+/// Whether `address` is in the OBJ palette, and its index in that palette. The palette
+/// RAM repeats every `KByte`.
+const fn palette_offset(address: u32) -> (bool, usize) {
+    let unmasked_address = get_unmasked_address(address as usize, 0x00FF_FF00, 0xFF00_00FF, 8, 4);
+    let offset = unmasked_address - BG_PALETTE_START as usize;
+
+    (offset >= PALETTE_SIZE, offset % PALETTE_SIZE)
+}
+
+/// Index in the OAM of `address`, it repeats every `KByte`.
+const fn oam_offset(address: u32) -> usize {
+    get_unmasked_address(address as usize, 0x00FF_FF00, 0xFF00_00FF, 8, 4) - OAM_START as usize
+}
+
+/// Waitstates of the first access to the cartridge, by the `WAITCNT` setting.
+const FIRST_ACCESS_WAITS: [u32; 4] = [4, 3, 2, 8];
+
+/// Cycles of a cartridge access and of the sequential one completing a word. Sequential
+/// accesses wait `slow` cycles, or a single one when `fast_sequential`.
+fn rom_cycles(
+    first_setting: u16,
+    fast_sequential: bool,
+    slow: u32,
+    sequential: bool,
+) -> (u32, u32) {
+    let sequential_cycles = 1 + if fast_sequential { 1 } else { slow };
+    let first = if sequential {
+        sequential_cycles
+    } else {
+        1 + FIRST_ACCESS_WAITS[usize::from(first_setting)]
+    };
+
+    (first, sequential_cycles)
+}
+
+/// Next address of a DMA transfer by its address control: increment, decrement, fixed,
+/// and increment again for the last one, whose reload is up to the channel.
+const fn step_address(address: u32, control: u16, width: u32) -> u32 {
+    match control {
+        1 => address.wrapping_sub(width),
+        2 => address,
+        _ => address.wrapping_add(width),
+    }
+}
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Bus {
     pub internal_memory: InternalMemory,
     pub lcd: Lcd,
@@ -27,777 +99,650 @@ pub struct Bus {
     cycles_count: u128,
     last_used_address: usize,
     unused_region: HashMap<usize, u8>,
+    prefetch: Prefetch,
+    /// Last opcode fetched by the CPU, what the open bus reads.
+    last_opcode: u32,
+    /// Values written again at every Vblank, see [`Self::freeze`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    frozen: BTreeMap<u32, EditValue>,
+    /// Run at every Vblank with the frozen values, see [`Self::cheats_mut`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cheats: Vec<Cheat>,
+    /// See [`Self::set_overclock`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    overclock: Overclock,
+    /// Cycles of the CPU since the components last stepped.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    overclock_phase: u32,
+    /// First access to an unmapped address not taken yet, see
+    /// [`Self::take_invalid_access`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    invalid_access: Option<u32>,
+    /// Events since the last [`Self::drain_events`], `None` when nobody listens.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    events: Option<Vec<Event>>,
+    /// See [`Self::set_debug_output`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    debug_print: DebugPrint,
+    /// See [`Self::set_profiling`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    profile: Option<Box<BusProfile>>,
+    /// The DMA channel whose transfer has the bus, the channels started meanwhile wait
+    /// for its end. The transfers never outlast a step, so it's never saved set.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    dma_channel: Option<usize>,
+    /// Who had the bus since the last Vblank.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    masters: BusMasters,
+    /// See [`Self::bus_masters`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    last_masters: BusMasters,
+    #[cfg(feature = "debug-hooks")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    io_trace: Option<Box<dyn IoTraceWriter>>,
+    /// Logs the writes to the LCD registers, see [`Self::set_ppu_write_log`].
+    #[cfg(feature = "debug-hooks")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    ppu_write_log: bool,
 }
 
-#[allow(dead_code)]
-enum IrqType {
-    VBlank,
-    HBlank,
-    VCount,
-    Timer0,
-    Timer1,
-    Timer2,
-    Timer3,
-    Serial,
-    Dma0,
-    Dma1,
-    Dma2,
-    Dma3,
-    Keypad,
-    Gamepak,
-}
-
-impl IrqType {
-    /// Returns the index of the corresponding `IrqType` inside the Interrupt Request Flag register
-    const fn get_idx_in_if(&self) -> u8 {
-        match self {
-            Self::VBlank => 0,
-            Self::HBlank => 1,
-            Self::VCount => 2,
-            Self::Timer0 => 3,
-            Self::Timer1 => 4,
-            Self::Timer2 => 5,
-            Self::Timer3 => 6,
-            Self::Serial => 7,
-            Self::Dma0 => 8,
-            Self::Dma1 => 9,
-            Self::Dma2 => 10,
-            Self::Dma3 => 11,
-            Self::Keypad => 12,
-            Self::Gamepak => 13,
-        }
-    }
-}
 impl Bus {
-    fn read_interrupt_control_raw(&self, address: usize) -> u8 {
+    /// Component whose I/O registers are mapped at `address`.
+    fn io_component(&self, address: usize) -> &dyn HardwareComponent {
         match address {
-            0x0400_0200 => self.interrupt_control.interrupt_enable.get_byte(0),
-            0x0400_0201 => self.interrupt_control.interrupt_enable.get_byte(1),
-            0x0400_0202 => self
-                .interrupt_control
-                .interrupt_request
-                .front()
-                .unwrap_or(&0)
-                .get_byte(0),
-            0x0400_0203 => self
-                .interrupt_control
-                .interrupt_request
-                .front()
-                .unwrap_or(&0)
-                .get_byte(1),
-            0x0400_0204 => self.interrupt_control.wait_state_control.get_byte(0),
-            0x0400_0205 => self.interrupt_control.wait_state_control.get_byte(1),
-            0x0400_0208 => self.interrupt_control.interrupt_master_enable.get_byte(0),
-            0x0400_0209 => self.interrupt_control.interrupt_master_enable.get_byte(1),
-            0x0400_0300 => self.interrupt_control.post_boot_flag.get_byte(0),
-            0x0400_0301 => panic!("Reading a write-only InterruptControl address"),
-            0x0400_0410 => self.interrupt_control.purpose_unknown.get_byte(0),
-            0x0400_0206
-            | 0x0400_0207
-            | 0x400_020A..=0x400_02FF
-            | 0x0400_0302..=0x0400_040F
-            | 0x0400_0411 => {
-                log("read on unused memory");
-                *self.unused_region.get(&address).unwrap_or(&0)
-            }
-            _ => match address & 0b111 {
-                0x800 => self.interrupt_control.internal_memory_control.get_byte(0),
-                0x801 => self.interrupt_control.internal_memory_control.get_byte(1),
-                0x802 => self.interrupt_control.internal_memory_control.get_byte(2),
-                0x803 => self.interrupt_control.internal_memory_control.get_byte(3),
-                _ => {
-                    log("read on unused memory");
-                    *self.unused_region.get(&address).unwrap_or(&0)
-                }
-            },
+            0x4000000..=0x400005F => &self.lcd,
+            0x4000060..=0x40000AF => &self.sound,
+            0x40000B0..=0x40000FF => &self.dma,
+            0x4000100..=0x400011F => &self.timers,
+            0x4000120..=0x400012F | 0x4000134..=0x40001FF => &self.serial,
+            0x4000130..=0x4000133 => &self.keypad,
+            _ => &self.interrupt_control,
         }
     }
 
-    fn write_interrupt_control_raw(&mut self, address: usize, value: u8) {
+    fn io_component_mut(&mut self, address: usize) -> &mut dyn HardwareComponent {
         match address {
-            0x04000200 => self.interrupt_control.interrupt_enable.set_byte(0, value),
-            0x04000201 => self.interrupt_control.interrupt_enable.set_byte(1, value),
-            0x04000202 => {
-                let current_val = self.interrupt_control.interrupt_request.back_mut().unwrap();
-
-                *current_val &= !(value as u16);
-            }
-            0x04000203 => {
-                let current_val = self.interrupt_control.interrupt_request.back_mut().unwrap();
-
-                *current_val &= !((value as u16) << 8);
-            }
-            0x04000204 => self.interrupt_control.wait_state_control.set_byte(0, value),
-            0x04000205 => self.interrupt_control.wait_state_control.set_byte(1, value),
-            0x04000208 => self
-                .interrupt_control
-                .interrupt_master_enable
-                .set_byte(0, value),
-            0x04000209 => self
-                .interrupt_control
-                .interrupt_master_enable
-                .set_byte(1, value),
-            0x04000300 => self.interrupt_control.post_boot_flag.set_byte(0, value),
-            0x04000301 => self.interrupt_control.power_down_control.set_byte(0, value),
-            0x04000410 => self.interrupt_control.purpose_unknown.set_byte(0, value),
-            0x04000206
-            | 0x04000207
-            | 0x400020A..=0x40002FF
-            | 0x04000302..=0x0400040F
-            | 0x04000411 => {
-                log("write on unused memory");
-                self.unused_region.insert(address, value);
-            }
-            _ => match address & 0b111 {
-                0x800 => self
-                    .interrupt_control
-                    .internal_memory_control
-                    .set_byte(0, value),
-                0x801 => self
-                    .interrupt_control
-                    .internal_memory_control
-                    .set_byte(1, value),
-                0x802 => self
-                    .interrupt_control
-                    .internal_memory_control
-                    .set_byte(2, value),
-                0x803 => self
-                    .interrupt_control
-                    .internal_memory_control
-                    .set_byte(3, value),
-                _ => {
-                    log("write on unused memory");
-                    self.unused_region.insert(address, value);
-                }
-            },
+            0x4000000..=0x400005F => &mut self.lcd,
+            0x4000060..=0x40000AF => &mut self.sound,
+            0x40000B0..=0x40000FF => &mut self.dma,
+            0x4000100..=0x400011F => &mut self.timers,
+            0x4000120..=0x400012F | 0x4000134..=0x40001FF => &mut self.serial,
+            0x4000130..=0x4000133 => &mut self.keypad,
+            _ => &mut self.interrupt_control,
         }
     }
 
-    fn read_keypad_raw(&self, address: usize) -> u8 {
-        match address {
-            0x4000130 => self.keypad.key_input.get_byte(0),
-            0x4000131 => self.keypad.key_input.get_byte(1),
-            0x4000132 => self.keypad.key_interrupt_control.get_byte(0),
-            0x4000133 => self.keypad.key_interrupt_control.get_byte(1),
-            _ => panic!("Keypad read address is out of bound"),
-        }
+    /// Every component plugged on the bus, in the order they are stepped.
+    fn components_mut(&mut self) -> [&mut dyn HardwareComponent; 7] {
+        [
+            &mut self.interrupt_control,
+            &mut self.lcd,
+            &mut self.sound,
+            &mut self.dma,
+            &mut self.timers,
+            &mut self.serial,
+            &mut self.keypad,
+        ]
     }
 
-    fn write_keypad_raw(&mut self, address: usize, value: u8) {
-        match address {
-            // 0x4000130 and 0x4000131 Should be read-only but CPU bios writes it.
-            0x4000130 => self.keypad.key_input.set_byte(0, value),
-            0x4000131 => self.keypad.key_input.set_byte(1, value),
-            0x4000132 => self.keypad.key_interrupt_control.set_byte(0, value),
-            0x4000133 => self.keypad.key_interrupt_control.set_byte(1, value),
-            _ => panic!("Keypad write address is out of bound"),
-        }
-    }
-
-    fn read_serial_raw(&self, address: usize) -> u8 {
-        match address {
-            0x04000120 => self.serial.sio_data_32_multi_data_0_data_1.get_byte(0),
-            0x04000121 => self.serial.sio_data_32_multi_data_0_data_1.get_byte(1),
-            0x04000122 => self.serial.sio_data_32_multi_data_0_data_1.get_byte(2),
-            0x04000123 => self.serial.sio_data_32_multi_data_0_data_1.get_byte(3),
-            0x04000124 => self.serial.sio_multi_data_2.get_byte(0),
-            0x04000125 => self.serial.sio_multi_data_2.get_byte(1),
-            0x04000126 => self.serial.sio_multi_data_3.get_byte(0),
-            0x04000127 => self.serial.sio_multi_data_3.get_byte(1),
-            0x04000128 => self.serial.sio_control_register.get_byte(0),
-            0x04000129 => self.serial.sio_control_register.get_byte(1),
-            0x0400012A => self.serial.sio_multi_data_send_data_8.get_byte(0),
-            0x0400012B => self.serial.sio_multi_data_send_data_8.get_byte(1),
-            0x04000134 => self.serial.sio_mode_select.get_byte(0),
-            0x04000135 => self.serial.sio_mode_select.get_byte(1),
-            0x04000136 => self.serial.infrared_register.get_byte(0),
-            0x04000137 => self.serial.infrared_register.get_byte(1),
-            0x04000140 => self.serial.sio_joy_bus_control.get_byte(0),
-            0x04000141 => self.serial.sio_joy_bus_control.get_byte(1),
-            0x04000150 => self.serial.sio_joy_bus_receive_data.get_byte(0),
-            0x04000151 => self.serial.sio_joy_bus_receive_data.get_byte(1),
-            0x04000152 => self.serial.sio_joy_bus_receive_data.get_byte(2),
-            0x04000153 => self.serial.sio_joy_bus_receive_data.get_byte(3),
-            0x04000154 => self.serial.sio_joy_bus_transmit_data.get_byte(0),
-            0x04000155 => self.serial.sio_joy_bus_transmit_data.get_byte(1),
-            0x04000156 => self.serial.sio_joy_bus_transmit_data.get_byte(2),
-            0x04000157 => self.serial.sio_joy_bus_transmit_data.get_byte(3),
-            0x04000158 => self.serial.sio_joy_bus_receive_status.get_byte(0),
-            0x04000159 => self.serial.sio_joy_bus_receive_status.get_byte(1),
-            0x0400012C..=0x0400012F
-            | 0x04000138..=0x04000139
-            | 0x04000142..=0x0400014F
-            | 0x0400015A..=0x040001FF => {
+    fn read_io(&self, address: u32) -> u8 {
+        if DebugPrint::contains(address) {
+            return self.debug_print.read(address).unwrap_or_else(|| {
                 log(format!("read on unused memory {address:x}"));
-                *self.unused_region.get(&address).unwrap_or(&0)
-            }
-            _ => panic!("Serial read address is out of bound"),
+                self.open_bus_byte(address)
+            });
         }
-    }
 
-    fn write_serial_raw(&mut self, address: usize, value: u8) {
-        match address {
-            0x04000120 => self
-                .serial
-                .sio_data_32_multi_data_0_data_1
-                .set_byte(0, value),
-            0x04000121 => self
-                .serial
-                .sio_data_32_multi_data_0_data_1
-                .set_byte(1, value),
-            0x04000122 => self
-                .serial
-                .sio_data_32_multi_data_0_data_1
-                .set_byte(2, value),
-            0x04000123 => self
-                .serial
-                .sio_data_32_multi_data_0_data_1
-                .set_byte(3, value),
-            0x04000124 => self.serial.sio_multi_data_2.set_byte(0, value),
-            0x04000125 => self.serial.sio_multi_data_2.set_byte(1, value),
-            0x04000126 => self.serial.sio_multi_data_3.set_byte(0, value),
-            0x04000127 => self.serial.sio_multi_data_3.set_byte(1, value),
-            0x04000128 => self.serial.sio_control_register.set_byte(0, value),
-            0x04000129 => self.serial.sio_control_register.set_byte(1, value),
-            0x0400012A => self.serial.sio_multi_data_send_data_8.set_byte(0, value),
-            0x0400012B => self.serial.sio_multi_data_send_data_8.set_byte(1, value),
-            0x04000134 => self.serial.sio_mode_select.set_byte(0, value),
-            0x04000135 => self.serial.sio_mode_select.set_byte(1, value),
-            0x04000136 => self.serial.infrared_register.set_byte(0, value),
-            0x04000137 => self.serial.infrared_register.set_byte(1, value),
-            0x04000140 => self.serial.sio_joy_bus_control.set_byte(0, value),
-            0x04000141 => self.serial.sio_joy_bus_control.set_byte(1, value),
-            0x04000150 => self.serial.sio_joy_bus_receive_data.set_byte(0, value),
-            0x04000151 => self.serial.sio_joy_bus_receive_data.set_byte(1, value),
-            0x04000152 => self.serial.sio_joy_bus_receive_data.set_byte(2, value),
-            0x04000153 => self.serial.sio_joy_bus_receive_data.set_byte(3, value),
-            0x04000154 => self.serial.sio_joy_bus_transmit_data.set_byte(0, value),
-            0x04000155 => self.serial.sio_joy_bus_transmit_data.set_byte(1, value),
-            0x04000156 => self.serial.sio_joy_bus_transmit_data.set_byte(2, value),
-            0x04000157 => self.serial.sio_joy_bus_transmit_data.set_byte(3, value),
-            0x04000158 => self.serial.sio_joy_bus_receive_status.set_byte(0, value),
-            0x04000159 => self.serial.sio_joy_bus_receive_status.set_byte(1, value),
-            0x0400012C..=0x0400012F
-            | 0x04000138..=0x04000139
-            | 0x04000142..=0x0400014F
-            | 0x0400015A..=0x040001FF => {
-                log(format!("write on unused memory {address:x}"));
-                self.unused_region.insert(address, value);
-            }
-            _ => panic!("Serial write address is out of bound"),
+        let register = io_register(address);
+        let readable = register.map_or(0xFF, |register| register.readable_byte(address));
+        // Write-only registers aren't kept by every component.
+        if readable == 0 {
+            let open_bus =
+                register.is_some_and(|register| register.readable == 0 && register.open_bus);
+            return if open_bus {
+                self.open_bus_byte(address)
+            } else {
+                0
+            };
         }
-    }
 
-    fn read_timers_raw(&self, address: usize) -> u8 {
-        match address {
-            0x04000100 => self.timers.tm0cnt_l.get_byte(0),
-            0x04000101 => self.timers.tm0cnt_l.get_byte(1),
-            0x04000102 => self.timers.tm0cnt_h.get_byte(0),
-            0x04000103 => self.timers.tm0cnt_h.get_byte(1),
-            0x04000104 => self.timers.tm1cnt_l.get_byte(0),
-            0x04000105 => self.timers.tm1cnt_l.get_byte(1),
-            0x04000106 => self.timers.tm1cnt_h.get_byte(0),
-            0x04000107 => self.timers.tm1cnt_h.get_byte(1),
-            0x04000108 => self.timers.tm2cnt_l.get_byte(0),
-            0x04000109 => self.timers.tm2cnt_l.get_byte(1),
-            0x0400010A => self.timers.tm2cnt_h.get_byte(0),
-            0x0400010B => self.timers.tm2cnt_h.get_byte(1),
-            0x0400010C => self.timers.tm3cnt_l.get_byte(0),
-            0x0400010D => self.timers.tm3cnt_l.get_byte(1),
-            0x0400010E => self.timers.tm3cnt_h.get_byte(0),
-            0x0400010F => self.timers.tm3cnt_h.get_byte(1),
-            0x04000110..=0x0400011F => self.unused_region.get(&address).map_or(0, |v| *v),
-            _ => panic!("Timers read address is out of bound"),
-        }
-    }
-
-    fn write_timers_raw(&mut self, address: usize, value: u8) {
-        match address {
-            0x04000100 => self.timers.tm0cnt_l.set_byte(0, value),
-            0x04000101 => self.timers.tm0cnt_l.set_byte(1, value),
-            0x04000102 => self.timers.tm0cnt_h.set_byte(0, value),
-            0x04000103 => self.timers.tm0cnt_h.set_byte(1, value),
-            0x04000104 => self.timers.tm1cnt_l.set_byte(0, value),
-            0x04000105 => self.timers.tm1cnt_l.set_byte(1, value),
-            0x04000106 => self.timers.tm1cnt_h.set_byte(0, value),
-            0x04000107 => self.timers.tm1cnt_h.set_byte(1, value),
-            0x04000108 => self.timers.tm2cnt_l.set_byte(0, value),
-            0x04000109 => self.timers.tm2cnt_l.set_byte(1, value),
-            0x0400010A => self.timers.tm2cnt_h.set_byte(0, value),
-            0x0400010B => self.timers.tm2cnt_h.set_byte(1, value),
-            0x0400010C => self.timers.tm3cnt_l.set_byte(0, value),
-            0x0400010D => self.timers.tm3cnt_l.set_byte(1, value),
-            0x0400010E => self.timers.tm3cnt_h.set_byte(0, value),
-            0x0400010F => self.timers.tm3cnt_h.set_byte(1, value),
-            0x04000110..=0x0400011F => {
-                log(format!("write on unused memory {address:x}"));
-                self.unused_region.insert(address, value);
-            }
-            _ => panic!("Timers write address is out of bound"),
-        }
+        let value = self
+            .io_component(address as usize)
+            .on_read(address as usize);
+        value.map_or_else(
+            || {
+                log(format!("read on unused memory {address:x}"));
+                self.open_bus_byte(address)
+            },
+            |value| value & readable,
+        )
     }
 
-    fn read_dma_raw(&self, address: usize) -> u8 {
-        let read_dma_bank = |channel: &Registers, address: usize| match address {
-            0..=9 => panic!("Reading a write-only DMA I/O register"),
-            10 => channel.control.get_byte(0),
-            11 => channel.control.get_byte(1),
-            _ => panic!("DMA channel read address is out of bound"),
-        };
-
-        match address {
-            0x040000B0..=0x040000BB => read_dma_bank(&self.dma.channels[0], address - 0x040000B0),
-            0x040000BC..=0x040000C7 => read_dma_bank(&self.dma.channels[0], address - 0x040000BC),
-            0x040000C8..=0x040000D3 => read_dma_bank(&self.dma.channels[0], address - 0x040000C8),
-            0x040000D4..=0x040000DF => read_dma_bank(&self.dma.channels[0], address - 0x040000D4),
-            0x040000E0..=0x040000FF => {
-                log("read on unused memory");
-                self.unused_region.get(&address).map_or(0, |v| *v)
-            }
-            _ => panic!("DMA read address is out of bound"),
-        }
-    }
-
-    fn write_dma_raw(&mut self, address: usize, value: u8) {
-        let write_dma_bank = |channel: &mut Registers, address: usize, value: u8| match address {
-            0 => channel.source_address.set_byte(0, value),
-            1 => channel.source_address.set_byte(1, value),
-            2 => channel.source_address.set_byte(2, value),
-            3 => channel.source_address.set_byte(3, value),
-            4 => channel.destination_address.set_byte(0, value),
-            5 => channel.destination_address.set_byte(1, value),
-            6 => channel.destination_address.set_byte(2, value),
-            7 => channel.destination_address.set_byte(3, value),
-            8 => channel.word_count.set_byte(0, value),
-            9 => channel.word_count.set_byte(1, value),
-            10 => channel.control.set_byte(0, value),
-            11 => channel.control.set_byte(1, value),
-            _ => panic!("DMA channel write-address is out of bound"),
-        };
-
-        match address {
-            0x040000B0..=0x040000BB => {
-                write_dma_bank(&mut self.dma.channels[0], address - 0x040000B0, value);
-            }
-            0x040000BC..=0x040000C7 => {
-                write_dma_bank(&mut self.dma.channels[1], address - 0x040000BC, value);
-            }
-            0x040000C8..=0x040000D3 => {
-                write_dma_bank(&mut self.dma.channels[2], address - 0x040000C8, value);
-            }
-            0x040000D4..=0x040000DF => {
-                write_dma_bank(&mut self.dma.channels[3], address - 0x040000D4, value);
-            }
-            0x040000E0..=0x040000FF => {
-                log("write on unused memory");
-                self.unused_region.insert(address, value);
-            }
-            _ => panic!("Not implemented write memory address: {address:x}"),
-        }
+    /// Byte of the open bus at `address`, from the last fetched opcode.
+    const fn open_bus_byte(&self, address: u32) -> u8 {
+        self.last_opcode.to_le_bytes()[(address & 3) as usize]
     }
 
-    fn read_sound_raw(&self, address: usize) -> u8 {
-        match address {
-            0x04000060 => self.sound.channel1_sweep.get_byte(0),
-            0x04000061 => self.sound.channel1_sweep.get_byte(1),
-            0x04000062 => self.sound.channel1_duty_length_envelope.get_byte(0),
-            0x04000063 => self.sound.channel1_duty_length_envelope.get_byte(1),
-            0x04000064 => self.sound.channel1_frequency_control.get_byte(0),
-            0x04000065 => self.sound.channel1_frequency_control.get_byte(1),
-            0x04000068 => self.sound.channel2_duty_length_envelope.get_byte(0),
-            0x04000069 => self.sound.channel2_duty_length_envelope.get_byte(1),
-            0x0400006C => self.sound.channel2_frequency_control.get_byte(0),
-            0x0400006D => self.sound.channel2_frequency_control.get_byte(1),
-            0x04000070 => self.sound.channel3_stop_wave_ram_select.get_byte(0),
-            0x04000071 => self.sound.channel3_stop_wave_ram_select.get_byte(1),
-            0x04000072 => self.sound.channel3_length_volume.get_byte(0),
-            0x04000073 => self.sound.channel3_length_volume.get_byte(1),
-            0x04000074 => self.sound.channel3_frequency_control.get_byte(0),
-            0x04000075 => self.sound.channel3_frequency_control.get_byte(1),
-            0x04000078 => self.sound.channel4_length_envelope.get_byte(0),
-            0x04000079 => self.sound.channel4_length_envelope.get_byte(1),
-            0x0400007C => self.sound.channel4_frequency_control.get_byte(0),
-            0x0400007D => self.sound.channel4_frequency_control.get_byte(1),
-            0x04000080 => self.sound.control_stereo_volume_enable.get_byte(0),
-            0x04000081 => self.sound.control_stereo_volume_enable.get_byte(1),
-            0x04000082 => self.sound.control_mixing_dma_control.get_byte(0),
-            0x04000083 => self.sound.control_mixing_dma_control.get_byte(1),
-            0x04000084 => self.sound.control_sound_on_off.get_byte(0),
-            0x04000085 => self.sound.control_sound_on_off.get_byte(1),
-            0x04000088 => self.sound.sound_pwm_control.get_byte(0),
-            0x04000089 => self.sound.sound_pwm_control.get_byte(1),
-            0x04000090..=0x0400009F => self.sound.channel3_wave_pattern_ram[address - 0x0400090],
-            0x040000A0..=0x040000A7 => panic!("Reading a write-only Sound I/O register"),
-            0x04000066..=0x04000067
-            | 0x0400006A..=0x0400006B
-            | 0x0400006E..=0x0400006F
-            | 0x04000076..=0x04000077
-            | 0x0400007A..=0x0400007B
-            | 0x0400007E..=0x0400007F
-            | 0x04000086..=0x04000087
-            | 0x0400008A..=0x0400008F
-            | 0x040000A8..=0x040000AF => {
-                log(format!("read on unused memory {address:x}"));
-                self.unused_region.get(&address).map_or(0, |v| *v)
+    /// Only the writable bits of the byte change, the read-only ones keep their value.
+    fn write_io(&mut self, address: u32, value: u8) {
+        if DebugPrint::contains(address) {
+            if !self.debug_print.write(address, value) {
+                log(format!("write on unused memory {address:x}"));
+                self.unused_region.insert(address as usize, value);
             }
-            _ => panic!("Sound read address is out of bound"),
+            return;
         }
-    }
 
-    fn write_sound_raw(&mut self, address: usize, value: u8) {
-        match address {
-            0x04000060 => self.sound.channel1_sweep.set_byte(0, value),
-            0x04000061 => self.sound.channel1_sweep.set_byte(1, value),
-            0x04000062 => self.sound.channel1_duty_length_envelope.set_byte(0, value),
-            0x04000063 => self.sound.channel1_duty_length_envelope.set_byte(1, value),
-            0x04000064 => self.sound.channel1_frequency_control.set_byte(0, value),
-            0x04000065 => self.sound.channel1_frequency_control.set_byte(1, value),
-            0x04000068 => self.sound.channel2_duty_length_envelope.set_byte(0, value),
-            0x04000069 => self.sound.channel2_duty_length_envelope.set_byte(1, value),
-            0x0400006C => self.sound.channel2_frequency_control.set_byte(0, value),
-            0x0400006D => self.sound.channel2_frequency_control.set_byte(1, value),
-            0x04000070 => self.sound.channel3_stop_wave_ram_select.set_byte(0, value),
-            0x04000071 => self.sound.channel3_stop_wave_ram_select.set_byte(1, value),
-            0x04000072 => self.sound.channel3_length_volume.set_byte(0, value),
-            0x04000073 => self.sound.channel3_length_volume.set_byte(1, value),
-            0x04000074 => self.sound.channel3_frequency_control.set_byte(0, value),
-            0x04000075 => self.sound.channel3_frequency_control.set_byte(1, value),
-            0x04000078 => self.sound.channel4_length_envelope.set_byte(0, value),
-            0x04000079 => self.sound.channel4_length_envelope.set_byte(1, value),
-            0x0400007C => self.sound.channel4_frequency_control.set_byte(0, value),
-            0x0400007D => self.sound.channel4_frequency_control.set_byte(1, value),
-            0x04000080 => self.sound.control_stereo_volume_enable.set_byte(0, value),
-            0x04000081 => self.sound.control_stereo_volume_enable.set_byte(1, value),
-            0x04000082 => self.sound.control_mixing_dma_control.set_byte(0, value),
-            0x04000083 => self.sound.control_mixing_dma_control.set_byte(1, value),
-            0x04000084 => self.sound.control_sound_on_off.set_byte(0, value),
-            0x04000085 => self.sound.control_sound_on_off.set_byte(1, value),
-            0x04000088 => self.sound.sound_pwm_control.set_byte(0, value),
-            0x04000089 => self.sound.sound_pwm_control.set_byte(1, value),
-            0x04000090..=0x0400009F => {
-                self.sound.channel3_wave_pattern_ram[address - 0x04000090] = value;
-            }
-            0x040000A0 => self.sound.channel_a_fifo.set_byte(0, value),
-            0x040000A1 => self.sound.channel_a_fifo.set_byte(1, value),
-            0x040000A2 => self.sound.channel_a_fifo.set_byte(2, value),
-            0x040000A3 => self.sound.channel_a_fifo.set_byte(3, value),
-            0x040000A4 => self.sound.channel_b_fifo.set_byte(0, value),
-            0x040000A5 => self.sound.channel_b_fifo.set_byte(1, value),
-            0x040000A6 => self.sound.channel_b_fifo.set_byte(2, value),
-            0x040000A7 => self.sound.channel_b_fifo.set_byte(3, value),
-            0x04000066..=0x04000067
-            | 0x0400006A..=0x0400006B
-            | 0x0400006E..=0x0400006F
-            | 0x04000076..=0x04000077
-            | 0x0400007A..=0x0400007B
-            | 0x0400007E..=0x0400007F
-            | 0x04000086..=0x04000087
-            | 0x0400008A..=0x0400008F
-            | 0x040000A8..=0x040000AF => {
-                log(format!("write on unused memory, {address:x}"));
-                self.unused_region.insert(address, value);
-            }
-            _ => panic!("Sound write address is out of bound"),
-        }
-    }
+        let value = match io_register(address) {
+            Some(register) => {
+                let writable = register.writable_byte(address);
+                let read_only = register.readable_byte(address) & !writable;
+                if writable == 0 {
+                    return;
+                }
 
-    fn read_lcd_raw(&self, address: usize) -> u8 {
-        match address {
-            0x04000000 => self.lcd.registers.dispcnt.get_byte(0),
-            0x04000001 => self.lcd.registers.dispcnt.get_byte(1),
-            0x04000002 => self.lcd.registers.green_swap.get_byte(0),
-            0x04000003 => self.lcd.registers.green_swap.get_byte(1),
-            0x04000004 => self.lcd.registers.dispstat.get_byte(0),
-            0x04000005 => self.lcd.registers.dispstat.get_byte(1),
-            0x04000006 => self.lcd.registers.vcount.get_byte(0),
-            0x04000007 => self.lcd.registers.vcount.get_byte(1),
-            0x04000008 => self.lcd.registers.bg0cnt.get_byte(0),
-            0x04000009 => self.lcd.registers.bg0cnt.get_byte(1),
-            0x0400000A => self.lcd.registers.bg1cnt.get_byte(0),
-            0x0400000B => self.lcd.registers.bg1cnt.get_byte(1),
-            0x0400000C => self.lcd.registers.bg2cnt.get_byte(0),
-            0x0400000D => self.lcd.registers.bg2cnt.get_byte(1),
-            0x0400000E => self.lcd.registers.bg3cnt.get_byte(0),
-            0x0400000F => self.lcd.registers.bg3cnt.get_byte(1),
-            (0x04000010..=0x04000047) | (0x04000054..=0x04000055) => {
-                panic!("Reading a write-only LCD I/O register")
-            }
-            0x04000048 => self.lcd.registers.winin.get_byte(0),
-            0x04000049 => self.lcd.registers.winin.get_byte(1),
-            0x0400004A => self.lcd.registers.winout.get_byte(0),
-            0x0400004B => self.lcd.registers.winout.get_byte(1),
-            0x0400004C => self.lcd.registers.mosaic.get_byte(0),
-            0x0400004D => self.lcd.registers.mosaic.get_byte(1),
-            0x04000050 => self.lcd.registers.bldcnt.get_byte(0),
-            0x04000051 => self.lcd.registers.bldcnt.get_byte(1),
-            0x04000052 => self.lcd.registers.bldalpha.get_byte(0),
-            0x04000053 => self.lcd.registers.bldalpha.get_byte(1),
-            0x0400004E..=0x0400004F | 0x04000056..=0x0400005F => {
-                log("read on unused memory");
-                self.unused_region.get(&address).map_or(0, |v| *v)
-            }
-            _ => panic!("LCD read address is out of bound"),
-        }
-    }
+                let kept = if read_only == 0 {
+                    0
+                } else {
+                    self.io_component(address as usize)
+                        .on_read(address as usize)
+                        .unwrap_or(0)
+                        & read_only
+                };
 
-    fn write_lcd_raw(&mut self, address: usize, value: u8) {
-        match address {
-            0x04000000 => self.lcd.registers.dispcnt.set_byte(0, value),
-            0x04000001 => self.lcd.registers.dispcnt.set_byte(1, value),
-            0x04000002 => self.lcd.registers.green_swap.set_byte(0, value),
-            0x04000003 => self.lcd.registers.green_swap.set_byte(1, value),
-            0x04000004 => self.lcd.registers.dispstat.set_byte(0, value),
-            0x04000005 => self.lcd.registers.dispstat.set_byte(1, value),
-            0x04000008 => self.lcd.registers.bg0cnt.set_byte(0, value),
-            0x04000006 => self.lcd.registers.vcount.set_byte(0, value),
-            0x04000007 => self.lcd.registers.vcount.set_byte(1, value),
-            0x04000009 => self.lcd.registers.bg0cnt.set_byte(1, value),
-            0x0400000A => self.lcd.registers.bg1cnt.set_byte(0, value),
-            0x0400000B => self.lcd.registers.bg1cnt.set_byte(1, value),
-            0x0400000C => self.lcd.registers.bg2cnt.set_byte(0, value),
-            0x0400000D => self.lcd.registers.bg2cnt.set_byte(1, value),
-            0x0400000E => self.lcd.registers.bg3cnt.set_byte(0, value),
-            0x0400000F => self.lcd.registers.bg3cnt.set_byte(1, value),
-            0x04000010 => self.lcd.registers.bg0hofs.set_byte(0, value),
-            0x04000011 => self.lcd.registers.bg0hofs.set_byte(1, value),
-            0x04000012 => self.lcd.registers.bg0vofs.set_byte(0, value),
-            0x04000013 => self.lcd.registers.bg0vofs.set_byte(1, value),
-            0x04000014 => self.lcd.registers.bg1hofs.set_byte(0, value),
-            0x04000015 => self.lcd.registers.bg1hofs.set_byte(1, value),
-            0x04000016 => self.lcd.registers.bg1vofs.set_byte(0, value),
-            0x04000017 => self.lcd.registers.bg1vofs.set_byte(1, value),
-            0x04000018 => self.lcd.registers.bg2hofs.set_byte(0, value),
-            0x04000019 => self.lcd.registers.bg2hofs.set_byte(1, value),
-            0x0400001A => self.lcd.registers.bg2vofs.set_byte(0, value),
-            0x0400001B => self.lcd.registers.bg2vofs.set_byte(1, value),
-            0x0400001C => self.lcd.registers.bg3hofs.set_byte(0, value),
-            0x0400001D => self.lcd.registers.bg3hofs.set_byte(1, value),
-            0x0400001E => self.lcd.registers.bg3vofs.set_byte(0, value),
-            0x0400001F => self.lcd.registers.bg3vofs.set_byte(1, value),
-            0x04000020 => self.lcd.registers.bg2pa.set_byte(0, value),
-            0x04000021 => self.lcd.registers.bg2pa.set_byte(1, value),
-            0x04000022 => self.lcd.registers.bg2pb.set_byte(0, value),
-            0x04000023 => self.lcd.registers.bg2pb.set_byte(1, value),
-            0x04000024 => self.lcd.registers.bg2pc.set_byte(0, value),
-            0x04000025 => self.lcd.registers.bg2pc.set_byte(1, value),
-            0x04000026 => self.lcd.registers.bg2pd.set_byte(0, value),
-            0x04000027 => self.lcd.registers.bg2pd.set_byte(1, value),
-            0x04000028 => self.lcd.registers.bg2x.set_byte(0, value),
-            0x04000029 => self.lcd.registers.bg2x.set_byte(1, value),
-            0x0400002A => self.lcd.registers.bg2x.set_byte(2, value),
-            0x0400002B => self.lcd.registers.bg2x.set_byte(3, value),
-            0x0400002C => self.lcd.registers.bg2y.set_byte(0, value),
-            0x0400002D => self.lcd.registers.bg2y.set_byte(1, value),
-            0x0400002E => self.lcd.registers.bg2y.set_byte(2, value),
-            0x0400002F => self.lcd.registers.bg2y.set_byte(3, value),
-            0x04000030 => self.lcd.registers.bg3pa.set_byte(0, value),
-            0x04000031 => self.lcd.registers.bg3pa.set_byte(1, value),
-            0x04000032 => self.lcd.registers.bg3pb.set_byte(0, value),
-            0x04000033 => self.lcd.registers.bg3pb.set_byte(1, value),
-            0x04000034 => self.lcd.registers.bg3pc.set_byte(0, value),
-            0x04000035 => self.lcd.registers.bg3pc.set_byte(1, value),
-            0x04000036 => self.lcd.registers.bg3pd.set_byte(0, value),
-            0x04000037 => self.lcd.registers.bg3pd.set_byte(1, value),
-            0x04000038 => self.lcd.registers.bg3x.set_byte(0, value),
-            0x04000039 => self.lcd.registers.bg3x.set_byte(1, value),
-            0x0400003A => self.lcd.registers.bg3x.set_byte(2, value),
-            0x0400003B => self.lcd.registers.bg3x.set_byte(3, value),
-            0x0400003C => self.lcd.registers.bg3y.set_byte(0, value),
-            0x0400003D => self.lcd.registers.bg3y.set_byte(1, value),
-            0x0400003E => self.lcd.registers.bg3y.set_byte(2, value),
-            0x0400003F => self.lcd.registers.bg3y.set_byte(3, value),
-            0x04000040 => self.lcd.registers.win0h.set_byte(0, value),
-            0x04000041 => self.lcd.registers.win0h.set_byte(1, value),
-            0x04000042 => self.lcd.registers.win1h.set_byte(0, value),
-            0x04000043 => self.lcd.registers.win1h.set_byte(1, value),
-            0x04000044 => self.lcd.registers.win0v.set_byte(0, value),
-            0x04000045 => self.lcd.registers.win0v.set_byte(1, value),
-            0x04000046 => self.lcd.registers.win1v.set_byte(0, value),
-            0x04000047 => self.lcd.registers.win1v.set_byte(1, value),
-            0x04000048 => self.lcd.registers.winin.set_byte(0, value),
-            0x04000049 => self.lcd.registers.winin.set_byte(1, value),
-            0x0400004A => self.lcd.registers.winout.set_byte(0, value),
-            0x0400004B => self.lcd.registers.winout.set_byte(1, value),
-            0x0400004C => self.lcd.registers.mosaic.set_byte(0, value),
-            0x0400004D => self.lcd.registers.mosaic.set_byte(1, value),
-            // 0x0400004E, 0x0400004F are not used
-            0x04000050 => self.lcd.registers.bldcnt.set_byte(0, value),
-            0x04000051 => self.lcd.registers.bldcnt.set_byte(1, value),
-            0x04000052 => self.lcd.registers.bldalpha.set_byte(0, value),
-            0x04000053 => self.lcd.registers.bldalpha.set_byte(1, value),
-            0x04000054 => self.lcd.registers.bldy.set_byte(0, value),
-            0x04000055 => self.lcd.registers.bldy.set_byte(1, value),
-            0x0400004E..=0x0400004F | 0x04000056..=0x0400005F => {
-                log("write on unused memory");
-                self.unused_region.insert(address, value);
+                (value & writable) | kept
             }
-            _ => panic!("LCD write address is out of bound"),
+            None => value,
+        };
+        let address = address as usize;
+
+        if !self.io_component_mut(address).on_write(address, value) {
+            log(format!("write on unused memory {address:x}"));
+            self.unused_region.insert(address, value);
         }
     }
 
     #[must_use]
-    pub fn read_raw(&self, address: usize) -> u8 {
+    pub fn read_raw(&self, address: u32) -> u8 {
         match address {
             (0x0000000..=0x0003FFF) | (0x2000000..=0x03FFFFFF) | (0x08000000..=0x0E00FFFF) => {
-                self.internal_memory.read_at(address)
+                self.internal_memory.read_at(address as usize)
             }
-            0x4000000..=0x400005F => self.read_lcd_raw(address),
-            0x4000060..=0x40000AF => self.read_sound_raw(address),
-            0x40000B0..=0x40000FF => self.read_dma_raw(address),
-            0x4000100..=0x400011F => self.read_timers_raw(address),
-            0x4000120..=0x400012F | 0x4000134..=0x40001FF => self.read_serial_raw(address),
-            0x4000130..=0x4000133 => self.read_keypad_raw(address),
-            0x4000200..=0x4FFFFFF => self.read_interrupt_control_raw(address),
+            0x4000000..=0x4FFFFFF => self.read_io(address),
             0x5000000..=0x5FFFFFF => {
-                let unmasked_address = get_unmasked_address(address, 0x00FFFF00, 0xFF0000FF, 8, 4);
-
-                match unmasked_address {
-                    0x05000000..=0x050001FF => {
-                        self.lcd.memory.bg_palette_ram[unmasked_address - 0x05000000]
-                    }
-                    0x05000200..=0x050003FF => {
-                        self.lcd.memory.obj_palette_ram[unmasked_address - 0x05000200]
-                    }
-                    _ => unreachable!(),
+                let (is_obj, offset) = palette_offset(address);
+
+                if is_obj {
+                    self.lcd.memory.obj_palette_ram[offset]
+                } else {
+                    self.lcd.memory.bg_palette_ram[offset]
                 }
             }
             0x6000000..=0x6FFFFFF => {
-                let unmasked_address = get_unmasked_address(address, 0x00FF0000, 0xFF00FFFF, 16, 2);
-
-                // VRAM is 64k+32k+32k with the last two 32k being one mirrors of each other
-                match unmasked_address {
-                    0x06000000..=0x06017FFF => {
-                        self.lcd.memory.video_ram[unmasked_address - 0x06000000]
-                    }
-                    0x06018000..=0x0601FFFF => {
-                        self.lcd.memory.video_ram[unmasked_address - 0x06000000 - 0x8000]
-                    }
-                    _ => unreachable!(),
-                }
-            }
-            0x7000000..=0x7FFFFFF => {
-                let unmasked_address = get_unmasked_address(address, 0x00FFFF00, 0xFF0000FF, 8, 4);
-
-                self.lcd.memory.obj_attributes[unmasked_address - 0x07000000]
+                self.lcd.memory.video_ram[VramAddr::mirrored(address).offset()]
             }
+            0x7000000..=0x7FFFFFF => self.lcd.memory.obj_attributes[oam_offset(address)],
             0x000_4000..=0x1FF_FFFF | 0xE01_0000..=0xFFF_FFFF | 0x1000_0000..=0xFFFF_FFFF => {
                 log(format!("read on unused memory {address:x}"));
-                *self.unused_region.get(&address).unwrap_or(&0)
+                *self.unused_region.get(&(address as usize)).unwrap_or(&0)
             }
-            _ => unimplemented!(),
         }
     }
 
-    pub fn write_raw(&mut self, address: usize, value: u8) {
+    pub fn write_raw(&mut self, address: u32, value: u8) {
         match address {
             0x0000000..=0x0003FFF | 0x2000000..=0x03FFFFFF | 0x08000000..=0x0E00FFFF => {
-                self.internal_memory.write_at(address, value);
+                self.internal_memory.write_at(address as usize, value);
             }
-            0x4000000..=0x400005F => self.write_lcd_raw(address, value),
-            0x4000060..=0x40000AF => self.write_sound_raw(address, value),
-            0x40000B0..=0x40000FF => self.write_dma_raw(address, value),
-            0x4000100..=0x400011F => self.write_timers_raw(address, value),
-            0x4000120..=0x400012F | 0x4000134..=0x40001FF => self.write_serial_raw(address, value),
-            0x4000130..=0x4000133 => self.write_keypad_raw(address, value),
-            0x4000200..=0x4FFFFFF => self.write_interrupt_control_raw(address, value),
+            0x4000000..=0x4FFFFFF => self.write_io(address, value),
             0x5000000..=0x5FFFFFF => {
-                let unmasked_address = get_unmasked_address(address, 0x00FFFF00, 0xFF0000FF, 8, 4);
-
-                match unmasked_address {
-                    0x05000000..=0x050001FF => {
-                        self.lcd.memory.bg_palette_ram[unmasked_address - 0x05000000] = value;
-                    }
-                    0x05000200..=0x050003FF => {
-                        self.lcd.memory.obj_palette_ram[unmasked_address - 0x05000200] = value;
-                    }
-                    _ => unreachable!(),
-                };
+                let (is_obj, offset) = palette_offset(address);
+
+                if is_obj {
+                    self.lcd.memory.obj_palette_ram[offset] = value;
+                } else {
+                    self.lcd.memory.bg_palette_ram[offset] = value;
+                }
             }
             0x6000000..=0x6FFFFFF => {
-                let unmasked_address = get_unmasked_address(address, 0x00FF0000, 0xFF00FFFF, 16, 2);
-
-                // VRAM is 64k+32k+32k with the last two 32k being one mirrors of each other
-                match unmasked_address {
-                    0x06000000..=0x06017FFF => {
-                        self.lcd.memory.video_ram[unmasked_address - 0x06000000] = value;
-                    }
-                    0x06018000..=0x0601FFFF => {
-                        self.lcd.memory.video_ram[unmasked_address - 0x06000000 - 0x8000] = value;
-                    }
-                    _ => unreachable!(),
-                }
+                self.lcd.memory.video_ram[VramAddr::mirrored(address).offset()] = value;
             }
             0x700_0000..=0x7FF_FFFF => {
-                let unmasked_address =
-                    get_unmasked_address(address, 0x00FF_FF00, 0xFF00_00FF, 8, 4);
-
-                self.lcd.memory.obj_attributes[unmasked_address - 0x0700_0000] = value;
+                self.lcd.memory.obj_attributes[oam_offset(address)] = value;
             }
             0x000_4000..=0x1FF_FFFF | 0xE01_0000..=0xFFF_FFFF | 0x1000_0000..=0xFFFF_FFFF => {
                 log(format!("write on unused memory {address:x}"));
-                self.unused_region.insert(address, value);
+                self.unused_region.insert(address as usize, value);
             }
-            _ => unimplemented!(),
         }
     }
 
-    pub fn read_byte(&mut self, address: usize) -> u8 {
-        for _ in 0..self.get_wait_cycles(address) {
-            self.step();
-        }
+    pub fn read_byte(&mut self, address: u32) -> u8 {
+        self.wait_access(address, 1, false);
 
-        self.last_used_address = address;
+        self.last_used_address = address as usize;
 
-        self.read_raw(address)
+        let value = self.read_raw(address);
+        self.serial.acknowledge_read(address, 1);
+        self.trace_io(address, 1, value.into(), IoAccessKind::Read);
+
+        value
     }
 
-    pub fn write_byte(&mut self, address: usize, value: u8) {
-        for _ in 0..self.get_wait_cycles(address) {
-            self.step();
-        }
+    pub fn write_byte(&mut self, address: u32, value: u8) {
+        self.wait_access(address, 1, false);
 
-        self.last_used_address = address;
+        self.last_used_address = address as usize;
 
         self.write_raw(address, value);
+        self.trace_io(address, 1, value.into(), IoAccessKind::Write);
     }
 
+    /// A cycle of the CPU, the components step once every [`Overclock::multiplier`]
+    /// of them.
+    fn cpu_step(&mut self) {
+        self.overclock_phase += 1;
+        if self.overclock_phase < self.overclock.multiplier() {
+            return;
+        }
+        self.overclock_phase = 0;
+
+        self.step();
+    }
+
+    /// A cycle of the components, the one [`Self::cycles`] counts.
     fn step(&mut self) {
         // Step cycles at beginning or end?
         // It may have an impact when we will introduce timers.
         self.cycles_count += 1;
+        self.masters.count(self.dma_channel);
 
         // TODO: move this somewhere in the UI
         #[cfg(feature = "logger")]
         log(format!("CPU Cycles: {}", self.cycles_count));
 
         // Step ppu, dma, interrupts, timers, etc...
-        let val = *self.interrupt_control.interrupt_request.back().unwrap();
-        self.interrupt_control.interrupt_request.push(val);
+        let output = match self.profile.take_if(|profile| profile.sample()) {
+            Some(profile) => self.step_components_timed(profile),
+            None => self.step_components(),
+        };
 
-        // A pixel takes 4 cycles to get drawn
-        if self.cycles_count % 4 == 0 {
-            let lcd_output = self.lcd.step();
+        if output.entered_hblank && self.lcd.registers.vcount < 160 {
+            self.dma.trigger(dma::START_AT_HBLANK);
+        }
+        if output.entered_vblank {
+            self.last_masters = std::mem::take(&mut self.masters);
+            self.dma.trigger(dma::START_AT_VBLANK);
+            self.keypad.latch();
+            self.sound.end_audio_frame();
+
+            for (address, value) in self.frozen.clone() {
+                self.write_edit(address, value);
+            }
 
-            if lcd_output.request_hblank_irq {
-                self.request_interrupt(&IrqType::HBlank);
+            let cheats = std::mem::take(&mut self.cheats);
+            for cheat in cheats.iter().filter(|cheat| cheat.enabled) {
+                cheat.apply(self);
             }
+            self.cheats = cheats;
+        }
 
-            if lcd_output.request_vblank_irq {
-                self.request_interrupt(&IrqType::VBlank);
+        if let Some(events) = &mut self.events {
+            if output.entered_hblank {
+                events.push(Event::HBlank {
+                    scanline: self.lcd.registers.vcount,
+                });
+            }
+            if output.entered_vblank {
+                events.push(Event::VBlank);
             }
+            if output.serial_transfer_completed {
+                events.push(Event::SerialTransfer);
+            }
+        }
 
-            if lcd_output.request_vcount_irq {
-                self.request_interrupt(&IrqType::VCount);
+        *self.interrupt_control.interrupt_request.back_mut().unwrap() |= output.interrupts;
+
+        if self.dma.is_pending() && self.dma_channel.is_none() {
+            self.run_dma();
+        }
+    }
+
+    /// Runs the started DMA channels by priority. The CPU waits meanwhile: the
+    /// components keep stepping with the accesses of the transfers.
+    fn run_dma(&mut self) {
+        let start = self.cycles_count;
+
+        while let Some((index, control, transfer)) = self.dma.take_pending() {
+            self.dma_channel = Some(index);
+            let width = if control.word_transfer() { 4 } else { 2 };
+            let mut source = transfer.source & !(width - 1);
+            let mut destination = transfer.destination & !(width - 1);
+
+            // The transfer starts after 2 internal cycles.
+            self.idle(2);
+            for _ in 0..transfer.count {
+                if width == 4 {
+                    let value = self.read_word(source);
+                    self.write_word(destination, value);
+                } else {
+                    let value = self.read_half_word(source);
+                    self.write_half_word(destination, value);
+                }
+
+                source = step_address(source, control.source_control(), width);
+                destination = step_address(destination, control.destination_control(), width);
             }
+
+            if self.dma.complete(index, source, destination) {
+                let irq = [IrqType::Dma0, IrqType::Dma1, IrqType::Dma2, IrqType::Dma3][index];
+                self.interrupt_control
+                    .interrupt_request
+                    .back_mut()
+                    .unwrap()
+                    .set_bit(irq.get_idx_in_if(), true);
+            }
+        }
+
+        self.dma_channel = None;
+        let stall = u64::try_from(self.cycles_count - start).unwrap_or(u64::MAX);
+        self.masters.longest_stall = self.masters.longest_stall.max(stall);
+    }
+
+    fn step_components(&mut self) -> StepOutput {
+        let mut output = StepOutput::default();
+        for component in self.components_mut() {
+            output.merge(component.step(1));
+        }
+        output.merge(self.internal_memory.step());
+
+        output
+    }
+
+    /// [`Self::step_components`] timing each of them in `profile`.
+    fn step_components_timed(&mut self, mut profile: Box<BusProfile>) -> StepOutput {
+        let mut output = StepOutput::default();
+        let components = self.components_mut();
+        let cartridge = components.len();
+        for (index, component) in components.into_iter().enumerate() {
+            let start = Instant::now();
+            output.merge(component.step(1));
+            profile.record(index, start);
+        }
+        let start = Instant::now();
+        output.merge(self.internal_memory.step());
+        profile.record(cartridge, start);
+
+        self.profile = Some(profile);
+        output
+    }
+
+    /// Times the components on a cycle every [`SAMPLE_PERIOD`](crate::bench::SAMPLE_PERIOD)
+    /// for [`bench`](crate::bench), or stops with `false`.
+    pub(crate) fn set_profiling(&mut self, enabled: bool) {
+        if enabled != self.profile.is_some() {
+            self.profile = enabled.then(|| Box::new(BusProfile::new()));
+        }
+    }
+
+    /// The times measured since profiling started, stopping it.
+    pub(crate) const fn take_profile(&mut self) -> Option<Box<BusProfile>> {
+        self.profile.take()
+    }
+
+    /// Collects the hardware events for [`Self::drain_events`], or stops with `false`.
+    pub(crate) fn set_event_recording(&mut self, enabled: bool) {
+        if enabled != self.events.is_some() {
+            self.events = enabled.then(Vec::new);
         }
     }
 
-    fn request_interrupt(&mut self, irq_type: &IrqType) {
-        self.interrupt_control
-            .interrupt_request
-            .back_mut()
-            .unwrap()
-            .set_bit(irq_type.get_idx_in_if(), true);
+    /// The events since the last call, oldest first.
+    pub(crate) fn drain_events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        self.events.iter_mut().flat_map(|events| events.drain(..))
+    }
+
+    /// Writes `value` at `address` for a debugging tool: it takes no cycles and is not
+    /// traced.
+    ///
+    /// # Errors
+    /// It fails if one of the bytes is read-only or unmapped, memory is untouched in this
+    /// case.
+    pub fn debug_write(&mut self, address: u32, value: EditValue) -> Result<(), MemoryEditError> {
+        memory_edit::check(address, value)?;
+        self.write_edit(address, value);
+
+        Ok(())
+    }
+
+    /// Writes `value` now and again at the start of every Vblank, until [`Self::unfreeze`]:
+    /// a simple cheat to keep a counter or a flag to the same value.
+    ///
+    /// # Errors
+    /// It fails as [`Self::debug_write`].
+    pub fn freeze(&mut self, address: u32, value: EditValue) -> Result<(), MemoryEditError> {
+        self.debug_write(address, value)?;
+        self.frozen.insert(address, value);
+
+        Ok(())
+    }
+
+    /// Stops writing the value frozen at `address`, returning it.
+    pub fn unfreeze(&mut self, address: u32) -> Option<EditValue> {
+        self.frozen.remove(&address)
+    }
+
+    /// Values currently frozen, by address.
+    #[must_use]
+    pub const fn frozen(&self) -> &BTreeMap<u32, EditValue> {
+        &self.frozen
+    }
+
+    /// Cheats run at the start of every Vblank after the frozen values, in order.
+    pub const fn cheats_mut(&mut self) -> &mut Vec<Cheat> {
+        &mut self.cheats
+    }
+
+    #[must_use]
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    /// Bus cycles since power-on or the last reset, see [`crate::clock`].
+    #[must_use]
+    pub const fn cycles(&self) -> u128 {
+        self.cycles_count
+    }
+
+    fn write_edit(&mut self, address: u32, value: EditValue) {
+        for (offset, byte) in (0..).zip(value.bytes()) {
+            self.write_raw(address.wrapping_add(offset), byte);
+        }
+    }
+
+    /// Handle to press and release the buttons, see [`KeypadInput`].
+    #[must_use]
+    pub fn keypad_input(&self) -> KeypadInput {
+        self.keypad.input()
+    }
+
+    pub const fn set_key_sampling(&mut self, sampling: KeySampling) {
+        self.keypad.set_sampling(sampling);
+    }
+
+    /// See [`Keypad::set_forced_keys`].
+    #[cfg(feature = "serde")]
+    pub(crate) const fn set_forced_keys(&mut self, keys: Option<u16>) {
+        self.keypad.set_forced_keys(keys);
+    }
+
+    /// See [`Keypad::latched_keys`].
+    #[cfg(feature = "serde")]
+    pub(crate) const fn latched_keys(&self) -> u16 {
+        self.keypad.latched_keys()
+    }
+
+    /// Puts every component back to its power-on state, see [`InternalMemory::reset`] for
+    /// what `hard` clears. The frontend handles, the frozen values, the cheats and the I/O
+    /// trace are kept.
+    pub fn reset(&mut self, hard: bool) {
+        for component in self.components_mut() {
+            component.reset();
+        }
+
+        self.internal_memory.reset(hard);
+        self.cycles_count = 0;
+        self.last_used_address = 0;
+        self.unused_region.clear();
+        self.last_opcode = 0;
+        self.invalid_access = None;
+        self.debug_print.reset();
+        self.masters = BusMasters::default();
+        self.last_masters = BusMasters::default();
+    }
+
+    /// Who had the bus during the last complete frame, from a Vblank to the next.
+    #[must_use]
+    pub const fn bus_masters(&self) -> BusMasters {
+        self.last_masters
+    }
+
+    /// Does nothing if the cartridge doesn't save to Flash.
+    pub const fn set_flash_timing(&mut self, timing: FlashTiming) {
+        if let Some(flash) = &mut self.internal_memory.flash {
+            flash.set_timing(timing);
+        }
+    }
+
+    /// Chooses the filters of the sound output, see [`Sound::set_output_filter`].
+    pub fn set_output_filter(&mut self, settings: FilterSettings, sample_rate: u32) {
+        self.sound.set_output_filter(settings, sample_rate);
+    }
+
+    /// See [`Sound::set_samples_per_frame`].
+    pub fn set_audio_samples_per_frame(&mut self, samples_per_frame: u32) {
+        self.sound.set_samples_per_frame(samples_per_frame);
+    }
+
+    #[must_use]
+    pub const fn audio_samples_per_frame(&self) -> u32 {
+        self.sound.samples_per_frame()
+    }
+
+    #[must_use]
+    pub const fn audio_sample_rate(&self) -> u32 {
+        self.sound.sample_rate()
+    }
+
+    /// Samples of the last completed frame, see [`Sound::take_audio_frame`].
+    pub fn take_audio_frame(&mut self) -> Vec<StereoSample> {
+        self.sound.take_audio_frame()
+    }
+
+    /// Plugs a device in the serial port, replacing the current one.
+    pub fn connect_serial_peripheral(&mut self, peripheral: SerialPeripheral) {
+        self.serial.connect(peripheral);
+    }
+
+    /// Plugs the wireless adapter, joined to the session reached through `transport`.
+    pub fn connect_wireless_adapter(&mut self, transport: Box<dyn WirelessTransport>) {
+        self.serial.connect_wireless(transport);
+    }
+
+    /// Plugs a link cable to the consoles reached through `transport`, see
+    /// [`LocalLinkCable`](crate::cpu::hardware::serial::link_cable::LocalLinkCable) to
+    /// wire two `Gba` of the same process.
+    pub fn connect_link_cable(&mut self, transport: Box<dyn LinkTransport>) {
+        self.serial.connect_link(transport);
+    }
+
+    /// Sends the strings the game prints through the debug registers of mGBA to `output`,
+    /// besides the log. `None` only logs them.
+    pub fn set_debug_output(&mut self, output: Option<DebugOutput>) {
+        self.debug_print.set_output(output);
+    }
+
+    /// Connects the UART of the serial port to `host`, see
+    /// [`PipeUart`](crate::cpu::hardware::serial::uart::PipeUart) for a host pipe.
+    pub fn connect_uart(&mut self, host: Box<dyn UartHost>) {
+        self.serial.connect_uart(host);
+    }
+
+    /// Records every following I/O register access in `trace`, or stops tracing with `None`.
+    /// The previous writer is returned so that it can be flushed.
+    #[cfg(feature = "debug-hooks")]
+    pub fn set_io_trace(
+        &mut self,
+        trace: Option<Box<dyn IoTraceWriter>>,
+    ) -> Option<Box<dyn IoTraceWriter>> {
+        std::mem::replace(&mut self.io_trace, trace)
+    }
+
+    /// Logs every following write to the LCD registers with the raster position it
+    /// happens at, to match a graphical glitch with the line and the dot it shows up on.
+    #[cfg(feature = "debug-hooks")]
+    pub const fn set_ppu_write_log(&mut self, enabled: bool) {
+        self.ppu_write_log = enabled;
+    }
+
+    #[cfg(feature = "debug-hooks")]
+    #[must_use]
+    pub const fn ppu_write_log(&self) -> bool {
+        self.ppu_write_log
+    }
+
+    /// Sorted I/O addresses written by the game that no register is mapped to yet.
+    #[must_use]
+    pub fn unmapped_io_writes(&self) -> Vec<usize> {
+        let mut addresses = self
+            .unused_region
+            .keys()
+            .copied()
+            .filter(|&address| IoAccess::is_io(address))
+            .collect::<Vec<_>>();
+        addresses.sort_unstable();
+
+        addresses
+    }
+
+    #[cfg(not(feature = "debug-hooks"))]
+    #[allow(clippy::unused_self)]
+    const fn trace_io(&self, _address: u32, _width: u8, _value: u32, _kind: IoAccessKind) {}
+
+    #[cfg(feature = "debug-hooks")]
+    fn trace_io(&mut self, address: u32, width: u8, value: u32, kind: IoAccessKind) {
+        if self.ppu_write_log
+            && kind == IoAccessKind::Write
+            && (IO_START..=LCD_REGISTERS_END.get()).contains(&address)
+        {
+            log(format!(
+                "PPU write 0x{address:08X} = 0x{value:0digits$X} at {}, cycle {}",
+                self.lcd.raster_position(),
+                self.cycles_count,
+                digits = usize::from(width) * 2,
+            ));
+        }
+
+        let Some(trace) = self.io_trace.as_mut() else {
+            return;
+        };
+
+        if !IoAccess::is_io(address as usize) {
+            return;
+        }
+
+        let access = IoAccess {
+            cycle: self.cycles_count,
+            address,
+            width,
+            value,
+            kind,
+        };
+
+        if let Err(err) = trace.record(&access) {
+            log(format!("I/O trace stopped: {err}"));
+            self.io_trace = None;
+        }
     }
 
     #[must_use]
@@ -808,57 +753,240 @@ impl Bus {
         }
     }
 
-    const fn get_wait_cycles(&self, address: usize) -> u128 {
-        let _ = self;
-        let _ = address;
+    #[cfg(feature = "serde")]
+    pub(crate) fn encode_section(&self, section: Section) -> bincode::Result<Vec<u8>> {
+        match section {
+            Section::Cpu => unreachable!("cpu section is owned by Arm7tdmi"),
+            Section::Bus => bincode::serialize(&(
+                &self.cycles_count,
+                &self.last_used_address,
+                &self.unused_region,
+                &self.prefetch,
+                &self.last_opcode,
+            )),
+            Section::InternalMemory => bincode::serialize(&self.internal_memory),
+            Section::Peripherals => self.internal_memory.save_peripherals(),
+            Section::Lcd => self.lcd.save_state(),
+            Section::Sound => self.sound.save_state(),
+            Section::Dma => self.dma.save_state(),
+            Section::Timers => self.timers.save_state(),
+            Section::Serial => self.serial.save_state(),
+            Section::Keypad => self.keypad.save_state(),
+            Section::InterruptControl => self.interrupt_control.save_state(),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn decode_section(&mut self, section: Section, data: &[u8]) -> bincode::Result<()> {
+        match section {
+            Section::Cpu => unreachable!("cpu section is owned by Arm7tdmi"),
+            Section::Bus => {
+                (
+                    self.cycles_count,
+                    self.last_used_address,
+                    self.unused_region,
+                    self.prefetch,
+                    self.last_opcode,
+                ) = bincode::deserialize(data)?;
+            }
+            Section::InternalMemory => {
+                let mut internal_memory: InternalMemory = bincode::deserialize(data)?;
+                internal_memory.rom = std::mem::take(&mut self.internal_memory.rom);
+                internal_memory.peripherals = std::mem::take(&mut self.internal_memory.peripherals);
+                if let (Some(flash), Some(current)) =
+                    (&mut internal_memory.flash, &self.internal_memory.flash)
+                {
+                    flash.set_timing(current.timing());
+                }
+                self.internal_memory = internal_memory;
+            }
+            Section::Peripherals => self.internal_memory.load_peripherals(data)?,
+            Section::Lcd => self.lcd.load_state(data)?,
+            Section::Sound => self.sound.load_state(data)?,
+            Section::Dma => self.dma.load_state(data)?,
+            Section::Timers => self.timers.load_state(data)?,
+            Section::Serial => self.serial.load_state(data)?,
+            Section::Keypad => self.keypad.load_state(data)?,
+            Section::InterruptControl => self.interrupt_control.load_state(data)?,
+        }
+
+        Ok(())
+    }
+
+    /// Cycles an access of `width` bytes at `address` takes, waitstates included.
+    ///
+    /// The work RAM, the palette, the VRAM and the cartridge have a 16 bit bus: a word
+    /// takes two accesses, the second one sequential. Cartridge accesses following the
+    /// previous one are sequential too, and faster as set in `WAITCNT`.
+    fn access_cycles(&self, address: u32, width: u32) -> u32 {
+        let sequential = address as usize == self.last_used_address.wrapping_add(width as usize);
+        let waitcnt = self.interrupt_control.wait_state_control;
+
+        let (first, second) = match address >> 24 {
+            0x02 => (3, 3),
+            0x05 | 0x06 => (1, 1),
+            0x08..=0x0D => self.rom_cycles(address, sequential),
+            // The backup memory has an 8 bit bus and no sequential accesses.
+            0x0E | 0x0F => return 1 + FIRST_ACCESS_WAITS[usize::from(waitcnt.get_bits(0..=1))],
+            // BIOS, internal work RAM, I/O and OAM have a 32 bit bus.
+            _ => return 1,
+        };
 
-        // let _is_sequential =
-        // address == self.last_used_address || address + 4 == self.last_used_address;
+        if width == 4 {
+            first + second
+        } else {
+            first
+        }
+    }
 
-        // TODO: Restore this when we have a proper memory map
-        // match address {
-        // Bios
-        // 0x0..=0x3FFF => 1,
-        // _ => 1,
-        // }
+    /// Cycles of an access to the ROM at `address` and of the sequential one completing
+    /// a word, by the waitstates of its region in `WAITCNT`.
+    fn rom_cycles(&self, address: u32, sequential: bool) -> (u32, u32) {
+        let waitcnt = self.interrupt_control.wait_state_control;
 
-        1
+        match address >> 24 {
+            0x08 | 0x09 => rom_cycles(waitcnt.get_bits(2..=3), waitcnt.get_bit(4), 2, sequential),
+            0x0A | 0x0B => rom_cycles(waitcnt.get_bits(5..=6), waitcnt.get_bit(7), 4, sequential),
+            _ => rom_cycles(waitcnt.get_bits(8..=9), waitcnt.get_bit(10), 8, sequential),
+        }
     }
 
-    pub fn read_word(&mut self, mut address: usize) -> u32 {
-        // TODO: here we have to see how many times to wait for the waitcycles
-        // It depends on the bus width of the memory region
-        // Right now we're assuming that every region has a bus width of 32 bits
-        // So we wait only once to read a word.
-        // In reality for example WRAM has a bus width of 16 bits so we would
-        // have to repeat this cycle 2 times (to emulate the fact that we will access the memory
-        // two times)
-        for _ in 0..self.get_wait_cycles(address) {
-            self.step();
+    /// Takes the cycles of an access of `width` bytes at `address`. Instruction
+    /// `fetch`es from the cartridge go through the prefetch unit when it's enabled, other
+    /// accesses to the cartridge stop it.
+    fn wait_access(&mut self, address: u32, width: u32, fetch: bool) {
+        self.record_invalid_access(address);
+
+        let cycles = self.access_cycles(address, width);
+        if !(0x0800_0000..=0x0FFF_FFFF).contains(&address) {
+            self.idle(cycles);
+            return;
+        }
+
+        let prefetch_enabled = self.interrupt_control.wait_state_control.get_bit(14);
+        if fetch && prefetch_enabled && address < 0x0E00_0000 {
+            // The buffer answers in a cycle per half-word, and keeps filling meanwhile.
+            if self.prefetch.take(address, width.div_ceil(2)) {
+                self.idle(width.div_ceil(2));
+            } else {
+                self.stall(cycles);
+                self.prefetch.restart(address + width);
+            }
+        } else {
+            self.prefetch.stop();
+            self.stall(cycles);
+        }
+    }
+
+    const fn record_invalid_access(&mut self, address: u32) {
+        if self.invalid_access.is_none() && is_unmapped(address) {
+            self.invalid_access = Some(address);
+        }
+    }
+
+    /// First address accessed in an unmapped region since the last call, instruction
+    /// fetches included. The CPU turns it into an abort when asked to.
+    #[must_use]
+    pub const fn invalid_access(&self) -> Option<u32> {
+        self.invalid_access
+    }
+
+    pub const fn take_invalid_access(&mut self) -> Option<u32> {
+        self.invalid_access.take()
+    }
+
+    /// Lets `cycles` internal cycles of the CPU pass: the bus isn't accessed, but the other
+    /// components keep running.
+    pub fn idle(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            self.step_prefetch();
+            self.cpu_step();
         }
+    }
 
-        self.last_used_address = address;
+    /// Lets `cycles` cycles of a cartridge access pass, the prefetch unit waits.
+    fn stall(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            self.cpu_step();
+        }
+    }
+
+    /// The components keep their speed, [`Self::cycles`] counts theirs. Kept across
+    /// resets, and not saved: it's an option of the emulator.
+    pub const fn set_overclock(&mut self, overclock: Overclock) {
+        self.overclock = overclock;
+        self.overclock_phase = 0;
+    }
+
+    #[must_use]
+    pub const fn overclock(&self) -> Overclock {
+        self.overclock
+    }
+
+    /// A free cycle of the cartridge bus for the prefetch unit.
+    fn step_prefetch(&mut self) {
+        if !self.interrupt_control.wait_state_control.get_bit(14) {
+            return;
+        }
+
+        if let Some(address) = self.prefetch.next_address() {
+            let (cycles, _) = self.rom_cycles(address, true);
+            self.prefetch.tick(cycles);
+        }
+    }
+
+    /// Reads the instruction at `address`, see [`Prefetch`] for the cartridge.
+    pub(crate) fn fetch_word(&mut self, address: u32) -> u32 {
+        self.wait_access(address, 4, true);
+        let op_code = self.load_word(address);
+        self.last_opcode = op_code;
+
+        op_code
+    }
+
+    /// Reads the Thumb instruction at `address`, see [`Prefetch`] for the cartridge.
+    /// In Thumb the open bus reads the opcode in both halves.
+    pub(crate) fn fetch_half_word(&mut self, address: u32) -> u16 {
+        self.wait_access(address, 2, true);
+        let op_code = self.load_half_word(address);
+        self.last_opcode = u32::from(op_code) * 0x1_0001;
+
+        op_code
+    }
+
+    pub fn read_word(&mut self, address: u32) -> u32 {
+        self.wait_access(address, 4, false);
+        self.load_word(address)
+    }
+
+    fn load_word(&mut self, mut address: u32) -> u32 {
+        self.last_used_address = address as usize;
 
         if address & 3 != 0 {
             log("warning, read_word has address not word aligned");
             address &= !3;
         }
 
+        if let Some(bytes) = self.internal_memory.rom_unit(address, 4) {
+            return u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+
         let part_0: u32 = self.read_raw(address).into();
         let part_1: u32 = self.read_raw(address + 1).into();
         let part_2: u32 = self.read_raw(address + 2).into();
         let part_3: u32 = self.read_raw(address + 3).into();
 
-        part_3 << 24_u32 | part_2 << 16_u32 | part_1 << 8_u32 | part_0
+        let value = part_3 << 24_u32 | part_2 << 16_u32 | part_1 << 8_u32 | part_0;
+        self.serial.acknowledge_read(address, 4);
+        self.trace_io(address, 4, value, IoAccessKind::Read);
+
+        value
     }
 
-    pub fn write_word(&mut self, mut address: usize, value: u32) {
-        // TODO: Look at read_word
-        for _ in 0..self.get_wait_cycles(address) {
-            self.step();
-        }
+    pub fn write_word(&mut self, mut address: u32, value: u32) {
+        self.wait_access(address, 4, false);
 
-        self.last_used_address = address;
+        self.last_used_address = address as usize;
 
         if address & 3 != 0 {
             log("warning, write_word has address not word aligned");
@@ -874,34 +1002,40 @@ impl Bus {
         self.write_raw(address + 1, part_1);
         self.write_raw(address + 2, part_2);
         self.write_raw(address + 3, part_3);
+        self.trace_io(address, 4, value, IoAccessKind::Write);
     }
 
-    pub fn read_half_word(&mut self, mut address: usize) -> u16 {
-        // TODO: Look at read_word
-        for _ in 0..self.get_wait_cycles(address) {
-            self.step();
-        }
+    pub fn read_half_word(&mut self, address: u32) -> u16 {
+        self.wait_access(address, 2, false);
+        self.load_half_word(address)
+    }
 
-        self.last_used_address = address;
+    fn load_half_word(&mut self, mut address: u32) -> u16 {
+        self.last_used_address = address as usize;
 
         if address & 1 != 0 {
             log("warning, read_half_word has address not half-word aligned");
             address &= !1;
         }
 
+        if let Some(bytes) = self.internal_memory.rom_unit(address, 2) {
+            return u16::from_le_bytes(bytes.try_into().unwrap());
+        }
+
         let part_0: u16 = self.read_raw(address).into();
         let part_1: u16 = self.read_raw(address + 1).into();
 
-        part_1 << 8 | part_0
+        let value = part_1 << 8 | part_0;
+        self.serial.acknowledge_read(address, 2);
+        self.trace_io(address, 2, value.into(), IoAccessKind::Read);
+
+        value
     }
 
-    pub fn write_half_word(&mut self, mut address: usize, value: u16) {
-        // TODO: Look at read_word
-        for _ in 0..self.get_wait_cycles(address) {
-            self.step();
-        }
+    pub fn write_half_word(&mut self, mut address: u32, value: u16) {
+        self.wait_access(address, 2, false);
 
-        self.last_used_address = address;
+        self.last_used_address = address as usize;
 
         if address & 1 != 0 {
             log("warning, write_half_word has address not half-word aligned");
@@ -913,13 +1047,41 @@ impl Bus {
 
         self.write_raw(address, part_0);
         self.write_raw(address + 1, part_1);
+        self.trace_io(address, 2, value.into(), IoAccessKind::Write);
+    }
+
+    #[must_use]
+    pub const fn halted(&self) -> bool {
+        self.interrupt_control.halted
+    }
+
+    /// Takes the CPU out of a halt without an interrupt, for a reset.
+    pub(crate) const fn wake(&mut self) {
+        self.interrupt_control.halted = false;
+    }
+
+    /// Runs the components while the CPU is halted, until an enabled interrupt is
+    /// requested or `max_cycles` of them have passed, overclocked or not. The interrupt
+    /// wakes the CPU even if `IME` or the CPSR disable it.
+    pub(crate) fn halt_step(&mut self, max_cycles: u32) {
+        for _ in 0..max_cycles {
+            let interrupts = self.interrupt_control.interrupt_enable
+                & *self.interrupt_control.interrupt_request.front().unwrap();
+            if interrupts != 0 {
+                self.interrupt_control.halted = false;
+                return;
+            }
+
+            self.step_prefetch();
+            self.step();
+        }
     }
 
     /// Returns the value of the interrupt control register
     ///
     /// # Panics
     #[must_use]
-    pub fn is_irq_pending(&self) -> bool {
+    pub(crate) fn is_irq_pending(&self) -> bool {
         // Interrupt Master Enable has to be 1
         // && there needs to be an interrupt requested which is also enabled in the interrupt enable reg
         (self.interrupt_control.interrupt_master_enable == 1)
@@ -931,7 +1093,18 @@ impl Bus {
 
 #[cfg(test)]
 mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
     use crate::bus::Bus;
+    use crate::config::Overclock;
+    use crate::cpu::hardware::dma::DmaCnt;
+    use crate::cpu::hardware::internal_memory::InternalMemory;
+    use crate::cpu::hardware::keypad::Key;
+    use crate::cpu::hardware::lcd::registers::DispStat;
+    use crate::cpu::hardware::sound::registers::SoundCntX;
+    use crate::cpu::hardware::sound::resampler::DEFAULT_SAMPLES_PER_FRAME;
+    use crate::memory_edit::{EditValue, MemoryEditError};
+    use crate::memory_map::{IF, IO_REGISTERS, WAITCNT};
 
     #[test]
     fn test_write_lcd_reg() {
@@ -981,6 +1154,138 @@ mod tests {
         assert_eq!(bus.read_raw(address), 10);
     }
 
+    #[test]
+    fn byte_writes_keep_the_other_byte_and_read_only_bits() {
+        let mut bus = Bus::default();
+
+        // VCOUNT setting 0x12, Vblank and VCOUNT match flags set.
+        bus.lcd.registers.dispstat = DispStat(0x1205);
+        bus.write_raw(0x0400_0004, 0xFF);
+        assert_eq!(bus.lcd.registers.dispstat.0, 0x123D);
+        bus.write_raw(0x0400_0005, 0x34);
+        assert_eq!(bus.lcd.registers.dispstat.0, 0x343D);
+
+        // Channels 1 and 2 playing.
+        bus.sound.control_sound_on_off = SoundCntX(0x0003);
+        bus.write_raw(0x0400_0084, 0xF0);
+        assert_eq!(bus.read_raw(0x0400_0084), 0x83);
+
+        // The game pak type bit of WAITCNT is read-only.
+        bus.write_raw(WAITCNT.get() + 1, 0xFF);
+        assert_eq!(bus.read_raw(WAITCNT.get() + 1), 0x5F);
+    }
+
+    #[test]
+    fn write_only_bits_read_as_zero() {
+        let mut bus = Bus::default();
+
+        bus.write_raw(0x0400_0010, 0x34);
+        assert_eq!(bus.lcd.registers.bg0hofs, 0x34);
+        assert_eq!(bus.read_raw(0x0400_0010), 0);
+
+        // Duty pattern is readable, sound length isn't.
+        bus.write_raw(0x0400_0062, 0xFF);
+        assert_eq!(bus.read_raw(0x0400_0062), 0xC0);
+
+        bus.write_raw(0x0400_00C6, 0xFF);
+        assert_eq!(bus.dma.channels[1].control, DmaCnt(0x00E0));
+        assert_eq!(bus.read_raw(0x0400_00C6), 0xE0);
+        assert_eq!(bus.read_raw(0x0400_00C4), 0);
+    }
+
+    #[test]
+    fn unused_io_reads_open_bus_or_zero() {
+        let mut bus = Bus::default();
+        bus.write_word(0x0300_0000, 0x1234_5678);
+        bus.fetch_word(0x0300_0000);
+
+        // Write-only registers and unmapped addresses read the last fetched opcode.
+        bus.write_half_word(0x0400_0010, 0x0001);
+        assert_eq!(bus.read_half_word(0x0400_0010), 0x5678);
+        assert_eq!(bus.read_word(0x0400_0028), 0x1234_5678);
+        bus.write_half_word(0x0400_004E, 0xFFFF);
+        assert_eq!(bus.read_half_word(0x0400_004E), 0x1234);
+        assert_eq!(bus.read_byte(0x0400_0301 + 0x100), 0x56);
+
+        // Unused bits of readable registers, and some unused half-words, read 0.
+        assert_eq!(bus.read_half_word(0x0400_0006) & 0xFF00, 0);
+        for address in [0x0400_0066, 0x0400_0136, 0x0400_015A, 0x0400_020A] {
+            assert_eq!(bus.read_half_word(address), 0, "{address:#010X}");
+        }
+        assert_eq!(bus.read_half_word(0x0400_00B8), 0);
+
+        // In Thumb the opcode fills both halves.
+        bus.fetch_half_word(0x0300_0002);
+        assert_eq!(bus.read_word(0x0400_0010), 0x1234_1234);
+    }
+
+    #[test]
+    fn every_io_address_is_accessible() {
+        let mut bus = Bus::default();
+
+        for address in 0x0400_0000..=0x0400_03FF {
+            let value = bus.read_byte(address);
+            bus.write_byte(address, value);
+        }
+        for address in (0x0400_0000..=0x0400_03FF).step_by(2) {
+            let value = bus.read_half_word(address);
+            bus.write_half_word(address, value);
+        }
+        for address in (0x0400_0000..=0x0400_03FF).step_by(4) {
+            let value = bus.read_word(address);
+            bus.write_word(address, value);
+        }
+    }
+
+    #[test]
+    fn haltcnt_reads_zero() {
+        let mut bus = Bus::default();
+        bus.write_word(0x0300_0000, 0x1234_5678);
+        bus.fetch_word(0x0300_0000);
+
+        bus.write_half_word(0x0400_0300, 0xFFFF);
+        assert_eq!(bus.read_half_word(0x0400_0300), 0x0001);
+        assert_eq!(bus.read_byte(0x0400_0301), 0);
+        assert!(bus.interrupt_control.halted);
+    }
+
+    fn next_vblank(bus: &mut Bus) {
+        while bus.lcd.registers.dispstat.vblank_flag() {
+            bus.idle(1);
+        }
+        while !bus.lcd.registers.dispstat.vblank_flag() {
+            bus.idle(1);
+        }
+    }
+
+    #[test]
+    fn bus_masters_of_a_frame() {
+        let mut bus = Bus::default();
+        next_vblank(&mut bus);
+        assert_eq!(bus.bus_masters().dma_total(), 0);
+
+        // DMA3 copies 16 words within the internal work RAM right away.
+        for (offset, byte) in (0..).zip(0x0300_0000_u32.to_le_bytes()) {
+            bus.write_raw(0x0400_00D4 + offset, byte);
+        }
+        for (offset, byte) in (0..).zip(0x0300_1000_u32.to_le_bytes()) {
+            bus.write_raw(0x0400_00D8 + offset, byte);
+        }
+        bus.write_raw(0x0400_00DC, 16);
+        bus.write_raw(0x0400_00DF, 0x84);
+        next_vblank(&mut bus);
+
+        let masters = bus.bus_masters();
+        // 2 internal cycles, then a read and a write of a cycle each per word.
+        assert_eq!(masters.dma, [0, 0, 0, 34]);
+        assert_eq!(masters.longest_stall, 34);
+        assert_eq!(masters.cpu + masters.dma_total(), 280_896);
+
+        next_vblank(&mut bus);
+        assert_eq!(bus.bus_masters().dma_total(), 0);
+        assert_eq!(bus.bus_masters().longest_stall, 0);
+    }
+
     #[test]
     fn write_bg_palette_ram() {
         let mut bus = Bus::default();
@@ -1167,4 +1472,372 @@ mod tests {
         bus.write_raw(0x07FFFD34, 13);
         assert_eq!(bus.lcd.memory.obj_attributes[0x134], 13);
     }
+
+    #[test]
+    fn frozen_values_are_written_every_vblank() {
+        let mut bus = Bus::default();
+
+        assert_eq!(bus.freeze(0x0200_0010, EditValue::HalfWord(0x1234)), Ok(()));
+        assert_eq!(bus.read_half_word(0x0200_0010), 0x1234);
+
+        bus.write_half_word(0x0200_0010, 0);
+        let output = bus.lcd.frame_output();
+        while output.frame_count() == 0 {
+            bus.step();
+        }
+        assert_eq!(bus.read_half_word(0x0200_0010), 0x1234);
+
+        assert_eq!(bus.unfreeze(0x0200_0010), Some(EditValue::HalfWord(0x1234)));
+        assert!(bus.frozen().is_empty());
+
+        assert_eq!(
+            bus.freeze(0x0800_0000, EditValue::Byte(1)),
+            Err(MemoryEditError::ReadOnly(0x0800_0000))
+        );
+        assert!(bus.frozen().is_empty());
+    }
+
+    #[test]
+    fn every_frame_has_the_same_number_of_samples() {
+        let mut bus = Bus::default();
+        let output = bus.lcd.frame_output();
+
+        for frame in 1..=3 {
+            while output.frame_count() < frame {
+                bus.step();
+            }
+            assert_eq!(
+                bus.take_audio_frame().len(),
+                DEFAULT_SAMPLES_PER_FRAME as usize
+            );
+        }
+
+        bus.set_audio_samples_per_frame(800);
+        while output.frame_count() < 4 {
+            bus.step();
+        }
+        assert_eq!(bus.take_audio_frame().len(), 800);
+        assert!(bus.take_audio_frame().is_empty());
+    }
+
+    #[test]
+    fn components_reset_keeps_frontend_handles() {
+        let mut bus = Bus::default();
+        let output = bus.lcd.frame_output();
+        let input = bus.keypad_input();
+
+        bus.write_half_word(0x0400_0000, 0x0403);
+        bus.write_half_word(0x0400_0132, 0x4001);
+        bus.write_byte(0x0400_004E, 7);
+
+        for component in bus.components_mut() {
+            component.reset();
+        }
+
+        assert_eq!(bus.read_half_word(0x0400_0000), 0);
+        assert_eq!(bus.read_half_word(0x0400_0132), 0);
+        // Unused addresses aren't owned by a component.
+        assert_eq!(bus.unmapped_io_writes(), [0x0400_004E]);
+
+        input.set_pressed(Key::A, true);
+        while output.frame_count() == 0 {
+            bus.step();
+        }
+        assert_eq!(bus.read_half_word(0x0400_0130), 0x03FE);
+    }
+
+    #[test]
+    fn lcd_status_registers_are_read_only() {
+        let mut bus = Bus::default();
+        bus.lcd.registers.dispstat = DispStat(0b101);
+        bus.lcd.registers.vcount = 100;
+
+        bus.write_half_word(0x0400_0004, 0x1238);
+        assert_eq!(bus.read_half_word(0x0400_0004), 0x123D);
+
+        bus.write_half_word(0x0400_0006, 300);
+        assert_eq!(bus.read_half_word(0x0400_0006), 100);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-hooks")]
+    fn io_accesses_are_traced() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::io_trace::{IoAccess, IoAccessKind, IoTraceWriter};
+
+        struct Recorder(Arc<Mutex<Vec<IoAccess>>>);
+
+        impl IoTraceWriter for Recorder {
+            fn record(&mut self, access: &IoAccess) -> std::io::Result<()> {
+                self.0.lock().unwrap().push(*access);
+                Ok(())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let accesses = Arc::new(Mutex::new(Vec::new()));
+        let mut bus = Bus::default();
+        bus.set_io_trace(Some(Box::new(Recorder(Arc::clone(&accesses)))));
+
+        bus.write_half_word(0x0400_0000, 0x0403);
+        bus.write_word(0x0300_0000, 1);
+        bus.read_byte(0x0400_0000);
+
+        assert!(bus.set_io_trace(None).is_some());
+        bus.write_half_word(0x0400_0000, 0);
+
+        assert_eq!(
+            *accesses.lock().unwrap(),
+            vec![
+                IoAccess {
+                    cycle: 1,
+                    address: 0x0400_0000,
+                    width: 2,
+                    value: 0x0403,
+                    kind: IoAccessKind::Write,
+                },
+                IoAccess {
+                    cycle: 3,
+                    address: 0x0400_0000,
+                    width: 1,
+                    value: 0x03,
+                    kind: IoAccessKind::Read,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn overclocked_cpu_keeps_the_components_speed() {
+        let mut bus = Bus::default();
+        bus.set_overclock(Overclock::X4);
+
+        // A scanline of the LCD takes 4 times as many CPU cycles.
+        bus.idle(1232 * 4 - 1);
+        assert_eq!(bus.cycles(), 1231);
+        assert_eq!(bus.read_raw(0x0400_0006), 0);
+        bus.idle(1);
+        assert_eq!(bus.cycles(), 1232);
+        assert_eq!(bus.read_raw(0x0400_0006), 1);
+
+        // Halted, the CPU waits for the components at their speed.
+        bus.halt_step(1232);
+        assert_eq!(bus.read_raw(0x0400_0006), 2);
+    }
+
+    /// A bus with a 1 `MByte` ROM holding the low byte of each half-word address.
+    fn bus_with_rom() -> Bus {
+        let rom = (0..0x8_0000_u32)
+            .flat_map(|half_word| [half_word.to_le_bytes()[0], 0])
+            .collect();
+
+        Bus::with_memory(InternalMemory::new([0; 0x4000], rom))
+    }
+
+    #[test]
+    fn access_cycles_depend_on_region_and_waitcnt() {
+        fn read_cycles(bus: &mut Bus, address: u32, width: u32) -> u128 {
+            let start = bus.cycles();
+            if width == 2 {
+                bus.read_half_word(address);
+            } else {
+                bus.read_word(address);
+            }
+
+            bus.cycles() - start
+        }
+
+        let mut bus = bus_with_rom();
+
+        // Address, width and cycles, in order: cartridge accesses following the previous
+        // one are sequential.
+        let accesses = [
+            (0x0300_0000, 4, 1),
+            (0x0200_0000, 4, 6),
+            (0x0200_0000, 2, 3),
+            (0x0600_0000, 4, 2),
+            // Waitstate 0 defaults to 4 waitstates, then 2 for sequential accesses.
+            (0x0800_0000, 4, 8),
+            (0x0800_0004, 4, 6),
+            (0x0800_0100, 2, 5),
+            (0x0800_0102, 2, 3),
+            // Waitstate 2 is slower for sequential accesses.
+            (0x0C00_0000, 4, 14),
+        ];
+        for (address, width, expected) in accesses {
+            assert_eq!(
+                read_cycles(&mut bus, address, width),
+                expected,
+                "{address:#010X}"
+            );
+        }
+
+        // 3 and 1 waitstates, as set by most games.
+        bus.write_half_word(WAITCNT.get(), 0x4317);
+        assert_eq!(read_cycles(&mut bus, 0x0800_0000, 4), 6);
+        assert_eq!(read_cycles(&mut bus, 0x0800_0004, 4), 4);
+
+        let start = bus.cycles();
+        bus.idle(3);
+        assert_eq!(bus.cycles() - start, 3);
+    }
+
+    #[test]
+    fn code_fetches_from_the_cartridge() {
+        fn fetch_cycles(bus: &mut Bus, address: u32, width: u32) -> u128 {
+            let start = bus.cycles();
+            if width == 2 {
+                bus.fetch_half_word(address);
+            } else {
+                bus.fetch_word(address);
+            }
+
+            bus.cycles() - start
+        }
+
+        let mut bus = bus_with_rom();
+
+        // Without prefetch, ARM code takes two accesses and Thumb code one.
+        assert_eq!(fetch_cycles(&mut bus, 0x0800_0000, 4), 8);
+        assert_eq!(fetch_cycles(&mut bus, 0x0800_0004, 4), 6);
+        assert_eq!(fetch_cycles(&mut bus, 0x0800_0080, 2), 5);
+        assert_eq!(fetch_cycles(&mut bus, 0x0800_0082, 2), 3);
+
+        bus.write_half_word(WAITCNT.get(), 0x4000);
+        assert_eq!(fetch_cycles(&mut bus, 0x0800_0100, 2), 5);
+
+        // Three half-words buffered in 3 cycles each while the CPU works.
+        bus.idle(10);
+        assert_eq!(fetch_cycles(&mut bus, 0x0800_0102, 2), 1);
+        assert_eq!(fetch_cycles(&mut bus, 0x0800_0104, 4), 2);
+
+        // Data accesses to the cartridge stop the unit.
+        bus.idle(10);
+        bus.read_half_word(0x0800_0000);
+        assert_eq!(fetch_cycles(&mut bus, 0x0800_0108, 2), 5);
+
+        // Other regions leave the cartridge bus to the unit.
+        bus.read_word(0x0200_0000);
+        assert_eq!(fetch_cycles(&mut bus, 0x0800_010A, 2), 1);
+    }
+
+    #[test]
+    fn random_addresses_across_the_address_space() {
+        let mut rng = StdRng::seed_from_u64(0xC1E3_E471);
+        let mut bus = bus_with_rom();
+
+        for _ in 0..20_000 {
+            let address = rng.gen::<u32>();
+            // Without a backup chip the SRAM region isn't implemented.
+            if (0x0E00_0000..=0x0E00_FFFF).contains(&address) {
+                continue;
+            }
+
+            match rng.gen_range(0..6) {
+                0 => drop(bus.read_byte(address)),
+                1 => drop(bus.read_half_word(address)),
+                2 => drop(bus.read_word(address)),
+                3 => bus.write_byte(address, rng.gen()),
+                4 => bus.write_half_word(address, rng.gen()),
+                _ => bus.write_word(address, rng.gen()),
+            }
+        }
+
+        // The last word and half-word of the address space are unused.
+        assert_eq!(bus.read_word(0xFFFF_FFFF), bus.read_word(0xFFFF_FFFC));
+        assert_eq!(
+            bus.read_half_word(0xFFFF_FFFF),
+            bus.read_half_word(0xFFFF_FFFE)
+        );
+    }
+
+    #[test]
+    fn random_byte_writes_to_io_registers() {
+        let mut rng = StdRng::seed_from_u64(0x0400_0000);
+        let mut bus = Bus::default();
+
+        for _ in 0..20_000 {
+            let register = &IO_REGISTERS[rng.gen_range(0..IO_REGISTERS.len())];
+            // Writing to IF acknowledges the interrupts instead of setting the bits.
+            if register.address == IF {
+                continue;
+            }
+
+            let start = register.address.get();
+            let address = start + rng.gen_range(0..register.size);
+            let value = rng.gen::<u8>();
+            let before = (start..start + register.size)
+                .map(|address| bus.read_raw(address))
+                .collect::<Vec<_>>();
+
+            bus.write_raw(address, value);
+
+            for (byte_address, before) in (start..).zip(before) {
+                let readable = register.readable_byte(byte_address);
+                let expected = if byte_address == address {
+                    let writable = register.writable_byte(address);
+                    (value & writable & readable) | (before & readable & !writable)
+                } else {
+                    before
+                };
+
+                assert_eq!(
+                    bus.read_raw(byte_address),
+                    expected,
+                    "{} at {byte_address:#010X} after writing {value:#04X} at {address:#010X}",
+                    register.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn high_mirrors_reach_the_same_memory() {
+        let mut rng = StdRng::seed_from_u64(0x0800_0000);
+        let mut bus = bus_with_rom();
+
+        // Base address, size of the memory and number of mirrors up to the next region.
+        let mirrored = [
+            (0x0200_0000, 0x4_0000, 0x40),
+            (0x0300_0000, 0x8000, 0x200),
+            (0x0500_0000, 0x400, 0x4000),
+            (0x0700_0000, 0x400, 0x4000),
+        ];
+
+        for (base, size, mirrors) in mirrored {
+            for _ in 0..1000 {
+                let offset = rng.gen_range(0..size) & !3;
+                let mirror = base + rng.gen_range(1..mirrors) * size + offset;
+                let value = rng.gen();
+
+                bus.write_word(mirror, value);
+                assert_eq!(bus.read_word(base + offset), value, "{mirror:#010X}");
+            }
+        }
+
+        // VRAM mirrors every 128 `KBytes`, the last 32 `KBytes` mirroring the previous ones.
+        for _ in 0..1000 {
+            let offset = rng.gen_range(0x1_8000..0x2_0000) & !3;
+            let mirror = 0x0600_0000 + rng.gen_range(1..0x80) * 0x2_0000 + offset;
+            let value = rng.gen();
+
+            bus.write_word(mirror, value);
+            assert_eq!(bus.read_word(0x0600_0000 + offset - 0x8000), value);
+        }
+
+        // The ROM is seen three times, and can't be written.
+        for _ in 0..1000 {
+            let offset: u32 = rng.gen_range(0..0x10_0000) & !1;
+            let expected = u16::from((offset / 2).to_le_bytes()[0]);
+
+            for base in [0x0800_0000, 0x0A00_0000, 0x0C00_0000] {
+                bus.write_half_word(base + offset, !expected);
+                assert_eq!(bus.read_half_word(base + offset), expected);
+            }
+        }
+    }
 }
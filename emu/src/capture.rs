@@ -0,0 +1,237 @@
+//! Screenshots and state dumps taken by the core itself, for long unattended runs such
+//! as compatibility sweeps: no frontend has to watch the emulation.
+//!
+//! A [`CapturePolicy`] tells when to capture, a [`CaptureSink`] where the captures go,
+//! see [`Gba::set_capture`](crate::gba::Gba::set_capture).
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use emu::capture::{CapturePolicy, DirectorySink, FrameCapture};
+//! # fn run(gba: &mut emu::gba::Gba) -> std::io::Result<()> {
+//! let policy = CapturePolicy {
+//!     every: Some(Duration::from_secs(30)),
+//!     on_error: true,
+//! };
+//! let sink = DirectorySink::new("captures")?;
+//! gba.set_capture(Some(FrameCapture::new(policy, Box::new(sink))));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    clock,
+    config::ColorCorrection,
+    cpu::{execution_trap::ExecutionTrap, hardware::lcd::Frame},
+    frame_guard::FrameOverrun,
+};
+
+/// Why a capture was taken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CaptureReason {
+    /// [`CapturePolicy::every`] passed.
+    Periodic,
+    Trap(ExecutionTrap),
+    Overrun(FrameOverrun),
+}
+
+impl CaptureReason {
+    /// Short name for file names.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Periodic => "periodic",
+            Self::Trap(_) => "trap",
+            Self::Overrun(_) => "overrun",
+        }
+    }
+}
+
+impl fmt::Display for CaptureReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Periodic => write!(f, "periodic screenshot"),
+            Self::Trap(trap) => write!(f, "execution trap: {trap}"),
+            Self::Overrun(overrun) => write!(f, "frame overrun: {overrun}"),
+        }
+    }
+}
+
+pub struct Capture<'a> {
+    /// Frames completed when the capture was taken, as counted by the frame output.
+    pub frame: u64,
+    pub reason: &'a CaptureReason,
+    /// The last completed frame.
+    pub pixels: &'a Frame,
+    /// Save-state of the emulator at the time of the capture, only taken on errors and
+    /// with the `serde` feature.
+    pub state: Option<&'a [u8]>,
+}
+
+/// Where the captures are written.
+pub trait CaptureSink: Send {
+    /// # Errors
+    /// It fails if the capture can't be written, the following ones are still attempted.
+    fn capture(&mut self, capture: &Capture<'_>) -> io::Result<()>;
+}
+
+/// Writes every capture in a directory: `frame-000123-trap.ppm` for the picture, and
+/// for errors `.state` for the save-state and `.txt` for the description.
+pub struct DirectorySink {
+    dir: PathBuf,
+}
+
+impl DirectorySink {
+    /// # Errors
+    /// It fails if `dir` doesn't exist and can't be created.
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+}
+
+impl CaptureSink for DirectorySink {
+    fn capture(&mut self, capture: &Capture<'_>) -> io::Result<()> {
+        let name = format!("frame-{:06}-{}", capture.frame, capture.reason.name());
+        let path = |extension: &str| self.dir.join(format!("{name}.{extension}"));
+
+        fs::write(path("ppm"), encode_ppm(capture.pixels))?;
+        if let Some(state) = capture.state {
+            fs::write(path("state"), state)?;
+        }
+        if *capture.reason != CaptureReason::Periodic {
+            fs::write(path("txt"), format!("{}\n", capture.reason))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Encodes `pixels` as a binary PPM image, readable by most image tools without a
+/// dependency on an image library here.
+#[must_use]
+pub fn encode_ppm(pixels: &Frame) -> Vec<u8> {
+    let mut image = format!("P6\n{} {}\n255\n", pixels[0].len(), pixels.len()).into_bytes();
+    for color in pixels.iter().flatten() {
+        image.extend(color.to_rgb8(ColorCorrection::Off));
+    }
+
+    image
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CapturePolicy {
+    /// Emulated time between two periodic screenshots, `None` for none.
+    pub every: Option<Duration>,
+    /// Screenshot and save-state when the CPU hits an
+    /// [`ExecutionTrap`] or a frame overruns.
+    pub on_error: bool,
+}
+
+/// A policy and its sink, with what was captured so far.
+pub struct FrameCapture {
+    policy: CapturePolicy,
+    sink: Box<dyn CaptureSink>,
+    /// Frames completed at the last step.
+    frame: u64,
+    /// Cycle count of the last periodic screenshot.
+    last_periodic: Option<u128>,
+    /// The pending trap is captured once, it stays pending until the frontend takes it.
+    last_trap: Option<ExecutionTrap>,
+    /// Captures that couldn't be written.
+    errors: u64,
+}
+
+impl FrameCapture {
+    #[must_use]
+    pub fn new(policy: CapturePolicy, sink: Box<dyn CaptureSink>) -> Self {
+        Self {
+            policy,
+            sink,
+            frame: 0,
+            last_periodic: None,
+            last_trap: None,
+            errors: 0,
+        }
+    }
+
+    #[must_use]
+    pub const fn policy(&self) -> CapturePolicy {
+        self.policy
+    }
+
+    /// Captures that couldn't be written, the first one is also notified.
+    #[must_use]
+    pub const fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Whether a periodic screenshot is due at `cycles`, taken when `frame` was just
+    /// completed so that the picture is whole.
+    pub(crate) fn periodic_due(&mut self, frame: u64, cycles: u128) -> bool {
+        let completed = frame != self.frame;
+        self.frame = frame;
+        let Some(every) = self.policy.every.filter(|_| completed) else {
+            return false;
+        };
+
+        // The cycles go back after a reset or a state load.
+        let due = self
+            .last_periodic
+            .and_then(|last| cycles.checked_sub(last))
+            .is_none_or(|elapsed| clock::cycles_to_duration(elapsed) >= every);
+        if due {
+            self.last_periodic = Some(cycles);
+        }
+
+        due
+    }
+
+    /// Whether `trap` wasn't captured yet.
+    pub(crate) fn new_trap(&mut self, trap: ExecutionTrap) -> bool {
+        if !self.policy.on_error || self.last_trap == Some(trap) {
+            return false;
+        }
+        self.last_trap = Some(trap);
+
+        true
+    }
+
+    /// Writes the capture, returning the error only for the first failure.
+    pub(crate) fn write(&mut self, capture: &Capture<'_>) -> Option<io::Error> {
+        let error = self.sink.capture(capture).err()?;
+        self.errors += 1;
+
+        (self.errors == 1).then_some(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::hardware::lcd::Color;
+
+    #[test]
+    fn ppm_has_the_full_intensity_range() {
+        let mut pixels: Box<Frame> = vec![[Color::default(); 240]; 160]
+            .into_boxed_slice()
+            .try_into()
+            .unwrap_or_else(|_| unreachable!());
+        pixels[0][0] = Color::from_rgb(31, 0, 16);
+
+        let image = encode_ppm(&pixels);
+        let header = b"P6\n240 160\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 240 * 160 * 3);
+        assert_eq!(image[header.len()..][..3], [0xFF, 0x00, 0x84]);
+    }
+}
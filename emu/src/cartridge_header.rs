@@ -1,4 +1,85 @@
+use std::fmt;
+
+/// Nintendo logo of the Game Boy and Game Boy Color cartridges, at 0x104.
+const GB_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+/// Bytes of the GBA header read by [`CartridgeHeader::new`].
+const HEADER_SIZE: usize = 0xE4;
+
+/// Console a ROM was made for, told apart by its header.
+///
+/// Only the GBA is emulated: the other systems are reported so that frontends can
+/// explain why a ROM doesn't start, and could pick another core for them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum System {
+    GameBoyAdvance,
+    GameBoy,
+    /// A Game Boy Color cartridge, also the ones that run on a Game Boy.
+    GameBoyColor,
+}
+
+impl System {
+    /// The Game Boy header is at 0x100, where a GBA ROM has code: a ROM with the Game
+    /// Boy logo there is one, everything else is assumed to be a GBA ROM.
+    #[must_use]
+    pub fn detect(data: &[u8]) -> Self {
+        if data.get(0x104..0x134) != Some(GB_LOGO.as_slice()) {
+            return Self::GameBoyAdvance;
+        }
+
+        // Bit 7 of the CGB flag is set by the games using the Game Boy Color features.
+        match data.get(0x143) {
+            Some(flag) if flag & 0x80 != 0 => Self::GameBoyColor,
+            _ => Self::GameBoy,
+        }
+    }
+}
+
+impl fmt::Display for System {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::GameBoyAdvance => "Game Boy Advance",
+            Self::GameBoy => "Game Boy",
+            Self::GameBoyColor => "Game Boy Color",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CartridgeError {
+    /// The ROM is for another console, see [`System::detect`].
+    UnsupportedSystem(System),
+    /// The ROM is shorter than the header, its length is given.
+    TooShort(usize),
+    /// The header checksum at 0xBD doesn't match the one computed.
+    Checksum { expected: u8, computed: u8 },
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedSystem(system) => {
+                write!(
+                    f,
+                    "{system} ROMs are not supported, only Game Boy Advance ones"
+                )
+            }
+            Self::TooShort(len) => write!(f, "{len} bytes are too few for a cartridge header"),
+            Self::Checksum { expected, computed } => {
+                write!(f, "Expected {expected} but got {computed}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {}
+
 #[allow(dead_code)] // FIXME: remove this `allow` when all member are used.
+#[derive(Clone, Debug)]
 pub struct CartridgeHeader {
     pub rom_entry_point: [u8; 4],
     pub nintendo_logo: [u8; 156],
@@ -23,7 +104,17 @@ impl CartridgeHeader {
     /// Create a new `CartridgeHeader` from a slice of bytes.
     ///
     /// # Errors
-    pub fn new(data: &[u8]) -> Result<Self, String> {
+    /// It fails if `data` isn't a GBA ROM or its header checksum is wrong.
+    pub fn new(data: &[u8]) -> Result<Self, CartridgeError> {
+        match System::detect(data) {
+            System::GameBoyAdvance => {}
+            system => return Err(CartridgeError::UnsupportedSystem(system)),
+        }
+
+        if data.len() < HEADER_SIZE {
+            return Err(CartridgeError::TooShort(data.len()));
+        }
+
         let rom_entry_point = Self::extract_rom_entry_point(data);
         let nintendo_logo = Self::extract_nintendo_logo(data);
         let game_title = Self::extract_game_title(data);
@@ -139,16 +230,26 @@ impl CartridgeHeader {
             .expect("extracting software version")
     }
 
-    /// Header checksum, required
-    fn extract_complement_check(data: &[u8]) -> Result<u8, String> {
-        let checksum_expected = data[0xBD];
-        let checksum = data[0xA0..0xBD]
+    /// Header checksum the BIOS expects at 0xBD, computed from the bytes 0xA0 to 0xBC of
+    /// `data`, which must hold a whole header.
+    #[must_use]
+    pub fn compute_complement_check(data: &[u8]) -> u8 {
+        data[0xA0..0xBD]
             .iter()
             .fold(0u8, |acc, &item| acc.wrapping_sub(item))
-            .wrapping_sub(0x19);
+            .wrapping_sub(0x19)
+    }
+
+    /// Header checksum, required
+    fn extract_complement_check(data: &[u8]) -> Result<u8, CartridgeError> {
+        let checksum_expected = data[0xBD];
+        let checksum = Self::compute_complement_check(data);
 
         if checksum != checksum_expected {
-            return Err(format!("Expected {checksum_expected} but got {checksum}"));
+            return Err(CartridgeError::Checksum {
+                expected: checksum_expected,
+                computed: checksum,
+            });
         }
 
         Ok(checksum)
@@ -218,3 +319,42 @@ impl CartridgeHeader {
             .expect("extracting joybus mode entry point")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn gb_rom(cgb_flag: u8) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x104..0x134].copy_from_slice(&GB_LOGO);
+        rom[0x143] = cgb_flag;
+        rom
+    }
+
+    #[test]
+    fn game_boy_roms_are_rejected() {
+        assert_eq!(System::detect(&gb_rom(0x00)), System::GameBoy);
+        assert_eq!(System::detect(&gb_rom(0x80)), System::GameBoyColor);
+        assert_eq!(System::detect(&gb_rom(0xC0)), System::GameBoyColor);
+        assert_eq!(System::detect(&[0; 0x200]), System::GameBoyAdvance);
+        assert_eq!(System::detect(&[]), System::GameBoyAdvance);
+
+        assert_eq!(
+            CartridgeHeader::new(&gb_rom(0xC0)).err(),
+            Some(CartridgeError::UnsupportedSystem(System::GameBoyColor))
+        );
+        assert_eq!(
+            CartridgeHeader::new(&[0; 0x10]).err(),
+            Some(CartridgeError::TooShort(0x10))
+        );
+        assert_eq!(
+            CartridgeHeader::new(&gb_rom(0x00))
+                .err()
+                .unwrap()
+                .to_string(),
+            "Game Boy ROMs are not supported, only Game Boy Advance ones"
+        );
+    }
+}
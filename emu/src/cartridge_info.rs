@@ -0,0 +1,147 @@
+//! Everything known about the inserted cartridge, for a "game properties" view: what
+//! its header and its ROM tell, and what the emulated cartridge actually has.
+
+use std::fmt;
+
+use crate::cartridge_header::CartridgeHeader;
+use crate::cpu::hardware::flash::FlashSize;
+
+/// Backup memory a game saves to, told by the ID string its save library leaves in the
+/// ROM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveType {
+    /// No ID string: the game doesn't save, or uses passwords.
+    None,
+    Sram,
+    Eeprom,
+    Flash(FlashSize),
+}
+
+impl SaveType {
+    #[must_use]
+    pub fn detect(rom: &[u8]) -> Self {
+        let contains = |id: &[u8]| rom.windows(id.len()).any(|window| window == id);
+
+        FlashSize::detect(rom).map_or_else(
+            || {
+                if contains(b"EEPROM_V") {
+                    Self::Eeprom
+                } else if contains(b"SRAM_V") || contains(b"SRAM_F_V") {
+                    Self::Sram
+                } else {
+                    Self::None
+                }
+            },
+            Self::Flash,
+        )
+    }
+}
+
+impl fmt::Display for SaveType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::None => "none",
+            Self::Sram => "SRAM",
+            Self::Eeprom => "EEPROM",
+            Self::Flash(FlashSize::Flash64K) => "Flash 64K",
+            Self::Flash(FlashSize::Flash128K) => "Flash 128K",
+        })
+    }
+}
+
+/// Add-on hardware a cartridge is built with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CartridgeHardware {
+    Rtc,
+    Rumble,
+    Gyro,
+    Tilt,
+    Solar,
+}
+
+impl CartridgeHardware {
+    /// The hardware told by the first letter of the game code, plus the clock of the
+    /// games that have the ID string of the RTC library in their ROM.
+    #[must_use]
+    pub fn detect(rom: &[u8], game_code: &str) -> Vec<Self> {
+        let mut hardware = match game_code.chars().next() {
+            Some('K') => vec![Self::Tilt],
+            Some('R') => vec![Self::Gyro, Self::Rumble],
+            Some('U') => vec![Self::Rtc, Self::Solar],
+            Some('V') => vec![Self::Rumble],
+            _ => Vec::new(),
+        };
+
+        let id = b"SIIRTC_V";
+        if !hardware.contains(&Self::Rtc) && rom.windows(id.len()).any(|window| window == id) {
+            hardware.push(Self::Rtc);
+        }
+
+        hardware
+    }
+}
+
+impl fmt::Display for CartridgeHardware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Rtc => "real-time clock",
+            Self::Rumble => "rumble",
+            Self::Gyro => "gyro sensor",
+            Self::Tilt => "tilt sensor",
+            Self::Solar => "solar sensor",
+        })
+    }
+}
+
+/// See [`Gba::cartridge_info`](crate::gba::Gba::cartridge_info).
+#[derive(Clone, Debug)]
+pub struct CartridgeInfo {
+    pub header: CartridgeHeader,
+    /// Size of the ROM in bytes.
+    pub rom_size: usize,
+    /// Whether the checksum at 0xBD matches the header, the BIOS refuses to boot if not.
+    pub header_checksum_valid: bool,
+    /// Whether 0xB2 holds the fixed value 0x96.
+    pub fixed_value_valid: bool,
+    pub save_type: SaveType,
+    /// Hardware the game expects, from its ROM.
+    pub hardware: Vec<CartridgeHardware>,
+    /// Flash chip of the emulated cartridge, `None` if the game saves to another memory
+    /// or not at all.
+    pub flash: Option<FlashSize>,
+    /// Names of the peripherals plugged in the emulated cartridge.
+    pub peripherals: Vec<&'static str>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_type_from_the_rom() {
+        assert_eq!(SaveType::detect(b"..EEPROM_V124.."), SaveType::Eeprom);
+        assert_eq!(SaveType::detect(b"..SRAM_F_V103.."), SaveType::Sram);
+        assert_eq!(
+            SaveType::detect(b"..FLASH1M_V103.."),
+            SaveType::Flash(FlashSize::Flash128K)
+        );
+        assert_eq!(SaveType::detect(&[0; 0x100]), SaveType::None);
+        assert_eq!(
+            SaveType::Flash(FlashSize::Flash64K).to_string(),
+            "Flash 64K"
+        );
+    }
+
+    #[test]
+    fn hardware_from_the_game_code() {
+        assert_eq!(
+            CartridgeHardware::detect(&[], "U3IE"),
+            [CartridgeHardware::Rtc, CartridgeHardware::Solar]
+        );
+        assert_eq!(
+            CartridgeHardware::detect(b"..SIIRTC_V001..", "AXVE"),
+            [CartridgeHardware::Rtc]
+        );
+        assert!(CartridgeHardware::detect(&[], "BPEE").is_empty());
+    }
+}
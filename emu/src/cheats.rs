@@ -0,0 +1,203 @@
+//! Cheat codes: lists of memory writes, some of them conditional, run at the start of
+//! every Vblank while enabled.
+//!
+//! The cheat devices wrote the codes into the game from a hook in its code. The emulator
+//! doesn't patch the game: every format is parsed into the same [`CheatOp`]s, run
+//! through the debug writes of the bus (see [`memory_edit`](crate::memory_edit)) at the
+//! same time as the frozen values.
+//!
+//! Formats: [`codebreaker`].
+
+use std::fmt;
+
+use crate::bus::Bus;
+use crate::memory_edit::{self, EditValue};
+
+pub mod codebreaker;
+
+/// Address of KEYINPUT, 0 for the pressed keys.
+const KEYINPUT: u32 = 0x0400_0130;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheatFormat {
+    CodeBreaker,
+}
+
+impl fmt::Display for CheatFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CodeBreaker => write!(f, "CodeBreaker"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cheat {
+    pub description: String,
+    pub enabled: bool,
+    pub format: CheatFormat,
+    /// The codes as entered.
+    pub codes: String,
+    ops: Vec<CheatOp>,
+    /// Checksum of the game the codes were made for, see [`Self::made_for`].
+    game_checksum: Option<u16>,
+}
+
+impl Cheat {
+    /// Whether the codes were made for `rom`. Only the `CodeBreaker` master codes name the
+    /// game, the others are assumed to fit.
+    #[must_use]
+    pub fn made_for(&self, rom: &[u8]) -> bool {
+        self.game_checksum
+            .is_none_or(|checksum| checksum == codebreaker::game_checksum(rom))
+    }
+
+    /// Runs the codes, on every Vblank.
+    pub(crate) fn apply(&self, bus: &mut Bus) {
+        let mut skip_next = false;
+        for op in &self.ops {
+            if std::mem::take(&mut skip_next) {
+                continue;
+            }
+
+            match *op {
+                CheatOp::Write(address, value) => write(bus, address, value),
+                CheatOp::Or(address, operand) => {
+                    let value = read_half_word(bus, address) | operand;
+                    write(bus, address, EditValue::HalfWord(value));
+                }
+                CheatOp::And(address, operand) => {
+                    let value = read_half_word(bus, address) & operand;
+                    write(bus, address, EditValue::HalfWord(value));
+                }
+                CheatOp::Add(address, operand) => {
+                    let value = read_half_word(bus, address).wrapping_add(operand);
+                    write(bus, address, EditValue::HalfWord(value));
+                }
+                CheatOp::Slide {
+                    address,
+                    value,
+                    count,
+                    address_step,
+                    value_step,
+                } => {
+                    let (mut address, mut value) = (address, value);
+                    for _ in 0..count {
+                        write(bus, address, EditValue::HalfWord(value));
+                        address = address.wrapping_add(address_step.into());
+                        value = value.wrapping_add(value_step);
+                    }
+                }
+                CheatOp::Bytes { address, ref bytes } => {
+                    for (offset, byte) in (0..).zip(bytes) {
+                        write(bus, address.wrapping_add(offset), EditValue::Byte(*byte));
+                    }
+                }
+                CheatOp::If {
+                    address,
+                    condition,
+                    operand,
+                } => skip_next = !condition.holds(read_half_word(bus, address), operand),
+            }
+        }
+    }
+}
+
+/// What the codes do, whatever their format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum CheatOp {
+    Write(u32, EditValue),
+    /// ORs the half-word at the address with the operand.
+    Or(u32, u16),
+    And(u32, u16),
+    Add(u32, u16),
+    /// Writes `count` half-words, the address and the value moving on by their steps.
+    Slide {
+        address: u32,
+        value: u16,
+        count: u16,
+        address_step: u16,
+        value_step: u16,
+    },
+    Bytes {
+        address: u32,
+        bytes: Vec<u8>,
+    },
+    /// Skips the next operation unless the half-word at `address` meets the condition.
+    If {
+        address: u32,
+        condition: Condition,
+        operand: u16,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Condition {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+    /// One of the bits of the operand is set.
+    AnySet,
+    /// None of the bits of the operand is set, as the pressed keys in KEYINPUT.
+    NoneSet,
+}
+
+impl Condition {
+    const fn holds(self, value: u16, operand: u16) -> bool {
+        match self {
+            Self::Equal => value == operand,
+            Self::NotEqual => value != operand,
+            Self::Greater => value > operand,
+            Self::Less => value < operand,
+            Self::AnySet => value & operand != 0,
+            Self::NoneSet => value & operand == 0,
+        }
+    }
+}
+
+/// Writes to read-only memory are dropped: the codes patching the game's ROM aren't
+/// supported.
+fn write(bus: &mut Bus, address: u32, value: EditValue) {
+    if memory_edit::check(address, value).is_ok() {
+        // Can't fail once checked.
+        let _ = bus.debug_write(address, value);
+    }
+}
+
+fn read_half_word(bus: &Bus, address: u32) -> u16 {
+    u16::from_le_bytes([bus.read_raw(address), bus.read_raw(address.wrapping_add(1))])
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheatErrorKind {
+    /// The line isn't a code of the format.
+    Syntax,
+    /// The code type isn't supported.
+    Unsupported,
+    /// The code goes on the following lines, which are missing.
+    Incomplete,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CheatError {
+    /// Line of the code, from 1.
+    pub line: usize,
+    pub kind: CheatErrorKind,
+}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line = self.line;
+        match self.kind {
+            CheatErrorKind::Syntax => write!(f, "line {line}: not a code"),
+            CheatErrorKind::Unsupported => write!(f, "line {line}: unsupported code type"),
+            CheatErrorKind::Incomplete => write!(
+                f,
+                "line {line}: the following lines of the code are missing"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CheatError {}
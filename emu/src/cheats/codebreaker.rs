@@ -0,0 +1,367 @@
+//! `CodeBreaker` codes, one `XXXXXXXX YYYY` per line: the top digit of `XXXXXXXX` is the
+//! code type, the others the address in most of them.
+//!
+//! ```text
+//! 0000CCCC 000Y  master code, CCCC is the checksum of the game, see `game_checksum`
+//! 1AAAAAAA YYYY  master code, the hook in the game's ROM
+//! 2AAAAAAA YYYY  ORs the half-word at A with Y
+//! 3AAAAAAA 00YY  writes the byte Y at A
+//! 4AAAAAAA YYYY  slide: writes Y at A, C times,
+//! SSSSCCCC VVVV    the address moving on by S bytes and the value by V
+//! 5AAAAAAA CCCC  super code: writes the 2 * C bytes of the following lines at A, 6 per
+//!                  line, in the order they are written
+//! 6AAAAAAA YYYY  ANDs the half-word at A with Y
+//! 7AAAAAAA YYYY  runs the next code if the half-word at A equals Y
+//! 8AAAAAAA YYYY  writes the half-word Y at A
+//! 9XXXXXXX XXXX  encrypted master code, the following codes are encrypted
+//! AAAAAAAA YYYY  runs the next code if the half-word at A isn't Y
+//! BAAAAAAA YYYY  ... if it's greater than Y
+//! CAAAAAAA YYYY  ... if it's less than Y
+//! D0000020 YYYY  ... if all the keys of Y (as in KEYINPUT) are pressed
+//! EAAAAAAA YYYY  adds Y to the half-word at A
+//! FAAAAAAA YYYY  runs the next code if the half-word at A and Y have a bit in common
+//! ```
+//!
+//! The hook is where the device patched the game to run the codes: the emulator runs
+//! them at every Vblank instead, the hook is skipped.
+
+use super::{Cheat, CheatError, CheatErrorKind, CheatFormat, CheatOp, Condition, KEYINPUT};
+use crate::memory_edit::EditValue;
+
+/// Bytes of the ROM covered by the checksum of the master code.
+const CHECKSUM_BYTES: usize = 0x1_0000;
+
+/// Parses the codes, one per line. Blank lines are skipped.
+///
+/// # Errors
+/// It fails on the first line that isn't a supported code.
+pub fn parse(description: impl Into<String>, codes: &str) -> Result<Cheat, CheatError> {
+    let mut lines = codes
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let line_number = index + 1;
+            parse_line(line)
+                .map(|(op1, op2)| (line_number, op1, op2))
+                .ok_or(CheatError {
+                    line: line_number,
+                    kind: CheatErrorKind::Syntax,
+                })
+        });
+    let mut encryption: Option<Encryption> = None;
+    let mut next_code = |encryption: &Option<Encryption>| {
+        lines.next().transpose().map(|code| {
+            code.map(|(line, op1, op2)| {
+                encryption.as_ref().map_or((line, op1, op2), |encryption| {
+                    let (op1, op2) = encryption.decrypt(op1, op2);
+                    (line, op1, op2)
+                })
+            })
+        })
+    };
+
+    let mut ops = Vec::new();
+    let mut game_checksum = None;
+    while let Some((line, op1, op2)) = next_code(&encryption)? {
+        let address = op1 & 0x0FFF_FFFF;
+        let incomplete = CheatError {
+            line,
+            kind: CheatErrorKind::Incomplete,
+        };
+        let condition = |condition| CheatOp::If {
+            address,
+            condition,
+            operand: op2,
+        };
+
+        let op = match op1 >> 28 {
+            0x0 => {
+                game_checksum = Some(op1 as u16);
+                continue;
+            }
+            // The codes run at every Vblank, they don't need the hook.
+            0x1 => continue,
+            0x2 => CheatOp::Or(address, op2),
+            0x3 => CheatOp::Write(address, EditValue::Byte(op2 as u8)),
+            0x4 => {
+                let (_, step, value_step) = next_code(&encryption)?.ok_or(incomplete)?;
+                CheatOp::Slide {
+                    address,
+                    value: op2,
+                    count: step as u16,
+                    address_step: (step >> 16) as u16,
+                    value_step,
+                }
+            }
+            0x5 => {
+                let len = usize::from(op2) * 2;
+                let mut bytes = Vec::with_capacity(len);
+                while bytes.len() < len {
+                    let (_, data, data_end) = next_code(&encryption)?.ok_or(incomplete)?;
+                    bytes.extend(data.to_be_bytes().into_iter().chain(data_end.to_be_bytes()));
+                }
+                bytes.truncate(len);
+                CheatOp::Bytes { address, bytes }
+            }
+            0x6 => CheatOp::And(address, op2),
+            0x7 => condition(Condition::Equal),
+            0x8 => CheatOp::Write(address, EditValue::HalfWord(op2)),
+            0x9 => {
+                encryption = Some(Encryption::new(op1, op2));
+                continue;
+            }
+            0xA => condition(Condition::NotEqual),
+            0xB => condition(Condition::Greater),
+            0xC => condition(Condition::Less),
+            0xD if address == 0x20 => CheatOp::If {
+                address: KEYINPUT,
+                condition: Condition::NoneSet,
+                operand: op2,
+            },
+            0xE => CheatOp::Add(address, op2),
+            0xF => condition(Condition::AnySet),
+            _ => {
+                return Err(CheatError {
+                    line,
+                    kind: CheatErrorKind::Unsupported,
+                })
+            }
+        };
+        ops.push(op);
+    }
+
+    Ok(Cheat {
+        description: description.into(),
+        enabled: true,
+        format: CheatFormat::CodeBreaker,
+        codes: codes.to_owned(),
+        ops,
+        game_checksum,
+    })
+}
+
+/// The address part and the value part of a code.
+fn parse_line(line: &str) -> Option<(u32, u16)> {
+    let digits: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.len() != 12 || !digits.is_ascii() {
+        return None;
+    }
+
+    Some((
+        u32::from_str_radix(&digits[..8], 16).ok()?,
+        u16::from_str_radix(&digits[8..], 16).ok()?,
+    ))
+}
+
+/// The checksum of the game in the first master code: a CRC-16 (polynomial 0x1021,
+/// starting at 0xFFFF) of the first 64 KB of the ROM, 4 bytes at a time.
+#[must_use]
+pub fn game_checksum(rom: &[u8]) -> u16 {
+    let len = rom.len().min(CHECKSUM_BYTES) & !3;
+
+    rom[..len].iter().fold(0xFFFF, |crc: u16, byte| {
+        (0..8).fold(crc ^ (u16::from(*byte) << 8), |crc, _| {
+            if crc & 0x8000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x1021
+            }
+        })
+    })
+}
+
+/// The encryption of the codes following a `9` master code: the 48 bits of each code
+/// are shuffled by a table and mixed with four seeds, all derived from the master code.
+struct Encryption {
+    /// Bit swapped with each bit of the code.
+    table: [u8; 0x30],
+    seeds: [u32; 4],
+    master: u32,
+}
+
+impl Encryption {
+    fn new(op1: u32, op2: u16) -> Self {
+        let mut rng = Rng(u32::from(op2 & 0xFF) ^ 0x1111);
+        let mut table: [u8; 0x30] = std::array::from_fn(|bit| bit as u8);
+        for _ in 0..0x50 {
+            let a = rng.index(table.len());
+            let b = rng.index(table.len());
+            table.swap(a, b);
+        }
+
+        let mut seeds = [0; 4];
+        rng = Rng(0x4EFA_D1C3);
+        for _ in 0..(op1 >> 24) & 0xF {
+            rng = Rng(rng.next());
+        }
+        seeds[2] = rng.next();
+        seeds[3] = rng.next();
+
+        rng = Rng(u32::from(op2 >> 8) ^ 0xF254);
+        for _ in 0..op2 >> 8 {
+            rng = Rng(rng.next());
+        }
+        seeds[0] = rng.next();
+        seeds[1] = rng.next();
+
+        Self {
+            table,
+            seeds,
+            master: op1,
+        }
+    }
+
+    fn decrypt(&self, op1: u32, op2: u16) -> (u32, u16) {
+        let mut code = to_bytes(op1, op2);
+        for (bit, &other) in self.table.iter().enumerate().rev() {
+            let other = usize::from(other);
+            let a = code[bit >> 3] >> (bit & 7) & 1;
+            let b = code[other >> 3] >> (other & 7) & 1;
+            code[bit >> 3] = code[bit >> 3] & !(1 << (bit & 7)) | b << (bit & 7);
+            code[other >> 3] = code[other >> 3] & !(1 << (other & 7)) | a << (other & 7);
+        }
+
+        let mut code = xor_seeds(code, self.seeds[0], self.seeds[1]);
+        let [_, _, high, low] = self.master.to_be_bytes();
+        for i in 0..5 {
+            code[i] ^= high ^ code[i + 1];
+        }
+        code[5] ^= high;
+        for i in (1..6).rev() {
+            code[i] ^= low ^ code[i - 1];
+        }
+        code[0] ^= low;
+
+        from_bytes(xor_seeds(code, self.seeds[2], self.seeds[3]))
+    }
+}
+
+/// The 48 bits of a code, big endian.
+const fn to_bytes(op1: u32, op2: u16) -> [u8; 6] {
+    let [a0, a1, a2, a3] = op1.to_be_bytes();
+    let [v0, v1] = op2.to_be_bytes();
+
+    [a0, a1, a2, a3, v0, v1]
+}
+
+const fn from_bytes(code: [u8; 6]) -> (u32, u16) {
+    let [op1 @ .., v0, v1] = code;
+
+    (u32::from_be_bytes(op1), u16::from_be_bytes([v0, v1]))
+}
+
+const fn xor_seeds(code: [u8; 6], seed_op1: u32, seed_op2: u32) -> [u8; 6] {
+    let (op1, op2) = from_bytes(code);
+
+    to_bytes(op1 ^ seed_op1, op2 ^ seed_op2 as u16)
+}
+
+/// The generator of the encryption: three steps of a linear congruential generator
+/// mixed into one 32 bits value.
+struct Rng(u32);
+
+impl Rng {
+    const fn step(state: u32) -> u32 {
+        state.wrapping_mul(0x41C6_4E6D).wrapping_add(0x3039)
+    }
+
+    const fn next(&mut self) -> u32 {
+        let first = Self::step(self.0);
+        let second = Self::step(first);
+        let third = Self::step(second);
+        self.0 = third;
+
+        (first << 14) & 0xC000_0000 | (second >> 1) & 0x3FFF_8000 | (third >> 16) & 0x7FFF
+    }
+
+    const fn index(&mut self, len: usize) -> usize {
+        self.next() as usize % len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parses_the_plain_codes() {
+        let cheat = parse(
+            "Infinite lives",
+            "00001234 000A\n\
+             1000028C 0007\n\
+             \n\
+             7200 1000 0001\n\
+             82001000 0063\n\
+             42002000 0001\n\
+             00040003 0002\n\
+             52003000 0003\n\
+             11223344 5566\n",
+        )
+        .unwrap();
+
+        assert_eq!(cheat.game_checksum, Some(0x1234));
+        assert_eq!(
+            cheat.ops,
+            [
+                CheatOp::If {
+                    address: 0x0200_1000,
+                    condition: Condition::Equal,
+                    operand: 1,
+                },
+                CheatOp::Write(0x0200_1000, EditValue::HalfWord(0x63)),
+                CheatOp::Slide {
+                    address: 0x0200_2000,
+                    value: 1,
+                    count: 3,
+                    address_step: 4,
+                    value_step: 2,
+                },
+                CheatOp::Bytes {
+                    address: 0x0200_3000,
+                    bytes: vec![0x11, 0x22, 0x33, 0x44, 0x55, 0x66],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_the_line_of_the_errors() {
+        let error = |codes| parse("", codes).unwrap_err();
+
+        assert_eq!(
+            error("82001000 0063\n8200100 0063"),
+            CheatError {
+                line: 2,
+                kind: CheatErrorKind::Syntax,
+            }
+        );
+        assert_eq!(error("\n42002000 0001").line, 2);
+        assert_eq!(error("42002000 0001").kind, CheatErrorKind::Incomplete);
+        assert_eq!(error("D0000010 0001").kind, CheatErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn encryption_depends_on_the_master_code() {
+        let code = (0x8200_1000, 0x0063);
+        let first = Encryption::new(0x9123_ABCD, 0x4567).decrypt(code.0, code.1);
+        let second = Encryption::new(0x9123_ABCD, 0x4568).decrypt(code.0, code.1);
+
+        assert_ne!(first, code);
+        assert_ne!(first, second);
+        assert_eq!(
+            first,
+            Encryption::new(0x9123_ABCD, 0x4567).decrypt(code.0, code.1)
+        );
+    }
+
+    #[test]
+    fn game_checksum_is_a_crc16() {
+        // CRC-16/CCITT-FALSE, the trailing byte that isn't part of a word is left out.
+        assert_eq!(game_checksum(b"12345678"), 0xA12B);
+        assert_eq!(game_checksum(b"123456789"), 0xA12B);
+        assert_eq!(game_checksum(b"123"), 0xFFFF);
+    }
+}
@@ -0,0 +1,501 @@
+//! Emulated time, counted in bus cycles since power-on.
+//!
+//! The same counter timestamps the I/O trace and the execution traps, so that events
+//! can be placed on a single timeline and compared with the host clock.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Cycles per second of the bus and the CPU.
+pub const CPU_FREQUENCY: u64 = 16_777_216;
+
+/// A frame lasts 228 scanlines of 1232 cycles.
+pub const CYCLES_PER_FRAME: u64 = 280_896;
+
+/// Emulated time `cycles` last on hardware.
+#[must_use]
+pub fn cycles_to_duration(cycles: u128) -> Duration {
+    let nanos = cycles * 1_000_000_000 / u128::from(CPU_FREQUENCY);
+
+    Duration::from_nanos(nanos.try_into().unwrap_or(u64::MAX))
+}
+
+/// Frames, complete or not, drawn in `cycles`.
+#[must_use]
+pub fn cycles_to_frames(cycles: u128) -> f64 {
+    cycles as f64 / CYCLES_PER_FRAME as f64
+}
+
+/// Emulated cycle count read at a known host instant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClockSample {
+    pub cycles: u128,
+    pub host: Instant,
+}
+
+impl ClockSample {
+    /// Emulation speed between `earlier` and this sample compared to hardware (1.0 is
+    /// full speed). `None` if no host time passed or the cycles went back, as they do
+    /// when a save-state is loaded or the console is reset.
+    #[must_use]
+    pub fn speed_since(&self, earlier: &Self) -> Option<f64> {
+        let host = self.host.checked_duration_since(earlier.host)?;
+        let cycles = self.cycles.checked_sub(earlier.cycles)?;
+
+        if host.is_zero() {
+            return None;
+        }
+
+        Some(cycles_to_duration(cycles).as_secs_f64() / host.as_secs_f64())
+    }
+}
+
+/// Falling further behind than this (a breakpoint, a slow host) restarts the pacing
+/// instead of running as fast as possible to catch up.
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Frames per second of the hardware, about 59.73.
+pub const FRAME_RATE: f64 = CPU_FREQUENCY as f64 / CYCLES_PER_FRAME as f64;
+
+/// Displays refreshing within this fraction of [`FRAME_RATE`] are followed, see
+/// [`PacingStrategy::Display`].
+const MAX_DISPLAY_DEVIATION: f64 = 0.02;
+
+/// What the [`Pacer`] keeps the emulation in time with.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PacingStrategy {
+    /// The host clock, scaled by the speed.
+    #[default]
+    Timer,
+    /// The refresh of the display, for frontends presenting with vsync: a 60 Hz display
+    /// gets exactly a frame per refresh, without the stutter of the 0.45% the hardware
+    /// is slower by. Displays too far from [`FRAME_RATE`] fall back to the timer.
+    Display { refresh_rate: f64 },
+    /// The audio output, for frontends feeding a device at the hardware rate: the
+    /// emulation waits while more than `target` is queued, see
+    /// [`Pacer::report_audio_queued`]. Paced by the timer at full speed until the first
+    /// report, the speed is ignored.
+    Audio { target: Duration },
+    /// As fast as possible, like a `None` speed.
+    FreeRun,
+}
+
+impl fmt::Display for PacingStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timer => write!(f, "timer"),
+            Self::Display { refresh_rate } => write!(f, "display at {refresh_rate:.2} Hz"),
+            Self::Audio { target } => write!(f, "audio, {} ms queued", target.as_millis()),
+            Self::FreeRun => write!(f, "unlimited"),
+        }
+    }
+}
+
+/// How the pacing went since the strategy was chosen.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PacerTelemetry {
+    pub strategy: PacingStrategy,
+    /// Speed compared to hardware the emulation is held at, `None` when it's unlimited
+    /// or follows the audio.
+    pub target_speed: Option<f64>,
+    /// Host time the pacer asked to wait.
+    pub waited: Duration,
+    /// Times the emulation fell too far behind and the pacing restarted.
+    pub resyncs: u64,
+    /// Audio queued at the last report.
+    pub audio_queued: Option<Duration>,
+}
+
+/// Pacing governor: keeps the emulation at a fraction or a multiple of the hardware
+/// speed, for example 0.25 to watch raster effects in slow motion.
+///
+/// The frontend runs the core for a while, then sleeps for [`Self::delay`]. What the
+/// emulation keeps in time with is chosen by the [`PacingStrategy`], at any time.
+#[derive(Clone, Copy, Debug)]
+pub struct Pacer {
+    /// Speed compared to hardware, `None` runs as fast as possible.
+    speed: Option<f64>,
+    strategy: PacingStrategy,
+    origin: Option<ClockSample>,
+    /// Audio queued by the frontend, and when it was reported.
+    audio_queued: Option<(Duration, Instant)>,
+    waited: Duration,
+    resyncs: u64,
+}
+
+impl Default for Pacer {
+    fn default() -> Self {
+        Self::new(Some(1.0))
+    }
+}
+
+impl Pacer {
+    #[must_use]
+    pub const fn new(speed: Option<f64>) -> Self {
+        Self {
+            speed,
+            strategy: PacingStrategy::Timer,
+            origin: None,
+            audio_queued: None,
+            waited: Duration::ZERO,
+            resyncs: 0,
+        }
+    }
+
+    #[must_use]
+    pub const fn strategy(&self) -> PacingStrategy {
+        self.strategy
+    }
+
+    /// Restarts the pacing and its telemetry.
+    pub const fn set_strategy(&mut self, strategy: PacingStrategy) {
+        self.strategy = strategy;
+        self.audio_queued = None;
+        self.waited = Duration::ZERO;
+        self.resyncs = 0;
+        self.restart();
+    }
+
+    /// Audio the frontend queued and the device didn't play yet, reported at `host` for
+    /// [`PacingStrategy::Audio`].
+    pub const fn report_audio_queued(&mut self, queued: Duration, host: Instant) {
+        self.audio_queued = Some((queued, host));
+    }
+
+    #[must_use]
+    pub fn telemetry(&self) -> PacerTelemetry {
+        PacerTelemetry {
+            strategy: self.strategy,
+            target_speed: self.target_speed(),
+            waited: self.waited,
+            resyncs: self.resyncs,
+            audio_queued: self.audio_queued.map(|(queued, _)| queued),
+        }
+    }
+
+    /// Speed the timer holds the emulation at, `None` when it's not paced by the timer.
+    fn target_speed(&self) -> Option<f64> {
+        let speed = self.speed.filter(|speed| *speed > 0.0);
+        match self.strategy {
+            PacingStrategy::Timer => speed,
+            PacingStrategy::Display { refresh_rate } => {
+                let ratio = refresh_rate / FRAME_RATE;
+                if (ratio - 1.0).abs() <= MAX_DISPLAY_DEVIATION {
+                    speed.map(|speed| speed * ratio)
+                } else {
+                    speed
+                }
+            }
+            PacingStrategy::Audio { .. } => self.audio_queued.is_none().then_some(1.0),
+            PacingStrategy::FreeRun => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn speed(&self) -> Option<f64> {
+        self.speed
+    }
+
+    pub const fn set_speed(&mut self, speed: Option<f64>) {
+        self.speed = speed;
+        self.restart();
+    }
+
+    /// Forgets the time elapsed so far, to be called when the emulation resumes after a
+    /// pause so that it doesn't try to make up for it.
+    pub const fn restart(&mut self) {
+        self.origin = None;
+    }
+
+    /// How long to wait at `now`, following the [`PacingStrategy`].
+    pub fn delay(&mut self, now: ClockSample) -> Duration {
+        let delay = match (self.strategy, self.audio_queued) {
+            (PacingStrategy::Audio { target }, Some((queued, reported))) => {
+                let played = now.host.saturating_duration_since(reported);
+                queued.saturating_sub(played).saturating_sub(target)
+            }
+            _ => self.timer_delay(now),
+        };
+        self.waited += delay;
+
+        delay
+    }
+
+    /// How long to wait at `now` for the emulated time to match the host time scaled by
+    /// the target speed.
+    fn timer_delay(&mut self, now: ClockSample) -> Duration {
+        let Some(speed) = self.target_speed() else {
+            return Duration::ZERO;
+        };
+
+        // Loading a save-state or resetting takes the cycles back.
+        let origin = match self.origin {
+            Some(origin) if origin.cycles <= now.cycles => origin,
+            _ => *self.origin.insert(now),
+        };
+
+        let emulated = cycles_to_duration(now.cycles - origin.cycles);
+        let target = emulated.div_f64(speed);
+        let elapsed = now.host.saturating_duration_since(origin.host);
+
+        if elapsed > target + MAX_LAG {
+            self.origin = Some(now);
+            self.resyncs += 1;
+            return Duration::ZERO;
+        }
+
+        target.saturating_sub(elapsed)
+    }
+
+    /// Host time an emulated frame lasts at the current speed, to the nanosecond. `None`
+    /// when running as fast as possible or following the audio.
+    #[must_use]
+    pub fn frame_duration(&self) -> Option<Duration> {
+        let speed = self.target_speed()?;
+        let nanos = CYCLES_PER_FRAME as f64 * 1e9 / CPU_FREQUENCY as f64 / speed;
+
+        Some(Duration::from_nanos(nanos.round() as u64))
+    }
+}
+
+/// Completed frames whose host timing [`FramePacing`] keeps, about two seconds.
+const PACING_WINDOW: usize = 120;
+
+/// Host time between the last completed frames.
+///
+/// Frontends presenting them on variable refresh rate displays get the interval to
+/// schedule the next one and how much it varies. Compare with
+/// [`Pacer::frame_duration`] for the target.
+#[derive(Clone, Debug, Default)]
+pub struct FramePacing {
+    frame: u64,
+    last: Option<Instant>,
+    intervals: VecDeque<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FramePacingStats {
+    /// Intervals the statistics are computed on.
+    pub frames: usize,
+    pub mean: Duration,
+    /// Standard deviation of the intervals.
+    pub jitter: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl FramePacing {
+    /// Last frame count recorded.
+    #[must_use]
+    pub const fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Records that the frame count is `frame` at `host`, only changes of the count
+    /// are timed.
+    pub fn record(&mut self, frame: u64, host: Instant) {
+        if frame == self.frame && self.last.is_some() {
+            return;
+        }
+
+        // A frame count going back (reset, save-state) isn't a frame interval.
+        if let Some(last) = self.last.filter(|_| frame > self.frame) {
+            if self.intervals.len() == PACING_WINDOW {
+                self.intervals.pop_front();
+            }
+            self.intervals
+                .push_back(host.saturating_duration_since(last));
+        }
+
+        self.frame = frame;
+        self.last = Some(host);
+    }
+
+    /// Forgets the intervals measured so far, to be called when the emulation resumes
+    /// after a pause that would count as a long frame.
+    pub fn restart(&mut self) {
+        self.last = None;
+        self.intervals.clear();
+    }
+
+    /// `None` until two frames were completed since the last restart.
+    #[must_use]
+    pub fn stats(&self) -> Option<FramePacingStats> {
+        let frames = self.intervals.len();
+        if frames == 0 {
+            return None;
+        }
+
+        let nanos = self.intervals.iter().map(Duration::as_nanos);
+        let mean = nanos.clone().sum::<u128>() as f64 / frames as f64;
+        let variance = nanos
+            .map(|nanos| (nanos as f64 - mean).powi(2))
+            .sum::<f64>()
+            / frames as f64;
+
+        Some(FramePacingStats {
+            frames,
+            mean: Duration::from_nanos(mean.round() as u64),
+            jitter: Duration::from_nanos(variance.sqrt().round() as u64),
+            min: *self.intervals.iter().min()?,
+            max: *self.intervals.iter().max()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(
+            cycles_to_duration(CPU_FREQUENCY.into()),
+            Duration::from_secs(1)
+        );
+        assert_eq!(cycles_to_duration(16), Duration::from_nanos(953));
+        assert!(
+            (cycles_to_frames(u128::from(CYCLES_PER_FRAME) * 3 / 2) - 1.5).abs() < f64::EPSILON
+        );
+
+        let start = Instant::now();
+        let earlier = ClockSample {
+            cycles: 1000,
+            host: start,
+        };
+        let later = ClockSample {
+            cycles: 1000 + u128::from(CPU_FREQUENCY),
+            host: start + Duration::from_secs(2),
+        };
+        assert_eq!(later.speed_since(&earlier), Some(0.5));
+        assert_eq!(earlier.speed_since(&later), None);
+        assert_eq!(earlier.speed_since(&earlier), None);
+    }
+
+    #[test]
+    fn pacer_delays_to_match_speed() {
+        let start = Instant::now();
+        let sample = |cycles: u64, millis: u64| ClockSample {
+            cycles: cycles.into(),
+            host: start + Duration::from_millis(millis),
+        };
+
+        let mut pacer = Pacer::new(Some(0.25));
+        assert_eq!(pacer.delay(sample(1000, 0)), Duration::ZERO);
+        // A quarter of a second emulated lasts a second.
+        assert_eq!(
+            pacer.delay(sample(1000 + CPU_FREQUENCY / 4, 400)),
+            Duration::from_millis(600)
+        );
+
+        // Far behind: no burst to catch up.
+        assert_eq!(
+            pacer.delay(sample(1000 + CPU_FREQUENCY / 4, 2000)),
+            Duration::ZERO
+        );
+        assert_eq!(
+            pacer.delay(sample(1000 + CPU_FREQUENCY / 2, 2000)),
+            Duration::from_secs(1)
+        );
+
+        // Cycles going back restart the pacing.
+        assert_eq!(pacer.delay(sample(0, 2500)), Duration::ZERO);
+
+        pacer.set_speed(None);
+        assert_eq!(pacer.delay(sample(CPU_FREQUENCY, 2500)), Duration::ZERO);
+    }
+
+    #[test]
+    fn strategies() {
+        let start = Instant::now();
+        let sample = |cycles: u64, millis: u64| ClockSample {
+            cycles: cycles.into(),
+            host: start + Duration::from_millis(millis),
+        };
+
+        // A 60 Hz display is followed, a 144 Hz one isn't.
+        let mut pacer = Pacer::default();
+        pacer.set_strategy(PacingStrategy::Display { refresh_rate: 60.0 });
+        assert_eq!(
+            pacer.frame_duration(),
+            Some(Duration::from_nanos(16_666_667))
+        );
+        pacer.set_strategy(PacingStrategy::Display {
+            refresh_rate: 144.0,
+        });
+        assert_eq!(pacer.frame_duration(), Pacer::default().frame_duration());
+
+        // The timer paces until the audio queue is reported.
+        pacer.set_strategy(PacingStrategy::Audio {
+            target: Duration::from_millis(50),
+        });
+        assert_eq!(pacer.delay(sample(0, 0)), Duration::ZERO);
+        assert_eq!(
+            pacer.delay(sample(CPU_FREQUENCY / 4, 0)),
+            Duration::from_millis(250)
+        );
+        pacer.report_audio_queued(Duration::from_millis(80), start);
+        assert_eq!(
+            pacer.delay(sample(CPU_FREQUENCY / 4, 10)),
+            Duration::from_millis(20)
+        );
+        assert_eq!(pacer.delay(sample(CPU_FREQUENCY / 4, 40)), Duration::ZERO);
+        let telemetry = pacer.telemetry();
+        assert_eq!(telemetry.target_speed, None);
+        assert_eq!(telemetry.waited, Duration::from_millis(270));
+        assert_eq!(telemetry.audio_queued, Some(Duration::from_millis(80)));
+
+        pacer.set_strategy(PacingStrategy::FreeRun);
+        assert_eq!(pacer.delay(sample(CPU_FREQUENCY, 0)), Duration::ZERO);
+        assert_eq!(pacer.frame_duration(), None);
+        assert_eq!(pacer.telemetry().waited, Duration::ZERO);
+    }
+
+    #[test]
+    fn frame_duration() {
+        assert_eq!(
+            Pacer::default().frame_duration(),
+            Some(Duration::from_nanos(16_742_706))
+        );
+        assert_eq!(
+            Pacer::new(Some(0.5)).frame_duration(),
+            Some(Duration::from_nanos(33_485_413))
+        );
+        assert_eq!(Pacer::new(None).frame_duration(), None);
+    }
+
+    #[test]
+    fn frame_pacing_stats() {
+        let start = Instant::now();
+        let mut pacing = FramePacing::default();
+
+        pacing.record(0, start);
+        assert_eq!(pacing.stats(), None);
+
+        for (frame, millis) in [(1, 16), (1, 20), (2, 34), (3, 50), (4, 66)] {
+            pacing.record(frame, start + Duration::from_millis(millis));
+        }
+        assert_eq!(
+            pacing.stats(),
+            Some(FramePacingStats {
+                frames: 4,
+                mean: Duration::from_micros(16_500),
+                jitter: Duration::from_nanos(866_025),
+                min: Duration::from_millis(16),
+                max: Duration::from_millis(18),
+            })
+        );
+
+        // Back to an earlier frame: no interval.
+        pacing.record(0, start + Duration::from_millis(80));
+        assert_eq!(pacing.stats().unwrap().frames, 4);
+
+        pacing.restart();
+        assert_eq!(pacing.stats(), None);
+    }
+}
@@ -0,0 +1,325 @@
+//! Headless compatibility sweep: runs every ROM of a directory for a few frames and
+//! reports how far each one got, so that regressions show up in a single JSON diff.
+//!
+//! ```no_run
+//! use emu::compatibility::{sweep, SweepOptions};
+//!
+//! let bios = std::fs::read("gba_bios.bin").unwrap().try_into().unwrap();
+//! let report = sweep("roms".as_ref(), &bios, &SweepOptions::default()).unwrap();
+//! println!("{}", report.to_json());
+//! ```
+
+use std::{
+    any::Any,
+    fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use serde::Serialize;
+
+use crate::{
+    cartridge_header::{CartridgeError, CartridgeHeader},
+    config::EmuConfig,
+    gba::Gba,
+    save_state::crc32,
+};
+
+pub struct SweepOptions {
+    /// Frames to run every ROM for.
+    pub frames: u64,
+    /// Number of ROMs run at the same time, `0` uses every available core.
+    pub threads: usize,
+    /// Options every ROM is run with.
+    pub config: EmuConfig,
+}
+
+impl Default for SweepOptions {
+    fn default() -> Self {
+        Self {
+            frames: 600,
+            threads: 0,
+            config: EmuConfig::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RomStatus {
+    /// Every requested frame was emulated.
+    Completed,
+    /// The header can't be parsed, the ROM wasn't started.
+    InvalidHeader,
+    /// A ROM for another console, like the Game Boy, that wasn't started.
+    UnsupportedSystem,
+    /// The CPU executed from a region it never runs code from, see
+    /// [`crate::cpu::execution_trap::ExecutionTrap`].
+    Trapped,
+    /// The emulator panicked.
+    Panicked,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RomReport {
+    pub file: String,
+    pub title: Option<String>,
+    pub status: RomStatus,
+    /// Frames emulated before stopping.
+    pub frames: u64,
+    /// Description of the trap, the header error or the panic message.
+    pub error: Option<String>,
+    /// CRC-32 of the last completed frame, to spot rendering changes between runs.
+    pub frame_hash: String,
+    /// I/O addresses written by the game that no register is mapped to yet.
+    pub unmapped_io_writes: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompatibilityReport {
+    pub frames: u64,
+    pub roms: Vec<RomReport>,
+}
+
+impl CompatibilityReport {
+    /// # Panics
+    /// It never panics, the report only contains serializable values.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report is always serializable")
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(ToString::to_string)
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Runs `rom` for `frames` frames, stopping early on traps and panics.
+#[must_use]
+pub fn run_rom(
+    file: &str,
+    bios: &[u8; 0x4000],
+    rom: Vec<u8>,
+    frames: u64,
+    config: &EmuConfig,
+) -> RomReport {
+    let mut report = RomReport {
+        file: file.to_string(),
+        title: None,
+        status: RomStatus::Completed,
+        frames: 0,
+        error: None,
+        frame_hash: String::new(),
+        unmapped_io_writes: Vec::new(),
+    };
+
+    let header = panic::catch_unwind(|| CartridgeHeader::new(&rom))
+        .map_err(|payload| panic_message(payload.as_ref()));
+    let header = match header {
+        Ok(Ok(header)) => header,
+        Ok(Err(error)) => {
+            report.status = match error {
+                CartridgeError::UnsupportedSystem(_) => RomStatus::UnsupportedSystem,
+                _ => RomStatus::InvalidHeader,
+            };
+            report.error = Some(error.to_string());
+            return report;
+        }
+        Err(message) => {
+            report.status = RomStatus::InvalidHeader;
+            report.error = Some(message);
+            return report;
+        }
+    };
+    report.title = Some(header.game_title.trim_end_matches('\0').to_string());
+
+    let mut gba = Gba::with_config(header, *bios, rom, config.clone());
+    let output = gba.frame_output();
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        while output.frame_count() < frames {
+            gba.step();
+
+            if let Some(trap) = gba.cpu.take_execution_trap() {
+                return Err(trap);
+            }
+        }
+
+        Ok(())
+    }));
+
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(trap)) => {
+            report.status = RomStatus::Trapped;
+            report.error = Some(trap.to_string());
+        }
+        Err(payload) => {
+            report.status = RomStatus::Panicked;
+            report.error = Some(panic_message(payload.as_ref()));
+        }
+    }
+
+    report.frames = output.frame_count().min(frames);
+
+    let pixels = output
+        .load()
+        .iter()
+        .flatten()
+        .flat_map(|color| color.0.to_le_bytes())
+        .collect::<Vec<_>>();
+    report.frame_hash = format!("{:08x}", crc32(&pixels));
+
+    // The bus may be left in an inconsistent state after a panic, only read it.
+    report.unmapped_io_writes = gba
+        .cpu
+        .bus
+        .unmapped_io_writes()
+        .into_iter()
+        .map(|address| format!("0x{address:08X}"))
+        .collect();
+
+    report
+}
+
+/// Runs every `.gba` file of `dir`, in parallel, and collects the reports sorted by
+/// file name.
+///
+/// # Errors
+/// It fails if `dir` can't be listed. ROMs that can't be read are reported as
+/// [`RomStatus::InvalidHeader`].
+pub fn sweep(
+    dir: &Path,
+    bios: &[u8; 0x4000],
+    options: &SweepOptions,
+) -> io::Result<CompatibilityReport> {
+    let mut files = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    files.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("gba"))
+    });
+    files.sort();
+
+    let threads = match options.threads {
+        0 => thread::available_parallelism().map_or(1, usize::from),
+        threads => threads,
+    }
+    .min(files.len().max(1));
+
+    let next = AtomicUsize::new(0);
+    let reports = Mutex::new(Vec::with_capacity(files.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while let Some(path) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let file = path
+                        .file_name()
+                        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                    let report = match fs::read(path) {
+                        Ok(rom) => run_rom(&file, bios, rom, options.frames, &options.config),
+                        Err(error) => RomReport {
+                            file,
+                            title: None,
+                            status: RomStatus::InvalidHeader,
+                            frames: 0,
+                            error: Some(error.to_string()),
+                            frame_hash: String::new(),
+                            unmapped_io_writes: Vec::new(),
+                        },
+                    };
+
+                    reports
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner)
+                        .push(report);
+                }
+            });
+        }
+    });
+
+    let mut roms = reports
+        .into_inner()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    roms.sort_by(|a, b| a.file.cmp(&b.file));
+
+    Ok(CompatibilityReport {
+        frames: options.frames,
+        roms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::cpu::asm::ArmAsm;
+    use crate::test_rom::TestRom;
+
+    fn bios() -> [u8; 0x4000] {
+        let mut bios = [0; 0x4000];
+        bios[..4].copy_from_slice(&ArmAsm::mov(15).imm(0x0800_0000).encode().to_le_bytes());
+        bios
+    }
+
+    fn rom(code: &[u32]) -> Vec<u8> {
+        TestRom::new(0x200)
+            .bytes(0xA0, b"TEST")
+            .words(0, code)
+            .build()
+    }
+
+    #[test]
+    fn reports() {
+        let bios = bios();
+
+        let report = run_rom(
+            "loop.gba",
+            &bios,
+            rom(&[ArmAsm::b(-8).encode()]),
+            2,
+            &EmuConfig::default(),
+        );
+        assert_eq!(report.status, RomStatus::Completed);
+        assert_eq!(report.title.as_deref(), Some("TEST"));
+        assert_eq!(report.frames, 2);
+        assert_eq!(report.frame_hash.len(), 8);
+
+        let report = run_rom(
+            "trap.gba",
+            &bios,
+            rom(&[ArmAsm::mov(15).imm(0x0400_0000).encode()]),
+            2,
+            &EmuConfig::default(),
+        );
+        assert_eq!(report.status, RomStatus::Trapped);
+        assert!(report.error.unwrap().contains("0x04000000"));
+
+        // The coprocessor instructions aren't emulated, `MRC p15, 0, r0, c0, c0` panics.
+        let report = run_rom(
+            "panic.gba",
+            &bios,
+            rom(&[0xEE10_0F10, ArmAsm::b(-8).encode()]),
+            2,
+            &EmuConfig::default(),
+        );
+        assert_eq!(report.status, RomStatus::Panicked);
+        assert!(report.error.unwrap().contains("implemented"));
+
+        let report = run_rom("empty.gba", &bios, Vec::new(), 2, &EmuConfig::default());
+        assert_eq!(report.status, RomStatus::InvalidHeader);
+        assert_eq!(report.title, None);
+    }
+}
@@ -0,0 +1,171 @@
+//! Options of the emulation chosen when the emulator is created, see
+//! [`Gba::with_config`](crate::gba::Gba::with_config).
+//!
+//! Every option defaults to the behavior of the hardware, or to the one the emulator had
+//! before it could be chosen. The ones with a setter on [`Gba`](crate::gba::Gba) can
+//! also change while the game runs, [`Gba::config`](crate::gba::Gba::config) follows.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::cartridge_info::SaveType;
+use crate::cpu::hardware::flash::FlashTiming;
+use crate::cpu::hardware::keypad::KeySampling;
+use crate::cpu::hardware::serial::SerialPeripheral;
+use crate::memory_map::BIOS_SIZE;
+use crate::replacement_bios::replacement_bios;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct EmuConfig {
+    /// The BIOS image [`Self::load_bios`] reads, `None` for the replacement BIOS.
+    pub bios_path: Option<PathBuf>,
+    /// Starts the game right away, as the BIOS leaves the CPU after its boot animation.
+    /// Also on [`Gba::reset`](crate::gba::Gba::reset), but for the soft reset which never
+    /// runs the boot.
+    pub skip_bios: bool,
+    /// Runs the BIOS functions emulated in Rust in place of the BIOS code, see
+    /// [`Arm7tdmi::set_bios_hle`](crate::cpu::arm7tdmi::Arm7tdmi::set_bios_hle).
+    ///
+    /// Off by default: every SWI runs the loaded BIOS image, as on hardware. Keep it off
+    /// for accuracy tests, and to tell whether a bug comes from the emulated functions.
+    pub bios_hle: bool,
+    /// See [`Gba::set_max_accuracy`](crate::gba::Gba::set_max_accuracy).
+    pub max_accuracy: bool,
+    /// Where the battery saves are kept, see [`SaveProfiles`](crate::save_profiles::SaveProfiles).
+    /// `None` keeps them in memory only: the frontend imports and exports them itself.
+    pub save_directory: Option<PathBuf>,
+    /// Battery save profile loaded when the cartridge is inserted, and written when the
+    /// game saves. `None` is [`DEFAULT_PROFILE`](crate::save_profiles::DEFAULT_PROFILE).
+    pub save_profile: Option<String>,
+    /// Previous versions kept of each battery save profile, `None` for
+    /// [`DEFAULT_BACKUPS`](crate::atomic_file::DEFAULT_BACKUPS).
+    pub save_backups: Option<usize>,
+    /// How much memory the emulator may take besides the cartridge ROM, see
+    /// [`memory_budget`](crate::memory_budget).
+    pub memory_profile: MemoryProfile,
+    /// Runs the CPU faster than the hardware, see [`Overclock`].
+    pub overclock: Overclock,
+    /// The backup memory of the cartridge, for the games whose ID string is missing or
+    /// wrong. `None` detects it, see [`SaveType::detect`]. Only Flash is emulated, any
+    /// other type leaves the cartridge without backup memory.
+    pub save_type: Option<SaveType>,
+    /// Whether the cartridge has a real-time clock, `None` detects it from the game code,
+    /// see [`rtc::detect`](crate::cpu::hardware::rtc::detect).
+    pub rtc: Option<bool>,
+    /// Whether the cartridge has the solar sensor of Boktai, `None` detects it from the
+    /// game code, see [`solar::detect`](crate::cpu::hardware::solar::detect).
+    pub solar_sensor: Option<bool>,
+    /// See [`Gba::set_key_sampling`](crate::gba::Gba::set_key_sampling).
+    pub key_sampling: KeySampling,
+    /// See [`Gba::set_flash_timing`](crate::gba::Gba::set_flash_timing).
+    pub flash_timing: FlashTiming,
+    /// See [`Gba::set_abort_on_invalid_access`](crate::gba::Gba::set_abort_on_invalid_access).
+    pub abort_on_invalid_access: bool,
+    /// Device plugged in the serial port, see
+    /// [`Bus::connect_serial_peripheral`](crate::bus::Bus::connect_serial_peripheral). The
+    /// wireless adapter is alone: games initialize it and find nobody to play with.
+    pub serial_peripheral: SerialPeripheral,
+    /// See [`Gba::set_audio_samples_per_frame`](crate::gba::Gba::set_audio_samples_per_frame),
+    /// `None` keeps the 549 samples of the hardware rate.
+    pub audio_samples_per_frame: Option<u32>,
+    /// How the frontends turn the colors of the LCD into the ones of the host screen, the
+    /// core itself only outputs the 15-bit colors.
+    pub color_correction: ColorCorrection,
+}
+
+impl EmuConfig {
+    /// The BIOS image at [`Self::bios_path`]. Without a path, or when the file doesn't
+    /// exist, the replacement BIOS with [`Self::bios_hle`] turned on as it needs, and
+    /// `bios_path` cleared for the frontend to tell.
+    ///
+    /// # Errors
+    /// It fails if the file can't be read or isn't 16 KB.
+    pub fn load_bios(&mut self) -> Result<[u8; BIOS_SIZE], BiosError> {
+        let file = match self.bios_path.as_deref().map(std::fs::read) {
+            Some(Ok(file)) => file,
+            Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => return Err(BiosError::Io(e)),
+            _ => {
+                self.bios_path = None;
+                self.bios_hle = true;
+                return Ok(replacement_bios());
+            }
+        };
+
+        let size = file.len();
+        file.try_into().map_err(|_| BiosError::Size(size))
+    }
+}
+
+#[derive(Debug)]
+pub enum BiosError {
+    Io(io::Error),
+    /// The size of the file, which isn't 16 KB.
+    Size(usize),
+}
+
+impl fmt::Display for BiosError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "can't read the BIOS: {e}"),
+            Self::Size(size) => write!(f, "the BIOS is {size} bytes instead of 16 KB"),
+        }
+    }
+}
+
+impl std::error::Error for BiosError {}
+
+/// See [`memory_budget`](crate::memory_budget) for what each profile costs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MemoryProfile {
+    /// Every debugging aid can be turned on, for desktop frontends.
+    #[default]
+    Standard,
+    /// Only what the emulation needs, with a fixed budget: for the web and mobile
+    /// frontends. The step history can't be recorded, the disassembly isn't kept and the
+    /// blocks aren't compiled.
+    Bounded,
+}
+
+/// How many times faster than the hardware the CPU runs.
+///
+/// The video, the sound, the timers and the DMA keep their speed: a game that missed
+/// frames has more time to draw each one, a game that didn't waits longer for the Vblank.
+///
+/// The memory waitstates are counted in cycles of the CPU, they shrink with it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overclock {
+    /// The 16.78 MHz of the hardware.
+    #[default]
+    Off,
+    X2,
+    X3,
+    X4,
+}
+
+impl Overclock {
+    pub const ALL: [Self; 4] = [Self::Off, Self::X2, Self::X3, Self::X4];
+
+    /// Cycles of the CPU in a cycle of the rest of the console.
+    #[must_use]
+    pub const fn multiplier(self) -> u32 {
+        match self {
+            Self::Off => 1,
+            Self::X2 => 2,
+            Self::X3 => 3,
+            Self::X4 => 4,
+        }
+    }
+}
+
+/// See [`Color::to_rgb8`](crate::cpu::hardware::lcd::Color::to_rgb8).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorCorrection {
+    /// The colors as the game wrote them.
+    #[default]
+    Off,
+    /// The darker and washed out colors of the unlit LCD of the GBA, that games
+    /// brightened to make up for.
+    Lcd,
+}
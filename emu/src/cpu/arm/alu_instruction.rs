@@ -1,9 +1,11 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use crate::bitwise::Bits;
 use crate::cpu::flags::ShiftKind;
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ArmModeAluInstr {
     And = 0x0,
     Eor = 0x1,
@@ -234,7 +236,8 @@ pub fn shift(kind: ShiftKind, shift_amount: u32, rm: u32, carry: bool) -> Arithm
 }
 
 /// Represents the kind of PSR operation
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PsrOpKind {
     /// MSR operation (transfer PSR contents to a register)
     Mrs { destination_register: u32 },
@@ -285,7 +288,8 @@ impl From<u32> for PsrOpKind {
 }
 
 /// Represents the kind of PSR register to user
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PsrKind {
     Cpsr,
     Spsr,
@@ -310,7 +314,8 @@ impl std::fmt::Display for PsrKind {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ShiftOperator {
     Immediate(u32),
     Register(u32),
@@ -325,7 +330,8 @@ impl std::fmt::Display for ShiftOperator {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AluSecondOperandInfo {
     Register {
         shift_op: ShiftOperator,
@@ -7,12 +7,14 @@ use crate::cpu::flags::{
     ReadWriteKind, ShiftKind,
 };
 use logger::log;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use super::alu_instruction::{PsrKind, PsrOpKind};
 
 /// Possible operation on transfer data.
-#[derive(Debug, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SingleDataTransferKind {
     /// Load from memory into a register.
     Ldr,
@@ -37,7 +39,8 @@ impl From<u32> for SingleDataTransferKind {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SingleDataTransferOffsetInfo {
     Immediate {
         offset: u32,
@@ -49,6 +52,23 @@ pub enum SingleDataTransferOffsetInfo {
     },
 }
 
+impl From<u32> for SingleDataTransferOffsetInfo {
+    fn from(op_code: u32) -> Self {
+        // NOTE: This bit is negated because the meaning is inverted in SingleDataTransfer then other istructions.
+        let op_kind: OperandKind = (!op_code.get_bit(25)).into();
+        match op_kind {
+            OperandKind::Immediate => Self::Immediate {
+                offset: op_code.get_bits(0..=11),
+            },
+            OperandKind::Register => Self::RegisterImmediate {
+                shift_amount: op_code.get_bits(7..=11),
+                shift_kind: op_code.get_bits(5..=6).into(),
+                reg_offset: op_code.get_bits(0..=3),
+            },
+        }
+    }
+}
+
 impl std::fmt::Display for SingleDataTransferOffsetInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -61,15 +81,21 @@ impl std::fmt::Display for SingleDataTransferOffsetInfo {
                 shift_amount,
                 shift_kind,
                 reg_offset,
-            } => {
-                write!(f, "{reg_offset}, {shift_kind} #{shift_amount}")?;
-            }
+            } => match (shift_kind, shift_amount) {
+                // Shifts by 0 encode the ones by 32, and RRX for ROR.
+                (ShiftKind::Ror, 0) => write!(f, "{reg_offset}, RRX")?,
+                (ShiftKind::Lsr | ShiftKind::Asr, 0) => {
+                    write!(f, "{reg_offset}, {shift_kind} #32")?;
+                }
+                _ => write!(f, "{reg_offset}, {shift_kind} #{shift_amount}")?,
+            },
         };
 
         Ok(())
     }
 }
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ArmModeInstruction {
     DataProcessing {
         condition: Condition,
@@ -160,16 +186,25 @@ pub enum ArmModeInstruction {
     },
     CoprocessorDataOperation,
     CoprocessorRegisterTransfer,
-    SoftwareInterrupt,
+    /// The fields aren't saved, so that the states keep the layout of the unit variant:
+    /// only the raw op code of a decoded instruction is read back.
+    SoftwareInterrupt {
+        #[cfg_attr(feature = "serde", serde(skip, default = "Condition::always"))]
+        condition: Condition,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        comment: u32,
+    },
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ArmModeMultiplyVariant {
     Mul,
     Mla,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ArmModeMultiplyLongVariant {
     Umull,
     Umlal,
@@ -447,259 +482,289 @@ impl ArmModeInstruction {
             }
             Self::CoprocessorDataOperation => panic!("CoprocessorDataOperation not implemented"),
             Self::CoprocessorRegisterTransfer => panic!("CoprocessorRegisterTransfer not implemented"),
-            Self::SoftwareInterrupt => panic!("SoftwareInterrupt not implemented"),
+            Self::SoftwareInterrupt { condition, comment } => {
+                format!("SWI{condition} #0x{comment:X}")
+            }
         }
     }
 }
 
 impl From<u32> for ArmModeInstruction {
-    #[allow(clippy::too_many_lines)]
     fn from(op_code: u32) -> Self {
-        let condition = Condition::from(op_code.get_bits(28..=31) as u8);
         // NOTE: The order is based on how many bits are already know at decoding time.
         // It can happen `op_code` coalesced into one/two or more than two possible solution, that's because
         // we tried to order with this priority.
         if op_code.get_bits(4..=27) == 0b0001_0010_1111_1111_1111_0001 {
-            let register = op_code.get_bits(0..=3) as usize;
-            Self::BranchAndExchange {
-                condition,
-                register,
-            }
+            Self::branch_and_exchange(op_code)
         } else if op_code.get_bits(23..=27) == 0b00010
             && op_code.get_bits(20..=21) == 0b00
             && op_code.get_bits(4..=11) == 0b0000_1001
         {
             Self::SingleDataSwap
         } else if op_code.get_bits(23..=27) == 0b00001 && op_code.get_bits(4..=7) == 0b1001 {
-            let variant = ArmModeMultiplyLongVariant::from(op_code);
-
-            let should_set_codes = op_code.get_bit(20);
-
-            let rm_operand_register = op_code.get_bits(0..=3);
-            let rs_operand_register = op_code.get_bits(8..=11);
-            let rdlo_destination_register = op_code.get_bits(12..=15);
-            let rdhi_destination_register = op_code.get_bits(16..=19);
-
-            Self::MultiplyLong {
-                variant,
-                condition,
-                should_set_codes,
-                rdhi_destination_register,
-                rdlo_destination_register,
-                rm_operand_register,
-                rs_operand_register,
-            }
+            Self::multiply_long(op_code)
         } else if op_code.get_bits(22..=27) == 0b00_0000 && op_code.get_bits(4..=7) == 0b1001 {
-            let variant = ArmModeMultiplyVariant::from(op_code);
-
-            let should_set_codes = op_code.get_bit(20);
-
-            let rm_operand_register = op_code.get_bits(0..=3);
-            let rs_operand_register = op_code.get_bits(8..=11);
-            let rn_accumulate_register = op_code.get_bits(12..=15);
-            let rd_destination_register = op_code.get_bits(16..=19);
-
-            Self::Multiply {
-                variant,
-                condition,
-                should_set_codes,
-                rd_destination_register,
-                rn_accumulate_register,
-                rm_operand_register,
-                rs_operand_register,
-            }
+            Self::multiply(op_code)
         } else if op_code.get_bits(25..=27) == 0b000 && op_code.get_bit(7) && op_code.get_bit(4) {
-            let indexing: Indexing = op_code.get_bit(24).into();
-            let offsetting: Offsetting = op_code.get_bit(23).into();
-            let write_back = op_code.get_bit(21);
-            let load_store_kind: LoadStoreKind = op_code.get_bit(20).into();
-            let base_register = op_code.get_bits(16..=19);
-            let source_destination_register = op_code.get_bits(12..=15);
-            let transfer_kind: HalfwordTransferKind = (op_code.get_bits(5..=6) as u8).into();
-            let operand_kind: OperandKind = op_code.get_bit(22).into();
-
-            Self::HalfwordDataTransfer {
-                condition,
-                indexing,
-                offsetting,
-                write_back,
-                load_store_kind,
-                offset_kind: if operand_kind == OperandKind::Register {
-                    HalfwordDataTransferOffsetKind::Register {
-                        register: op_code.get_bits(0..=3),
-                    }
-                } else {
-                    let immediate_offset_high = op_code.get_bits(8..=11);
-                    let immediate_offset_low = op_code.get_bits(0..=3);
-
-                    HalfwordDataTransferOffsetKind::Immediate {
-                        offset: (immediate_offset_high << 4) | immediate_offset_low,
-                    }
-                },
-                base_register,
-                source_destination_register,
-                transfer_kind,
-            }
+            Self::halfword_data_transfer(op_code)
         } else if op_code.get_bits(25..=27) == 0b011 && op_code.get_bit(4) {
             log("undefined instruction decode...");
             Self::Undefined
         } else if op_code.get_bits(24..=27) == 0b1111 {
-            Self::SoftwareInterrupt
+            Self::SoftwareInterrupt {
+                condition: Condition::from(op_code.get_bits(28..=31) as u8),
+                comment: op_code.get_bits(0..=23),
+            }
         } else if op_code.get_bits(24..=27) == 0b1110 && op_code.get_bit(4) {
             Self::CoprocessorRegisterTransfer
         } else if op_code.get_bits(24..=27) == 0b1110 && !op_code.get_bit(4) {
             Self::CoprocessorDataOperation
         } else if op_code.get_bits(25..=27) == 0b110 {
-            let indexing: Indexing = op_code.get_bit(24).into();
-            let offsetting: Offsetting = op_code.get_bit(23).into();
-            let transfer_length = op_code.get_bit(22);
-            let write_back = op_code.get_bit(21);
-            let load_store: LoadStoreKind = op_code.get_bit(20).into();
-
-            let rn = op_code.get_bits(16..=19);
-            let crd = op_code.get_bits(12..=15);
-            let cp_number = op_code.get_bits(8..=11);
-            let offset = op_code.get_bits(0..=7);
-
-            Self::CoprocessorDataTransfer {
-                condition,
-                indexing,
-                offsetting,
-                transfer_length,
-                write_back,
-                load_store,
-                rn,
-                crd,
-                cp_number,
-                offset,
-            }
+            Self::coprocessor_data_transfer(op_code)
         } else if op_code.get_bits(25..=27) == 0b100 {
-            let indexing = op_code.get_bit(24).into();
-            let offsetting = op_code.get_bit(23).into();
-            let load_psr = op_code.get_bit(22);
-            let write_back = op_code.get_bit(21);
-            let load_store = op_code.get_bit(20).into();
-            let rn = op_code.get_bits(16..=19);
-            let reg_list = op_code.get_bits(0..=15);
-
-            Self::BlockDataTransfer {
-                condition,
-                indexing,
-                offsetting,
-                load_psr,
-                write_back,
-                load_store,
-                rn,
-                register_list: reg_list,
-            }
+            Self::block_data_transfer(op_code)
         } else if op_code.get_bits(25..=27) == 0b101 {
-            let link = op_code.get_bit(24);
-            let offset = op_code.get_bits(0..=23) << 2;
-            Self::Branch {
-                condition,
-                link,
-                offset,
-            }
+            Self::branch(op_code)
         } else if op_code.get_bits(26..=27) == 0b01 {
-            // NOTE: This bit is negated because the meaning is inverted in SingleDataTransfer then other istructions.
-            let op_kind: OperandKind = (!op_code.get_bit(25)).into();
-            let indexing: Indexing = op_code.get_bit(24).into(); // FIXME: should we use this?
-            let offsetting: Offsetting = op_code.get_bit(23).into();
-            let byte_or_word: ReadWriteKind = op_code.into(); // TODO: is this the same for all instruction?
-            let load_store: SingleDataTransferKind = op_code.into(); // TODO: is this the same bit for all instruction?
-            let write_back = op_code.get_bit(21);
-            let rn = op_code.get_bits(16..=19);
-            let rd = op_code.get_bits(12..=15);
-
-            let offset_info = match op_kind {
-                OperandKind::Immediate => {
-                    let offset = op_code.get_bits(0..=11);
-                    SingleDataTransferOffsetInfo::Immediate { offset }
-                }
-                OperandKind::Register => {
-                    let shift_amount = op_code.get_bits(7..=11);
-                    let shift_kind: ShiftKind = op_code.get_bits(5..=6).into();
-                    let reg_offset = op_code.get_bits(0..=3);
-                    SingleDataTransferOffsetInfo::RegisterImmediate {
-                        shift_amount,
-                        shift_kind,
-                        reg_offset,
-                    }
-                }
-            };
+            Self::single_data_transfer(op_code)
+        } else if op_code.get_bits(26..=27) == 0b00 {
+            Self::data_processing(op_code)
+        } else {
+            log("not identified instruction");
+            unimplemented!()
+        }
+    }
+}
 
-            Self::SingleDataTransfer {
+/// Decoding of each kind of instruction, once the kind is known. They are shared by
+/// [`ArmModeInstruction::from`] and the lookup table of the CPU.
+impl ArmModeInstruction {
+    #[must_use]
+    pub fn branch_and_exchange(op_code: u32) -> Self {
+        let condition = Condition::from(op_code.get_bits(28..=31) as u8);
+        let register = op_code.get_bits(0..=3) as usize;
+        Self::BranchAndExchange {
+            condition,
+            register,
+        }
+    }
+
+    #[must_use]
+    pub fn multiply_long(op_code: u32) -> Self {
+        let condition = Condition::from(op_code.get_bits(28..=31) as u8);
+        let variant = ArmModeMultiplyLongVariant::from(op_code);
+
+        let should_set_codes = op_code.get_bit(20);
+
+        let rm_operand_register = op_code.get_bits(0..=3);
+        let rs_operand_register = op_code.get_bits(8..=11);
+        let rdlo_destination_register = op_code.get_bits(12..=15);
+        let rdhi_destination_register = op_code.get_bits(16..=19);
+
+        Self::MultiplyLong {
+            variant,
+            condition,
+            should_set_codes,
+            rdhi_destination_register,
+            rdlo_destination_register,
+            rm_operand_register,
+            rs_operand_register,
+        }
+    }
+
+    #[must_use]
+    pub fn multiply(op_code: u32) -> Self {
+        let condition = Condition::from(op_code.get_bits(28..=31) as u8);
+        let variant = ArmModeMultiplyVariant::from(op_code);
+
+        let should_set_codes = op_code.get_bit(20);
+
+        let rm_operand_register = op_code.get_bits(0..=3);
+        let rs_operand_register = op_code.get_bits(8..=11);
+        let rn_accumulate_register = op_code.get_bits(12..=15);
+        let rd_destination_register = op_code.get_bits(16..=19);
+
+        Self::Multiply {
+            variant,
+            condition,
+            should_set_codes,
+            rd_destination_register,
+            rn_accumulate_register,
+            rm_operand_register,
+            rs_operand_register,
+        }
+    }
+
+    #[must_use]
+    pub fn halfword_data_transfer(op_code: u32) -> Self {
+        let condition = Condition::from(op_code.get_bits(28..=31) as u8);
+        let indexing: Indexing = op_code.get_bit(24).into();
+        let offsetting: Offsetting = op_code.get_bit(23).into();
+        let write_back = op_code.get_bit(21);
+        let load_store_kind: LoadStoreKind = op_code.get_bit(20).into();
+        let base_register = op_code.get_bits(16..=19);
+        let source_destination_register = op_code.get_bits(12..=15);
+        let transfer_kind: HalfwordTransferKind = (op_code.get_bits(5..=6) as u8).into();
+
+        Self::HalfwordDataTransfer {
+            condition,
+            indexing,
+            offsetting,
+            write_back,
+            load_store_kind,
+            offset_kind: HalfwordDataTransferOffsetKind::from(op_code),
+            base_register,
+            source_destination_register,
+            transfer_kind,
+        }
+    }
+
+    #[must_use]
+    pub fn coprocessor_data_transfer(op_code: u32) -> Self {
+        let condition = Condition::from(op_code.get_bits(28..=31) as u8);
+        let indexing: Indexing = op_code.get_bit(24).into();
+        let offsetting: Offsetting = op_code.get_bit(23).into();
+        let transfer_length = op_code.get_bit(22);
+        let write_back = op_code.get_bit(21);
+        let load_store: LoadStoreKind = op_code.get_bit(20).into();
+
+        let rn = op_code.get_bits(16..=19);
+        let crd = op_code.get_bits(12..=15);
+        let cp_number = op_code.get_bits(8..=11);
+        let offset = op_code.get_bits(0..=7);
+
+        Self::CoprocessorDataTransfer {
+            condition,
+            indexing,
+            offsetting,
+            transfer_length,
+            write_back,
+            load_store,
+            rn,
+            crd,
+            cp_number,
+            offset,
+        }
+    }
+
+    #[must_use]
+    pub fn block_data_transfer(op_code: u32) -> Self {
+        let condition = Condition::from(op_code.get_bits(28..=31) as u8);
+        let indexing = op_code.get_bit(24).into();
+        let offsetting = op_code.get_bit(23).into();
+        let load_psr = op_code.get_bit(22);
+        let write_back = op_code.get_bit(21);
+        let load_store = op_code.get_bit(20).into();
+        let rn = op_code.get_bits(16..=19);
+        let reg_list = op_code.get_bits(0..=15);
+
+        Self::BlockDataTransfer {
+            condition,
+            indexing,
+            offsetting,
+            load_psr,
+            write_back,
+            load_store,
+            rn,
+            register_list: reg_list,
+        }
+    }
+
+    #[must_use]
+    pub fn branch(op_code: u32) -> Self {
+        let condition = Condition::from(op_code.get_bits(28..=31) as u8);
+        let link = op_code.get_bit(24);
+        let offset = op_code.get_bits(0..=23) << 2;
+        Self::Branch {
+            condition,
+            link,
+            offset,
+        }
+    }
+
+    #[must_use]
+    pub fn single_data_transfer(op_code: u32) -> Self {
+        let condition = Condition::from(op_code.get_bits(28..=31) as u8);
+        let indexing: Indexing = op_code.get_bit(24).into(); // FIXME: should we use this?
+        let offsetting: Offsetting = op_code.get_bit(23).into();
+        let byte_or_word: ReadWriteKind = op_code.into(); // TODO: is this the same for all instruction?
+        let load_store: SingleDataTransferKind = op_code.into(); // TODO: is this the same bit for all instruction?
+        let write_back = op_code.get_bit(21);
+        let rn = op_code.get_bits(16..=19);
+        let rd = op_code.get_bits(12..=15);
+
+        Self::SingleDataTransfer {
+            condition,
+            kind: load_store,
+            quantity: byte_or_word,
+            write_back,
+            indexing,
+            rd,
+            base_register: rn,
+            offset_info: SingleDataTransferOffsetInfo::from(op_code),
+            offsetting,
+        }
+    }
+
+    #[must_use]
+    pub fn data_processing(op_code: u32) -> Self {
+        let condition = Condition::from(op_code.get_bits(28..=31) as u8);
+        let alu_instruction = op_code.get_bits(21..=24).into();
+        let set_conditions = op_code.get_bit(20);
+        let rn = op_code.get_bits(16..=19);
+        let op_kind: OperandKind = op_code.get_bit(25).into();
+        let rd = op_code.get_bits(12..=15);
+
+        if matches!(
+            alu_instruction,
+            ArmModeAluInstr::Tst
+                | ArmModeAluInstr::Teq
+                | ArmModeAluInstr::Cmp
+                | ArmModeAluInstr::Cmn
+        ) && !set_conditions
+        {
+            // PSR instruction
+            return Self::PSRTransfer {
                 condition,
-                kind: load_store,
-                quantity: byte_or_word,
-                write_back,
-                indexing,
-                rd,
-                base_register: rn,
-                offset_info,
-                offsetting,
-            }
-        } else if op_code.get_bits(26..=27) == 0b00 {
-            let alu_instruction = op_code.get_bits(21..=24).into();
-            let set_conditions = op_code.get_bit(20);
-            let rn = op_code.get_bits(16..=19);
-            let op_kind: OperandKind = op_code.get_bit(25).into();
-            let rd = op_code.get_bits(12..=15);
+                psr_kind: PsrKind::from(op_code.get_bit(22)),
+                kind: PsrOpKind::from(op_code),
+            };
+        }
 
-            if matches!(
-                alu_instruction,
-                ArmModeAluInstr::Tst
-                    | ArmModeAluInstr::Teq
-                    | ArmModeAluInstr::Cmp
-                    | ArmModeAluInstr::Cmn
-            ) && !set_conditions
-            {
-                // PSR instruction
-                return Self::PSRTransfer {
-                    condition,
-                    psr_kind: PsrKind::from(op_code.get_bit(22)),
-                    kind: PsrOpKind::from(op_code),
-                };
+        let op2 = match op_kind {
+            OperandKind::Immediate => {
+                let shift = op_code.get_bits(8..=11) * 2;
+                let base = op_code.get_bits(0..=7);
+                AluSecondOperandInfo::Immediate { base, shift }
             }
-
-            let op2 = match op_kind {
-                OperandKind::Immediate => {
-                    let shift = op_code.get_bits(8..=11) * 2;
-                    let base = op_code.get_bits(0..=7);
-                    AluSecondOperandInfo::Immediate { base, shift }
-                }
-                OperandKind::Register => {
-                    let shift_kind: ShiftKind = op_code.get_bits(5..=6).into();
-                    let shift_by_register_bit = op_code.get_bit(4);
-                    let register = op_code.get_bits(0..=3);
-                    let shift_op = if shift_by_register_bit {
-                        if op_code.get_bit(7) {
-                            todo!("should be zero or need different work")
-                        }
-                        ShiftOperator::Register(op_code.get_bits(8..=11))
-                    } else {
-                        ShiftOperator::Immediate(op_code.get_bits(7..=11))
-                    };
-                    AluSecondOperandInfo::Register {
-                        shift_op,
-                        shift_kind,
-                        register,
+            OperandKind::Register => {
+                let shift_kind: ShiftKind = op_code.get_bits(5..=6).into();
+                let shift_by_register_bit = op_code.get_bit(4);
+                let register = op_code.get_bits(0..=3);
+                let shift_op = if shift_by_register_bit {
+                    if op_code.get_bit(7) {
+                        todo!("should be zero or need different work")
                     }
+                    ShiftOperator::Register(op_code.get_bits(8..=11))
+                } else {
+                    ShiftOperator::Immediate(op_code.get_bits(7..=11))
+                };
+                AluSecondOperandInfo::Register {
+                    shift_op,
+                    shift_kind,
+                    register,
                 }
-            };
-
-            Self::DataProcessing {
-                condition,
-                alu_instruction,
-                set_conditions,
-                op_kind,
-                rn,
-                destination: rd,
-                op2,
             }
-        } else {
-            log("not identified instruction");
-            unimplemented!()
+        };
+
+        Self::DataProcessing {
+            condition,
+            alu_instruction,
+            set_conditions,
+            op_kind,
+            rn,
+            destination: rd,
+            op2,
         }
     }
 }
@@ -763,6 +828,26 @@ mod tests {
         assert_eq!("BLEQ 0x000001FC", output.disassembler());
     }
 
+    #[test]
+    fn decode_software_interrupt() {
+        let output = ArmModeInstruction::from(0xEF14_0000);
+        assert_eq!(
+            ArmModeInstruction::SoftwareInterrupt {
+                condition: Condition::AL,
+                comment: 0x14_0000,
+            },
+            output
+        );
+        assert_eq!("SWI #0x140000", output.disassembler());
+
+        let output = ArmModeInstruction::from(0x1F00_0005);
+        assert_eq!("SWINE #0x5", output.disassembler());
+
+        // Saved as the unit variant it used to be.
+        #[cfg(feature = "serde")]
+        assert_eq!(bincode::serialize(&output).unwrap().len(), 4);
+    }
+
     #[test]
     fn decode_branch_and_exchange() {
         let output = ArmModeInstruction::from(0b1110_0001_0010_1111_1111_1111_0001_0001);
@@ -0,0 +1,333 @@
+//! Execution by lookup table. Bits 27-20 and 7-4 of an instruction are enough to tell
+//! its kind, so the 4096 combinations are resolved at compile time to the handler of
+//! that kind, which reads its fields and runs it, instead of testing every kind in turn
+//! as [`ArmModeInstruction::from`] does and matching on the result.
+//!
+//! The enum is only built for the disassembler, the logger and the save-states, with a
+//! table of the decoders of each kind.
+
+use crate::bitwise::Bits;
+use crate::cpu::arm::alu_instruction::{ArmModeAluInstr, PsrKind, PsrOpKind};
+use crate::cpu::arm::instructions::{
+    ArmModeInstruction, ArmModeMultiplyLongVariant, ArmModeMultiplyVariant,
+    SingleDataTransferOffsetInfo,
+};
+use crate::cpu::arm::mode::ArmModeOpcode;
+use crate::cpu::arm7tdmi::Arm7tdmi;
+use crate::cpu::condition::Condition;
+use crate::cpu::flags::HalfwordDataTransferOffsetKind;
+
+type Handler = fn(&mut Arm7tdmi, u32);
+type Decoder = fn(u32) -> ArmModeInstruction;
+
+static HANDLERS: [Handler; 4096] = handlers();
+static DECODERS: [Decoder; 4096] = decoders();
+
+const fn index(op_code: u32) -> usize {
+    (((op_code >> 16) & 0xFF0) | ((op_code >> 4) & 0xF)) as usize
+}
+
+const fn bits(value: u32, low: u32, high: u32) -> u32 {
+    (value >> low) & ((1 << (high - low + 1)) - 1)
+}
+
+/// What the bits in the index tell of an instruction.
+#[derive(Clone, Copy)]
+enum Kind {
+    /// Kinds that need more bits than the index, and the ones without fields.
+    Other,
+    MultiplyLong,
+    Multiply,
+    HalfwordDataTransfer,
+    CoprocessorDataTransfer,
+    BlockDataTransfer,
+    Branch,
+    SingleDataTransfer,
+    DataProcessing,
+}
+
+impl Kind {
+    /// Same order as [`ArmModeInstruction::from`], on the bits in the index.
+    const fn of(index: usize) -> Self {
+        let op_code = ((index as u32 & 0xFF0) << 16) | ((index as u32 & 0xF) << 4);
+        let multiply_or_swap = bits(op_code, 4, 7) == 0b1001;
+
+        if bits(op_code, 20, 27) == 0b0001_0010 && bits(op_code, 4, 7) == 0b0001 {
+            // Branch and exchange, bits 8-19 are set as well.
+            Self::Other
+        } else if bits(op_code, 23, 27) == 0b00010 && bits(op_code, 20, 21) == 0 && multiply_or_swap
+        {
+            // Single data swap, bits 8-11 are clear as well.
+            Self::Other
+        } else if bits(op_code, 23, 27) == 0b00001 && multiply_or_swap {
+            Self::MultiplyLong
+        } else if bits(op_code, 22, 27) == 0 && multiply_or_swap {
+            Self::Multiply
+        } else if bits(op_code, 25, 27) == 0 && bits(op_code, 7, 7) == 1 && bits(op_code, 4, 4) == 1
+        {
+            Self::HalfwordDataTransfer
+        } else if bits(op_code, 25, 27) == 0b011 && bits(op_code, 4, 4) == 1 {
+            // Undefined.
+            Self::Other
+        } else if bits(op_code, 24, 27) >= 0b1110 {
+            // Software interrupt and coprocessor operations.
+            Self::Other
+        } else if bits(op_code, 25, 27) == 0b110 {
+            Self::CoprocessorDataTransfer
+        } else if bits(op_code, 25, 27) == 0b100 {
+            Self::BlockDataTransfer
+        } else if bits(op_code, 25, 27) == 0b101 {
+            Self::Branch
+        } else if bits(op_code, 26, 27) == 0b01 {
+            Self::SingleDataTransfer
+        } else {
+            Self::DataProcessing
+        }
+    }
+
+    const fn handler(self) -> Handler {
+        match self {
+            // The coprocessor isn't emulated, like the other rare kinds it goes through
+            // the full decoding.
+            Self::Other | Self::CoprocessorDataTransfer => execute_fully,
+            Self::MultiplyLong => multiply_long,
+            Self::Multiply => multiply,
+            Self::HalfwordDataTransfer => halfword_data_transfer,
+            Self::BlockDataTransfer => block_data_transfer,
+            Self::Branch => branch,
+            Self::SingleDataTransfer => single_data_transfer,
+            Self::DataProcessing => data_processing,
+        }
+    }
+
+    const fn decoder(self) -> Decoder {
+        match self {
+            Self::Other => decode_fully,
+            Self::MultiplyLong => ArmModeInstruction::multiply_long,
+            Self::Multiply => ArmModeInstruction::multiply,
+            Self::HalfwordDataTransfer => ArmModeInstruction::halfword_data_transfer,
+            Self::CoprocessorDataTransfer => ArmModeInstruction::coprocessor_data_transfer,
+            Self::BlockDataTransfer => ArmModeInstruction::block_data_transfer,
+            Self::Branch => ArmModeInstruction::branch,
+            Self::SingleDataTransfer => ArmModeInstruction::single_data_transfer,
+            Self::DataProcessing => ArmModeInstruction::data_processing,
+        }
+    }
+}
+
+// Only evaluated at compile time.
+#[allow(clippy::large_stack_arrays)]
+const fn handlers() -> [Handler; 4096] {
+    let mut table: [Handler; 4096] = [execute_fully; 4096];
+
+    let mut index = 0;
+    while index < table.len() {
+        table[index] = Kind::of(index).handler();
+        index += 1;
+    }
+
+    table
+}
+
+// Only evaluated at compile time.
+#[allow(clippy::large_stack_arrays)]
+const fn decoders() -> [Decoder; 4096] {
+    let mut table: [Decoder; 4096] = [decode_fully; 4096];
+
+    let mut index = 0;
+    while index < table.len() {
+        table[index] = Kind::of(index).decoder();
+        index += 1;
+    }
+
+    table
+}
+
+/// Runs `op_code` with the handler of its kind, its condition already checked.
+pub fn execute(cpu: &mut Arm7tdmi, op_code: u32) {
+    HANDLERS[index(op_code)](cpu, op_code);
+}
+
+/// The instruction for the disassembler, the logger and the save-states.
+#[cfg_attr(
+    not(any(feature = "serde", feature = "disassembler", feature = "logger")),
+    allow(dead_code)
+)]
+pub fn decode(op_code: u32) -> ArmModeOpcode {
+    ArmModeOpcode {
+        instruction: DECODERS[index(op_code)](op_code),
+        condition: Condition::from(op_code.get_bits(28..=31) as u8),
+        raw: op_code,
+    }
+}
+
+fn decode_fully(op_code: u32) -> ArmModeInstruction {
+    ArmModeInstruction::from(op_code)
+}
+
+fn execute_fully(cpu: &mut Arm7tdmi, op_code: u32) {
+    match ArmModeInstruction::from(op_code) {
+        ArmModeInstruction::BranchAndExchange { register, .. } => {
+            cpu.branch_and_exchange(register);
+        }
+        ArmModeInstruction::PSRTransfer { psr_kind, kind, .. } => cpu.psr_transfer(kind, psr_kind),
+        ArmModeInstruction::SoftwareInterrupt { .. } => {
+            // In ARM state the BIOS takes the function from bits 16-23 of the comment.
+            cpu.software_interrupt(op_code.get_bits(16..=23) as u8);
+        }
+        // The encodings next to the rare kinds.
+        ArmModeInstruction::DataProcessing { .. } => data_processing(cpu, op_code),
+        ArmModeInstruction::Multiply { .. } => multiply(cpu, op_code),
+        ArmModeInstruction::MultiplyLong { .. } => multiply_long(cpu, op_code),
+        ArmModeInstruction::HalfwordDataTransfer { .. } => halfword_data_transfer(cpu, op_code),
+        ArmModeInstruction::SingleDataTransfer { .. } => single_data_transfer(cpu, op_code),
+        ArmModeInstruction::BlockDataTransfer { .. } => block_data_transfer(cpu, op_code),
+        ArmModeInstruction::Branch { .. } => branch(cpu, op_code),
+        ArmModeInstruction::SingleDataSwap
+        | ArmModeInstruction::Undefined
+        | ArmModeInstruction::CoprocessorDataTransfer { .. }
+        | ArmModeInstruction::CoprocessorDataOperation
+        | ArmModeInstruction::CoprocessorRegisterTransfer => todo!(),
+    }
+}
+
+fn data_processing(cpu: &mut Arm7tdmi, op_code: u32) {
+    let alu_instruction: ArmModeAluInstr = op_code.get_bits(21..=24).into();
+    let set_conditions = op_code.get_bit(20);
+
+    // The tests without S are PSR transfers.
+    if !set_conditions
+        && matches!(
+            alu_instruction,
+            ArmModeAluInstr::Tst
+                | ArmModeAluInstr::Teq
+                | ArmModeAluInstr::Cmp
+                | ArmModeAluInstr::Cmn
+        )
+    {
+        cpu.psr_transfer(PsrOpKind::from(op_code), PsrKind::from(op_code.get_bit(22)));
+        return;
+    }
+
+    cpu.data_processing(
+        op_code,
+        alu_instruction,
+        set_conditions,
+        op_code.get_bit(25).into(),
+        op_code.get_bits(16..=19),
+        op_code.get_bits(12..=15),
+    );
+}
+
+fn multiply(cpu: &mut Arm7tdmi, op_code: u32) {
+    cpu.multiply(
+        ArmModeMultiplyVariant::from(op_code),
+        op_code.get_bit(20),
+        op_code.get_bits(16..=19),
+        op_code.get_bits(12..=15),
+        op_code.get_bits(8..=11),
+        op_code.get_bits(0..=3),
+    );
+}
+
+fn multiply_long(cpu: &mut Arm7tdmi, op_code: u32) {
+    cpu.multiply_long(
+        ArmModeMultiplyLongVariant::from(op_code),
+        op_code.get_bit(20),
+        op_code.get_bits(16..=19),
+        op_code.get_bits(12..=15),
+        op_code.get_bits(8..=11),
+        op_code.get_bits(0..=3),
+    );
+}
+
+fn halfword_data_transfer(cpu: &mut Arm7tdmi, op_code: u32) {
+    cpu.half_word_data_transfer(
+        op_code.get_bit(24).into(),
+        op_code.get_bit(23).into(),
+        op_code.get_bit(21),
+        op_code.get_bit(20).into(),
+        HalfwordDataTransferOffsetKind::from(op_code),
+        op_code.get_bits(16..=19),
+        op_code.get_bits(12..=15),
+        (op_code.get_bits(5..=6) as u8).into(),
+    );
+}
+
+fn single_data_transfer(cpu: &mut Arm7tdmi, op_code: u32) {
+    cpu.single_data_transfer(
+        op_code.into(),
+        op_code.into(),
+        op_code.get_bit(21),
+        op_code.get_bit(24).into(),
+        op_code.get_bits(12..=15),
+        op_code.get_bits(16..=19),
+        SingleDataTransferOffsetInfo::from(op_code),
+        op_code.get_bit(23).into(),
+    );
+}
+
+fn block_data_transfer(cpu: &mut Arm7tdmi, op_code: u32) {
+    cpu.block_data_transfer(
+        op_code.get_bit(24).into(),
+        op_code.get_bit(23).into(),
+        op_code.get_bit(22),
+        op_code.get_bit(21),
+        op_code.get_bit(20).into(),
+        op_code.get_bits(16..=19),
+        op_code.get_bits(0..=15),
+    );
+}
+
+fn branch(cpu: &mut Arm7tdmi, op_code: u32) {
+    cpu.branch(op_code.get_bit(24), op_code.get_bits(0..=23) << 2);
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn table_matches_full_decoding() {
+        let mut rng = StdRng::seed_from_u64(0xA7_4096);
+
+        for _ in 0..100_000 {
+            let op_code = rng.gen::<u32>();
+            // Some PSR transfers, and halfword transfers without a transfer kind, aren't
+            // supported by either decoder.
+            let psr_transfer = op_code.get_bits(26..=27) == 0 && op_code.get_bits(23..=24) == 0b10;
+            let halfword_without_kind = op_code.get_bits(25..=27) == 0
+                && op_code.get_bits(4..=7) == 0b1001
+                && op_code.get_bits(23..=24) != 0b01
+                && op_code.get_bits(22..=24) != 0;
+            let unsupported = psr_transfer || halfword_without_kind;
+            if unsupported {
+                continue;
+            }
+
+            assert_eq!(
+                decode(op_code).instruction,
+                ArmModeInstruction::from(op_code),
+                "{op_code:08X}"
+            );
+        }
+
+        // Branch and exchange, single data swap and the PSR transfers.
+        for op_code in [
+            0xE12F_FF11,
+            0xE108_0092,
+            0xE148_0092,
+            0xE10F_0000,
+            0xE129_F001,
+        ] {
+            assert_eq!(
+                decode(op_code).instruction,
+                ArmModeInstruction::from(op_code),
+                "{op_code:08X}"
+            );
+        }
+    }
+}
@@ -9,6 +9,9 @@ pub mod alu_instruction;
 #[allow(clippy::similar_names)]
 pub mod instructions;
 
+#[allow(clippy::cast_possible_truncation)]
+pub mod lut;
+
 #[allow(clippy::cast_possible_truncation)]
 pub mod mode;
 
@@ -1,12 +1,15 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use crate::bitwise::Bits;
 use crate::cpu::arm::instructions::ArmModeInstruction;
 use crate::cpu::condition::Condition;
 
-#[derive(Copy, Clone, Serialize, Deserialize)]
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ArmModeOpcode {
     pub instruction: ArmModeInstruction,
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
     pub condition: Condition,
     pub raw: u32,
 }
@@ -91,7 +94,7 @@ impl std::fmt::Display for ArmModeOpcode {
             | ArmModeInstruction::SingleDataSwap
             | ArmModeInstruction::CoprocessorDataOperation
             | ArmModeInstruction::CoprocessorRegisterTransfer
-            | ArmModeInstruction::SoftwareInterrupt => "FMT: |_Cond__|",
+            | ArmModeInstruction::SoftwareInterrupt { .. } => "FMT: |_Cond__|",
         };
 
         let mut raw_bits = String::new();
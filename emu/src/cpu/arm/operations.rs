@@ -6,7 +6,6 @@ use crate::cpu::arm::instructions::{
     ArmModeMultiplyLongVariant, ArmModeMultiplyVariant, SingleDataTransferKind,
     SingleDataTransferOffsetInfo,
 };
-use crate::cpu::arm::mode::ArmModeOpcode;
 use crate::cpu::arm7tdmi::{Arm7tdmi, HalfwordTransferKind};
 use crate::cpu::cpu_modes::Mode;
 use crate::cpu::flags::{
@@ -21,10 +20,41 @@ use super::alu_instruction::{AluSecondOperandInfo, PsrKind};
 
 pub const SIZE_OF_INSTRUCTION: u32 = 4;
 
+/// Internal cycles of the multiplier, from 1 to 4: it stops early when the top bytes of
+/// the multiplier `rs` are all zeros, or all ones for `signed` multiplications.
+pub const fn multiply_cycles(rs: u32, signed: bool) -> u32 {
+    let mut cycles = 1;
+    while cycles < 4 {
+        let top = rs >> (cycles * 8);
+        if top == 0 || (signed && top == u32::MAX >> (cycles * 8)) {
+            break;
+        }
+
+        cycles += 1;
+    }
+
+    cycles
+}
+
+/// Offset of a single data transfer `[rn, rm, kind #amount]`. Shifts by 0 other than
+/// `LSL #0` encode `LSR #32`, `ASR #32` and `RRX`, as for data processing, but the
+/// carry out is never kept: only the address depends on the shift.
+pub const fn transfer_offset(kind: ShiftKind, amount: u32, rm: u32, carry: bool) -> u32 {
+    match (kind, amount) {
+        (ShiftKind::Lsl, _) => rm << amount,
+        (ShiftKind::Lsr, 0) => 0,
+        (ShiftKind::Lsr, _) => rm >> amount,
+        (ShiftKind::Asr, 0) => ((rm as i32) >> 31) as u32,
+        (ShiftKind::Asr, _) => ((rm as i32) >> amount) as u32,
+        (ShiftKind::Ror, 0) => (rm >> 1) | (carry as u32) << 31,
+        (ShiftKind::Ror, _) => rm.rotate_right(amount),
+    }
+}
+
 impl Arm7tdmi {
     pub fn data_processing(
         &mut self,
-        op_code: ArmModeOpcode, // FIXME: This parameter will be remove after change `psr_transfer`.
+        op_code: u32,
         alu_instruction: ArmModeAluInstr,
         set_conditions: bool,
         op_kind: OperandKind,
@@ -256,6 +286,9 @@ impl Arm7tdmi {
                 let shift_kind = op2.get_bits(5..=6).into();
 
                 let shift_amount = if r {
+                    // Reading Rs takes an internal cycle.
+                    self.bus.idle(1);
+
                     // the shift amount is read from Rs
                     // bits [11-8] - Shift register (R0-R14) - only lower 8bit 0-255 used
                     let rs = op2.get_bits(8..=11);
@@ -589,9 +622,9 @@ impl Arm7tdmi {
             Offsetting::Up => address.wrapping_add(offset),
         };
 
-        let address: usize = match indexing {
-            Indexing::Pre => effective.try_into().unwrap(),
-            Indexing::Post => address.try_into().unwrap(),
+        let address = match indexing {
+            Indexing::Pre => effective,
+            Indexing::Post => address,
         };
 
         match load_store_kind {
@@ -618,12 +651,12 @@ impl Arm7tdmi {
                         .set_register_at(source_destination_register as usize, v.into());
                 }
                 HalfwordTransferKind::SignedByte => {
-                    let v = self.bus.read_byte(address) as u32;
+                    let v = u32::from(self.bus.read_byte(address));
                     self.registers
                         .set_register_at(source_destination_register as usize, v.sign_extended(8));
                 }
                 HalfwordTransferKind::SignedHalfwords => {
-                    let v = self.bus.read_half_word(address) as u32;
+                    let v = u32::from(self.bus.read_half_word(address));
                     self.registers
                         .set_register_at(source_destination_register as usize, v.sign_extended(16));
                 }
@@ -635,6 +668,10 @@ impl Arm7tdmi {
                 .set_register_at(base_register.try_into().unwrap(), effective);
         }
 
+        if load_store_kind == LoadStoreKind::Load {
+            self.bus.idle(1);
+        }
+
         if load_store_kind == LoadStoreKind::Load
             && source_destination_register == REG_PROGRAM_COUNTER
         {
@@ -665,9 +702,8 @@ impl Arm7tdmi {
                 shift_kind,
                 reg_offset,
             } => {
-                let v = self.registers.register_at(reg_offset.try_into().unwrap());
-                let r = shift(shift_kind, shift_amount, v, self.cpsr.carry_flag());
-                r.result
+                let rm = self.registers.register_at(reg_offset.try_into().unwrap());
+                transfer_offset(shift_kind, shift_amount, rm, self.cpsr.carry_flag())
             }
         };
 
@@ -681,26 +717,27 @@ impl Arm7tdmi {
                 // write back is always true when using post indexing
                 self.registers
                     .set_register_at(base_register as usize, offset_address);
-                address as usize
+                address
             }
             Indexing::Pre => {
                 if write_back {
                     self.registers
-                        .set_register_at(offset_address as usize, base_register);
+                        .set_register_at(base_register as usize, offset_address);
                 }
-                offset_address as usize
+                offset_address
             }
         };
 
         match kind {
             SingleDataTransferKind::Ldr => match quantity {
                 ReadWriteKind::Byte => {
-                    let value = self.bus.read_byte(address) as u32;
+                    let value = u32::from(self.bus.read_byte(address));
                     self.registers
                         .set_register_at(rd.try_into().unwrap(), value);
                 }
                 ReadWriteKind::Word => {
-                    let v = self.bus.read_word(address);
+                    // Unaligned words are rotated, see `Arm7tdmi::read_word`.
+                    let v = self.read_word(address);
                     self.registers.set_register_at(rd.try_into().unwrap(), v);
                 }
             },
@@ -729,6 +766,10 @@ impl Arm7tdmi {
             SingleDataTransferKind::Pld => todo!("implement single data transfer operation"),
         }
 
+        if kind == SingleDataTransferKind::Ldr {
+            self.bus.idle(1);
+        }
+
         // If LDR and Rd == R15 we flush the pipeline
         if kind == SingleDataTransferKind::Ldr && rd == REG_PROGRAM_COUNTER {
             self.flush_pipeline();
@@ -748,7 +789,7 @@ impl Arm7tdmi {
     ) {
         let base_register = rn.try_into().unwrap();
         let memory_base = self.registers.register_at(base_register);
-        let mut address = memory_base.try_into().unwrap();
+        let mut address = memory_base;
 
         if load_psr {
             unimplemented!();
@@ -756,18 +797,18 @@ impl Arm7tdmi {
 
         let transfer = match load_store {
             LoadStoreKind::Store => {
-                |arm: &mut Self, address: usize, reg_source: usize| {
+                |arm: &mut Self, address: u32, reg_source: usize| {
                     let mut value = arm.registers.register_at(reg_source);
 
                     // If R15 we get the value of the current instruction + 4 (it is +8 already)
-                    if reg_source == REG_PROGRAM_COUNTER.try_into().unwrap() {
+                    if reg_source == REG_PROGRAM_COUNTER as usize {
                         value += 4;
                     }
 
                     arm.bus.write_word(address, value);
                 }
             }
-            LoadStoreKind::Load => |arm: &mut Self, address: usize, reg_destination: usize| {
+            LoadStoreKind::Load => |arm: &mut Self, address: u32, reg_destination: usize| {
                 let v = arm.bus.read_word(address);
                 arm.registers.set_register_at(reg_destination, v);
             },
@@ -776,10 +817,13 @@ impl Arm7tdmi {
         self.exec_data_transfer(reg_list, indexing, &mut address, offsetting, transfer);
 
         if write_back {
-            self.registers
-                .set_register_at(base_register, address.try_into().unwrap());
+            self.registers.set_register_at(base_register, address);
         };
 
+        if load_store == LoadStoreKind::Load {
+            self.bus.idle(1);
+        }
+
         // If LDM and R15 is in register list we flush the pipeline
         if load_store == LoadStoreKind::Load && reg_list.is_bit_on(15) {
             self.flush_pipeline();
@@ -790,15 +834,15 @@ impl Arm7tdmi {
         &mut self,
         reg_list: u32,
         indexing: Indexing,
-        address: &mut usize,
+        address: &mut u32,
         offsetting: Offsetting,
         transfer: F,
     ) where
-        F: Fn(&mut Self, usize, usize),
+        F: Fn(&mut Self, u32, usize),
     {
         let alignment = 4; // Since are word, the alignment is 4.
 
-        let change_address = |address: usize| match offsetting {
+        let change_address = |address: u32| match offsetting {
             Offsetting::Down => address.wrapping_sub(alignment),
             Offsetting::Up => address.wrapping_add(alignment),
         };
@@ -900,6 +944,8 @@ impl Arm7tdmi {
     ) {
         let rm_operand_value = self.registers.register_at(rm as usize);
         let rs_operand_value = self.registers.register_at(rs as usize);
+        self.bus
+            .idle(multiply_cycles(rs_operand_value, true) + u32::from(does_accumulate));
 
         let (mut result, _) = rm_operand_value.overflowing_mul(rs_operand_value);
         if does_accumulate {
@@ -927,6 +973,8 @@ impl Arm7tdmi {
     ) {
         let rm_operand_value = self.registers.register_at(rm as usize) as u64;
         let rs_operand_value = self.registers.register_at(rs as usize) as u64;
+        self.bus
+            .idle(multiply_cycles(rs_operand_value as u32, false) + 1 + u32::from(does_accumulate));
 
         let (mut result, _) = rm_operand_value.overflowing_mul(rs_operand_value);
         if does_accumulate {
@@ -959,6 +1007,8 @@ impl Arm7tdmi {
     ) {
         let rm_operand_value = self.registers.register_at(rm as usize);
         let rs_operand_value = self.registers.register_at(rs as usize);
+        self.bus
+            .idle(multiply_cycles(rs_operand_value, true) + 1 + u32::from(does_accumulate));
         let rm_operand_value_sgn: i64 = (rm_operand_value as i32) as i64;
         let rs_operand_value_sgn: i64 = (rs_operand_value as i32) as i64;
 
@@ -991,6 +1041,8 @@ mod tests {
     use crate::cpu::arm::alu_instruction::{AluSecondOperandInfo, ShiftOperator};
     use crate::cpu::arm::instructions::ArmModeInstruction::SingleDataTransfer;
     use crate::cpu::arm::instructions::{ArmModeInstruction, SingleDataTransferOffsetInfo};
+    use crate::cpu::arm::mode::ArmModeOpcode;
+    use crate::cpu::asm::ArmAsm;
     use crate::cpu::condition::Condition;
     use crate::cpu::flags::ShiftKind;
 
@@ -1059,6 +1111,74 @@ mod tests {
         assert_eq!(b, 0b00000000_11111111_u32);
     }
 
+    #[test]
+    fn transfer_offsets_of_every_shift() {
+        // `LDR R1, [R0, R2, kind #amount]!` with R2 = 0x8000_0003, returns the new base.
+        let base = |kind: ShiftKind, amount: u32, carry: bool| {
+            let mut cpu = Arm7tdmi::default();
+            cpu.registers.set_register_at(0, 0x0300_0000);
+            cpu.registers.set_register_at(2, 0x8000_0003);
+            cpu.cpsr.set_carry_flag(carry);
+            let op_code = ArmAsm::ldr(1)
+                .base(0)
+                .reg(2)
+                .shift(kind, amount)
+                .write_back();
+            cpu.execute_arm(op_code.encode());
+
+            // The flags never change.
+            assert_eq!(cpu.cpsr.carry_flag(), carry);
+            cpu.registers.register_at(0).wrapping_sub(0x0300_0000)
+        };
+
+        assert_eq!(base(ShiftKind::Lsl, 0, false), 0x8000_0003);
+        assert_eq!(base(ShiftKind::Lsl, 2, false), 0x0000_000C);
+        assert_eq!(base(ShiftKind::Lsr, 1, false), 0x4000_0001);
+        // LSR #32, ASR #32
+        assert_eq!(base(ShiftKind::Lsr, 0, false), 0);
+        assert_eq!(base(ShiftKind::Asr, 1, false), 0xC000_0001);
+        assert_eq!(base(ShiftKind::Asr, 0, false), 0xFFFF_FFFF);
+        assert_eq!(base(ShiftKind::Ror, 4, false), 0x3800_0000);
+        // RRX
+        assert_eq!(base(ShiftKind::Ror, 0, false), 0x4000_0001);
+        assert_eq!(base(ShiftKind::Ror, 0, true), 0xC000_0001);
+    }
+
+    #[test]
+    fn multiply_cycles_stop_on_sign_bytes() {
+        assert_eq!(multiply_cycles(0x0000_00FF, false), 1);
+        assert_eq!(multiply_cycles(0x0000_FF00, false), 2);
+        assert_eq!(multiply_cycles(0x00FF_0000, false), 3);
+        assert_eq!(multiply_cycles(0xFF00_0000, false), 4);
+        assert_eq!(multiply_cycles(0xFFFF_FF80, true), 1);
+        assert_eq!(multiply_cycles(0xFFFF_FF80, false), 4);
+        assert_eq!(multiply_cycles(0xFF80_0000, true), 3);
+    }
+
+    #[test]
+    fn multiplies_add_their_internal_cycles() {
+        let cycles = |instruction: ArmAsm, rs: u32| {
+            let mut cpu = Arm7tdmi::default();
+            cpu.registers.set_register_at(1, 3);
+            cpu.registers.set_register_at(2, rs);
+            let before = cpu.bus.cycles();
+            cpu.execute_arm(instruction.encode());
+
+            cpu.bus.cycles() - before
+        };
+
+        assert_eq!(cycles(ArmAsm::mul(0, 1, 2), 0x10), 1);
+        assert_eq!(cycles(ArmAsm::mul(0, 1, 2), 0x0010_0000), 3);
+        assert_eq!(cycles(ArmAsm::mul(0, 1, 2), 0xFFFF_FFF0), 1);
+        // Accumulating takes one more, long multiplies too.
+        assert_eq!(cycles(ArmAsm::mla(0, 1, 2, 3), 0x10), 2);
+        assert_eq!(cycles(ArmAsm::umull(0, 3, 1, 2), 0x10), 2);
+        assert_eq!(cycles(ArmAsm::umull(0, 3, 1, 2), 0xFFFF_FFF0), 5);
+        assert_eq!(cycles(ArmAsm::smull(0, 3, 1, 2), 0xFFFF_FFF0), 2);
+        assert_eq!(cycles(ArmAsm::smlal(0, 3, 1, 2), 0x0010_0000), 5);
+        assert_eq!(cycles(ArmAsm::umlal(0, 3, 1, 2), 0x1000_0000), 6);
+    }
+
     #[test]
     fn check_cmn() {
         {
@@ -1068,7 +1188,7 @@ mod tests {
             assert!(!cpu.cpsr.zero_flag());
             assert!(!cpu.cpsr.carry_flag());
             assert!(!cpu.cpsr.overflow_flag());
-            cpu.execute_arm(Arm7tdmi::decode(op_code));
+            cpu.execute_arm(op_code);
             assert!(!cpu.cpsr.sign_flag());
             assert!(!cpu.cpsr.zero_flag());
             assert!(!cpu.cpsr.carry_flag());
@@ -1079,7 +1199,7 @@ mod tests {
     #[test]
     fn check_teq() {
         {
-            let op_code = 0b1110_00_1_1001_1_1100_0000_000000000001;
+            let op_code = ArmAsm::alu(ArmModeAluInstr::Teq, 0, 12).imm(1).encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
             assert_eq!(
@@ -1106,7 +1226,7 @@ mod tests {
             assert!(!cpu.cpsr.zero_flag());
             assert!(!cpu.cpsr.carry_flag());
             assert!(!cpu.cpsr.overflow_flag());
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
             assert!(cpu.cpsr.sign_flag());
             assert!(!cpu.cpsr.zero_flag());
             assert!(!cpu.cpsr.carry_flag());
@@ -1135,7 +1255,7 @@ mod tests {
             }
         }
         {
-            let op_code = 0b1110_00_0_1001_1_1001_0011_000000000000;
+            let op_code = ArmAsm::alu(ArmModeAluInstr::Teq, 3, 9).reg(0).encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
             assert_eq!(
@@ -1158,7 +1278,7 @@ mod tests {
             let rn = 9_usize;
             cpu.registers.set_register_at(rn, 100);
             cpu.cpsr.set_sign_flag(true); // set for later verify.
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
             assert!(!cpu.cpsr.sign_flag());
             assert!(!cpu.cpsr.zero_flag());
         }
@@ -1166,7 +1286,7 @@ mod tests {
 
     #[test]
     fn check_cmp() {
-        let op_code: u32 = 0b1110_00_1_1010_1_1110_0000_000000000000;
+        let op_code = ArmAsm::cmp(14).imm(0).encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1191,7 +1311,7 @@ mod tests {
         assert!(!cpu.cpsr.zero_flag());
         assert!(!cpu.cpsr.carry_flag());
         assert!(!cpu.cpsr.overflow_flag());
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
         assert!(!cpu.cpsr.sign_flag());
         assert!(cpu.cpsr.zero_flag());
         assert!(cpu.cpsr.carry_flag());
@@ -1201,7 +1321,10 @@ mod tests {
     #[test]
     fn check_orr() {
         {
-            let op_code: u32 = 0b0000_00_1_1100_0_1100_1100_000011000000;
+            let op_code = ArmAsm::alu(ArmModeAluInstr::Orr, 12, 12)
+                .imm(0xC0)
+                .cond(Condition::EQ)
+                .encode();
             let cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
             assert_eq!(
@@ -1232,7 +1355,7 @@ mod tests {
     #[test]
     fn check_mov() {
         {
-            let op_code: u32 = 0b0000_00_1_1101_0_0000_1110_000000000100;
+            let op_code = ArmAsm::mov(14).imm(4).cond(Condition::EQ).encode();
             let cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
             assert_eq!(
@@ -1256,7 +1379,7 @@ mod tests {
             }
         }
         {
-            let op_code: u32 = 0b1110_00_1_1101_0_0000_0000_000011011111;
+            let op_code = ArmAsm::mov(0).imm(0xDF).encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
             assert_eq!(
@@ -1286,7 +1409,7 @@ mod tests {
             assert!(!cpu.cpsr.zero_flag());
             assert!(!cpu.cpsr.carry_flag());
             assert!(!cpu.cpsr.overflow_flag());
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
             assert_eq!(cpu.registers.register_at(0), 0xDF);
             assert!(!cpu.cpsr.sign_flag());
             assert!(!cpu.cpsr.zero_flag());
@@ -1294,7 +1417,7 @@ mod tests {
             assert!(!cpu.cpsr.overflow_flag());
         }
         {
-            let op_code: u32 = 0b1110_00_1_1101_0_0000_1100_001100000001;
+            let op_code = ArmAsm::mov(12).imm(0x0400_0000).encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
             assert_eq!(
@@ -1321,7 +1444,7 @@ mod tests {
             assert!(!cpu.cpsr.zero_flag());
             assert!(!cpu.cpsr.carry_flag());
             assert!(!cpu.cpsr.overflow_flag());
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
             assert_eq!(cpu.registers.register_at(12), 0x4000000);
             assert!(!cpu.cpsr.sign_flag());
             assert!(!cpu.cpsr.zero_flag());
@@ -1333,7 +1456,7 @@ mod tests {
     #[test]
     fn check_add() {
         {
-            let op_code: u32 = 0b1110_00_1_0100_0_1111_0000_000000000001;
+            let op_code = ArmAsm::add(0, 15).imm(1).encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
             assert_eq!(
@@ -1360,7 +1483,7 @@ mod tests {
             assert!(!cpu.cpsr.zero_flag());
             assert!(!cpu.cpsr.carry_flag());
             assert!(!cpu.cpsr.overflow_flag());
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
             assert_eq!(cpu.registers.register_at(0), 16);
             assert!(!cpu.cpsr.sign_flag());
             assert!(!cpu.cpsr.zero_flag());
@@ -1368,7 +1491,7 @@ mod tests {
             assert!(!cpu.cpsr.overflow_flag());
         }
 
-        let op_code = 0b1110_00_1_0100_0_1111_0000_000000100000;
+        let op_code = ArmAsm::add(0, 15).imm(0x20).encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1384,7 +1507,7 @@ mod tests {
             }
         );
         cpu.registers.set_register_at(15, 15);
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
         assert_eq!(cpu.registers.register_at(0), 15 + 32);
     }
 
@@ -1392,7 +1515,10 @@ mod tests {
     fn check_add_pc_operand_shift_register() {
         // Case when R15 is used as operand and shift amount is taken from register:
         // R2 = R1 + (R15 << R3)
-        let op_code = 0b1110_00_0_0100_0_0001_0010_0011_0001_1111;
+        let op_code = ArmAsm::add(2, 1)
+            .reg(15)
+            .shift_reg(ShiftKind::Lsl, 3)
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1417,14 +1543,14 @@ mod tests {
         cpu.registers.set_register_at(15, 500);
         cpu.registers.set_register_at(3, 0);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(2), 500 + 4 + 10);
     }
 
     #[test]
     fn check_add_carry_bit() {
-        let op_code: u32 = 0b1110_00_0_0100_1_1111_0000_0000_0000_1110;
+        let op_code = ArmAsm::add(0, 15).reg(14).set_flags().encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1446,7 +1572,7 @@ mod tests {
 
         cpu.registers.set_register_at(15, (1 << 31) + 1);
         cpu.registers.set_register_at(14, 1 << 31);
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
         assert_eq!(cpu.registers.register_at(0), 1);
         assert!(cpu.cpsr.carry_flag());
         assert!(cpu.cpsr.overflow_flag());
@@ -1487,7 +1613,7 @@ mod tests {
             }
         );
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
         let rotated = rx.rotate_right(is * 2);
         assert_eq!(cpu.registers.register_at(rx.try_into().unwrap()), rotated);
     }
@@ -1495,7 +1621,7 @@ mod tests {
     #[test]
     fn check_mov_cpsr() {
         // Checks for Z flag
-        let op_code = 0b1110_00_0_1101_1_0000_0001_00000_00_0_0010;
+        let op_code = ArmAsm::mov(1).reg(2).set_flags().encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1515,24 +1641,27 @@ mod tests {
             }
         );
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert!(cpu.cpsr.zero_flag());
 
         // Checks for Z flag
-        let op_code = 0b1110_00_0_1101_1_0000_0001_00000_00_0_0010;
+        let op_code = ArmAsm::mov(1).reg(2).set_flags().encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
         cpu.registers.set_register_at(2, -5_i32 as u32);
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert!(cpu.cpsr.sign_flag());
     }
 
     #[test]
     fn shift_from_register_is_0() {
-        let op_code = 0b1110_00_0_0100_0_0000_0001_0011_0111_0010;
+        let op_code = ArmAsm::add(1, 0)
+            .reg(2)
+            .shift_reg(ShiftKind::Ror, 3)
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1556,14 +1685,14 @@ mod tests {
         cpu.registers.set_register_at(2, 11);
         cpu.registers.set_register_at(3, 8 << 8);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), 16);
     }
 
     #[test]
     fn check_and() {
-        let op_code = 0b1110_00_1_0000_0_0000_0001_0000_10101010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::And, 1, 0).imm(0xAA).encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1585,14 +1714,14 @@ mod tests {
         // All 1 except msb
         cpu.registers.set_register_at(0, 2_u32.pow(31) - 1);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), 0b10101010);
     }
 
     #[test]
     fn check_eor() {
-        let op_code = 0b1110_00_1_0001_0_0000_0001_0000_10101010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Eor, 1, 0).imm(0xAA).encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1613,7 +1742,7 @@ mod tests {
 
         cpu.registers.set_register_at(0, 0b11111111);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), 0b01010101);
     }
@@ -1639,7 +1768,7 @@ mod tests {
         }
         {
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b1110_00_1_1000_1_0000_0001_0000_00000000;
+            let op_code = ArmAsm::alu(ArmModeAluInstr::Tst, 1, 0).imm(0).encode();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
             assert_eq!(
@@ -1656,7 +1785,7 @@ mod tests {
             );
             cpu.cpsr.set_sign_flag(true);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
             assert!(cpu.cpsr.zero_flag());
             assert!(!cpu.cpsr.sign_flag());
         }
@@ -1664,7 +1793,7 @@ mod tests {
 
     #[test]
     fn check_bic() {
-        let op_code = 0b1110_00_1_1110_0_0000_0001_0000_10101010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Bic, 1, 0).imm(0xAA).encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1685,14 +1814,14 @@ mod tests {
 
         cpu.registers.set_register_at(0, 0b11111111);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), 0b01010101);
     }
 
     #[test]
     fn check_mvn() {
-        let op_code = 0b1110_00_1_1111_1_0000_0001_0000_11111111;
+        let op_code = ArmAsm::mvn(1).imm(0xFF).set_flags().encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1711,7 +1840,7 @@ mod tests {
             }
         );
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), (2_u32.pow(24) - 1) << 8);
         assert!(cpu.cpsr.sign_flag());
@@ -1719,7 +1848,7 @@ mod tests {
 
     #[test]
     fn check_sub() {
-        let op_code = 0b1110_00_0_0010_1_0000_0001_00000_00_0_0010;
+        let op_code = ArmAsm::sub(1, 0).reg(2).set_flags().encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1741,7 +1870,7 @@ mod tests {
 
         cpu.registers.set_register_at(0, 10);
         cpu.registers.set_register_at(2, 5);
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), 5);
         assert!(cpu.cpsr.carry_flag());
@@ -1750,10 +1879,10 @@ mod tests {
         assert!(!cpu.cpsr.sign_flag());
 
         //Covers carry logic
-        let op_code = 0b1110_00_0_0010_1_0000_0001_00000_00_0_0010;
+        let op_code = ArmAsm::sub(1, 0).reg(2).set_flags().encode();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         cpu.registers.set_register_at(2, 15);
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1) as i32, -5);
         assert!(!cpu.cpsr.carry_flag());
@@ -1762,7 +1891,7 @@ mod tests {
         assert!(!cpu.cpsr.zero_flag());
 
         // Covers overflow logic
-        let op_code = 0b1110_00_0_0010_1_0000_0001_00000_00_0_0010;
+        let op_code = ArmAsm::sub(1, 0).reg(2).set_flags().encode();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
             op_code.instruction,
@@ -1784,7 +1913,7 @@ mod tests {
         cpu.registers.set_register_at(0, 1);
         cpu.registers.set_register_at(2, i32::MIN as u32);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), (i32::MIN + 1) as u32);
         assert!(!cpu.cpsr.carry_flag());
@@ -1796,7 +1925,10 @@ mod tests {
     #[test]
     fn check_adc() {
         // Covers all flags=0
-        let op_code = 0b1110_00_0_0101_1_0000_0001_0000_0_00_0_0010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Adc, 1, 0)
+            .reg(2)
+            .set_flags()
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1820,7 +1952,7 @@ mod tests {
         cpu.registers.set_register_at(2, 1);
         cpu.cpsr.set_carry_flag(true);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), 3);
         assert!(!cpu.cpsr.carry_flag());
@@ -1829,7 +1961,10 @@ mod tests {
         assert!(!cpu.cpsr.sign_flag());
 
         // Covers carry during first sum
-        let op_code = 0b1110_00_0_0101_1_0000_0001_0000_0_00_0_0010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Adc, 1, 0)
+            .reg(2)
+            .set_flags()
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1853,7 +1988,7 @@ mod tests {
         cpu.registers.set_register_at(0, u32::MAX);
         cpu.registers.set_register_at(2, 1);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), 1);
         assert!(cpu.cpsr.carry_flag());
@@ -1862,7 +1997,10 @@ mod tests {
         assert!(!cpu.cpsr.sign_flag());
 
         // Covers carry during second sum
-        let op_code = 0b1110_00_0_0101_1_0000_0001_0000_0_00_0_0010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Adc, 1, 0)
+            .reg(2)
+            .set_flags()
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1886,7 +2024,7 @@ mod tests {
         cpu.registers.set_register_at(0, u32::MAX - 1);
         cpu.registers.set_register_at(2, 1);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), 0);
         assert!(cpu.cpsr.carry_flag());
@@ -1895,7 +2033,10 @@ mod tests {
         assert!(!cpu.cpsr.sign_flag());
 
         // Covers overflow during first sum
-        let op_code = 0b1110_00_0_0101_1_0000_0001_0000_0_00_0_0010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Adc, 1, 0)
+            .reg(2)
+            .set_flags()
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1920,7 +2061,7 @@ mod tests {
         cpu.registers.set_register_at(0, i32::MAX as u32);
         cpu.registers.set_register_at(2, 1);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), (1 << 31) + 1);
         assert!(!cpu.cpsr.carry_flag());
@@ -1929,7 +2070,10 @@ mod tests {
         assert!(cpu.cpsr.sign_flag());
 
         // Covers overflow during second sum
-        let op_code = 0b1110_00_0_0101_1_0000_0001_0000_0_00_0_0010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Adc, 1, 0)
+            .reg(2)
+            .set_flags()
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1954,7 +2098,7 @@ mod tests {
         cpu.registers.set_register_at(0, i32::MAX as u32 - 1);
         cpu.registers.set_register_at(2, 1);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), 1 << 31);
         assert!(!cpu.cpsr.carry_flag());
@@ -1966,7 +2110,10 @@ mod tests {
     #[test]
     fn check_sbc() {
         // Covers all flag=0
-        let op_code = 0b1110_00_0_0110_1_0000_0001_0000_0_00_0_0010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Sbc, 1, 0)
+            .reg(2)
+            .set_flags()
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -1990,7 +2137,7 @@ mod tests {
         cpu.registers.set_register_at(0, 10);
         cpu.registers.set_register_at(2, 5);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), 5);
         assert!(cpu.cpsr.carry_flag());
@@ -1999,7 +2146,10 @@ mod tests {
         assert!(!cpu.cpsr.sign_flag());
 
         // Covers carry during first diff
-        let op_code = 0b1110_00_0_0110_1_0000_0001_0000_0_00_0_0010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Sbc, 1, 0)
+            .reg(2)
+            .set_flags()
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -2023,7 +2173,7 @@ mod tests {
         cpu.registers.set_register_at(0, 0);
         cpu.registers.set_register_at(2, 1);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), -1_i32 as u32);
         assert!(cpu.cpsr.carry_flag());
@@ -2032,7 +2182,10 @@ mod tests {
         assert!(cpu.cpsr.sign_flag());
 
         // Covers carry during sum
-        let op_code = 0b1110_00_0_0110_1_0000_0001_0000_0_00_0_0010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Sbc, 1, 0)
+            .reg(2)
+            .set_flags()
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -2056,7 +2209,7 @@ mod tests {
         cpu.registers.set_register_at(0, u32::MAX);
         cpu.registers.set_register_at(2, 0);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), -1_i32 as u32);
         assert!(cpu.cpsr.carry_flag());
@@ -2065,7 +2218,10 @@ mod tests {
         assert!(cpu.cpsr.sign_flag());
 
         // Covers carry during second diff
-        let op_code = 0b1110_00_0_0110_1_0000_0001_0000_0_00_0_0010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Sbc, 1, 0)
+            .reg(2)
+            .set_flags()
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -2089,7 +2245,7 @@ mod tests {
         cpu.registers.set_register_at(0, 0);
         cpu.registers.set_register_at(2, 0);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), -1_i32 as u32);
         assert!(cpu.cpsr.carry_flag());
@@ -2098,7 +2254,10 @@ mod tests {
         assert!(cpu.cpsr.sign_flag());
 
         // Covers overflow during first diff
-        let op_code = 0b1110_00_0_0110_1_0000_0001_0000_0_00_0_0010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Sbc, 1, 0)
+            .reg(2)
+            .set_flags()
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -2122,7 +2281,7 @@ mod tests {
         cpu.registers.set_register_at(0, i32::MAX as u32);
         cpu.registers.set_register_at(2, -1_i32 as u32);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), 1 << 31);
         assert!(cpu.cpsr.carry_flag());
@@ -2131,7 +2290,10 @@ mod tests {
         assert!(cpu.cpsr.sign_flag());
 
         // Covers overflow during sum
-        let op_code = 0b1110_00_0_0110_1_0000_0001_0000_0_00_0_0010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Sbc, 1, 0)
+            .reg(2)
+            .set_flags()
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -2155,7 +2317,7 @@ mod tests {
         cpu.registers.set_register_at(0, i32::MAX as u32);
         cpu.registers.set_register_at(2, 0);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), i32::MAX as u32);
         assert!(cpu.cpsr.carry_flag());
@@ -2164,7 +2326,10 @@ mod tests {
         assert!(!cpu.cpsr.sign_flag());
 
         // Covers overflow during second diff
-        let op_code = 0b1110_00_0_0110_1_0000_0001_0000_0_00_0_0010;
+        let op_code = ArmAsm::alu(ArmModeAluInstr::Sbc, 1, 0)
+            .reg(2)
+            .set_flags()
+            .encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(
@@ -2188,7 +2353,7 @@ mod tests {
         cpu.registers.set_register_at(0, i32::MIN as u32);
         cpu.registers.set_register_at(2, 0);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), i32::MAX as u32);
         assert!(cpu.cpsr.carry_flag());
@@ -2235,7 +2400,7 @@ mod tests {
             cpu.cpsr.set_zero_flag(true);
             cpu.cpsr.set_sign_flag(true);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(
                 0b1111_00000000000000000000_110_10000,
@@ -2267,7 +2432,7 @@ mod tests {
 
             cpu.swap_mode(&Mode::Fiq);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(
                 cpu.registers.register_at(0),
@@ -2291,7 +2456,7 @@ mod tests {
 
             cpu.registers.set_register_at(0, 0b1111 << 28);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             // All flags set and User mode
             assert_eq!(0b1111_00000000000000000000_110_10000, u32::from(cpu.cpsr));
@@ -2313,7 +2478,7 @@ mod tests {
 
             cpu.registers.set_register_at(0, 0b1111 << 28 | (0b10001));
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             // All flags set and Fiq mode
             assert_eq!(u32::from(cpu.spsr), 0b1111 << 28 | (0b10001));
@@ -2341,7 +2506,7 @@ mod tests {
 
             cpu.registers.set_register_at(0, 0b1111 << 28);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             // All flags set and User mode
             assert_eq!(0b1111_00000000000000000000_110_10000, u32::from(cpu.cpsr));
@@ -2370,7 +2535,7 @@ mod tests {
             // Trying to change MODE bits to a User mode
             cpu.registers.set_register_at(0, 0b1111 << 28 | (0b10000));
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             // All flags set
             assert_eq!(u32::from(cpu.spsr), 0b1111 << 28);
@@ -2502,7 +2667,7 @@ mod tests {
             // simulate mem already contains something.
             cpu.bus.write_byte(0x03000068, 99);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
             assert_eq!(cpu.registers.register_at(13), 99);
             assert_eq!(cpu.registers.program_counter(), 0x03000050);
         }
@@ -2562,7 +2727,7 @@ mod tests {
             // then will be 0x03000050 + 8 (.wrapping_sub(offset))
             cpu.registers.set_program_counter(0x03000050);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             let bus = cpu.bus;
 
@@ -2601,7 +2766,7 @@ mod tests {
             cpu.registers.set_program_counter(0x03000050);
             cpu.registers.set_register_at(13, 50);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             let memory = cpu.bus.internal_memory;
 
@@ -2633,11 +2798,23 @@ mod tests {
         // simulate mem already contains something.
         // in u32 this is 16843009 00000001_00000001_00000001_00000001.
         cpu.bus.write_word(0x28, 0x01010101);
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
         assert_eq!(cpu.registers.register_at(13), 16843009);
         assert_eq!(cpu.registers.program_counter(), 0);
     }
 
+    #[test]
+    fn check_ldr_pre_indexed_write_back_at_the_top_of_memory() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.registers.set_register_at(1, 0xFFFF_FFF8);
+
+        let op_code = ArmAsm::ldr(0).base(1).offset(4).write_back().encode();
+        cpu.execute_arm(op_code);
+
+        assert_eq!(cpu.registers.register_at(1), 0xFFFF_FFFC);
+        assert_eq!(cpu.registers.register_at(0), 0);
+    }
+
     #[test]
     fn check_multiply_non_halfword_mul() {
         let mut cpu = Arm7tdmi::default();
@@ -2666,7 +2843,7 @@ mod tests {
 
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
         assert_eq!(
             cpu.registers.register_at(rd_destination_register as usize),
             10100_u32
@@ -2708,7 +2885,7 @@ mod tests {
 
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
         assert_eq!(
             cpu.registers.register_at(rd_destination_register as usize),
             10132_u32
@@ -2751,7 +2928,7 @@ mod tests {
 
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
         let rdhi_register_value: u64 =
             cpu.registers
                 .register_at(rdhi_destination_register as usize) as u64;
@@ -2801,7 +2978,7 @@ mod tests {
 
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
         let rdhi_register_value: u64 =
             cpu.registers
                 .register_at(rdhi_destination_register as usize) as u64;
@@ -2856,7 +3033,7 @@ mod tests {
 
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
         let rdhi_register_value: u64 =
             cpu.registers
                 .register_at(rdhi_destination_register as usize) as u64;
@@ -2919,7 +3096,7 @@ mod tests {
 
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
         let rdhi_register_value: u64 =
             cpu.registers
                 .register_at(rdhi_destination_register as usize) as u64;
@@ -1,5 +1,6 @@
-use std::convert::TryInto;
+use std::collections::BTreeMap;
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "logger")]
@@ -10,18 +11,42 @@ use vecfixed::VecFixed;
 use crate::bitwise::Bits;
 use crate::bus::Bus;
 use crate::cpu::arm;
-use crate::cpu::arm::instructions::ArmModeInstruction;
+#[cfg(feature = "serde")]
 use crate::cpu::arm::mode::ArmModeOpcode;
+use crate::cpu::asm::{assemble_arm, assemble_thumb, AssembleError};
+use crate::cpu::bios_hle::HleShortcut;
+#[cfg(feature = "debug-hooks")]
+use crate::cpu::breakpoint_condition::ConditionContext;
+use crate::cpu::breakpoints::StepResult;
+#[cfg(feature = "debug-hooks")]
+use crate::cpu::breakpoints::{Breakpoint, Breakpoints};
+use crate::cpu::condition::Condition;
 use crate::cpu::cpu_modes::Mode;
+#[cfg(feature = "debug-hooks")]
+use crate::cpu::execution_trap::NonExecutableRegion;
+use crate::cpu::execution_trap::{ExecutionTrap, MisalignedPc};
+#[cfg(feature = "debug-hooks")]
+use crate::cpu::instrumentation::{
+    CpuObserver, ExceptionEntry, ExecutedInstruction, ObserverId, Observers,
+};
+#[cfg(feature = "jit")]
+use crate::cpu::jit::{Jit, JitStats};
 use crate::cpu::psr::{CpuState, Psr};
 use crate::cpu::register_bank::RegisterBank;
-use crate::cpu::thumb::instruction::Instruction;
+#[cfg(feature = "serde")]
 use crate::cpu::thumb::mode::ThumbModeOpcode;
+#[cfg(feature = "debug-hooks")]
+use crate::cpu_trace::{CpuTraceEntry, CpuTraceWriter};
+use crate::memory_edit::{EditValue, MemoryEditError};
+use crate::memory_map::is_unmapped;
+#[cfg(feature = "serde")]
+use crate::save_state::Section;
 
 use super::registers::Registers;
 use super::thumb;
 
-#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Arm7tdmi {
     pub bus: Bus,
 
@@ -33,18 +58,111 @@ pub struct Arm7tdmi {
 
     #[cfg(feature = "disassembler")]
     pub disassembler_buffer: VecFixed<1000, String>,
+    /// Whether the executed instructions are written to [`Self::disassembler_buffer`].
+    #[cfg(feature = "disassembler")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    disassembly: bool,
 
     fetched_arm: Option<u32>,
-    decoded_arm: Option<ArmModeOpcode>,
+    decoded_arm: Option<u32>,
     fetched_thumb: Option<u16>,
-    decoded_thumb: Option<ThumbModeOpcode>,
+    decoded_thumb: Option<u16>,
 
+    /// Bus cycles spent executing, accesses and internal cycles included.
     pub current_cycle: u128,
+    /// See [`Self::instructions`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    instructions: u64,
+
+    /// Address of the last executed instruction that flushed the pipeline.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    last_jump_source: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    execution_trap: Option<ExecutionTrap>,
+    /// Every trap recorded since the CPU was created, taken or not, up to
+    /// [`MAX_LOGGED_TRAPS`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(not(feature = "debug-hooks"), allow(dead_code))]
+    trap_log: Vec<ExecutionTrap>,
+    /// Fetches whose program counter had to be aligned, and the first of them.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    misaligned_pc_count: u64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    first_misaligned_pc: Option<MisalignedPc>,
+    /// See [`Self::set_trap_on_misaligned_pc`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    trap_on_misaligned_pc: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    misaligned_pc: Option<MisalignedPc>,
+    /// See [`Self::set_abort_on_invalid_access`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    abort_on_invalid_access: bool,
+    /// See [`Self::set_bios_hle`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    bios_hle: bool,
+    /// See [`Self::set_max_accuracy`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    max_accuracy: bool,
+    /// Calls of the emulated BIOS functions by SWI number, see [`Self::hle_shortcuts`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    hle_calls: BTreeMap<u8, u64>,
+    /// An emulated `IntrWait` is executed again after each interrupt until it returns,
+    /// only the first time discards the flags.
+    pub(super) intr_waiting: bool,
+    /// See [`Self::set_cpu_trace`].
+    #[cfg(feature = "debug-hooks")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cpu_trace: Option<Box<dyn CpuTraceWriter>>,
+    #[cfg(feature = "debug-hooks")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    breakpoints: Breakpoints,
+    /// See [`Self::add_observer`].
+    #[cfg(feature = "debug-hooks")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    observers: Observers,
+
+    #[cfg(feature = "jit")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    jit: Jit,
+}
+
+/// Once stopped on a trap the CPU keeps executing garbage if resumed, the first traps
+/// are the interesting ones.
+#[cfg(feature = "debug-hooks")]
+const MAX_LOGGED_TRAPS: usize = 16;
+
+/// Cycles of a scanline: a halted CPU waits by this much at most per step, so the
+/// frontend can still pause or break in between.
+const HALT_STEP_CYCLES: u32 = 1232;
+
+/// What the condition of a breakpoint reads, borrowed apart from the breakpoints.
+#[cfg(feature = "debug-hooks")]
+struct CpuView<'a> {
+    registers: &'a Registers,
+    cpsr: Psr,
+    bus: &'a Bus,
 }
 
-#[derive(Copy, Clone)]
+#[cfg(feature = "debug-hooks")]
+impl ConditionContext for CpuView<'_> {
+    fn register(&self, index: usize) -> u32 {
+        self.registers.register_at(index)
+    }
+
+    fn cpsr(&self) -> u32 {
+        self.cpsr.into()
+    }
+
+    fn read_word(&self, address: u32) -> u32 {
+        (0..4).rev().fold(0, |word, offset| {
+            word << 8 | u32::from(self.bus.read_raw(address.wrapping_add(offset)))
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[allow(dead_code)]
-enum ExceptionType {
+pub enum ExceptionType {
     Reset,
     UndefinedInstruction,
     SoftwareInterrupt,
@@ -55,6 +173,8 @@ enum ExceptionType {
 }
 
 impl ExceptionType {
+    /// Address of the vector of the exception.
+    #[must_use]
     pub const fn address(self) -> usize {
         match self {
             Self::Reset => 0x0,
@@ -67,6 +187,8 @@ impl ExceptionType {
         }
     }
 
+    /// Mode the handler runs in.
+    #[must_use]
     pub const fn mode(self) -> Mode {
         match self {
             Self::SoftwareInterrupt | Self::Reset => Mode::Supervisor,
@@ -77,7 +199,7 @@ impl ExceptionType {
         }
     }
 
-    pub fn next_instruction_func(
+    pub(crate) fn next_instruction_func(
         self,
         current_state: CpuState,
         current_pc: usize,
@@ -120,11 +242,34 @@ impl Default for Arm7tdmi {
             register_bank: RegisterBank::default(),
             #[cfg(feature = "disassembler")]
             disassembler_buffer: VecFixed::new(),
+            #[cfg(feature = "disassembler")]
+            disassembly: true,
             fetched_arm: None,
             decoded_arm: None,
             fetched_thumb: None,
             decoded_thumb: None,
             current_cycle: u128::default(),
+            instructions: 0,
+            last_jump_source: None,
+            execution_trap: None,
+            trap_log: Vec::new(),
+            misaligned_pc_count: 0,
+            first_misaligned_pc: None,
+            trap_on_misaligned_pc: false,
+            misaligned_pc: None,
+            abort_on_invalid_access: false,
+            bios_hle: false,
+            max_accuracy: false,
+            hle_calls: BTreeMap::new(),
+            intr_waiting: false,
+            #[cfg(feature = "debug-hooks")]
+            cpu_trace: None,
+            #[cfg(feature = "debug-hooks")]
+            breakpoints: Breakpoints::default(),
+            #[cfg(feature = "debug-hooks")]
+            observers: Observers::default(),
+            #[cfg(feature = "jit")]
+            jit: Jit::default(),
         };
 
         // Setting ARM mode at startup
@@ -137,7 +282,10 @@ impl Default for Arm7tdmi {
 }
 
 impl Arm7tdmi {
-    pub fn flush_pipeline(&mut self) {
+    /// Empties the pipeline after a write to PC. It costs nothing here: the next two
+    /// steps only fetch, a non-sequential access to the target and a sequential one,
+    /// which with the fetch of the flushing instruction make the 2S+1N of a branch.
+    pub(crate) fn flush_pipeline(&mut self) {
         self.decoded_arm = None;
         self.decoded_thumb = None;
         self.fetched_arm = None;
@@ -145,29 +293,351 @@ impl Arm7tdmi {
     }
 
     #[must_use]
-    pub fn fetch_arm(&mut self) -> u32 {
+    pub(crate) fn fetch_arm(&mut self) -> u32 {
         let mut pc = self.registers.program_counter() as u32;
         pc.set_bit_off(0);
         pc.set_bit_off(1);
+        self.check_alignment(pc, false);
         self.registers.set_program_counter(pc);
+        self.check_execution_region(pc);
 
-        self.bus.read_word(pc as usize)
+        let op_code = self.bus.fetch_word(pc);
+        self.forget_invalid_fetch(pc);
+
+        op_code
     }
 
     #[must_use]
-    pub fn fetch_thumb(&mut self) -> u16 {
+    pub(crate) fn fetch_thumb(&mut self) -> u16 {
         let mut pc = self.registers.program_counter() as u32;
         pc.set_bit_off(0);
+        self.check_alignment(pc, true);
         self.registers.set_program_counter(pc);
+        self.check_execution_region(pc);
+
+        let op_code = self.bus.fetch_half_word(pc);
+        self.forget_invalid_fetch(pc);
+
+        op_code
+    }
+
+    /// Counts the fetches where `aligned`, the program counter to fetch from, differs
+    /// from the one the last instruction left.
+    fn check_alignment(&mut self, aligned: u32, thumb: bool) {
+        let address = self.registers.program_counter() as u32;
+        if address == aligned {
+            return;
+        }
+
+        let misaligned = MisalignedPc {
+            address,
+            thumb,
+            jump_source: self.last_jump_source,
+            cycle: self.bus.cycles(),
+        };
+
+        self.misaligned_pc_count += 1;
+        self.first_misaligned_pc.get_or_insert(misaligned);
+        if self.trap_on_misaligned_pc && self.misaligned_pc.is_none() {
+            self.misaligned_pc = Some(misaligned);
+        }
+    }
+
+    /// Off by default: fetches from a misaligned program counter are only counted, see
+    /// [`Self::misaligned_pc_count`].
+    ///
+    /// On, the first one is also kept until it's taken with [`Self::take_misaligned_pc`],
+    /// for the frontend to stop there.
+    pub const fn set_trap_on_misaligned_pc(&mut self, enabled: bool) {
+        self.trap_on_misaligned_pc = enabled;
+    }
+
+    #[must_use]
+    pub const fn trap_on_misaligned_pc(&self) -> bool {
+        self.trap_on_misaligned_pc
+    }
+
+    /// Returns the pending [`MisalignedPc`], if any, clearing it.
+    /// Always `None` unless [`Self::set_trap_on_misaligned_pc`] is on.
+    pub const fn take_misaligned_pc(&mut self) -> Option<MisalignedPc> {
+        self.misaligned_pc.take()
+    }
+
+    /// Fetches since the CPU was created whose program counter had to be aligned.
+    #[must_use]
+    pub const fn misaligned_pc_count(&self) -> u64 {
+        self.misaligned_pc_count
+    }
+
+    #[must_use]
+    pub const fn first_misaligned_pc(&self) -> Option<MisalignedPc> {
+        self.first_misaligned_pc
+    }
+
+    /// Fetching from an unmapped address raises a prefetch abort only when the instruction
+    /// is executed, not a data abort.
+    fn forget_invalid_fetch(&mut self, pc: u32) {
+        if self.bus.invalid_access() == Some(pc) {
+            self.bus.take_invalid_access();
+        }
+    }
+
+    /// Records an [`ExecutionTrap`] when fetching from a region that can't hold code.
+    /// Only the first one is kept until it's taken with [`Self::take_execution_trap`].
+    #[cfg(feature = "debug-hooks")]
+    fn check_execution_region(&mut self, pc: u32) {
+        if self.execution_trap.is_some() {
+            return;
+        }
+
+        if let Some(region) = NonExecutableRegion::from_address(pc) {
+            let trap = ExecutionTrap {
+                address: pc,
+                region,
+                jump_source: self.last_jump_source,
+                cycle: self.bus.cycles(),
+            };
+
+            self.execution_trap = Some(trap);
+            if self.trap_log.len() < MAX_LOGGED_TRAPS {
+                self.trap_log.push(trap);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "debug-hooks"))]
+    #[allow(clippy::unused_self)]
+    const fn check_execution_region(&self, _pc: u32) {}
+
+    /// Records every following executed instruction in `trace`, or stops tracing with
+    /// `None`. The previous writer is returned so that it can be flushed.
+    #[cfg(feature = "debug-hooks")]
+    pub fn set_cpu_trace(
+        &mut self,
+        trace: Option<Box<dyn CpuTraceWriter>>,
+    ) -> Option<Box<dyn CpuTraceWriter>> {
+        std::mem::replace(&mut self.cpu_trace, trace)
+    }
+
+    /// Calls `observer` on every following instruction, exception, mode change and
+    /// `SWI`, after the observers already added. The observers are kept across resets.
+    #[cfg(feature = "debug-hooks")]
+    pub fn add_observer(&mut self, observer: Box<dyn CpuObserver>) -> ObserverId {
+        self.observers.add(observer)
+    }
+
+    /// Returns the observer, `None` if it was already removed.
+    #[cfg(feature = "debug-hooks")]
+    pub fn remove_observer(&mut self, id: ObserverId) -> Option<Box<dyn CpuObserver>> {
+        self.observers.remove(id)
+    }
+
+    #[cfg(feature = "debug-hooks")]
+    fn notify_executed(&mut self, address: u32, opcode: u32, thumb: bool, start: u128) {
+        if self.observers.is_empty() {
+            return;
+        }
+
+        let instruction = ExecutedInstruction {
+            address,
+            opcode,
+            thumb,
+            cycles: self.bus.cycles() - start,
+        };
+        self.observers
+            .each(|observer| observer.on_instruction_executed(&instruction));
+    }
+
+    /// Stops every following step about to execute the instruction of the `breakpoint`,
+    /// replacing the breakpoint at the same address. The breakpoints are kept across
+    /// resets.
+    #[cfg(feature = "debug-hooks")]
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.insert(breakpoint);
+    }
+
+    /// Returns whether there was a breakpoint at `address`.
+    #[cfg(feature = "debug-hooks")]
+    pub fn remove_breakpoint(&mut self, address: u32) -> bool {
+        self.breakpoints.remove(address)
+    }
+
+    #[cfg(feature = "debug-hooks")]
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// The breakpoints, sorted by address.
+    #[cfg(feature = "debug-hooks")]
+    #[must_use]
+    pub fn breakpoints(&self) -> Vec<Breakpoint> {
+        self.breakpoints.list()
+    }
+
+    /// Address of the instruction the step would execute if it has a breakpoint. A halted
+    /// CPU or a refilling pipeline executes nothing and never hits.
+    #[cfg(feature = "debug-hooks")]
+    fn breakpoint_hit(&mut self) -> Option<u32> {
+        if self.bus.halted() {
+            return None;
+        }
+
+        let address = self.next_instruction_address()?;
+        let thumb = matches!(self.cpsr.cpu_state(), CpuState::Thumb);
+
+        let context = CpuView {
+            registers: &self.registers,
+            cpsr: self.cpsr,
+            bus: &self.bus,
+        };
+
+        self.breakpoints
+            .hit(address, thumb, &context)
+            .then_some(address)
+    }
+
+    #[cfg(feature = "debug-hooks")]
+    fn trace_instruction(&mut self, address: u32, thumb: bool) {
+        let Some(trace) = self.cpu_trace.as_mut() else {
+            return;
+        };
+
+        let size = if thumb { 2 } else { 4 };
+        let opcode = (0..size).rev().fold(0, |opcode, offset| {
+            opcode << 8 | u32::from(self.bus.read_raw(address + offset))
+        });
+
+        let entry = CpuTraceEntry {
+            registers: std::array::from_fn(|index| self.registers.register_at(index)),
+            cpsr: self.cpsr.into(),
+            address,
+            opcode,
+            thumb,
+        };
+
+        if let Err(err) = trace.record(&entry) {
+            logger::log(format!("CPU trace stopped: {err}"));
+            self.cpu_trace = None;
+        }
+    }
+
+    /// The pending [`ExecutionTrap`], left for [`Self::take_execution_trap`].
+    #[must_use]
+    pub const fn execution_trap(&self) -> Option<&ExecutionTrap> {
+        self.execution_trap.as_ref()
+    }
+
+    /// Returns the pending [`ExecutionTrap`], if any, clearing it.
+    /// Always `None` without the `debug-hooks` feature.
+    pub const fn take_execution_trap(&mut self) -> Option<ExecutionTrap> {
+        self.execution_trap.take()
+    }
+
+    /// The first traps recorded since the CPU was created, including the taken ones.
+    #[must_use]
+    pub fn trap_log(&self) -> &[ExecutionTrap] {
+        &self.trap_log
+    }
+
+    /// Assembles `source` as ARM or Thumb code and writes it at `address`,
+    /// returning the number of bytes written.
+    /// Instructions already in the pipeline keep their old value, as on hardware.
+    ///
+    /// # Errors
+    /// It fails if `address` isn't aligned or if `source` can't be assembled,
+    /// memory is untouched in this case.
+    pub fn patch_instruction(
+        &mut self,
+        address: u32,
+        source: &str,
+        thumb: bool,
+    ) -> Result<usize, AssembleError> {
+        let alignment = if thumb { 2 } else { 4 };
+        if !address.is_multiple_of(alignment) {
+            return Err(AssembleError::Misaligned {
+                value: address.into(),
+                alignment: alignment.into(),
+            });
+        }
+
+        let bytes = if thumb {
+            assemble_thumb(source, address)?
+                .into_iter()
+                .flat_map(u16::to_le_bytes)
+                .collect::<Vec<_>>()
+        } else {
+            assemble_arm(source, address)?.to_le_bytes().to_vec()
+        };
+
+        for (offset, byte) in (0..).zip(&bytes) {
+            self.bus.write_raw(address.wrapping_add(offset), *byte);
+        }
+        #[cfg(feature = "jit")]
+        self.jit.invalidate();
 
-        self.bus.read_half_word(pc as usize)
+        Ok(bytes.len())
     }
 
-    /// This function is used to execute the Data Processing instruction.
+    /// Writes `value` at `address` for a debugging tool, see [`Bus::debug_write`].
+    /// Unlike [`Self::patch_instruction`], overwritten instructions already in the
+    /// pipeline are fetched again: the edit is visible from the next step.
+    ///
+    /// # Errors
+    /// It fails if one of the bytes is read-only or unmapped.
+    pub fn debug_write(&mut self, address: u32, value: EditValue) -> Result<(), MemoryEditError> {
+        self.bus.debug_write(address, value)?;
+        #[cfg(feature = "jit")]
+        self.jit.invalidate();
+
+        let written = address..address + value.width();
+        let pc = self.registers.program_counter() as u32;
+        // Between two steps the fetched instruction is one instruction behind the program
+        // counter and the decoded one two instructions behind.
+        match self.cpsr.cpu_state() {
+            CpuState::Arm => {
+                let fetched = pc.wrapping_sub(arm::operations::SIZE_OF_INSTRUCTION);
+                let decoded = fetched.wrapping_sub(arm::operations::SIZE_OF_INSTRUCTION);
+                let read = |bus: &Bus, address: u32| {
+                    u32::from_le_bytes(std::array::from_fn(|i| {
+                        bus.read_raw(address.wrapping_add(i as u32))
+                    }))
+                };
+
+                if self.fetched_arm.is_some() && written.contains(&fetched) {
+                    self.fetched_arm = Some(read(&self.bus, fetched));
+                }
+                if self.decoded_arm.is_some() && written.contains(&decoded) {
+                    self.decoded_arm = Some(read(&self.bus, decoded));
+                }
+            }
+            CpuState::Thumb => {
+                let fetched = pc.wrapping_sub(thumb::operations::SIZE_OF_INSTRUCTION);
+                let decoded = fetched.wrapping_sub(thumb::operations::SIZE_OF_INSTRUCTION);
+                let read = |bus: &Bus, address: u32| {
+                    u16::from_le_bytes(std::array::from_fn(|i| {
+                        bus.read_raw(address.wrapping_add(i as u32))
+                    }))
+                };
+
+                if self.fetched_thumb.is_some() && written.contains(&fetched) {
+                    self.fetched_thumb = Some(read(&self.bus, fetched));
+                }
+                if self.decoded_thumb.is_some() && written.contains(&decoded) {
+                    self.decoded_thumb = Some(read(&self.bus, decoded));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decodes `op_code` by testing every kind of instruction in turn. The pipeline uses
+    /// the lookup tables of [`arm::lut`] and [`thumb::lut`] instead.
     ///
     /// # Panics
-    /// It can panics if `op_code` is not a `DataProcessing` instruction.
-    pub fn decode<T, V>(op_code: V) -> T
+    /// It can panics if `op_code` is not a valid instruction.
+    #[cfg(test)]
+    pub(crate) fn decode<T, V>(op_code: V) -> T
     where
         T: std::fmt::Display + TryFrom<V>,
         <T as TryFrom<V>>::Error: std::fmt::Debug,
@@ -175,259 +645,57 @@ impl Arm7tdmi {
         T::try_from(op_code).unwrap()
     }
 
-    #[allow(clippy::too_many_lines)]
-    pub fn execute_arm(&mut self, op_code: ArmModeOpcode) {
-        // Instruction functions should return whether PC has to be advanced
-        // after instruction executed.
-        if !self.cpsr.can_execute(op_code.condition) {
+    /// Runs the ARM instruction `op_code` with the handler of its kind, see [`arm::lut`].
+    pub(crate) fn execute_arm(&mut self, op_code: u32) {
+        if !self
+            .cpsr
+            .can_execute(Condition::from(op_code.get_bits(28..=31) as u8))
+        {
             return;
         }
 
         #[cfg(feature = "disassembler")]
-        {
+        if self.disassembly {
             let decimal_value = self.registers.program_counter();
             let padded_hex_value = format!("{decimal_value:#04X}");
             self.disassembler_buffer.push(format!(
                 "{}: {}",
                 padded_hex_value,
-                op_code.instruction.disassembler()
+                arm::lut::decode(op_code).instruction.disassembler()
             ));
         }
 
-        match op_code.instruction {
-            ArmModeInstruction::DataProcessing {
-                condition: _,
-                alu_instruction,
-                set_conditions,
-                op_kind,
-                rn,
-                destination,
-                op2: _,
-            } => self.data_processing(
-                op_code,
-                alu_instruction,
-                set_conditions,
-                op_kind,
-                rn,
-                destination,
-            ),
-            ArmModeInstruction::PSRTransfer { psr_kind, kind, .. } => {
-                self.psr_transfer(kind, psr_kind);
-            }
-            ArmModeInstruction::Multiply {
-                variant,
-                should_set_codes,
-                rd_destination_register,
-                rn_accumulate_register,
-                rm_operand_register,
-                rs_operand_register,
-                ..
-            } => self.multiply(
-                variant,
-                should_set_codes,
-                rd_destination_register,
-                rn_accumulate_register,
-                rm_operand_register,
-                rs_operand_register,
-            ),
-            ArmModeInstruction::MultiplyLong {
-                variant,
-                should_set_codes,
-                rdhi_destination_register,
-                rdlo_destination_register,
-                rm_operand_register,
-                rs_operand_register,
-                ..
-            } => self.multiply_long(
-                variant,
-                should_set_codes,
-                rdhi_destination_register,
-                rdlo_destination_register,
-                rm_operand_register,
-                rs_operand_register,
-            ),
-            ArmModeInstruction::SingleDataSwap => todo!(),
-            ArmModeInstruction::BranchAndExchange {
-                condition: _,
-                register,
-            } => self.branch_and_exchange(register),
-            ArmModeInstruction::HalfwordDataTransfer {
-                condition: _,
-                indexing,
-                offsetting,
-                write_back,
-                load_store_kind,
-                offset_kind,
-                base_register,
-                source_destination_register,
-                transfer_kind,
-            } => self.half_word_data_transfer(
-                indexing,
-                offsetting,
-                write_back,
-                load_store_kind,
-                offset_kind,
-                base_register,
-                source_destination_register,
-                transfer_kind,
-            ),
-            ArmModeInstruction::SingleDataTransfer {
-                condition: _,
-                kind,
-                quantity,
-                write_back,
-                indexing,
-                rd,
-                base_register,
-                offset_info,
-                offsetting,
-            } => self.single_data_transfer(
-                kind,
-                quantity,
-                write_back,
-                indexing,
-                rd,
-                base_register,
-                offset_info,
-                offsetting,
-            ),
-            ArmModeInstruction::Undefined => todo!(),
-            ArmModeInstruction::BlockDataTransfer {
-                condition: _,
-                indexing,
-                offsetting,
-                load_psr,
-                write_back,
-                load_store,
-                rn,
-                register_list: reg_list,
-            } => self.block_data_transfer(
-                indexing, offsetting, load_psr, write_back, load_store, rn, reg_list,
-            ),
-            ArmModeInstruction::Branch {
-                condition: _,
-                link,
-                offset,
-            } => self.branch(link, offset),
-            ArmModeInstruction::CoprocessorDataTransfer { .. } => todo!(),
-            ArmModeInstruction::CoprocessorDataOperation => todo!(),
-            ArmModeInstruction::CoprocessorRegisterTransfer => todo!(),
-            ArmModeInstruction::SoftwareInterrupt => {
-                self.handle_exception(ExceptionType::SoftwareInterrupt);
-            }
-        };
+        arm::lut::execute(self, op_code);
     }
 
-    /// This function is used to execute the Data Processing instruction.
-    ///
-    /// # Panics
-    /// It can panics if destination register is None.
-    #[allow(clippy::too_many_lines)]
-    pub fn execute_thumb(&mut self, op_code: ThumbModeOpcode) {
+    /// Runs the Thumb instruction `op_code` with the handler of its kind, see
+    /// [`thumb::lut`].
+    pub(crate) fn execute_thumb(&mut self, op_code: u16) {
         #[cfg(feature = "disassembler")]
-        {
+        if self.disassembly {
             let decimal_value = self.registers.program_counter();
             let padded_hex_value = format!("{decimal_value:#04X}");
             self.disassembler_buffer.push(format!(
                 "{padded_hex_value}: {}",
-                op_code.instruction.disassembler()
+                thumb::lut::decode(op_code).instruction.disassembler()
             ));
         }
 
-        match op_code.instruction {
-            Instruction::MoveShiftedRegister {
-                shift_operation: op,
-                offset5,
-                source_register,
-                destination_register,
-            } => self.move_shifted_reg(op, offset5, source_register, destination_register),
-            Instruction::AddSubtract {
-                operation_kind,
-                op,
-                rn_offset3,
-                source_register: rs,
-                destination_register: rd,
-            } => self.add_subtract(operation_kind, op, rn_offset3, rs, rd),
-            Instruction::MoveCompareAddSubtractImm {
-                operation: op,
-                destination_register: r_destination,
-                offset,
-            } => self.move_compare_add_sub_imm(op, r_destination, offset),
-            Instruction::AluOp {
-                alu_operation: op,
-                source_register: rs,
-                destination_register: rd,
-            } => self.alu_op(op, rs, rd),
-            Instruction::HiRegisterOpBX {
-                register_operation: op,
-                source_register,
-                destination_register,
-            } => self.hi_reg_operation_branch_ex(op, source_register, destination_register),
-            Instruction::PCRelativeLoad {
-                destination_register: r_destination,
-                immediate_value,
-            } => self.pc_relative_load(r_destination, immediate_value),
-            Instruction::LoadStoreRegisterOffset {
-                load_store,
-                byte_word,
-                ro,
-                base_register: rb,
-                destination_register: rd,
-            } => self.load_store_register_offset(load_store, byte_word, ro, rb, rd),
-            Instruction::LoadStoreSignExtByteHalfword {
-                h: h_flag,
-                sign_extend_flag,
-                offset_register: r_offset,
-                base_register: r_base,
-                destination_register: r_destination,
-            } => self.load_store_sign_extend_byte_halfword(
-                h_flag,
-                sign_extend_flag,
-                r_offset,
-                r_base,
-                r_destination,
-            ),
-            Instruction::LoadStoreImmOffset => self.load_store_immediate_offset(op_code),
-            Instruction::LoadStoreHalfword {
-                load_store,
-                offset,
-                base_register,
-                source_destination_register,
-            } => self.load_store_halfword(
-                load_store,
-                offset,
-                base_register,
-                source_destination_register,
-            ),
-            Instruction::SPRelativeLoadStore {
-                load_store,
-                destination_register: r_destination,
-                word8,
-            } => self.sp_relative_load_store(load_store, r_destination, word8),
-            Instruction::LoadAddress {
-                sp,
-                destination_register: r_destination,
-                offset,
-            } => self.load_address(sp, r_destination.try_into().unwrap(), offset),
-            Instruction::AddOffsetSP { s, word7 } => self.add_offset_sp(s, word7),
-            Instruction::PushPopReg {
-                load_store,
-                pc_lr,
-                register_list,
-            } => self.push_pop_register(load_store, pc_lr, register_list),
-            Instruction::MultipleLoadStore {
-                load_store,
-                base_register,
-                register_list,
-            } => self.multiple_load_store(load_store, base_register as usize, register_list),
-            Instruction::CondBranch {
-                condition,
-                immediate_offset,
-            } => self.cond_branch(condition, immediate_offset),
-            Instruction::Swi => unimplemented!(),
-            Instruction::UncondBranch { offset } => self.uncond_branch(offset),
-            Instruction::LongBranchLink { h, offset } => self.long_branch_link(h, offset),
-        };
+        thumb::lut::execute(self, op_code);
+    }
+
+    /// Runs the BIOS function `number` in place of the BIOS if it's emulated, see
+    /// [`Self::set_bios_hle`].
+    pub(crate) fn software_interrupt(&mut self, number: u8) {
+        #[cfg(feature = "debug-hooks")]
+        self.observers.each(|observer| observer.on_swi(number));
+
+        if self.bios_hle && !self.max_accuracy && self.handle_swi_hle(number) {
+            *self.hle_calls.entry(number).or_default() += 1;
+            return;
+        }
+
+        self.handle_exception(ExceptionType::SoftwareInterrupt);
     }
 
     fn handle_exception(&mut self, exception_type: ExceptionType) {
@@ -445,6 +713,14 @@ impl Arm7tdmi {
         self.registers.set_register_at(14, next_ins as u32);
         self.spsr = old_cpsr;
 
+        #[cfg(feature = "debug-hooks")]
+        self.observers.each(|observer| {
+            observer.on_exception(&ExceptionEntry {
+                exception: exception_type,
+                return_address: next_ins as u32,
+            });
+        });
+
         self.cpsr.set_irq_disable(true);
 
         if matches!(exception_type, ExceptionType::Fiq | ExceptionType::Reset) {
@@ -454,20 +730,67 @@ impl Arm7tdmi {
         let new_pc = exception_type.address() as u32;
         self.registers.set_program_counter(new_pc);
 
-        self.decoded_arm = Some(Self::decode(self.fetch_arm()));
+        self.decoded_arm = Some(self.fetch_arm());
         self.registers
             .set_program_counter(new_pc + arm::operations::SIZE_OF_INSTRUCTION);
 
         self.fetched_arm = Some(self.fetch_arm());
     }
 
-    pub fn step(&mut self) {
-        self.current_cycle += 1;
+    /// Address of the instruction the next step executes, `None` while the pipeline
+    /// refills.
+    #[must_use]
+    pub fn next_instruction_address(&self) -> Option<u32> {
+        let pc = self.registers.program_counter() as u32;
+        match self.cpsr.cpu_state() {
+            CpuState::Thumb => self.decoded_thumb.map(|_| pc.wrapping_sub(4)),
+            CpuState::Arm => self.decoded_arm.map(|_| pc.wrapping_sub(8)),
+        }
+    }
+
+    /// Runs a pipeline stage, the cycles it takes depend on the memory accessed and on the
+    /// instruction executed.
+    /// While halted, a step lets up to a scanline pass without executing anything, or
+    /// less if an interrupt wakes the CPU.
+    ///
+    /// An instruction with a breakpoint isn't executed the first time, see
+    /// [`StepResult::BreakpointHit`].
+    pub fn step(&mut self) -> StepResult {
+        #[cfg(feature = "debug-hooks")]
+        if let Some(address) = self.breakpoint_hit() {
+            return StepResult::BreakpointHit(address);
+        }
+
+        let start = self.bus.cycles();
+        if self.bus.halted() {
+            self.bus.halt_step(HALT_STEP_CYCLES);
+        } else {
+            self.step_pipeline();
+        }
+        self.current_cycle += self.bus.cycles() - start;
+
+        StepResult::Normal
+    }
+
+    /// Instructions executed since power-on or the last reset, interpreted or compiled.
+    #[must_use]
+    pub const fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    fn step_pipeline(&mut self) {
+        #[cfg(feature = "jit")]
+        if self.run_compiled_block() {
+            return;
+        }
+        #[cfg(feature = "debug-hooks")]
+        let start = self.bus.cycles();
+
         match self.cpsr.cpu_state() {
             CpuState::Thumb => {
                 let to_execute = self.decoded_thumb;
 
-                self.decoded_thumb = self.fetched_thumb.map(Self::decode);
+                self.decoded_thumb = self.fetched_thumb;
                 self.fetched_thumb = Some(self.fetch_thumb());
 
                 if let Some(decoded) = to_execute {
@@ -477,17 +800,32 @@ impl Arm7tdmi {
                         return;
                     }
 
-                    #[cfg(feature = "logger")]
                     let current_ins = self.registers.program_counter() - 4;
+                    if self.raise_prefetch_abort(current_ins as u32) {
+                        return;
+                    }
                     #[cfg(feature = "logger")]
-                    log(format!("PC: 0x{current_ins:X} {decoded}"));
+                    log(format!(
+                        "PC: 0x{current_ins:X} {}",
+                        thumb::lut::decode(decoded)
+                    ));
+                    #[cfg(feature = "debug-hooks")]
+                    self.trace_instruction(current_ins as u32, true);
 
                     self.execute_thumb(decoded);
-                }
+                    self.instructions += 1;
+                    #[cfg(feature = "debug-hooks")]
+                    self.notify_executed(current_ins as u32, decoded.into(), true, start);
+
+                    if self.raise_data_abort(current_ins as u32 + 4) {
+                        return;
+                    }
 
-                // This means that the instruction flushed the pipeline
-                if self.fetched_thumb.is_none() {
-                    return;
+                    // This means that the instruction flushed the pipeline
+                    if self.fetched_thumb.is_none() {
+                        self.last_jump_source = Some(current_ins as u32);
+                        return;
+                    }
                 }
 
                 self.registers.set_program_counter(
@@ -498,7 +836,7 @@ impl Arm7tdmi {
             CpuState::Arm => {
                 let to_execute = self.decoded_arm;
 
-                self.decoded_arm = self.fetched_arm.map(Self::decode);
+                self.decoded_arm = self.fetched_arm;
                 self.fetched_arm = Some(self.fetch_arm());
 
                 if let Some(decoded) = to_execute {
@@ -508,17 +846,32 @@ impl Arm7tdmi {
                         return;
                     }
 
-                    #[cfg(feature = "logger")]
                     let current_ins = self.registers.program_counter() - 8;
+                    if self.raise_prefetch_abort(current_ins as u32) {
+                        return;
+                    }
                     #[cfg(feature = "logger")]
-                    log(format!("PC: 0x{current_ins:X} {decoded}"));
+                    log(format!(
+                        "PC: 0x{current_ins:X} {}",
+                        arm::lut::decode(decoded)
+                    ));
+                    #[cfg(feature = "debug-hooks")]
+                    self.trace_instruction(current_ins as u32, false);
 
                     self.execute_arm(decoded);
-                }
+                    self.instructions += 1;
+                    #[cfg(feature = "debug-hooks")]
+                    self.notify_executed(current_ins as u32, decoded, false, start);
 
-                // This means that the instruction flushed the pipeline
-                if self.fetched_arm.is_none() {
-                    return;
+                    if self.raise_data_abort(current_ins as u32 + 8) {
+                        return;
+                    }
+
+                    // This means that the instruction flushed the pipeline
+                    if self.fetched_arm.is_none() {
+                        self.last_jump_source = Some(current_ins as u32);
+                        return;
+                    }
                 }
 
                 self.registers.set_program_counter(
@@ -528,6 +881,163 @@ impl Arm7tdmi {
         }
     }
 
+    /// Raises a prefetch abort instead of executing the instruction at `address` if it's
+    /// unmapped, see [`Self::set_abort_on_invalid_access`].
+    fn raise_prefetch_abort(&mut self, address: u32) -> bool {
+        if !self.abort_on_invalid_access || !is_unmapped(address) {
+            return false;
+        }
+
+        self.handle_exception(ExceptionType::PrefetchAbort);
+        true
+    }
+
+    /// Raises a data abort if the instruction just executed accessed an unmapped address,
+    /// `pipeline_pc` being the program counter while it was executed.
+    fn raise_data_abort(&mut self, pipeline_pc: u32) -> bool {
+        if !self.abort_on_invalid_access || self.bus.take_invalid_access().is_none() {
+            return false;
+        }
+
+        // The return address is the one of the aborted instruction, even if it wrote the
+        // program counter.
+        self.registers.set_program_counter(pipeline_pc);
+        self.handle_exception(ExceptionType::DataAbort);
+        true
+    }
+
+    /// Off by default, as on hardware where nothing aborts.
+    ///
+    /// On, executing from an unmapped address raises a prefetch abort and accessing one a
+    /// data abort, so a homebrew bug stops in Abort mode with the faulty instruction in LR
+    /// instead of running on with what the open bus reads. The aborted access isn't
+    /// undone: a load still writes what it read.
+    pub const fn set_abort_on_invalid_access(&mut self, enabled: bool) {
+        self.abort_on_invalid_access = enabled;
+        // Accesses made while it was off aren't aborted afterwards.
+        self.bus.take_invalid_access();
+    }
+
+    #[must_use]
+    pub const fn abort_on_invalid_access(&self) -> bool {
+        self.abort_on_invalid_access
+    }
+
+    /// Off by default: every SWI jumps to the BIOS.
+    ///
+    /// On, the BIOS functions emulated in Rust run in place of the BIOS code, for games
+    /// run without a BIOS dump. The others still jump to the BIOS.
+    pub const fn set_bios_hle(&mut self, enabled: bool) {
+        self.bios_hle = enabled;
+    }
+
+    #[must_use]
+    pub const fn bios_hle(&self) -> bool {
+        self.bios_hle
+    }
+
+    /// Off by default. On, no shortcut is taken for speed: every SWI runs the BIOS code,
+    /// even with [`Self::set_bios_hle`]. It needs a BIOS dump, the replacement BIOS
+    /// doesn't have the functions.
+    pub const fn set_max_accuracy(&mut self, enabled: bool) {
+        self.max_accuracy = enabled;
+    }
+
+    #[must_use]
+    pub const fn max_accuracy(&self) -> bool {
+        self.max_accuracy
+    }
+
+    /// The BIOS functions emulated in Rust since the CPU was created, by SWI number.
+    #[must_use]
+    pub fn hle_shortcuts(&self) -> Vec<HleShortcut> {
+        self.hle_calls
+            .iter()
+            .map(|(&swi, &calls)| HleShortcut { swi, calls })
+            .collect()
+    }
+
+    /// Whether compiled blocks can run: the features checking each instruction as it's
+    /// executed need the interpreter.
+    #[cfg(feature = "jit")]
+    fn compiled_blocks_allowed(&self) -> bool {
+        // A breakpoint inside a block would be run past, and the observers and the trace
+        // would miss its instructions.
+        #[cfg(feature = "debug-hooks")]
+        if !self.breakpoints.is_empty() || !self.observers.is_empty() || self.cpu_trace.is_some() {
+            return false;
+        }
+
+        !self.abort_on_invalid_access
+    }
+
+    /// Runs the compiled block starting at the instruction about to be executed, if there
+    /// is one. The pipeline fetches the same instructions as if they were interpreted, so
+    /// the timing is the same. Only interrupts wait for the end of the block, and the
+    /// frame guard only sees its first instruction.
+    #[cfg(feature = "jit")]
+    fn run_compiled_block(&mut self) -> bool {
+        if !self.compiled_blocks_allowed() {
+            return false;
+        }
+
+        let thumb = matches!(self.cpsr.cpu_state(), CpuState::Thumb);
+        let (decoded, size) = if thumb {
+            (
+                self.decoded_thumb.is_some(),
+                thumb::operations::SIZE_OF_INSTRUCTION,
+            )
+        } else {
+            (
+                self.decoded_arm.is_some(),
+                arm::operations::SIZE_OF_INSTRUCTION,
+            )
+        };
+        if !decoded || (!self.cpsr.irq_disable() && self.bus.is_irq_pending()) {
+            return false;
+        }
+
+        let address = (self.registers.program_counter() as u32).wrapping_sub(2 * size);
+        let Some(block) = self.jit.block(&self.bus, address, thumb) else {
+            return false;
+        };
+
+        block.run(self.registers.as_mut_array());
+        self.instructions += u64::from(block.length());
+        for _ in 0..block.length() {
+            if thumb {
+                self.decoded_thumb = self.fetched_thumb;
+                self.fetched_thumb = Some(self.fetch_thumb());
+            } else {
+                self.decoded_arm = self.fetched_arm;
+                self.fetched_arm = Some(self.fetch_arm());
+            }
+            self.registers.advance_program_counter(size);
+        }
+
+        true
+    }
+
+    /// Compiled blocks are used by default, disabled every instruction is interpreted.
+    #[cfg(feature = "jit")]
+    pub fn set_jit_enabled(&mut self, enabled: bool) {
+        self.jit.set_enabled(enabled);
+    }
+
+    /// Counters of the compiled blocks, see [`JitStats`].
+    #[cfg(feature = "jit")]
+    #[must_use]
+    pub const fn jit_stats(&self) -> JitStats {
+        self.jit.stats()
+    }
+
+    /// Disassembly of the executed instructions is kept by default, disabled
+    /// [`Self::disassembler_buffer`] stays as it is.
+    #[cfg(feature = "disassembler")]
+    pub const fn set_disassembly_enabled(&mut self, enabled: bool) {
+        self.disassembly = enabled;
+    }
+
     #[must_use]
     pub fn new(bus: Bus) -> Self {
         Self {
@@ -536,8 +1046,84 @@ impl Arm7tdmi {
         }
     }
 
+    /// Restarts from the reset vector with the bus reset as well, see [`Bus::reset`].
+    /// The traps, misaligned fetches and BIOS functions emulated so far are kept for the
+    /// session report. The compiled blocks are kept too, the BIOS and the ROM don't change.
+    pub fn reset(&mut self, hard: bool) {
+        let mut bus = std::mem::take(&mut self.bus);
+        bus.reset(hard);
+
+        *self = Self {
+            trap_log: std::mem::take(&mut self.trap_log),
+            misaligned_pc_count: self.misaligned_pc_count,
+            first_misaligned_pc: self.first_misaligned_pc,
+            trap_on_misaligned_pc: self.trap_on_misaligned_pc,
+            abort_on_invalid_access: self.abort_on_invalid_access,
+            bios_hle: self.bios_hle,
+            max_accuracy: self.max_accuracy,
+            hle_calls: std::mem::take(&mut self.hle_calls),
+            #[cfg(feature = "disassembler")]
+            disassembly: self.disassembly,
+            #[cfg(feature = "debug-hooks")]
+            cpu_trace: self.cpu_trace.take(),
+            #[cfg(feature = "debug-hooks")]
+            breakpoints: std::mem::take(&mut self.breakpoints),
+            #[cfg(feature = "debug-hooks")]
+            observers: std::mem::take(&mut self.observers),
+            #[cfg(feature = "jit")]
+            jit: std::mem::take(&mut self.jit),
+            ..Self::new(bus)
+        };
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn encode_section(&self, section: Section) -> bincode::Result<Vec<u8>> {
+        match section {
+            Section::Cpu => bincode::serialize(&(
+                &self.cpsr,
+                &self.spsr,
+                &self.registers,
+                &self.register_bank,
+                &self.fetched_arm,
+                &self.decoded_arm.map(arm::lut::decode),
+                &self.fetched_thumb,
+                &self.decoded_thumb.map(thumb::lut::decode),
+                &self.current_cycle,
+                &self.intr_waiting,
+            )),
+            _ => self.bus.encode_section(section),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn decode_section(&mut self, section: Section, data: &[u8]) -> bincode::Result<()> {
+        match section {
+            Section::Cpu => {
+                let decoded_arm: Option<ArmModeOpcode>;
+                let decoded_thumb: Option<ThumbModeOpcode>;
+                (
+                    self.cpsr,
+                    self.spsr,
+                    self.registers,
+                    self.register_bank,
+                    self.fetched_arm,
+                    decoded_arm,
+                    self.fetched_thumb,
+                    decoded_thumb,
+                    self.current_cycle,
+                    self.intr_waiting,
+                ) = bincode::deserialize(data)?;
+                self.decoded_arm = decoded_arm.map(|op_code| op_code.raw);
+                self.decoded_thumb = decoded_thumb.map(|op_code| op_code.raw);
+
+                Ok(())
+            }
+            _ => self.bus.decode_section(section, data),
+        }
+    }
+
     #[allow(clippy::too_many_lines)]
-    pub fn swap_mode(&mut self, new_mode: &Mode) {
+    pub(crate) fn swap_mode(&mut self, new_mode: &Mode) {
         if self.cpsr.mode() == *new_mode {
             return;
         }
@@ -655,18 +1241,23 @@ impl Arm7tdmi {
             }
         }
 
+        #[cfg(feature = "debug-hooks")]
+        let old_mode = self.cpsr.mode();
         self.cpsr.set_mode(new_mode);
+        #[cfg(feature = "debug-hooks")]
+        self.observers
+            .each(|observer| observer.on_mode_change(&old_mode, new_mode));
     }
 
-    pub fn read_half_word(&mut self, address: usize, sign_extended: bool) -> u32 {
+    pub fn read_half_word(&mut self, address: u32, sign_extended: bool) -> u32 {
         // Misaligned reads are unsupported in ARMv4.
         // When reading an half-word from a misaligned halfword address (even address)
         // the CPU will read at the aligned halfword address and will put the selected
         // byte to the lower byte of the address. That's why we rotate right by 8 if the lowest
         // in the address is 1.
 
-        let rotation = ((address & 0b1) * 8) as u32;
-        let mut value = (self.bus.read_half_word(address) as u32).rotate_right(rotation);
+        let rotation = (address & 0b1) * 8;
+        let mut value = u32::from(self.bus.read_half_word(address)).rotate_right(rotation);
 
         if sign_extended {
             let is_halfword_aligned: bool = address & 0b1 == 0;
@@ -681,17 +1272,18 @@ impl Arm7tdmi {
         value
     }
 
-    pub fn read_word(&mut self, address: usize) -> u32 {
+    pub fn read_word(&mut self, address: u32) -> u32 {
         // From documentation: An address offset from a word boundary will cause the data to be rotated
         // into the register so that the addressed byte occupies bits 0 to 7.
         // So if the last 2 bits of the address are 01, we still word-align the address but the byte 1 of the
         // read word will be in the lower 0-7 bits of the register. That's why we rotate it.
-        let rotation = ((address & 0b11) * 8) as u32;
+        let rotation = (address & 0b11) * 8;
         self.bus.read_word(address).rotate_right(rotation)
     }
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum HalfwordTransferKind {
     UnsignedHalfwords,
     SignedByte,
@@ -723,47 +1315,482 @@ impl std::fmt::Display for HalfwordTransferKind {
 mod tests {
     use pretty_assertions::assert_eq;
 
+    use crate::cpu::arm::instructions::ArmModeInstruction;
+    use crate::cpu::arm::mode::ArmModeOpcode;
+    use crate::cpu::asm::{ArmAsm, ThumbAsm};
     use crate::cpu::condition::Condition;
     use crate::cpu::flags::{HalfwordDataTransferOffsetKind, Indexing, LoadStoreKind, Offsetting};
+    use crate::cpu::hardware::internal_memory::InternalMemory;
     use crate::cpu::registers::{REG_LR, REG_PROGRAM_COUNTER, REG_SP};
+    use crate::cpu::thumb::alu_instructions::{
+        ThumbHighRegisterOperation, ThumbModeAluInstruction,
+    };
     use crate::cpu::thumb::instruction::Instruction;
+    use crate::cpu::thumb::mode::ThumbModeOpcode;
+    use crate::memory_map::{HALTCNT, IE};
 
     use super::*;
 
+    #[test]
+    fn pipeline_refills_take_2s_1n() {
+        // Runs `code` from the cartridge and counts the cycles of the steps up to the
+        // first instruction after it.
+        fn cycles(code: &[ArmAsm], steps: usize) -> u128 {
+            let mut rom = vec![0; 0x100];
+            for (index, instruction) in code.iter().enumerate() {
+                rom[index * 4..index * 4 + 4].copy_from_slice(&instruction.encode().to_le_bytes());
+            }
+            let memory = InternalMemory::new([0; 0x4000], rom);
+            let mut cpu = Arm7tdmi::new(Bus::with_memory(memory));
+            cpu.registers.set_program_counter(0x0800_0000);
+
+            // Fills the pipeline and executes the first instruction.
+            for _ in 0..3 {
+                cpu.step();
+            }
+            let start = cpu.bus.cycles();
+            for _ in 0..steps {
+                cpu.step();
+            }
+
+            cpu.bus.cycles() - start
+        }
+
+        // With the default waitstates a word takes 8 cycles to fetch from the cartridge,
+        // 6 when sequential.
+        let nop = ArmAsm::mov(0).reg(0);
+        assert_eq!(cycles(&[nop, nop], 1), 6);
+        // The branch and the two fetches refilling the pipeline.
+        assert_eq!(cycles(&[nop, ArmAsm::b(-8)], 3), 6 + 8 + 6);
+        assert_eq!(
+            cycles(&[nop, ArmAsm::mov(15).imm(0x0800_0000)], 3),
+            6 + 8 + 6
+        );
+        // Exceptions refill it from the vectors, in the single cycle BIOS.
+        assert_eq!(cycles(&[nop, ArmAsm::swi(0)], 1), 6 + 1 + 1);
+    }
+
     #[test]
     fn arm_branch() {
         // Covers a positive offset
 
         // 15(1111b) << 2 = 60 bytes
-        let op_code = 0b1110_1010_0000_0000_0000_0000_0000_1111;
+        let op_code = ArmAsm::b(60).encode();
         let mut cpu = Arm7tdmi::default();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.program_counter(), 60);
 
         // Covers a negative offset
 
         // -9 << 2 = -36 bytes
-        let op_code = 0b1110_1010_1111_1111_1111_1111_1111_0111;
+        let op_code = ArmAsm::b(-36).encode();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         assert_eq!(cpu.registers.program_counter(), 60 - 36);
 
         // Covers link
 
-        let op_code = 0b1110_1011_0000_0000_0000_0000_0000_1111;
+        let op_code = ArmAsm::bl(60).encode();
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
 
         // -4 because of pipelining (pc is at +8 so we've to do -4 to get the next instruction)
         assert_eq!(cpu.registers.register_at(14), 24 - 4);
     }
 
+    #[test]
+    #[cfg(feature = "debug-hooks")]
+    fn execution_trap_records_jump_source() {
+        let mut cpu = Arm7tdmi::default();
+
+        // BX R0 with R0 pointing to the palette RAM
+        cpu.bus.write_word(0x0300_0000, ArmAsm::bx(0).encode());
+        cpu.registers.set_register_at(0, 0x0500_0000);
+        cpu.registers.set_program_counter(0x0300_0000);
+        cpu.flush_pipeline();
+
+        for _ in 0..3 {
+            cpu.step();
+            assert_eq!(cpu.take_execution_trap(), None);
+        }
+
+        cpu.step();
+        let trap = cpu.take_execution_trap().unwrap();
+        assert_eq!(trap.address, 0x0500_0000);
+        assert_eq!(trap.region, NonExecutableRegion::Palette);
+        assert_eq!(trap.jump_source, Some(0x0300_0000));
+        assert_eq!(cpu.take_execution_trap(), None);
+        assert_eq!(cpu.trap_log(), [trap]);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-hooks")]
+    fn cpu_trace_records_executed_instructions() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::cpu_trace::{CpuTraceEntry, CpuTraceWriter};
+
+        struct Recorder(Arc<Mutex<Vec<CpuTraceEntry>>>);
+
+        impl CpuTraceWriter for Recorder {
+            fn record(&mut self, entry: &CpuTraceEntry) -> std::io::Result<()> {
+                self.0.lock().unwrap().push(*entry);
+                Ok(())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut cpu = Arm7tdmi::default();
+        cpu.patch_instruction(0x0300_0000, "MOV R1, #5", false)
+            .unwrap();
+        cpu.patch_instruction(0x0300_0004, "ADD R1, R1, #1", false)
+            .unwrap();
+        cpu.registers.set_program_counter(0x0300_0000);
+
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        cpu.set_cpu_trace(Some(Box::new(Recorder(Arc::clone(&entries)))));
+        for _ in 0..4 {
+            cpu.step();
+        }
+        assert!(cpu.set_cpu_trace(None).is_some());
+
+        let entries = entries.lock().unwrap().clone();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].address, 0x0300_0000);
+        assert_eq!(entries[0].opcode, ArmAsm::mov(1).imm(5).encode());
+        assert_eq!(entries[0].registers[15], 0x0300_0008);
+        assert_eq!(entries[1].registers[1], 5);
+        assert!(!entries[1].thumb);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-hooks")]
+    fn observers_see_instructions_exceptions_and_modes() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::cpu::instrumentation::{CpuObserver, ExceptionEntry, ExecutedInstruction};
+
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        enum Event {
+            Executed(ExecutedInstruction),
+            Exception(ExceptionEntry),
+            ModeChange(String, String),
+            Swi(u8),
+        }
+
+        struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+        impl CpuObserver for Recorder {
+            fn on_instruction_executed(&mut self, instruction: &ExecutedInstruction) {
+                self.0.lock().unwrap().push(Event::Executed(*instruction));
+            }
+
+            fn on_exception(&mut self, entry: &ExceptionEntry) {
+                self.0.lock().unwrap().push(Event::Exception(*entry));
+            }
+
+            fn on_mode_change(&mut self, from: &Mode, to: &Mode) {
+                let event = Event::ModeChange(format!("{from:?}"), format!("{to:?}"));
+                self.0.lock().unwrap().push(event);
+            }
+
+            fn on_swi(&mut self, number: u8) {
+                self.0.lock().unwrap().push(Event::Swi(number));
+            }
+        }
+
+        let mut cpu = Arm7tdmi::default();
+        cpu.swap_mode(&Mode::System);
+        cpu.patch_instruction(0x0300_0000, "MOV R1, #5", false)
+            .unwrap();
+        cpu.patch_instruction(0x0300_0004, "SWI 0x50000", false)
+            .unwrap();
+        cpu.registers.set_program_counter(0x0300_0000);
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let id = cpu.add_observer(Box::new(Recorder(Arc::clone(&events))));
+        for _ in 0..4 {
+            cpu.step();
+        }
+        assert!(cpu.remove_observer(id).is_some());
+        assert!(cpu.remove_observer(id).is_none());
+        cpu.step();
+
+        let events = events.lock().unwrap().clone();
+        assert_eq!(events.len(), 5, "{events:?}");
+        let Event::Executed(mov) = events[0] else {
+            panic!("{events:?}");
+        };
+        assert_eq!(mov.address, 0x0300_0000);
+        assert_eq!(mov.opcode, ArmAsm::mov(1).imm(5).encode());
+        assert!(!mov.thumb);
+        assert!(mov.cycles > 0);
+        assert_eq!(events[1], Event::Swi(5));
+        assert_eq!(
+            events[2],
+            Event::ModeChange("System".to_string(), "Supervisor".to_string())
+        );
+        assert_eq!(
+            events[3],
+            Event::Exception(ExceptionEntry {
+                exception: ExceptionType::SoftwareInterrupt,
+                return_address: 0x0300_0008,
+            })
+        );
+        assert!(matches!(events[4], Event::Executed(swi) if swi.address == 0x0300_0004));
+    }
+
+    #[test]
+    #[cfg(feature = "debug-hooks")]
+    fn breakpoints_stop_before_the_instruction() {
+        use crate::cpu::breakpoints::{Breakpoint, StateMask, StepResult};
+
+        let mut cpu = Arm7tdmi::default();
+        cpu.patch_instruction(0x0300_0000, "MOV R1, #5", false)
+            .unwrap();
+        cpu.patch_instruction(0x0300_0004, "ADD R1, R1, #1", false)
+            .unwrap();
+        cpu.registers.set_program_counter(0x0300_0000);
+
+        // Only in Thumb code.
+        cpu.add_breakpoint(Breakpoint::new(0x0300_0000, StateMask::THUMB));
+        cpu.add_breakpoint(Breakpoint::new(0x0300_0004, StateMask::ARM));
+
+        let results = (0..4).map(|_| cpu.step()).collect::<Vec<_>>();
+        assert_eq!(results[3], StepResult::BreakpointHit(0x0300_0004));
+        assert!(results[..3]
+            .iter()
+            .all(|&result| result == StepResult::Normal));
+        assert_eq!(cpu.registers.register_at(1), 5);
+
+        // Resuming executes the instruction.
+        assert_eq!(cpu.step(), StepResult::Normal);
+        assert_eq!(cpu.registers.register_at(1), 6);
+
+        assert!(cpu.remove_breakpoint(0x0300_0004));
+        assert_eq!(
+            cpu.breakpoints(),
+            [Breakpoint::new(0x0300_0000, StateMask::THUMB)]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "debug-hooks")]
+    fn conditional_breakpoints_stop_when_true() {
+        use crate::cpu::breakpoints::{Breakpoint, StateMask, StepResult};
+
+        let mut cpu = Arm7tdmi::default();
+        cpu.patch_instruction(0x0300_0000, "ADD R1, R1, #1", false)
+            .unwrap();
+        cpu.patch_instruction(0x0300_0004, "STR R1, [R2]", false)
+            .unwrap();
+        cpu.patch_instruction(0x0300_0008, "B 0x03000000", false)
+            .unwrap();
+        cpu.registers.set_register_at(2, 0x0300_1000);
+        cpu.registers.set_program_counter(0x0300_0000);
+
+        let condition = "r1 == 3 && [0x03001000] == 2".parse().unwrap();
+        cpu.add_breakpoint(Breakpoint::new(0x0300_0004, StateMask::ANY).with_condition(condition));
+
+        let hit = (0..100)
+            .map(|_| cpu.step())
+            .find(|&result| result != StepResult::Normal);
+        assert_eq!(hit, Some(StepResult::BreakpointHit(0x0300_0004)));
+        assert_eq!(cpu.registers.register_at(1), 3);
+    }
+
+    #[test]
+    fn misaligned_pc_is_counted() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.patch_instruction(0x0300_0000, "MOV PC, R0", false)
+            .unwrap();
+        cpu.registers.set_register_at(0, 0x0300_0102);
+        cpu.registers.set_program_counter(0x0300_0000);
+        cpu.set_trap_on_misaligned_pc(true);
+
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert_eq!(cpu.misaligned_pc_count(), 0);
+
+        cpu.step();
+        let misaligned = cpu.take_misaligned_pc().unwrap();
+        assert_eq!(misaligned.address, 0x0300_0102);
+        assert!(!misaligned.thumb);
+        assert_eq!(misaligned.jump_source, Some(0x0300_0000));
+        assert_eq!(cpu.registers.program_counter(), 0x0300_0104);
+        assert_eq!(cpu.misaligned_pc_count(), 1);
+        assert_eq!(cpu.first_misaligned_pc(), Some(misaligned));
+        assert_eq!(cpu.take_misaligned_pc(), None);
+    }
+
+    #[test]
+    fn reset_restarts_from_the_reset_vector() {
+        let mut cpu = Arm7tdmi::default();
+        let output = cpu.bus.lcd.frame_output();
+
+        cpu.bus.write_word(0x0300_0000, ArmAsm::b(-8).encode());
+        cpu.registers.set_program_counter(0x0300_0000);
+        cpu.registers.set_register_at(0, 42);
+        cpu.flush_pipeline();
+        cpu.bus.write_half_word(0x0400_0000, 0x0403);
+        while output.frame_count() == 0 {
+            cpu.step();
+        }
+
+        cpu.reset(false);
+        assert_eq!(cpu.registers.program_counter(), 0);
+        assert_eq!(cpu.registers.register_at(0), 0);
+        assert_eq!(cpu.current_cycle, 0);
+        assert_eq!(cpu.bus.read_half_word(0x0400_0000), 0);
+        // The work RAM survives a soft reset, the frame output keeps counting.
+        assert_eq!(cpu.bus.read_word(0x0300_0000), ArmAsm::b(-8).encode());
+        assert_eq!(output.frame_count(), 1);
+
+        cpu.reset(true);
+        assert_eq!(cpu.bus.read_word(0x0300_0000), 0);
+    }
+
+    #[test]
+    fn data_abort_on_invalid_access() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.patch_instruction(0x0300_0000, "LDR R1, [R0]", false)
+            .unwrap();
+        cpu.registers.set_register_at(0, 0x1000_0000);
+        cpu.registers.set_program_counter(0x0300_0000);
+        let cpsr = u32::from(cpu.cpsr);
+
+        // Off, the load reads the open bus and the next instruction follows.
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert_eq!(cpu.cpsr.mode(), Mode::Supervisor);
+        assert_eq!(cpu.registers.program_counter(), 0x0300_000C);
+
+        cpu.set_abort_on_invalid_access(true);
+        cpu.registers.set_program_counter(0x0300_0000);
+        cpu.flush_pipeline();
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert_eq!(cpu.cpsr.mode(), Mode::Abort);
+        assert_eq!(cpu.registers.register_at(14), 0x0300_0008);
+        assert_eq!(u32::from(cpu.spsr), cpsr);
+        assert_eq!(cpu.registers.program_counter(), 0x14);
+    }
+
+    #[test]
+    fn prefetch_abort_on_invalid_fetch() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.set_abort_on_invalid_access(true);
+
+        cpu.bus.write_word(0x0300_0000, ArmAsm::bx(0).encode());
+        cpu.registers.set_register_at(0, 0x0000_8000);
+        cpu.registers.set_program_counter(0x0300_0000);
+        cpu.flush_pipeline();
+
+        // Branching, fetching and decoding don't abort.
+        for _ in 0..5 {
+            cpu.step();
+            assert_eq!(cpu.cpsr.mode(), Mode::Supervisor);
+        }
+
+        cpu.step();
+        assert_eq!(cpu.cpsr.mode(), Mode::Abort);
+        assert_eq!(cpu.registers.register_at(14), 0x0000_8004);
+        assert_eq!(cpu.registers.program_counter(), 0x10);
+    }
+
+    #[test]
+    fn halt_waits_for_an_enabled_interrupt() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.patch_instruction(0x0300_0000, "STRB R0, [R1]", false)
+            .unwrap();
+        cpu.patch_instruction(0x0300_0004, "MOV R2, #1", false)
+            .unwrap();
+        cpu.registers.set_register_at(1, HALTCNT.get());
+        cpu.registers.set_program_counter(0x0300_0000);
+        // Vblank interrupt enabled, but not taken: IME is clear.
+        cpu.bus.write_half_word(0x0400_0004, 0x0008);
+        cpu.bus.write_half_word(IE.get(), 0x0001);
+
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert!(cpu.bus.halted());
+
+        let mut steps = 0;
+        while cpu.bus.halted() {
+            cpu.step();
+            steps += 1;
+        }
+        assert_eq!(steps, 161);
+        assert_eq!(cpu.bus.lcd.registers.vcount, 160);
+        assert_eq!(cpu.registers.register_at(2), 0);
+
+        cpu.step();
+        assert_eq!(cpu.registers.register_at(2), 1);
+    }
+
+    #[test]
+    fn patch_instruction() {
+        let mut cpu = Arm7tdmi::default();
+
+        assert_eq!(
+            cpu.patch_instruction(0x0300_0000, "MOV R0, #1", false),
+            Ok(4)
+        );
+        assert_eq!(cpu.bus.read_word(0x0300_0000), 0xE3A0_0001);
+
+        assert_eq!(
+            cpu.patch_instruction(0x0300_0010, "BL 0x03001014", true),
+            Ok(4)
+        );
+        assert_eq!(cpu.bus.read_half_word(0x0300_0010), 0xF001);
+        assert_eq!(cpu.bus.read_half_word(0x0300_0012), 0xF800);
+
+        assert_eq!(
+            cpu.patch_instruction(0x0300_0002, "MOV R0, #1", false),
+            Err(AssembleError::Misaligned {
+                value: 0x0300_0002,
+                alignment: 4
+            })
+        );
+        assert!(cpu.patch_instruction(0x0300_0000, "MOV R0", false).is_err());
+        assert_eq!(cpu.bus.read_word(0x0300_0000), 0xE3A0_0001);
+    }
+
+    #[test]
+    fn debug_write_refreshes_pipeline() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.patch_instruction(0x0300_0000, "MOV R0, #1", false)
+            .unwrap();
+        cpu.registers.set_program_counter(0x0300_0000);
+
+        // Fetch then decode the first instruction.
+        cpu.step();
+        cpu.step();
+
+        let mov = ArmAsm::mov(0).imm(7).encode();
+        assert_eq!(cpu.debug_write(0x0300_0000, EditValue::Word(mov)), Ok(()));
+        cpu.step();
+        assert_eq!(cpu.registers.register_at(0), 7);
+
+        assert_eq!(
+            cpu.debug_write(0x0000_0000, EditValue::Byte(0)),
+            Err(MemoryEditError::ReadOnly(0))
+        );
+    }
+
     #[test]
     #[should_panic]
     fn arm_unknown_instruction() {
@@ -773,14 +1800,14 @@ mod tests {
         let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
         assert_eq!(op_code.condition, Condition::AL);
 
-        cpu.execute_arm(op_code);
+        cpu.execute_arm(op_code.raw);
     }
 
     #[test]
     fn arm_block_data_transfer() {
         {
             // LDM with post-increment
-            let op_code = 0b1110_100_0_1_0_1_1_1101_0000000010100010;
+            let op_code = ArmAsm::ldm(13, 0xA2).write_back().encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
@@ -788,7 +1815,7 @@ mod tests {
             cpu.bus.write_byte(0x1000, 1);
             cpu.bus.write_byte(0x1004, 5);
             cpu.bus.write_byte(0x1008, 7);
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), 1);
             assert_eq!(cpu.registers.register_at(5), 5);
@@ -797,7 +1824,7 @@ mod tests {
         }
         {
             // LDM with pre-increment
-            let op_code = 0b1110_100_1_1_0_1_1_1101_0000000010100010;
+            let op_code = ArmAsm::ldm(13, 0xA2).pre().write_back().encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
@@ -805,7 +1832,7 @@ mod tests {
             cpu.bus.write_byte(0x1004, 1);
             cpu.bus.write_byte(0x1008, 5);
             cpu.bus.write_byte(0x100C, 7);
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), 1);
             assert_eq!(cpu.registers.register_at(5), 5);
@@ -814,7 +1841,7 @@ mod tests {
         }
         {
             // LDM with post-decrement
-            let op_code = 0b1110_100_0_0_0_1_1_1101_0000000010100010;
+            let op_code = ArmAsm::ldm(13, 0xA2).down().write_back().encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
@@ -822,7 +1849,7 @@ mod tests {
             cpu.bus.write_byte(0x1000, 7);
             cpu.bus.write_byte(0x0FFC, 5);
             cpu.bus.write_byte(0x0FF8, 1);
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), 1);
             assert_eq!(cpu.registers.register_at(5), 5);
@@ -831,7 +1858,7 @@ mod tests {
         }
         {
             // LDM with pre-decrement
-            let op_code = 0b1110_100_1_0_0_1_1_1101_0000000010100010;
+            let op_code = ArmAsm::ldm(13, 0xA2).pre().down().write_back().encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
@@ -839,7 +1866,7 @@ mod tests {
             cpu.bus.write_byte(0x0FFC, 7);
             cpu.bus.write_byte(0x0FF8, 5);
             cpu.bus.write_byte(0x0FF4, 1);
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), 1);
             assert_eq!(cpu.registers.register_at(5), 5);
@@ -848,7 +1875,7 @@ mod tests {
         }
         {
             // STM with post-increment
-            let op_code = 0b1110_100_0_1_0_1_0_1101_0000000010100010;
+            let op_code = ArmAsm::stm(13, 0xA2).write_back().encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
@@ -858,7 +1885,7 @@ mod tests {
 
             cpu.registers.set_register_at(13, 0x1000);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             let mut bus = cpu.bus;
 
@@ -869,7 +1896,7 @@ mod tests {
         }
         {
             // STM with pre-increment
-            let op_code = 0b1110_100_1_1_0_1_0_1101_0000000010100010;
+            let op_code = ArmAsm::stm(13, 0xA2).pre().write_back().encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
@@ -879,7 +1906,7 @@ mod tests {
 
             cpu.registers.set_register_at(13, 0x1000);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             let mut bus = cpu.bus;
 
@@ -891,7 +1918,7 @@ mod tests {
         }
         {
             // STM with post-decrement
-            let op_code = 0b1110_100_0_0_0_1_0_1101_0000000010100010;
+            let op_code = ArmAsm::stm(13, 0xA2).down().write_back().encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
@@ -901,7 +1928,7 @@ mod tests {
 
             cpu.registers.set_register_at(13, 0x1000);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             let mut bus = cpu.bus;
 
@@ -913,7 +1940,7 @@ mod tests {
         {
             // STM with pre-decrement and storing R15
 
-            let op_code = 0b1110_100_1_0_0_1_0_1101_1000000010100010;
+            let op_code = ArmAsm::stm(13, 0x80A2).pre().down().write_back().encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
@@ -923,7 +1950,7 @@ mod tests {
 
             cpu.registers.set_register_at(13, 0x1000);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             let mut bus = cpu.bus;
 
@@ -940,7 +1967,7 @@ mod tests {
     fn arm_half_word_data_transfer() {
         {
             // Register offset
-            let op_code = 0b1110_0001_1000_0010_0000_0000_1011_0001;
+            let op_code = ArmAsm::strh(0).base(2).reg(1).encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
             assert_eq!(
@@ -959,7 +1986,7 @@ mod tests {
             );
 
             cpu.registers.set_register_at(0, 16843009);
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             let mut bus = cpu.bus;
 
@@ -972,13 +1999,13 @@ mod tests {
         {
             // Immediate offset, pre-index, down, no wb, load, unsigned halfword
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b1110_000_1_0_1_0_1_0000_0001_0001_1_01_1_1100;
+            let op_code = ArmAsm::ldrh(1).base(0).offset(-28).encode();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 100);
             cpu.bus.write_word(100 - 0b11100, 0xFFFF1234);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), 0x1234);
             assert_eq!(cpu.registers.register_at(0), 100);
@@ -986,13 +2013,13 @@ mod tests {
         {
             // Immediate offset, pre-index, down, wb, load, unsigned halfword
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b1110_000_1_0_1_1_1_0000_0001_0001_1_01_1_1100;
+            let op_code = ArmAsm::ldrh(1).base(0).offset(-28).write_back().encode();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 100);
             cpu.bus.write_word(100 - 0b11100, 0xFFFF1234);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), 0x1234);
             assert_eq!(cpu.registers.register_at(0), 100 - 0b11100);
@@ -1000,13 +2027,13 @@ mod tests {
         {
             // Immediate offset, pre-index, up, wb, load, unsigned halfword
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b1110_000_1_1_1_1_1_0000_0001_0001_1_01_1_1100;
+            let op_code = ArmAsm::ldrh(1).base(0).offset(28).write_back().encode();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 100);
             cpu.bus.write_word(100 + 0b11100, 0xFFFF1234);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), 0x1234);
             assert_eq!(cpu.registers.register_at(0), 100 + 0b11100);
@@ -1014,13 +2041,13 @@ mod tests {
         {
             // Immediate offset, post-index, down, no wb (but implicit), load, unsigned halfword
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b1110_000_0_0_1_0_1_0000_0001_0001_1_01_1_1111;
+            let op_code = ArmAsm::ldrh(1).base(0).offset(-31).post().encode();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 100);
             cpu.bus.write_word(100, 0xFFFF1234);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), 0x1234);
             assert_eq!(cpu.registers.register_at(0), 100 - 0b11111);
@@ -1028,13 +2055,13 @@ mod tests {
         {
             // Immediate offset, post-index, down, no wb (but implicit), load, signed byte
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b1110_000_0_0_1_0_1_0000_0001_0001_1_10_1_1111;
+            let op_code = ArmAsm::ldrsb(1).base(0).offset(-31).post().encode();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 100);
             cpu.bus.write_byte(100, -5_i8 as u8);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), -5_i32 as u32);
             assert_eq!(cpu.registers.register_at(0), 100 - 0b11111);
@@ -1042,13 +2069,13 @@ mod tests {
         {
             // Immediate offset, post-index, down, no wb (but implicit), load, signed halfword
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b1110_000_0_0_1_0_1_0000_0001_0001_1_11_1_1111;
+            let op_code = ArmAsm::ldrsh(1).base(0).offset(-31).post().encode();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 100);
             cpu.bus.write_half_word(100, -300_i16 as u16);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), -300_i32 as u32);
             assert_eq!(cpu.registers.register_at(0), 100 - 0b11111);
@@ -1056,13 +2083,13 @@ mod tests {
         {
             // Immediate offset, post-index, down, no wb (but implicit), store, unsigned halfword
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b1110_000_0_0_1_0_0_0000_0001_0001_1_01_1_1111;
+            let op_code = ArmAsm::strh(1).base(0).offset(-31).post().encode();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 100);
             cpu.registers.set_register_at(1, 0xFFFF1234);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.bus.read_word(100), 0x1234);
             assert_eq!(cpu.registers.register_at(0), 100 - 0b11111);
@@ -1070,13 +2097,13 @@ mod tests {
         {
             // Immediate offset, post-index, down, no wb (but implicit), store PC, unsigned halfword
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b1110_000_0_0_1_0_0_0000_1111_0001_1_01_1_1111;
+            let op_code = ArmAsm::strh(15).base(0).offset(-31).post().encode();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 100);
             cpu.registers.set_program_counter(500);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.bus.read_word(100), 504);
             assert_eq!(cpu.registers.register_at(0), 100 - 0b11111);
@@ -1084,12 +2111,12 @@ mod tests {
         {
             // Immediate offset, pre-index, down, no wb, store PC, unsigned halfword, base PC
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b1110_000_1_0_1_0_0_1111_1111_0001_1_01_1_1100;
+            let op_code = ArmAsm::strh(15).base(15).offset(-28).encode();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_program_counter(500);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.bus.read_word(500 - 0b11100), 504);
             assert_eq!(cpu.registers.program_counter(), 500);
@@ -1097,14 +2124,14 @@ mod tests {
         {
             // Register offset, post-index, down, no wb (but implicit), store PC, unsigned halfword
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b1110_000_0_0_0_0_0_0000_1111_0000_1_01_1_0010;
+            let op_code = ArmAsm::strh(15).base(0).reg(2).post().down().encode();
             let op_code: ArmModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 100);
             cpu.registers.set_program_counter(500);
             cpu.registers.set_register_at(2, 0b11111);
 
-            cpu.execute_arm(op_code);
+            cpu.execute_arm(op_code.raw);
 
             assert_eq!(cpu.bus.read_word(100), 504);
             assert_eq!(cpu.registers.register_at(0), 100 - 0b11111);
@@ -1114,12 +2141,12 @@ mod tests {
     #[test]
     fn thumb_pc_relative_load() {
         let mut cpu = Arm7tdmi::default();
-        let op_code = 0b0100_1001_0101_1000_u16;
+        let op_code = ThumbAsm::ldr(1).pc().offset(352).encode();
         let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
         cpu.registers.set_register_at(1, 10);
         cpu.bus.write_byte(352, 1);
-        cpu.execute_thumb(op_code);
+        cpu.execute_thumb(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(1), 1);
     }
@@ -1129,28 +2156,28 @@ mod tests {
         // Checks Store Word
         {
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b0101_00_0_000_001_010;
+            let op_code = ThumbAsm::str(2).base(1).reg(0).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 100);
             cpu.registers.set_register_at(1, 100);
             cpu.registers.set_register_at(2, 0xFEEFAC1F);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.bus.read_word(200), 0xFEEFAC1F);
         }
         // Checks Store Byte
         {
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b0101_01_0_000_001_010;
+            let op_code = ThumbAsm::str(2).base(1).reg(0).byte().encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 100);
             cpu.registers.set_register_at(1, 100);
             cpu.registers.set_register_at(2, 0xFEEFAC1F);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.bus.read_byte(200), 0x1F);
             assert_eq!(cpu.bus.read_byte(201), 0);
@@ -1160,28 +2187,28 @@ mod tests {
         // Checks Load Word
         {
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b0101_10_0_000_001_010;
+            let op_code = ThumbAsm::ldr(2).base(1).reg(0).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 100);
             cpu.registers.set_register_at(1, 100);
             cpu.bus.write_word(200, 0xFEEFAC1F);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(2), 0xFEEFAC1F);
         }
         // Checks Load Byte
         {
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b0101_11_0_000_001_010;
+            let op_code = ThumbAsm::ldr(2).base(1).reg(0).byte().encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 100);
             cpu.registers.set_register_at(1, 100);
             cpu.bus.write_word(200, 0xFEEFAC1F);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(2), 0x1F);
         }
@@ -1191,54 +2218,54 @@ mod tests {
     fn thumb_load_store_immediate_offset() {
         {
             // Store Word
-            let op_code = 0b0110_0011_0111_1000;
+            let op_code = ThumbAsm::str(0).base(7).offset(52).encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
             assert_eq!(op_code.instruction, Instruction::LoadStoreImmOffset);
 
             cpu.registers.set_register_at(7, 4);
             cpu.registers.set_register_at(0, 0xFFFF_FFFF);
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             let mut bus = cpu.bus;
             assert_eq!(bus.read_word(56), 0xFFFF_FFFF);
         }
         {
             // Store Word misaligned
-            let op_code = 0b0110_0011_0111_1000;
+            let op_code = ThumbAsm::str(0).base(7).offset(52).encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
             assert_eq!(op_code.instruction, Instruction::LoadStoreImmOffset);
 
             cpu.registers.set_register_at(7, 2);
             cpu.registers.set_register_at(0, 0xFFFF_FFFF);
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             let mut bus = cpu.bus;
             assert_eq!(bus.read_word(52), 0xFFFF_FFFF);
         }
         {
             // Load Word
-            let op_code = 0b0110_1011_0000_1111;
+            let op_code = ThumbAsm::ldr(7).base(1).offset(48).encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
             assert_eq!(op_code.instruction, Instruction::LoadStoreImmOffset);
             cpu.bus.write_word(1048, 0xFFFF_FFFF);
             cpu.registers.set_register_at(1, 1000);
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(7), 0xFFFF_FFFF);
         }
         {
             // Store Byte
-            let op_code = 0b0111_0010_0011_1000;
+            let op_code = ThumbAsm::str(0).base(7).offset(8).byte().encode();
             let mut cpu = Arm7tdmi::default();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
             assert_eq!(op_code.instruction, Instruction::LoadStoreImmOffset);
 
             cpu.registers.set_register_at(7, 2);
             cpu.registers.set_register_at(0, 0xFFFF_FFFF);
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             let mut bus = cpu.bus;
             assert_eq!(bus.read_byte(10), 0xFF);
@@ -1250,12 +2277,12 @@ mod tests {
         // Check sub
         {
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b00011_1_1_111_000_001;
+            let op_code = ThumbAsm::sub(1, 0, 0).imm(7).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 0b110);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), -1_i32 as u32);
             assert!(!cpu.cpsr.zero_flag());
@@ -1267,12 +2294,12 @@ mod tests {
         // Check add
         {
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b00011_1_0_001_000_001;
+            let op_code = ThumbAsm::add(1, 0, 0).imm(1).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, u32::MAX);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), 0);
             assert!(cpu.cpsr.zero_flag());
@@ -1285,35 +2312,134 @@ mod tests {
     #[test]
     fn thumb_cond_branch() {
         let mut cpu = Arm7tdmi::default();
-        let op_code = 0b1101_1011_11111100;
+        let op_code = ThumbAsm::b_cond(Condition::LT, -8).encode();
         let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
         cpu.registers.set_program_counter(1000);
 
-        cpu.execute_thumb(op_code);
+        cpu.execute_thumb(op_code.raw);
 
         // Asserting no branch since condition is not satisfied
         assert_eq!(cpu.registers.program_counter(), 1000);
 
         cpu.cpsr.set_sign_flag(true);
 
-        let op_code = 0b1101_1011_11111100;
+        let op_code = ThumbAsm::b_cond(Condition::LT, -8).encode();
         let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
-        cpu.execute_thumb(op_code);
+        cpu.execute_thumb(op_code.raw);
 
         // Asserting branch now that we set the condition
         assert_eq!(cpu.registers.program_counter(), 1000 - 8);
     }
 
+    /// Runs `B<condition> -8` from 1000 and returns whether it branched.
+    fn thumb_branches(cpu: &mut Arm7tdmi, condition: Condition) -> bool {
+        cpu.registers.set_program_counter(1000);
+        let op_code: ThumbModeOpcode = Arm7tdmi::decode(ThumbAsm::b_cond(condition, -8).encode());
+        cpu.execute_thumb(op_code.raw);
+
+        cpu.registers.program_counter() != 1000
+    }
+
+    #[test]
+    fn thumb_cond_branch_every_flag_combination() {
+        use Condition::{CC, CS, EQ, GE, GT, HI, LE, LS, LT, MI, NE, PL, VC, VS};
+
+        for flags in 0..16_u8 {
+            let (n, z, c, v) = (
+                flags & 8 != 0,
+                flags & 4 != 0,
+                flags & 2 != 0,
+                flags & 1 != 0,
+            );
+            let mut cpu = Arm7tdmi::default();
+            cpu.cpsr.set_sign_flag(n);
+            cpu.cpsr.set_zero_flag(z);
+            cpu.cpsr.set_carry_flag(c);
+            cpu.cpsr.set_overflow_flag(v);
+
+            // The table of the ARM7TDMI data sheet.
+            for (condition, expected) in [
+                (EQ, z),
+                (NE, !z),
+                (CS, c),
+                (CC, !c),
+                (MI, n),
+                (PL, !n),
+                (VS, v),
+                (VC, !v),
+                (HI, c && !z),
+                (LS, !c || z),
+                (GE, n == v),
+                (LT, n != v),
+                (GT, !z && n == v),
+                (LE, z || n != v),
+            ] {
+                assert_eq!(
+                    thumb_branches(&mut cpu, condition),
+                    expected,
+                    "{condition:?} with NZCV={flags:04b}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn thumb_cond_branch_after_cmp() {
+        use Condition::{CC, CS, EQ, GE, GT, HI, LE, LS, LT, NE};
+
+        // The edges of the signed and unsigned ranges, where the overflow flag decides.
+        let values = [
+            0,
+            1,
+            2,
+            0x7FFF_FFFE,
+            0x7FFF_FFFF,
+            0x8000_0000,
+            0x8000_0001,
+            0xFFFF_FFFE,
+            0xFFFF_FFFF,
+        ];
+        for a in values {
+            for b in values {
+                let mut cpu = Arm7tdmi::default();
+                cpu.registers.set_register_at(0, a);
+                cpu.registers.set_register_at(1, b);
+                let cmp = ThumbAsm::alu(ThumbModeAluInstruction::Cmp, 0, 1).encode();
+                cpu.execute_thumb(cmp);
+
+                let (signed_a, signed_b) = (a.cast_signed(), b.cast_signed());
+                for (condition, expected) in [
+                    (EQ, a == b),
+                    (NE, a != b),
+                    (CS, a >= b),
+                    (CC, a < b),
+                    (HI, a > b),
+                    (LS, a <= b),
+                    (GE, signed_a >= signed_b),
+                    (LT, signed_a < signed_b),
+                    (GT, signed_a > signed_b),
+                    (LE, signed_a <= signed_b),
+                ] {
+                    assert_eq!(
+                        thumb_branches(&mut cpu, condition),
+                        expected,
+                        "CMP 0x{a:08X}, 0x{b:08X} then B{condition:?}"
+                    );
+                }
+            }
+        }
+    }
+
     #[test]
     fn thumb_uncond_branch() {
         let mut cpu = Arm7tdmi::default();
-        let op_code = 0b1110_0001_0010_1111;
+        let op_code = ThumbAsm::b(606).encode();
         let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
         cpu.registers.set_program_counter(1000);
 
-        cpu.execute_thumb(op_code);
+        cpu.execute_thumb(op_code.raw);
 
         assert_eq!(cpu.registers.program_counter(), 1606);
     }
@@ -1323,63 +2449,63 @@ mod tests {
         {
             // BX Hs
             let mut cpu = Arm7tdmi::default();
-            let op_code: u16 = 0b0100_0111_0111_0000;
+            let op_code = ThumbAsm::bx(14).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(14, 123);
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.program_counter(), 122);
         }
         {
             // Add Rd, Hs
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b010001_00_0_1_000_001;
+            let op_code = ThumbAsm::high(ThumbHighRegisterOperation::Add, 1, 8).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(8, 10);
             cpu.registers.set_register_at(1, 10);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), 20);
         }
         {
             // Add Hd, Rs
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b010001_00_1_0_000_001;
+            let op_code = ThumbAsm::high(ThumbHighRegisterOperation::Add, 9, 0).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 10);
             cpu.registers.set_register_at(9, 10);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(9), 20);
         }
         {
             // Add Hd, Hs
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b010001_00_1_1_000_001;
+            let op_code = ThumbAsm::high(ThumbHighRegisterOperation::Add, 9, 8).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(8, 10);
             cpu.registers.set_register_at(9, 10);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(9), 20);
         }
         {
             // Cmp Rd, Hs
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b010001_01_0_1_000_001;
+            let op_code = ThumbAsm::high(ThumbHighRegisterOperation::Cmp, 1, 8).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(8, 10);
             cpu.registers.set_register_at(1, 10);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert!(cpu.cpsr.zero_flag());
             assert!(!cpu.cpsr.sign_flag());
@@ -1389,13 +2515,13 @@ mod tests {
         {
             // Cmp Hd, Rs
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b010001_01_1_0_000_001;
+            let op_code = ThumbAsm::high(ThumbHighRegisterOperation::Cmp, 9, 0).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 11);
             cpu.registers.set_register_at(9, 10);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert!(!cpu.cpsr.zero_flag());
             assert!(cpu.cpsr.sign_flag());
@@ -1405,13 +2531,13 @@ mod tests {
         {
             // Cmp Hd, Hs
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b010001_01_1_1_000_001;
+            let op_code = ThumbAsm::high(ThumbHighRegisterOperation::Cmp, 9, 8).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(8, 10);
             cpu.registers.set_register_at(9, 11);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert!(!cpu.cpsr.zero_flag());
             assert!(!cpu.cpsr.sign_flag());
@@ -1421,39 +2547,39 @@ mod tests {
         {
             // Mov Rd, Hs
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b010001_10_0_1_000_001;
+            let op_code = ThumbAsm::high(ThumbHighRegisterOperation::Mov, 1, 8).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(8, 10);
             cpu.registers.set_register_at(1, 11);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), 10);
         }
         {
             // Mov Hd, Rs
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b010001_10_1_0_000_001;
+            let op_code = ThumbAsm::high(ThumbHighRegisterOperation::Mov, 9, 0).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(0, 10);
             cpu.registers.set_register_at(9, 11);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(9), 10);
         }
         {
             // Mov Hd, Hs
             let mut cpu = Arm7tdmi::default();
-            let op_code = 0b010001_10_1_1_000_001;
+            let op_code = ThumbAsm::high(ThumbHighRegisterOperation::Mov, 9, 8).encode();
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
             cpu.registers.set_register_at(8, 10);
             cpu.registers.set_register_at(9, 11);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(9), 10);
         }
@@ -1531,7 +2657,7 @@ mod tests {
                 cpu.registers.set_register_at(r, r.try_into().unwrap());
             }
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.bus.read_word(1000 - 4), 1000);
             assert_eq!(cpu.bus.read_word(1000 - 4 - 4), 7);
@@ -1553,7 +2679,7 @@ mod tests {
             cpu.bus.write_word(1012, 400);
             cpu.bus.write_word(1016, 500);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(4), 100);
             assert_eq!(cpu.registers.register_at(5), 200);
@@ -1566,6 +2692,63 @@ mod tests {
             );
             assert_eq!(cpu.registers.register_at(REG_SP), 1020);
         }
+        {
+            // POP {PC} ignores bit 0 and stays in Thumb state
+            let mut cpu = Arm7tdmi::default();
+            cpu.cpsr.set_cpu_state(CpuState::Thumb);
+            let op_code: ThumbModeOpcode = Arm7tdmi::decode(0b1011_1101_0000_0000);
+
+            cpu.registers.set_register_at(REG_SP, 1000);
+            cpu.bus.write_word(1000, 0x0800_0101);
+
+            cpu.execute_thumb(op_code.raw);
+
+            assert_eq!(cpu.registers.program_counter(), 0x0800_0100);
+            assert!(matches!(cpu.cpsr.cpu_state(), CpuState::Thumb));
+            assert_eq!(cpu.registers.register_at(REG_SP), 1004);
+        }
+        {
+            // PUSH {} stores PC and moves SP by 0x40
+            let mut cpu = Arm7tdmi::default();
+            let op_code: ThumbModeOpcode = Arm7tdmi::decode(0b1011_0100_0000_0000);
+
+            cpu.registers.set_program_counter(2000);
+            cpu.registers.set_register_at(REG_SP, 1000);
+
+            cpu.execute_thumb(op_code.raw);
+
+            assert_eq!(cpu.registers.register_at(REG_SP), 1000 - 0x40);
+            assert_eq!(cpu.bus.read_word(1000 - 0x40), 2002);
+            assert_eq!(cpu.bus.read_word(1000 - 4), 0);
+        }
+        {
+            // POP {} loads PC and moves SP by 0x40
+            let mut cpu = Arm7tdmi::default();
+            let op_code: ThumbModeOpcode = Arm7tdmi::decode(0b1011_1100_0000_0000);
+
+            cpu.registers.set_register_at(REG_SP, 1000);
+            cpu.bus.write_word(1000, 3000);
+
+            cpu.execute_thumb(op_code.raw);
+
+            assert_eq!(cpu.registers.program_counter(), 3000);
+            assert_eq!(cpu.registers.register_at(REG_SP), 1000 + 0x40);
+        }
+        {
+            // A misaligned SP is accessed aligned but moves by whole words
+            let mut cpu = Arm7tdmi::default();
+            let op_code: ThumbModeOpcode = Arm7tdmi::decode(0b1011_0100_0000_0011);
+
+            cpu.registers.set_register_at(REG_SP, 1002);
+            cpu.registers.set_register_at(0, 10);
+            cpu.registers.set_register_at(1, 11);
+
+            cpu.execute_thumb(op_code.raw);
+
+            assert_eq!(cpu.registers.register_at(REG_SP), 994);
+            assert_eq!(cpu.bus.read_word(992), 10);
+            assert_eq!(cpu.bus.read_word(996), 11);
+        }
     }
 
     #[test]
@@ -1578,7 +2761,7 @@ mod tests {
 
             cpu.registers.set_register_at(REG_SP, 1000);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(REG_SP), 1000 + (7 << 2));
         }
@@ -1589,7 +2772,7 @@ mod tests {
 
         cpu.registers.set_register_at(REG_SP, 1000);
 
-        cpu.execute_thumb(op_code);
+        cpu.execute_thumb(op_code.raw);
 
         assert_eq!(cpu.registers.register_at(REG_SP), 1000 - (7 << 2));
     }
@@ -1605,7 +2788,7 @@ mod tests {
             cpu.registers.set_register_at(REG_SP, 100);
             cpu.bus.write_word(100 + 0b11100, 999);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(0), 999);
         }
@@ -1618,7 +2801,7 @@ mod tests {
             cpu.registers.set_register_at(REG_SP, 100);
             cpu.registers.set_register_at(0, 999);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.bus.read_word(100 + 0b11100), 999);
         }
@@ -1636,7 +2819,7 @@ mod tests {
             cpu.cpsr.set_zero_flag(true);
             cpu.registers.set_register_at(0, 0xFFFF_FFFF);
             cpu.registers.set_register_at(4, 1);
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(0), 0xFFFF_FFFF);
             assert!(cpu.cpsr.sign_flag());
@@ -1651,7 +2834,7 @@ mod tests {
             cpu.cpsr.set_zero_flag(true);
             cpu.registers.set_register_at(0, 1000);
             cpu.registers.set_register_at(3, 8);
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(0), 8);
             assert!(!cpu.cpsr.sign_flag());
@@ -1663,7 +2846,7 @@ mod tests {
             let op_code = 0b0100_0010_0011_1110;
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert!(!cpu.cpsr.sign_flag());
             assert!(cpu.cpsr.zero_flag());
@@ -1678,7 +2861,7 @@ mod tests {
             cpu.registers.set_register_at(5, 97);
             cpu.cpsr.set_sign_flag(true);
             cpu.cpsr.set_zero_flag(true);
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(2), 123);
             assert!(!cpu.cpsr.sign_flag());
@@ -1690,7 +2873,7 @@ mod tests {
             let op_code = 0b0100_0011_1100_1111;
             let op_code: ThumbModeOpcode = Arm7tdmi::decode(op_code);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(7), !0);
             assert!(cpu.cpsr.sign_flag());
@@ -1705,7 +2888,7 @@ mod tests {
             cpu.registers.set_register_at(0, 0x1);
             cpu.registers.set_register_at(1, 0x20);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(0), 0);
             assert_eq!(cpu.registers.register_at(1), 32);
@@ -1727,7 +2910,7 @@ mod tests {
 
         cpu.registers.set_program_counter(100);
         cpu.registers.set_register_at(REG_LR, 200);
-        cpu.execute_thumb(op_code);
+        cpu.execute_thumb(op_code.raw);
 
         // 98 | 1
         assert_eq!(cpu.registers.register_at(REG_LR), 99);
@@ -1745,7 +2928,7 @@ mod tests {
             cpu.registers.set_register_at(0, 100);
             cpu.bus.write_half_word(102, 0xFF);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.registers.register_at(1), 0xFF);
         }
@@ -1758,7 +2941,7 @@ mod tests {
             cpu.registers.set_register_at(0, 100);
             cpu.registers.set_register_at(1, 0xFF);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             assert_eq!(cpu.bus.read_half_word(102), 0xFF);
         }
@@ -1856,7 +3039,7 @@ mod tests {
 
             (*case.prepare_fn)(&mut cpu);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             (*case.check_fn)(cpu);
         }
@@ -1910,7 +3093,7 @@ mod tests {
 
             (*case.prepare_fn)(&mut cpu);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             (*case.check_fn)(cpu);
         }
@@ -1972,7 +3155,7 @@ mod tests {
 
             (*case.prepare_fn)(&mut cpu);
 
-            cpu.execute_thumb(op_code);
+            cpu.execute_thumb(op_code.raw);
 
             (*case.check_fn)(cpu);
         }
@@ -0,0 +1,834 @@
+//! Builders encoding ARM and Thumb instructions, so that tests read as assembly
+//! instead of bit patterns:
+//!
+//! ```
+//! use emu::cpu::asm::{assemble_arm, ArmAsm};
+//!
+//! // LDR R1, [R11, #3]
+//! let op_code = ArmAsm::ldr(1).base(11).offset(3).encode();
+//!
+//! assert_eq!(assemble_arm("LDR R1, [R11, #3]", 0x0800_0000), Ok(op_code));
+//! ```
+//!
+//! Registers are plain numbers, offsets are in bytes and branch offsets are relative
+//! to the value of PC when the instruction executes (pipeline included).
+//! [`assemble_arm`] and [`assemble_thumb`] do the same starting from text, for the debugger.
+//!
+//! The builders panic when a modifier doesn't apply to the instruction or when a value
+//! can't be encoded, the text assembler validates everything before using them.
+
+mod parser;
+
+pub use parser::{assemble_arm, assemble_thumb, AssembleError};
+
+use crate::cpu::arm::alu_instruction::ArmModeAluInstr;
+use crate::cpu::condition::Condition;
+use crate::cpu::flags::ShiftKind;
+use crate::cpu::thumb::alu_instructions::{ThumbHighRegisterOperation, ThumbModeAluInstruction};
+
+/// Encodes `value` as the 8bit immediate rotated right by an even amount
+/// used by the data processing instructions, if possible.
+#[must_use]
+pub const fn arm_immediate(value: u32) -> Option<u32> {
+    let mut rotate = 0;
+    while rotate < 16 {
+        let base = value.rotate_left(rotate * 2);
+        if base <= 0xFF {
+            return Some(rotate << 8 | base);
+        }
+        rotate += 1;
+    }
+
+    None
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ArmFormat {
+    DataProcessing,
+    Multiply,
+    PsrTransfer,
+    BranchAndExchange,
+    HalfwordTransfer,
+    SingleTransfer,
+    BlockTransfer,
+    Branch,
+    SoftwareInterrupt,
+}
+
+/// Builder of a single ARM instruction, `AL` condition unless [`Self::cond`] is used.
+#[derive(Clone, Copy, Debug)]
+#[must_use]
+pub struct ArmAsm {
+    format: ArmFormat,
+    op_code: u32,
+}
+
+impl ArmAsm {
+    const fn new(format: ArmFormat, op_code: u32) -> Self {
+        Self {
+            format,
+            op_code: (Condition::AL as u32) << 28 | op_code,
+        }
+    }
+
+    /// Data processing instruction, the second operand is set with [`Self::imm`] or [`Self::reg`].
+    pub const fn alu(alu: ArmModeAluInstr, rd: u32, rn: u32) -> Self {
+        let op_code = (alu as u32) << 21 | rn << 16 | rd << 12;
+        let s = Self::new(ArmFormat::DataProcessing, op_code);
+
+        // Without S the test operations are PSR transfers.
+        match alu {
+            ArmModeAluInstr::Tst
+            | ArmModeAluInstr::Teq
+            | ArmModeAluInstr::Cmp
+            | ArmModeAluInstr::Cmn => s.set_flags(),
+            _ => s,
+        }
+    }
+
+    pub const fn mov(rd: u32) -> Self {
+        Self::alu(ArmModeAluInstr::Mov, rd, 0)
+    }
+
+    pub const fn mvn(rd: u32) -> Self {
+        Self::alu(ArmModeAluInstr::Mvn, rd, 0)
+    }
+
+    pub const fn add(rd: u32, rn: u32) -> Self {
+        Self::alu(ArmModeAluInstr::Add, rd, rn)
+    }
+
+    pub const fn sub(rd: u32, rn: u32) -> Self {
+        Self::alu(ArmModeAluInstr::Sub, rd, rn)
+    }
+
+    pub const fn cmp(rn: u32) -> Self {
+        Self::alu(ArmModeAluInstr::Cmp, 0, rn)
+    }
+
+    pub const fn tst(rn: u32) -> Self {
+        Self::alu(ArmModeAluInstr::Tst, 0, rn)
+    }
+
+    /// `MUL rd, rm, rs`
+    pub const fn mul(rd: u32, rm: u32, rs: u32) -> Self {
+        Self::new(ArmFormat::Multiply, rd << 16 | rs << 8 | 0b1001 << 4 | rm)
+    }
+
+    /// `MLA rd, rm, rs, rn`
+    pub const fn mla(rd: u32, rm: u32, rs: u32, rn: u32) -> Self {
+        Self::new(
+            ArmFormat::Multiply,
+            1 << 21 | rd << 16 | rn << 12 | rs << 8 | 0b1001 << 4 | rm,
+        )
+    }
+
+    const fn multiply_long(op: u32, rdlo: u32, rdhi: u32, rm: u32, rs: u32) -> Self {
+        Self::new(
+            ArmFormat::Multiply,
+            op << 21 | rdhi << 16 | rdlo << 12 | rs << 8 | 0b1001 << 4 | rm,
+        )
+    }
+
+    /// `UMULL rdlo, rdhi, rm, rs`
+    pub const fn umull(rdlo: u32, rdhi: u32, rm: u32, rs: u32) -> Self {
+        Self::multiply_long(0b0100, rdlo, rdhi, rm, rs)
+    }
+
+    /// `UMLAL rdlo, rdhi, rm, rs`
+    pub const fn umlal(rdlo: u32, rdhi: u32, rm: u32, rs: u32) -> Self {
+        Self::multiply_long(0b0101, rdlo, rdhi, rm, rs)
+    }
+
+    /// `SMULL rdlo, rdhi, rm, rs`
+    pub const fn smull(rdlo: u32, rdhi: u32, rm: u32, rs: u32) -> Self {
+        Self::multiply_long(0b0110, rdlo, rdhi, rm, rs)
+    }
+
+    /// `SMLAL rdlo, rdhi, rm, rs`
+    pub const fn smlal(rdlo: u32, rdhi: u32, rm: u32, rs: u32) -> Self {
+        Self::multiply_long(0b0111, rdlo, rdhi, rm, rs)
+    }
+
+    /// `MRS rd, CPSR`, see [`Self::spsr`].
+    pub const fn mrs(rd: u32) -> Self {
+        Self::new(ArmFormat::PsrTransfer, 0b0001_0000_1111 << 16 | rd << 12)
+    }
+
+    /// `MSR CPSR, rm`, see [`Self::spsr`].
+    pub const fn msr(rm: u32) -> Self {
+        Self::new(ArmFormat::PsrTransfer, 0b0001_0010_1001_1111 << 12 | rm)
+    }
+
+    /// `BX rn`
+    pub const fn bx(rn: u32) -> Self {
+        Self::new(ArmFormat::BranchAndExchange, 0x012F_FF10 | rn)
+    }
+
+    const fn halfword_transfer(load: bool, sh: u32, rd: u32) -> Self {
+        // Pre-indexed, up, immediate offset 0.
+        Self::new(
+            ArmFormat::HalfwordTransfer,
+            1 << 24
+                | 1 << 23
+                | 1 << 22
+                | (load as u32) << 20
+                | rd << 12
+                | 1 << 7
+                | sh << 5
+                | 1 << 4,
+        )
+    }
+
+    pub const fn ldrh(rd: u32) -> Self {
+        Self::halfword_transfer(true, 0b01, rd)
+    }
+
+    pub const fn strh(rd: u32) -> Self {
+        Self::halfword_transfer(false, 0b01, rd)
+    }
+
+    pub const fn ldrsb(rd: u32) -> Self {
+        Self::halfword_transfer(true, 0b10, rd)
+    }
+
+    pub const fn ldrsh(rd: u32) -> Self {
+        Self::halfword_transfer(true, 0b11, rd)
+    }
+
+    const fn single_transfer(load: bool, rd: u32) -> Self {
+        // Pre-indexed, up, immediate offset 0.
+        Self::new(
+            ArmFormat::SingleTransfer,
+            1 << 26 | 1 << 24 | 1 << 23 | (load as u32) << 20 | rd << 12,
+        )
+    }
+
+    /// `LDR rd, [base, offset]`, see [`Self::base`] and [`Self::offset`].
+    pub const fn ldr(rd: u32) -> Self {
+        Self::single_transfer(true, rd)
+    }
+
+    /// `STR rd, [base, offset]`, see [`Self::base`] and [`Self::offset`].
+    pub const fn str(rd: u32) -> Self {
+        Self::single_transfer(false, rd)
+    }
+
+    const fn block_transfer(load: bool, rn: u32, registers: u16) -> Self {
+        // Increment after.
+        Self::new(
+            ArmFormat::BlockTransfer,
+            1 << 27 | 1 << 23 | (load as u32) << 20 | rn << 16 | registers as u32,
+        )
+    }
+
+    /// `LDMIA rn, {registers}` with a bit for each register.
+    pub const fn ldm(rn: u32, registers: u16) -> Self {
+        Self::block_transfer(true, rn, registers)
+    }
+
+    /// `STMIA rn, {registers}` with a bit for each register.
+    pub const fn stm(rn: u32, registers: u16) -> Self {
+        Self::block_transfer(false, rn, registers)
+    }
+
+    pub const fn b(offset: i32) -> Self {
+        Self::new(
+            ArmFormat::Branch,
+            0b101 << 25 | (offset as u32 >> 2) & 0x00FF_FFFF,
+        )
+    }
+
+    pub const fn bl(offset: i32) -> Self {
+        let s = Self::b(offset);
+        Self {
+            op_code: s.op_code | 1 << 24,
+            ..s
+        }
+    }
+
+    pub const fn swi(comment: u32) -> Self {
+        Self::new(
+            ArmFormat::SoftwareInterrupt,
+            0b1111 << 24 | comment & 0x00FF_FFFF,
+        )
+    }
+
+    pub const fn cond(self, condition: Condition) -> Self {
+        Self {
+            op_code: self.op_code & 0x0FFF_FFFF | (condition as u32) << 28,
+            ..self
+        }
+    }
+
+    const fn with_bit(self, bit: u32, value: bool) -> Self {
+        let op_code = if value {
+            self.op_code | 1 << bit
+        } else {
+            self.op_code & !(1 << bit)
+        };
+
+        Self { op_code, ..self }
+    }
+
+    const fn with_bits(self, mask: u32, bits: u32) -> Self {
+        Self {
+            op_code: self.op_code & !mask | bits,
+            ..self
+        }
+    }
+
+    /// Sets the condition codes (S bit).
+    pub const fn set_flags(self) -> Self {
+        self.with_bit(20, true)
+    }
+
+    /// Immediate second operand of a data processing instruction.
+    ///
+    /// # Panics
+    /// If `value` can't be encoded as a rotated 8bit value.
+    pub const fn imm(self, value: u32) -> Self {
+        assert!(matches!(self.format, ArmFormat::DataProcessing));
+        let Some(encoded) = arm_immediate(value) else {
+            panic!("immediate can't be encoded");
+        };
+
+        self.with_bit(25, true).with_bits(0xFFF, encoded)
+    }
+
+    /// Register second operand of a data processing instruction
+    /// or register offset of a transfer.
+    pub const fn reg(self, rm: u32) -> Self {
+        match self.format {
+            ArmFormat::DataProcessing => self.with_bit(25, false).with_bits(0xFFF, rm),
+            ArmFormat::SingleTransfer => self.with_bit(25, true).with_bits(0xFFF, rm),
+            ArmFormat::HalfwordTransfer => self.with_bit(22, false).with_bits(0xF0F, rm),
+            _ => panic!("register operand not supported"),
+        }
+    }
+
+    /// Shifts the register set with [`Self::reg`] by an immediate amount.
+    pub const fn shift(self, kind: ShiftKind, amount: u32) -> Self {
+        assert!(matches!(
+            self.format,
+            ArmFormat::DataProcessing | ArmFormat::SingleTransfer
+        ));
+        self.with_bits(0xFF0, amount << 7 | (kind as u32) << 5)
+    }
+
+    /// Shifts the register set with [`Self::reg`] by the value of `rs`.
+    pub const fn shift_reg(self, kind: ShiftKind, rs: u32) -> Self {
+        assert!(matches!(self.format, ArmFormat::DataProcessing));
+        self.with_bits(0xFF0, rs << 8 | (kind as u32) << 5 | 1 << 4)
+    }
+
+    /// Base register of a transfer.
+    pub const fn base(self, rn: u32) -> Self {
+        self.with_bits(0xF << 16, rn << 16)
+    }
+
+    /// Immediate offset of a transfer, negative values count down.
+    ///
+    /// # Panics
+    /// If the offset doesn't fit the instruction.
+    pub const fn offset(self, offset: i32) -> Self {
+        let s = self.with_bit(23, offset >= 0);
+        let offset = offset.unsigned_abs();
+
+        match self.format {
+            ArmFormat::SingleTransfer => {
+                assert!(offset <= 0xFFF);
+                s.with_bit(25, false).with_bits(0xFFF, offset)
+            }
+            ArmFormat::HalfwordTransfer => {
+                assert!(offset <= 0xFF);
+                s.with_bit(22, true)
+                    .with_bits(0xF0F, (offset & 0xF0) << 4 | offset & 0xF)
+            }
+            _ => panic!("offset not supported"),
+        }
+    }
+
+    /// Transfers a byte instead of a word.
+    pub const fn byte(self) -> Self {
+        assert!(matches!(self.format, ArmFormat::SingleTransfer));
+        self.with_bit(22, true)
+    }
+
+    /// Adds the offset before the transfer.
+    pub const fn pre(self) -> Self {
+        self.with_bit(24, true)
+    }
+
+    /// Adds the offset after the transfer.
+    pub const fn post(self) -> Self {
+        self.with_bit(24, false)
+    }
+
+    /// Subtracts the offset (or decrements in block transfers).
+    pub const fn down(self) -> Self {
+        self.with_bit(23, false)
+    }
+
+    pub const fn write_back(self) -> Self {
+        self.with_bit(21, true)
+    }
+
+    /// Uses the SPSR in PSR transfers.
+    pub const fn spsr(self) -> Self {
+        assert!(matches!(self.format, ArmFormat::PsrTransfer));
+        self.with_bit(22, true)
+    }
+
+    /// Block transfers with the user bank or restoring the CPSR (`^`).
+    pub const fn psr(self) -> Self {
+        assert!(matches!(self.format, ArmFormat::BlockTransfer));
+        self.with_bit(22, true)
+    }
+
+    #[must_use]
+    pub const fn encode(self) -> u32 {
+        self.op_code
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ThumbFormat {
+    MoveShifted,
+    AddSubtract,
+    Immediate,
+    Alu,
+    HighRegister,
+    PcRelativeLoad,
+    RegisterOffset,
+    ImmediateOffset,
+    HalfwordImmediate,
+    SpRelative,
+    LoadAddress,
+    AddSp,
+    PushPop,
+    MultipleTransfer,
+    ConditionalBranch,
+    SoftwareInterrupt,
+    Branch,
+}
+
+/// Builder of a single Thumb instruction.
+#[derive(Clone, Copy, Debug)]
+#[must_use]
+pub struct ThumbAsm {
+    format: ThumbFormat,
+    op_code: u16,
+    /// Offset in bytes of loads and stores, scaled when encoding.
+    offset: u16,
+}
+
+impl ThumbAsm {
+    const fn new(format: ThumbFormat, op_code: u16) -> Self {
+        Self {
+            format,
+            op_code,
+            offset: 0,
+        }
+    }
+
+    const fn move_shifted(op: u16, rd: u16, rs: u16, offset: u16) -> Self {
+        Self::new(
+            ThumbFormat::MoveShifted,
+            op << 11 | (offset & 0x1F) << 6 | rs << 3 | rd,
+        )
+    }
+
+    /// `LSL rd, rs, #offset`
+    pub const fn lsl(rd: u16, rs: u16, offset: u16) -> Self {
+        Self::move_shifted(0, rd, rs, offset)
+    }
+
+    /// `LSR rd, rs, #offset`
+    pub const fn lsr(rd: u16, rs: u16, offset: u16) -> Self {
+        Self::move_shifted(1, rd, rs, offset)
+    }
+
+    /// `ASR rd, rs, #offset`
+    pub const fn asr(rd: u16, rs: u16, offset: u16) -> Self {
+        Self::move_shifted(2, rd, rs, offset)
+    }
+
+    /// `ADD rd, rs, rn`, use [`Self::imm`] for an immediate instead of `rn`.
+    pub const fn add(rd: u16, rs: u16, rn: u16) -> Self {
+        Self::new(
+            ThumbFormat::AddSubtract,
+            0b00011 << 11 | rn << 6 | rs << 3 | rd,
+        )
+    }
+
+    /// `SUB rd, rs, rn`, use [`Self::imm`] for an immediate instead of `rn`.
+    pub const fn sub(rd: u16, rs: u16, rn: u16) -> Self {
+        Self::new(
+            ThumbFormat::AddSubtract,
+            0b00011 << 11 | 1 << 9 | rn << 6 | rs << 3 | rd,
+        )
+    }
+
+    const fn immediate(op: u16, rd: u16, value: u16) -> Self {
+        Self::new(
+            ThumbFormat::Immediate,
+            0b001 << 13 | op << 11 | rd << 8 | value & 0xFF,
+        )
+    }
+
+    /// `MOV rd, #value`
+    pub const fn mov_imm(rd: u16, value: u16) -> Self {
+        Self::immediate(0, rd, value)
+    }
+
+    /// `CMP rd, #value`
+    pub const fn cmp_imm(rd: u16, value: u16) -> Self {
+        Self::immediate(1, rd, value)
+    }
+
+    /// `ADD rd, #value`
+    pub const fn add_imm(rd: u16, value: u16) -> Self {
+        Self::immediate(2, rd, value)
+    }
+
+    /// `SUB rd, #value`
+    pub const fn sub_imm(rd: u16, value: u16) -> Self {
+        Self::immediate(3, rd, value)
+    }
+
+    /// ALU operation between low registers: `op rd, rs`.
+    pub const fn alu(op: ThumbModeAluInstruction, rd: u16, rs: u16) -> Self {
+        Self::new(
+            ThumbFormat::Alu,
+            0b01_0000 << 10 | (op as u16) << 6 | rs << 3 | rd,
+        )
+    }
+
+    /// Operation involving high registers: `op rd, rs`.
+    pub const fn high(op: ThumbHighRegisterOperation, rd: u16, rs: u16) -> Self {
+        let op = match op {
+            ThumbHighRegisterOperation::Add => 0,
+            ThumbHighRegisterOperation::Cmp => 1,
+            ThumbHighRegisterOperation::Mov => 2,
+            ThumbHighRegisterOperation::BxOrBlx => 3,
+        };
+
+        Self::new(
+            ThumbFormat::HighRegister,
+            0b01_0001 << 10
+                | op << 8
+                | ((rd >> 3) & 1) << 7
+                | ((rs >> 3) & 1) << 6
+                | (rs & 0b111) << 3
+                | rd & 0b111,
+        )
+    }
+
+    /// `BX rs`
+    pub const fn bx(rs: u16) -> Self {
+        Self::high(ThumbHighRegisterOperation::BxOrBlx, 0, rs)
+    }
+
+    /// `LDR rd, [base, #offset]`, see [`Self::base`], [`Self::pc`] and [`Self::sp`].
+    pub const fn ldr(rd: u16) -> Self {
+        Self::new(ThumbFormat::ImmediateOffset, 0b011 << 13 | 1 << 11 | rd)
+    }
+
+    /// `STR rd, [base, #offset]`, see [`Self::base`] and [`Self::sp`].
+    pub const fn str(rd: u16) -> Self {
+        Self::new(ThumbFormat::ImmediateOffset, 0b011 << 13 | rd)
+    }
+
+    /// `LDRH rd, [base, #offset]`
+    pub const fn ldrh(rd: u16) -> Self {
+        Self::new(ThumbFormat::HalfwordImmediate, 0b1000 << 12 | 1 << 11 | rd)
+    }
+
+    /// `STRH rd, [base, #offset]`
+    pub const fn strh(rd: u16) -> Self {
+        Self::new(ThumbFormat::HalfwordImmediate, 0b1000 << 12 | rd)
+    }
+
+    /// `ADD rd, PC, #offset`, or SP with [`Self::sp`].
+    pub const fn adr(rd: u16, offset: u16) -> Self {
+        Self::new(
+            ThumbFormat::LoadAddress,
+            0b1010 << 12 | rd << 8 | (offset >> 2),
+        )
+    }
+
+    /// `ADD SP, #offset`
+    pub const fn add_sp(offset: i16) -> Self {
+        let value = offset.unsigned_abs() >> 2 & 0x7F;
+        let sign = if offset < 0 { 1 << 7 } else { 0 };
+
+        Self::new(ThumbFormat::AddSp, 0b1011_0000 << 8 | sign | value)
+    }
+
+    /// `PUSH {registers}`, see [`Self::pc_lr`] to push LR too.
+    pub const fn push(registers: u8) -> Self {
+        Self::new(ThumbFormat::PushPop, 0b1011_0100 << 8 | registers as u16)
+    }
+
+    /// `POP {registers}`, see [`Self::pc_lr`] to pop PC too.
+    pub const fn pop(registers: u8) -> Self {
+        Self::new(ThumbFormat::PushPop, 0b1011_1100 << 8 | registers as u16)
+    }
+
+    /// `LDMIA rb!, {registers}`
+    pub const fn ldmia(rb: u16, registers: u8) -> Self {
+        Self::new(
+            ThumbFormat::MultipleTransfer,
+            0b11001 << 11 | rb << 8 | registers as u16,
+        )
+    }
+
+    /// `STMIA rb!, {registers}`
+    pub const fn stmia(rb: u16, registers: u8) -> Self {
+        Self::new(
+            ThumbFormat::MultipleTransfer,
+            0b11000 << 11 | rb << 8 | registers as u16,
+        )
+    }
+
+    /// Conditional branch, `offset` in bytes.
+    pub const fn b_cond(condition: Condition, offset: i16) -> Self {
+        Self::new(
+            ThumbFormat::ConditionalBranch,
+            0b1101 << 12 | (condition as u16) << 8 | (offset as u16 >> 1) & 0xFF,
+        )
+    }
+
+    pub const fn swi(value: u8) -> Self {
+        Self::new(
+            ThumbFormat::SoftwareInterrupt,
+            0b1101_1111 << 8 | value as u16,
+        )
+    }
+
+    /// Unconditional branch, `offset` in bytes.
+    pub const fn b(offset: i16) -> Self {
+        Self::new(
+            ThumbFormat::Branch,
+            0b11100 << 11 | (offset as u16 >> 1) & 0x7FF,
+        )
+    }
+
+    /// Long branch with link, it takes two instructions: the high and the low part
+    /// of `offset` (in bytes).
+    #[must_use]
+    pub const fn bl(offset: i32) -> [u16; 2] {
+        let offset = (offset as u32 >> 1) & 0x3F_FFFF;
+
+        [
+            0b11110 << 11 | (offset >> 11) as u16,
+            0b11111 << 11 | (offset & 0x7FF) as u16,
+        ]
+    }
+
+    const fn with_bits(self, mask: u16, bits: u16) -> Self {
+        Self {
+            op_code: self.op_code & !mask | bits,
+            ..self
+        }
+    }
+
+    /// Immediate operand of `ADD`/`SUB` instead of the third register.
+    pub const fn imm(self, value: u16) -> Self {
+        assert!(matches!(self.format, ThumbFormat::AddSubtract));
+        self.with_bits(0b111 << 6, 1 << 10 | (value & 0b111) << 6)
+    }
+
+    /// Base register of a load or store.
+    pub const fn base(self, rb: u16) -> Self {
+        assert!(matches!(
+            self.format,
+            ThumbFormat::ImmediateOffset
+                | ThumbFormat::RegisterOffset
+                | ThumbFormat::HalfwordImmediate
+        ));
+        self.with_bits(0b111 << 3, rb << 3)
+    }
+
+    /// Immediate offset in bytes of a load or store.
+    pub const fn offset(self, offset: u16) -> Self {
+        assert!(matches!(
+            self.format,
+            ThumbFormat::ImmediateOffset
+                | ThumbFormat::HalfwordImmediate
+                | ThumbFormat::PcRelativeLoad
+                | ThumbFormat::SpRelative
+        ));
+
+        Self { offset, ..self }
+    }
+
+    /// Register offset of a load or store: `[base, ro]`.
+    pub const fn reg(self, ro: u16) -> Self {
+        match self.format {
+            ThumbFormat::ImmediateOffset => {
+                // L and B swap place: L is bit 11 in both, B moves from 12 to 10.
+                let load = self.op_code & 1 << 11;
+                let byte = (self.op_code >> 12 & 1) << 10;
+                Self::new(
+                    ThumbFormat::RegisterOffset,
+                    0b0101 << 12 | load | byte | ro << 6 | self.op_code & 0x3F,
+                )
+            }
+            ThumbFormat::RegisterOffset => self.with_bits(0b111 << 6, ro << 6),
+            _ => panic!("register offset not supported"),
+        }
+    }
+
+    /// Transfers a byte instead of a word.
+    pub const fn byte(self) -> Self {
+        match self.format {
+            ThumbFormat::ImmediateOffset => self.with_bits(1 << 12, 1 << 12),
+            ThumbFormat::RegisterOffset => self.with_bits(1 << 10, 1 << 10),
+            _ => panic!("byte transfer not supported"),
+        }
+    }
+
+    /// Load relative to PC: `LDR rd, [PC, #offset]`.
+    pub const fn pc(self) -> Self {
+        assert!(matches!(self.format, ThumbFormat::ImmediateOffset));
+        assert!(self.op_code & 1 << 11 != 0, "only loads are PC relative");
+        Self {
+            format: ThumbFormat::PcRelativeLoad,
+            op_code: 0b01001 << 11 | (self.op_code & 0b111) << 8,
+            ..self
+        }
+    }
+
+    /// Transfer relative to SP: `LDR rd, [SP, #offset]`, or `ADD rd, SP, #offset`.
+    pub const fn sp(self) -> Self {
+        match self.format {
+            ThumbFormat::ImmediateOffset => Self {
+                format: ThumbFormat::SpRelative,
+                op_code: 0b1001 << 12 | self.op_code & 1 << 11 | (self.op_code & 0b111) << 8,
+                ..self
+            },
+            ThumbFormat::LoadAddress => self.with_bits(1 << 11, 1 << 11),
+            _ => panic!("SP relative not supported"),
+        }
+    }
+
+    /// Pushes LR or pops PC together with the registers.
+    pub const fn pc_lr(self) -> Self {
+        assert!(matches!(self.format, ThumbFormat::PushPop));
+        self.with_bits(1 << 8, 1 << 8)
+    }
+
+    #[must_use]
+    pub const fn encode(self) -> u16 {
+        let offset = self.offset;
+
+        match self.format {
+            // Byte transfers aren't scaled.
+            ThumbFormat::ImmediateOffset if self.op_code & 1 << 12 != 0 => {
+                self.op_code | (offset & 0x1F) << 6
+            }
+            ThumbFormat::ImmediateOffset => self.op_code | (offset >> 2 & 0x1F) << 6,
+            ThumbFormat::HalfwordImmediate => self.op_code | (offset >> 1 & 0x1F) << 6,
+            ThumbFormat::PcRelativeLoad | ThumbFormat::SpRelative => {
+                self.op_code | offset >> 2 & 0xFF
+            }
+            _ => self.op_code,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn immediates() {
+        assert_eq!(arm_immediate(0xFF), Some(0xFF));
+        assert_eq!(arm_immediate(0x3FC), Some(0xFFF));
+        assert_eq!(arm_immediate(0xFF00_0000), Some(0x4FF));
+        assert_eq!(arm_immediate(0x101), None);
+    }
+
+    #[test]
+    fn arm_encodings() {
+        // Values from an assembler.
+        assert_eq!(ArmAsm::mov(0).imm(1).encode(), 0xE3A0_0001);
+        assert_eq!(
+            ArmAsm::add(1, 2)
+                .reg(3)
+                .shift(ShiftKind::Lsl, 2)
+                .set_flags()
+                .encode(),
+            0xE092_1103
+        );
+        assert_eq!(ArmAsm::cmp(4).imm(0x100).encode(), 0xE354_0C01);
+        assert_eq!(
+            ArmAsm::mov(0).reg(1).shift_reg(ShiftKind::Ror, 2).encode(),
+            0xE1A0_0271
+        );
+        assert_eq!(ArmAsm::ldr(1).base(11).offset(3).encode(), 0xE59B_1003);
+        assert_eq!(
+            ArmAsm::str(0).base(13).offset(-4).write_back().encode(),
+            0xE52D_0004
+        );
+        assert_eq!(ArmAsm::ldr(2).base(3).reg(4).byte().encode(), 0xE7D3_2004);
+        assert_eq!(ArmAsm::ldrh(0).base(1).offset(0x12).encode(), 0xE1D1_01B2);
+        assert_eq!(
+            ArmAsm::strh(0).base(1).offset(-2).post().encode(),
+            0xE041_00B2
+        );
+        assert_eq!(ArmAsm::ldrsb(0).base(1).reg(2).encode(), 0xE191_00D2);
+        assert_eq!(
+            ArmAsm::stm(13, 0x4010).pre().down().write_back().encode(),
+            0xE92D_4010
+        );
+        assert_eq!(ArmAsm::ldm(13, 0x8010).write_back().encode(), 0xE8BD_8010);
+        assert_eq!(ArmAsm::mul(0, 1, 2).encode(), 0xE000_0291);
+        assert_eq!(ArmAsm::mla(0, 1, 2, 3).encode(), 0xE020_3291);
+        assert_eq!(ArmAsm::umull(0, 1, 2, 3).encode(), 0xE081_0392);
+        assert_eq!(ArmAsm::mrs(0).spsr().encode(), 0xE14F_0000);
+        assert_eq!(ArmAsm::msr(0).encode(), 0xE129_F000);
+        assert_eq!(ArmAsm::bx(14).cond(Condition::NE).encode(), 0x112F_FF1E);
+        assert_eq!(ArmAsm::b(-8).encode(), 0xEAFF_FFFE);
+        assert_eq!(ArmAsm::bl(0x100).encode(), 0xEB00_0040);
+        assert_eq!(ArmAsm::swi(0x06).encode(), 0xEF00_0006);
+    }
+
+    #[test]
+    fn thumb_encodings() {
+        // Values from an assembler.
+        assert_eq!(ThumbAsm::lsl(0, 1, 2).encode(), 0x0088);
+        assert_eq!(ThumbAsm::add(0, 1, 2).encode(), 0x1888);
+        assert_eq!(ThumbAsm::sub(0, 1, 0).imm(3).encode(), 0x1EC8);
+        assert_eq!(ThumbAsm::mov_imm(3, 0x42).encode(), 0x2342);
+        assert_eq!(
+            ThumbAsm::alu(ThumbModeAluInstruction::Mul, 0, 1).encode(),
+            0x4348
+        );
+        assert_eq!(
+            ThumbAsm::high(ThumbHighRegisterOperation::Mov, 8, 1).encode(),
+            0x4688
+        );
+        assert_eq!(ThumbAsm::bx(14).encode(), 0x4770);
+        assert_eq!(ThumbAsm::ldr(0).pc().offset(8).encode(), 0x4802);
+        assert_eq!(ThumbAsm::str(0).base(1).reg(2).encode(), 0x5088);
+        assert_eq!(ThumbAsm::ldr(0).base(1).reg(2).byte().encode(), 0x5C88);
+        assert_eq!(ThumbAsm::ldr(0).base(1).offset(4).encode(), 0x6848);
+        assert_eq!(ThumbAsm::str(0).base(1).offset(3).byte().encode(), 0x70C8);
+        assert_eq!(ThumbAsm::ldrh(0).base(1).offset(2).encode(), 0x8848);
+        assert_eq!(ThumbAsm::str(2).sp().offset(8).encode(), 0x9202);
+        assert_eq!(ThumbAsm::adr(1, 8).sp().encode(), 0xA902);
+        assert_eq!(ThumbAsm::add_sp(-16).encode(), 0xB084);
+        assert_eq!(ThumbAsm::push(0x10).pc_lr().encode(), 0xB510);
+        assert_eq!(ThumbAsm::pop(0x10).pc_lr().encode(), 0xBD10);
+        assert_eq!(ThumbAsm::stmia(0, 0x06).encode(), 0xC006);
+        assert_eq!(ThumbAsm::b_cond(Condition::EQ, -4).encode(), 0xD0FE);
+        assert_eq!(ThumbAsm::swi(0x0B).encode(), 0xDF0B);
+        assert_eq!(ThumbAsm::b(-4).encode(), 0xE7FE);
+        assert_eq!(ThumbAsm::bl(0x1000), [0xF001, 0xF800]);
+    }
+}
@@ -0,0 +1,949 @@
+//! Text front-end of [`ArmAsm`] and [`ThumbAsm`]: it assembles a single instruction so
+//! that the debugger can patch code in memory.
+//!
+//! The syntax is the one of the disassembler (and of common assemblers): registers are
+//! `R0`-`R15`, `SP`, `LR` or `PC`, numbers are decimal, hexadecimal (`0x`) or binary (`0b`)
+//! and branch targets are absolute addresses. Only the instructions known by the builders
+//! are supported.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use super::{arm_immediate, ArmAsm, ThumbAsm};
+use crate::cpu::arm::alu_instruction::ArmModeAluInstr;
+use crate::cpu::condition::Condition;
+use crate::cpu::flags::ShiftKind;
+use crate::cpu::thumb::alu_instructions::{ThumbHighRegisterOperation, ThumbModeAluInstruction};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssembleError {
+    /// There is nothing to assemble.
+    Empty,
+    /// The mnemonic, suffixes included, isn't supported.
+    UnknownMnemonic(String),
+    /// The operand can't be parsed or can't be used there.
+    InvalidOperand(String),
+    /// The operands don't match any form of the instruction.
+    InvalidOperands,
+    /// The value doesn't fit in the instruction.
+    OutOfRange(i64),
+    /// The value must be a multiple of `alignment`.
+    Misaligned { value: i64, alignment: i64 },
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "nothing to assemble"),
+            Self::UnknownMnemonic(m) => write!(f, "unknown instruction `{m}`"),
+            Self::InvalidOperand(o) => write!(f, "invalid operand `{o}`"),
+            Self::InvalidOperands => write!(f, "wrong operands for the instruction"),
+            Self::OutOfRange(v) => write!(f, "value {v} out of range"),
+            Self::Misaligned { value, alignment } => {
+                write!(f, "value {value} is not a multiple of {alignment}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+const CONDITIONS: [(&str, Condition); 17] = [
+    ("EQ", Condition::EQ),
+    ("NE", Condition::NE),
+    ("CS", Condition::CS),
+    ("HS", Condition::CS),
+    ("CC", Condition::CC),
+    ("LO", Condition::CC),
+    ("MI", Condition::MI),
+    ("PL", Condition::PL),
+    ("VS", Condition::VS),
+    ("VC", Condition::VC),
+    ("HI", Condition::HI),
+    ("LS", Condition::LS),
+    ("GE", Condition::GE),
+    ("LT", Condition::LT),
+    ("GT", Condition::GT),
+    ("LE", Condition::LE),
+    ("AL", Condition::AL),
+];
+
+#[derive(Clone, Copy)]
+enum ArmMnemonic {
+    Alu(ArmModeAluInstr),
+    Mul,
+    Mla,
+    MulLong(fn(u32, u32, u32, u32) -> ArmAsm),
+    Mrs,
+    Msr,
+    Bx,
+    B,
+    Bl,
+    Swi,
+    Ldr,
+    Str,
+    Ldm,
+    Stm,
+    Push,
+    Pop,
+    Nop,
+}
+
+impl ArmMnemonic {
+    /// Suffixes accepted together with the condition.
+    const fn extras(self) -> &'static [&'static str] {
+        match self {
+            Self::Alu(_) | Self::Mul | Self::Mla | Self::MulLong(_) => &["", "S"],
+            Self::Ldr => &["", "B", "H", "SB", "SH"],
+            Self::Str => &["", "B", "H"],
+            Self::Ldm | Self::Stm => &["", "IA", "IB", "DA", "DB", "FD", "ED", "FA", "EA"],
+            _ => &[""],
+        }
+    }
+}
+
+const ARM_MNEMONICS: [(&str, ArmMnemonic); 34] = [
+    ("AND", ArmMnemonic::Alu(ArmModeAluInstr::And)),
+    ("EOR", ArmMnemonic::Alu(ArmModeAluInstr::Eor)),
+    ("SUB", ArmMnemonic::Alu(ArmModeAluInstr::Sub)),
+    ("RSB", ArmMnemonic::Alu(ArmModeAluInstr::Rsb)),
+    ("ADD", ArmMnemonic::Alu(ArmModeAluInstr::Add)),
+    ("ADC", ArmMnemonic::Alu(ArmModeAluInstr::Adc)),
+    ("SBC", ArmMnemonic::Alu(ArmModeAluInstr::Sbc)),
+    ("RSC", ArmMnemonic::Alu(ArmModeAluInstr::Rsc)),
+    ("TST", ArmMnemonic::Alu(ArmModeAluInstr::Tst)),
+    ("TEQ", ArmMnemonic::Alu(ArmModeAluInstr::Teq)),
+    ("CMP", ArmMnemonic::Alu(ArmModeAluInstr::Cmp)),
+    ("CMN", ArmMnemonic::Alu(ArmModeAluInstr::Cmn)),
+    ("ORR", ArmMnemonic::Alu(ArmModeAluInstr::Orr)),
+    ("MOV", ArmMnemonic::Alu(ArmModeAluInstr::Mov)),
+    ("BIC", ArmMnemonic::Alu(ArmModeAluInstr::Bic)),
+    ("MVN", ArmMnemonic::Alu(ArmModeAluInstr::Mvn)),
+    ("MUL", ArmMnemonic::Mul),
+    ("MLA", ArmMnemonic::Mla),
+    ("UMULL", ArmMnemonic::MulLong(ArmAsm::umull)),
+    ("UMLAL", ArmMnemonic::MulLong(ArmAsm::umlal)),
+    ("SMULL", ArmMnemonic::MulLong(ArmAsm::smull)),
+    ("SMLAL", ArmMnemonic::MulLong(ArmAsm::smlal)),
+    ("MRS", ArmMnemonic::Mrs),
+    ("MSR", ArmMnemonic::Msr),
+    ("BX", ArmMnemonic::Bx),
+    ("BL", ArmMnemonic::Bl),
+    ("B", ArmMnemonic::B),
+    ("SWI", ArmMnemonic::Swi),
+    ("LDR", ArmMnemonic::Ldr),
+    ("STR", ArmMnemonic::Str),
+    ("LDM", ArmMnemonic::Ldm),
+    ("STM", ArmMnemonic::Stm),
+    ("PUSH", ArmMnemonic::Push),
+    ("POP", ArmMnemonic::Pop),
+];
+
+const THUMB_ALU: [(&str, ThumbModeAluInstruction); 16] = [
+    ("AND", ThumbModeAluInstruction::And),
+    ("EOR", ThumbModeAluInstruction::Eor),
+    ("LSL", ThumbModeAluInstruction::Lsl),
+    ("LSR", ThumbModeAluInstruction::Lsr),
+    ("ASR", ThumbModeAluInstruction::Asr),
+    ("ADC", ThumbModeAluInstruction::Adc),
+    ("SBC", ThumbModeAluInstruction::Sbc),
+    ("ROR", ThumbModeAluInstruction::Ror),
+    ("TST", ThumbModeAluInstruction::Tst),
+    ("NEG", ThumbModeAluInstruction::Neg),
+    ("CMP", ThumbModeAluInstruction::Cmp),
+    ("CMN", ThumbModeAluInstruction::Cmn),
+    ("ORR", ThumbModeAluInstruction::Orr),
+    ("MUL", ThumbModeAluInstruction::Mul),
+    ("BIC", ThumbModeAluInstruction::Bic),
+    ("MVN", ThumbModeAluInstruction::Mvn),
+];
+
+const THUMB_MNEMONICS: [&str; 15] = [
+    "ADD", "SUB", "MOV", "BX", "LDR", "STR", "LDRB", "STRB", "LDRH", "STRH", "PUSH", "POP",
+    "LDMIA", "STMIA", "SWI",
+];
+
+/// Assembles the ARM instruction `source` placed at `address`.
+///
+/// # Errors
+/// It fails if `source` isn't a supported instruction or if its operands can't be encoded.
+pub fn assemble_arm(source: &str, address: u32) -> Result<u32, AssembleError> {
+    let (mnemonic, operands) = split(source)?;
+    let operands = operands.iter().map(String::as_str).collect::<Vec<_>>();
+
+    if mnemonic == "NOP" {
+        return arm_instruction(ArmMnemonic::Nop, "", &operands, address).map(ArmAsm::encode);
+    }
+
+    // Candidates sharing a prefix (`BL`, `BLS`, `BLT`...) are told apart by their suffix.
+    for (name, kind) in ARM_MNEMONICS {
+        let Some(rest) = mnemonic.strip_prefix(name) else {
+            continue;
+        };
+        let Some((condition, extra)) = suffix(rest, kind.extras()) else {
+            continue;
+        };
+
+        return arm_instruction(kind, extra, &operands, address)
+            .map(|asm| asm.cond(condition).encode());
+    }
+
+    Err(AssembleError::UnknownMnemonic(mnemonic))
+}
+
+/// Assembles the Thumb instruction `source` placed at `address`.
+/// `BL` takes two half-words, every other instruction one.
+///
+/// # Errors
+/// It fails if `source` isn't a supported instruction or if its operands can't be encoded.
+pub fn assemble_thumb(source: &str, address: u32) -> Result<Vec<u16>, AssembleError> {
+    let (mnemonic, operands) = split(source)?;
+    let operands = operands.iter().map(String::as_str).collect::<Vec<_>>();
+
+    thumb_instruction(&mnemonic, &operands, address).map_err(|err| match err {
+        AssembleError::InvalidOperands if !is_thumb_mnemonic(&mnemonic) => {
+            AssembleError::UnknownMnemonic(mnemonic.clone())
+        }
+        err => err,
+    })
+}
+
+/// Splits `source` in the upper case mnemonic and its operands.
+fn split(source: &str) -> Result<(String, Vec<String>), AssembleError> {
+    // Comments as written by assemblers.
+    let source = source.split([';', '@']).next().unwrap_or_default().trim();
+    if source.is_empty() {
+        return Err(AssembleError::Empty);
+    }
+
+    let (mnemonic, rest) = source
+        .split_once(char::is_whitespace)
+        .unwrap_or((source, ""));
+
+    let mut operands = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in rest.chars() {
+        match c {
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                operands.push(current.trim().to_uppercase());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+
+    if !current.trim().is_empty() || !operands.is_empty() {
+        operands.push(current.trim().to_uppercase());
+    }
+
+    Ok((mnemonic.to_uppercase(), operands))
+}
+
+/// Splits `suffix` in a condition and one of `extras`, in both orders (`ADDEQS` and `ADDSEQ`).
+fn suffix(suffix: &str, extras: &[&'static str]) -> Option<(Condition, &'static str)> {
+    std::iter::once(("", Condition::AL))
+        .chain(CONDITIONS)
+        .find_map(|(name, condition)| {
+            extras
+                .iter()
+                .find(|extra| {
+                    suffix == format!("{name}{extra}") || suffix == format!("{extra}{name}")
+                })
+                .map(|extra| (condition, *extra))
+        })
+}
+
+fn invalid(operand: &str) -> AssembleError {
+    AssembleError::InvalidOperand(operand.to_string())
+}
+
+fn register(operand: &str) -> Result<u32, AssembleError> {
+    match operand {
+        "SP" => Ok(13),
+        "LR" => Ok(14),
+        "PC" => Ok(15),
+        _ => operand
+            .strip_prefix('R')
+            .and_then(|index| index.parse().ok())
+            .filter(|index| *index < 16)
+            .ok_or_else(|| invalid(operand)),
+    }
+}
+
+/// Register between `R0` and `R7`, as used by most Thumb instructions.
+fn low_register(operand: &str) -> Result<u16, AssembleError> {
+    match register(operand)? {
+        index @ 0..=7 => Ok(index as u16),
+        _ => Err(invalid(operand)),
+    }
+}
+
+fn number(operand: &str) -> Result<i64, AssembleError> {
+    let (negative, digits) = operand
+        .strip_prefix('-')
+        .map_or((false, operand), |digits| (true, digits));
+
+    let (digits, radix) = digits
+        .strip_prefix("0X")
+        .map(|hex| (hex, 16))
+        .or_else(|| digits.strip_prefix("0B").map(|bin| (bin, 2)))
+        .unwrap_or((digits, 10));
+    let value = i64::from_str_radix(digits, radix).map_err(|_| invalid(operand))?;
+
+    Ok(if negative { -value } else { value })
+}
+
+/// `#value`
+fn immediate(operand: &str) -> Result<i64, AssembleError> {
+    operand
+        .strip_prefix('#')
+        .and_then(|value| number(value).ok())
+        .ok_or_else(|| invalid(operand))
+}
+
+/// Branch targets and comments, the `#` is optional.
+fn bare_immediate(operand: &str) -> Result<i64, AssembleError> {
+    number(operand.strip_prefix('#').unwrap_or(operand))
+}
+
+fn in_range(value: i64, range: RangeInclusive<i64>) -> Result<i64, AssembleError> {
+    if range.contains(&value) {
+        Ok(value)
+    } else {
+        Err(AssembleError::OutOfRange(value))
+    }
+}
+
+const fn aligned(value: i64, alignment: i64) -> Result<i64, AssembleError> {
+    if value % alignment == 0 {
+        Ok(value)
+    } else {
+        Err(AssembleError::Misaligned { value, alignment })
+    }
+}
+
+/// `{R0-R3, LR}` with a bit for each register.
+fn register_list(operand: &str) -> Result<u16, AssembleError> {
+    let inner = operand
+        .strip_prefix('{')
+        .and_then(|list| list.strip_suffix('}'))
+        .ok_or_else(|| invalid(operand))?;
+
+    let mut registers = 0;
+    for item in inner.split(',').map(str::trim) {
+        let (first, last) = match item.split_once('-') {
+            Some((first, last)) => (register(first.trim())?, register(last.trim())?),
+            None => (register(item)?, register(item)?),
+        };
+
+        if first > last {
+            return Err(invalid(item));
+        }
+
+        for index in first..=last {
+            registers |= 1 << index;
+        }
+    }
+
+    Ok(registers)
+}
+
+/// Strips the write back `!` of an operand.
+fn write_back(operand: &str) -> (&str, bool) {
+    operand
+        .strip_suffix('!')
+        .map_or((operand, false), |operand| (operand.trim(), true))
+}
+
+enum ShiftBy {
+    Immediate(u32),
+    Register(u32),
+}
+
+/// `LSL #2`, `ASR R3` or `RRX`.
+fn shift(operand: &str) -> Result<(ShiftKind, ShiftBy), AssembleError> {
+    if operand == "RRX" {
+        return Ok((ShiftKind::Ror, ShiftBy::Immediate(0)));
+    }
+
+    let (kind, amount) = operand
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| invalid(operand))?;
+    let amount = amount.trim();
+
+    let (kind, range) = match kind {
+        "LSL" | "ASL" => (ShiftKind::Lsl, 0..=31),
+        "LSR" => (ShiftKind::Lsr, 1..=32),
+        "ASR" => (ShiftKind::Asr, 1..=32),
+        "ROR" => (ShiftKind::Ror, 1..=31),
+        _ => return Err(invalid(operand)),
+    };
+
+    if amount.starts_with('#') {
+        // Shifts by 32 are encoded as 0.
+        let amount = in_range(immediate(amount)?, range)? % 32;
+        Ok((kind, ShiftBy::Immediate(amount as u32)))
+    } else {
+        Ok((kind, ShiftBy::Register(register(amount)?)))
+    }
+}
+
+/// `CPSR` or `SPSR`, returns whether it's the SPSR.
+fn psr(operand: &str) -> Result<bool, AssembleError> {
+    match operand {
+        "CPSR" | "CPSR_ALL" | "CPSR_FC" => Ok(false),
+        "SPSR" | "SPSR_ALL" | "SPSR_FC" => Ok(true),
+        _ => Err(invalid(operand)),
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+fn arm_instruction(
+    mnemonic: ArmMnemonic,
+    extra: &str,
+    operands: &[&str],
+    address: u32,
+) -> Result<ArmAsm, AssembleError> {
+    use ArmModeAluInstr::{Cmn, Cmp, Mov, Mvn, Teq, Tst};
+
+    let asm = match (mnemonic, operands) {
+        (ArmMnemonic::Alu(op @ (Mov | Mvn)), [rd, op2 @ ..]) => {
+            arm_operand2(ArmAsm::alu(op, register(rd)?, 0), op2)?
+        }
+        (ArmMnemonic::Alu(op @ (Tst | Teq | Cmp | Cmn)), [rn, op2 @ ..]) => {
+            arm_operand2(ArmAsm::alu(op, 0, register(rn)?), op2)?
+        }
+        (ArmMnemonic::Alu(op), [rd, rn, op2 @ ..]) => {
+            arm_operand2(ArmAsm::alu(op, register(rd)?, register(rn)?), op2)?
+        }
+        (ArmMnemonic::Mul, [rd, rm, rs]) => {
+            ArmAsm::mul(register(rd)?, register(rm)?, register(rs)?)
+        }
+        (ArmMnemonic::Mla, [rd, rm, rs, rn]) => {
+            ArmAsm::mla(register(rd)?, register(rm)?, register(rs)?, register(rn)?)
+        }
+        (ArmMnemonic::MulLong(multiply), [rdlo, rdhi, rm, rs]) => multiply(
+            register(rdlo)?,
+            register(rdhi)?,
+            register(rm)?,
+            register(rs)?,
+        ),
+        (ArmMnemonic::Mrs, [rd, source]) => {
+            let asm = ArmAsm::mrs(register(rd)?);
+            if psr(source)? {
+                asm.spsr()
+            } else {
+                asm
+            }
+        }
+        (ArmMnemonic::Msr, [destination, rm]) => {
+            let asm = ArmAsm::msr(register(rm)?);
+            if psr(destination)? {
+                asm.spsr()
+            } else {
+                asm
+            }
+        }
+        (ArmMnemonic::Bx, [rn]) => ArmAsm::bx(register(rn)?),
+        (ArmMnemonic::B | ArmMnemonic::Bl, [target]) => {
+            let offset = bare_immediate(target)? - (i64::from(address) + 8);
+            let offset = aligned(in_range(offset, -0x200_0000..=0x1FF_FFFC)?, 4)? as i32;
+
+            if matches!(mnemonic, ArmMnemonic::Bl) {
+                ArmAsm::bl(offset)
+            } else {
+                ArmAsm::b(offset)
+            }
+        }
+        (ArmMnemonic::Swi, [comment]) => {
+            ArmAsm::swi(in_range(bare_immediate(comment)?, 0..=0x00FF_FFFF)? as u32)
+        }
+        (ArmMnemonic::Ldr | ArmMnemonic::Str, [rd, operands @ ..]) => arm_transfer(
+            matches!(mnemonic, ArmMnemonic::Ldr),
+            extra,
+            register(rd)?,
+            operands,
+        )?,
+        (ArmMnemonic::Ldm | ArmMnemonic::Stm, [rn, registers]) => {
+            let load = matches!(mnemonic, ArmMnemonic::Ldm);
+            let (rn, write_back) = write_back(rn);
+            let (registers, psr) = registers
+                .strip_suffix('^')
+                .map_or((*registers, false), |list| (list.trim(), true));
+
+            let (rn, registers) = (register(rn)?, register_list(registers)?);
+            let asm = if load {
+                ArmAsm::ldm(rn, registers)
+            } else {
+                ArmAsm::stm(rn, registers)
+            };
+
+            // Stack modes name the stack (full/empty, descending/ascending), not the addressing.
+            let (pre, down) = match (extra, load) {
+                ("" | "IA", _) | ("FD", true) | ("EA", false) => (false, false),
+                ("IB", _) | ("ED", true) | ("FA", false) => (true, false),
+                ("DA", _) | ("FA", true) | ("ED", false) => (false, true),
+                _ => (true, true),
+            };
+
+            let asm = if pre { asm.pre() } else { asm };
+            let asm = if down { asm.down() } else { asm };
+            let asm = if write_back { asm.write_back() } else { asm };
+            if psr {
+                asm.psr()
+            } else {
+                asm
+            }
+        }
+        (ArmMnemonic::Push, [registers]) => ArmAsm::stm(13, register_list(registers)?)
+            .pre()
+            .down()
+            .write_back(),
+        (ArmMnemonic::Pop, [registers]) => ArmAsm::ldm(13, register_list(registers)?).write_back(),
+        (ArmMnemonic::Nop, []) => ArmAsm::mov(0).reg(0),
+        _ => return Err(AssembleError::InvalidOperands),
+    };
+
+    Ok(if extra == "S" { asm.set_flags() } else { asm })
+}
+
+/// Second operand of data processing instructions: `#value`, `Rm` or `Rm, shift`.
+fn arm_operand2(asm: ArmAsm, operands: &[&str]) -> Result<ArmAsm, AssembleError> {
+    match operands {
+        [value] if value.starts_with('#') => {
+            // Negative values are accepted as their two's complement.
+            let value = in_range(immediate(value)?, -0x8000_0000..=0xFFFF_FFFF)?;
+            arm_immediate(value as u32)
+                .map(|_| asm.imm(value as u32))
+                .ok_or(AssembleError::OutOfRange(value))
+        }
+        [rm] => Ok(asm.reg(register(rm)?)),
+        [rm, operand] => {
+            let asm = asm.reg(register(rm)?);
+            Ok(match shift(operand)? {
+                (kind, ShiftBy::Immediate(amount)) => asm.shift(kind, amount),
+                (kind, ShiftBy::Register(rs)) => asm.shift_reg(kind, rs),
+            })
+        }
+        _ => Err(AssembleError::InvalidOperands),
+    }
+}
+
+/// `[Rn, offset]{!}` or `[Rn], offset` where the offset is `#value` or `{-}Rm{, shift}`.
+fn arm_transfer(
+    load: bool,
+    size: &str,
+    rd: u32,
+    operands: &[&str],
+) -> Result<ArmAsm, AssembleError> {
+    let (asm, max_offset) = match (size, load) {
+        ("", true) => (ArmAsm::ldr(rd), 0xFFF),
+        ("", false) => (ArmAsm::str(rd), 0xFFF),
+        ("B", true) => (ArmAsm::ldr(rd).byte(), 0xFFF),
+        ("B", false) => (ArmAsm::str(rd).byte(), 0xFFF),
+        ("H", true) => (ArmAsm::ldrh(rd), 0xFF),
+        ("H", false) => (ArmAsm::strh(rd), 0xFF),
+        ("SB", _) => (ArmAsm::ldrsb(rd), 0xFF),
+        _ => (ArmAsm::ldrsh(rd), 0xFF),
+    };
+    // Only word and byte transfers have shifted register offsets.
+    let shifts = max_offset == 0xFFF;
+
+    let Some((address, post)) = operands.split_first() else {
+        return Err(AssembleError::InvalidOperands);
+    };
+    let (address, write_back) = write_back(address);
+    let inner = address
+        .strip_prefix('[')
+        .and_then(|address| address.strip_suffix(']'))
+        .ok_or_else(|| invalid(address))?;
+
+    let mut parts = inner.split(',').map(str::trim);
+    let base = register(parts.next().unwrap_or_default())?;
+    let pre = parts.collect::<Vec<_>>();
+
+    let (offset, pre_indexed) = match (pre.as_slice(), post) {
+        (offset, []) => (offset, true),
+        ([], offset) if !write_back => (offset, false),
+        _ => return Err(AssembleError::InvalidOperands),
+    };
+
+    let asm = asm.base(base);
+    let asm = match offset {
+        [] => asm,
+        [value] if value.starts_with('#') => {
+            asm.offset(in_range(immediate(value)?, -max_offset..=max_offset)? as i32)
+        }
+        [rm, shifted @ ..] => {
+            let (down, rm) = rm.strip_prefix('-').map_or((false, *rm), |rm| (true, rm));
+            let asm = asm.reg(register(rm.strip_prefix('+').unwrap_or(rm))?);
+            let asm = match shifted {
+                [] => asm,
+                [operand] if shifts => match shift(operand)? {
+                    (kind, ShiftBy::Immediate(amount)) => asm.shift(kind, amount),
+                    (_, ShiftBy::Register(_)) => return Err(invalid(operand)),
+                },
+                _ => return Err(AssembleError::InvalidOperands),
+            };
+
+            if down {
+                asm.down()
+            } else {
+                asm
+            }
+        }
+    };
+
+    let asm = if pre_indexed { asm.pre() } else { asm.post() };
+    Ok(if write_back { asm.write_back() } else { asm })
+}
+
+/// Condition of a Thumb conditional branch, which can't be `AL`.
+fn thumb_condition(mnemonic: &str) -> Option<Condition> {
+    let rest = mnemonic.strip_prefix('B')?;
+
+    CONDITIONS
+        .iter()
+        .find(|(name, condition)| *name == rest && *condition != Condition::AL)
+        .map(|(_, condition)| *condition)
+}
+
+fn is_thumb_mnemonic(mnemonic: &str) -> bool {
+    THUMB_MNEMONICS.contains(&mnemonic)
+        || THUMB_ALU.iter().any(|(name, _)| *name == mnemonic)
+        || thumb_condition(mnemonic).is_some()
+}
+
+#[allow(clippy::too_many_lines)]
+fn thumb_instruction(
+    mnemonic: &str,
+    operands: &[&str],
+    address: u32,
+) -> Result<Vec<u16>, AssembleError> {
+    if let (Some(condition), [target]) = (thumb_condition(mnemonic), operands) {
+        let offset = bare_immediate(target)? - (i64::from(address) + 4);
+        let offset = aligned(in_range(offset, -0x100..=0xFE)?, 2)? as i16;
+
+        return Ok(vec![ThumbAsm::b_cond(condition, offset).encode()]);
+    }
+
+    let asm = match (mnemonic, operands) {
+        ("LSL" | "LSR" | "ASR", [rd, rs, offset]) => {
+            let (rd, rs) = (low_register(rd)?, low_register(rs)?);
+            let range = if mnemonic == "LSL" { 0..=31 } else { 1..=32 };
+            // Shifts by 32 are encoded as 0.
+            let offset = (in_range(immediate(offset)?, range)? % 32) as u16;
+
+            match mnemonic {
+                "LSL" => ThumbAsm::lsl(rd, rs, offset),
+                "LSR" => ThumbAsm::lsr(rd, rs, offset),
+                _ => ThumbAsm::asr(rd, rs, offset),
+            }
+        }
+        ("ADD", [rd, base, offset]) if matches!(register(base), Ok(13 | 15)) => {
+            let offset = aligned(in_range(immediate(offset)?, 0..=1020)?, 4)?;
+            let asm = ThumbAsm::adr(low_register(rd)?, offset as u16);
+
+            if register(base)? == 13 {
+                asm.sp()
+            } else {
+                asm
+            }
+        }
+        ("ADD" | "SUB", [rd, rs, operand]) => {
+            let (rd, rs) = (low_register(rd)?, low_register(rs)?);
+
+            if operand.starts_with('#') {
+                let value = in_range(immediate(operand)?, 0..=7)? as u16;
+                if mnemonic == "ADD" {
+                    ThumbAsm::add(rd, rs, 0).imm(value)
+                } else {
+                    ThumbAsm::sub(rd, rs, 0).imm(value)
+                }
+            } else if mnemonic == "ADD" {
+                ThumbAsm::add(rd, rs, low_register(operand)?)
+            } else {
+                ThumbAsm::sub(rd, rs, low_register(operand)?)
+            }
+        }
+        ("ADD" | "SUB", [sp, offset]) if register(sp) == Ok(13) => {
+            let offset = aligned(in_range(immediate(offset)?, -508..=508)?, 4)? as i16;
+            ThumbAsm::add_sp(if mnemonic == "ADD" { offset } else { -offset })
+        }
+        ("MOV" | "CMP" | "ADD" | "SUB", [rd, value]) if value.starts_with('#') => {
+            let (rd, value) = (
+                low_register(rd)?,
+                in_range(immediate(value)?, 0..=0xFF)? as u16,
+            );
+
+            match mnemonic {
+                "MOV" => ThumbAsm::mov_imm(rd, value),
+                "CMP" => ThumbAsm::cmp_imm(rd, value),
+                "ADD" => ThumbAsm::add_imm(rd, value),
+                _ => ThumbAsm::sub_imm(rd, value),
+            }
+        }
+        ("ADD" | "CMP" | "MOV", [rd, rs]) => {
+            let (rd, rs) = (register(rd)? as u16, register(rs)? as u16);
+
+            match mnemonic {
+                // High registers operations between low registers are unpredictable.
+                "ADD" if rd < 8 && rs < 8 => ThumbAsm::add(rd, rd, rs),
+                "CMP" if rd < 8 && rs < 8 => ThumbAsm::alu(ThumbModeAluInstruction::Cmp, rd, rs),
+                "MOV" if rd < 8 && rs < 8 => ThumbAsm::add(rd, rs, 0).imm(0),
+                "ADD" => ThumbAsm::high(ThumbHighRegisterOperation::Add, rd, rs),
+                "CMP" => ThumbAsm::high(ThumbHighRegisterOperation::Cmp, rd, rs),
+                _ => ThumbAsm::high(ThumbHighRegisterOperation::Mov, rd, rs),
+            }
+        }
+        (_, [rd, rs]) if THUMB_ALU.iter().any(|(name, _)| *name == mnemonic) => {
+            let op = THUMB_ALU
+                .iter()
+                .find_map(|(name, op)| (*name == mnemonic).then_some(*op))
+                .unwrap_or(ThumbModeAluInstruction::And);
+
+            ThumbAsm::alu(op, low_register(rd)?, low_register(rs)?)
+        }
+        ("BX", [rs]) => ThumbAsm::bx(register(rs)? as u16),
+        ("LDR" | "STR" | "LDRB" | "STRB" | "LDRH" | "STRH", [rd, address]) => {
+            thumb_transfer(mnemonic, low_register(rd)?, address)?
+        }
+        ("PUSH" | "POP", [registers]) => {
+            // PUSH can store LR and POP can load PC together with the low registers.
+            let extra = if mnemonic == "PUSH" { 14 } else { 15 };
+            let list = register_list(registers)?;
+            let low = u8::try_from(list & !(1 << extra)).map_err(|_| invalid(registers))?;
+
+            let asm = if mnemonic == "PUSH" {
+                ThumbAsm::push(low)
+            } else {
+                ThumbAsm::pop(low)
+            };
+
+            if list & 1 << extra == 0 {
+                asm
+            } else {
+                asm.pc_lr()
+            }
+        }
+        ("LDMIA" | "STMIA", [rb, registers]) => {
+            let (rb, true) = write_back(rb) else {
+                return Err(invalid(rb));
+            };
+            let (rb, list) = (low_register(rb)?, register_list(registers)?);
+            let list = u8::try_from(list).map_err(|_| invalid(registers))?;
+
+            if mnemonic == "LDMIA" {
+                ThumbAsm::ldmia(rb, list)
+            } else {
+                ThumbAsm::stmia(rb, list)
+            }
+        }
+        ("SWI", [value]) => ThumbAsm::swi(in_range(bare_immediate(value)?, 0..=0xFF)? as u8),
+        ("BL", [target]) => {
+            let offset = bare_immediate(target)? - (i64::from(address) + 4);
+            let offset = aligned(in_range(offset, -0x40_0000..=0x3F_FFFE)?, 2)?;
+
+            return Ok(ThumbAsm::bl(offset as i32).to_vec());
+        }
+        ("B", [target]) => {
+            let offset = bare_immediate(target)? - (i64::from(address) + 4);
+            ThumbAsm::b(aligned(in_range(offset, -0x800..=0x7FE)?, 2)? as i16)
+        }
+        ("NOP", []) => ThumbAsm::high(ThumbHighRegisterOperation::Mov, 8, 8),
+        _ => return Err(AssembleError::InvalidOperands),
+    };
+
+    Ok(vec![asm.encode()])
+}
+
+/// `[Rb, #offset]` or `[Rb, Ro]`, with `PC` and `SP` as base of word transfers.
+fn thumb_transfer(mnemonic: &str, rd: u16, address: &str) -> Result<ThumbAsm, AssembleError> {
+    let inner = address
+        .strip_prefix('[')
+        .and_then(|address| address.strip_suffix(']'))
+        .ok_or_else(|| invalid(address))?;
+
+    let (base, offset) = match inner.split(',').map(str::trim).collect::<Vec<_>>()[..] {
+        [base] => (base, "#0"),
+        [base, offset] => (base, offset),
+        _ => return Err(invalid(address)),
+    };
+
+    let asm = match mnemonic {
+        "LDR" | "LDRB" => ThumbAsm::ldr(rd),
+        "STR" | "STRB" => ThumbAsm::str(rd),
+        "LDRH" => ThumbAsm::ldrh(rd),
+        _ => ThumbAsm::strh(rd),
+    };
+    let byte = mnemonic.ends_with('B');
+
+    if !offset.starts_with('#') {
+        if mnemonic.ends_with('H') {
+            return Err(invalid(offset));
+        }
+
+        let asm = asm.base(low_register(base)?).reg(low_register(offset)?);
+        return Ok(if byte { asm.byte() } else { asm });
+    }
+
+    let offset = immediate(offset)?;
+    match (register(base)?, mnemonic) {
+        (15, "LDR") => Ok(asm
+            .pc()
+            .offset(aligned(in_range(offset, 0..=1020)?, 4)? as u16)),
+        (13, "LDR" | "STR") => Ok(asm
+            .sp()
+            .offset(aligned(in_range(offset, 0..=1020)?, 4)? as u16)),
+        _ => {
+            let (max, alignment) = match mnemonic {
+                "LDRB" | "STRB" => (31, 1),
+                "LDRH" | "STRH" => (62, 2),
+                _ => (124, 4),
+            };
+
+            let asm = asm
+                .base(low_register(base)?)
+                .offset(aligned(in_range(offset, 0..=max)?, alignment)? as u16);
+            Ok(if byte { asm.byte() } else { asm })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn arm_instructions() {
+        // Values from an assembler.
+        let cases = [
+            ("mov r0, #1", 0xE3A0_0001),
+            ("ADDS R1, R2, R3, LSL #2", 0xE092_1103),
+            ("addeqs r1, r2, r3, lsl #2", 0x0092_1103),
+            ("CMP R4, #0x100", 0xE354_0C01),
+            ("MOV R0, R1, ROR R2", 0xE1A0_0271),
+            ("MOV R0, #-0x1000000", 0xE3A0_04FF),
+            ("LDR R1, [R11, #3]", 0xE59B_1003),
+            ("STR R0, [SP, #-4]!", 0xE52D_0004),
+            ("LDREQB R2, [R3, R4]", 0x07D3_2004),
+            ("LDRBEQ R2, [R3, R4]", 0x07D3_2004),
+            ("LDR R0, [R1], -R2, LSR #32", 0xE611_0022),
+            ("LDRH R0, [R1, #0x12]", 0xE1D1_01B2),
+            ("STRH R0, [R1], #-2", 0xE041_00B2),
+            ("LDRSB R0, [R1, R2]", 0xE191_00D2),
+            ("STMFD SP!, {R4, LR}", 0xE92D_4010),
+            ("LDMIA SP!, {R4, PC}", 0xE8BD_8010),
+            ("PUSH {R4, LR}", 0xE92D_4010),
+            ("LDMDB R0, {R1-R3}^", 0xE950_000E),
+            ("MUL R0, R1, R2", 0xE000_0291),
+            ("MLAS R0, R1, R2, R3", 0xE030_3291),
+            ("UMULL R0, R1, R2, R3", 0xE081_0392),
+            ("MRS R0, SPSR", 0xE14F_0000),
+            ("MSR CPSR, R0", 0xE129_F000),
+            ("BXNE LR", 0x112F_FF1E),
+            ("B 0x08000000", 0xEAFF_FFFE),
+            ("BLS 0x08000000", 0x9AFF_FFFE),
+            ("BL 0x08000108", 0xEB00_0040),
+            ("SWI 0x06", 0xEF00_0006),
+            ("NOP ; comment", 0xE1A0_0000),
+        ];
+
+        for (source, op_code) in cases {
+            assert_eq!(assemble_arm(source, 0x0800_0000), Ok(op_code), "{source}");
+        }
+    }
+
+    #[test]
+    fn thumb_instructions() {
+        // Values from an assembler.
+        let cases: [(&str, &[u16]); 27] = [
+            ("lsl r0, r1, #2", &[0x0088]),
+            ("ADD R0, R1, R2", &[0x1888]),
+            ("SUB R0, R1, #3", &[0x1EC8]),
+            ("MOV R3, #0x42", &[0x2342]),
+            ("MOV R0, R1", &[0x1C08]),
+            ("MOV R8, R1", &[0x4688]),
+            ("ADD R0, R1", &[0x1840]),
+            ("CMP R0, R9", &[0x4548]),
+            ("MUL R0, R1", &[0x4348]),
+            ("LSL R0, R1", &[0x4088]),
+            ("BX LR", &[0x4770]),
+            ("LDR R0, [PC, #8]", &[0x4802]),
+            ("STR R0, [R1, R2]", &[0x5088]),
+            ("LDRB R0, [R1, R2]", &[0x5C88]),
+            ("LDR R0, [R1, #4]", &[0x6848]),
+            ("STRB R0, [R1, #3]", &[0x70C8]),
+            ("LDRH R0, [R1, #2]", &[0x8848]),
+            ("STR R2, [SP, #8]", &[0x9202]),
+            ("ADD R1, SP, #8", &[0xA902]),
+            ("SUB SP, #16", &[0xB084]),
+            ("PUSH {R4, LR}", &[0xB510]),
+            ("POP {R4, PC}", &[0xBD10]),
+            ("STMIA R0!, {R1, R2}", &[0xC006]),
+            ("BEQ 0x08000004", &[0xD0FE]),
+            ("SWI 0x0B", &[0xDF0B]),
+            ("B 0x08000004", &[0xE7FE]),
+            ("BL 0x08001008", &[0xF001, 0xF800]),
+        ];
+
+        for (source, op_codes) in cases {
+            assert_eq!(
+                assemble_thumb(source, 0x0800_0004).as_deref(),
+                Ok(op_codes),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(assemble_arm("  ; nothing", 0), Err(AssembleError::Empty));
+        assert_eq!(
+            assemble_arm("FOO R0", 0),
+            Err(AssembleError::UnknownMnemonic("FOO".to_string()))
+        );
+        assert_eq!(
+            assemble_arm("MOV R16, #1", 0),
+            Err(AssembleError::InvalidOperand("R16".to_string()))
+        );
+        assert_eq!(
+            assemble_arm("MOV R0, #0x101", 0),
+            Err(AssembleError::OutOfRange(0x101))
+        );
+        assert_eq!(
+            assemble_arm("MUL R0, R1", 0),
+            Err(AssembleError::InvalidOperands)
+        );
+        assert_eq!(
+            assemble_arm("B 0x0802", 0),
+            Err(AssembleError::Misaligned {
+                value: 0x07FA,
+                alignment: 4
+            })
+        );
+        assert_eq!(
+            assemble_arm("LDR R0, [R1], #4!", 0),
+            Err(AssembleError::InvalidOperand("#4!".to_string()))
+        );
+        assert_eq!(
+            assemble_thumb("ADD R8, R1, R2", 0),
+            Err(AssembleError::InvalidOperand("R8".to_string()))
+        );
+        assert_eq!(
+            assemble_thumb("BEQ 0x1000", 0),
+            Err(AssembleError::OutOfRange(0x0FFC))
+        );
+        assert_eq!(
+            assemble_thumb("SMULL R0, R1, R2, R3", 0),
+            Err(AssembleError::UnknownMnemonic("SMULL".to_string()))
+        );
+    }
+}
@@ -0,0 +1,914 @@
+//! High-level emulation of the BIOS functions, run in place of the SWI handler of the
+//! BIOS when [`Arm7tdmi::set_bios_hle`] is on.
+//!
+//! The SWIs not listed in [`Arm7tdmi::handle_swi_hle`] still jump to the BIOS. The
+//! functions access memory through the bus like the BIOS does, but don't take the
+//! internal cycles of its loops: the ones that ran are listed by
+//! [`Arm7tdmi::hle_shortcuts`], to tell timing bugs of the emulated functions from the
+//! ones of the core.
+
+use std::fmt;
+
+use crate::bitwise::Bits;
+use crate::cpu::cpu_modes::Mode;
+use crate::cpu::psr::{CpuState, Psr};
+use crate::memory_map::{BIOS_INTERRUPT_FLAGS, HALTCNT, IME, SOFT_RESET_TARGET};
+
+use super::arm7tdmi::Arm7tdmi;
+use super::{arm, thumb};
+
+/// Size of the writes to the destination. The VRAM variants write half-words since VRAM
+/// ignores byte writes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Unit {
+    Byte,
+    HalfWord,
+    Word,
+}
+
+/// First quarter of the sine table of the BIOS: `sin(2π i / 256)` in 1.14 fixed point.
+const QUARTER_SINE: [i32; 65] = [
+    0x0000, 0x0192, 0x0323, 0x04B5, 0x0645, 0x07D5, 0x0964, 0x0AF1, 0x0C7C, 0x0E05, 0x0F8C, 0x1111,
+    0x1294, 0x1413, 0x158F, 0x1708, 0x187D, 0x19EF, 0x1B5D, 0x1CC6, 0x1E2B, 0x1F8B, 0x20E7, 0x223D,
+    0x238E, 0x24DA, 0x261F, 0x275F, 0x2899, 0x29CD, 0x2AFA, 0x2C21, 0x2D41, 0x2E5A, 0x2F6B, 0x3076,
+    0x3179, 0x3274, 0x3367, 0x3453, 0x3536, 0x3612, 0x36E5, 0x37AF, 0x3871, 0x392A, 0x39DA, 0x3A82,
+    0x3B20, 0x3BB6, 0x3C42, 0x3CC5, 0x3D3E, 0x3DAE, 0x3E14, 0x3E71, 0x3EC5, 0x3F0E, 0x3F4E, 0x3F84,
+    0x3FB1, 0x3FD3, 0x3FEC, 0x3FFB, 0x4000,
+];
+
+/// Sine of `angle`, a full turn being 256, in 1.14 fixed point.
+const fn sine(angle: u8) -> i32 {
+    match angle {
+        0..=64 => QUARTER_SINE[angle as usize],
+        65..=128 => QUARTER_SINE[128 - angle as usize],
+        129..=192 => -QUARTER_SINE[angle as usize - 128],
+        _ => -QUARTER_SINE[256 - angle as usize],
+    }
+}
+
+const fn cosine(angle: u8) -> i32 {
+    sine(angle.wrapping_add(64))
+}
+
+/// PA, PB, PC and PD in 8.8 fixed point for scaling by `scale_x` and `scale_y` (8.8) and
+/// rotating by `angle` (8.8, a full turn being 0x10000). The products are rounded down
+/// like in the BIOS.
+fn affine_matrix(scale_x: i16, scale_y: i16, angle: u16) -> [i32; 4] {
+    let angle = (angle >> 8) as u8;
+    let (sin, cos) = (sine(angle), cosine(angle));
+    let (scale_x, scale_y) = (i32::from(scale_x), i32::from(scale_y));
+
+    [
+        (cos * scale_x) >> 14,
+        (sin * -scale_x) >> 14,
+        (sin * scale_y) >> 14,
+        (cos * scale_y) >> 14,
+    ]
+}
+
+/// A BIOS function run in Rust instead of the BIOS code, and how many times it was.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HleShortcut {
+    pub swi: u8,
+    pub calls: u64,
+}
+
+impl HleShortcut {
+    /// Name of the function in the BIOS documentation.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self.swi {
+            0x00 => "SoftReset",
+            0x02 => "Halt",
+            0x04 => "IntrWait",
+            0x05 => "VBlankIntrWait",
+            0x06 => "Div",
+            0x07 => "DivArm",
+            0x08 => "Sqrt",
+            0x0B => "CpuSet",
+            0x0C => "CpuFastSet",
+            0x0E => "BgAffineSet",
+            0x0F => "ObjAffineSet",
+            0x10 => "BitUnPack",
+            0x11 => "LZ77UnCompWram",
+            0x12 => "LZ77UnCompVram",
+            0x13 => "HuffUnComp",
+            0x14 => "RLUnCompWram",
+            0x15 => "RLUnCompVram",
+            0x16 => "Diff8bitUnFilterWram",
+            0x17 => "Diff8bitUnFilterVram",
+            0x18 => "Diff16bitUnFilter",
+            _ => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for HleShortcut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (SWI 0x{:02X}) x{}",
+            self.name(),
+            self.swi,
+            self.calls
+        )
+    }
+}
+
+impl Arm7tdmi {
+    /// Runs SWI `number` with the arguments in R0-R3, `false` if it isn't emulated and
+    /// the BIOS has to handle it.
+    pub(super) fn handle_swi_hle(&mut self, number: u8) -> bool {
+        match number {
+            0x00 => self.soft_reset(),
+            0x02 => self.bus.write_byte(HALTCNT.get(), 0),
+            0x04 => {
+                let discard = self.registers.register_at(0) != 0;
+                self.intr_wait(discard, self.registers.register_at(1) as u16);
+            }
+            0x05 => self.intr_wait(true, 1),
+            // The BIOS never returns from a division by zero, the SWI is left to it.
+            0x06 | 0x07 if self.divisor(number) == 0 => return false,
+            0x06 => self.div(self.registers.register_at(0), self.registers.register_at(1)),
+            0x07 => self.div(self.registers.register_at(1), self.registers.register_at(0)),
+            0x08 => {
+                let root = self.registers.register_at(0).isqrt();
+                self.registers.set_register_at(0, root);
+            }
+            0x0B => self.cpu_set(),
+            0x0C => self.cpu_fast_set(),
+            0x0E => self.bg_affine_set(),
+            0x0F => self.obj_affine_set(),
+            0x10 => self.bit_unpack(),
+            0x11 => self.lz77_uncomp(Unit::Byte),
+            0x12 => self.lz77_uncomp(Unit::HalfWord),
+            0x13 => self.huff_uncomp(),
+            0x14 => self.rl_uncomp(Unit::Byte),
+            0x15 => self.rl_uncomp(Unit::HalfWord),
+            0x16 => self.diff_unfilter(Unit::Byte, Unit::Byte),
+            0x17 => self.diff_unfilter(Unit::Byte, Unit::HalfWord),
+            0x18 => self.diff_unfilter(Unit::HalfWord, Unit::HalfWord),
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// SWI 0x00, what games run on A+B+Select+Start: restarts the game without going
+    /// through the boot of the BIOS. The I/O registers and the work RAMs are kept, but
+    /// for the top of the internal work RAM holding the stacks and the interrupt vector.
+    /// The game restarts in ROM, or in EWRAM for a multiboot game if the byte at
+    /// [`SOFT_RESET_TARGET`] isn't 0.
+    pub fn soft_reset(&mut self) {
+        let target = if self.bus.read_raw(SOFT_RESET_TARGET) == 0 {
+            0x0800_0000
+        } else {
+            0x0200_0000
+        };
+        for address in 0x0300_7E00..0x0300_8000 {
+            self.bus.write_raw(address, 0);
+        }
+
+        self.swap_mode(&Mode::System);
+        self.register_bank.r13_svc = 0x0300_7FE0;
+        self.register_bank.r14_svc = 0;
+        self.register_bank.spsr_svc = Psr::default();
+        self.register_bank.r13_irq = 0x0300_7FA0;
+        self.register_bank.r14_irq = 0;
+        self.register_bank.spsr_irq = Psr::default();
+        for register in 0..13 {
+            self.registers.set_register_at(register, 0);
+        }
+        self.registers.set_register_at(13, 0x0300_7F00);
+
+        self.cpsr.set_cpu_state(CpuState::Arm);
+        self.cpsr.set_irq_disable(false);
+        self.cpsr.set_fiq_disable(false);
+        self.intr_waiting = false;
+        self.bus.wake();
+        self.registers.set_program_counter(target);
+        self.flush_pipeline();
+    }
+
+    /// Divisor of SWI 0x06, or of SWI 0x07 which swaps the operands.
+    fn divisor(&self, number: u8) -> u32 {
+        self.registers.register_at(usize::from(number == 0x06))
+    }
+
+    /// SWI 0x06 and 0x07: signed division, the quotient in R0, the remainder in R1 and
+    /// the absolute value of the quotient in R3.
+    fn div(&mut self, numerator: u32, denominator: u32) {
+        let (numerator, denominator) = (numerator as i32, denominator as i32);
+        let quotient = numerator.wrapping_div(denominator);
+
+        self.registers.set_register_at(0, quotient as u32);
+        self.registers
+            .set_register_at(1, numerator.wrapping_rem(denominator) as u32);
+        self.registers.set_register_at(3, quotient.unsigned_abs());
+    }
+
+    /// SWI 0x0B: copies or fills (bit 24 of R2) from R0 to R1 the number of half-words,
+    /// or words with bit 26, in the low 21 bits of R2.
+    fn cpu_set(&mut self) {
+        let control = self.registers.register_at(2);
+        let unit = if control.get_bit(26) {
+            Unit::Word
+        } else {
+            Unit::HalfWord
+        };
+
+        self.transfer(control & 0x1F_FFFF, control.get_bit(24), unit);
+    }
+
+    /// SWI 0x0C: like [`Self::cpu_set`] in words, by blocks of 8 words.
+    fn cpu_fast_set(&mut self) {
+        let control = self.registers.register_at(2);
+        let count = (control & 0x1F_FFFF).next_multiple_of(8);
+
+        self.transfer(count, control.get_bit(24), Unit::Word);
+    }
+
+    /// Copies or fills `count` `unit`s from R0 to R1, aligned to the unit.
+    fn transfer(&mut self, count: u32, fill: bool, unit: Unit) {
+        let size = if unit == Unit::Word { 4 } else { 2 };
+        let mut source = self.registers.register_at(0) & !(size - 1);
+        let mut destination = self.registers.register_at(1) & !(size - 1);
+
+        for _ in 0..count {
+            if unit == Unit::Word {
+                let value = self.bus.read_word(source);
+                self.bus.write_word(destination, value);
+            } else {
+                let value = self.bus.read_half_word(source);
+                self.bus.write_half_word(destination, value);
+            }
+
+            if !fill {
+                source = source.wrapping_add(size);
+            }
+            destination = destination.wrapping_add(size);
+        }
+    }
+
+    /// Source, destination and decompressed size from the header at the source. The
+    /// compressed data follows the header.
+    fn decompression_arguments(&mut self) -> (u32, u32, usize) {
+        let source = self.registers.register_at(0);
+        let destination = self.registers.register_at(1);
+        let header = self.bus.read_word(source);
+
+        (source.wrapping_add(4), destination, (header >> 8) as usize)
+    }
+
+    fn read_next(&mut self, address: &mut u32) -> u8 {
+        let value = self.bus.read_byte(*address);
+        *address = address.wrapping_add(1);
+        value
+    }
+
+    /// Writes `data` from `destination` in `unit`s, the last one padded with zeroes.
+    fn write_output(&mut self, destination: u32, data: &[u8], unit: Unit) {
+        let size = match unit {
+            Unit::Byte => 1,
+            Unit::HalfWord => 2,
+            Unit::Word => 4,
+        };
+
+        for (index, chunk) in data.chunks(size).enumerate() {
+            let address = destination.wrapping_add((index * size) as u32);
+            let mut bytes = [0; 4];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+
+            match unit {
+                Unit::Byte => self.bus.write_byte(address, bytes[0]),
+                Unit::HalfWord => {
+                    self.bus
+                        .write_half_word(address, u16::from_le_bytes([bytes[0], bytes[1]]));
+                }
+                Unit::Word => self.bus.write_word(address, u32::from_le_bytes(bytes)),
+            }
+        }
+    }
+
+    /// SWI 0x04 and 0x05 (with R0 and R1 set to 1 for the Vblank): sets `IME` and halts
+    /// until the IRQ handler of the game acknowledges one of `flags` at
+    /// [`BIOS_INTERRUPT_FLAGS`], clearing it. With `discard` the flags set before the
+    /// call don't count.
+    ///
+    /// While waiting the SWI is executed again after each interrupt, so the IRQ handler
+    /// returns to it. As in the BIOS, the interrupts have to be enabled in the CPSR.
+    fn intr_wait(&mut self, discard: bool, flags: u16) {
+        self.bus.write_half_word(IME.get(), 1);
+
+        let mut acknowledged = self.bus.read_half_word(BIOS_INTERRUPT_FLAGS);
+        if discard && !self.intr_waiting {
+            acknowledged &= !flags;
+        }
+
+        if acknowledged & flags != 0 {
+            self.bus
+                .write_half_word(BIOS_INTERRUPT_FLAGS, acknowledged & !flags);
+            self.intr_waiting = false;
+            return;
+        }
+
+        self.bus.write_half_word(BIOS_INTERRUPT_FLAGS, acknowledged);
+        self.intr_waiting = true;
+
+        let size = match self.cpsr.cpu_state() {
+            CpuState::Arm => arm::operations::SIZE_OF_INSTRUCTION,
+            CpuState::Thumb => thumb::operations::SIZE_OF_INSTRUCTION,
+        };
+        let swi = self.registers.program_counter() as u32 - 2 * size;
+        self.registers.set_program_counter(swi);
+        self.flush_pipeline();
+        self.bus.write_byte(HALTCNT.get(), 0);
+    }
+
+    /// SWI 0x0E: computes R2 background transformations from the parameters at R0 to R1.
+    /// A source entry is 20 bytes: the center in the background (two 19.8 words), the
+    /// center on screen (two half-words), the scales and the angle. A destination entry
+    /// is 16 bytes: PA to PD, then the reference point X and Y.
+    fn bg_affine_set(&mut self) {
+        let mut source = self.registers.register_at(0);
+        let mut destination = self.registers.register_at(1);
+        let count = self.registers.register_at(2);
+
+        for _ in 0..count {
+            let origin_x = self.bus.read_word(source) as i32;
+            let origin_y = self.bus.read_word(source.wrapping_add(4)) as i32;
+            let center_x = i32::from(self.bus.read_half_word(source.wrapping_add(8)) as i16);
+            let center_y = i32::from(self.bus.read_half_word(source.wrapping_add(10)) as i16);
+            let scale_x = self.bus.read_half_word(source.wrapping_add(12)) as i16;
+            let scale_y = self.bus.read_half_word(source.wrapping_add(14)) as i16;
+            let angle = self.bus.read_half_word(source.wrapping_add(16));
+            source = source.wrapping_add(20);
+
+            let [pa, pb, pc, pd] = affine_matrix(scale_x, scale_y, angle);
+            let x = origin_x.wrapping_sub(pa * center_x + pb * center_y);
+            let y = origin_y.wrapping_sub(pc * center_x + pd * center_y);
+
+            for (offset, parameter) in (0..).step_by(2).zip([pa, pb, pc, pd]) {
+                self.bus
+                    .write_half_word(destination.wrapping_add(offset), parameter as u16);
+            }
+            self.bus.write_word(destination.wrapping_add(8), x as u32);
+            self.bus.write_word(destination.wrapping_add(12), y as u32);
+            destination = destination.wrapping_add(16);
+        }
+    }
+
+    /// SWI 0x0F: computes R2 sprite transformations from the parameters at R0 to R1. A
+    /// source entry is 8 bytes: the scales and the angle. PA to PD are written R3 bytes
+    /// apart, 2 for a plain array and 8 to write them straight to OAM.
+    fn obj_affine_set(&mut self) {
+        let mut source = self.registers.register_at(0);
+        let mut destination = self.registers.register_at(1);
+        let count = self.registers.register_at(2);
+        let stride = self.registers.register_at(3);
+
+        for _ in 0..count {
+            let scale_x = self.bus.read_half_word(source) as i16;
+            let scale_y = self.bus.read_half_word(source.wrapping_add(2)) as i16;
+            let angle = self.bus.read_half_word(source.wrapping_add(4));
+            source = source.wrapping_add(8);
+
+            for parameter in affine_matrix(scale_x, scale_y, angle) {
+                self.bus.write_half_word(destination, parameter as u16);
+                destination = destination.wrapping_add(stride);
+            }
+        }
+    }
+
+    /// SWI 0x10: widens every unit of `source width` bits to `destination width` bits,
+    /// adding the offset to the non-zero ones (to all of them with bit 31 of the offset).
+    fn bit_unpack(&mut self) {
+        let mut source = self.registers.register_at(0);
+        let mut destination = self.registers.register_at(1);
+        let info = self.registers.register_at(2);
+
+        let length = self.bus.read_half_word(info);
+        let source_width = self.bus.read_byte(info.wrapping_add(2));
+        let destination_width = self.bus.read_byte(info.wrapping_add(3));
+        let offset = self.bus.read_word(info.wrapping_add(4));
+        let (offset, offset_zeroes) = (offset.get_bits(0..=30), offset.get_bit(31));
+
+        if !matches!(source_width, 1 | 2 | 4 | 8)
+            || !matches!(destination_width, 1 | 2 | 4 | 8 | 16 | 32)
+        {
+            return;
+        }
+
+        let mask = (1 << source_width) - 1;
+        let mut word = 0_u32;
+        let mut bits = 0;
+        for _ in 0..length {
+            let byte = self.read_next(&mut source);
+
+            for shift in (0..8).step_by(source_width.into()) {
+                let mut value = u32::from(byte >> shift) & mask;
+                if value != 0 || offset_zeroes {
+                    value += offset;
+                }
+
+                word |= value << bits;
+                bits += destination_width;
+                if bits == 32 {
+                    self.bus.write_word(destination, word);
+                    destination = destination.wrapping_add(4);
+                    word = 0;
+                    bits = 0;
+                }
+            }
+        }
+    }
+
+    /// SWI 0x11 and 0x12: blocks of 8 literal bytes or copies of the previous output,
+    /// led by a byte of flags, most significant bit first.
+    fn lz77_uncomp(&mut self, unit: Unit) {
+        let (mut source, destination, size) = self.decompression_arguments();
+        let mut output = Vec::with_capacity(size);
+
+        while output.len() < size {
+            let flags = self.read_next(&mut source);
+
+            for block in (0..8).rev() {
+                if output.len() >= size {
+                    break;
+                }
+
+                if !flags.get_bit(block) {
+                    output.push(self.read_next(&mut source));
+                    continue;
+                }
+
+                let high = self.read_next(&mut source);
+                let low = self.read_next(&mut source);
+                let length = usize::from(high >> 4) + 3;
+                let distance = (usize::from(high & 0xF) << 8 | usize::from(low)) + 1;
+
+                for _ in 0..length {
+                    // Before the start of the output the destination isn't initialized.
+                    let byte = output
+                        .len()
+                        .checked_sub(distance)
+                        .map_or(0, |index| output[index]);
+                    output.push(byte);
+                }
+            }
+        }
+
+        output.truncate(size);
+        self.write_output(destination, &output, unit);
+    }
+
+    /// SWI 0x13: codes read from 32-bit words, most significant bit first, walking the
+    /// tree that follows the header. 4-bit data fill the bytes low nibble first.
+    fn huff_uncomp(&mut self) {
+        let (tree, destination, size) = self.decompression_arguments();
+        let data_bits = self.bus.read_word(tree.wrapping_sub(4)).get_bits(0..=3);
+        let tree_size = u32::from(self.bus.read_byte(tree));
+        let root = tree.wrapping_add(1);
+        let mut stream = tree.wrapping_add((tree_size + 1) * 2);
+
+        let mut output = Vec::with_capacity(size);
+        let mut nibble = None;
+        let (mut node_address, mut node) = (root, self.bus.read_byte(root));
+
+        while output.len() < size {
+            let word = self.bus.read_word(stream);
+            stream = stream.wrapping_add(4);
+
+            for bit in (0..32).rev() {
+                let right = word.get_bit(bit);
+                let child = (node_address & !1)
+                    .wrapping_add(u32::from(node & 0x3F) * 2 + 2 + u32::from(right));
+
+                if !node.get_bit(if right { 6 } else { 7 }) {
+                    (node_address, node) = (child, self.bus.read_byte(child));
+                    continue;
+                }
+
+                let data = self.bus.read_byte(child);
+                if data_bits == 8 {
+                    output.push(data);
+                } else if let Some(low) = nibble.take() {
+                    output.push(low | (data & 0xF) << 4);
+                } else {
+                    nibble = Some(data & 0xF);
+                }
+
+                (node_address, node) = (root, self.bus.read_byte(root));
+                if output.len() >= size {
+                    break;
+                }
+            }
+        }
+
+        self.write_output(destination, &output, Unit::Word);
+    }
+
+    /// SWI 0x14 and 0x15: runs of a repeated byte (flag bit 7 set, 3 to 130 bytes) and
+    /// runs of literal bytes (1 to 128 bytes).
+    fn rl_uncomp(&mut self, unit: Unit) {
+        let (mut source, destination, size) = self.decompression_arguments();
+        let mut output = Vec::with_capacity(size);
+
+        while output.len() < size {
+            let flag = self.read_next(&mut source);
+
+            if flag.get_bit(7) {
+                let length = usize::from(flag & 0x7F) + 3;
+                let byte = self.read_next(&mut source);
+                output.resize(output.len() + length, byte);
+            } else {
+                for _ in 0..=(flag & 0x7F) {
+                    output.push(self.read_next(&mut source));
+                }
+            }
+        }
+
+        output.truncate(size);
+        self.write_output(destination, &output, unit);
+    }
+
+    /// SWI 0x16 to 0x18: every 8 or 16-bit unit is the difference with the previous one.
+    fn diff_unfilter(&mut self, width: Unit, unit: Unit) {
+        let (mut source, destination, size) = self.decompression_arguments();
+        let mut output = Vec::with_capacity(size);
+
+        if width == Unit::Byte {
+            let mut value = 0_u8;
+            for _ in 0..size {
+                value = value.wrapping_add(self.read_next(&mut source));
+                output.push(value);
+            }
+        } else {
+            let mut value = 0_u16;
+            for _ in 0..size / 2 {
+                value = value.wrapping_add(self.bus.read_half_word(source));
+                source = source.wrapping_add(2);
+                output.extend(value.to_le_bytes());
+            }
+        }
+
+        self.write_output(destination, &output, unit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::memory_map::IE;
+
+    const SOURCE: u32 = 0x0200_0000;
+    const DESTINATION: u32 = 0x0300_0000;
+
+    fn run(number: u8, source: &[u8], size: usize) -> Vec<u8> {
+        let mut cpu = Arm7tdmi::default();
+        for (address, byte) in (SOURCE..).zip(source) {
+            cpu.bus.write_byte(address, *byte);
+        }
+        cpu.registers.set_register_at(0, SOURCE);
+        cpu.registers.set_register_at(1, DESTINATION);
+
+        assert!(cpu.handle_swi_hle(number));
+
+        (DESTINATION..)
+            .take(size)
+            .map(|address| cpu.bus.read_raw(address))
+            .collect()
+    }
+
+    #[test]
+    fn lz77() {
+        // "ABC", then 6 bytes copied from 3 bytes back, then "D".
+        let source = [
+            0x10,
+            10,
+            0,
+            0,
+            0b0001_0000,
+            b'A',
+            b'B',
+            b'C',
+            0x30,
+            0x02,
+            b'D',
+        ];
+
+        assert_eq!(run(0x11, &source, 10), b"ABCABCABCD");
+        assert_eq!(run(0x12, &source, 10), b"ABCABCABCD");
+    }
+
+    #[test]
+    fn huffman() {
+        // The root has 'a' on the left and 'b' on the right, codes 0 and 1.
+        let source = [0x28, 4, 0, 0, 1, 0xC0, b'a', b'b', 0, 0, 0, 0b0110_0000];
+        assert_eq!(run(0x13, &source, 4), b"abba");
+
+        // 4-bit data: 0xA and 0xB, the first one in the low nibble.
+        let source = [0x24, 2, 0, 0, 1, 0xC0, 0xA, 0xB, 0, 0, 0, 0b0110_0000];
+        assert_eq!(run(0x13, &source, 2), [0xBA, 0xAB]);
+    }
+
+    #[test]
+    fn run_length() {
+        // 3 literal bytes then 5 times 'Q'.
+        let source = [0x30, 8, 0, 0, 0x02, b'X', b'Y', b'Z', 0x82, b'Q'];
+
+        assert_eq!(run(0x14, &source, 8), b"XYZQQQQQ");
+        assert_eq!(run(0x15, &source, 8), b"XYZQQQQQ");
+    }
+
+    #[test]
+    fn diff_unfilter() {
+        let source = [0x81, 4, 0, 0, 1, 1, 1, 0xFF];
+        assert_eq!(run(0x16, &source, 4), [1, 2, 3, 2]);
+
+        let source = [0x82, 6, 0, 0, 0x00, 0x01, 0x10, 0x00, 0xFF, 0xFF];
+        assert_eq!(run(0x18, &source, 6), [0x00, 0x01, 0x10, 0x01, 0x0F, 0x01]);
+    }
+
+    #[test]
+    fn bit_unpack() {
+        let mut cpu = Arm7tdmi::default();
+        // 2-bit units to bytes, 0x10 added to the non-zero ones.
+        cpu.bus.write_byte(SOURCE, 0b1110_0100);
+        cpu.bus.write_half_word(SOURCE + 0x10, 1);
+        cpu.bus.write_half_word(SOURCE + 0x12, 2 | 8 << 8);
+        cpu.bus.write_word(SOURCE + 0x14, 0x10);
+        cpu.registers.set_register_at(0, SOURCE);
+        cpu.registers.set_register_at(1, DESTINATION);
+        cpu.registers.set_register_at(2, SOURCE + 0x10);
+
+        assert!(cpu.handle_swi_hle(0x10));
+        assert_eq!(cpu.bus.read_word(DESTINATION), 0x1312_1100);
+
+        // With bit 31 the offset is added to zeroes too.
+        cpu.bus.write_word(SOURCE + 0x14, 1 << 31 | 0x10);
+        assert!(cpu.handle_swi_hle(0x10));
+        assert_eq!(cpu.bus.read_word(DESTINATION), 0x1312_1110);
+    }
+
+    #[test]
+    fn sine_table() {
+        assert_eq!(sine(0), 0);
+        assert_eq!(sine(64), 0x4000);
+        assert_eq!(sine(96), sine(32));
+        assert_eq!(sine(160), -sine(32));
+        assert_eq!(sine(224), -sine(32));
+        assert_eq!(cosine(0), 0x4000);
+        assert_eq!(cosine(128), -0x4000);
+    }
+
+    #[test]
+    fn obj_affine_set() {
+        let mut cpu = Arm7tdmi::default();
+        // Twice as small horizontally, 1/8 of a turn.
+        cpu.bus.write_half_word(SOURCE, 0x200);
+        cpu.bus.write_half_word(SOURCE + 2, 0x100);
+        cpu.bus.write_half_word(SOURCE + 4, 0x2000);
+        cpu.registers.set_register_at(0, SOURCE);
+        cpu.registers.set_register_at(1, DESTINATION);
+        cpu.registers.set_register_at(2, 1);
+        cpu.registers.set_register_at(3, 8);
+
+        assert!(cpu.handle_swi_hle(0x0F));
+        let parameters = (0..4)
+            .map(|index| cpu.bus.read_half_word(DESTINATION + index * 8))
+            .collect::<Vec<_>>();
+        // 0x2D41 is sin(π/4) in 1.14, the negative product is rounded down.
+        assert_eq!(parameters, [0x016A, 0xFE95, 0x00B5, 0x00B5]);
+    }
+
+    #[test]
+    fn bg_affine_set() {
+        let mut cpu = Arm7tdmi::default();
+        // Background point (64, 32) shown at (120, 80), not scaled and rotated by 1/4
+        // of a turn.
+        cpu.bus.write_word(SOURCE, 64 << 8);
+        cpu.bus.write_word(SOURCE + 4, 32 << 8);
+        cpu.bus.write_half_word(SOURCE + 8, 120);
+        cpu.bus.write_half_word(SOURCE + 10, 80);
+        cpu.bus.write_half_word(SOURCE + 12, 0x100);
+        cpu.bus.write_half_word(SOURCE + 14, 0x100);
+        cpu.bus.write_half_word(SOURCE + 16, 0x4000);
+        cpu.registers.set_register_at(0, SOURCE);
+        cpu.registers.set_register_at(1, DESTINATION);
+        cpu.registers.set_register_at(2, 1);
+
+        assert!(cpu.handle_swi_hle(0x0E));
+        assert_eq!(cpu.bus.read_half_word(DESTINATION), 0);
+        assert_eq!(cpu.bus.read_half_word(DESTINATION + 2), 0xFF00);
+        assert_eq!(cpu.bus.read_half_word(DESTINATION + 4), 0x0100);
+        assert_eq!(cpu.bus.read_half_word(DESTINATION + 6), 0);
+        assert_eq!(cpu.bus.read_word(DESTINATION + 8) as i32, (64 + 80) << 8);
+        assert_eq!(cpu.bus.read_word(DESTINATION + 12) as i32, (32 - 120) << 8);
+    }
+
+    #[test]
+    fn vblank_intr_wait() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.set_bios_hle(true);
+        // IRQ handler: acknowledges the Vblank in IF and for the BIOS, then returns.
+        for (address, instruction) in [
+            (0x18, "B 0x100"),
+            (0x100, "MOV R3, #0x4000000"),
+            (0x104, "ADD R3, R3, #0x200"),
+            (0x108, "MOV R4, #1"),
+            (0x10C, "STRH R4, [R3, #2]"),
+            (0x110, "MOV R5, #0x3000000"),
+            (0x114, "ADD R5, R5, #0x7F00"),
+            (0x118, "STRH R4, [R5, #0xF8]"),
+            (0x11C, "SUBS PC, LR, #4"),
+            (0x0300_1000, "SWI 0x050000"),
+            (0x0300_1004, "MOV R2, #1"),
+        ] {
+            cpu.patch_instruction(address, instruction, false).unwrap();
+        }
+        cpu.registers.set_program_counter(0x0300_1000);
+        cpu.cpsr.set_irq_disable(false);
+        cpu.bus.write_half_word(0x0400_0004, 0x0008);
+        cpu.bus.write_half_word(IE.get(), 0x0001);
+        // Acknowledged before the call: discarded.
+        cpu.bus.write_half_word(BIOS_INTERRUPT_FLAGS, 1);
+
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert!(cpu.bus.halted());
+        assert!(cpu.intr_waiting);
+        assert_eq!(cpu.bus.read_half_word(IME.get()), 1);
+        assert_eq!(cpu.bus.read_half_word(BIOS_INTERRUPT_FLAGS), 0);
+
+        // A state saved during the wait doesn't discard the flags again.
+        #[cfg(feature = "serde")]
+        {
+            let state = crate::save_state::encode(&cpu).unwrap();
+            cpu.intr_waiting = false;
+            assert!(crate::save_state::decode(&mut cpu, &state)
+                .unwrap()
+                .is_complete());
+            assert!(cpu.intr_waiting);
+        }
+
+        let mut steps = 0;
+        while cpu.registers.register_at(2) == 0 {
+            cpu.step();
+            steps += 1;
+            assert!(steps < 1000);
+        }
+        assert_eq!(cpu.bus.lcd.registers.vcount, 160);
+        assert_eq!(cpu.bus.read_half_word(BIOS_INTERRUPT_FLAGS), 0);
+        assert!(!cpu.intr_waiting);
+    }
+
+    #[test]
+    fn swi_runs_the_emulated_function() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.set_bios_hle(true);
+        cpu.patch_instruction(0x0300_1000, "SWI 0x140000", false)
+            .unwrap();
+        cpu.patch_instruction(0x0300_1004, "MOV R2, #1", false)
+            .unwrap();
+        for (address, byte) in (SOURCE..).zip([0x30, 4, 0, 0, 0x81, b'Z']) {
+            cpu.bus.write_byte(address, byte);
+        }
+        cpu.registers.set_register_at(0, SOURCE);
+        cpu.registers.set_register_at(1, DESTINATION);
+        cpu.registers.set_program_counter(0x0300_1000);
+
+        for _ in 0..4 {
+            cpu.step();
+        }
+
+        // Back to the next instruction without going through the BIOS.
+        assert_eq!(cpu.registers.register_at(2), 1);
+        assert_eq!(cpu.bus.read_word(DESTINATION), 0x5A5A_5A5A);
+        assert!(!cpu.handle_swi_hle(0x01));
+    }
+
+    #[test]
+    fn shortcuts_are_listed_unless_max_accuracy() {
+        let run_div = |max_accuracy: bool| {
+            let mut cpu = Arm7tdmi::default();
+            cpu.set_bios_hle(true);
+            cpu.set_max_accuracy(max_accuracy);
+            cpu.patch_instruction(0x0300_1000, "SWI 0x60000", false)
+                .unwrap();
+            cpu.registers.set_register_at(0, 7);
+            cpu.registers.set_register_at(1, 2);
+            cpu.registers.set_program_counter(0x0300_1000);
+            for _ in 0..3 {
+                cpu.step();
+            }
+
+            cpu
+        };
+
+        let cpu = run_div(false);
+        assert_eq!(cpu.registers.register_at(0), 3);
+        let shortcuts = cpu.hle_shortcuts();
+        assert_eq!(
+            shortcuts,
+            [HleShortcut {
+                swi: 0x06,
+                calls: 1
+            }]
+        );
+        assert_eq!(shortcuts[0].to_string(), "Div (SWI 0x06) x1");
+
+        // The BIOS runs it instead.
+        let cpu = run_div(true);
+        assert!(cpu.hle_shortcuts().is_empty());
+        assert_eq!(cpu.cpsr.mode(), Mode::Supervisor);
+    }
+
+    #[test]
+    fn soft_reset() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.bus.write_word(SOURCE, 0x1234_5678);
+        cpu.bus.write_word(0x0300_7FFC, 0x0300_1000);
+        cpu.registers.set_register_at(0, 1);
+        cpu.registers.set_program_counter(0x0300_1000);
+        cpu.swap_mode(&Mode::Irq);
+
+        assert!(cpu.handle_swi_hle(0x00));
+        assert_eq!(cpu.cpsr.mode(), Mode::System);
+        assert!(matches!(cpu.cpsr.cpu_state(), CpuState::Arm));
+        assert_eq!(cpu.registers.register_at(0), 0);
+        assert_eq!(cpu.registers.register_at(13), 0x0300_7F00);
+        assert_eq!(cpu.register_bank.r13_irq, 0x0300_7FA0);
+        assert_eq!(cpu.register_bank.r13_svc, 0x0300_7FE0);
+        // The interrupt vector is cleared, the rest of the work RAM kept.
+        assert_eq!(cpu.bus.read_word(0x0300_7FFC), 0);
+        assert_eq!(cpu.bus.read_word(SOURCE), 0x1234_5678);
+        assert_eq!(cpu.registers.program_counter(), 0x0800_0000);
+
+        // A multiboot game restarts in EWRAM.
+        cpu.bus.write_byte(SOFT_RESET_TARGET, 1);
+        cpu.soft_reset();
+        assert_eq!(cpu.registers.program_counter(), 0x0200_0000);
+    }
+
+    #[test]
+    fn div_and_sqrt() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.registers.set_register_at(0, -7_i32 as u32);
+        cpu.registers.set_register_at(1, 2);
+
+        assert!(cpu.handle_swi_hle(0x06));
+        assert_eq!(cpu.registers.register_at(0), -3_i32 as u32);
+        assert_eq!(cpu.registers.register_at(1), -1_i32 as u32);
+        assert_eq!(cpu.registers.register_at(3), 3);
+
+        cpu.registers.set_register_at(0, 0);
+        assert!(!cpu.handle_swi_hle(0x07));
+
+        cpu.registers.set_register_at(0, 1000);
+        assert!(cpu.handle_swi_hle(0x08));
+        assert_eq!(cpu.registers.register_at(0), 31);
+    }
+
+    #[test]
+    fn cpu_set() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.bus.write_word(SOURCE, 0xAABB_CCDD);
+        cpu.bus.write_word(SOURCE + 4, 0x1122_3344);
+        cpu.registers.set_register_at(0, SOURCE);
+        cpu.registers.set_register_at(1, DESTINATION);
+
+        // Copies 3 half-words.
+        cpu.registers.set_register_at(2, 3);
+        assert!(cpu.handle_swi_hle(0x0B));
+        assert_eq!(cpu.bus.read_word(DESTINATION), 0xAABB_CCDD);
+        assert_eq!(cpu.bus.read_word(DESTINATION + 4), 0x3344);
+
+        // Fills 10 words, rounded up to 16 by CpuFastSet.
+        cpu.registers.set_register_at(2, 1 << 24 | 0xA);
+
+        assert!(cpu.handle_swi_hle(0x0C));
+        assert_eq!(cpu.bus.read_word(DESTINATION + 15 * 4), 0xAABB_CCDD);
+        assert_eq!(cpu.bus.read_word(DESTINATION + 16 * 4), 0);
+    }
+
+    #[test]
+    fn addresses_wrap_around() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.registers.set_register_at(0, 0xFFFF_FFFC);
+        cpu.registers.set_register_at(1, DESTINATION);
+
+        // The source goes on from the BIOS, after the end of the address space.
+        cpu.registers.set_register_at(2, 1 << 26 | 2);
+        assert!(cpu.handle_swi_hle(0x0B));
+
+        // The destination goes on from the start of the address space too.
+        cpu.registers.set_register_at(0, SOURCE);
+        cpu.registers.set_register_at(1, 0xFFFF_FFFE);
+        cpu.registers.set_register_at(2, 2);
+        assert!(cpu.handle_swi_hle(0x0B));
+    }
+}
@@ -0,0 +1,384 @@
+//! Conditions of the breakpoints, evaluated only when the CPU reaches the address of
+//! the breakpoint so that they cost nothing to the other instructions.
+//!
+//! A condition is an expression on 32-bit values, true when it isn't 0:
+//! ```text
+//! r0 == 0x40 && [0x03001234] != 0
+//! (cpsr & 0x20) || [sp + 4] & 0xFF == 12
+//! ```
+//! - numbers in decimal or hexadecimal (`0x`);
+//! - the registers of the current mode, `r0` to `r15`, `sp`, `lr`, `pc` and `cpsr`. The
+//!   program counter reads as the instruction sees it, 8 bytes after it in ARM and 4
+//!   bytes in Thumb;
+//! - `[address]`, the word in memory at `address`, read without side effects;
+//! - from the lowest precedence: `||`, `&&`, the comparisons `==`, `!=`, `<`, `<=`,
+//!   `>`, `>=` (unsigned), `|`, `&`, `+` and `-`, the unary `!` and `-`, and parentheses.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConditionError {
+    /// Byte offset in the source where the error is.
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ConditionError {}
+
+/// What a condition reads from the emulator.
+pub trait ConditionContext {
+    /// R0 to R15 of the current mode.
+    fn register(&self, index: usize) -> u32;
+
+    fn cpsr(&self) -> u32;
+
+    fn read_word(&self, address: u32) -> u32;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    BitOr,
+    BitAnd,
+    Add,
+    Sub,
+}
+
+impl Operator {
+    /// Binding strength, the higher the tighter.
+    const fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 0,
+            Self::And => 1,
+            Self::Equal
+            | Self::NotEqual
+            | Self::Less
+            | Self::LessEqual
+            | Self::Greater
+            | Self::GreaterEqual => 2,
+            Self::BitOr => 3,
+            Self::BitAnd => 4,
+            Self::Add | Self::Sub => 5,
+        }
+    }
+
+    fn apply(self, left: u32, right: u32) -> u32 {
+        match self {
+            Self::Or => u32::from(left != 0 || right != 0),
+            Self::And => u32::from(left != 0 && right != 0),
+            Self::Equal => u32::from(left == right),
+            Self::NotEqual => u32::from(left != right),
+            Self::Less => u32::from(left < right),
+            Self::LessEqual => u32::from(left <= right),
+            Self::Greater => u32::from(left > right),
+            Self::GreaterEqual => u32::from(left >= right),
+            Self::BitOr => left | right,
+            Self::BitAnd => left & right,
+            Self::Add => left.wrapping_add(right),
+            Self::Sub => left.wrapping_sub(right),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Expr {
+    Number(u32),
+    Register(usize),
+    Cpsr,
+    Memory(Box<Self>),
+    Not(Box<Self>),
+    Negate(Box<Self>),
+    Binary(Operator, Box<Self>, Box<Self>),
+}
+
+impl Expr {
+    fn evaluate(&self, context: &dyn ConditionContext) -> u32 {
+        match self {
+            Self::Number(value) => *value,
+            Self::Register(index) => context.register(*index),
+            Self::Cpsr => context.cpsr(),
+            Self::Memory(address) => context.read_word(address.evaluate(context)),
+            Self::Not(value) => u32::from(value.evaluate(context) == 0),
+            Self::Negate(value) => value.evaluate(context).wrapping_neg(),
+            Self::Binary(operator, left, right) => {
+                let left = left.evaluate(context);
+                // `||` and `&&` don't read the right side when the left one decides.
+                match (operator, left != 0) {
+                    (Operator::Or, true) => 1,
+                    (Operator::And, false) => 0,
+                    _ => operator.apply(left, right.evaluate(context)),
+                }
+            }
+        }
+    }
+}
+
+/// A parsed condition, displayed as its source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    #[must_use]
+    pub fn evaluate(&self, context: &dyn ConditionContext) -> bool {
+        self.expr.evaluate(context) != 0
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ConditionError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            source,
+            position: 0,
+        };
+        let expr = parser.expression(0)?;
+
+        parser.skip_whitespace();
+        if parser.position < source.len() {
+            return Err(parser.error("unexpected character"));
+        }
+
+        Ok(Self {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Recursive descent parser, binary operators by precedence climbing.
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    const fn error(&self, message: &'static str) -> ConditionError {
+        ConditionError {
+            position: self.position,
+            message,
+        }
+    }
+
+    fn rest(&self) -> &str {
+        &self.source[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes `token` if the source continues with it.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let found = self.rest().starts_with(token);
+        if found {
+            self.position += token.len();
+        }
+
+        found
+    }
+
+    fn operator(&mut self) -> Option<Operator> {
+        const OPERATORS: [(&str, Operator); 12] = [
+            ("||", Operator::Or),
+            ("&&", Operator::And),
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("<=", Operator::LessEqual),
+            (">=", Operator::GreaterEqual),
+            ("<", Operator::Less),
+            (">", Operator::Greater),
+            ("|", Operator::BitOr),
+            ("&", Operator::BitAnd),
+            ("+", Operator::Add),
+            ("-", Operator::Sub),
+        ];
+
+        self.skip_whitespace();
+        OPERATORS
+            .into_iter()
+            .find(|(token, _)| self.rest().starts_with(token))
+            .map(|(token, operator)| {
+                self.position += token.len();
+                operator
+            })
+    }
+
+    /// An expression whose operators bind at least as tight as `min_precedence`.
+    fn expression(&mut self, min_precedence: u8) -> Result<Expr, ConditionError> {
+        let mut left = self.unary()?;
+
+        loop {
+            let start = self.position;
+            match self.operator() {
+                Some(operator) if operator.precedence() >= min_precedence => {
+                    let right = self.expression(operator.precedence() + 1)?;
+                    left = Expr::Binary(operator, Box::new(left), Box::new(right));
+                }
+                _ => {
+                    self.position = start;
+                    return Ok(left);
+                }
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ConditionError> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("-") {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ConditionError> {
+        if self.eat("(") {
+            let expr = self.expression(0)?;
+            return if self.eat(")") {
+                Ok(expr)
+            } else {
+                Err(self.error("expected `)`"))
+            };
+        }
+
+        if self.eat("[") {
+            let address = self.expression(0)?;
+            return if self.eat("]") {
+                Ok(Expr::Memory(Box::new(address)))
+            } else {
+                Err(self.error("expected `]`"))
+            };
+        }
+
+        let start = self.position;
+        let len = self
+            .rest()
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or_else(|| self.rest().len());
+        if len == 0 {
+            return Err(self.error("expected a value"));
+        }
+
+        let word = &self.rest()[..len];
+        let expr = number(word).map(Expr::Number).or_else(|| operand(word));
+        if expr.is_some() {
+            self.position += len;
+        }
+
+        expr.ok_or(ConditionError {
+            position: start,
+            message: "unknown value",
+        })
+    }
+}
+
+fn number(word: &str) -> Option<u32> {
+    let word = word.replace('_', "");
+    word.strip_prefix("0x")
+        .or_else(|| word.strip_prefix("0X"))
+        .map_or_else(
+            || word.parse().ok(),
+            |hex| u32::from_str_radix(hex, 16).ok(),
+        )
+}
+
+fn operand(word: &str) -> Option<Expr> {
+    let word = word.to_ascii_lowercase();
+    match word.as_str() {
+        "sp" => Some(Expr::Register(13)),
+        "lr" => Some(Expr::Register(14)),
+        "pc" => Some(Expr::Register(15)),
+        "cpsr" => Some(Expr::Cpsr),
+        _ => word
+            .strip_prefix('r')
+            .and_then(|index| index.parse().ok())
+            .filter(|&index| index < 16)
+            .map(Expr::Register),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Context;
+
+    impl ConditionContext for Context {
+        fn register(&self, index: usize) -> u32 {
+            [0x40, 7][index.min(1)]
+        }
+
+        fn cpsr(&self) -> u32 {
+            0x3F
+        }
+
+        fn read_word(&self, address: u32) -> u32 {
+            if address == 0x0300_1234 {
+                0x1234_5678
+            } else {
+                0
+            }
+        }
+    }
+
+    fn evaluate(source: &str) -> bool {
+        source.parse::<Condition>().unwrap().evaluate(&Context)
+    }
+
+    #[test]
+    fn evaluates_conditions() {
+        assert!(evaluate("r0 == 0x40 && [0x03001234] != 0"));
+        assert!(!evaluate("r0 == 0x40 && [0x03001230] != 0"));
+        assert!(evaluate("R1 > 6 || [0] == 1"));
+        assert!(evaluate("[0x03001230 + 4] & 0xFF == 0x78"));
+        assert!(evaluate("(cpsr & 0x20) && !(r0 < 0x40)"));
+        assert!(evaluate("r0 - 0x41 == -1"));
+        assert!(evaluate("sp == 7"));
+        assert!(evaluate("1_000 == 1000"));
+    }
+
+    #[test]
+    fn reports_errors() {
+        let error = |source: &str| source.parse::<Condition>().unwrap_err();
+
+        assert_eq!(error("r0 == ").message, "expected a value");
+        assert_eq!(error("r16 == 0").position, 0);
+        assert_eq!(error("[r0").message, "expected `]`");
+        assert_eq!(
+            error("r0 == 1 )").to_string(),
+            "unexpected character at column 9"
+        );
+        assert_eq!(
+            "  r0 == 1 ".parse::<Condition>().unwrap().to_string(),
+            "r0 == 1"
+        );
+    }
+}
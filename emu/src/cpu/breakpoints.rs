@@ -0,0 +1,142 @@
+//! Execution breakpoints, checked by the CPU before each instruction.
+//!
+//! A frontend learns about a hit from the result of
+//! [`Arm7tdmi::step`](super::arm7tdmi::Arm7tdmi::step) instead of comparing the program
+//! counter after every step.
+
+use std::collections::HashMap;
+use std::ops::BitOr;
+
+use super::breakpoint_condition::{Condition, ConditionContext};
+
+/// States of the CPU a breakpoint stops in.
+///
+/// The same address can hold ARM code in one place of a game and Thumb code in another,
+/// an overlay in work RAM for example.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct StateMask(u8);
+
+impl StateMask {
+    pub const ARM: Self = Self(0b01);
+    pub const THUMB: Self = Self(0b10);
+    pub const ANY: Self = Self(0b11);
+
+    #[must_use]
+    pub const fn matches(self, thumb: bool) -> bool {
+        let state = if thumb { Self::THUMB } else { Self::ARM };
+        self.0 & state.0 != 0
+    }
+}
+
+impl BitOr for StateMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// What a step of the CPU did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepResult {
+    /// The pipeline advanced, or the CPU waited while halted.
+    Normal,
+    /// Nothing was executed: the instruction at this address has a breakpoint. The next
+    /// step executes it.
+    BreakpointHit(u32),
+}
+
+/// Stops the CPU before it executes the instruction at `address`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: u32,
+    pub states: StateMask,
+    /// Evaluated when the CPU reaches the address, it only stops if it's true.
+    pub condition: Option<Condition>,
+}
+
+impl Breakpoint {
+    /// A breakpoint without condition.
+    #[must_use]
+    pub const fn new(address: u32, states: StateMask) -> Self {
+        Self {
+            address,
+            states,
+            condition: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_condition(self, condition: Condition) -> Self {
+        Self {
+            condition: Some(condition),
+            ..self
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Breakpoints {
+    breakpoints: HashMap<u32, Breakpoint>,
+    /// Address of the last hit, executed by the next check at this address instead of
+    /// hitting again.
+    resume_from: Option<u32>,
+}
+
+impl Breakpoints {
+    /// Adds `breakpoint`, replacing the one at the same address.
+    pub fn insert(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.insert(breakpoint.address, breakpoint);
+    }
+
+    /// Returns whether there was a breakpoint at `address`.
+    pub fn remove(&mut self, address: u32) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.resume_from = None;
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    /// The breakpoints, sorted by address.
+    #[must_use]
+    pub fn list(&self) -> Vec<Breakpoint> {
+        let mut list = self.breakpoints.values().cloned().collect::<Vec<_>>();
+        list.sort_unstable_by_key(|breakpoint| breakpoint.address);
+
+        list
+    }
+
+    /// Whether the instruction at `address`, about to be executed, stops the CPU. The
+    /// condition of the breakpoint reads the CPU from `context`.
+    #[cfg_attr(not(feature = "debug-hooks"), allow(dead_code))]
+    pub(crate) fn hit(
+        &mut self,
+        address: u32,
+        thumb: bool,
+        context: &dyn ConditionContext,
+    ) -> bool {
+        if self.resume_from.take() == Some(address) {
+            return false;
+        }
+
+        let hit = self.breakpoints.get(&address).is_some_and(|breakpoint| {
+            breakpoint.states.matches(thumb)
+                && breakpoint
+                    .condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.evaluate(context))
+        });
+        if hit {
+            self.resume_from = Some(address);
+        }
+
+        hit
+    }
+}
@@ -1,3 +1,4 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 /// In ARM state, all instructions are conditionally executed according to the state of the CPSR,
@@ -7,7 +8,8 @@ use serde::{Deserialize, Serialize};
 /// the instruction is executed, otherwise it is ignored.
 /// In the absence of a suffix, the condition field of most instructions is set to "Always" (sufix AL).
 /// This means the instruction will always be executed regardless of the CPSR condition codes.
-#[derive(Debug, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Condition {
     /// Z set (equal).
     EQ = 0x0,
@@ -58,6 +60,13 @@ pub enum Condition {
     NV = 0xF,
 }
 
+impl Condition {
+    #[cfg(feature = "serde")]
+    pub(crate) const fn always() -> Self {
+        Self::AL
+    }
+}
+
 impl From<u8> for Condition {
     fn from(item: u8) -> Self {
         match item {
@@ -0,0 +1,151 @@
+use std::fmt;
+
+/// Memory regions the CPU never executes from on hardware: ending up there
+/// is almost always the symptom of an earlier emulation bug.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonExecutableRegion {
+    Io,
+    Palette,
+    Oam,
+}
+
+impl NonExecutableRegion {
+    #[must_use]
+    pub const fn from_address(address: u32) -> Option<Self> {
+        match address >> 24 {
+            0x04 => Some(Self::Io),
+            0x05 => Some(Self::Palette),
+            0x07 => Some(Self::Oam),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for NonExecutableRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io => write!(f, "I/O registers"),
+            Self::Palette => write!(f, "palette RAM"),
+            Self::Oam => write!(f, "OAM"),
+        }
+    }
+}
+
+/// Recorded when the CPU fetches an instruction from a [`NonExecutableRegion`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExecutionTrap {
+    /// Address of the fetched instruction.
+    pub address: u32,
+    pub region: NonExecutableRegion,
+    /// Address of the last instruction that moved the program counter
+    /// (branch, write to PC, exception return...), where the bug usually is.
+    pub jump_source: Option<u32>,
+    /// Bus cycle of the fetch, on the same clock as the I/O trace.
+    pub cycle: u128,
+}
+
+impl fmt::Display for ExecutionTrap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "executing from {} at 0x{:08X} (cycle {})",
+            self.region, self.address, self.cycle
+        )?;
+
+        match self.jump_source {
+            Some(source) => write!(f, ", jumped from 0x{source:08X}"),
+            None => write!(f, ", jump source unknown"),
+        }
+    }
+}
+
+/// Recorded when the CPU fetches from a program counter that isn't a multiple of the
+/// instruction size.
+///
+/// The hardware ignores the low bits, but compiled code never jumps there: it's a bug
+/// of the game or of the emulation of the jump.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MisalignedPc {
+    /// Program counter before its low bits were cleared.
+    pub address: u32,
+    pub thumb: bool,
+    /// Address of the last instruction that moved the program counter, see
+    /// [`ExecutionTrap::jump_source`].
+    pub jump_source: Option<u32>,
+    /// Bus cycle of the fetch.
+    pub cycle: u128,
+}
+
+impl fmt::Display for MisalignedPc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "fetching {} code from misaligned 0x{:08X} (cycle {})",
+            if self.thumb { "Thumb" } else { "ARM" },
+            self.address,
+            self.cycle
+        )?;
+
+        match self.jump_source {
+            Some(source) => write!(f, ", jumped from 0x{source:08X}"),
+            None => write!(f, ", jump source unknown"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::memory_map::{IE, IRQ_HANDLER_ADDRESS};
+
+    #[test]
+    fn regions() {
+        assert_eq!(NonExecutableRegion::from_address(0x0800_0000), None);
+        assert_eq!(NonExecutableRegion::from_address(IRQ_HANDLER_ADDRESS), None);
+        assert_eq!(NonExecutableRegion::from_address(0x0600_0000), None);
+        assert_eq!(
+            NonExecutableRegion::from_address(IE.get()),
+            Some(NonExecutableRegion::Io)
+        );
+        assert_eq!(
+            NonExecutableRegion::from_address(0x0500_0000),
+            Some(NonExecutableRegion::Palette)
+        );
+        assert_eq!(
+            NonExecutableRegion::from_address(0x0700_03FE),
+            Some(NonExecutableRegion::Oam)
+        );
+    }
+
+    #[test]
+    fn display() {
+        let trap = ExecutionTrap {
+            address: 0x0500_0000,
+            region: NonExecutableRegion::Palette,
+            jump_source: Some(0x0800_01A4),
+            cycle: 42,
+        };
+
+        assert_eq!(
+            trap.to_string(),
+            "executing from palette RAM at 0x05000000 (cycle 42), jumped from 0x080001A4"
+        );
+    }
+
+    #[test]
+    fn display_misaligned_pc() {
+        let misaligned = MisalignedPc {
+            address: 0x0800_0102,
+            thumb: false,
+            jump_source: None,
+            cycle: 7,
+        };
+
+        assert_eq!(
+            misaligned.to_string(),
+            "fetching ARM code from misaligned 0x08000102 (cycle 7), jump source unknown"
+        );
+    }
+}
@@ -1,8 +1,10 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use crate::bitwise::Bits;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum OperandKind {
     Immediate,
     Register,
@@ -19,7 +21,8 @@ impl From<bool> for OperandKind {
 }
 
 /// Operation to perform in the Move Compare Add Subtract Immediate instruction.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Operation {
     Mov,
     Cmp,
@@ -50,7 +53,8 @@ impl From<u16> for Operation {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ShiftKind {
     Lsl,
     Lsr,
@@ -94,7 +98,8 @@ impl std::fmt::Display for ShiftKind {
 }
 
 /// There two different kind of write or read for memory.
-#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ReadWriteKind {
     /// Word is a u32 value for ARM mode and u16 for Thumb mode.
     #[default]
@@ -120,7 +125,8 @@ impl From<u32> for ReadWriteKind {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum LoadStoreKind {
     Store,
     Load,
@@ -145,7 +151,8 @@ impl std::fmt::Display for LoadStoreKind {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Indexing {
     /// Add offset after transfer.
     Post,
@@ -164,7 +171,8 @@ impl From<bool> for Indexing {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Offsetting {
     /// Subtract the offset from base.
     Down,
@@ -183,8 +191,23 @@ impl From<bool> for Offsetting {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum HalfwordDataTransferOffsetKind {
     Immediate { offset: u32 },
     Register { register: u32 },
 }
+
+impl From<u32> for HalfwordDataTransferOffsetKind {
+    fn from(op_code: u32) -> Self {
+        if op_code.get_bit(22) {
+            Self::Immediate {
+                offset: (op_code.get_bits(8..=11) << 4) | op_code.get_bits(0..=3),
+            }
+        } else {
+            Self::Register {
+                register: op_code.get_bits(0..=3),
+            }
+        }
+    }
+}
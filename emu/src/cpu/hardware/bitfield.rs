@@ -0,0 +1,189 @@
+//! Typed I/O registers: a newtype over the raw value with an accessor for every field.
+//!
+//! The bus reads and writes them by byte, the components and the debuggers by field
+//! name, so the bit positions are written once in the [`bitfield!`] invocation.
+
+use std::ops::RangeInclusive;
+
+/// A field of a register, as listed in its `FIELDS` for debuggers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub bits: RangeInclusive<u8>,
+}
+
+impl Field {
+    /// Value of the field in the raw `register`.
+    #[must_use]
+    pub const fn value(&self, register: u32) -> u32 {
+        let width = *self.bits.end() - *self.bits.start() + 1;
+        let mask = (1_u64 << width) - 1;
+
+        ((register as u64 >> *self.bits.start()) & mask) as u32
+    }
+}
+
+/// Declares a register as a newtype over its raw value. Every field gets a getter, a
+/// `bool` for a single bit and the raw type for a range, and a setter when one is named
+/// after the getter:
+///
+/// ```ignore
+/// bitfield! {
+///     pub struct DispStat(u16) {
+///         vblank_flag, set_vblank_flag: 0;
+///         vcount_setting: 8..=15;
+///     }
+/// }
+/// ```
+macro_rules! bitfield {
+    (
+        $(#[$attr:meta])*
+        pub struct $name:ident($ty:ty) {
+            $(
+                $(#[$field_attr:meta])*
+                $getter:ident $(, $setter:ident)?: $low:literal $(..= $high:literal)?;
+            )*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        #[cfg_attr(feature = "serde", serde(transparent))]
+        pub struct $name(pub $ty);
+
+        impl $name {
+            pub const FIELDS: &'static [$crate::cpu::hardware::bitfield::Field] = &[
+                $(
+                    $crate::cpu::hardware::bitfield::Field {
+                        name: stringify!($getter),
+                        bits: $low..=$crate::cpu::hardware::bitfield::bitfield!(
+                            @high $low $(, $high)?
+                        ),
+                    },
+                )*
+            ];
+
+            $(
+                $crate::cpu::hardware::bitfield::bitfield!(
+                    @field $(#[$field_attr])* $ty, $getter $(, $setter)?, $low $(, $high)?
+                );
+            )*
+
+            #[must_use]
+            pub const fn get_byte(self, index: u8) -> u8 {
+                self.0.to_le_bytes()[index as usize]
+            }
+
+            pub const fn set_byte(&mut self, index: u8, value: u8) {
+                let mut bytes = self.0.to_le_bytes();
+                bytes[index as usize] = value;
+                self.0 = <$ty>::from_le_bytes(bytes);
+            }
+        }
+
+        impl From<$ty> for $name {
+            fn from(value: $ty) -> Self {
+                Self(value)
+            }
+        }
+
+        impl From<$name> for $ty {
+            fn from(register: $name) -> Self {
+                register.0
+            }
+        }
+    };
+
+    (@high $low:literal) => {
+        $low
+    };
+    (@high $low:literal, $high:literal) => {
+        $high
+    };
+
+    (@field $(#[$attr:meta])* $ty:ty, $getter:ident, $bit:literal) => {
+        $(#[$attr])*
+        #[must_use]
+        pub const fn $getter(self) -> bool {
+            self.0 & (1 << $bit) != 0
+        }
+    };
+    (@field $(#[$attr:meta])* $ty:ty, $getter:ident, $setter:ident, $bit:literal) => {
+        $crate::cpu::hardware::bitfield::bitfield!(@field $(#[$attr])* $ty, $getter, $bit);
+
+        pub const fn $setter(&mut self, value: bool) {
+            if value {
+                self.0 |= 1 << $bit;
+            } else {
+                self.0 &= !(1 << $bit);
+            }
+        }
+    };
+    (@field $(#[$attr:meta])* $ty:ty, $getter:ident, $low:literal, $high:literal) => {
+        $(#[$attr])*
+        #[must_use]
+        pub const fn $getter(self) -> $ty {
+            (self.0 >> $low) & ((1 << ($high - $low + 1)) - 1)
+        }
+    };
+    (@field $(#[$attr:meta])* $ty:ty, $getter:ident, $setter:ident, $low:literal, $high:literal) => {
+        $crate::cpu::hardware::bitfield::bitfield!(@field $(#[$attr])* $ty, $getter, $low, $high);
+
+        pub const fn $setter(&mut self, value: $ty) {
+            let mask = ((1 << ($high - $low + 1)) - 1) << $low;
+            self.0 = (self.0 & !mask) | ((value << $low) & mask);
+        }
+    };
+}
+
+pub(crate) use bitfield;
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    bitfield! {
+        pub struct Example(u16) {
+            flag, set_flag: 0;
+            read_only: 3;
+            mode, set_mode: 4..=6;
+            high: 12..=15;
+        }
+    }
+
+    #[test]
+    fn fields() {
+        let mut register = Example(0b1011_0000_0101_1000);
+        assert!(!register.flag());
+        assert!(register.read_only());
+        assert_eq!(register.mode(), 0b101);
+        assert_eq!(register.high(), 0b1011);
+
+        register.set_flag(true);
+        register.set_mode(0b1010);
+        assert_eq!(register.0, 0b1011_0000_0010_1001);
+    }
+
+    #[test]
+    fn bytes() {
+        let mut register = Example(0x1234);
+        assert_eq!(register.get_byte(0), 0x34);
+        assert_eq!(register.get_byte(1), 0x12);
+
+        register.set_byte(1, 0xAB);
+        assert_eq!(u16::from(register), 0xAB34);
+    }
+
+    #[test]
+    fn introspection() {
+        let names = Example::FIELDS
+            .iter()
+            .map(|field| field.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["flag", "read_only", "mode", "high"]);
+
+        assert_eq!(Example::FIELDS[2].bits, 4..=6);
+        assert_eq!(Example::FIELDS[2].value(0b1011_0000_0101_1000), 0b101);
+        assert_eq!(Example::FIELDS[3].value(0xF000), 0xF);
+    }
+}
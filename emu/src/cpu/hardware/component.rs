@@ -0,0 +1,69 @@
+//! Common interface of the hardware plugged on the bus.
+//!
+//! The bus owns every component, routes the I/O registers accesses to the one mapped at
+//! the address, steps them together and stores each of them in its own save-state
+//! section.
+
+use crate::bitwise::Bits;
+
+use super::interrupt_control::IrqType;
+
+/// What happened while stepping a component.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StepOutput {
+    /// Interrupts to request, one bit per source as in IF.
+    pub interrupts: u16,
+    /// The LCD just entered Vblank.
+    pub entered_vblank: bool,
+    /// The LCD just entered Hblank.
+    pub entered_hblank: bool,
+    /// A serial transfer just completed.
+    pub serial_transfer_completed: bool,
+}
+
+impl StepOutput {
+    pub fn request_interrupt(&mut self, irq_type: IrqType) {
+        self.interrupts.set_bit(irq_type.get_idx_in_if(), true);
+    }
+
+    pub const fn merge(&mut self, other: Self) {
+        self.interrupts |= other.interrupts;
+        self.entered_vblank |= other.entered_vblank;
+        self.entered_hblank |= other.entered_hblank;
+        self.serial_transfer_completed |= other.serial_transfer_completed;
+    }
+}
+
+pub trait HardwareComponent {
+    /// Goes back to the power-on state. Handles shared with the frontend and its settings
+    /// are kept.
+    fn reset(&mut self);
+
+    /// Advances the component by `cycles` CPU cycles.
+    fn step(&mut self, cycles: u32) -> StepOutput {
+        let _ = cycles;
+
+        StepOutput::default()
+    }
+
+    /// Reads the I/O register byte at `address`, `None` if no register is mapped there.
+    fn on_read(&self, address: usize) -> Option<u8>;
+
+    /// Writes the I/O register byte at `address`, `false` if no register is mapped there.
+    fn on_write(&mut self, address: usize, value: u8) -> bool;
+
+    /// Content of the component's save-state section.
+    ///
+    /// # Errors
+    /// It fails if the component can't be serialized.
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> bincode::Result<Vec<u8>>;
+
+    /// Restores the component from its save-state section, keeping what isn't part of the
+    /// state (see [`Self::reset`]).
+    ///
+    /// # Errors
+    /// It fails if `data` isn't a valid section, the component is untouched in this case.
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()>;
+}
@@ -0,0 +1,226 @@
+//! The debug output registers of mGBA, which homebrew built with its logging helpers
+//! print through: the game unlocks them, writes a string in the buffer and sends it with
+//! a level.
+//!
+//! They aren't part of the hardware: games only find them once unlocked, and the
+//! unlocking isn't part of the save-states.
+
+use std::fmt;
+
+use logger::log;
+
+use crate::bitwise::Bits;
+
+/// Where the game writes the string to print, up to 256 bytes.
+const BUFFER_START: u32 = 0x04FF_F600;
+/// Written with the level and the send bit to print the buffer.
+const FLAGS: u32 = 0x04FF_F700;
+/// Written with [`UNLOCK`], reads [`UNLOCKED`] once done.
+const ENABLE: u32 = 0x04FF_F780;
+
+const UNLOCK: u16 = 0xC0DE;
+const UNLOCKED: u16 = 0x1DEA;
+
+/// Bit of the flags printing the buffer.
+const SEND: u8 = 8;
+
+const BUFFER_SIZE: usize = 0x100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugLevel {
+    Fatal,
+    Error,
+    Warning,
+    Info,
+    Debug,
+}
+
+impl DebugLevel {
+    /// Level of the low bits of the flags, the unknown ones being the most verbose.
+    const fn from_flags(flags: u16) -> Self {
+        match flags & 0b111 {
+            0 => Self::Fatal,
+            1 => Self::Error,
+            2 => Self::Warning,
+            3 => Self::Info,
+            _ => Self::Debug,
+        }
+    }
+}
+
+impl fmt::Display for DebugLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Fatal => "FATAL",
+            Self::Error => "ERROR",
+            Self::Warning => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+        })
+    }
+}
+
+/// A string printed by the game.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugMessage {
+    pub level: DebugLevel,
+    pub text: String,
+}
+
+impl fmt::Display for DebugMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.level, self.text)
+    }
+}
+
+/// Receives the messages printed, see [`Bus::set_debug_output`](crate::bus::Bus::set_debug_output).
+pub type DebugOutput = Box<dyn FnMut(&DebugMessage) + Send>;
+
+pub struct DebugPrint {
+    enable: u16,
+    flags: u16,
+    buffer: [u8; BUFFER_SIZE],
+    output: Option<DebugOutput>,
+}
+
+impl Default for DebugPrint {
+    fn default() -> Self {
+        Self {
+            enable: 0,
+            flags: 0,
+            buffer: [0; BUFFER_SIZE],
+            output: None,
+        }
+    }
+}
+
+impl DebugPrint {
+    /// Whether `address` is one of the registers, unlocked or not.
+    #[must_use]
+    pub const fn contains(address: u32) -> bool {
+        matches!(address, BUFFER_START..=0x04FF_F7FF)
+    }
+
+    const fn unlocked(&self) -> bool {
+        self.enable == UNLOCK
+    }
+
+    pub fn set_output(&mut self, output: Option<DebugOutput>) {
+        self.output = output;
+    }
+
+    /// Locks the registers again, the output is kept.
+    pub fn reset(&mut self) {
+        *self = Self {
+            output: self.output.take(),
+            ..Self::default()
+        };
+    }
+
+    /// Byte at `address`, `None` where nothing is mapped: everywhere until unlocked.
+    #[must_use]
+    pub fn read(&self, address: u32) -> Option<u8> {
+        match address {
+            ENABLE | 0x04FF_F781 if self.unlocked() => {
+                Some(UNLOCKED.get_byte((address - ENABLE) as u8))
+            }
+            _ if !self.unlocked() => None,
+            BUFFER_START..FLAGS => Some(self.buffer[(address - BUFFER_START) as usize]),
+            FLAGS | 0x04FF_F701 => Some(self.flags.get_byte((address - FLAGS) as u8)),
+            _ => None,
+        }
+    }
+
+    /// Writes the byte at `address`, `false` where nothing is mapped.
+    pub fn write(&mut self, address: u32, value: u8) -> bool {
+        match address {
+            ENABLE | 0x04FF_F781 => {
+                self.enable.set_byte((address - ENABLE) as u8, value);
+            }
+            _ if !self.unlocked() => return false,
+            BUFFER_START..FLAGS => self.buffer[(address - BUFFER_START) as usize] = value,
+            FLAGS => self.flags.set_byte(0, value),
+            0x04FF_F701 => {
+                self.flags.set_byte(1, value);
+                if self.flags.get_bit(SEND) {
+                    self.print();
+                }
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Sends the buffer to the log and to the output, then clears it.
+    fn print(&mut self) {
+        let end = self
+            .buffer
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(BUFFER_SIZE);
+        let message = DebugMessage {
+            level: DebugLevel::from_flags(self.flags),
+            text: String::from_utf8_lossy(&self.buffer[..end]).into_owned(),
+        };
+        self.buffer.fill(0);
+        self.flags = 0;
+
+        log(format!("debug print {message}"));
+        if let Some(output) = &mut self.output {
+            output(&message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn write_half_word(debug: &mut DebugPrint, address: u32, value: u16) -> bool {
+        debug.write(address, value as u8) & debug.write(address + 1, (value >> 8) as u8)
+    }
+
+    #[test]
+    fn locked_until_unlocked() {
+        let mut debug = DebugPrint::default();
+        assert!(!debug.write(BUFFER_START, b'x'));
+        assert_eq!(debug.read(ENABLE), None);
+
+        assert!(write_half_word(&mut debug, ENABLE, UNLOCK));
+        assert_eq!(debug.read(ENABLE), Some(0xEA));
+        assert_eq!(debug.read(ENABLE + 1), Some(0x1D));
+        assert!(debug.write(BUFFER_START, b'x'));
+        assert_eq!(debug.read(BUFFER_START), Some(b'x'));
+    }
+
+    #[test]
+    fn send_prints_and_clears_the_buffer() {
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let mut debug = DebugPrint::default();
+        let output = Arc::clone(&printed);
+        debug.set_output(Some(Box::new(move |message: &DebugMessage| {
+            output.lock().unwrap().push(message.clone());
+        })));
+
+        write_half_word(&mut debug, ENABLE, UNLOCK);
+        for (offset, byte) in b"hello".iter().enumerate() {
+            debug.write(BUFFER_START + offset as u32, *byte);
+        }
+        write_half_word(&mut debug, FLAGS, 0x100 | 2);
+
+        assert_eq!(
+            *printed.lock().unwrap(),
+            [DebugMessage {
+                level: DebugLevel::Warning,
+                text: "hello".into(),
+            }]
+        );
+        assert_eq!(debug.read(BUFFER_START), Some(0));
+        assert_eq!(printed.lock().unwrap()[0].to_string(), "[WARN] hello");
+    }
+}
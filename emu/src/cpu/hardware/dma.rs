@@ -1,14 +1,339 @@
+//! The four DMA channels.
+//!
+//! A channel latches its addresses and count when it's enabled, then the bus runs its
+//! transfer when it starts: right away, at Vblank or at the Hblank of the visible lines.
+//! The special timings, the sound FIFOs and the video capture, aren't emulated: those
+//! channels never start.
+
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Serialize, Deserialize)]
+use crate::bitwise::Bits;
+use crate::cpu::hardware::bitfield::bitfield;
+use crate::cpu::hardware::component::HardwareComponent;
+
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Registers {
     pub source_address: u32,
     pub destination_address: u32,
     pub word_count: u16,
-    pub control: u16,
+    pub control: DmaCnt,
+}
+
+bitfield! {
+    /// `DMAxCNT_H`
+    pub struct DmaCnt(u16) {
+        /// Increment, decrement, fixed, increment and reload.
+        destination_control: 5..=6;
+        /// Increment, decrement, fixed, 3 is prohibited.
+        source_control: 7..=8;
+        /// Started again at every Vblank or Hblank, with the count reloaded.
+        repeat: 9;
+        /// 32 bits units, 16 bits otherwise.
+        word_transfer: 10;
+        gamepak_drq: 11;
+        /// Immediately, Vblank, Hblank, special.
+        start_timing: 12..=13;
+        irq_enable: 14;
+        enabled, set_enabled: 15;
+    }
+}
+
+/// Values of [`DmaCnt::start_timing`].
+pub const START_IMMEDIATELY: u16 = 0;
+pub const START_AT_VBLANK: u16 = 1;
+pub const START_AT_HBLANK: u16 = 2;
+
+/// Value of [`DmaCnt::destination_control`] reloading the destination on repeats.
+const INCREMENT_RELOAD: u16 = 3;
+
+/// Where a channel is in its transfers, latched from its registers when it's enabled:
+/// the repeats continue from there.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Transfer {
+    pub source: u32,
+    pub destination: u32,
+    /// Units to copy, of 16 or 32 bits.
+    pub count: u32,
+}
+
+/// Who had the bus during a frame, in cycles, to see how much the transfers steal from
+/// the CPU when a game slows down.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BusMasters {
+    /// Cycles left to the CPU, halted or not.
+    pub cpu: u64,
+    /// Cycles of the transfers of each channel, DMA0 first.
+    pub dma: [u64; 4],
+    /// Longest the CPU waited, for transfers run back to back.
+    pub longest_stall: u64,
 }
 
-#[derive(Default, Serialize, Deserialize)]
+impl BusMasters {
+    /// Counts a cycle for `channel`, the CPU when it's `None`.
+    pub(crate) const fn count(&mut self, channel: Option<usize>) {
+        match channel {
+            Some(index) => self.dma[index] += 1,
+            None => self.cpu += 1,
+        }
+    }
+
+    /// The cycles of all the channels.
+    #[must_use]
+    pub fn dma_total(&self) -> u64 {
+        self.dma.iter().sum()
+    }
+}
+
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Dma {
     pub channels: [Registers; 4],
+    transfers: [Transfer; 4],
+    /// Channels started and waiting for the bus, a bit each.
+    pending: u8,
+}
+
+impl Dma {
+    /// Starts the enabled channels waiting for `timing`, see [`DmaCnt::start_timing`].
+    pub fn trigger(&mut self, timing: u16) {
+        for (index, channel) in self.channels.iter().enumerate() {
+            if channel.control.enabled() && channel.control.start_timing() == timing {
+                self.pending |= 1 << index;
+            }
+        }
+    }
+
+    #[must_use]
+    pub const fn is_pending(&self) -> bool {
+        self.pending != 0
+    }
+
+    /// The started channel with the highest priority, the lowest one, and its transfer.
+    pub const fn take_pending(&mut self) -> Option<(usize, DmaCnt, Transfer)> {
+        if self.pending == 0 {
+            return None;
+        }
+
+        let index = self.pending.trailing_zeros() as usize;
+        self.pending &= !(1 << index);
+
+        Some((index, self.channels[index].control, self.transfers[index]))
+    }
+
+    /// Ends the transfer of channel `index`, at the addresses it reached. Repeated ones
+    /// wait for their next start with the count reloaded, the others are disabled.
+    /// Returns whether it requests an interrupt.
+    pub fn complete(&mut self, index: usize, source: u32, destination: u32) -> bool {
+        let channel = &mut self.channels[index];
+        let transfer = &mut self.transfers[index];
+        transfer.source = source;
+        transfer.destination = destination;
+
+        if channel.control.repeat() && channel.control.start_timing() != START_IMMEDIATELY {
+            transfer.count = unit_count(index, channel.word_count);
+            if channel.control.destination_control() == INCREMENT_RELOAD {
+                transfer.destination = channel.destination_address;
+            }
+        } else {
+            channel.control.set_enabled(false);
+        }
+
+        channel.control.irq_enable()
+    }
+
+    fn enable(&mut self, index: usize) {
+        let channel = &self.channels[index];
+        self.transfers[index] = Transfer {
+            source: channel.source_address,
+            destination: channel.destination_address,
+            count: unit_count(index, channel.word_count),
+        };
+
+        if channel.control.start_timing() == START_IMMEDIATELY {
+            self.pending |= 1 << index;
+        }
+    }
+
+    /// Upgrades a section saved before the transfers were latched, when only the
+    /// registers were: the enabled channels start over from them.
+    #[cfg(feature = "serde")]
+    pub(crate) fn upgrade_registers_only(data: &[u8]) -> bincode::Result<Vec<u8>> {
+        let mut dma = Self {
+            channels: bincode::deserialize(data)?,
+            ..Self::default()
+        };
+        for index in 0..dma.channels.len() {
+            if dma.channels[index].control.enabled() {
+                dma.enable(index);
+            }
+        }
+        dma.pending = 0;
+
+        bincode::serialize(&dma)
+    }
+}
+
+/// Units a transfer of channel `index` copies: 0 stands for the largest count.
+fn unit_count(index: usize, word_count: u16) -> u32 {
+    match (word_count, index) {
+        (0, 3) => 0x1_0000,
+        (0, _) => 0x4000,
+        (count, _) => u32::from(count),
+    }
+}
+
+impl HardwareComponent for Dma {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn on_read(&self, address: usize) -> Option<u8> {
+        let read_dma_bank = |channel: &Registers, address: usize| match address {
+            0..=9 => panic!("Reading a write-only DMA I/O register"),
+            10 => channel.control.get_byte(0),
+            11 => channel.control.get_byte(1),
+            _ => panic!("DMA channel read address is out of bound"),
+        };
+
+        let value = match address {
+            0x0400_00B0..=0x0400_00BB => read_dma_bank(&self.channels[0], address - 0x0400_00B0),
+            0x0400_00BC..=0x0400_00C7 => read_dma_bank(&self.channels[1], address - 0x0400_00BC),
+            0x0400_00C8..=0x0400_00D3 => read_dma_bank(&self.channels[2], address - 0x0400_00C8),
+            0x0400_00D4..=0x0400_00DF => read_dma_bank(&self.channels[3], address - 0x0400_00D4),
+            0x0400_00E0..=0x0400_00FF => return None,
+            _ => panic!("DMA read address is out of bound"),
+        };
+
+        Some(value)
+    }
+
+    fn on_write(&mut self, address: usize, value: u8) -> bool {
+        let write_dma_bank = |channel: &mut Registers, address: usize, value: u8| match address {
+            0 => channel.source_address.set_byte(0, value),
+            1 => channel.source_address.set_byte(1, value),
+            2 => channel.source_address.set_byte(2, value),
+            3 => channel.source_address.set_byte(3, value),
+            4 => channel.destination_address.set_byte(0, value),
+            5 => channel.destination_address.set_byte(1, value),
+            6 => channel.destination_address.set_byte(2, value),
+            7 => channel.destination_address.set_byte(3, value),
+            8 => channel.word_count.set_byte(0, value),
+            9 => channel.word_count.set_byte(1, value),
+            10 => channel.control.set_byte(0, value),
+            11 => channel.control.set_byte(1, value),
+            _ => panic!("DMA channel write-address is out of bound"),
+        };
+
+        let (index, offset) = match address {
+            0x0400_00B0..=0x0400_00DF => {
+                ((address - 0x0400_00B0) / 12, (address - 0x0400_00B0) % 12)
+            }
+            0x0400_00E0..=0x0400_00FF => return false,
+            _ => panic!("Not implemented write memory address: {address:x}"),
+        };
+
+        let was_enabled = self.channels[index].control.enabled();
+        write_dma_bank(&mut self.channels[index], offset, value);
+        match (was_enabled, self.channels[index].control.enabled()) {
+            (false, true) => self.enable(index),
+            (true, false) => self.pending &= !(1 << index),
+            _ => {}
+        }
+
+        true
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> bincode::Result<Vec<u8>> {
+        bincode::serialize(self)
+    }
+
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        *self = bincode::deserialize(data)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn write_half_word(dma: &mut Dma, address: usize, value: u16) {
+        for (offset, byte) in value.to_le_bytes().into_iter().enumerate() {
+            dma.on_write(address + offset, byte);
+        }
+    }
+
+    #[test]
+    fn repeated_hblank_transfers() {
+        let mut dma = Dma::default();
+        dma.channels[1].source_address = 0x0200_0000;
+        dma.channels[1].destination_address = 0x0400_0010;
+        write_half_word(&mut dma, 0x0400_00C4, 2);
+        write_half_word(
+            &mut dma,
+            0x0400_00C6,
+            1 << 15 | 1 << 14 | 1 << 9 | 0b10 << 12,
+        );
+        assert!(!dma.is_pending());
+
+        dma.trigger(START_AT_VBLANK);
+        assert!(!dma.is_pending());
+
+        dma.trigger(START_AT_HBLANK);
+        let (index, _, transfer) = dma.take_pending().unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(
+            transfer,
+            Transfer {
+                source: 0x0200_0000,
+                destination: 0x0400_0010,
+                count: 2,
+            }
+        );
+        assert!(dma.take_pending().is_none());
+
+        // The next repeat continues from the source reached, the channel stays enabled.
+        assert!(dma.complete(1, 0x0200_0004, 0x0400_0010));
+        assert!(dma.channels[1].control.enabled());
+        dma.trigger(START_AT_HBLANK);
+        assert_eq!(dma.take_pending().unwrap().2.source, 0x0200_0004);
+    }
+
+    #[test]
+    fn immediate_transfers_run_once() {
+        let mut dma = Dma::default();
+        write_half_word(&mut dma, 0x0400_00DE, 1 << 15 | 1 << 9);
+
+        let (index, _, transfer) = dma.take_pending().unwrap();
+        assert_eq!(index, 3);
+        assert_eq!(transfer.count, 0x1_0000);
+
+        assert!(!dma.complete(3, 0, 0));
+        assert!(!dma.channels[3].control.enabled());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn upgrades_the_registers_only() {
+        let mut old = Dma::default();
+        old.channels[0].source_address = 0x0300_0000;
+        old.channels[0].word_count = 8;
+        old.channels[0].control.set_enabled(true);
+        let data = bincode::serialize(&old.channels).unwrap();
+
+        let mut dma = Dma::default();
+        dma.load_state(&Dma::upgrade_registers_only(&data).unwrap())
+            .unwrap();
+        assert!(!dma.is_pending());
+        assert_eq!(dma.transfers[0].source, 0x0300_0000);
+        assert_eq!(dma.transfers[0].count, 8);
+    }
 }
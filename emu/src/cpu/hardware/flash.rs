@@ -0,0 +1,386 @@
+//! Flash backup memory of the cartridge, mapped from 0x0E000000 to 0x0E00FFFF.
+//!
+//! Every operation is unlocked by writing 0xAA at 0x5555 and 0x55 at 0x2AAA, then the
+//! command at 0x5555. Erasing also needs the 0x80 command followed by a second unlock
+//! sequence.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const BANK_SIZE: usize = 0x1_0000;
+const SECTOR_SIZE: usize = 0x1000;
+
+/// Cycles of the 16.78MHz CPU in a microsecond.
+const CYCLES_PER_US: u32 = 16;
+
+/// Durations in the range of the datasheets of the chips found in cartridges.
+const PROGRAM_CYCLES: u32 = 20 * CYCLES_PER_US;
+const SECTOR_ERASE_CYCLES: u32 = 25_000 * CYCLES_PER_US;
+const CHIP_ERASE_CYCLES: u32 = 40_000 * CYCLES_PER_US;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FlashSize {
+    /// 64 `KBytes`, reported as a Panasonic chip.
+    Flash64K,
+    /// 128 `KBytes` in two banks, reported as a Sanyo chip.
+    Flash128K,
+}
+
+impl FlashSize {
+    /// Looks for the ID string the Nintendo save library leaves in the ROM.
+    #[must_use]
+    pub fn detect(rom: &[u8]) -> Option<Self> {
+        let contains = |id: &[u8]| rom.windows(id.len()).any(|window| window == id);
+
+        if contains(b"FLASH1M_V") {
+            Some(Self::Flash128K)
+        } else if contains(b"FLASH_V") || contains(b"FLASH512_V") {
+            Some(Self::Flash64K)
+        } else {
+            None
+        }
+    }
+
+    const fn id(self) -> [u8; 2] {
+        match self {
+            Self::Flash64K => [0x32, 0x1B],
+            Self::Flash128K => [0x62, 0x13],
+        }
+    }
+
+    const fn banks(self) -> usize {
+        match self {
+            Self::Flash64K => 1,
+            Self::Flash128K => 2,
+        }
+    }
+}
+
+/// How long the chip stays busy after an erase or a program command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FlashTiming {
+    /// Every operation completes right away.
+    #[default]
+    Instant,
+    /// Operations take as long as on real chips: until they complete reads return the
+    /// status byte, and some games rely on polling it.
+    Accurate,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum State {
+    #[default]
+    Ready,
+    /// 0xAA was written at 0x5555.
+    Unlocking,
+    /// 0x55 was written at 0x2AAA, the next write is a command.
+    Unlocked,
+    /// The next write is programmed.
+    Program,
+    /// The next write at 0x0000 selects the bank.
+    BankSwitch,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Flash {
+    size: FlashSize,
+    data: Vec<u8>,
+    bank: usize,
+    state: State,
+    /// Reads return the manufacturer and device ID.
+    id_mode: bool,
+    /// The 0x80 command was received, erase commands are accepted.
+    erase_armed: bool,
+    /// Cycles left before the last operation completes.
+    busy_cycles: u32,
+    /// Address written by the last operation, polled while the chip is busy.
+    busy_address: usize,
+
+    /// Not part of the state, it's a setting of the frontend.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    timing: FlashTiming,
+    /// The content changed since the last [`Self::take_written`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    written: bool,
+}
+
+impl Flash {
+    #[must_use]
+    pub fn new(size: FlashSize) -> Self {
+        Self {
+            size,
+            data: vec![0xFF; BANK_SIZE * size.banks()],
+            bank: 0,
+            state: State::Ready,
+            id_mode: false,
+            erase_armed: false,
+            busy_cycles: 0,
+            busy_address: 0,
+            timing: FlashTiming::default(),
+            written: false,
+        }
+    }
+
+    /// Goes back to reading the array, the content is kept unless `erase`.
+    pub fn reset(&mut self, erase: bool) {
+        self.bank = 0;
+        self.state = State::Ready;
+        self.id_mode = false;
+        self.erase_armed = false;
+        self.busy_cycles = 0;
+
+        if erase {
+            self.data.fill(0xFF);
+        }
+    }
+
+    #[must_use]
+    pub const fn size(&self) -> FlashSize {
+        self.size
+    }
+
+    /// Content of the whole chip, banks one after the other.
+    #[must_use]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Replaces the content of the chip, such as with an imported save. `data` is cut
+    /// or filled with erased bytes to the size of the chip.
+    pub fn load_data(&mut self, data: &[u8]) {
+        let len = data.len().min(self.data.len());
+
+        self.data.fill(0xFF);
+        self.data[..len].copy_from_slice(&data[..len]);
+    }
+
+    #[must_use]
+    pub const fn timing(&self) -> FlashTiming {
+        self.timing
+    }
+
+    pub const fn set_timing(&mut self, timing: FlashTiming) {
+        self.timing = timing;
+    }
+
+    /// Whether the game programmed or erased something since the last call.
+    pub const fn take_written(&mut self) -> bool {
+        std::mem::replace(&mut self.written, false)
+    }
+
+    #[must_use]
+    pub const fn is_busy(&self) -> bool {
+        self.busy_cycles > 0
+    }
+
+    pub const fn step(&mut self) {
+        self.busy_cycles = self.busy_cycles.saturating_sub(1);
+    }
+
+    /// `address` is relative to 0x0E000000.
+    #[must_use]
+    pub fn read(&self, address: usize) -> u8 {
+        let address = address & 0xFFFF;
+
+        if self.id_mode && address < 2 {
+            return self.size.id()[address];
+        }
+
+        let value = self.data[self.bank * BANK_SIZE + address];
+
+        if self.is_busy() && self.bank * BANK_SIZE + address == self.busy_address {
+            // Data polling: bit 7 reads inverted until the operation completes.
+            value ^ 0x80
+        } else {
+            value
+        }
+    }
+
+    /// `address` is relative to 0x0E000000.
+    pub fn write(&mut self, address: usize, value: u8) {
+        let address = address & 0xFFFF;
+
+        if self.is_busy() {
+            return;
+        }
+
+        self.state = match (self.state, address, value) {
+            (State::Program, _, _) => {
+                self.program(address, value);
+                State::Ready
+            }
+            (State::BankSwitch, 0x0000, _) => {
+                self.bank = usize::from(value) % self.size.banks();
+                State::Ready
+            }
+            (State::Ready, 0x5555, 0xAA) => State::Unlocking,
+            (State::Unlocking, 0x2AAA, 0x55) => State::Unlocked,
+            (State::Unlocked, _, 0x30) if self.erase_armed => {
+                self.erase_armed = false;
+                self.erase_sector(address);
+                State::Ready
+            }
+            (State::Unlocked, 0x5555, command) => self.command(command),
+            _ => {
+                self.erase_armed = false;
+                State::Ready
+            }
+        };
+    }
+
+    fn command(&mut self, command: u8) -> State {
+        let erase_armed = std::mem::take(&mut self.erase_armed);
+
+        match command {
+            0x90 => self.id_mode = true,
+            0xF0 => self.id_mode = false,
+            0x80 => self.erase_armed = true,
+            0x10 if erase_armed => self.erase_chip(),
+            0xA0 => return State::Program,
+            0xB0 if self.size == FlashSize::Flash128K => return State::BankSwitch,
+            _ => {}
+        }
+
+        State::Ready
+    }
+
+    fn program(&mut self, address: usize, value: u8) {
+        let address = self.bank * BANK_SIZE + address;
+
+        // Programming only clears bits, erasing sets them back.
+        self.data[address] &= value;
+        self.set_busy(address, PROGRAM_CYCLES);
+    }
+
+    fn erase_sector(&mut self, address: usize) {
+        let start = self.bank * BANK_SIZE + (address & !(SECTOR_SIZE - 1));
+
+        self.data[start..start + SECTOR_SIZE].fill(0xFF);
+        self.set_busy(start, SECTOR_ERASE_CYCLES);
+    }
+
+    fn erase_chip(&mut self) {
+        self.data.fill(0xFF);
+        self.set_busy(self.bank * BANK_SIZE, CHIP_ERASE_CYCLES);
+    }
+
+    const fn set_busy(&mut self, address: usize, cycles: u32) {
+        self.written = true;
+        self.busy_address = address;
+        self.busy_cycles = match self.timing {
+            FlashTiming::Instant => 0,
+            FlashTiming::Accurate => cycles,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn send_command(flash: &mut Flash, command: u8) {
+        flash.write(0x5555, 0xAA);
+        flash.write(0x2AAA, 0x55);
+        flash.write(0x5555, command);
+    }
+
+    fn program(flash: &mut Flash, address: usize, value: u8) {
+        send_command(flash, 0xA0);
+        flash.write(address, value);
+    }
+
+    fn erase_sector(flash: &mut Flash, address: usize) {
+        send_command(flash, 0x80);
+        flash.write(0x5555, 0xAA);
+        flash.write(0x2AAA, 0x55);
+        flash.write(address, 0x30);
+    }
+
+    #[test]
+    fn detect_size() {
+        assert_eq!(
+            FlashSize::detect(b"..FLASH_V124.."),
+            Some(FlashSize::Flash64K)
+        );
+        assert_eq!(
+            FlashSize::detect(b"..FLASH512_V131.."),
+            Some(FlashSize::Flash64K)
+        );
+        assert_eq!(
+            FlashSize::detect(b"..FLASH1M_V103.."),
+            Some(FlashSize::Flash128K)
+        );
+        assert_eq!(FlashSize::detect(b"..SRAM_V113.."), None);
+    }
+
+    #[test]
+    fn id_mode() {
+        let mut flash = Flash::new(FlashSize::Flash128K);
+
+        send_command(&mut flash, 0x90);
+        assert_eq!([flash.read(0), flash.read(1)], [0x62, 0x13]);
+
+        send_command(&mut flash, 0xF0);
+        assert_eq!([flash.read(0), flash.read(1)], [0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn program_erase_and_banks() {
+        let mut flash = Flash::new(FlashSize::Flash128K);
+
+        program(&mut flash, 0x1234, 0x5A);
+        // A write without the unlock sequence is ignored.
+        flash.write(0x1235, 0x00);
+        assert_eq!(flash.read(0x1234), 0x5A);
+        assert_eq!(flash.read(0x1235), 0xFF);
+
+        send_command(&mut flash, 0xB0);
+        flash.write(0, 1);
+        program(&mut flash, 0x1234, 0xA5);
+        assert_eq!(flash.read(0x1234), 0xA5);
+        assert_eq!(flash.data()[0x1234], 0x5A);
+
+        erase_sector(&mut flash, 0x1000);
+        assert_eq!(flash.read(0x1234), 0xFF);
+        assert_eq!(flash.data()[0x1234], 0x5A);
+
+        // 0x10 alone doesn't erase the chip.
+        send_command(&mut flash, 0x10);
+        assert_eq!(flash.data()[0x1234], 0x5A);
+
+        send_command(&mut flash, 0x80);
+        send_command(&mut flash, 0x10);
+        assert!(flash.data().iter().all(|value| *value == 0xFF));
+    }
+
+    #[test]
+    fn accurate_timing_polls_status() {
+        let mut flash = Flash::new(FlashSize::Flash64K);
+        flash.set_timing(FlashTiming::Accurate);
+
+        program(&mut flash, 0x0010, 0x12);
+        assert!(flash.is_busy());
+        assert_eq!(flash.read(0x0010), 0x92);
+
+        // Commands are ignored while the chip is busy.
+        program(&mut flash, 0x0011, 0x34);
+        for _ in 0..PROGRAM_CYCLES {
+            flash.step();
+        }
+        assert!(!flash.is_busy());
+        assert_eq!(flash.read(0x0010), 0x12);
+        assert_eq!(flash.read(0x0011), 0xFF);
+
+        erase_sector(&mut flash, 0x0000);
+        let mut polls = 0;
+        while flash.read(0x0000) != 0xFF {
+            flash.step();
+            polls += 1;
+        }
+        assert_eq!(polls, SECTOR_ERASE_CYCLES);
+    }
+}
@@ -0,0 +1,171 @@
+//! The GPIO port of the cartridge: 4 lines the game drives or reads through 3 registers
+//! in the ROM, wired to the real-time clock, the solar sensor or the rumble of the games
+//! that have them.
+//!
+//! - 0x080000C4: the level of the lines.
+//! - 0x080000C6: their direction, 1 where the GBA drives the line.
+//! - 0x080000C8: 1 makes the registers readable, the ROM answers there otherwise.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::component::StepOutput;
+use super::peripheral::CartridgePeripheral;
+
+const DATA: u32 = 0xC4;
+const DIRECTION: u32 = 0xC6;
+const CONTROL: u32 = 0xC8;
+
+/// The 4 lines of the port.
+const LINES: u8 = 0b1111;
+
+/// Hardware wired to the lines of the [`GpioPort`].
+pub trait GpioDevice: Send {
+    /// Identifies the device in the save-states, unique on the port.
+    fn name(&self) -> &'static str;
+
+    /// The game wrote the lines: `lines` has the level of the ones it drives, see
+    /// [`GpioPort`], the others read as 0.
+    fn write(&mut self, lines: u8);
+
+    /// Levels the device drives, only read on the lines the game doesn't drive.
+    fn read(&self) -> u8;
+
+    /// Advances the device by `cycles` CPU cycles.
+    fn step(&mut self, cycles: u32) -> StepOutput {
+        let _ = cycles;
+
+        StepOutput::default()
+    }
+
+    /// Goes back to the power-on state, see [`CartridgePeripheral::reset`].
+    fn reset(&mut self, hard: bool) {
+        let _ = hard;
+    }
+
+    /// # Errors
+    /// It fails if the device can't be serialized.
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> bincode::Result<Vec<u8>>;
+
+    /// # Errors
+    /// It fails if `data` isn't valid, the device is untouched in this case.
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()>;
+}
+
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Registers {
+    data: u8,
+    direction: u8,
+    readable: bool,
+}
+
+/// The port, with the devices wired to it. Plug it with
+/// [`Gba::add_cartridge_peripheral`](crate::gba::Gba::add_cartridge_peripheral).
+#[derive(Default)]
+pub struct GpioPort {
+    registers: Registers,
+    devices: Vec<Box<dyn GpioDevice>>,
+}
+
+impl GpioPort {
+    #[must_use]
+    pub fn new(devices: Vec<Box<dyn GpioDevice>>) -> Self {
+        Self {
+            registers: Registers::default(),
+            devices,
+        }
+    }
+
+    /// Levels of the lines as the game reads them.
+    fn lines(&self) -> u8 {
+        let driven = self.registers.direction;
+        let devices = self
+            .devices
+            .iter()
+            .fold(0, |lines, device| lines | device.read());
+
+        (self.registers.data & driven | devices & !driven) & LINES
+    }
+}
+
+impl CartridgePeripheral for GpioPort {
+    fn name(&self) -> &'static str {
+        "gpio"
+    }
+
+    fn claims(&self, address: u32) -> bool {
+        (0x0800_0000..0x0E00_0000).contains(&address)
+            && (DATA..CONTROL + 2).contains(&(address & 0x01FF_FFFF))
+    }
+
+    fn read(&self, address: u32) -> Option<u8> {
+        if !self.registers.readable {
+            return None;
+        }
+
+        Some(match address & 0x01FF_FFFF {
+            DATA => self.lines(),
+            DIRECTION => self.registers.direction,
+            CONTROL => u8::from(self.registers.readable),
+            _ => 0,
+        })
+    }
+
+    fn write(&mut self, address: u32, value: u8) {
+        match address & 0x01FF_FFFF {
+            DATA => {
+                self.registers.data = value & LINES;
+                let lines = self.registers.data & self.registers.direction;
+                for device in &mut self.devices {
+                    device.write(lines);
+                }
+            }
+            DIRECTION => self.registers.direction = value & LINES,
+            CONTROL => self.registers.readable = value & 1 != 0,
+            _ => {}
+        }
+    }
+
+    fn step(&mut self, cycles: u32) -> StepOutput {
+        let mut output = StepOutput::default();
+        for device in &mut self.devices {
+            output.merge(device.step(cycles));
+        }
+
+        output
+    }
+
+    fn reset(&mut self, hard: bool) {
+        self.registers = Registers::default();
+        for device in &mut self.devices {
+            device.reset(hard);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> bincode::Result<Vec<u8>> {
+        let devices = self
+            .devices
+            .iter()
+            .map(|device| Ok((device.name(), device.save_state()?)))
+            .collect::<bincode::Result<Vec<_>>>()?;
+
+        bincode::serialize(&(self.registers, devices))
+    }
+
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        let (registers, devices): (Registers, Vec<(String, Vec<u8>)>) = bincode::deserialize(data)?;
+        for (name, state) in devices {
+            if let Some(device) = self.devices.iter_mut().find(|device| device.name() == name) {
+                device.load_state(&state)?;
+            }
+        }
+        self.registers = registers;
+
+        Ok(())
+    }
+}
@@ -1,13 +1,21 @@
 use std::collections::HashMap;
 
 use logger::log;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use crate::bitwise::Bits;
+use crate::memory_map::{
+    RomAddr, BIOS_SIZE, EWRAM_SIZE, EWRAM_START, IWRAM_SIZE, IWRAM_START, ROM_END, ROM_START,
+    SRAM_START,
+};
 
+use super::component::StepOutput;
+use super::flash::{Flash, FlashSize};
 use super::get_unmasked_address;
+use super::peripheral::CartridgePeripheral;
 
-#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct InternalMemory {
     /// From 0x00000000 to 0x00003FFF (16 `KBytes`).
     bios_system_rom: Vec<u8>,
@@ -26,8 +34,20 @@ pub struct InternalMemory {
     // 0C000000-0DFFFFFF Game Pak ROM/FlashROM (max 32MB) - Wait State 2
     // 0E000000-0E00FFFF Game Pak SRAM (max 64 KBytes) - 8bit Bus width
     // 0E010000-0FFFFFFF Not used
+    ///
+    /// Not part of the state: the cartridge is already loaded when restoring a
+    /// save-state, and 32MB carts would make every save-state as big.
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub rom: Vec<u8>,
 
+    /// From 0x0E000000 to 0x0E00FFFF, when the cartridge saves to Flash.
+    pub flash: Option<Flash>,
+
+    /// Add-on hardware of the cartridge, answering before the ROM and the save memory at
+    /// the addresses it claims. Saved in its own section, see [`Self::save_peripherals`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) peripherals: Vec<Box<dyn CartridgePeripheral>>,
+
     /// From 0x00004000 to `0x01FF_FFFF`.
     /// From 0x10000000 to `0xFFFF_FFFF`.
     unused_region: HashMap<usize, u8>,
@@ -35,22 +55,119 @@ pub struct InternalMemory {
 
 impl Default for InternalMemory {
     fn default() -> Self {
-        Self::new([0_u8; 0x0000_4000], vec![])
+        Self::new([0_u8; BIOS_SIZE], vec![])
     }
 }
 
 impl InternalMemory {
     #[must_use]
-    pub fn new(bios: [u8; 0x0000_4000], rom: Vec<u8>) -> Self {
+    pub fn new(bios: [u8; BIOS_SIZE], rom: Vec<u8>) -> Self {
         Self {
             bios_system_rom: bios.to_vec(),
-            working_ram: vec![0; 0x0004_0000],
-            working_iram: vec![0; 0x0000_8000],
+            working_ram: vec![0; EWRAM_SIZE],
+            working_iram: vec![0; IWRAM_SIZE],
+            flash: FlashSize::detect(&rom).map(Flash::new),
             rom,
+            peripherals: Vec::new(),
             unused_region: HashMap::new(),
         }
     }
 
+    /// The BIOS image.
+    #[cfg(feature = "serde")]
+    pub(crate) fn bios(&self) -> &[u8] {
+        &self.bios_system_rom
+    }
+
+    /// EWRAM and IWRAM, with the address they start at.
+    pub(crate) fn work_rams(&self) -> [(u32, &[u8]); 2] {
+        [
+            (EWRAM_START, &self.working_ram),
+            (IWRAM_START, &self.working_iram),
+        ]
+    }
+
+    /// Bytes taken by the BIOS, the work RAM and the writes to unused addresses.
+    pub(crate) fn system_bytes(&self) -> usize {
+        self.bios_system_rom.len()
+            + self.working_ram.len()
+            + self.working_iram.len()
+            + self.unused_region.len() * std::mem::size_of::<(usize, u8)>()
+    }
+
+    /// Plugs `peripheral` in the cartridge. The peripherals added first answer first
+    /// when several claim the same address.
+    pub fn add_peripheral(&mut self, peripheral: Box<dyn CartridgePeripheral>) {
+        self.peripherals.push(peripheral);
+    }
+
+    fn read_peripheral(&self, address: usize) -> Option<u8> {
+        let address = address as u32;
+        self.peripherals
+            .iter()
+            .filter(|peripheral| peripheral.claims(address))
+            .find_map(|peripheral| peripheral.read(address))
+    }
+
+    /// Returns `false` if no peripheral claims `address`.
+    fn write_peripheral(&mut self, address: usize, value: u8) -> bool {
+        let address = address as u32;
+        self.peripherals
+            .iter_mut()
+            .find(|peripheral| peripheral.claims(address))
+            .map(|peripheral| peripheral.write(address, value))
+            .is_some()
+    }
+
+    /// The state of every peripheral, by name.
+    ///
+    /// # Errors
+    /// It fails if one of the peripherals can't be serialized.
+    #[cfg(feature = "serde")]
+    pub(crate) fn save_peripherals(&self) -> bincode::Result<Vec<u8>> {
+        let states = self
+            .peripherals
+            .iter()
+            .map(|peripheral| Ok((peripheral.name(), peripheral.save_state()?)))
+            .collect::<bincode::Result<Vec<_>>>()?;
+
+        bincode::serialize(&states)
+    }
+
+    /// Restores the peripherals found in `data`. The others, plugged after the state was
+    /// saved, are left as they are.
+    ///
+    /// # Errors
+    /// It fails if `data` or the state of a peripheral isn't valid.
+    #[cfg(feature = "serde")]
+    pub(crate) fn load_peripherals(&mut self, data: &[u8]) -> bincode::Result<()> {
+        let states: Vec<(String, Vec<u8>)> = bincode::deserialize(data)?;
+        for (name, state) in states {
+            if let Some(peripheral) = self
+                .peripherals
+                .iter_mut()
+                .find(|peripheral| peripheral.name() == name)
+            {
+                peripheral.load_state(&state)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The `width` bytes of the aligned unit at `address` in the cartridge ROM, read at
+    /// once when nothing else answers there: no peripheral is plugged and the ROM holds
+    /// them. Streaming games read the ROM a unit at a time, byte reads would go through
+    /// the whole address decoding for each of them.
+    pub(crate) fn rom_unit(&self, address: u32, width: usize) -> Option<&[u8]> {
+        if !(ROM_START..=ROM_END).contains(&address) || !self.peripherals.is_empty() {
+            return None;
+        }
+
+        let offset = RomAddr::new(address).offset();
+        self.rom.get(offset..offset + width)
+    }
+
     fn read_rom(&self, address: usize) -> u8 {
         if address < self.rom.len() {
             self.rom[address]
@@ -78,6 +195,37 @@ impl InternalMemory {
             (((address >> 1) & 0xFFFF) as u16).get_byte((address & 0b1) as u8)
         }
     }
+
+    /// Keeps the BIOS and the cartridge. The work RAMs and the Flash content are only
+    /// cleared by a `hard` reset, a soft one leaves them as the game wrote them.
+    pub fn reset(&mut self, hard: bool) {
+        if hard {
+            self.working_ram.fill(0);
+            self.working_iram.fill(0);
+            self.unused_region.clear();
+        }
+
+        if let Some(flash) = &mut self.flash {
+            flash.reset(hard);
+        }
+        for peripheral in &mut self.peripherals {
+            peripheral.reset(hard);
+        }
+    }
+
+    /// Advances the backup chip and the peripherals by a cycle.
+    pub fn step(&mut self) -> StepOutput {
+        if let Some(flash) = &mut self.flash {
+            flash.step();
+        }
+
+        let mut output = StepOutput::default();
+        for peripheral in &mut self.peripherals {
+            output.merge(peripheral.step(1));
+        }
+
+        output
+    }
 }
 
 impl InternalMemory {
@@ -86,17 +234,22 @@ impl InternalMemory {
         match address {
             0x0000_0000..=0x0000_3FFF => self.bios_system_rom[address],
             0x0200_0000..=0x02FF_FFFF => {
-                self.working_ram
-                    [get_unmasked_address(address, 0x00FF_0000, 0xFF00_FFFF, 16, 4) - 0x0200_0000]
+                self.working_ram[get_unmasked_address(address, 0x00FF_0000, 0xFF00_FFFF, 16, 4)
+                    - EWRAM_START as usize]
             }
             0x0300_0000..=0x03FF_FFFF => {
-                self.working_iram
-                    [get_unmasked_address(address, 0x00FF_F000, 0xFF00_0FFF, 12, 8) - 0x0300_0000]
+                self.working_iram[get_unmasked_address(address, 0x00FF_F000, 0xFF00_0FFF, 12, 8)
+                    - IWRAM_START as usize]
             }
-            0x0800_0000..=0x09FF_FFFF => self.read_rom(address - 0x0800_0000),
-            0x0A00_0000..=0x0BFF_FFFF => self.read_rom(address - 0x0A00_0000),
-            0x0C00_0000..=0x0DFF_FFFF => self.read_rom(address - 0x0C00_0000),
-            0x0E00_0000..=0x0E00_FFFF => unimplemented!("SRAM region is unimplemented"),
+            0x0800_0000..=0x0DFF_FFFF => self
+                .read_peripheral(address)
+                .unwrap_or_else(|| self.read_rom(RomAddr::new(address as u32).offset())),
+            0x0E00_0000..=0x0E00_FFFF => self.read_peripheral(address).unwrap_or_else(|| {
+                self.flash.as_ref().map_or_else(
+                    || unimplemented!("SRAM region is unimplemented"),
+                    |flash| flash.read(address - SRAM_START as usize),
+                )
+            }),
             0x0000_4000..=0x01FF_FFFF | 0x1000_0000..=0xFFFF_FFFF => {
                 log(format!("read on unused memory {address:x}"));
                 self.unused_region.get(&address).map_or(0, |v| *v)
@@ -108,22 +261,25 @@ impl InternalMemory {
     pub fn write_at(&mut self, address: usize, value: u8) {
         match address {
             0x0000_0000..=0x0000_3FFF => self.bios_system_rom[address] = value,
-            0x0200_0000..=0x0203_FFFF => self.working_ram[address - 0x0200_0000] = value,
+            0x0200_0000..=0x0203_FFFF => self.working_ram[address - EWRAM_START as usize] = value,
             // Mirror
             0x0204_0000..=0x02FF_FFFF => {
                 self.working_ram[get_unmasked_address(address, 0x00FF_0000, 0xFF00_FFFF, 16, 4)
-                    - 0x0200_0000] = value;
+                    - EWRAM_START as usize] = value;
             }
-            0x0300_0000..=0x0300_7FFF => self.working_iram[address - 0x0300_0000] = value,
+            0x0300_0000..=0x0300_7FFF => self.working_iram[address - IWRAM_START as usize] = value,
             // Mirror
             0x0300_8000..=0x03FF_FFFF => {
                 self.working_iram[get_unmasked_address(address, 0x00FF_F000, 0xFF00_0FFF, 12, 8)
-                    - 0x0300_0000] = value;
-            }
-            0x0800_0000..=0x0FFF_FFFF => {
-                // TODO: this should be split
-                self.rom[address - 0x0800_0000] = value;
+                    - IWRAM_START as usize] = value;
             }
+            0x0800_0000..=0x0E00_FFFF if self.write_peripheral(address, value) => {}
+            0x0E00_0000..=0x0E00_FFFF => match &mut self.flash {
+                Some(flash) => flash.write(address - SRAM_START as usize, value),
+                None => unimplemented!("SRAM region is unimplemented"),
+            },
+            // The ROM and its mirrors are read-only.
+            0x0800_0000..=0x0DFF_FFFF => log(format!("write on ROM {address:x}")),
             _ => unimplemented!("Unimplemented memory region {address:x}."),
         }
     }
@@ -132,6 +288,7 @@ impl InternalMemory {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cpu::hardware::interrupt_control::IrqType;
 
     #[test]
     fn test_write_work_ram() {
@@ -189,6 +346,30 @@ mod tests {
         assert_eq!(im.read_at(address), 0xFF);
     }
 
+    #[test]
+    fn rom_units_are_read_at_once() {
+        let mut im = InternalMemory {
+            rom: (0..=0xFF).collect(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            im.rom_unit(0x0800_0010, 4),
+            Some(&[0x10, 0x11, 0x12, 0x13][..])
+        );
+        // The mirrors too, but not past the end of the ROM.
+        assert_eq!(im.rom_unit(0x0C00_00FE, 2), Some(&[0xFE, 0xFF][..]));
+        assert_eq!(im.rom_unit(0x0800_0100, 2), None);
+        assert_eq!(im.rom_unit(0x0300_0000, 4), None);
+
+        // The peripherals may answer anywhere.
+        im.add_peripheral(Box::new(Port {
+            value: 0,
+            readable: false,
+        }));
+        assert_eq!(im.rom_unit(0x0800_0010, 4), None);
+    }
+
     #[test]
     fn test_mirror_3ffffxx() {
         let mut im = InternalMemory::default();
@@ -246,4 +427,124 @@ mod tests {
         im.write_at(0x03FFF1FF, 1);
         assert_eq!(im.working_iram[0x71FF], 1);
     }
+
+    #[test]
+    fn reset_clears_ram_and_flash_only_when_hard() {
+        let mut rom = vec![0; 0x200];
+        rom[0x100..0x107].copy_from_slice(b"FLASH_V");
+        let mut im = InternalMemory::new([0; 0x4000], rom);
+
+        im.write_at(0x0200_0010, 1);
+        im.write_at(0x0300_0010, 2);
+        for (address, value) in [(0x5555, 0xAA), (0x2AAA, 0x55), (0x5555, 0xA0), (0x10, 3)] {
+            im.write_at(0x0E00_0000 + address, value);
+        }
+
+        im.reset(false);
+        assert_eq!(im.read_at(0x0200_0010), 1);
+        assert_eq!(im.read_at(0x0300_0010), 2);
+        assert_eq!(im.read_at(0x0E00_0010), 3);
+        assert_eq!(im.read_at(0x0800_0100), b'F');
+
+        im.reset(true);
+        assert_eq!(im.read_at(0x0200_0010), 0);
+        assert_eq!(im.read_at(0x0300_0010), 0);
+        assert_eq!(im.read_at(0x0E00_0010), 0xFF);
+        assert_eq!(im.read_at(0x0800_0100), b'F');
+    }
+
+    /// A 16-bit port at 0x080000C4, readable only after writing 1 at 0x080000C6. It
+    /// requests the Game Pak interrupt when its value reaches 0.
+    struct Port {
+        value: u16,
+        readable: bool,
+    }
+
+    impl CartridgePeripheral for Port {
+        fn name(&self) -> &'static str {
+            "port"
+        }
+
+        fn claims(&self, address: u32) -> bool {
+            matches!(address, 0x0800_00C4..=0x0800_00C7)
+        }
+
+        fn read(&self, address: u32) -> Option<u8> {
+            match address {
+                0x0800_00C4 if self.readable => Some(self.value.get_byte(0)),
+                0x0800_00C5 if self.readable => Some(self.value.get_byte(1)),
+                _ => None,
+            }
+        }
+
+        fn write(&mut self, address: u32, value: u8) {
+            match address {
+                0x0800_00C4 => self.value.set_byte(0, value),
+                0x0800_00C5 => self.value.set_byte(1, value),
+                0x0800_00C6 => self.readable = value == 1,
+                _ => {}
+            }
+        }
+
+        fn step(&mut self, cycles: u32) -> StepOutput {
+            let mut output = StepOutput::default();
+            if self.value > 0 {
+                self.value = self.value.saturating_sub(cycles as u16);
+                if self.value == 0 {
+                    output.request_interrupt(IrqType::Gamepak);
+                }
+            }
+
+            output
+        }
+
+        fn reset(&mut self, _hard: bool) {
+            self.value = 0;
+        }
+
+        #[cfg(feature = "serde")]
+        fn save_state(&self) -> bincode::Result<Vec<u8>> {
+            bincode::serialize(&self.value)
+        }
+
+        #[cfg(feature = "serde")]
+        fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+            self.value = bincode::deserialize(data)?;
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn peripherals_answer_at_their_addresses() {
+        let mut im = InternalMemory {
+            rom: (0..=0xFF).collect(),
+            ..Default::default()
+        };
+        im.add_peripheral(Box::new(Port {
+            value: 0,
+            readable: false,
+        }));
+
+        im.write_at(0x0800_00C4, 2);
+        // Not readable yet: the ROM answers.
+        assert_eq!(im.read_at(0x0800_00C4), 0xC4);
+        im.write_at(0x0800_00C6, 1);
+        assert_eq!(im.read_at(0x0800_00C4), 2);
+        assert_eq!(im.read_at(0x0800_00C8), 0xC8);
+
+        assert_eq!(im.step().interrupts, 0);
+        let interrupts = im.step().interrupts;
+        assert_eq!(interrupts, 1 << IrqType::Gamepak.get_idx_in_if());
+
+        #[cfg(feature = "serde")]
+        {
+            im.write_at(0x0800_00C4, 7);
+            let state = im.save_peripherals().unwrap();
+            im.reset(false);
+            assert_eq!(im.read_at(0x0800_00C4), 0);
+            im.load_peripherals(&state).unwrap();
+            assert_eq!(im.read_at(0x0800_00C4), 7);
+        }
+    }
 }
@@ -1,7 +1,11 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use vecfixed::VecFixed;
 
-#[derive(Serialize, Deserialize)]
+use crate::bitwise::Bits;
+use crate::cpu::hardware::component::{HardwareComponent, StepOutput};
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct InterruptControl {
     pub interrupt_enable: u16,
     // It is a ring buffer since when we write to this register, the value will reach the CPU
@@ -16,6 +20,8 @@ pub struct InterruptControl {
     pub power_down_control: u8,
     pub purpose_unknown: u8,
     pub internal_memory_control: u32,
+    /// Set by a write to `HALTCNT`, cleared when an enabled interrupt is requested.
+    pub halted: bool,
 }
 
 impl Default for InterruptControl {
@@ -29,6 +35,149 @@ impl Default for InterruptControl {
             power_down_control: 0,
             purpose_unknown: 0,
             internal_memory_control: 0,
+            halted: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IrqType {
+    VBlank,
+    HBlank,
+    VCount,
+    Timer0,
+    Timer1,
+    Timer2,
+    Timer3,
+    Serial,
+    Dma0,
+    Dma1,
+    Dma2,
+    Dma3,
+    Keypad,
+    Gamepak,
+}
+
+impl IrqType {
+    /// Returns the index of the corresponding `IrqType` inside the Interrupt Request Flag register
+    #[must_use]
+    pub const fn get_idx_in_if(self) -> u8 {
+        match self {
+            Self::VBlank => 0,
+            Self::HBlank => 1,
+            Self::VCount => 2,
+            Self::Timer0 => 3,
+            Self::Timer1 => 4,
+            Self::Timer2 => 5,
+            Self::Timer3 => 6,
+            Self::Serial => 7,
+            Self::Dma0 => 8,
+            Self::Dma1 => 9,
+            Self::Dma2 => 10,
+            Self::Dma3 => 11,
+            Self::Keypad => 12,
+            Self::Gamepak => 13,
+        }
+    }
+}
+
+impl HardwareComponent for InterruptControl {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn step(&mut self, cycles: u32) -> StepOutput {
+        for _ in 0..cycles {
+            let val = *self.interrupt_request.back().unwrap();
+            self.interrupt_request.push(val);
+        }
+
+        StepOutput::default()
+    }
+
+    fn on_read(&self, address: usize) -> Option<u8> {
+        let value = match address {
+            0x0400_0200 => self.interrupt_enable.get_byte(0),
+            0x0400_0201 => self.interrupt_enable.get_byte(1),
+            0x0400_0202 => self.interrupt_request.front().unwrap_or(&0).get_byte(0),
+            0x0400_0203 => self.interrupt_request.front().unwrap_or(&0).get_byte(1),
+            0x0400_0204 => self.wait_state_control.get_byte(0),
+            0x0400_0205 => self.wait_state_control.get_byte(1),
+            0x0400_0208 => self.interrupt_master_enable.get_byte(0),
+            0x0400_0209 => self.interrupt_master_enable.get_byte(1),
+            0x0400_0300 => self.post_boot_flag.get_byte(0),
+            // HALTCNT is write-only.
+            0x0400_0301 => 0,
+            0x0400_0410 => self.purpose_unknown.get_byte(0),
+            0x0400_0206
+            | 0x0400_0207
+            | 0x0400_020A..=0x0400_02FF
+            | 0x0400_0302..=0x0400_040F
+            | 0x0400_0411 => return None,
+            _ => match address & 0b111 {
+                0x800 => self.internal_memory_control.get_byte(0),
+                0x801 => self.internal_memory_control.get_byte(1),
+                0x802 => self.internal_memory_control.get_byte(2),
+                0x803 => self.internal_memory_control.get_byte(3),
+                _ => return None,
+            },
+        };
+
+        Some(value)
+    }
+
+    fn on_write(&mut self, address: usize, value: u8) -> bool {
+        match address {
+            0x0400_0200 => self.interrupt_enable.set_byte(0, value),
+            0x0400_0201 => self.interrupt_enable.set_byte(1, value),
+            0x0400_0202 => {
+                let current_val = self.interrupt_request.back_mut().unwrap();
+
+                *current_val &= !u16::from(value);
+            }
+            0x0400_0203 => {
+                let current_val = self.interrupt_request.back_mut().unwrap();
+
+                *current_val &= !(u16::from(value) << 8);
+            }
+            0x0400_0204 => self.wait_state_control.set_byte(0, value),
+            0x0400_0205 => self.wait_state_control.set_byte(1, value),
+            0x0400_0208 => self.interrupt_master_enable.set_byte(0, value),
+            0x0400_0209 => self.interrupt_master_enable.set_byte(1, value),
+            0x0400_0300 => self.post_boot_flag.set_byte(0, value),
+            0x0400_0301 => {
+                // Stop (bit 7) also switches the LCD and the sound off, it's treated as
+                // Halt: they keep running while the CPU waits.
+                self.power_down_control.set_byte(0, value);
+                self.halted = true;
+            }
+            0x0400_0410 => self.purpose_unknown.set_byte(0, value),
+            0x0400_0206
+            | 0x0400_0207
+            | 0x0400_020A..=0x0400_02FF
+            | 0x0400_0302..=0x0400_040F
+            | 0x0400_0411 => return false,
+            _ => match address & 0b111 {
+                0x800 => self.internal_memory_control.set_byte(0, value),
+                0x801 => self.internal_memory_control.set_byte(1, value),
+                0x802 => self.internal_memory_control.set_byte(2, value),
+                0x803 => self.internal_memory_control.set_byte(3, value),
+                _ => return false,
+            },
         }
+
+        true
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> bincode::Result<Vec<u8>> {
+        bincode::serialize(self)
+    }
+
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        *self = bincode::deserialize(data)?;
+
+        Ok(())
     }
 }
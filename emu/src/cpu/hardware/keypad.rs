@@ -1,7 +1,364 @@
+use std::sync::{
+    atomic::{AtomicU16, Ordering},
+    Arc,
+};
+
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Serialize, Deserialize)]
+use crate::bitwise::Bits;
+use crate::cpu::hardware::component::{HardwareComponent, StepOutput};
+use crate::cpu::hardware::interrupt_control::IrqType;
+
+/// Buttons in the order of their bit in KEYINPUT.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    A,
+    B,
+    Select,
+    Start,
+    Right,
+    Left,
+    Up,
+    Down,
+    R,
+    L,
+}
+
+const ALL_KEYS: u16 = 0x03FF;
+
+impl Key {
+    pub const ALL: [Self; 10] = [
+        Self::A,
+        Self::B,
+        Self::Select,
+        Self::Start,
+        Self::Right,
+        Self::Left,
+        Self::Up,
+        Self::Down,
+        Self::R,
+        Self::L,
+    ];
+
+    pub(crate) const fn mask(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+/// Buttons held by the host, shared with the frontend.
+///
+/// The frontend updates it from its own thread whenever an input event arrives, the
+/// emulator reads it according to its [`KeySampling`].
+#[derive(Clone, Default)]
+pub struct KeypadInput(Arc<AtomicU16>);
+
+impl KeypadInput {
+    pub fn set_pressed(&self, key: Key, pressed: bool) {
+        if pressed {
+            self.0.fetch_or(key.mask(), Ordering::Release);
+        } else {
+            self.0.fetch_and(!key.mask(), Ordering::Release);
+        }
+    }
+
+    /// Replaces the whole state, one bit per [`Key`], set when pressed.
+    pub fn set_pressed_keys(&self, keys: u16) {
+        self.0.store(keys & ALL_KEYS, Ordering::Release);
+    }
+
+    #[must_use]
+    pub fn pressed_keys(&self) -> u16 {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Value of KEYINPUT for the current state, buttons are active low.
+    fn key_input(&self) -> u16 {
+        !self.pressed_keys() & ALL_KEYS
+    }
+}
+
+/// When the game sees the host input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeySampling {
+    /// KEYINPUT is latched when entering Vblank, the game sees the same state for a whole
+    /// frame whatever the timing of the host events: runs are reproducible, as needed by
+    /// movies and netplay.
+    #[default]
+    Latched,
+    /// Every read of KEYINPUT returns the last host state, shaving up to a frame of
+    /// latency.
+    Immediate,
+}
+
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Keypad {
     pub key_input: u16,
     pub key_interrupt_control: u16,
+
+    /// Not part of the state, the frontend keeps the same handle when loading a
+    /// save-state.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) input: KeypadInput,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) sampling: KeySampling,
+    /// Not part of the state, see [`Self::set_forced_keys`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    forced: Option<u16>,
+}
+
+impl Keypad {
+    /// Handle to press and release the buttons from another thread.
+    #[must_use]
+    pub fn input(&self) -> KeypadInput {
+        self.input.clone()
+    }
+
+    pub const fn set_sampling(&mut self, sampling: KeySampling) {
+        self.sampling = sampling;
+    }
+
+    /// Value of KEYINPUT as seen by the game.
+    #[must_use]
+    pub fn read_key_input(&self) -> u16 {
+        match (self.sampling, self.forced) {
+            (KeySampling::Immediate, None) => self.input.key_input(),
+            _ => self.key_input,
+        }
+    }
+
+    /// Called when entering Vblank.
+    pub fn latch(&mut self) {
+        self.key_input = self
+            .forced
+            .map_or_else(|| self.input.key_input(), |keys| !keys & ALL_KEYS);
+    }
+
+    /// Latches `keys` in place of the host input, one bit per [`Key`] set when pressed,
+    /// until set back to `None`. Movies play this way: what the game sees can't depend
+    /// on the frontend, whatever the [`KeySampling`].
+    pub const fn set_forced_keys(&mut self, keys: Option<u16>) {
+        self.forced = keys;
+    }
+
+    /// Keys seen by the game since the last latch, one bit per [`Key`] set when pressed.
+    #[must_use]
+    pub const fn latched_keys(&self) -> u16 {
+        !self.key_input & ALL_KEYS
+    }
+
+    /// Whether KEYCNT requests the interrupt for the keys seen by the game.
+    ///
+    /// In AND mode (bit 15) every selected key has to be pressed in the same sample: with
+    /// [`KeySampling::Latched`], keys pressed one after the other between two Vblanks
+    /// only count if they are all still held at the latch, which keeps movies and
+    /// netplay in sync. Selecting no key in AND mode never requests the interrupt.
+    #[must_use]
+    pub fn interrupt_condition(&self) -> bool {
+        if !self.key_interrupt_control.get_bit(14) {
+            return false;
+        }
+
+        let selected = self.key_interrupt_control & ALL_KEYS;
+        let pressed = !self.read_key_input() & ALL_KEYS;
+
+        if self.key_interrupt_control.get_bit(15) {
+            selected != 0 && pressed & selected == selected
+        } else {
+            pressed & selected != 0
+        }
+    }
+}
+
+impl HardwareComponent for Keypad {
+    fn reset(&mut self) {
+        *self = Self {
+            input: std::mem::take(&mut self.input),
+            sampling: self.sampling,
+            forced: self.forced,
+            ..Self::default()
+        };
+    }
+
+    /// The interrupt is a level: it's requested again right after being acknowledged
+    /// as long as the condition holds, like on hardware.
+    fn step(&mut self, cycles: u32) -> StepOutput {
+        let _ = cycles;
+        let mut output = StepOutput::default();
+
+        if self.interrupt_condition() {
+            output.request_interrupt(IrqType::Keypad);
+        }
+
+        output
+    }
+
+    fn on_read(&self, address: usize) -> Option<u8> {
+        let value = match address {
+            0x0400_0130 => self.read_key_input().get_byte(0),
+            0x0400_0131 => self.read_key_input().get_byte(1),
+            0x0400_0132 => self.key_interrupt_control.get_byte(0),
+            0x0400_0133 => self.key_interrupt_control.get_byte(1),
+            _ => panic!("Keypad read address is out of bound"),
+        };
+
+        Some(value)
+    }
+
+    fn on_write(&mut self, address: usize, value: u8) -> bool {
+        match address {
+            // 0x0400_0130 and 0x0400_0131 Should be read-only but CPU bios writes it.
+            0x0400_0130 => self.key_input.set_byte(0, value),
+            0x0400_0131 => self.key_input.set_byte(1, value),
+            0x0400_0132 => self.key_interrupt_control.set_byte(0, value),
+            0x0400_0133 => self.key_interrupt_control.set_byte(1, value),
+            _ => panic!("Keypad write address is out of bound"),
+        }
+
+        true
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> bincode::Result<Vec<u8>> {
+        bincode::serialize(self)
+    }
+
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        let keypad: Self = bincode::deserialize(data)?;
+        self.key_input = keypad.key_input;
+        self.key_interrupt_control = keypad.key_interrupt_control;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn sampling() {
+        let mut keypad = Keypad::default();
+        let input = keypad.input();
+
+        keypad.latch();
+        assert_eq!(keypad.read_key_input(), 0x03FF);
+
+        input.set_pressed(Key::A, true);
+        input.set_pressed(Key::Up, true);
+        assert_eq!(input.pressed_keys(), 0b00_0100_0001);
+
+        // The game keeps seeing the state of the last Vblank.
+        assert_eq!(keypad.read_key_input(), 0x03FF);
+        keypad.latch();
+        assert_eq!(keypad.read_key_input(), 0x03BE);
+
+        keypad.set_sampling(KeySampling::Immediate);
+        input.set_pressed(Key::A, false);
+        assert_eq!(keypad.read_key_input(), 0x03BF);
+
+        input.set_pressed_keys(0xFFFF);
+        assert_eq!(keypad.read_key_input(), 0);
+    }
+
+    #[test]
+    fn forced_keys_replace_the_host_input() {
+        let mut keypad = Keypad::default();
+        keypad.set_sampling(KeySampling::Immediate);
+        keypad.input().set_pressed(Key::A, true);
+
+        keypad.set_forced_keys(Some(Key::B.mask()));
+        keypad.latch();
+        assert_eq!(keypad.read_key_input(), 0x03FD);
+        assert_eq!(keypad.latched_keys(), Key::B.mask());
+
+        keypad.set_forced_keys(None);
+        assert_eq!(keypad.read_key_input(), 0x03FE);
+    }
+
+    const IRQ_ENABLE: u16 = 1 << 14;
+    const AND_MODE: u16 = 1 << 15;
+
+    fn requests_interrupt(keypad: &mut Keypad) -> bool {
+        keypad.step(1).interrupts != 0
+    }
+
+    #[test]
+    fn interrupt_or_mode() {
+        let mut keypad = Keypad::default();
+        let input = keypad.input();
+        keypad.key_interrupt_control = IRQ_ENABLE | Key::A.mask() | Key::B.mask();
+
+        input.set_pressed(Key::Start, true);
+        keypad.latch();
+        assert!(!requests_interrupt(&mut keypad));
+
+        input.set_pressed(Key::B, true);
+        keypad.latch();
+        assert_eq!(keypad.step(1).interrupts, 1 << 12);
+
+        // Requested again as long as the key is held.
+        assert!(requests_interrupt(&mut keypad));
+
+        keypad.key_interrupt_control &= !IRQ_ENABLE;
+        assert!(!requests_interrupt(&mut keypad));
+    }
+
+    #[test]
+    fn interrupt_and_mode_needs_every_key_in_the_same_sample() {
+        let mut keypad = Keypad::default();
+        let input = keypad.input();
+        keypad.key_interrupt_control = IRQ_ENABLE | AND_MODE | Key::A.mask() | Key::B.mask();
+
+        // A then B, never held together at a latch.
+        input.set_pressed(Key::A, true);
+        keypad.latch();
+        assert!(!requests_interrupt(&mut keypad));
+        input.set_pressed(Key::A, false);
+        input.set_pressed(Key::B, true);
+        keypad.latch();
+        assert!(!requests_interrupt(&mut keypad));
+
+        // Held together between two latches but released before the second one.
+        input.set_pressed(Key::A, true);
+        assert!(!requests_interrupt(&mut keypad));
+        input.set_pressed(Key::A, false);
+        keypad.latch();
+        assert!(!requests_interrupt(&mut keypad));
+
+        // Other keys don't matter.
+        input.set_pressed_keys(Key::A.mask() | Key::B.mask() | Key::L.mask());
+        keypad.latch();
+        assert!(requests_interrupt(&mut keypad));
+    }
+
+    #[test]
+    fn interrupt_and_mode_immediate_sampling() {
+        let mut keypad = Keypad::default();
+        let input = keypad.input();
+        keypad.set_sampling(KeySampling::Immediate);
+        keypad.key_interrupt_control = IRQ_ENABLE | AND_MODE | Key::A.mask() | Key::B.mask();
+
+        input.set_pressed(Key::A, true);
+        assert!(!requests_interrupt(&mut keypad));
+        input.set_pressed(Key::B, true);
+        assert!(requests_interrupt(&mut keypad));
+        input.set_pressed(Key::A, false);
+        assert!(!requests_interrupt(&mut keypad));
+    }
+
+    #[test]
+    fn interrupt_and_mode_without_keys() {
+        let mut keypad = Keypad::default();
+        let input = keypad.input();
+        keypad.key_interrupt_control = IRQ_ENABLE | AND_MODE;
+
+        input.set_pressed_keys(ALL_KEYS);
+        keypad.latch();
+        assert!(!requests_interrupt(&mut keypad));
+    }
 }
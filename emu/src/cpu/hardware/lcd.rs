@@ -1,11 +1,23 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+use arc_swap::ArcSwap;
 use logger::log;
-use serde::Deserialize;
-use serde::Serialize;
-use serde_with::serde_as;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use crate::bitwise::Bits;
+use crate::config::ColorCorrection;
+use crate::cpu::hardware::component::{HardwareComponent, StepOutput};
+use crate::cpu::hardware::interrupt_control::IrqType;
 use crate::cpu::hardware::lcd::layers::Layer;
 
+use self::effects::{Dot, LayerId, WindowControl};
 use self::layers::layer_0::Layer0;
 use self::layers::layer_1::Layer1;
 use self::layers::layer_2::Layer2;
@@ -14,11 +26,12 @@ use self::layers::layer_obj::LayerObj;
 use self::memory::Memory;
 use self::registers::Registers;
 
+mod effects;
 mod layers;
 mod memory;
 mod object_attributes;
 mod point;
-mod registers;
+pub(crate) mod registers;
 
 /// GBA display width
 const LCD_WIDTH: usize = 240;
@@ -26,11 +39,15 @@ const LCD_WIDTH: usize = 240;
 /// GBA display height
 const LCD_HEIGHT: usize = 160;
 
+/// A dot takes 4 CPU cycles to get drawn
+const CYCLES_PER_DOT: u32 = 4;
+
 // Sprites are positioned inside a 512x256 size (x position is 9 bits and y position is 8 bits)
 /// World height
 const WORLD_HEIGHT: u16 = 256;
 
-#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Color(pub u16);
 
 impl Color {
@@ -62,9 +79,51 @@ impl Color {
     pub fn blue(&self) -> u8 {
         self.0.get_bits(10..=14) as u8
     }
+
+    /// The color with 8 bits per component, for the host screen.
+    #[must_use]
+    pub fn to_rgb8(self, correction: ColorCorrection) -> [u8; 3] {
+        let expand = |component: u8| (component << 3) | (component >> 2);
+
+        match correction {
+            ColorCorrection::Off => [
+                expand(self.red()),
+                expand(self.green()),
+                expand(self.blue()),
+            ],
+            ColorCorrection::Lcd => {
+                static LCD_COLORS: OnceLock<Vec<[u8; 3]>> = OnceLock::new();
+                let colors = LCD_COLORS
+                    .get_or_init(|| (0..0x8000).map(|color| lcd_color(Self(color))).collect());
+                colors[usize::from(self.0 & 0x7FFF)]
+            }
+        }
+    }
+}
+
+/// The color as the LCD shows it: with a gamma of 4, the components bleeding into each
+/// other, and dimmer. The weights are those of higan.
+#[allow(clippy::cast_sign_loss)]
+fn lcd_color(color: Color) -> [u8; 3] {
+    let linear = |component: u8| (f64::from(component) / 31.0).powi(4);
+    let (red, green, blue) = (
+        linear(color.red()),
+        linear(color.green()),
+        linear(color.blue()),
+    );
+    let output = |[for_red, for_green, for_blue]: [f64; 3]| {
+        let mixed = for_red.mul_add(red, for_green.mul_add(green, for_blue * blue)) / 255.0;
+        (mixed.powf(1.0 / 2.2) * 255.0 * 255.0 / 280.0).round() as u8
+    };
+
+    [
+        output([255.0, 50.0, 0.0]),
+        output([10.0, 230.0, 30.0]),
+        output([50.0, 10.0, 220.0]),
+    ]
 }
 
-#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 enum ObjMappingKind {
     TwoDimensional,
     OneDimensional,
@@ -80,23 +139,117 @@ impl From<bool> for ObjMappingKind {
     }
 }
 
-#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+#[derive(Copy, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct PixelInfo {
     color: Color,
     priority: u8,
+    /// OBJ in semi-transparent mode, alpha blended over the layer below.
+    semi_transparent: bool,
+}
+
+/// A whole picture as shown on the display.
+pub type Frame = [[Color; LCD_WIDTH]; LCD_HEIGHT];
+
+/// Last frame completed by the LCD, shared with the frontend.
+///
+/// The LCD swaps in a new frame when it enters Vblank, readers never lock the emulator
+/// and always get a complete frame, even when the emulation runs faster than the display.
+#[derive(Clone)]
+pub struct FrameOutput {
+    frame: Arc<ArcSwap<Frame>>,
+    published: Arc<AtomicU64>,
+}
+
+impl Default for FrameOutput {
+    fn default() -> Self {
+        let frame: Box<Frame> = vec![[Color::default(); LCD_WIDTH]; LCD_HEIGHT]
+            .into_boxed_slice()
+            .try_into()
+            .unwrap_or_else(|_| unreachable!());
+
+        Self {
+            frame: Arc::new(ArcSwap::new(Arc::from(frame))),
+            published: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl FrameOutput {
+    /// Returns the last completed frame.
+    #[must_use]
+    pub fn load(&self) -> Arc<Frame> {
+        self.frame.load_full()
+    }
+
+    /// Number of frames completed since the LCD was created.
+    #[must_use]
+    pub fn frame_count(&self) -> u64 {
+        self.published.load(Ordering::Acquire)
+    }
+
+    fn publish(&self, frame: &Frame) {
+        self.frame.store(Arc::from(Box::new(*frame)));
+        self.published.fetch_add(1, Ordering::Release);
+    }
+
+    /// Counts a frame that wasn't drawn, the last one drawn stays the output.
+    fn publish_skipped(&self) {
+        self.published.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// Where the LCD is in the picture: the frame counts from the creation of the LCD, the
+/// scanline goes up to 227 and the dot up to 307, Vblank and Hblank included.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RasterPosition {
+    pub frame: u64,
+    pub scanline: u16,
+    pub dot: u16,
 }
 
-#[serde_as]
-#[derive(Serialize, Deserialize)]
+impl fmt::Display for RasterPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "frame {}, line {}, dot {}",
+            self.frame, self.scanline, self.dot
+        )
+    }
+}
+
+/// Picture processing unit.
+///
+/// Everything but the frame output is part of the save-state, down to the dot being
+/// drawn, the cycles towards the next one and the OBJs cached for the scanline: a state
+/// saved mid-frame resumes drawing exactly where it stopped.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Lcd {
     pub(crate) registers: Registers,
     pub(crate) memory: Memory,
 
-    #[serde_as(as = "[[_; 240]; 160]")]
-    pub buffer: [[Color; LCD_WIDTH]; LCD_HEIGHT],
+    // Using Box here to avoid stack overflow when (de)serializing
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<Box<[[serde_with::Same; 240]; 160]>>")
+    )]
+    pub buffer: Box<[[Color; LCD_WIDTH]; LCD_HEIGHT]>,
+
+    /// Not part of the state, the same output is kept when loading a save-state.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) frame_output: FrameOutput,
+
+    /// Not part of the state, it's a setting of the frontend, see [`Self::set_frame_skip`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    frame_skip: u32,
+    /// Whether the frame being drawn is skipped.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    skipping: bool,
 
     pixel_index: u32,
     should_draw: bool,
+    /// CPU cycles since the last dot was drawn.
+    dot_cycles: u32,
 
     layer_0: Layer0,
     layer_1: Layer1,
@@ -111,7 +264,11 @@ impl Default for Lcd {
             registers: Registers::default(),
             memory: Memory::default(),
             pixel_index: 0,
-            buffer: [[Color::default(); LCD_WIDTH]; LCD_HEIGHT],
+            dot_cycles: 0,
+            buffer: Box::new([[Color::default(); LCD_WIDTH]; LCD_HEIGHT]),
+            frame_output: FrameOutput::default(),
+            frame_skip: 0,
+            skipping: false,
             should_draw: false,
             layer_0: Layer0,
             layer_1: Layer1,
@@ -123,90 +280,121 @@ impl Default for Lcd {
 }
 
 #[allow(clippy::module_name_repetitions)]
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Default)]
 pub struct LcdStepOutput {
+    pub entered_vblank: bool,
+    pub entered_hblank: bool,
     pub request_vblank_irq: bool,
     pub request_hblank_irq: bool,
     pub request_vcount_irq: bool,
 }
 
 impl Lcd {
-    pub fn step(&mut self) -> LcdStepOutput {
-        // This will be much more complex obviously
-        let mut output = LcdStepOutput::default();
+    /// Handle to read the completed frames from another thread.
+    #[must_use]
+    pub fn frame_output(&self) -> FrameOutput {
+        self.frame_output.clone()
+    }
 
-        if self.registers.vcount < 160 {
-            // We either are in Vdraw or Hblank
-            if self.pixel_index == 0 {
-                // We're drawing the first pixel of the scanline, we're entering Vdraw
+    /// Draws one frame out of `frame_skip + 1`, for fast-forward: the others are only
+    /// counted by the [`FrameOutput`], which keeps the last frame drawn. The registers,
+    /// the interrupts and the timing are the same as when drawing every frame.
+    pub const fn set_frame_skip(&mut self, frame_skip: u32) {
+        self.frame_skip = frame_skip;
+    }
 
-                self.registers.set_hblank_flag(false);
-                self.registers.set_vblank_flag(false);
+    #[must_use]
+    pub const fn frame_skip(&self) -> u32 {
+        self.frame_skip
+    }
 
-                self.should_draw = true;
+    /// Whether the frame being drawn, or the one just completed during Vblank, is skipped.
+    pub(crate) const fn skipping(&self) -> bool {
+        self.skipping
+    }
 
-                // Cache attributes and scanline
-                self.layer_obj
-                    .handle_enter_vdraw(&self.memory, &self.registers);
-            } else if self.pixel_index == 240 {
-                // We're entering Hblank
+    /// Dot about to be drawn.
+    #[must_use]
+    pub fn raster_position(&self) -> RasterPosition {
+        RasterPosition {
+            frame: self.frame_output.frame_count(),
+            scanline: self.registers.vcount,
+            dot: self.pixel_index as u16,
+        }
+    }
 
-                self.registers.set_hblank_flag(true);
+    /// Draws a dot, see [`HardwareComponent::step`] to advance by CPU cycles.
+    pub fn step_dot(&mut self) -> LcdStepOutput {
+        // This will be much more complex obviously
+        let mut output = LcdStepOutput::default();
 
-                if self.registers.get_hblank_irq_enable() {
-                    output.request_hblank_irq = true;
+        if self.pixel_index == 0 {
+            // Every scanline, Vblank ones included, starts with the Hblank flag cleared.
+            self.registers.dispstat.set_hblank_flag(false);
+
+            match self.registers.vcount {
+                0..=159 => {
+                    // We're drawing the first pixel of the scanline, we're entering Vdraw
+                    self.should_draw = true;
+                    if self.registers.vcount == 0 {
+                        let frame = self.frame_output.frame_count();
+                        self.skipping = !frame.is_multiple_of(u64::from(self.frame_skip) + 1);
+                    }
+
+                    // Cache attributes and scanline
+                    self.layer_obj
+                        .handle_enter_vdraw(&self.memory, &self.registers);
                 }
-
-                self.should_draw = false;
+                160 => {
+                    // We're drawing the first pixel of the Vblank period
+
+                    self.registers.dispstat.set_vblank_flag(true);
+                    if self.skipping {
+                        self.frame_output.publish_skipped();
+                    } else {
+                        self.frame_output.publish(&self.buffer);
+                    }
+                    output.entered_vblank = true;
+
+                    if self.registers.dispstat.vblank_irq_enable() {
+                        output.request_vblank_irq = true;
+                    }
+                }
+                // The Vblank flag is already cleared on the last scanline, VCOUNT
+                // only goes back to 0 on the next one.
+                227 => self.registers.dispstat.set_vblank_flag(false),
+                _ => {}
             }
-        } else if self.registers.vcount == 160 && self.pixel_index == 0 {
-            // We're drawing the first pixel of the Vblank period
+        } else if self.pixel_index == 240 {
+            // We're entering Hblank, this happens on Vblank scanlines too
 
-            self.registers.set_vblank_flag(true);
+            self.registers.dispstat.set_hblank_flag(true);
+            output.entered_hblank = true;
 
-            if self.registers.get_vblank_irq_enable() {
-                output.request_vblank_irq = true;
+            if self.registers.dispstat.hblank_irq_enable() {
+                output.request_hblank_irq = true;
             }
 
             self.should_draw = false;
         }
 
-        if self.should_draw {
-            let pixel_y = self.registers.vcount;
-            let pixel_x = self.pixel_index;
-
-            // We get the enabled layers (depending on BG mode and registers), we call render on them
-            // we filter out the `None` and we sort by priority.
-            let mut layers_with_pixel = self
-                .get_enabled_layers()
-                .into_iter()
-                .filter_map(|layer| {
-                    layer.render(
-                        pixel_x as usize,
-                        pixel_y as usize,
-                        &self.memory,
-                        &self.registers,
-                    )
-                })
-                .collect::<Vec<PixelInfo>>();
-
-            layers_with_pixel.sort_unstable_by_key(|pixel| pixel.priority);
-
-            let first_pixel = layers_with_pixel.first();
-
-            self.buffer[pixel_y as usize][pixel_x as usize] =
-                first_pixel.map_or_else(|| Color::from_rgb(31, 31, 31), |info| info.color);
+        if self.should_draw && !self.skipping {
+            let pixel_y = self.registers.vcount as usize;
+            let pixel_x = self.pixel_index as usize;
+
+            self.buffer[pixel_y][pixel_x] = self.compose_dot(pixel_x, pixel_y);
         }
 
         log(format!(
             "mode: {:?}, BG2: {:?} BG3: {:?}, OBJ: {:?}, WIN0: {:?}, WIN1: {:?}, WINOJB: {:?}",
-            self.registers.get_bg_mode(),
-            self.registers.get_bg2_enabled(),
-            self.registers.get_bg3_enabled(),
-            self.registers.get_obj_enabled(),
-            self.registers.get_win0_enabled(),
-            self.registers.get_win1_enabled(),
-            self.registers.get_winobj_enabled(),
+            self.registers.dispcnt.bg_mode(),
+            self.registers.dispcnt.bg2_enabled(),
+            self.registers.dispcnt.bg3_enabled(),
+            self.registers.dispcnt.obj_enabled(),
+            self.registers.dispcnt.win0_enabled(),
+            self.registers.dispcnt.win1_enabled(),
+            self.registers.dispcnt.winobj_enabled(),
         ));
 
         self.pixel_index += 1;
@@ -222,45 +410,436 @@ impl Lcd {
             }
         }
 
-        self.registers.set_vcounter_flag(false);
+        let vcount_match = self.registers.vcount == self.registers.dispstat.vcount_setting();
 
-        if self.registers.vcount.get_byte(0) == self.registers.get_vcount_setting() {
-            self.registers.set_vcounter_flag(true);
-
-            if self.registers.get_vcounter_irq_enable() {
-                output.request_vcount_irq = true;
-            }
+        // The interrupt is raised when the flag goes up, not on every dot of the line.
+        if vcount_match
+            && !self.registers.dispstat.vcounter_flag()
+            && self.registers.dispstat.vcounter_irq_enable()
+        {
+            output.request_vcount_irq = true;
         }
 
+        self.registers.dispstat.set_vcounter_flag(vcount_match);
+
         output
     }
 
-    fn get_enabled_layers(&self) -> Vec<&dyn Layer> {
-        let mut result: Vec<&dyn Layer> = Vec::new();
+    /// Draws the topmost layer enabled by the windows at the dot, with the special
+    /// effects applied.
+    fn compose_dot(&self, x: usize, y: usize) -> Color {
+        let in_obj_window = self.registers.dispcnt.obj_enabled() && self.layer_obj.in_obj_window(x);
+        let window = WindowControl::at(&self.registers, x as u16, y as u16, in_obj_window);
+
+        // We get the enabled layers (depending on BG mode and registers), we call render on them
+        // we filter out the `None` and we sort by priority.
+        let mut dots = self
+            .get_enabled_layers()
+            .into_iter()
+            .filter(|(layer, _)| window.layer_enabled(*layer))
+            .filter_map(|(layer, renderer)| {
+                renderer
+                    .render(x, y, &self.memory, &self.registers)
+                    .map(|pixel| Dot { layer, pixel })
+            })
+            .collect::<Vec<Dot>>();
+
+        dots.sort_unstable_by_key(|dot| (dot.pixel.priority, dot.layer.draw_order()));
+
+        // The backdrop is the first color of the BG palette, below everything.
+        dots.push(Dot {
+            layer: LayerId::Backdrop,
+            pixel: PixelInfo {
+                color: Color::from_palette_color(u16::from_le_bytes([
+                    self.memory.bg_palette_ram[0],
+                    self.memory.bg_palette_ram[1],
+                ])),
+                priority: 4,
+                semi_transparent: false,
+            },
+        });
+
+        effects::apply(
+            &self.registers,
+            dots[0],
+            dots.get(1).copied(),
+            window.effects_enabled(),
+        )
+    }
+
+    fn get_enabled_layers(&self) -> Vec<(LayerId, &dyn Layer)> {
+        let mut result: Vec<(LayerId, &dyn Layer)> = Vec::new();
 
-        let current_mode = self.registers.get_bg_mode();
+        let current_mode = self.registers.dispcnt.bg_mode();
 
-        if matches!(current_mode, 0 | 1) && self.registers.get_bg0_enabled() {
-            result.push(&self.layer_0);
+        if matches!(current_mode, 0 | 1) && self.registers.dispcnt.bg0_enabled() {
+            result.push((LayerId::Bg0, &self.layer_0));
         }
 
-        if matches!(current_mode, 0 | 1) && self.registers.get_bg1_enabled() {
-            result.push(&self.layer_1);
+        if matches!(current_mode, 0 | 1) && self.registers.dispcnt.bg1_enabled() {
+            result.push((LayerId::Bg1, &self.layer_1));
         }
 
         // BG2 is available in every mode
-        if self.registers.get_bg2_enabled() {
-            result.push(&self.layer_2);
+        if self.registers.dispcnt.bg2_enabled() {
+            result.push((LayerId::Bg2, &self.layer_2));
         }
 
-        if matches!(current_mode, 0 | 2) && self.registers.get_bg3_enabled() {
-            result.push(&self.layer_3);
+        if matches!(current_mode, 0 | 2) && self.registers.dispcnt.bg3_enabled() {
+            result.push((LayerId::Bg3, &self.layer_3));
         }
 
-        if self.registers.get_obj_enabled() {
-            result.push(&self.layer_obj);
+        if self.registers.dispcnt.obj_enabled() {
+            result.push((LayerId::Obj, &self.layer_obj));
         }
 
         result
     }
 }
+
+impl HardwareComponent for Lcd {
+    fn reset(&mut self) {
+        *self = Self {
+            frame_output: std::mem::take(&mut self.frame_output),
+            frame_skip: self.frame_skip,
+            ..Self::default()
+        };
+    }
+
+    fn step(&mut self, cycles: u32) -> StepOutput {
+        let mut output = StepOutput::default();
+
+        self.dot_cycles += cycles;
+        while self.dot_cycles >= CYCLES_PER_DOT {
+            self.dot_cycles -= CYCLES_PER_DOT;
+
+            let dot = self.step_dot();
+            output.entered_vblank |= dot.entered_vblank;
+            output.entered_hblank |= dot.entered_hblank;
+            if dot.request_hblank_irq {
+                output.request_interrupt(IrqType::HBlank);
+            }
+            if dot.request_vblank_irq {
+                output.request_interrupt(IrqType::VBlank);
+            }
+            if dot.request_vcount_irq {
+                output.request_interrupt(IrqType::VCount);
+            }
+        }
+
+        output
+    }
+
+    fn on_read(&self, address: usize) -> Option<u8> {
+        let value = match address {
+            0x0400_0000 => self.registers.dispcnt.get_byte(0),
+            0x0400_0001 => self.registers.dispcnt.get_byte(1),
+            0x0400_0002 => self.registers.green_swap.get_byte(0),
+            0x0400_0003 => self.registers.green_swap.get_byte(1),
+            0x0400_0004 => self.registers.dispstat.get_byte(0),
+            0x0400_0005 => self.registers.dispstat.get_byte(1),
+            0x0400_0006 => self.registers.vcount.get_byte(0),
+            0x0400_0007 => self.registers.vcount.get_byte(1),
+            0x0400_0008 => self.registers.bg0cnt.get_byte(0),
+            0x0400_0009 => self.registers.bg0cnt.get_byte(1),
+            0x0400_000A => self.registers.bg1cnt.get_byte(0),
+            0x0400_000B => self.registers.bg1cnt.get_byte(1),
+            0x0400_000C => self.registers.bg2cnt.get_byte(0),
+            0x0400_000D => self.registers.bg2cnt.get_byte(1),
+            0x0400_000E => self.registers.bg3cnt.get_byte(0),
+            0x0400_000F => self.registers.bg3cnt.get_byte(1),
+            (0x0400_0010..=0x0400_0047) | (0x0400_0054..=0x0400_0055) => {
+                panic!("Reading a write-only LCD I/O register")
+            }
+            0x0400_0048 => self.registers.winin.get_byte(0),
+            0x0400_0049 => self.registers.winin.get_byte(1),
+            0x0400_004A => self.registers.winout.get_byte(0),
+            0x0400_004B => self.registers.winout.get_byte(1),
+            0x0400_004C => self.registers.mosaic.get_byte(0),
+            0x0400_004D => self.registers.mosaic.get_byte(1),
+            0x0400_0050 => self.registers.bldcnt.get_byte(0),
+            0x0400_0051 => self.registers.bldcnt.get_byte(1),
+            0x0400_0052 => self.registers.bldalpha.get_byte(0),
+            0x0400_0053 => self.registers.bldalpha.get_byte(1),
+            0x0400_004E..=0x0400_004F | 0x0400_0056..=0x0400_005F => return None,
+            _ => panic!("LCD read address is out of bound"),
+        };
+
+        Some(value)
+    }
+
+    fn on_write(&mut self, address: usize, value: u8) -> bool {
+        match address {
+            0x0400_0000 => self.registers.dispcnt.set_byte(0, value),
+            0x0400_0001 => self.registers.dispcnt.set_byte(1, value),
+            0x0400_0002 => self.registers.green_swap.set_byte(0, value),
+            0x0400_0003 => self.registers.green_swap.set_byte(1, value),
+            0x0400_0004 => {
+                // The Vblank, Hblank and VCOUNT match flags are read-only.
+                let flags = self.registers.dispstat.get_byte(0) & 0b111;
+                self.registers.dispstat.set_byte(0, value & !0b111 | flags);
+            }
+            0x0400_0005 => self.registers.dispstat.set_byte(1, value),
+            0x0400_0008 => self.registers.bg0cnt.set_byte(0, value),
+            // VCOUNT is read-only, it can't be moved out of 0-227.
+            0x0400_0006 | 0x0400_0007 => {}
+            0x0400_0009 => self.registers.bg0cnt.set_byte(1, value),
+            0x0400_000A => self.registers.bg1cnt.set_byte(0, value),
+            0x0400_000B => self.registers.bg1cnt.set_byte(1, value),
+            0x0400_000C => self.registers.bg2cnt.set_byte(0, value),
+            0x0400_000D => self.registers.bg2cnt.set_byte(1, value),
+            0x0400_000E => self.registers.bg3cnt.set_byte(0, value),
+            0x0400_000F => self.registers.bg3cnt.set_byte(1, value),
+            0x0400_0010 => self.registers.bg0hofs.set_byte(0, value),
+            0x0400_0011 => self.registers.bg0hofs.set_byte(1, value),
+            0x0400_0012 => self.registers.bg0vofs.set_byte(0, value),
+            0x0400_0013 => self.registers.bg0vofs.set_byte(1, value),
+            0x0400_0014 => self.registers.bg1hofs.set_byte(0, value),
+            0x0400_0015 => self.registers.bg1hofs.set_byte(1, value),
+            0x0400_0016 => self.registers.bg1vofs.set_byte(0, value),
+            0x0400_0017 => self.registers.bg1vofs.set_byte(1, value),
+            0x0400_0018 => self.registers.bg2hofs.set_byte(0, value),
+            0x0400_0019 => self.registers.bg2hofs.set_byte(1, value),
+            0x0400_001A => self.registers.bg2vofs.set_byte(0, value),
+            0x0400_001B => self.registers.bg2vofs.set_byte(1, value),
+            0x0400_001C => self.registers.bg3hofs.set_byte(0, value),
+            0x0400_001D => self.registers.bg3hofs.set_byte(1, value),
+            0x0400_001E => self.registers.bg3vofs.set_byte(0, value),
+            0x0400_001F => self.registers.bg3vofs.set_byte(1, value),
+            0x0400_0020 => self.registers.bg2pa.set_byte(0, value),
+            0x0400_0021 => self.registers.bg2pa.set_byte(1, value),
+            0x0400_0022 => self.registers.bg2pb.set_byte(0, value),
+            0x0400_0023 => self.registers.bg2pb.set_byte(1, value),
+            0x0400_0024 => self.registers.bg2pc.set_byte(0, value),
+            0x0400_0025 => self.registers.bg2pc.set_byte(1, value),
+            0x0400_0026 => self.registers.bg2pd.set_byte(0, value),
+            0x0400_0027 => self.registers.bg2pd.set_byte(1, value),
+            0x0400_0028 => self.registers.bg2x.set_byte(0, value),
+            0x0400_0029 => self.registers.bg2x.set_byte(1, value),
+            0x0400_002A => self.registers.bg2x.set_byte(2, value),
+            0x0400_002B => self.registers.bg2x.set_byte(3, value),
+            0x0400_002C => self.registers.bg2y.set_byte(0, value),
+            0x0400_002D => self.registers.bg2y.set_byte(1, value),
+            0x0400_002E => self.registers.bg2y.set_byte(2, value),
+            0x0400_002F => self.registers.bg2y.set_byte(3, value),
+            0x0400_0030 => self.registers.bg3pa.set_byte(0, value),
+            0x0400_0031 => self.registers.bg3pa.set_byte(1, value),
+            0x0400_0032 => self.registers.bg3pb.set_byte(0, value),
+            0x0400_0033 => self.registers.bg3pb.set_byte(1, value),
+            0x0400_0034 => self.registers.bg3pc.set_byte(0, value),
+            0x0400_0035 => self.registers.bg3pc.set_byte(1, value),
+            0x0400_0036 => self.registers.bg3pd.set_byte(0, value),
+            0x0400_0037 => self.registers.bg3pd.set_byte(1, value),
+            0x0400_0038 => self.registers.bg3x.set_byte(0, value),
+            0x0400_0039 => self.registers.bg3x.set_byte(1, value),
+            0x0400_003A => self.registers.bg3x.set_byte(2, value),
+            0x0400_003B => self.registers.bg3x.set_byte(3, value),
+            0x0400_003C => self.registers.bg3y.set_byte(0, value),
+            0x0400_003D => self.registers.bg3y.set_byte(1, value),
+            0x0400_003E => self.registers.bg3y.set_byte(2, value),
+            0x0400_003F => self.registers.bg3y.set_byte(3, value),
+            0x0400_0040 => self.registers.win0h.set_byte(0, value),
+            0x0400_0041 => self.registers.win0h.set_byte(1, value),
+            0x0400_0042 => self.registers.win1h.set_byte(0, value),
+            0x0400_0043 => self.registers.win1h.set_byte(1, value),
+            0x0400_0044 => self.registers.win0v.set_byte(0, value),
+            0x0400_0045 => self.registers.win0v.set_byte(1, value),
+            0x0400_0046 => self.registers.win1v.set_byte(0, value),
+            0x0400_0047 => self.registers.win1v.set_byte(1, value),
+            0x0400_0048 => self.registers.winin.set_byte(0, value),
+            0x0400_0049 => self.registers.winin.set_byte(1, value),
+            0x0400_004A => self.registers.winout.set_byte(0, value),
+            0x0400_004B => self.registers.winout.set_byte(1, value),
+            0x0400_004C => self.registers.mosaic.set_byte(0, value),
+            0x0400_004D => self.registers.mosaic.set_byte(1, value),
+            // 0x0400_004E, 0x0400_004F are not used
+            0x0400_0050 => self.registers.bldcnt.set_byte(0, value),
+            0x0400_0051 => self.registers.bldcnt.set_byte(1, value),
+            0x0400_0052 => self.registers.bldalpha.set_byte(0, value),
+            0x0400_0053 => self.registers.bldalpha.set_byte(1, value),
+            0x0400_0054 => self.registers.bldy.set_byte(0, value),
+            0x0400_0055 => self.registers.bldy.set_byte(1, value),
+            0x0400_004E..=0x0400_004F | 0x0400_0056..=0x0400_005F => return false,
+            _ => panic!("LCD write address is out of bound"),
+        }
+
+        true
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> bincode::Result<Vec<u8>> {
+        bincode::serialize(self)
+    }
+
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        let mut lcd: Self = bincode::deserialize(data)?;
+        // The frontend keeps reading from the same output, with the same frame skip.
+        lcd.frame_output = std::mem::take(&mut self.frame_output);
+        lcd.frame_skip = self.frame_skip;
+        *self = lcd;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::cpu::hardware::lcd::registers::DispStat;
+
+    #[test]
+    fn frame_is_published_entering_vblank() {
+        let mut lcd = Lcd::default();
+        let output = lcd.frame_output();
+
+        // Vdraw lasts 160 scanlines of 308 dots.
+        for _ in 0..160 * 308 {
+            lcd.step_dot();
+        }
+
+        lcd.buffer[10][20] = Color::from_rgb(1, 2, 3);
+        assert_eq!(output.load()[10][20].0, Color::default().0);
+        assert_eq!(output.frame_count(), 0);
+
+        lcd.step_dot();
+        assert_eq!(output.load()[10][20].0, Color::from_rgb(1, 2, 3).0);
+        assert_eq!(output.frame_count(), 1);
+    }
+
+    #[test]
+    fn lcd_colors_are_dimmer() {
+        let white = Color::from_rgb(31, 31, 31);
+        assert_eq!(white.to_rgb8(ColorCorrection::Off), [255, 255, 255]);
+        assert!(white.to_rgb8(ColorCorrection::Lcd).iter().all(|c| *c < 255));
+        assert_eq!(Color(0).to_rgb8(ColorCorrection::Lcd), [0, 0, 0]);
+    }
+
+    #[test]
+    fn raster_position() {
+        let mut lcd = Lcd::default();
+
+        step_to(&mut lcd, 161, 12);
+        assert_eq!(
+            lcd.raster_position(),
+            RasterPosition {
+                frame: 1,
+                scanline: 161,
+                dot: 12,
+            }
+        );
+        assert_eq!(
+            lcd.raster_position().to_string(),
+            "frame 1, line 161, dot 12"
+        );
+    }
+
+    #[test]
+    fn skipped_frames_are_counted_not_drawn() {
+        let mut lcd = Lcd::default();
+        let output = lcd.frame_output();
+        lcd.set_frame_skip(1);
+
+        // The backdrop covers the whole picture, its color changes every frame.
+        for (frame, color) in [0x001F_u16, 0x03E0, 0x7C00].into_iter().enumerate() {
+            lcd.memory.bg_palette_ram[..2].copy_from_slice(&color.to_le_bytes());
+            step_to(&mut lcd, 161, 0);
+
+            assert_eq!(output.frame_count(), frame as u64 + 1);
+            let drawn = if frame == 1 { 0x001F } else { color };
+            assert_eq!(output.load()[80][120].0, drawn);
+            step_to(&mut lcd, 0, 0);
+        }
+    }
+
+    /// Steps until the next dot to draw is `dot` of scanline `line`.
+    fn step_to(lcd: &mut Lcd, line: u16, dot: u32) -> LcdStepOutput {
+        let mut output = LcdStepOutput::default();
+        while lcd.registers.vcount != line || lcd.pixel_index != dot {
+            output = lcd.step_dot();
+        }
+
+        output
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn save_state_resumes_mid_scanline() {
+        use crate::cpu::hardware::lcd::registers::{BgCnt, DispCnt};
+        use crate::render::compare::{compare_frames, CompareOptions};
+
+        let mut lcd = Lcd::default();
+        // BG0 and OBJs in mode 0. BG0 is tile 0 everywhere, the first OBJ covers the
+        // top left 8x8 dots.
+        lcd.registers.dispcnt = DispCnt(1 << 8 | 1 << 12);
+        lcd.registers.bg0cnt = BgCnt(1 << 8);
+        lcd.memory.video_ram[..32].fill(0x21);
+        lcd.memory.video_ram[0x10000..0x10020].fill(0x43);
+        lcd.memory.bg_palette_ram[2..4].copy_from_slice(&[0x02, 0x03]);
+
+        // Saved halfway through a dot, while drawing the OBJ on scanline 3.
+        let saved_at = (3 * 308 + 4) * CYCLES_PER_DOT + CYCLES_PER_DOT / 2;
+        lcd.step(saved_at);
+        let state = lcd.save_state().unwrap();
+
+        let mut restored = Lcd::default();
+        restored.load_state(&state).unwrap();
+
+        let remaining = 160 * 308 * CYCLES_PER_DOT - saved_at;
+        lcd.step(remaining);
+        restored.step(remaining);
+
+        assert_eq!(restored.registers.vcount, 160);
+        assert_eq!(restored.buffer[3][7].0, 0x0808);
+        assert_eq!(restored.buffer[3][8].0, 0x0302);
+        assert!(
+            compare_frames(&lcd.buffer, &restored.buffer, &CompareOptions::default()).is_match()
+        );
+    }
+
+    #[test]
+    fn dispstat_flags_at_scanline_boundaries() {
+        let mut lcd = Lcd::default();
+
+        step_to(&mut lcd, 159, 241);
+        assert_eq!(lcd.registers.dispstat.0 & 0b11, 0b10);
+
+        step_to(&mut lcd, 160, 1);
+        assert_eq!(lcd.registers.dispstat.0 & 0b11, 0b01);
+
+        // Hblank keeps happening during Vblank.
+        step_to(&mut lcd, 200, 241);
+        assert_eq!(lcd.registers.dispstat.0 & 0b11, 0b11);
+
+        // The Vblank flag is cleared while VCOUNT is still 227.
+        step_to(&mut lcd, 226, 307);
+        assert_eq!(lcd.registers.dispstat.0 & 0b11, 0b11);
+        step_to(&mut lcd, 227, 1);
+        assert_eq!(lcd.registers.dispstat.0 & 0b11, 0b00);
+
+        step_to(&mut lcd, 0, 0);
+        assert_eq!(lcd.registers.vcount, 0);
+    }
+
+    #[test]
+    fn vcount_irq_is_requested_once_per_match() {
+        let mut lcd = Lcd::default();
+        // VCOUNT setting 227, VCOUNT match interrupt enabled.
+        lcd.registers.dispstat = DispStat(227 << 8 | 1 << 5);
+
+        let mut requests = Vec::new();
+        for _ in 0..2 * 228 * 308 {
+            if lcd.step_dot().request_vcount_irq {
+                requests.push(lcd.registers.vcount);
+            }
+            assert_eq!(
+                lcd.registers.dispstat.vcounter_flag(),
+                lcd.registers.vcount == 227
+            );
+        }
+
+        assert_eq!(requests, vec![227, 227]);
+    }
+}
@@ -0,0 +1,275 @@
+//! Windows and color special effects, applied when composing a dot from the layers.
+
+use crate::bitwise::Bits;
+
+use super::{registers::Registers, Color, PixelInfo};
+
+/// Layers in the order of their bits in `WININ`, `WINOUT` and `BLDCNT`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayerId {
+    Bg0,
+    Bg1,
+    Bg2,
+    Bg3,
+    Obj,
+    Backdrop,
+}
+
+impl LayerId {
+    const fn bit(self) -> u8 {
+        self as u8
+    }
+
+    /// On the same priority OBJs are drawn above the backgrounds, and BG0 above BG3.
+    pub const fn draw_order(self) -> u8 {
+        match self {
+            Self::Obj => 0,
+            Self::Bg0 => 1,
+            Self::Bg1 => 2,
+            Self::Bg2 => 3,
+            Self::Bg3 => 4,
+            Self::Backdrop => 5,
+        }
+    }
+}
+
+/// A layer visible at the dot being composed.
+#[derive(Clone, Copy)]
+pub struct Dot {
+    pub layer: LayerId,
+    pub pixel: PixelInfo,
+}
+
+/// Layers and effects enabled at a dot, bits 0-5 of a `WININ` or `WINOUT` byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindowControl(u8);
+
+impl WindowControl {
+    /// Without any window everything is displayed.
+    const EVERYTHING: Self = Self(0b11_1111);
+
+    /// WIN0 has priority over WIN1, which has priority over the OBJ window.
+    pub fn at(registers: &Registers, x: u16, y: u16, in_obj_window: bool) -> Self {
+        let win0 = registers.dispcnt.win0_enabled();
+        let win1 = registers.dispcnt.win1_enabled();
+        let winobj = registers.dispcnt.winobj_enabled();
+
+        if !(win0 || win1 || winobj) {
+            return Self::EVERYTHING;
+        }
+
+        let control = if win0 && inside(registers.win0h, x) && inside(registers.win0v, y) {
+            registers.winin.get_byte(0)
+        } else if win1 && inside(registers.win1h, x) && inside(registers.win1v, y) {
+            registers.winin.get_byte(1)
+        } else if winobj && in_obj_window {
+            registers.winout.get_byte(1)
+        } else {
+            registers.winout.get_byte(0)
+        };
+
+        Self(control & 0b11_1111)
+    }
+
+    pub fn layer_enabled(self, layer: LayerId) -> bool {
+        self.0.get_bit(layer.bit())
+    }
+
+    pub fn effects_enabled(self) -> bool {
+        self.0.get_bit(5)
+    }
+}
+
+/// `dimension` is a `WINxH` or `WINxV` register: the first coordinate inside the window
+/// in bits 8-15, the first one outside in bits 0-7. The window wraps around the screen
+/// when the first is greater.
+fn inside(dimension: u16, coordinate: u16) -> bool {
+    let start = u16::from(dimension.get_byte(1));
+    let end = u16::from(dimension.get_byte(0));
+
+    if start <= end {
+        (start..end).contains(&coordinate)
+    } else {
+        coordinate >= start || coordinate < end
+    }
+}
+
+/// Final color of a dot from its two topmost layers, `below` is `None` when `top` is
+/// the backdrop.
+pub fn apply(registers: &Registers, top: Dot, below: Option<Dot>, effects_enabled: bool) -> Color {
+    if !effects_enabled {
+        return top.pixel.color;
+    }
+
+    let second_target = below.filter(|dot| registers.bldcnt.second_target(dot.layer.bit()));
+
+    // Semi-transparent OBJs are blended over a second target whatever BLDCNT selects,
+    // otherwise they get the effect selected for the OBJ layer.
+    if let (true, Some(below)) = (top.pixel.semi_transparent, second_target) {
+        return alpha_blend(registers, top.pixel.color, below.pixel.color);
+    }
+
+    if !registers.bldcnt.first_target(top.layer.bit()) {
+        return top.pixel.color;
+    }
+
+    match (registers.bldcnt.effect(), second_target) {
+        (1, Some(below)) => alpha_blend(registers, top.pixel.color, below.pixel.color),
+        (2, _) => brightness(registers, top.pixel.color, |channel, evy| {
+            channel + (31 - channel) * evy / 16
+        }),
+        (3, _) => brightness(registers, top.pixel.color, |channel, evy| {
+            channel - channel * evy / 16
+        }),
+        _ => top.pixel.color,
+    }
+}
+
+/// Coefficients are in 1/16 units, anything above 16 counts as 16.
+fn coefficient(value: u16) -> u16 {
+    value.get_bits(0..=4).min(16)
+}
+
+fn map_channels(colors: [Color; 2], f: impl Fn(u16, u16) -> u16) -> Color {
+    let [first, second] = colors;
+    let channel = |a: u8, b: u8| f(a.into(), b.into()).min(31) as u8;
+
+    Color::from_rgb(
+        channel(first.red(), second.red()),
+        channel(first.green(), second.green()),
+        channel(first.blue(), second.blue()),
+    )
+}
+
+fn alpha_blend(registers: &Registers, top: Color, below: Color) -> Color {
+    let eva = coefficient(registers.bldalpha);
+    let evb = coefficient(registers.bldalpha >> 8);
+
+    map_channels([top, below], |a, b| (a * eva + b * evb) / 16)
+}
+
+fn brightness(registers: &Registers, color: Color, f: impl Fn(u16, u16) -> u16) -> Color {
+    let evy = coefficient(registers.bldy);
+
+    map_channels([color, color], |channel, _| f(channel, evy))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::cpu::hardware::lcd::registers::{BldCnt, DispCnt};
+
+    const WIN0: u16 = 1 << 13;
+    const WINOBJ: u16 = 1 << 15;
+
+    /// Register with `high` in bits 8-15 and `low` in bits 0-7.
+    const fn bytes(high: u16, low: u16) -> u16 {
+        high << 8 | low
+    }
+
+    fn dot(layer: LayerId, color: Color, semi_transparent: bool) -> Dot {
+        Dot {
+            layer,
+            pixel: PixelInfo {
+                color,
+                priority: 0,
+                semi_transparent,
+            },
+        }
+    }
+
+    #[test]
+    fn window_precedence() {
+        let mut registers = Registers {
+            // WIN0 covers y 20-59 and wraps horizontally: x 50-239 and 0-9.
+            win0h: bytes(50, 10),
+            win0v: bytes(20, 60),
+            winin: 0b00_0001,
+            winout: bytes(0b01_0000, 0b10_0010),
+            ..Default::default()
+        };
+        assert_eq!(
+            WindowControl::at(&registers, 0, 0, false),
+            WindowControl::EVERYTHING
+        );
+
+        registers.dispcnt = DispCnt(WIN0 | WINOBJ);
+        let at = |x, y, obj| WindowControl::at(&registers, x, y, obj).0;
+        assert_eq!(at(5, 30, true), 0b00_0001);
+        assert_eq!(at(60, 30, false), 0b00_0001);
+        assert_eq!(at(30, 30, true), 0b01_0000);
+        assert_eq!(at(30, 30, false), 0b10_0010);
+        assert_eq!(at(5, 60, false), 0b10_0010);
+
+        let outside = WindowControl(0b10_0010);
+        assert!(outside.layer_enabled(LayerId::Bg1));
+        assert!(!outside.layer_enabled(LayerId::Obj));
+        assert!(outside.effects_enabled());
+    }
+
+    #[test]
+    fn semi_transparent_obj_is_always_alpha_blended() {
+        let mut registers = Registers {
+            // Brightness increase on BG0, the backdrop as second target.
+            bldcnt: BldCnt(1 << 13 | 0b10 << 6 | 1),
+            bldalpha: bytes(8, 8),
+            bldy: 16,
+            ..Default::default()
+        };
+        let obj = dot(LayerId::Obj, Color::from_rgb(20, 10, 0), true);
+        let backdrop = dot(LayerId::Backdrop, Color::from_rgb(10, 30, 31), false);
+
+        let color = apply(&registers, obj, Some(backdrop), true);
+        assert_eq!(color.0, Color::from_rgb(15, 20, 15).0);
+
+        // Disabled by the window.
+        let color = apply(&registers, obj, Some(backdrop), false);
+        assert_eq!(color.0, Color::from_rgb(20, 10, 0).0);
+
+        // Without a second target below, the OBJ gets the effect selected for it.
+        let bg1 = dot(LayerId::Bg1, Color::from_rgb(10, 30, 31), false);
+        let color = apply(&registers, obj, Some(bg1), true);
+        assert_eq!(color.0, Color::from_rgb(20, 10, 0).0);
+
+        registers.bldcnt.0 |= 1 << 4;
+        let color = apply(&registers, obj, Some(bg1), true);
+        assert_eq!(color.0, Color::from_rgb(31, 31, 31).0);
+    }
+
+    #[test]
+    fn bldcnt_effects() {
+        let mut registers = Registers {
+            bldcnt: BldCnt(1 << 9 | 0b01 << 6 | 1),
+            // EVA above 16 counts as 16.
+            bldalpha: bytes(4, 20),
+            bldy: 8,
+            ..Default::default()
+        };
+        let bg0 = dot(LayerId::Bg0, Color::from_rgb(30, 16, 0), false);
+        let bg1 = dot(LayerId::Bg1, Color::from_rgb(16, 16, 16), false);
+        let bg2 = dot(LayerId::Bg2, Color::from_rgb(16, 16, 16), false);
+
+        assert_eq!(
+            apply(&registers, bg0, Some(bg1), true).0,
+            Color::from_rgb(31, 20, 4).0
+        );
+        // BG2 isn't a second target.
+        assert_eq!(
+            apply(&registers, bg0, Some(bg2), true).0,
+            Color::from_rgb(30, 16, 0).0
+        );
+        // BG1 isn't a first target.
+        assert_eq!(
+            apply(&registers, bg1, Some(bg0), true).0,
+            Color::from_rgb(16, 16, 16).0
+        );
+
+        registers.bldcnt = BldCnt(0b11 << 6 | 1);
+        assert_eq!(
+            apply(&registers, bg0, None, true).0,
+            Color::from_rgb(15, 8, 0).0
+        );
+    }
+}
@@ -5,6 +5,7 @@ pub mod layer_1;
 pub mod layer_2;
 pub mod layer_3;
 pub mod layer_obj;
+pub mod text;
 
 pub trait Layer {
     fn render(
@@ -2,15 +2,16 @@ use crate::cpu::hardware::lcd::memory::Memory;
 use crate::cpu::hardware::lcd::registers::Registers;
 use crate::cpu::hardware::lcd::PixelInfo;
 
+use super::text::TextBackground;
 use super::Layer;
-use serde::Deserialize;
-use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Layer0;
 
 impl Layer for Layer0 {
-    #[allow(unused_variables)]
     fn render(
         &self,
         x: usize,
@@ -18,7 +19,12 @@ impl Layer for Layer0 {
         memory: &Memory,
         registers: &Registers,
     ) -> Option<PixelInfo> {
-        // TODO: To implement
-        None
+        // BG0 is only enabled in the text modes 0 and 1.
+        TextBackground {
+            control: registers.bg0cnt,
+            horizontal_offset: registers.bg0hofs,
+            vertical_offset: registers.bg0vofs,
+        }
+        .render(x, y, memory)
     }
 }
@@ -1,15 +1,18 @@
+use super::text::TextBackground;
 use super::Layer;
 use crate::cpu::hardware::lcd::memory::Memory;
 use crate::cpu::hardware::lcd::registers::Registers;
 use crate::cpu::hardware::lcd::{Color, PixelInfo, LCD_WIDTH};
-use serde::Deserialize;
-use serde::Serialize;
-use serde_with::serde_as;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-#[serde_as]
-#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Layer2 {
-    #[serde_as(as = "[_; 240]")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<[serde_with::Same; 240]>")
+    )]
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
     bg_pixels_scanline: [Option<PixelInfo>; LCD_WIDTH],
 }
 
@@ -22,7 +25,6 @@ impl Default for Layer2 {
 }
 
 impl Layer for Layer2 {
-    #[allow(unused_variables)]
     fn render(
         &self,
         x: usize,
@@ -30,6 +32,15 @@ impl Layer for Layer2 {
         memory: &Memory,
         registers: &Registers,
     ) -> Option<PixelInfo> {
+        if registers.dispcnt.bg_mode() == 0 {
+            return TextBackground {
+                control: registers.bg2cnt,
+                horizontal_offset: registers.bg2hofs,
+                vertical_offset: registers.bg2vofs,
+            }
+            .render(x, y, memory);
+        }
+
         let idx: usize = y * LCD_WIDTH + x;
 
         let color_idx = memory.video_ram[idx] as usize;
@@ -39,6 +50,7 @@ impl Layer for Layer2 {
         Some(PixelInfo {
             color: Color::from_palette_color((high_nibble << 8) | low_nibble),
             priority: 0,
+            semi_transparent: false,
         })
     }
 }
@@ -2,15 +2,16 @@ use crate::cpu::hardware::lcd::memory::Memory;
 use crate::cpu::hardware::lcd::registers::Registers;
 use crate::cpu::hardware::lcd::PixelInfo;
 
+use super::text::TextBackground;
 use super::Layer;
-use serde::Deserialize;
-use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Layer3;
 
 impl Layer for Layer3 {
-    #[allow(unused_variables)]
     fn render(
         &self,
         x: usize,
@@ -18,7 +19,16 @@ impl Layer for Layer3 {
         memory: &Memory,
         registers: &Registers,
     ) -> Option<PixelInfo> {
-        // TODO: To implement
-        None
+        if registers.dispcnt.bg_mode() != 0 {
+            // TODO: Implement the rotation/scaling background of mode 2
+            return None;
+        }
+
+        TextBackground {
+            control: registers.bg3cnt,
+            horizontal_offset: registers.bg3hofs,
+            vertical_offset: registers.bg3vofs,
+        }
+        .render(x, y, memory)
     }
 }
@@ -8,21 +8,35 @@ use crate::cpu::hardware::lcd::{PixelInfo, LCD_WIDTH, WORLD_HEIGHT};
 
 use super::Layer;
 use crate::bitwise::Bits;
-use serde::Deserialize;
-use serde::Serialize;
-use serde_with::serde_as;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-#[serde_as]
-#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LayerObj {
-    #[serde_as(as = "[_; 128]")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<[serde_with::Same; 128]>")
+    )]
     obj_attributes_arr: [object_attributes::ObjAttributes; 128],
 
-    #[serde_as(as = "[_; 32]")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<[serde_with::Same; 32]>")
+    )]
     rotation_scaling_params: [object_attributes::RotationScaling; 32],
 
-    #[serde_as(as = "[_; 240]")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<[serde_with::Same; 240]>")
+    )]
     sprite_pixels_scanline: [Option<PixelInfo>; LCD_WIDTH],
+
+    /// Dots covered by the visible pixels of the OBJ window sprites.
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<[serde_with::Same; 240]>")
+    )]
+    obj_window_scanline: [bool; LCD_WIDTH],
 }
 
 impl Default for LayerObj {
@@ -31,6 +45,7 @@ impl Default for LayerObj {
             obj_attributes_arr: [object_attributes::ObjAttributes::default(); 128],
             rotation_scaling_params: [object_attributes::RotationScaling::default(); 32],
             sprite_pixels_scanline: [None; LCD_WIDTH],
+            obj_window_scanline: [false; LCD_WIDTH],
         }
     }
 }
@@ -101,15 +116,13 @@ impl LayerObj {
     #[allow(clippy::too_many_lines)]
     fn process_sprites_scanline(&mut self, registers: &Registers, memory: &Memory) {
         self.sprite_pixels_scanline = [None; LCD_WIDTH];
+        self.obj_window_scanline = [false; LCD_WIDTH];
         let y = registers.vcount;
 
         for obj in self.obj_attributes_arr {
             if matches!(
                 obj.attribute0.obj_mode,
                 object_attributes::ObjMode::Disabled
-            ) || matches!(
-                obj.attribute0.gfx_mode,
-                object_attributes::GfxMode::ObjectWindow
             ) {
                 continue;
             }
@@ -217,7 +230,7 @@ impl LayerObj {
                 let y_tile_idx = pixel_texture_sprite_origin.y % 8;
                 let x_tile_idx = pixel_texture_sprite_origin.x % 8;
 
-                let obj_character_vram_mapping = registers.get_obj_character_vram_mapping();
+                let obj_character_vram_mapping = registers.dispcnt.obj_character_vram_mapping();
 
                 let color_offset = match obj.attribute0.color_mode {
                     object_attributes::ColorMode::Palette8bpp => {
@@ -243,7 +256,8 @@ impl LayerObj {
                             tile_number as u32 * 32 + y_tile_idx as u32 * 8 + x_tile_idx as u32;
 
                         // TODO: Move 0x10000 to a variable. It is the offset where OBJ VRAM starts in vram
-                        memory.video_ram[0x10000 + palette_offset as usize]
+                        // Offsets past the 32 `KBytes` of OBJ VRAM wrap around.
+                        memory.video_ram[0x10000 + (palette_offset as usize & 0x7FFF)]
                     }
                     object_attributes::ColorMode::Palette4bpp => {
                         let tile_number = obj.attribute2.tile_number
@@ -281,12 +295,27 @@ impl LayerObj {
                     continue;
                 }
 
+                // OBJ window sprites aren't drawn, their visible pixels shape the window.
+                if matches!(
+                    obj.attribute0.gfx_mode,
+                    object_attributes::GfxMode::ObjectWindow
+                ) {
+                    if color_offset != 0 {
+                        self.obj_window_scanline[x_screen as usize] = true;
+                    }
+                    continue;
+                }
+
                 let get_pixel_info_closure = || PixelInfo {
                     color: Self::read_color_from_obj_palette(
                         color_offset as usize,
                         memory.obj_palette_ram.as_slice(),
                     ),
                     priority: obj.attribute2.priority,
+                    semi_transparent: matches!(
+                        obj.attribute0.gfx_mode,
+                        object_attributes::GfxMode::AlphaBlending
+                    ),
                 };
 
                 self.sprite_pixels_scanline[x_screen as usize] =
@@ -304,6 +333,10 @@ impl LayerObj {
         }
     }
 
+    pub const fn in_obj_window(&self, x: usize) -> bool {
+        self.obj_window_scanline[x]
+    }
+
     pub fn handle_enter_vdraw(&mut self, memory: &Memory, registers: &Registers) {
         (self.obj_attributes_arr, self.rotation_scaling_params) =
             object_attributes::get_attributes(memory.obj_attributes.as_slice());
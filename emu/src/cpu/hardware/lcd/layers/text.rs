@@ -0,0 +1,238 @@
+//! Text (tiled) backgrounds: BG0 to BG3 in mode 0, BG0 and BG1 in mode 1.
+//!
+//! The screen is a map of 8x8 tiles, each map entry selecting the tile, its flips and,
+//! in 16 colors mode, the palette bank. In 256 colors mode tiles index the whole BG
+//! palette and the bank of the entry is ignored.
+
+use crate::bitwise::Bits;
+use crate::cpu::hardware::lcd::memory::Memory;
+use crate::cpu::hardware::lcd::registers::BgCnt;
+use crate::cpu::hardware::lcd::{Color, PixelInfo};
+
+/// Size of a map in tiles, in a 2 `KBytes` screen block.
+const SCREEN_BLOCK_TILES: usize = 32;
+const SCREEN_BLOCK_SIZE: usize = 0x800;
+const CHARACTER_BLOCK_SIZE: usize = 0x4000;
+
+/// Backgrounds can only fetch tiles from the first 64 `KBytes` of VRAM, the rest is
+/// OBJ tiles.
+const BG_VRAM_SIZE: usize = 0x1_0000;
+
+/// Settings of a text background, from its `BGxCNT`, `BGxHOFS` and `BGxVOFS`.
+pub struct TextBackground {
+    pub control: BgCnt,
+    pub horizontal_offset: u16,
+    pub vertical_offset: u16,
+}
+
+impl TextBackground {
+    pub fn render(&self, x: usize, y: usize, memory: &Memory) -> Option<PixelInfo> {
+        let (width, height) = match self.control.screen_size() {
+            0 => (256, 256),
+            1 => (512, 256),
+            2 => (256, 512),
+            _ => (512, 512),
+        };
+
+        // Offsets are 9 bits, the map repeats past its size.
+        let x = (x + usize::from(self.horizontal_offset.get_bits(0..=8))) % width;
+        let y = (y + usize::from(self.vertical_offset.get_bits(0..=8))) % height;
+
+        let entry = map_entry(
+            memory,
+            usize::from(self.control.screen_base_block()),
+            width,
+            x,
+            y,
+        );
+        let column = if entry.get_bit(10) { 7 - x % 8 } else { x % 8 };
+        let row = if entry.get_bit(11) { 7 - y % 8 } else { y % 8 };
+
+        let character_base =
+            usize::from(self.control.character_base_block()) * CHARACTER_BLOCK_SIZE;
+        let tile = usize::from(entry.get_bits(0..=9));
+
+        let palette_index = if self.control.single_palette() {
+            tile_color_8bpp(memory, character_base, tile, column, row)?
+        } else {
+            let bank = usize::from(entry.get_bits(12..=15));
+            bank * 16 + tile_color_4bpp(memory, character_base, tile, column, row)?
+        };
+
+        Some(PixelInfo {
+            color: bg_palette_color(memory, palette_index),
+            priority: self.control.priority() as u8,
+            semi_transparent: false,
+        })
+    }
+}
+
+/// Entry of the map at the dot (`x`, `y`) of the background. Maps larger than 256 dots
+/// span consecutive screen blocks: left to right, then top to bottom.
+fn map_entry(memory: &Memory, screen_base: usize, width: usize, x: usize, y: usize) -> u16 {
+    let blocks_per_row = width / 256;
+    let block = (y / 256) * blocks_per_row + x / 256;
+    let tile_x = (x % 256) / 8;
+    let tile_y = (y % 256) / 8;
+
+    let address =
+        (screen_base + block) * SCREEN_BLOCK_SIZE + (tile_y * SCREEN_BLOCK_TILES + tile_x) * 2;
+
+    u16::from_le_bytes([memory.video_ram[address], memory.video_ram[address + 1]])
+}
+
+/// Color number (1 to 15) of a dot in a 4 bits per pixel tile, `None` if transparent.
+fn tile_color_4bpp(
+    memory: &Memory,
+    character_base: usize,
+    tile: usize,
+    column: usize,
+    row: usize,
+) -> Option<usize> {
+    let address = character_base + tile * 32 + row * 4 + column / 2;
+    if address >= BG_VRAM_SIZE {
+        return None;
+    }
+
+    // The left dot of every pair is in the low nibble.
+    let byte = memory.video_ram[address];
+    let color = if column.is_multiple_of(2) {
+        byte & 0xF
+    } else {
+        byte >> 4
+    };
+
+    (color != 0).then_some(usize::from(color))
+}
+
+/// Color number (1 to 255) of a dot in an 8 bits per pixel tile, `None` if transparent.
+fn tile_color_8bpp(
+    memory: &Memory,
+    character_base: usize,
+    tile: usize,
+    column: usize,
+    row: usize,
+) -> Option<usize> {
+    let address = character_base + tile * 64 + row * 8 + column;
+    if address >= BG_VRAM_SIZE {
+        return None;
+    }
+
+    let color = memory.video_ram[address];
+
+    (color != 0).then_some(usize::from(color))
+}
+
+fn bg_palette_color(memory: &Memory, index: usize) -> Color {
+    Color::from_palette_color(u16::from_le_bytes([
+        memory.bg_palette_ram[index * 2],
+        memory.bg_palette_ram[index * 2 + 1],
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const COLORS_256: u16 = 1 << 7;
+
+    /// Every color of the BG palette is its own index.
+    fn memory() -> Memory {
+        let mut memory = Memory::default();
+        for index in 0..256_u16 {
+            let [low, high] = index.to_le_bytes();
+            memory.bg_palette_ram[usize::from(index) * 2] = low;
+            memory.bg_palette_ram[usize::from(index) * 2 + 1] = high;
+        }
+
+        memory
+    }
+
+    fn set_map_entry(memory: &mut Memory, screen_base: usize, index: usize, entry: u16) {
+        let address = screen_base * SCREEN_BLOCK_SIZE + index * 2;
+        memory.video_ram[address..address + 2].copy_from_slice(&entry.to_le_bytes());
+    }
+
+    /// Color index seen at a dot, `None` for transparent dots.
+    fn color_at(background: &TextBackground, memory: &Memory, x: usize, y: usize) -> Option<u16> {
+        background.render(x, y, memory).map(|pixel| pixel.color.0)
+    }
+
+    #[test]
+    fn tiles_4bpp_use_the_bank_of_the_entry() {
+        let mut memory = memory();
+        // Tile 1 at character base 1: the first row is 0, 1, 2 ... 7.
+        memory.video_ram[CHARACTER_BLOCK_SIZE + 32..CHARACTER_BLOCK_SIZE + 36]
+            .copy_from_slice(&[0x10, 0x32, 0x54, 0x76]);
+        // Tile 1 with palette bank 3, then tile 1 flipped horizontally with bank 5.
+        set_map_entry(&mut memory, 2, 0, 3 << 12 | 1);
+        set_map_entry(&mut memory, 2, 1, 5 << 12 | 1 << 10 | 1);
+
+        let background = TextBackground {
+            control: BgCnt(2 << 8 | 1 << 2 | 2),
+            horizontal_offset: 0,
+            vertical_offset: 0,
+        };
+
+        let row = (0..16)
+            .map(|x| color_at(&background, &memory, x, 0))
+            .collect::<Vec<_>>();
+        let mut expected = vec![None];
+        expected.extend((1..8).map(|color| Some(3 * 16 + color)));
+        expected.extend((1..8).rev().map(|color| Some(5 * 16 + color)));
+        expected.push(None);
+        assert_eq!(row, expected);
+
+        assert_eq!(background.render(1, 0, &memory).unwrap().priority, 2);
+    }
+
+    #[test]
+    fn tiles_8bpp_ignore_the_bank_of_the_entry() {
+        let mut memory = memory();
+        // Tile 2 (64 bytes per tile): row 5 is 0, 0x81, 0x82 ... 0x87.
+        let row = 2 * 64 + 5 * 8;
+        memory.video_ram[row..row + 8]
+            .copy_from_slice(&[0, 0x81, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87]);
+        // Vertically flipped, so row 5 of the tile is drawn on row 2.
+        set_map_entry(&mut memory, 4, 0, 7 << 12 | 1 << 11 | 2);
+
+        let background = TextBackground {
+            control: BgCnt(4 << 8 | COLORS_256),
+            horizontal_offset: 0,
+            vertical_offset: 0,
+        };
+
+        assert_eq!(color_at(&background, &memory, 0, 2), None);
+        assert_eq!(color_at(&background, &memory, 3, 2), Some(0x83));
+        assert_eq!(color_at(&background, &memory, 7, 2), Some(0x87));
+        assert_eq!(color_at(&background, &memory, 3, 5), None);
+    }
+
+    #[test]
+    fn scrolling_across_screen_blocks() {
+        let mut memory = memory();
+        // Tile 1 is filled with color 1, tile 2 with color 2.
+        memory.video_ram[32..64].fill(0x11);
+        memory.video_ram[64..96].fill(0x22);
+        // 512x256: screen block 8 is the left half, 9 the right half.
+        set_map_entry(&mut memory, 8, 0, 1);
+        set_map_entry(&mut memory, 9, 0, 2);
+
+        let background = TextBackground {
+            control: BgCnt(1 << 14 | 8 << 8),
+            horizontal_offset: 256,
+            vertical_offset: 0,
+        };
+        assert_eq!(color_at(&background, &memory, 0, 0), Some(2));
+        // Wrapping around to the left half.
+        assert_eq!(color_at(&background, &memory, 256, 0), Some(1));
+        // The map repeats vertically.
+        let background = TextBackground {
+            vertical_offset: 256,
+            ..background
+        };
+        assert_eq!(color_at(&background, &memory, 0, 0), Some(2));
+    }
+}
@@ -2,11 +2,13 @@
 
 use std::ops::{Index, IndexMut};
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use crate::bitwise::Bits;
 
-#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ObjMode {
     #[default]
     Normal,
@@ -27,7 +29,8 @@ impl From<u16> for ObjMode {
     }
 }
 
-#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum GfxMode {
     #[default]
     Normal,
@@ -42,13 +45,14 @@ impl TryFrom<u16> for GfxMode {
             0 => Ok(Self::Normal),
             1 => Ok(Self::AlphaBlending),
             2 => Ok(Self::ObjectWindow),
-            4 => Err("Forbidden GfxMode"),
+            3 => Err("Forbidden GfxMode"),
             _ => unreachable!(),
         }
     }
 }
 
-#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ColorMode {
     /// 16 colors
     #[default]
@@ -67,7 +71,8 @@ impl From<bool> for ColorMode {
     }
 }
 
-#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ObjShape {
     #[default]
     Square,
@@ -88,7 +93,8 @@ impl TryFrom<u16> for ObjShape {
     }
 }
 
-#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ObjSize {
     #[default]
     Size0,
@@ -110,7 +116,8 @@ impl From<u16> for ObjSize {
 }
 
 #[allow(dead_code)]
-#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ObjAttribute0 {
     pub y_coordinate: u8,
     pub obj_mode: ObjMode,
@@ -126,16 +133,17 @@ impl TryFrom<u16> for ObjAttribute0 {
         Ok(Self {
             y_coordinate: value.get_bits(0..=7) as u8,
             obj_mode: value.get_bits(8..=9).into(),
-            gfx_mode: value.get_bits(10..=11).try_into().unwrap(),
+            gfx_mode: value.get_bits(10..=11).try_into()?,
             obj_mosaic: value.get_bit(12),
             color_mode: value.get_bit(13).into(),
-            obj_shape: value.get_bits(14..=15).try_into().unwrap(),
+            obj_shape: value.get_bits(14..=15).try_into()?,
         })
     }
 }
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TransformationKind {
     RotationScaling {
         rotation_scaling_parameter: u8,
@@ -156,7 +164,8 @@ impl Default for TransformationKind {
 }
 
 #[allow(dead_code)]
-#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ObjAttribute1 {
     pub x_coordinate: u16,
     pub transformation_kind: TransformationKind,
@@ -182,7 +191,8 @@ impl ObjAttribute1 {
 }
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ObjAttribute2 {
     pub tile_number: u16,
     pub priority: u8,
@@ -211,7 +221,8 @@ impl From<u16> for ObjAttribute2 {
 }
 
 #[allow(dead_code)]
-#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ObjAttributes {
     pub attribute0: ObjAttribute0,
     pub attribute1: ObjAttribute1,
@@ -221,7 +232,7 @@ pub struct ObjAttributes {
 impl TryFrom<[u16; 3]> for ObjAttributes {
     type Error = &'static str;
     fn try_from(value: [u16; 3]) -> Result<Self, Self::Error> {
-        let obj_attribute0 = value[0].try_into().unwrap();
+        let obj_attribute0: ObjAttribute0 = value[0].try_into()?;
 
         Ok(Self {
             attribute0: obj_attribute0,
@@ -231,7 +242,8 @@ impl TryFrom<[u16; 3]> for ObjAttributes {
     }
 }
 
-#[derive(Default, Copy, Clone, Serialize, Deserialize)]
+#[derive(Default, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RotationScaling {
     pa: u16,
     pb: u16,
@@ -306,7 +318,16 @@ pub fn get_attributes(oam_memory: &[u8]) -> ([ObjAttributes; 128], [RotationScal
         })
         .enumerate()
     {
-        obj_attributes[idx] = [attribute0, attribute1, attribute2].try_into().unwrap();
+        // OBJs with a prohibited mode or shape aren't drawn.
+        obj_attributes[idx] = [attribute0, attribute1, attribute2]
+            .try_into()
+            .unwrap_or_else(|_| ObjAttributes {
+                attribute0: ObjAttribute0 {
+                    obj_mode: ObjMode::Disabled,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
         rotation_scalings[idx / 4][idx % 4] = rotation_scaling;
     }
 
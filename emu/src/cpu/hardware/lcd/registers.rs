@@ -1,27 +1,29 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::bitwise::Bits;
+use crate::cpu::hardware::bitfield::bitfield;
 
 use super::ObjMappingKind;
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Registers {
     /// LCD Control
-    pub dispcnt: u16,
+    pub dispcnt: DispCnt,
     /// Undocumented - Green Swap
     pub green_swap: u16,
     /// General LCD Status (STAT, LYC)
-    pub dispstat: u16,
+    pub dispstat: DispStat,
     /// Vertical Counter (LY)
     pub vcount: u16,
     /// BG0 Control
-    pub bg0cnt: u16,
+    pub bg0cnt: BgCnt,
     /// BG1 Control
-    pub bg1cnt: u16,
+    pub bg1cnt: BgCnt,
     /// BG2 Control
-    pub bg2cnt: u16,
+    pub bg2cnt: BgCnt,
     /// BG3 Control
-    pub bg3cnt: u16,
+    pub bg3cnt: BgCnt,
     /// BG0 `X-Offset`
     pub bg0hofs: u16,
     /// BG0 `Y_Offset`
@@ -77,80 +79,85 @@ pub struct Registers {
     /// Mosaic Size
     pub mosaic: u16,
     /// Color Special Effects Selection
-    pub bldcnt: u16,
+    pub bldcnt: BldCnt,
     /// Alpha Blending Coefficients
     pub bldalpha: u16,
     /// Brightness (Fade-In/Out) Coefficient
     pub bldy: u16,
 }
 
-impl Registers {
-    pub(super) fn get_bg0_enabled(&self) -> bool {
-        self.dispcnt.get_bit(8)
-    }
-
-    pub(super) fn get_bg1_enabled(&self) -> bool {
-        self.dispcnt.get_bit(9)
-    }
-
-    pub(super) fn get_bg2_enabled(&self) -> bool {
-        self.dispcnt.get_bit(10)
-    }
-
-    pub(super) fn get_bg3_enabled(&self) -> bool {
-        self.dispcnt.get_bit(11)
-    }
-
-    pub(super) fn get_obj_enabled(&self) -> bool {
-        self.dispcnt.get_bit(12)
-    }
-
-    pub(super) fn get_win0_enabled(&self) -> bool {
-        self.dispcnt.get_bit(13)
-    }
-
-    pub(super) fn get_win1_enabled(&self) -> bool {
-        self.dispcnt.get_bit(14)
-    }
-
-    pub(super) fn get_winobj_enabled(&self) -> bool {
-        self.dispcnt.get_bit(15)
-    }
-
-    /// Info about vram fields used to render display.
-    pub(super) fn get_bg_mode(&self) -> u8 {
-        self.dispcnt.get_bits(0..=2).try_into().unwrap()
-    }
-
-    pub(super) fn get_obj_character_vram_mapping(&self) -> ObjMappingKind {
-        self.dispcnt.get_bit(6).into()
-    }
-
-    pub(super) fn get_vcount_setting(&self) -> u8 {
-        self.dispstat.get_byte(1)
+bitfield! {
+    /// `DISPCNT`
+    pub struct DispCnt(u16) {
+        bg_mode: 0..=2;
+        cgb_mode: 3;
+        frame_select: 4;
+        hblank_interval_free: 5;
+        /// One-dimensional OBJ character mapping, see [`ObjMappingKind`].
+        obj_mapping_1d: 6;
+        forced_blank: 7;
+        bg0_enabled: 8;
+        bg1_enabled: 9;
+        bg2_enabled: 10;
+        bg3_enabled: 11;
+        obj_enabled: 12;
+        win0_enabled: 13;
+        win1_enabled: 14;
+        winobj_enabled: 15;
     }
+}
 
-    pub(super) fn get_vblank_irq_enable(&self) -> bool {
-        self.dispstat.get_bit(3)
+impl DispCnt {
+    pub(super) fn obj_character_vram_mapping(self) -> ObjMappingKind {
+        self.obj_mapping_1d().into()
     }
+}
 
-    pub(super) fn get_hblank_irq_enable(&self) -> bool {
-        self.dispstat.get_bit(4)
+bitfield! {
+    /// `DISPSTAT`, the flags are set by the LCD.
+    pub struct DispStat(u16) {
+        vblank_flag, set_vblank_flag: 0;
+        hblank_flag, set_hblank_flag: 1;
+        vcounter_flag, set_vcounter_flag: 2;
+        vblank_irq_enable: 3;
+        hblank_irq_enable: 4;
+        vcounter_irq_enable: 5;
+        vcount_setting: 8..=15;
     }
+}
 
-    pub(super) fn get_vcounter_irq_enable(&self) -> bool {
-        self.dispstat.get_bit(5)
+bitfield! {
+    /// `BGxCNT`
+    pub struct BgCnt(u16) {
+        priority: 0..=1;
+        /// In units of 16 `KBytes`.
+        character_base_block: 2..=3;
+        mosaic: 6;
+        /// 256 colors, 16 palettes of 16 colors otherwise.
+        single_palette: 7;
+        /// In units of 2 `KBytes`.
+        screen_base_block: 8..=12;
+        display_area_overflow: 13;
+        screen_size: 14..=15;
     }
+}
 
-    pub(super) fn set_vblank_flag(&mut self, value: bool) {
-        self.dispstat.set_bit(0, value);
+bitfield! {
+    /// `BLDCNT`, one bit per layer for the targets, see [`Self::first_target`].
+    pub struct BldCnt(u16) {
+        first_targets: 0..=5;
+        effect: 6..=7;
+        second_targets: 8..=13;
     }
+}
 
-    pub(super) fn set_hblank_flag(&mut self, value: bool) {
-        self.dispstat.set_bit(1, value);
+impl BldCnt {
+    /// Whether the layer with bit `layer` in the targets is a first target.
+    pub const fn first_target(self, layer: u8) -> bool {
+        self.first_targets() & (1 << layer) != 0
     }
 
-    pub(super) fn set_vcounter_flag(&mut self, value: bool) {
-        self.dispstat.set_bit(2, value);
+    pub const fn second_target(self, layer: u8) -> bool {
+        self.second_targets() & (1 << layer) != 0
     }
 }
@@ -1,4 +1,9 @@
+pub mod bitfield;
+pub mod component;
+pub mod debug_print;
 pub mod dma;
+pub mod flash;
+pub mod gpio;
 pub mod internal_memory;
 pub mod interrupt_control;
 pub mod keypad;
@@ -9,7 +14,14 @@ pub mod keypad;
 #[allow(clippy::cast_possible_wrap)]
 #[allow(clippy::large_stack_frames)]
 pub mod lcd;
+pub mod peripheral;
+pub mod prefetch;
+
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+pub mod rtc;
 pub mod serial;
+pub mod solar;
 pub mod sound;
 pub mod timers;
 
@@ -0,0 +1,55 @@
+//! Add-on hardware of the cartridge: real-time clock, rumble, tilt or solar sensors...
+//!
+//! Peripherals are registered with the cartridge, see
+//! [`InternalMemory::add_peripheral`](super::internal_memory::InternalMemory::add_peripheral).
+//! Each one claims a few addresses of the cartridge bus, such as the GPIO port in the ROM
+//! or a range of the SRAM region, and answers the accesses there in place of the ROM or
+//! the save memory. The cartridge steps them with the bus and stores their state in
+//! their own part of the save-state.
+
+use super::component::StepOutput;
+
+pub trait CartridgePeripheral: Send {
+    /// Identifies the peripheral in save-states, unique among the peripherals of a
+    /// cartridge.
+    fn name(&self) -> &'static str;
+
+    /// Whether the peripheral answers at `address`, a bus address in the ROM (mirrors
+    /// included) or SRAM regions.
+    fn claims(&self, address: u32) -> bool;
+
+    /// Reads the byte at a claimed `address`. `None` lets the ROM or the save memory
+    /// answer, for ports that can't be read back.
+    fn read(&self, address: u32) -> Option<u8>;
+
+    /// Writes the byte at a claimed `address`.
+    fn write(&mut self, address: u32, value: u8);
+
+    /// Advances the peripheral by `cycles` CPU cycles, it can request the Game Pak
+    /// interrupt.
+    fn step(&mut self, cycles: u32) -> StepOutput {
+        let _ = cycles;
+
+        StepOutput::default()
+    }
+
+    /// Goes back to the power-on state. A soft reset keeps what the cartridge keeps
+    /// powered, like the time of a clock.
+    fn reset(&mut self, hard: bool) {
+        let _ = hard;
+    }
+
+    /// Content of the peripheral's part of the save-state.
+    ///
+    /// # Errors
+    /// It fails if the peripheral can't be serialized.
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> bincode::Result<Vec<u8>>;
+
+    /// Restores the peripheral from its part of the save-state.
+    ///
+    /// # Errors
+    /// It fails if `data` isn't valid, the peripheral is untouched in this case.
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()>;
+}
@@ -0,0 +1,121 @@
+//! Prefetch unit of the cartridge bus, enabled by bit 14 of `WAITCNT`.
+//!
+//! While the CPU runs code from the cartridge, the unit reads the next half-words in the
+//! cycles the cartridge bus is free: internal cycles and accesses to the other regions.
+//! Up to 8 half-words are buffered and a fetch found in the buffer takes a single cycle
+//! per half-word, which is why games run Thumb code from ROM: an ARM instruction takes
+//! two half-words from the buffer, and twice the waitstates when it misses.
+//!
+//! A data access to the cartridge stops the unit, and a fetch that isn't in the buffer
+//! restarts it after the fetched instruction.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Half-words the buffer holds.
+const CAPACITY: u32 = 8;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Prefetch {
+    /// Address of the first buffered half-word, `None` when the unit is stopped.
+    head: Option<u32>,
+    /// Half-words buffered from `head`.
+    buffered: u32,
+    /// Cycles spent reading the half-word after the buffered ones.
+    progress: u32,
+}
+
+impl Prefetch {
+    /// Starts buffering from `address`, dropping the buffer.
+    pub const fn restart(&mut self, address: u32) {
+        *self = Self {
+            head: Some(address),
+            buffered: 0,
+            progress: 0,
+        };
+    }
+
+    /// Stops the unit and drops the buffer.
+    pub const fn stop(&mut self) {
+        self.head = None;
+        self.buffered = 0;
+        self.progress = 0;
+    }
+
+    /// Address the unit reads next, `None` when it's stopped or the buffer is full.
+    #[must_use]
+    pub const fn next_address(&self) -> Option<u32> {
+        match self.head {
+            Some(head) if self.buffered < CAPACITY => Some(head + 2 * self.buffered),
+            _ => None,
+        }
+    }
+
+    /// Spends a free cycle of the cartridge bus reading the next half-word, which takes
+    /// `access_cycles` cycles.
+    pub const fn tick(&mut self, access_cycles: u32) {
+        if self.next_address().is_none() {
+            return;
+        }
+
+        self.progress += 1;
+        if self.progress >= access_cycles {
+            self.progress = 0;
+            self.buffered += 1;
+        }
+    }
+
+    /// Takes the `halfwords` at `address` from the buffer, `false` if they aren't all
+    /// buffered yet.
+    pub const fn take(&mut self, address: u32, halfwords: u32) -> bool {
+        match self.head {
+            Some(head) if head == address && self.buffered >= halfwords => {
+                self.head = Some(head + 2 * halfwords);
+                self.buffered -= halfwords;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    #[must_use]
+    pub const fn buffered(&self) -> u32 {
+        self.buffered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_while_the_bus_is_free() {
+        let mut prefetch = Prefetch::default();
+        prefetch.tick(3);
+        assert_eq!(prefetch.buffered(), 0);
+
+        prefetch.restart(0x0800_0010);
+        for _ in 0..5 {
+            prefetch.tick(3);
+        }
+        assert_eq!(prefetch.buffered(), 1);
+        assert_eq!(prefetch.next_address(), Some(0x0800_0012));
+
+        // Not sequential to the buffer.
+        assert!(!prefetch.take(0x0800_0000, 1));
+        // A word needs two half-words.
+        assert!(!prefetch.take(0x0800_0010, 2));
+        assert!(prefetch.take(0x0800_0010, 1));
+        assert_eq!(prefetch.buffered(), 0);
+
+        for _ in 0..100 {
+            prefetch.tick(3);
+        }
+        assert_eq!(prefetch.buffered(), 8);
+        assert_eq!(prefetch.next_address(), None);
+
+        prefetch.stop();
+        assert!(!prefetch.take(0x0800_0012, 1));
+    }
+}
@@ -0,0 +1,520 @@
+//! The real-time clock of the cartridge (Seiko S-3511), on the [`GpioPort`](super::gpio::GpioPort):
+//! the day and night of Pokémon Ruby, Sapphire and Emerald, the sun of Boktai.
+//!
+//! The game clocks commands and data in and out serially: SCK on line 0, SIO on line 1
+//! and CS on line 2. A transfer starts when CS goes high with the command byte, most
+//! significant bit first, `0110 CCC R` with `R` set to read. The data bytes follow, in
+//! BCD and least significant bit first:
+//! - command 0 resets the clock to 2000-01-01 00:00:00,
+//! - 1 reads or writes the status register,
+//! - 2 the date and the time: year, month, day, day of the week, hour, minute, second,
+//! - 3 the time only.
+//!
+//! The clock runs with the host time, shifted by what the game set: see [`RtcClock`].
+
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::gpio::GpioDevice;
+
+const SCK: u8 = 0b001;
+const SIO: u8 = 0b010;
+const CS: u8 = 0b100;
+
+/// Commands are `0110 CCC R`.
+const COMMAND_MAGIC: u8 = 0b0110;
+
+const RESET: u8 = 0;
+const STATUS: u8 = 1;
+const DATE_TIME: u8 = 2;
+const TIME: u8 = 3;
+
+/// The hours run from 0 to 23, from 1 to 12 with the PM flag otherwise.
+const STATUS_24_HOURS: u8 = 1 << 6;
+/// Bits of the status register the game can write, the interrupt settings and the
+/// 24-hour mode. Bit 7, the power failure flag, is never set by the emulated clock.
+const STATUS_WRITABLE: u8 = 0b0110_1010;
+/// Set in the hour read in the 12-hour mode after noon.
+const PM: u8 = 1 << 7;
+
+/// Seconds from 1970-01-01 to 2000-01-01, where the clock starts after a reset.
+const EPOCH_2000: i64 = 946_684_800;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Game codes of the cartridges with a clock, without the region letter.
+const GAMES: [&str; 9] = [
+    "AXV", // Pokémon Ruby
+    "AXP", // Pokémon Sapphire
+    "BPE", // Pokémon Emerald
+    "U3I", // Boktai
+    "U32", // Boktai 2
+    "U33", // Boktai 3
+    "BR4", // Rockman EXE 4.5
+    "BKA", // Sennen Kazoku
+    "BRK", // Shin Bokura no Taiyou
+];
+
+/// Whether the cartridge of `game_code` has a clock.
+#[must_use]
+pub fn detect(game_code: &str) -> bool {
+    GAMES.iter().any(|game| game_code.starts_with(game))
+}
+
+#[derive(Default)]
+struct SharedClock {
+    offset: AtomicI64,
+    changed: AtomicBool,
+}
+
+/// The time of the clock, shared with the frontend: the time of the host in UTC, shifted
+/// by an offset in seconds that changes when the game sets the clock.
+///
+/// The offset is all there is to keep for the clock to go on where it was at the next
+/// run, see [`Gba::rtc_clock`](crate::gba::Gba::rtc_clock).
+#[derive(Clone, Default)]
+pub struct RtcClock(Arc<SharedClock>);
+
+impl RtcClock {
+    #[must_use]
+    pub fn offset(&self) -> i64 {
+        self.0.offset.load(Ordering::Acquire)
+    }
+
+    pub fn set_offset(&self, offset: i64) {
+        self.0.offset.store(offset, Ordering::Release);
+        self.0.changed.store(true, Ordering::Release);
+    }
+
+    /// Whether the offset changed since the last call.
+    #[must_use]
+    pub fn take_changed(&self) -> bool {
+        self.0.changed.swap(false, Ordering::AcqRel)
+    }
+}
+
+fn host_seconds() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs().cast_signed())
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum Phase {
+    /// CS is low.
+    #[default]
+    Idle,
+    /// Shifting in the command byte.
+    Command,
+    /// Shifting the data of `command` in or out.
+    Data { command: u8, reading: bool },
+    /// The transfer is over, waiting for CS to go low.
+    Done,
+}
+
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct State {
+    status: u8,
+    /// Lines of the last write, to find the edges of SCK and CS.
+    lines: u8,
+    phase: Phase,
+    /// Byte being shifted and how many of its bits already were.
+    shift: u8,
+    bits: u8,
+    /// Bytes of the data, and the one being shifted.
+    data: [u8; 7],
+    index: usize,
+    /// Level of SIO while the game reads.
+    output: u8,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            status: STATUS_24_HOURS,
+            lines: 0,
+            phase: Phase::Idle,
+            shift: 0,
+            bits: 0,
+            data: [0; 7],
+            index: 0,
+            output: 0,
+        }
+    }
+}
+
+/// The S-3511 clock, plugged on the [`GpioPort`](super::gpio::GpioPort).
+pub struct Rtc {
+    state: State,
+    clock: RtcClock,
+    /// Seconds since 1970-01-01 on the host.
+    host_time: fn() -> i64,
+}
+
+impl Rtc {
+    /// A clock running at the time of `clock`.
+    #[must_use]
+    pub fn new(clock: RtcClock) -> Self {
+        Self {
+            state: State::default(),
+            clock,
+            host_time: host_seconds,
+        }
+    }
+
+    fn now(&self) -> i64 {
+        (self.host_time)() + self.clock.offset()
+    }
+
+    fn set_now(&self, seconds: i64) {
+        self.clock.set_offset(seconds - (self.host_time)());
+    }
+
+    const fn length(command: u8) -> usize {
+        match command {
+            STATUS => 1,
+            DATE_TIME => 7,
+            TIME => 3,
+            _ => 0,
+        }
+    }
+
+    fn start(&mut self, command: u8, reading: bool) {
+        self.state.index = 0;
+        if reading {
+            let date_time = self.date_time();
+            match command {
+                STATUS => self.state.data[0] = self.state.status,
+                DATE_TIME => self.state.data = date_time,
+                TIME => self.state.data[..3].copy_from_slice(&date_time[4..]),
+                _ => {}
+            }
+        } else if command == RESET {
+            self.state.status = 0;
+            self.set_now(EPOCH_2000);
+        }
+
+        self.state.phase = if Self::length(command) == 0 {
+            Phase::Done
+        } else {
+            Phase::Data { command, reading }
+        };
+    }
+
+    fn finish_write(&mut self, command: u8) {
+        let data = self.state.data;
+        match command {
+            STATUS => self.state.status = data[0] & STATUS_WRITABLE,
+            DATE_TIME => self.set_now(self.seconds(data)),
+            TIME => {
+                let mut date_time = self.date_time();
+                date_time[4..].copy_from_slice(&data[..3]);
+                self.set_now(self.seconds(date_time));
+            }
+            _ => {}
+        }
+    }
+
+    /// A rising edge of SCK during a transfer.
+    fn clock_bit(&mut self, sio: u8) {
+        match self.state.phase {
+            Phase::Command => {
+                self.state.shift = self.state.shift << 1 | sio;
+                self.state.bits += 1;
+                if self.state.bits == 8 {
+                    let byte = self.state.shift;
+                    self.state.bits = 0;
+                    if byte >> 4 == COMMAND_MAGIC {
+                        self.start(byte >> 1 & 0b111, byte & 1 != 0);
+                    } else {
+                        self.state.phase = Phase::Done;
+                    }
+                }
+            }
+            Phase::Data { command, reading } => {
+                let bit = self.state.bits;
+                if reading {
+                    self.state.output = self.state.data[self.state.index] >> bit & 1;
+                } else {
+                    self.state.data[self.state.index] &= !(1 << bit);
+                    self.state.data[self.state.index] |= sio << bit;
+                }
+                self.state.bits += 1;
+                if self.state.bits == 8 {
+                    self.state.bits = 0;
+                    self.state.index += 1;
+                    if self.state.index == Self::length(command) {
+                        if !reading {
+                            self.finish_write(command);
+                        }
+                        self.state.phase = Phase::Done;
+                    }
+                }
+            }
+            Phase::Idle | Phase::Done => {}
+        }
+    }
+
+    /// The registers of the date and the time, in BCD.
+    fn date_time(&self) -> [u8; 7] {
+        let now = self.now();
+        let days = now.div_euclid(SECONDS_PER_DAY);
+        let time = now.rem_euclid(SECONDS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        // 1970-01-01 was a Thursday, Sunday is 0.
+        let weekday = (days + 4).rem_euclid(7);
+        let hour = time / 3600;
+        let hour = if self.state.status & STATUS_24_HOURS == 0 {
+            bcd(hour % 12) | if hour >= 12 { PM } else { 0 }
+        } else {
+            bcd(hour)
+        };
+
+        [
+            bcd(year.rem_euclid(100)),
+            bcd(i64::from(month)),
+            bcd(i64::from(day)),
+            bcd(weekday),
+            hour,
+            bcd(time / 60 % 60),
+            bcd(time % 60),
+        ]
+    }
+
+    /// Seconds since 1970-01-01 of the date and the time registers, out of range values
+    /// clamped.
+    fn seconds(&self, date_time: [u8; 7]) -> i64 {
+        let [year, month, day, _, hour, minute, second] = date_time;
+        let mut hours = from_bcd(hour & !PM).min(23);
+        if self.state.status & STATUS_24_HOURS == 0 && hour & PM != 0 {
+            hours = hours % 12 + 12;
+        }
+
+        let days = days_from_civil(
+            2000 + from_bcd(year).min(99),
+            from_bcd(month).clamp(1, 12) as u32,
+            from_bcd(day).clamp(1, 31) as u32,
+        );
+
+        days * SECONDS_PER_DAY
+            + hours * 3600
+            + from_bcd(minute).min(59) * 60
+            + from_bcd(second).min(59)
+    }
+}
+
+impl GpioDevice for Rtc {
+    fn name(&self) -> &'static str {
+        "rtc"
+    }
+
+    fn write(&mut self, lines: u8) {
+        let previous = self.state.lines;
+        self.state.lines = lines;
+
+        if lines & CS == 0 {
+            self.state.phase = Phase::Idle;
+            return;
+        }
+        if previous & CS == 0 {
+            self.state.phase = Phase::Command;
+            self.state.shift = 0;
+            self.state.bits = 0;
+        }
+        if previous & SCK == 0 && lines & SCK != 0 {
+            self.clock_bit((lines & SIO) >> 1);
+        }
+    }
+
+    fn read(&self) -> u8 {
+        match self.state.phase {
+            Phase::Data { reading: true, .. } | Phase::Done => self.state.output << 1,
+            _ => 0,
+        }
+    }
+
+    fn reset(&mut self, _hard: bool) {
+        // The clock has its own battery, only the transfer stops.
+        self.state.phase = Phase::Idle;
+        self.state.lines = 0;
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> bincode::Result<Vec<u8>> {
+        bincode::serialize(&(self.state, self.clock.offset()))
+    }
+
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        let (state, offset) = bincode::deserialize(data)?;
+        self.state = state;
+        self.clock.set_offset(offset);
+
+        Ok(())
+    }
+}
+
+const fn bcd(value: i64) -> u8 {
+    (value / 10 * 16 + value % 10) as u8
+}
+
+fn from_bcd(value: u8) -> i64 {
+    i64::from(value >> 4) * 10 + i64::from(value & 0xF)
+}
+
+/// Days since 1970-01-01 of a date of the proleptic Gregorian calendar, after Howard
+/// Hinnant's `days_from_civil`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * if month > 2 { month - 3 } else { month + 9 } + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The year, month and day of `days` since 1970-01-01, see [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+
+    (
+        year_of_era + era * 400 + i64::from(month <= 2),
+        month as u32,
+        day as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// 2024-02-29 23:59:58 UTC, a Thursday.
+    const NOW: i64 = 1_709_251_198;
+
+    fn rtc() -> Rtc {
+        Rtc {
+            host_time: || NOW,
+            ..Rtc::new(RtcClock::default())
+        }
+    }
+
+    /// Sends the command byte, most significant bit first, as the games do.
+    fn command(rtc: &mut Rtc, byte: u8) {
+        rtc.write(SCK);
+        rtc.write(SCK | CS);
+        for bit in (0..8).rev() {
+            let sio = (byte >> bit & 1) << 1;
+            rtc.write(CS | sio);
+            rtc.write(CS | SCK | sio);
+        }
+    }
+
+    fn write_bytes(rtc: &mut Rtc, bytes: &[u8]) {
+        for byte in bytes {
+            for bit in 0..8 {
+                let sio = (byte >> bit & 1) << 1;
+                rtc.write(CS | sio);
+                rtc.write(CS | SCK | sio);
+            }
+        }
+        rtc.write(SCK);
+    }
+
+    fn read_bytes(rtc: &mut Rtc, count: usize) -> Vec<u8> {
+        let bytes = (0..count)
+            .map(|_| {
+                (0..8).fold(0, |byte, bit| {
+                    rtc.write(CS);
+                    rtc.write(CS | SCK);
+                    byte | (rtc.read() & SIO) >> 1 << bit
+                })
+            })
+            .collect();
+        rtc.write(SCK);
+
+        bytes
+    }
+
+    #[test]
+    fn calendar() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 1, 1) * SECONDS_PER_DAY, EPOCH_2000);
+        assert_eq!(civil_from_days(NOW / SECONDS_PER_DAY), (2024, 2, 29));
+        assert_eq!(
+            civil_from_days(days_from_civil(2099, 12, 31)),
+            (2099, 12, 31)
+        );
+    }
+
+    #[test]
+    fn reads_the_host_time() {
+        let mut rtc = rtc();
+        command(&mut rtc, 0b0110_0101);
+        assert_eq!(
+            read_bytes(&mut rtc, 7),
+            [0x24, 0x02, 0x29, 0x04, 0x23, 0x59, 0x58]
+        );
+
+        command(&mut rtc, 0b0110_0011);
+        assert_eq!(read_bytes(&mut rtc, 1), [STATUS_24_HOURS]);
+    }
+
+    #[test]
+    fn writes_shift_the_clock() {
+        let clock = RtcClock::default();
+        let mut rtc = Rtc {
+            host_time: || NOW,
+            ..Rtc::new(clock.clone())
+        };
+
+        // 12-hour mode, then 2001-03-04 1:02:03 PM.
+        command(&mut rtc, 0b0110_0010);
+        write_bytes(&mut rtc, &[0]);
+        command(&mut rtc, 0b0110_0100);
+        write_bytes(&mut rtc, &[0x01, 0x03, 0x04, 0x00, 0x81, 0x02, 0x03]);
+        assert!(clock.take_changed());
+        assert_eq!(
+            clock.offset(),
+            days_from_civil(2001, 3, 4) * SECONDS_PER_DAY + 13 * 3600 + 2 * 60 + 3 - NOW
+        );
+
+        command(&mut rtc, 0b0110_0111);
+        assert_eq!(read_bytes(&mut rtc, 3), [0x81, 0x02, 0x03]);
+
+        command(&mut rtc, 0b0110_0000);
+        command(&mut rtc, 0b0110_0101);
+        assert_eq!(
+            read_bytes(&mut rtc, 7),
+            [0x00, 0x01, 0x01, 0x06, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn detects_the_games_with_a_clock() {
+        assert!(detect("BPEE"));
+        assert!(detect("U3IJ"));
+        assert!(!detect("BPRE"));
+    }
+}
@@ -1,6 +1,60 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Serialize, Deserialize)]
+use crate::bitwise::Bits;
+use crate::cpu::hardware::component::{HardwareComponent, StepOutput};
+use crate::cpu::hardware::interrupt_control::IrqType;
+
+use self::link_cable::{LinkTransport, LinkWords};
+use self::session::WirelessTransport;
+use self::uart::{Uart, UartControl, UartHost};
+use self::wireless_adapter::WirelessAdapter;
+
+pub mod link_cable;
+pub mod session;
+pub mod tcp_link;
+pub mod uart;
+mod wireless_adapter;
+
+/// CPU cycles needed to shift a single bit with the internal clock at 256KHz.
+const CYCLES_PER_BIT_256KHZ: u32 = 64;
+
+/// CPU cycles needed to shift a single bit with the internal clock at 2MHz.
+const CYCLES_PER_BIT_2MHZ: u32 = 8;
+
+/// Device plugged in the serial port.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SerialPeripheral {
+    /// Nothing is connected: every transfer completes right away and reads
+    /// back all ones (the lines are pulled up), so games probing for
+    /// a device give up instead of waiting forever.
+    #[default]
+    Absent,
+    /// The wireless adapter (RFU). Alone it completes the login handshake and
+    /// acknowledges every command without ever finding other players,
+    /// see [`Serial::connect_wireless`] to join a session.
+    WirelessAdapter,
+    /// A link cable to other consoles, see [`Serial::connect_link`]. Without them the
+    /// transfers complete as with nothing plugged.
+    LinkCable,
+    /// A host pipe on TXD and RXD, for the UART mode, see [`Serial::connect_uart`]. The
+    /// other modes see nothing plugged.
+    Uart,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SerialMode {
+    Normal8,
+    Normal32,
+    Multiplayer,
+    Uart,
+    GeneralPurpose,
+    JoyBus,
+}
+
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Serial {
     // This is SIODATA32 when single-player mode or two different 16bits registers in multiplayer mode
     // SIOMULTI0 and SIOMULTI1
@@ -16,4 +70,603 @@ pub struct Serial {
     pub sio_joy_bus_receive_data: u32,
     pub sio_joy_bus_transmit_data: u32,
     pub sio_joy_bus_receive_status: u16,
+
+    pub peripheral: SerialPeripheral,
+    wireless_adapter: WirelessAdapter,
+    /// The other end of the link cable, see [`Self::connect_link`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    link: Option<Box<dyn LinkTransport>>,
+    uart: Uart,
+    /// Remaining cycles of the transfer in progress, if any.
+    transfer_cycles_left: Option<u32>,
+}
+
+impl Serial {
+    #[must_use]
+    pub fn mode(&self) -> SerialMode {
+        if self.sio_mode_select.get_bit(15) {
+            if self.sio_mode_select.get_bit(14) {
+                SerialMode::JoyBus
+            } else {
+                SerialMode::GeneralPurpose
+            }
+        } else {
+            match self.sio_control_register.get_bits(12..=13) {
+                0b00 => SerialMode::Normal8,
+                0b01 => SerialMode::Normal32,
+                0b10 => SerialMode::Multiplayer,
+                _ => SerialMode::Uart,
+            }
+        }
+    }
+
+    pub fn connect(&mut self, peripheral: SerialPeripheral) {
+        self.peripheral = peripheral;
+        self.wireless_adapter = WirelessAdapter::default();
+        self.link = None;
+        self.uart.set_host(None);
+    }
+
+    /// Plugs the wireless adapter and lets it reach the others through `transport`.
+    pub fn connect_wireless(&mut self, transport: Box<dyn WirelessTransport>) {
+        self.connect(SerialPeripheral::WirelessAdapter);
+        self.wireless_adapter.set_transport(Some(transport));
+    }
+
+    /// Plugs the link cable reaching the other consoles through `transport`.
+    pub fn connect_link(&mut self, transport: Box<dyn LinkTransport>) {
+        self.connect(SerialPeripheral::LinkCable);
+        self.link = Some(transport);
+        self.publish_outgoing();
+    }
+
+    /// Connects TXD and RXD to `host`, for the UART mode.
+    pub fn connect_uart(&mut self, host: Box<dyn UartHost>) {
+        self.connect(SerialPeripheral::Uart);
+        self.uart.set_host(Some(host));
+    }
+
+    /// The CPU or a DMA read `width` bytes at `address`: in the UART mode, reading
+    /// SIODATA8 takes the byte received and reading SIOCNT clears the error flag.
+    pub fn acknowledge_read(&mut self, address: u32, width: u8) {
+        let covers = |register: u32| register.wrapping_sub(address) < u32::from(width);
+        if !(covers(0x0400_0128) || covers(0x0400_012A)) || self.mode() != SerialMode::Uart {
+            return;
+        }
+
+        if covers(0x0400_0128) {
+            self.uart.clear_error();
+        }
+        if covers(0x0400_012A) {
+            self.uart.pop_data();
+        }
+    }
+
+    /// The console on the other end of the link cable shifts out, `None` while alone.
+    fn link_parent(&self) -> Option<bool> {
+        self.link
+            .as_ref()
+            .filter(|link| link.players() > 1)
+            .map(|link| link.player() == 0)
+    }
+
+    /// Gives the link cable the word the next transfer shifts out in the current mode.
+    fn publish_outgoing(&mut self) {
+        let word = match self.mode() {
+            SerialMode::Normal8 => u32::from(self.sio_multi_data_send_data_8 & 0xFF),
+            SerialMode::Normal32 => self.sio_data_32_multi_data_0_data_1,
+            SerialMode::Multiplayer => u32::from(self.sio_multi_data_send_data_8),
+            SerialMode::Uart | SerialMode::GeneralPurpose | SerialMode::JoyBus => return,
+        };
+        if let Some(link) = &mut self.link {
+            link.set_outgoing(word);
+        }
+    }
+
+    /// Advances by a cycle, requesting the serial interrupt in `output` at the end of a
+    /// transfer if enabled.
+    fn step_cycle(&mut self, output: &mut StepOutput) {
+        // SIOCNT holds the UART settings, bit 2 is CTS set by the game.
+        if self.mode() == SerialMode::Uart {
+            if self.uart.step(&UartControl(self.sio_control_register)) {
+                output.serial_transfer_completed = true;
+                if self.sio_control_register.get_bit(14) {
+                    output.request_interrupt(IrqType::Serial);
+                }
+            }
+            return;
+        }
+
+        // SI is pulled up when nothing drives it, the adapter keeps it low when ready.
+        // On the cable it is low for the parent only, SD high once others are plugged.
+        let link_parent = self.link_parent();
+        self.sio_control_register.set_bit(
+            2,
+            match self.peripheral {
+                SerialPeripheral::Absent | SerialPeripheral::Uart => true,
+                SerialPeripheral::WirelessAdapter => false,
+                SerialPeripheral::LinkCable => link_parent.is_some_and(|parent| !parent),
+            },
+        );
+        if self.peripheral == SerialPeripheral::LinkCable {
+            self.sio_control_register.set_bit(3, link_parent.is_some());
+        }
+
+        if link_parent == Some(false) {
+            if let Some(words) = self.link.as_mut().and_then(|link| link.receive()) {
+                self.receive_link_transfer(&words, output);
+            }
+            return;
+        }
+
+        match self.transfer_cycles_left {
+            None => {
+                if self.sio_control_register.get_bit(7) {
+                    self.transfer_cycles_left = self.transfer_duration();
+                }
+            }
+            Some(0) => {
+                self.transfer_cycles_left = None;
+                match self.link.as_mut().filter(|link| link.players() > 1) {
+                    Some(link) => {
+                        let words = link.transfer();
+                        self.apply_link_words(&words);
+                    }
+                    None => self.complete_transfer(),
+                }
+                self.end_transfer(output);
+            }
+            Some(ref mut cycles) => *cycles -= 1,
+        }
+    }
+
+    fn end_transfer(&mut self, output: &mut StepOutput) {
+        self.sio_control_register.set_bit_off(7);
+
+        output.serial_transfer_completed = true;
+        if self.sio_control_register.get_bit(14) {
+            output.request_interrupt(IrqType::Serial);
+        }
+    }
+
+    /// A transfer clocked by the parent, received by a child: in the normal modes only
+    /// if it started its side with the external clock, in the multiplayer mode always.
+    fn receive_link_transfer(&mut self, words: &LinkWords, output: &mut StepOutput) {
+        let started = self.sio_control_register.get_bit(7);
+        let received = match self.mode() {
+            SerialMode::Normal8 | SerialMode::Normal32 => {
+                started && !self.sio_control_register.get_bit(0)
+            }
+            SerialMode::Multiplayer => true,
+            SerialMode::Uart | SerialMode::GeneralPurpose | SerialMode::JoyBus => false,
+        };
+        if received {
+            self.apply_link_words(words);
+            self.end_transfer(output);
+        }
+    }
+
+    /// Fills the data registers with what the other consoles shifted out.
+    fn apply_link_words(&mut self, words: &LinkWords) {
+        let Some(player) = self.link.as_ref().map(|link| link.player()) else {
+            return;
+        };
+        // In the normal modes the cable only links two consoles, crossed.
+        let other = words[usize::from(player == 0)];
+
+        match self.mode() {
+            SerialMode::Normal8 => {
+                let received = other.map_or(0xFF, |word| word as u8);
+                self.sio_multi_data_send_data_8.set_byte(0, received);
+            }
+            SerialMode::Normal32 => {
+                self.sio_data_32_multi_data_0_data_1 = other.unwrap_or(0xFFFF_FFFF);
+            }
+            SerialMode::Multiplayer => {
+                let [first, second, third, fourth] =
+                    words.map(|word| word.map_or(0xFFFF, |word| word as u16));
+                self.sio_data_32_multi_data_0_data_1 = u32::from(first) | (u32::from(second) << 16);
+                self.sio_multi_data_2 = third;
+                self.sio_multi_data_3 = fourth;
+                // The ID of this console and no error.
+                self.sio_control_register &= !0b0111_0000;
+                self.sio_control_register |= (player as u16) << 4;
+            }
+            SerialMode::Uart | SerialMode::GeneralPurpose | SerialMode::JoyBus => {}
+        }
+        self.publish_outgoing();
+    }
+
+    /// Returns how many cycles the transfer just started lasts,
+    /// `None` if this mode doesn't transfer through SIOCNT.
+    fn transfer_duration(&self) -> Option<u32> {
+        let cycles_per_bit = if self.sio_control_register.get_bit(1) {
+            CYCLES_PER_BIT_2MHZ
+        } else {
+            CYCLES_PER_BIT_256KHZ
+        };
+
+        match self.mode() {
+            // With the external clock the transfer is driven by the other side:
+            // when it's absent we complete it immediately.
+            SerialMode::Normal8 | SerialMode::Normal32 if !self.sio_control_register.get_bit(0) => {
+                Some(0)
+            }
+            SerialMode::Normal8 => Some(8 * cycles_per_bit),
+            SerialMode::Normal32 => Some(32 * cycles_per_bit),
+            SerialMode::Multiplayer => {
+                // 9600, 38400, 57600, 115200 bauds.
+                let cycles_per_bit = match self.sio_control_register.get_bits(0..=1) {
+                    0 => 1747,
+                    1 => 436,
+                    2 => 291,
+                    _ => 145,
+                };
+                // Start bit, 16 data bits and stop bit.
+                Some(18 * cycles_per_bit)
+            }
+            SerialMode::Uart | SerialMode::GeneralPurpose | SerialMode::JoyBus => None,
+        }
+    }
+
+    fn complete_transfer(&mut self) {
+        match self.mode() {
+            SerialMode::Normal8 => {
+                let received = match self.peripheral {
+                    SerialPeripheral::Absent
+                    | SerialPeripheral::LinkCable
+                    | SerialPeripheral::Uart => 0xFF,
+                    SerialPeripheral::WirelessAdapter => 0x00,
+                };
+                self.sio_multi_data_send_data_8.set_byte(0, received);
+            }
+            SerialMode::Normal32 => {
+                let sent = self.sio_data_32_multi_data_0_data_1;
+                self.sio_data_32_multi_data_0_data_1 = match self.peripheral {
+                    SerialPeripheral::Absent
+                    | SerialPeripheral::LinkCable
+                    | SerialPeripheral::Uart => 0xFFFF_FFFF,
+                    SerialPeripheral::WirelessAdapter => self.wireless_adapter.transfer(sent),
+                };
+            }
+            SerialMode::Multiplayer => {
+                // We are the parent and nobody else answers.
+                let sent = u32::from(self.sio_multi_data_send_data_8);
+                self.sio_data_32_multi_data_0_data_1 = 0xFFFF_0000 | sent;
+                self.sio_multi_data_2 = 0xFFFF;
+                self.sio_multi_data_3 = 0xFFFF;
+                // Multi-player ID 0 (parent) and no error.
+                self.sio_control_register &= !0b0111_0000;
+            }
+            SerialMode::Uart | SerialMode::GeneralPurpose | SerialMode::JoyBus => {}
+        }
+    }
+}
+
+impl HardwareComponent for Serial {
+    fn reset(&mut self) {
+        let transport = self.wireless_adapter.take_transport();
+        let host = self.uart.take_host();
+
+        *self = Self {
+            peripheral: self.peripheral,
+            link: self.link.take(),
+            ..Self::default()
+        };
+        self.wireless_adapter.set_transport(transport);
+        self.uart.set_host(host);
+        self.publish_outgoing();
+    }
+
+    fn step(&mut self, cycles: u32) -> StepOutput {
+        let mut output = StepOutput::default();
+
+        for _ in 0..cycles {
+            self.step_cycle(&mut output);
+        }
+
+        output
+    }
+
+    fn on_read(&self, address: usize) -> Option<u8> {
+        let value = match address {
+            0x0400_0120 => self.sio_data_32_multi_data_0_data_1.get_byte(0),
+            0x0400_0121 => self.sio_data_32_multi_data_0_data_1.get_byte(1),
+            0x0400_0122 => self.sio_data_32_multi_data_0_data_1.get_byte(2),
+            0x0400_0123 => self.sio_data_32_multi_data_0_data_1.get_byte(3),
+            0x0400_0124 => self.sio_multi_data_2.get_byte(0),
+            0x0400_0125 => self.sio_multi_data_2.get_byte(1),
+            0x0400_0126 => self.sio_multi_data_3.get_byte(0),
+            0x0400_0127 => self.sio_multi_data_3.get_byte(1),
+            0x0400_0128 if self.mode() == SerialMode::Uart => {
+                let control = UartControl(self.sio_control_register);
+                (self.sio_control_register & !0b0111_0000 | self.uart.status(&control)).get_byte(0)
+            }
+            0x0400_0128 => self.sio_control_register.get_byte(0),
+            0x0400_0129 => self.sio_control_register.get_byte(1),
+            0x0400_012A if self.mode() == SerialMode::Uart => self.uart.data(),
+            0x0400_012A => self.sio_multi_data_send_data_8.get_byte(0),
+            0x0400_012B => self.sio_multi_data_send_data_8.get_byte(1),
+            0x0400_0134 => self.sio_mode_select.get_byte(0),
+            0x0400_0135 => self.sio_mode_select.get_byte(1),
+            0x0400_0136 => self.infrared_register.get_byte(0),
+            0x0400_0137 => self.infrared_register.get_byte(1),
+            0x0400_0140 => self.sio_joy_bus_control.get_byte(0),
+            0x0400_0141 => self.sio_joy_bus_control.get_byte(1),
+            0x0400_0150 => self.sio_joy_bus_receive_data.get_byte(0),
+            0x0400_0151 => self.sio_joy_bus_receive_data.get_byte(1),
+            0x0400_0152 => self.sio_joy_bus_receive_data.get_byte(2),
+            0x0400_0153 => self.sio_joy_bus_receive_data.get_byte(3),
+            0x0400_0154 => self.sio_joy_bus_transmit_data.get_byte(0),
+            0x0400_0155 => self.sio_joy_bus_transmit_data.get_byte(1),
+            0x0400_0156 => self.sio_joy_bus_transmit_data.get_byte(2),
+            0x0400_0157 => self.sio_joy_bus_transmit_data.get_byte(3),
+            0x0400_0158 => self.sio_joy_bus_receive_status.get_byte(0),
+            0x0400_0159 => self.sio_joy_bus_receive_status.get_byte(1),
+            0x0400_012C..=0x0400_012F
+            | 0x0400_0138..=0x0400_013F
+            | 0x0400_0142..=0x0400_014F
+            | 0x0400_015A..=0x0400_01FF => return None,
+            _ => panic!("Serial read address is out of bound"),
+        };
+
+        Some(value)
+    }
+
+    fn on_write(&mut self, address: usize, value: u8) -> bool {
+        match address {
+            0x0400_0120 => self.sio_data_32_multi_data_0_data_1.set_byte(0, value),
+            0x0400_0121 => self.sio_data_32_multi_data_0_data_1.set_byte(1, value),
+            0x0400_0122 => self.sio_data_32_multi_data_0_data_1.set_byte(2, value),
+            0x0400_0123 => self.sio_data_32_multi_data_0_data_1.set_byte(3, value),
+            0x0400_0124 => self.sio_multi_data_2.set_byte(0, value),
+            0x0400_0125 => self.sio_multi_data_2.set_byte(1, value),
+            0x0400_0126 => self.sio_multi_data_3.set_byte(0, value),
+            0x0400_0127 => self.sio_multi_data_3.set_byte(1, value),
+            0x0400_0128 => self.sio_control_register.set_byte(0, value),
+            0x0400_0129 => self.sio_control_register.set_byte(1, value),
+            0x0400_012A => self.sio_multi_data_send_data_8.set_byte(0, value),
+            0x0400_012B => self.sio_multi_data_send_data_8.set_byte(1, value),
+            0x0400_0134 => self.sio_mode_select.set_byte(0, value),
+            0x0400_0135 => self.sio_mode_select.set_byte(1, value),
+            0x0400_0136 => self.infrared_register.set_byte(0, value),
+            0x0400_0137 => self.infrared_register.set_byte(1, value),
+            0x0400_0140 => self.sio_joy_bus_control.set_byte(0, value),
+            0x0400_0141 => self.sio_joy_bus_control.set_byte(1, value),
+            0x0400_0150 => self.sio_joy_bus_receive_data.set_byte(0, value),
+            0x0400_0151 => self.sio_joy_bus_receive_data.set_byte(1, value),
+            0x0400_0152 => self.sio_joy_bus_receive_data.set_byte(2, value),
+            0x0400_0153 => self.sio_joy_bus_receive_data.set_byte(3, value),
+            0x0400_0154 => self.sio_joy_bus_transmit_data.set_byte(0, value),
+            0x0400_0155 => self.sio_joy_bus_transmit_data.set_byte(1, value),
+            0x0400_0156 => self.sio_joy_bus_transmit_data.set_byte(2, value),
+            0x0400_0157 => self.sio_joy_bus_transmit_data.set_byte(3, value),
+            0x0400_0158 => self.sio_joy_bus_receive_status.set_byte(0, value),
+            0x0400_0159 => self.sio_joy_bus_receive_status.set_byte(1, value),
+            0x0400_012C..=0x0400_012F
+            | 0x0400_0138..=0x0400_013F
+            | 0x0400_0142..=0x0400_014F
+            | 0x0400_015A..=0x0400_01FF => return false,
+            _ => panic!("Serial write address is out of bound"),
+        }
+        if address == 0x0400_012A && self.mode() == SerialMode::Uart {
+            self.uart
+                .write_data(&UartControl(self.sio_control_register), value);
+        }
+        if matches!(address, 0x0400_0120..=0x0400_0123 | 0x0400_0128..=0x0400_012B) {
+            self.publish_outgoing();
+        }
+
+        true
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> bincode::Result<Vec<u8>> {
+        bincode::serialize(self)
+    }
+
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        let mut serial: Self = bincode::deserialize(data)?;
+        serial
+            .wireless_adapter
+            .set_transport(self.wireless_adapter.take_transport());
+        serial.link = self.link.take();
+        serial.uart.set_host(self.uart.take_host());
+        *self = serial;
+        self.publish_outgoing();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn run_transfer(serial: &mut Serial) -> bool {
+        for _ in 0..100_000 {
+            if serial.step(1).interrupts != 0 {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    #[test]
+    fn absent_normal32_internal_clock() {
+        let mut serial = Serial {
+            sio_data_32_multi_data_0_data_1: 0x1234_5678,
+            // Normal 32bit, internal clock 2MHz, start, IRQ enable
+            sio_control_register: 0b0101_0000_1000_0011,
+            ..Default::default()
+        };
+
+        assert!(run_transfer(&mut serial));
+        assert_eq!(serial.sio_data_32_multi_data_0_data_1, 0xFFFF_FFFF);
+        assert!(!serial.sio_control_register.get_bit(7));
+        assert!(serial.sio_control_register.get_bit(2));
+    }
+
+    #[test]
+    fn absent_normal8_external_clock_does_not_hang() {
+        let mut serial = Serial {
+            sio_multi_data_send_data_8: 0x42,
+            // Normal 8bit, external clock, start
+            sio_control_register: 0b0000_0000_1000_0000,
+            ..Default::default()
+        };
+
+        serial.step(1);
+        serial.step(1);
+
+        assert!(!serial.sio_control_register.get_bit(7));
+        assert_eq!(serial.sio_multi_data_send_data_8, 0xFF);
+    }
+
+    #[test]
+    fn absent_multiplayer() {
+        let mut serial = Serial {
+            sio_multi_data_send_data_8: 0xBEEF,
+            // Multiplayer, 115200 bauds, start, IRQ enable
+            sio_control_register: 0b0110_0000_1000_0011,
+            ..Default::default()
+        };
+
+        assert!(run_transfer(&mut serial));
+        assert_eq!(serial.sio_data_32_multi_data_0_data_1, 0xFFFF_BEEF);
+        assert_eq!(serial.sio_multi_data_2, 0xFFFF);
+        assert_eq!(serial.sio_multi_data_3, 0xFFFF);
+    }
+
+    #[test]
+    fn mode_select() {
+        let mut serial = Serial::default();
+        assert_eq!(serial.mode(), SerialMode::Normal8);
+
+        serial.sio_control_register = 0x3000;
+        assert_eq!(serial.mode(), SerialMode::Uart);
+
+        serial.sio_mode_select = 0x8000;
+        assert_eq!(serial.mode(), SerialMode::GeneralPurpose);
+
+        serial.sio_mode_select = 0xC000;
+        assert_eq!(serial.mode(), SerialMode::JoyBus);
+    }
+
+    fn linked(count: usize) -> (link_cable::LocalLinkCable, Vec<Serial>) {
+        let cable = link_cable::LocalLinkCable::default();
+        let serials = (0..count)
+            .map(|_| {
+                let mut serial = Serial::default();
+                serial.connect_link(Box::new(cable.plug().unwrap()));
+                serial
+            })
+            .collect();
+
+        (cable, serials)
+    }
+
+    /// Writes a half-word as the game does, through the I/O registers.
+    fn write(serial: &mut Serial, address: usize, value: u16) {
+        serial.on_write(address, value as u8);
+        serial.on_write(address + 1, (value >> 8) as u8);
+    }
+
+    #[test]
+    fn linked_multiplayer() {
+        let (_cable, mut serials) = linked(3);
+        for (serial, data) in serials.iter_mut().zip([0x1111, 0x2222, 0x3333]) {
+            // Multiplayer, 115200 bauds, IRQ enable.
+            write(serial, 0x0400_0128, 0b0110_0000_0000_0011);
+            write(serial, 0x0400_012A, data);
+            serial.step(1);
+        }
+        assert!(!serials[0].sio_control_register.get_bit(2));
+        assert!(serials[1].sio_control_register.get_bit(2));
+        assert!(serials[1].sio_control_register.get_bit(3));
+
+        serials[0].sio_control_register.set_bit_on(7);
+        assert!(run_transfer(&mut serials[0]));
+        for (player, serial) in serials.iter_mut().enumerate() {
+            if player > 0 {
+                assert_ne!(serial.step(1).interrupts, 0);
+            }
+            assert_eq!(serial.sio_data_32_multi_data_0_data_1, 0x2222_1111);
+            assert_eq!(serial.sio_multi_data_2, 0x3333);
+            assert_eq!(serial.sio_multi_data_3, 0xFFFF);
+            assert_eq!(serial.sio_control_register.get_bits(4..=5), player as u16);
+        }
+    }
+
+    #[test]
+    fn linked_normal32() {
+        let (_cable, mut serials) = linked(2);
+        serials[0].sio_data_32_multi_data_0_data_1 = 0xAAAA_AAAA;
+        serials[1].sio_data_32_multi_data_0_data_1 = 0xBBBB_BBBB;
+        // Normal 32bit, external clock, start.
+        write(&mut serials[1], 0x0400_0128, 0b0001_0000_1000_0000);
+        serials[1].step(1);
+        // Normal 32bit, internal clock 2MHz, start.
+        write(&mut serials[0], 0x0400_0128, 0b0101_0000_1000_0011);
+
+        assert!(run_transfer(&mut serials[0]));
+        serials[1].step(1);
+        assert_eq!(serials[0].sio_data_32_multi_data_0_data_1, 0xBBBB_BBBB);
+        assert_eq!(serials[1].sio_data_32_multi_data_0_data_1, 0xAAAA_AAAA);
+        assert!(!serials[1].sio_control_register.get_bit(7));
+    }
+
+    /// Keeps what the UART sends, gives it what's queued.
+    struct TestHost {
+        sent: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+        incoming: std::collections::VecDeque<u8>,
+    }
+
+    impl UartHost for TestHost {
+        fn send(&mut self, byte: u8) {
+            self.sent.lock().unwrap().push(byte);
+        }
+
+        fn receive(&mut self) -> Option<u8> {
+            self.incoming.pop_front()
+        }
+    }
+
+    #[test]
+    fn uart_sends_and_receives_through_the_host() {
+        let sent = std::sync::Arc::default();
+        let mut serial = Serial::default();
+        serial.connect_uart(Box::new(TestHost {
+            sent: std::sync::Arc::clone(&sent),
+            incoming: [b'?'].into(),
+        }));
+        // UART, IRQ enable, receive and send enable, FIFO, 8 bits, 115200 bauds.
+        write(&mut serial, 0x0400_0128, 0b0111_1101_1000_0011);
+        serial.on_write(0x0400_012A, b'o');
+        serial.on_write(0x0400_012A, b'k');
+
+        // The byte received comes first, then the ones sent.
+        assert!(run_transfer(&mut serial));
+        assert_eq!(serial.on_read(0x0400_0128).unwrap().get_bits(4..=6), 0b000);
+        assert_eq!(serial.on_read(0x0400_012A), Some(b'?'));
+        serial.acknowledge_read(0x0400_012A, 1);
+        assert_eq!(serial.on_read(0x0400_0128).unwrap().get_bits(4..=6), 0b010);
+
+        assert!(run_transfer(&mut serial));
+        assert!(run_transfer(&mut serial));
+        assert_eq!(*sent.lock().unwrap(), b"ok");
+    }
+
+    #[test]
+    fn wireless_adapter_keeps_si_low() {
+        let mut serial = Serial::default();
+        serial.connect(SerialPeripheral::WirelessAdapter);
+        serial.step(1);
+
+        assert!(!serial.sio_control_register.get_bit(2));
+    }
 }
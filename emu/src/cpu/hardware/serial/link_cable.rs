@@ -0,0 +1,196 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Consoles a link cable connects, with the multiplayer adapters.
+pub const MAX_PLAYERS: usize = 4;
+
+/// Words of a transfer by player, `None` for the missing ones.
+pub type LinkWords = [Option<u32>; MAX_PLAYERS];
+
+/// How consoles on a link cable reach each other.
+///
+/// The serial port only speaks in terms of this trait, so the same cable can wire
+/// consoles of the same process (see [`LocalLinkCable`]) or two instances on other
+/// machines (see [`TcpLink`](super::tcp_link::TcpLink)).
+///
+/// Player 0 is the parent: it clocks every transfer, in the multiplayer mode and in the
+/// normal modes with the internal clock. The others only shift when it does.
+pub trait LinkTransport: Send {
+    /// Position of this console on the cable, from 0 to [`MAX_PLAYERS`] - 1.
+    fn player(&self) -> usize;
+
+    /// Consoles on the cable, this one included. Called on every cycle.
+    fn players(&self) -> usize;
+
+    /// Sets the word this console shifts out at the next transfer.
+    fn set_outgoing(&mut self, word: u32);
+
+    /// As the parent: runs a transfer, delivering the outgoing word of every console to
+    /// the others. Returns them, this console's included.
+    fn transfer(&mut self) -> LinkWords;
+
+    /// As a child: the words of the last transfer the parent ran, if it ran one since
+    /// the last call. Called on every cycle, it should be cheap when there is none.
+    fn receive(&mut self) -> Option<LinkWords>;
+}
+
+#[derive(Default)]
+struct Port {
+    outgoing: u32,
+    /// Words of the last transfer, not taken by the console yet.
+    delivered: Option<LinkWords>,
+}
+
+#[derive(Default)]
+struct Cable {
+    ports: Mutex<[Option<Port>; MAX_PLAYERS]>,
+    /// Whether each port has a transfer delivered, read without locking the ports.
+    pending: [AtomicBool; MAX_PLAYERS],
+    /// Ports taken, read without locking them either.
+    players: AtomicUsize,
+}
+
+/// A link cable between `Gba`s living in the same process.
+///
+/// ```
+/// use emu::cpu::hardware::serial::link_cable::LocalLinkCable;
+///
+/// let cable = LocalLinkCable::default();
+/// let parent = cable.plug().unwrap();
+/// let child = cable.plug().unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct LocalLinkCable {
+    cable: Arc<Cable>,
+}
+
+impl LocalLinkCable {
+    /// Plugs a console in the first free port, `None` if the four are taken. The
+    /// first one plugged is the parent.
+    ///
+    /// # Panics
+    /// It panics if another port panicked while holding the cable.
+    #[must_use]
+    pub fn plug(&self) -> Option<LinkPort> {
+        let mut ports = self.cable.ports.lock().unwrap();
+        let player = ports.iter().position(Option::is_none)?;
+        ports[player] = Some(Port::default());
+        self.cable.players.fetch_add(1, Ordering::AcqRel);
+        drop(ports);
+
+        Some(LinkPort {
+            player,
+            cable: Arc::clone(&self.cable),
+        })
+    }
+}
+
+/// One console's end of a [`LocalLinkCable`], unplugged when dropped.
+pub struct LinkPort {
+    player: usize,
+    cable: Arc<Cable>,
+}
+
+impl LinkPort {
+    fn with_ports<T>(&self, f: impl FnOnce(&mut [Option<Port>; MAX_PLAYERS]) -> T) -> T {
+        f(&mut self.cable.ports.lock().unwrap())
+    }
+}
+
+impl LinkTransport for LinkPort {
+    fn player(&self) -> usize {
+        self.player
+    }
+
+    fn players(&self) -> usize {
+        self.cable.players.load(Ordering::Acquire)
+    }
+
+    fn set_outgoing(&mut self, word: u32) {
+        self.with_ports(|ports| {
+            if let Some(port) = &mut ports[self.player] {
+                port.outgoing = word;
+            }
+        });
+    }
+
+    fn transfer(&mut self) -> LinkWords {
+        let words = self.with_ports(|ports| {
+            let words = ports
+                .each_ref()
+                .map(|port| port.as_ref().map(|port| port.outgoing));
+            for (player, port) in ports.iter_mut().enumerate() {
+                if let Some(port) = port.as_mut().filter(|_| player != self.player) {
+                    port.delivered = Some(words);
+                    self.cable.pending[player].store(true, Ordering::Release);
+                }
+            }
+
+            words
+        });
+
+        words
+    }
+
+    fn receive(&mut self) -> Option<LinkWords> {
+        if !self.cable.pending[self.player].swap(false, Ordering::Acquire) {
+            return None;
+        }
+
+        self.with_ports(|ports| ports[self.player].as_mut()?.delivered.take())
+    }
+}
+
+impl Drop for LinkPort {
+    fn drop(&mut self) {
+        self.with_ports(|ports| ports[self.player] = None);
+        self.cable.players.fetch_sub(1, Ordering::AcqRel);
+        self.cable.pending[self.player].store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parent_transfers_to_every_child() {
+        let cable = LocalLinkCable::default();
+        let mut parent = cable.plug().unwrap();
+        let mut first = cable.plug().unwrap();
+        let mut second = cable.plug().unwrap();
+        assert_eq!(
+            [parent.player(), first.player(), second.player()],
+            [0, 1, 2]
+        );
+        assert_eq!(parent.players(), 3);
+
+        parent.set_outgoing(0xAAAA);
+        first.set_outgoing(0xBBBB);
+        second.set_outgoing(0xCCCC);
+        assert_eq!(first.receive(), None);
+
+        let words = [Some(0xAAAA), Some(0xBBBB), Some(0xCCCC), None];
+        assert_eq!(parent.transfer(), words);
+        assert_eq!(first.receive(), Some(words));
+        assert_eq!(first.receive(), None);
+        assert_eq!(second.receive(), Some(words));
+        assert_eq!(parent.receive(), None);
+    }
+
+    #[test]
+    fn unplugged_ports_are_reused() {
+        let cable = LocalLinkCable::default();
+        let ports = (0..MAX_PLAYERS)
+            .map(|_| cable.plug().unwrap())
+            .collect::<Vec<_>>();
+        assert!(cable.plug().is_none());
+
+        drop(ports);
+        let parent = cable.plug().unwrap();
+        assert_eq!(parent.player(), 0);
+        assert_eq!(parent.players(), 1);
+    }
+}
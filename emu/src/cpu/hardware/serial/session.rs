@@ -0,0 +1,286 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Maximum number of clients a wireless host accepts.
+pub const MAX_CLIENTS: u8 = 4;
+
+/// How wireless adapters reach each other.
+///
+/// The adapter only speaks in terms of this trait, so the same emulated device can be
+/// wired to other consoles in the same process (see [`LocalSessionBroker`]) or, later,
+/// to a network transport.
+pub trait WirelessTransport: Send {
+    /// Identifier of this adapter, unique inside the session.
+    fn id(&self) -> u16;
+
+    /// Sets the 6 words other adapters see while searching for hosts.
+    fn set_broadcast(&mut self, data: [u32; 6]);
+
+    /// Starts accepting connections from clients.
+    fn start_host(&mut self);
+
+    /// Stops accepting new connections, connected clients stay connected.
+    fn end_host(&mut self);
+
+    /// Adapters currently hosting, with their broadcast data.
+    fn hosts(&self) -> Vec<(u16, [u32; 6])>;
+
+    /// Connects as client to `host`, returns the assigned slot.
+    fn connect(&mut self, host: u16) -> Option<u8>;
+
+    /// Slot assigned by the host when this adapter is connected as a client.
+    fn client_slot(&self) -> Option<u8>;
+
+    /// Clients connected to this adapter as host: `(id, slot)`.
+    fn clients(&self) -> Vec<(u16, u8)>;
+
+    /// Sends `bytes` to the host (as client) or to every client (as host).
+    fn send(&mut self, bytes: Vec<u8>);
+
+    /// Takes every packet received so far with the slot of the sender
+    /// (`None` when it comes from the host).
+    fn receive(&mut self) -> Vec<(Option<u8>, Vec<u8>)>;
+
+    /// Leaves the session, as host this disconnects every client.
+    fn disconnect(&mut self);
+}
+
+#[derive(Default)]
+struct Adapter {
+    broadcast: [u32; 6],
+    hosting: bool,
+    /// Host id and slot when connected as a client.
+    host: Option<(u16, u8)>,
+    inbox: VecDeque<(Option<u8>, Vec<u8>)>,
+}
+
+#[derive(Default)]
+struct Session {
+    next_id: u16,
+    adapters: BTreeMap<u16, Adapter>,
+}
+
+impl Session {
+    fn clients_of(&self, host: u16) -> impl Iterator<Item = (u16, u8)> + '_ {
+        self.adapters.iter().filter_map(move |(&id, adapter)| {
+            adapter
+                .host
+                .filter(|(host_id, _)| *host_id == host)
+                .map(|(_, slot)| (id, slot))
+        })
+    }
+}
+
+/// Lets wireless adapters of several `Gba` living in the same process talk to each other.
+///
+/// ```
+/// use emu::cpu::hardware::serial::session::LocalSessionBroker;
+///
+/// let broker = LocalSessionBroker::default();
+/// let first = broker.join();
+/// let second = broker.join();
+/// ```
+#[derive(Clone, Default)]
+pub struct LocalSessionBroker {
+    session: Arc<Mutex<Session>>,
+}
+
+impl LocalSessionBroker {
+    /// Creates a new endpoint to be plugged in an adapter.
+    ///
+    /// # Panics
+    /// It panics if another endpoint panicked while holding the session.
+    #[must_use]
+    pub fn join(&self) -> LocalEndpoint {
+        let mut session = self.session.lock().unwrap();
+        // Real adapters have non-zero ids, we keep them recognizable.
+        session.next_id += 1;
+        let id = 0x2000 + session.next_id;
+        session.adapters.insert(id, Adapter::default());
+        drop(session);
+
+        LocalEndpoint {
+            id,
+            session: Arc::clone(&self.session),
+        }
+    }
+}
+
+/// One adapter's view of a [`LocalSessionBroker`].
+pub struct LocalEndpoint {
+    id: u16,
+    session: Arc<Mutex<Session>>,
+}
+
+impl LocalEndpoint {
+    fn with_session<T>(&self, f: impl FnOnce(&mut Session) -> T) -> T {
+        f(&mut self.session.lock().unwrap())
+    }
+
+    fn with_adapter<T>(&self, f: impl FnOnce(&mut Adapter) -> T) -> T {
+        self.with_session(|session| f(session.adapters.entry(self.id).or_default()))
+    }
+}
+
+impl WirelessTransport for LocalEndpoint {
+    fn id(&self) -> u16 {
+        self.id
+    }
+
+    fn set_broadcast(&mut self, data: [u32; 6]) {
+        self.with_adapter(|adapter| adapter.broadcast = data);
+    }
+
+    fn start_host(&mut self) {
+        self.with_adapter(|adapter| adapter.hosting = true);
+    }
+
+    fn end_host(&mut self) {
+        self.with_adapter(|adapter| adapter.hosting = false);
+    }
+
+    fn hosts(&self) -> Vec<(u16, [u32; 6])> {
+        self.with_session(|session| {
+            session
+                .adapters
+                .iter()
+                .filter(|(&id, adapter)| adapter.hosting && id != self.id)
+                .map(|(&id, adapter)| (id, adapter.broadcast))
+                .collect()
+        })
+    }
+
+    fn connect(&mut self, host: u16) -> Option<u8> {
+        self.with_session(|session| {
+            if !session.adapters.get(&host).is_some_and(|h| h.hosting) {
+                return None;
+            }
+
+            let taken = session
+                .clients_of(host)
+                .map(|(_, slot)| slot)
+                .collect::<Vec<_>>();
+            let slot = (0..MAX_CLIENTS).find(|slot| !taken.contains(slot))?;
+
+            session.adapters.entry(self.id).or_default().host = Some((host, slot));
+
+            Some(slot)
+        })
+    }
+
+    fn client_slot(&self) -> Option<u8> {
+        self.with_adapter(|adapter| adapter.host.map(|(_, slot)| slot))
+    }
+
+    fn clients(&self) -> Vec<(u16, u8)> {
+        self.with_session(|session| session.clients_of(self.id).collect())
+    }
+
+    fn send(&mut self, bytes: Vec<u8>) {
+        self.with_session(|session| {
+            let host = session.adapters.get(&self.id).and_then(|a| a.host);
+
+            if let Some((host, slot)) = host {
+                if let Some(host) = session.adapters.get_mut(&host) {
+                    host.inbox.push_back((Some(slot), bytes));
+                }
+            } else {
+                let clients = session.clients_of(self.id).collect::<Vec<_>>();
+                for (client, _) in clients {
+                    if let Some(client) = session.adapters.get_mut(&client) {
+                        client.inbox.push_back((None, bytes.clone()));
+                    }
+                }
+            }
+        });
+    }
+
+    fn receive(&mut self) -> Vec<(Option<u8>, Vec<u8>)> {
+        self.with_adapter(|adapter| adapter.inbox.drain(..).collect())
+    }
+
+    fn disconnect(&mut self) {
+        self.with_session(|session| {
+            let clients = session.clients_of(self.id).collect::<Vec<_>>();
+            for (client, _) in clients {
+                if let Some(client) = session.adapters.get_mut(&client) {
+                    client.host = None;
+                }
+            }
+
+            if let Some(adapter) = session.adapters.get_mut(&self.id) {
+                adapter.host = None;
+                adapter.hosting = false;
+            }
+        });
+    }
+}
+
+impl Drop for LocalEndpoint {
+    fn drop(&mut self) {
+        self.disconnect();
+        self.with_session(|session| session.adapters.remove(&self.id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn host_and_clients_exchange_data() {
+        let broker = LocalSessionBroker::default();
+        let mut host = broker.join();
+        let mut first = broker.join();
+        let mut second = broker.join();
+
+        assert!(first.hosts().is_empty());
+
+        host.set_broadcast([1, 2, 3, 4, 5, 6]);
+        host.start_host();
+        assert_eq!(first.hosts(), vec![(host.id(), [1, 2, 3, 4, 5, 6])]);
+
+        assert_eq!(first.connect(host.id()), Some(0));
+        assert_eq!(second.connect(host.id()), Some(1));
+        assert_eq!(host.clients(), vec![(first.id(), 0), (second.id(), 1)]);
+
+        host.send(vec![0xAA]);
+        second.send(vec![0xBB, 0xCC]);
+
+        assert_eq!(first.receive(), vec![(None, vec![0xAA])]);
+        assert_eq!(second.receive(), vec![(None, vec![0xAA])]);
+        assert_eq!(host.receive(), vec![(Some(1), vec![0xBB, 0xCC])]);
+    }
+
+    #[test]
+    fn leaving_host_disconnects_clients() {
+        let broker = LocalSessionBroker::default();
+        let mut host = broker.join();
+        let mut client = broker.join();
+
+        host.start_host();
+        client.connect(host.id());
+        assert_eq!(client.client_slot(), Some(0));
+
+        drop(host);
+
+        assert_eq!(client.client_slot(), None);
+        assert!(client.hosts().is_empty());
+    }
+
+    #[test]
+    fn host_is_full() {
+        let broker = LocalSessionBroker::default();
+        let mut host = broker.join();
+        host.start_host();
+
+        let mut clients = (0..MAX_CLIENTS).map(|_| broker.join()).collect::<Vec<_>>();
+        for client in &mut clients {
+            assert!(client.connect(host.id()).is_some());
+        }
+
+        assert_eq!(broker.join().connect(host.id()), None);
+    }
+}
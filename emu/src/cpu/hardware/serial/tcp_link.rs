@@ -0,0 +1,332 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::link_cable::{LinkTransport, LinkWords, MAX_PLAYERS};
+
+/// Sent by both sides first, with [`VERSION`].
+const MAGIC: [u8; 4] = *b"CLNK";
+/// Version of the messages below, both sides must speak the same.
+const VERSION: u8 = 1;
+
+/// The word the game shifts out next changed, followed by it.
+const OUTGOING: u8 = 0x01;
+/// The parent ran a transfer, followed by a mask of the players present and their
+/// words.
+const TRANSFER: u8 = 0x02;
+/// The child took a transfer.
+const ACK: u8 = 0x03;
+
+/// Transfers the parent runs ahead of the child by default, see [`TcpLink::set_window`].
+pub const DEFAULT_WINDOW: usize = 4;
+
+/// How long the parent waits for the child to catch up before running the transfer
+/// anyway, so a lost peer never freezes the game.
+const CATCH_UP_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+struct State {
+    /// Last word the other console shifts out.
+    remote_outgoing: u32,
+    /// Transfers received from the parent and not taken by the game yet.
+    delivered: VecDeque<LinkWords>,
+    /// Transfers sent to the child and not acknowledged yet.
+    in_flight: usize,
+}
+
+#[derive(Default)]
+struct Shared {
+    connected: AtomicBool,
+    /// Whether [`State::delivered`] has transfers, read without locking the state.
+    pending: AtomicBool,
+    state: Mutex<State>,
+    /// Notified on every acknowledgement and on disconnection.
+    acknowledged: Condvar,
+}
+
+/// A link cable tunneled over TCP between two Clementine instances: the host is the
+/// parent, the one that joins it the child.
+///
+/// The words the consoles shift out are sent as soon as the games write them, so the
+/// parent never waits for the network on a transfer: it uses the last word it got from
+/// the child, as the games that exchange the same word for a few frames (racing games
+/// in multiplayer mode) expect. To keep the consoles close, the parent runs at most
+/// [`DEFAULT_WINDOW`] transfers ahead of the child before it waits for it to catch up,
+/// at most [`CATCH_UP_TIMEOUT`] each time.
+pub struct TcpLink {
+    player: usize,
+    /// Word of the last [`LinkTransport::set_outgoing`].
+    outgoing: u32,
+    stream: TcpStream,
+    window: usize,
+    shared: Arc<Shared>,
+}
+
+impl TcpLink {
+    /// Waits for the other instance on `address` and links to it as the parent.
+    ///
+    /// # Errors
+    /// It fails if the address can't be bound, or the other side doesn't speak the
+    /// same version.
+    pub fn host(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::accept(&TcpListener::bind(address)?)
+    }
+
+    /// [`Self::host`] on a listener already bound, to tell the port chosen by the system.
+    ///
+    /// # Errors
+    /// It fails as [`Self::host`].
+    pub fn accept(listener: &TcpListener) -> io::Result<Self> {
+        Self::start(listener.accept()?.0, 0)
+    }
+
+    /// Links to the instance hosting on `address`, as the child.
+    ///
+    /// # Errors
+    /// It fails if the host can't be reached, or doesn't speak the same version.
+    pub fn join(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::start(TcpStream::connect(address)?, 1)
+    }
+
+    fn start(mut stream: TcpStream, player: usize) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.write_all(&MAGIC)?;
+        stream.write_all(&[VERSION])?;
+
+        let mut hello = [0; 5];
+        stream.read_exact(&mut hello)?;
+        if hello[..4] != MAGIC || hello[4] != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the other side isn't a Clementine of the same version",
+            ));
+        }
+
+        let shared = Arc::new(Shared::default());
+        shared.connected.store(true, Ordering::Release);
+        let reader = stream.try_clone()?;
+        let receiving = Arc::clone(&shared);
+        thread::spawn(move || {
+            // Any error ends the link, as a cable pulled out.
+            let _ = receive_messages(reader, &receiving);
+            receiving.connected.store(false, Ordering::Release);
+            receiving.acknowledged.notify_all();
+        });
+
+        Ok(Self {
+            player,
+            outgoing: 0,
+            stream,
+            window: DEFAULT_WINDOW,
+            shared,
+        })
+    }
+
+    /// Sets how many transfers the parent runs ahead of the child, see [`TcpLink`]. A
+    /// larger window hides more latency, the child seeing the transfers later.
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window.max(1);
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.shared.state.lock().unwrap()
+    }
+
+    /// A lost connection is seen by the reading thread, the link ends then.
+    fn send(&mut self, message: &[u8]) {
+        let _ = self.stream.write_all(message);
+    }
+}
+
+fn receive_messages(mut stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    loop {
+        let mut tag = [0];
+        stream.read_exact(&mut tag)?;
+        match tag[0] {
+            OUTGOING => {
+                let mut word = [0; 4];
+                stream.read_exact(&mut word)?;
+                shared.state.lock().unwrap().remote_outgoing = u32::from_le_bytes(word);
+            }
+            TRANSFER => {
+                let mut message = [0; 1 + 4 * MAX_PLAYERS];
+                stream.read_exact(&mut message)?;
+                let words = std::array::from_fn(|player| {
+                    let start = 1 + 4 * player;
+                    (message[0] & (1 << player) != 0)
+                        .then(|| u32::from_le_bytes(message[start..start + 4].try_into().unwrap()))
+                });
+                shared.state.lock().unwrap().delivered.push_back(words);
+                shared.pending.store(true, Ordering::Release);
+            }
+            ACK => {
+                let mut state = shared.state.lock().unwrap();
+                state.in_flight = state.in_flight.saturating_sub(1);
+                drop(state);
+                shared.acknowledged.notify_all();
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unknown link message",
+                ))
+            }
+        }
+    }
+}
+
+impl LinkTransport for TcpLink {
+    fn player(&self) -> usize {
+        self.player
+    }
+
+    fn players(&self) -> usize {
+        if self.shared.connected.load(Ordering::Acquire) {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn set_outgoing(&mut self, word: u32) {
+        self.outgoing = word;
+        let mut message = [OUTGOING, 0, 0, 0, 0];
+        message[1..].copy_from_slice(&word.to_le_bytes());
+        self.send(&message);
+    }
+
+    fn transfer(&mut self) -> LinkWords {
+        let shared = Arc::clone(&self.shared);
+        let mut state = shared.state.lock().unwrap();
+        if state.in_flight >= self.window {
+            state = shared
+                .acknowledged
+                .wait_timeout_while(state, CATCH_UP_TIMEOUT, |state| {
+                    state.in_flight >= self.window && shared.connected.load(Ordering::Acquire)
+                })
+                .unwrap()
+                .0;
+        }
+        state.in_flight += 1;
+        let remote = state.remote_outgoing;
+        drop(state);
+
+        // Only the parent runs transfers: the child is player 1.
+        let words = [Some(self.outgoing), Some(remote), None, None];
+
+        let mut message = [0; 2 + 4 * MAX_PLAYERS];
+        message[0] = TRANSFER;
+        for (player, word) in words.iter().enumerate() {
+            if let Some(word) = word {
+                message[1] |= 1 << player;
+                let start = 2 + 4 * player;
+                message[start..start + 4].copy_from_slice(&word.to_le_bytes());
+            }
+        }
+        self.send(&message);
+
+        words
+    }
+
+    fn receive(&mut self) -> Option<LinkWords> {
+        if !self.shared.pending.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let mut state = self.state();
+        let words = state.delivered.pop_front();
+        self.shared
+            .pending
+            .store(!state.delivered.is_empty(), Ordering::Release);
+        drop(state);
+        if words.is_some() {
+            self.send(&[ACK]);
+        }
+
+        words
+    }
+}
+
+impl Drop for TcpLink {
+    /// Ends the reading thread too, and the link on the other side.
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn linked() -> (TcpLink, TcpLink) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let child = thread::spawn(move || TcpLink::join(address).unwrap());
+        let parent = TcpLink::accept(&listener).unwrap();
+
+        (parent, child.join().unwrap())
+    }
+
+    /// Waits for the messages in flight to arrive.
+    fn receive(link: &mut TcpLink) -> Option<LinkWords> {
+        for _ in 0..200 {
+            if let Some(words) = link.receive() {
+                return Some(words);
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        None
+    }
+
+    #[test]
+    fn transfers_reach_the_child() {
+        let (mut parent, mut child) = linked();
+        assert_eq!((parent.player(), child.player()), (0, 1));
+        assert_eq!(parent.players(), 2);
+
+        child.set_outgoing(0xBEEF);
+        parent.set_outgoing(0xCAFE);
+        for _ in 0..200 {
+            if parent.state().remote_outgoing == 0xBEEF {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let words = [Some(0xCAFE), Some(0xBEEF), None, None];
+        assert_eq!(parent.transfer(), words);
+        assert_eq!(receive(&mut child), Some(words));
+        assert_eq!(child.receive(), None);
+
+        drop(child);
+        for _ in 0..200 {
+            if parent.players() == 1 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(parent.players(), 1);
+    }
+
+    #[test]
+    fn parent_waits_for_the_child_past_the_window() {
+        let (mut parent, mut child) = linked();
+        parent.set_window(2);
+
+        parent.transfer();
+        parent.transfer();
+        assert_eq!(parent.state().in_flight, 2);
+
+        assert!(receive(&mut child).is_some());
+        assert!(receive(&mut child).is_some());
+        parent.transfer();
+        assert!(parent.state().in_flight <= 2);
+    }
+}
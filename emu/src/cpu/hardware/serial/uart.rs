@@ -0,0 +1,233 @@
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Bytes the send and the receive FIFOs hold when enabled, 1 otherwise.
+const FIFO_SIZE: usize = 4;
+
+/// Where the UART of the serial port sends its bytes and takes the ones it receives.
+pub trait UartHost: Send {
+    /// A byte shifted out on TXD.
+    fn send(&mut self, byte: u8);
+
+    /// The next byte shifted in on RXD, if there is one. Called once per byte time.
+    fn receive(&mut self) -> Option<u8>;
+}
+
+/// A [`UartHost`] over a host pipe: the standard input and output, a pseudo-terminal or
+/// a named pipe.
+///
+/// ```no_run
+/// use emu::cpu::hardware::serial::uart::PipeUart;
+///
+/// // Logs of the game on the terminal.
+/// let stdio = PipeUart::stdio();
+/// // Or on a terminal emulator, to type into too.
+/// let pty = PipeUart::open("/dev/pts/3".as_ref()).unwrap();
+/// ```
+pub struct PipeUart {
+    writer: Box<dyn Write + Send>,
+    /// Filled by a thread reading the pipe, the emulation never waits for it.
+    received: Receiver<u8>,
+}
+
+impl PipeUart {
+    pub fn new(
+        mut reader: impl Read + Send + 'static,
+        writer: impl Write + Send + 'static,
+    ) -> Self {
+        let (sender, received) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 64];
+            // Ends at the end of the pipe, or once the UART is dropped.
+            while let Ok(read @ 1..) = reader.read(&mut buffer) {
+                if buffer[..read]
+                    .iter()
+                    .any(|&byte| sender.send(byte).is_err())
+                {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            writer: Box::new(writer),
+            received,
+        }
+    }
+
+    #[must_use]
+    pub fn stdio() -> Self {
+        Self::new(io::stdin(), io::stdout())
+    }
+
+    /// Reads and writes the file at `path`, a pseudo-terminal or a named pipe.
+    ///
+    /// # Errors
+    /// It fails if the file can't be opened for reading and writing.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        Ok(Self::new(file.try_clone()?, file))
+    }
+}
+
+impl UartHost for PipeUart {
+    fn send(&mut self, byte: u8) {
+        // A closed pipe drops the bytes, as a cable pulled out.
+        let _ = self
+            .writer
+            .write_all(&[byte])
+            .and_then(|()| self.writer.flush());
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        self.received.try_recv().ok()
+    }
+}
+
+/// The UART mode of the serial port, see [`Serial::connect_uart`](super::Serial::connect_uart).
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(super) struct Uart {
+    /// Bytes written by the game and not sent yet, the first one being shifted out.
+    send: VecDeque<u8>,
+    /// Bytes received and not read by the game yet.
+    receive: VecDeque<u8>,
+    /// Set when a byte is received with the receive FIFO full.
+    error: bool,
+    /// Cycles left to shift out the first byte of [`Self::send`].
+    send_cycles_left: u32,
+    /// Cycles left before the host is asked for the next byte.
+    receive_cycles_left: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    host: Option<Box<dyn UartHost>>,
+}
+
+/// Bits of SIOCNT in the UART mode.
+pub(super) struct UartControl(pub u16);
+
+impl UartControl {
+    /// Cycles to shift a whole byte: the start bit, the data, the parity and the stop bit.
+    fn byte_cycles(&self) -> u32 {
+        // 9600, 38400, 57600, 115200 bauds.
+        let cycles_per_bit = match self.0 & 0b11 {
+            0 => 1747,
+            1 => 436,
+            2 => 291,
+            _ => 145,
+        };
+        let data_bits = if self.eight_bits() { 8 } else { 7 };
+        let parity_bits = u32::from(self.0 & (1 << 9) != 0);
+
+        (2 + data_bits + parity_bits) * cycles_per_bit
+    }
+
+    const fn eight_bits(&self) -> bool {
+        self.0 & (1 << 7) != 0
+    }
+
+    const fn fifo_size(&self) -> usize {
+        if self.0 & (1 << 8) != 0 {
+            FIFO_SIZE
+        } else {
+            1
+        }
+    }
+
+    const fn send_enabled(&self) -> bool {
+        self.0 & (1 << 10) != 0
+    }
+
+    const fn receive_enabled(&self) -> bool {
+        self.0 & (1 << 11) != 0
+    }
+}
+
+impl Uart {
+    pub(super) fn set_host(&mut self, host: Option<Box<dyn UartHost>>) {
+        self.host = host;
+    }
+
+    pub(super) fn take_host(&mut self) -> Option<Box<dyn UartHost>> {
+        self.host.take()
+    }
+
+    /// Bits 4 to 6 of SIOCNT: send FIFO full, receive FIFO empty and error.
+    pub(super) fn status(&self, control: &UartControl) -> u16 {
+        (u16::from(self.send.len() >= control.fifo_size()) << 4)
+            | (u16::from(self.receive.is_empty()) << 5)
+            | (u16::from(self.error) << 6)
+    }
+
+    /// Byte SIODATA8 reads, the oldest one received.
+    pub(super) fn data(&self) -> u8 {
+        self.receive.front().copied().unwrap_or_default()
+    }
+
+    /// The game read SIODATA8: the next byte received comes forward.
+    pub(super) fn pop_data(&mut self) {
+        self.receive.pop_front();
+    }
+
+    /// The game read SIOCNT, which clears the error flag.
+    pub(super) const fn clear_error(&mut self) {
+        self.error = false;
+    }
+
+    /// The game wrote SIODATA8, dropped if sending is disabled or the FIFO is full.
+    pub(super) fn write_data(&mut self, control: &UartControl, byte: u8) {
+        if control.send_enabled() && self.send.len() < control.fifo_size() {
+            if self.send.is_empty() {
+                self.send_cycles_left = control.byte_cycles();
+            }
+            self.send.push_back(byte);
+        }
+    }
+
+    /// Advances by a cycle, returning whether a byte was sent or received, when the
+    /// serial interrupt is requested if enabled.
+    pub(super) fn step(&mut self, control: &UartControl) -> bool {
+        let mut shifted = false;
+
+        if control.send_enabled() && !self.send.is_empty() {
+            self.send_cycles_left = self.send_cycles_left.saturating_sub(1);
+            if self.send_cycles_left == 0 {
+                let byte = self.send.pop_front().unwrap_or_default();
+                let byte = if control.eight_bits() {
+                    byte
+                } else {
+                    byte & 0x7F
+                };
+                if let Some(host) = &mut self.host {
+                    host.send(byte);
+                }
+                self.send_cycles_left = control.byte_cycles();
+                shifted = true;
+            }
+        }
+
+        if control.receive_enabled() {
+            self.receive_cycles_left = self.receive_cycles_left.saturating_sub(1);
+            if self.receive_cycles_left == 0 {
+                self.receive_cycles_left = control.byte_cycles();
+                if let Some(byte) = self.host.as_mut().and_then(|host| host.receive()) {
+                    if self.receive.len() < control.fifo_size() {
+                        self.receive.push_back(byte);
+                    } else {
+                        self.error = true;
+                    }
+                    shifted = true;
+                }
+            }
+        }
+
+        shifted
+    }
+}
@@ -0,0 +1,376 @@
+use std::collections::VecDeque;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::session::WirelessTransport;
+
+/// Upper halfword of every command header sent by the GBA and every reply of the adapter.
+pub const COMMAND_MAGIC: u32 = 0x9966_0000;
+
+/// Word the adapter answers while it's busy receiving a command or its parameters.
+pub const ACK: u32 = 0x8000_0000;
+
+/// Answer to "is finished connect" while the host hasn't accepted us yet.
+const STILL_CONNECTING: u32 = 0x0100_0000;
+
+/// Emulated wireless adapter (RFU) speaking the command protocol over normal 32bit transfers.
+///
+/// Without a transport the adapter is alone: the login handshake is echoed back as the
+/// real adapter does and every command is acknowledged, so the game sees an adapter
+/// that never finds anyone to connect to. With a transport hosting, searching, connecting
+/// and exchanging data are forwarded to the other adapters of the session.
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WirelessAdapter {
+    /// Last word sent by the GBA, the adapter answers it during the login.
+    last_received: u32,
+    logged_in: bool,
+    /// Command being received and how many parameter words are still expected.
+    receiving: Option<(u8, u8)>,
+    /// Parameters of the command being received.
+    parameters: Vec<u32>,
+    /// Words queued to be answered to the next transfers.
+    replies: VecDeque<u32>,
+    /// Packets received from the session but not read by the game yet.
+    pending: VecDeque<(Option<u8>, Vec<u8>)>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    transport: Option<Box<dyn WirelessTransport>>,
+}
+
+impl WirelessAdapter {
+    pub fn set_transport(&mut self, transport: Option<Box<dyn WirelessTransport>>) {
+        self.transport = transport;
+    }
+
+    pub fn take_transport(&mut self) -> Option<Box<dyn WirelessTransport>> {
+        self.transport.take()
+    }
+
+    /// Exchanges a 32bit word: `sent` is what the GBA shifted out and the return value
+    /// is what the adapter shifted in at the same time.
+    pub fn transfer(&mut self, sent: u32) -> u32 {
+        let reply = if self.logged_in {
+            self.command_transfer(sent)
+        } else {
+            // During the login the adapter answers with the previous word, halves swapped.
+            // The GBA ends the "NINTENDO" exchange with the first command header.
+            let reply = self.last_received.rotate_left(16);
+            if sent & 0xFFFF_0000 == COMMAND_MAGIC {
+                self.logged_in = true;
+                self.command_transfer(sent)
+            } else {
+                reply
+            }
+        };
+
+        self.last_received = sent;
+
+        reply
+    }
+
+    fn command_transfer(&mut self, sent: u32) -> u32 {
+        if let Some((command, left)) = self.receiving {
+            self.parameters.push(sent);
+
+            if left <= 1 {
+                self.receiving = None;
+                self.queue_reply(command);
+            } else {
+                self.receiving = Some((command, left - 1));
+            }
+
+            return ACK;
+        }
+
+        if sent & 0xFFFF_0000 == COMMAND_MAGIC {
+            let command = (sent & 0xFF) as u8;
+            let parameters = ((sent >> 8) & 0xFF) as u8;
+
+            self.parameters.clear();
+            if parameters == 0 {
+                self.queue_reply(command);
+            } else {
+                self.receiving = Some((command, parameters));
+            }
+
+            return ACK;
+        }
+
+        self.replies.pop_front().unwrap_or(ACK)
+    }
+
+    fn queue_reply(&mut self, command: u8) {
+        let data = self.transport.take().map_or_else(
+            || Self::alone_reply(command),
+            |mut transport| {
+                let data = self.session_reply(transport.as_mut(), command);
+                self.transport = Some(transport);
+                data
+            },
+        );
+
+        self.replies
+            .push_back(COMMAND_MAGIC | ((data.len() as u32) << 8) | u32::from(command | 0x80));
+        self.replies.extend(data);
+    }
+
+    fn alone_reply(command: u8) -> Vec<u32> {
+        match command {
+            // Signal level and system status: a single zeroed word.
+            0x11 | 0x13 => vec![0],
+            // Everything else (hello, setup, broadcast, host search...) has an empty reply:
+            // searching for hosts never returns anybody.
+            _ => vec![],
+        }
+    }
+
+    fn session_reply(&mut self, transport: &mut dyn WirelessTransport, command: u8) -> Vec<u32> {
+        let id = u32::from(transport.id());
+
+        match command {
+            // Signal level: full strength for every connected slot.
+            0x11 => vec![Self::connected_slots(transport)
+                .iter()
+                .fold(0, |level, slot| level | 0xFF << (8 * slot))],
+            // System status: device id, connected slots and state
+            // (0 idle, 1 host with clients, 5 connected client).
+            0x13 => {
+                let slots = Self::connected_slots(transport);
+                let state = if transport.client_slot().is_some() {
+                    5
+                } else {
+                    u32::from(!slots.is_empty())
+                };
+                let slots = slots.iter().fold(0, |mask, slot| mask | 1 << slot);
+                vec![state << 24 | slots << 16 | id]
+            }
+            // Broadcast: the data other adapters see while searching.
+            0x16 => {
+                let mut broadcast = [0; 6];
+                for (word, parameter) in broadcast.iter_mut().zip(&self.parameters) {
+                    *word = *parameter;
+                }
+                transport.set_broadcast(broadcast);
+                vec![]
+            }
+            0x19 => {
+                transport.start_host();
+                vec![]
+            }
+            // Accept connections.
+            0x1A => Self::clients(transport),
+            // End host, it answers with the clients that made it in.
+            0x1B => {
+                let clients = Self::clients(transport);
+                transport.end_host();
+                clients
+            }
+            // Broadcast read poll and end: id and broadcast data of every host.
+            0x1D | 0x1E => transport
+                .hosts()
+                .into_iter()
+                .flat_map(|(host, broadcast)| std::iter::once(u32::from(host)).chain(broadcast))
+                .collect(),
+            0x1F => {
+                if let Some(&host) = self.parameters.first() {
+                    transport.connect(host as u16);
+                }
+                vec![]
+            }
+            // Is finished connect and finish connection.
+            0x20 | 0x21 => match transport.client_slot() {
+                Some(slot) => vec![u32::from(slot) << 16 | id],
+                None if command == 0x20 => vec![STILL_CONNECTING],
+                None => vec![],
+            },
+            // Send data and send data wait: the first parameter holds the size in bytes,
+            // in bits 0-6 for the host and in 5 bits starting from 8 + slot * 5 for clients.
+            0x24 | 0x25 => {
+                if let Some((&header, words)) = self.parameters.split_first() {
+                    let size = transport.client_slot().map_or(header & 0x7F, |slot| {
+                        (header >> (8 + 5 * u32::from(slot))) & 0x1F
+                    });
+                    let bytes = words
+                        .iter()
+                        .flat_map(|word| word.to_le_bytes())
+                        .take(size as usize)
+                        .collect();
+                    transport.send(bytes);
+                }
+                vec![]
+            }
+            0x26 => {
+                self.pending.extend(transport.receive());
+                self.receive_data()
+            }
+            0x30 | 0x3D => {
+                transport.disconnect();
+                self.pending.clear();
+                vec![]
+            }
+            // Hello, setup, broadcast read start, wait...
+            _ => vec![],
+        }
+    }
+
+    /// Our slot as client or the slots of our clients as host.
+    fn connected_slots(transport: &dyn WirelessTransport) -> Vec<u8> {
+        transport.client_slot().map_or_else(
+            || {
+                transport
+                    .clients()
+                    .into_iter()
+                    .map(|(_, slot)| slot)
+                    .collect()
+            },
+            |slot| vec![slot],
+        )
+    }
+
+    /// Slot and id of every connected client, as answered by the host commands.
+    fn clients(transport: &dyn WirelessTransport) -> Vec<u32> {
+        transport
+            .clients()
+            .into_iter()
+            .map(|(client, slot)| u32::from(slot) << 16 | u32::from(client))
+            .collect()
+    }
+
+    /// Builds the reply of "receive data": a header with the size of the data coming from
+    /// each sender followed by the data, at most one packet per sender.
+    fn receive_data(&mut self) -> Vec<u32> {
+        let mut header = 0;
+        let mut bytes = Vec::new();
+        let mut senders = Vec::new();
+        let mut left = VecDeque::new();
+
+        while let Some((sender, packet)) = self.pending.pop_front() {
+            if senders.contains(&sender) {
+                left.push_back((sender, packet));
+                continue;
+            }
+
+            let size = packet.len() as u32;
+            header |= sender.map_or(size & 0x7F, |slot| {
+                (size & 0x1F) << (8 + 5 * u32::from(slot))
+            });
+            bytes.extend(&packet);
+            bytes.resize(bytes.len().next_multiple_of(4), 0);
+            senders.push(sender);
+        }
+        self.pending = left;
+
+        if senders.is_empty() {
+            return vec![];
+        }
+
+        std::iter::once(header)
+            .chain(
+                bytes
+                    .chunks_exact(4)
+                    .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])),
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::cpu::hardware::serial::session::LocalSessionBroker;
+
+    use super::*;
+
+    fn command(adapter: &mut WirelessAdapter, command: u8, parameters: &[u32]) -> Vec<u32> {
+        let header = COMMAND_MAGIC | (parameters.len() as u32) << 8 | u32::from(command);
+        assert_eq!(adapter.transfer(header), ACK);
+        for parameter in parameters {
+            assert_eq!(adapter.transfer(*parameter), ACK);
+        }
+
+        let reply = adapter.transfer(ACK);
+        assert_eq!(
+            reply & 0xFFFF_00FF,
+            COMMAND_MAGIC | u32::from(command | 0x80)
+        );
+
+        (0..(reply >> 8) & 0xFF)
+            .map(|_| adapter.transfer(ACK))
+            .collect()
+    }
+
+    fn logged_in(broker: &LocalSessionBroker) -> (WirelessAdapter, u32) {
+        let mut adapter = WirelessAdapter::default();
+        let endpoint = broker.join();
+        let id = u32::from(endpoint.id());
+        adapter.set_transport(Some(Box::new(endpoint)));
+        adapter.transfer(0x494E_B6B1);
+
+        (adapter, id)
+    }
+
+    #[test]
+    fn login_echoes_previous_word() {
+        let mut adapter = WirelessAdapter::default();
+
+        assert_eq!(adapter.transfer(0x494E_B6B1), 0);
+        assert_eq!(adapter.transfer(0x494E_B6B1), 0xB6B1_494E);
+        assert_eq!(adapter.transfer(0x544E_B6B1), 0xB6B1_494E);
+        assert_eq!(adapter.transfer(0x544E_ABB1), 0xB6B1_544E);
+    }
+
+    #[test]
+    fn commands_are_acknowledged() {
+        let mut adapter = WirelessAdapter::default();
+        adapter.transfer(0x494E_B6B1);
+
+        // Hello (0x10) without parameters.
+        assert_eq!(adapter.transfer(0x9966_0010), ACK);
+        assert_eq!(adapter.transfer(ACK), 0x9966_0090);
+
+        // Setup (0x17) with a parameter.
+        assert_eq!(adapter.transfer(0x9966_0117), ACK);
+        assert_eq!(adapter.transfer(0x003C_0420), ACK);
+        assert_eq!(adapter.transfer(ACK), 0x9966_0097);
+
+        // System status (0x13) answers with one word.
+        assert_eq!(adapter.transfer(0x9966_0013), ACK);
+        assert_eq!(adapter.transfer(ACK), 0x9966_0193);
+        assert_eq!(adapter.transfer(ACK), 0);
+    }
+
+    #[test]
+    fn host_and_client_session() {
+        let broker = LocalSessionBroker::default();
+        let (mut host, host_id) = logged_in(&broker);
+        let (mut client, client_id) = logged_in(&broker);
+
+        command(&mut host, 0x16, &[0x1111, 0x2222, 0, 0, 0, 0]);
+        command(&mut host, 0x19, &[]);
+
+        command(&mut client, 0x1C, &[]);
+        assert_eq!(
+            command(&mut client, 0x1D, &[]),
+            vec![host_id, 0x1111, 0x2222, 0, 0, 0, 0]
+        );
+        command(&mut client, 0x1E, &[]);
+
+        command(&mut client, 0x1F, &[host_id]);
+        assert_eq!(command(&mut client, 0x20, &[]), vec![client_id]);
+        assert_eq!(command(&mut host, 0x1A, &[]), vec![client_id]);
+
+        // The host sends 5 bytes to everybody.
+        command(&mut host, 0x24, &[5, 0x4433_2211, 0x55]);
+        assert_eq!(command(&mut client, 0x26, &[]), vec![5, 0x4433_2211, 0x55]);
+
+        // The client in slot 0 sends 2 bytes back.
+        command(&mut client, 0x24, &[2 << 8, 0xBBAA]);
+        assert_eq!(command(&mut host, 0x26, &[]), vec![2 << 8, 0xBBAA]);
+        assert_eq!(command(&mut host, 0x26, &[]), Vec::<u32>::new());
+
+        command(&mut host, 0x30, &[]);
+        assert_eq!(command(&mut client, 0x20, &[]), vec![STILL_CONNECTING]);
+    }
+}
@@ -0,0 +1,198 @@
+//! The solar sensor of the Boktai cartridges, on the [`GpioPort`](super::gpio::GpioPort):
+//! the games charge the weapons and melt the vampires with the sunlight it measures.
+//!
+//! The game samples the light by raising line 1, which clears a counter, then clocks the
+//! counter up on line 0 until the sensor raises line 3: the more light, the sooner. Line 2
+//! low selects the sensor, the clock of the cartridge sharing the port.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::gpio::GpioDevice;
+
+const CLOCK: u8 = 0b0001;
+const RESET: u8 = 0b0010;
+/// High while the clock is selected, the sensor ignores the lines then.
+const DESELECT: u8 = 0b0100;
+const FLAG: u8 = 0b1000;
+
+/// Counts the sensor reaches the flag at for each level of [`SolarLight`] above 0, the
+/// ones of the gauge of the games from the lightest bar to the full one.
+const LEVELS: [u8; SolarLight::MAX as usize] = [5, 11, 18, 27, 42, 62, 88, 121, 151, 199];
+
+/// Game codes of the cartridges with a solar sensor, without the region letter.
+const GAMES: [&str; 3] = [
+    "U3I", // Boktai
+    "U32", // Boktai 2
+    "U33", // Boktai 3
+];
+
+/// Whether the cartridge of `game_code` has a solar sensor.
+#[must_use]
+pub fn detect(game_code: &str) -> bool {
+    GAMES.iter().any(|game| game_code.starts_with(game))
+}
+
+/// The light on the sensor, shared with the frontend: from 0 for darkness to
+/// [`SolarLight::MAX`] for the full sun.
+#[derive(Clone, Default)]
+pub struct SolarLight(Arc<AtomicU8>);
+
+impl SolarLight {
+    pub const MAX: u8 = 10;
+
+    #[must_use]
+    pub fn level(&self) -> u8 {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Sets the light, clamped to [`Self::MAX`]. The games see it at their next sample.
+    pub fn set_level(&self, level: u8) {
+        self.0.store(level.min(Self::MAX), Ordering::Release);
+    }
+
+    /// Count at which the sensor raises the flag.
+    fn threshold(&self) -> u8 {
+        match self.level() {
+            0 => u8::MAX,
+            level => u8::MAX - LEVELS[usize::from(level) - 1],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct State {
+    /// Lines of the last write, to find the rising edges of the clock.
+    lines: u8,
+    counter: u8,
+    /// [`SolarLight::threshold`] at the last reset of the counter.
+    threshold: u8,
+}
+
+/// The sensor, plugged on the [`GpioPort`](super::gpio::GpioPort).
+pub struct SolarSensor {
+    state: State,
+    light: SolarLight,
+}
+
+impl SolarSensor {
+    /// A sensor measuring `light`.
+    #[must_use]
+    pub fn new(light: SolarLight) -> Self {
+        Self {
+            state: State {
+                threshold: light.threshold(),
+                ..State::default()
+            },
+            light,
+        }
+    }
+}
+
+impl GpioDevice for SolarSensor {
+    fn name(&self) -> &'static str {
+        "solar"
+    }
+
+    fn write(&mut self, lines: u8) {
+        let previous = self.state.lines;
+        self.state.lines = lines;
+
+        if lines & DESELECT != 0 {
+            return;
+        }
+        if lines & RESET != 0 {
+            self.state.counter = 0;
+            self.state.threshold = self.light.threshold();
+        }
+        if previous & CLOCK == 0 && lines & CLOCK != 0 {
+            self.state.counter = self.state.counter.saturating_add(1);
+        }
+    }
+
+    fn read(&self) -> u8 {
+        if self.state.lines & DESELECT == 0 && self.state.counter >= self.state.threshold {
+            FLAG
+        } else {
+            0
+        }
+    }
+
+    fn reset(&mut self, _hard: bool) {
+        self.state = State {
+            threshold: self.light.threshold(),
+            ..State::default()
+        };
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> bincode::Result<Vec<u8>> {
+        bincode::serialize(&self.state)
+    }
+
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        self.state = bincode::deserialize(data)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Clocks the sensor as the games do, returning the count the flag rose at.
+    fn sample(sensor: &mut SolarSensor) -> u32 {
+        sensor.write(RESET);
+        sensor.write(0);
+        let mut count = 0;
+        while sensor.read() & FLAG == 0 && count < 0x100 {
+            sensor.write(CLOCK);
+            sensor.write(0);
+            count += 1;
+        }
+
+        count
+    }
+
+    #[test]
+    fn more_light_raises_the_flag_sooner() {
+        let light = SolarLight::default();
+        let mut sensor = SolarSensor::new(light.clone());
+        assert_eq!(sample(&mut sensor), 0xFF);
+
+        light.set_level(1);
+        let dim = sample(&mut sensor);
+        light.set_level(SolarLight::MAX);
+        let sunny = sample(&mut sensor);
+        assert_eq!(dim, 0xFF - 5);
+        assert_eq!(sunny, 0xFF - 199);
+
+        light.set_level(u8::MAX);
+        assert_eq!(light.level(), SolarLight::MAX);
+    }
+
+    #[test]
+    fn ignores_the_lines_while_deselected() {
+        let mut sensor = SolarSensor::new(SolarLight::default());
+        sensor.write(CLOCK);
+        sensor.write(DESELECT | RESET);
+        sensor.write(DESELECT | CLOCK);
+        assert_eq!(sensor.state.counter, 1);
+        assert_eq!(sensor.read(), 0);
+    }
+
+    #[test]
+    fn detects_the_games_with_a_sensor() {
+        assert!(detect("U3IE"));
+        assert!(detect("U33J"));
+        assert!(!detect("BPEE"));
+    }
+}
@@ -1,22 +1,418 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Serialize, Deserialize)]
+use crate::bitwise::Bits;
+use crate::cpu::hardware::component::{HardwareComponent, StepOutput};
+
+use self::filter::{FilterSettings, OutputFilter};
+use self::mixer::{ChannelSamples, Mixer, StereoSample};
+use self::registers::{Sound3CntH, Sound3CntL, Sound3CntX, SoundCntH, SoundCntL, SoundCntX};
+use self::resampler::Resampler;
+use self::wave::WaveChannel;
+
+pub mod filter;
+pub mod mixer;
+pub mod registers;
+pub mod resampler;
+mod wave;
+
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Sound {
     pub channel1_sweep: u16,
     pub channel1_duty_length_envelope: u16,
     pub channel1_frequency_control: u16,
     pub channel2_duty_length_envelope: u16,
     pub channel2_frequency_control: u16,
-    pub channel3_stop_wave_ram_select: u16,
-    pub channel3_length_volume: u16,
-    pub channel3_frequency_control: u16,
+    pub channel3_stop_wave_ram_select: Sound3CntL,
+    pub channel3_length_volume: Sound3CntH,
+    pub channel3_frequency_control: Sound3CntX,
     pub channel4_length_envelope: u16,
     pub channel4_frequency_control: u16,
-    pub control_stereo_volume_enable: u16,
-    pub control_mixing_dma_control: u16,
-    pub control_sound_on_off: u16,
+    pub control_stereo_volume_enable: SoundCntL,
+    pub control_mixing_dma_control: SoundCntH,
+    pub control_sound_on_off: SoundCntX,
     pub sound_pwm_control: u16,
-    pub channel3_wave_pattern_ram: [u8; 16],
+    /// Both banks of 32 digits, see [`Self::read_wave_ram`].
+    pub channel3_wave_ram: [[u8; 16]; 2],
     pub channel_a_fifo: u32,
     pub channel_b_fifo: u32,
+
+    channel3: WaveChannel,
+
+    /// Not part of the state, the ramps only last a few samples.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    mixer: Mixer,
+    /// Not part of the state, it's a setting of the frontend.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    filter: OutputFilter,
+    /// Not part of the state, a frame cut short by a load is padded.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    resampler: Resampler,
+}
+
+impl Sound {
+    /// The CPU sees the bank that isn't played by channel 3.
+    #[must_use]
+    pub fn read_wave_ram(&self, offset: usize) -> u8 {
+        self.channel3_wave_ram[self.cpu_wave_bank()][offset]
+    }
+
+    pub fn write_wave_ram(&mut self, offset: usize, value: u8) {
+        let bank = self.cpu_wave_bank();
+        self.channel3_wave_ram[bank][offset] = value;
+    }
+
+    fn cpu_wave_bank(&self) -> usize {
+        wave::selected_bank(self.channel3_stop_wave_ram_select) ^ 1
+    }
+
+    /// Called when bit 15 of `SOUND3CNT_X` is written.
+    pub fn restart_channel3(&mut self) {
+        self.channel3.restart(
+            self.channel3_stop_wave_ram_select,
+            self.channel3_frequency_control,
+        );
+    }
+
+    /// Output of channel 3, `None` while it isn't playing.
+    #[must_use]
+    pub fn channel3_sample(&self) -> Option<i8> {
+        self.channel3.sample(
+            &self.channel3_wave_ram,
+            self.channel3_stop_wave_ram_select,
+            self.channel3_length_volume,
+        )
+    }
+
+    /// Mixes one sample of every channel as selected by `SOUNDCNT_L`, `SOUNDCNT_H` and
+    /// `SOUNDCNT_X`, then runs it through the output filters.
+    pub fn mix(&mut self, samples: ChannelSamples) -> StereoSample {
+        let sample = self.mixer.mix(
+            self.control_stereo_volume_enable,
+            self.control_mixing_dma_control,
+            self.control_sound_on_off,
+            samples,
+        );
+
+        self.filter.apply(sample)
+    }
+
+    /// Chooses the filters applied to the mix, `sample_rate` being the rate of the calls
+    /// to [`Self::mix`].
+    pub fn set_output_filter(&mut self, settings: FilterSettings, sample_rate: u32) {
+        self.filter = OutputFilter::new(settings, sample_rate);
+    }
+
+    #[must_use]
+    pub const fn output_filter(&self) -> FilterSettings {
+        self.filter.settings()
+    }
+
+    /// Chooses how many samples of the mix are taken for every frame, the output filters
+    /// follow the new rate.
+    pub fn set_samples_per_frame(&mut self, samples_per_frame: u32) {
+        self.resampler = Resampler::new(samples_per_frame);
+        self.filter = OutputFilter::new(self.filter.settings(), self.resampler.sample_rate());
+    }
+
+    #[must_use]
+    pub const fn samples_per_frame(&self) -> u32 {
+        self.resampler.samples_per_frame()
+    }
+
+    #[must_use]
+    pub const fn sample_rate(&self) -> u32 {
+        self.resampler.sample_rate()
+    }
+
+    /// Called when the LCD enters Vblank.
+    pub fn end_audio_frame(&mut self) {
+        self.resampler.end_frame();
+    }
+
+    /// Samples of the last frame, always [`Self::samples_per_frame`] of them once a frame
+    /// is completed.
+    pub fn take_audio_frame(&mut self) -> Vec<StereoSample> {
+        self.resampler.take_frame()
+    }
+}
+
+impl HardwareComponent for Sound {
+    fn reset(&mut self) {
+        let filter = OutputFilter::new(self.filter.settings(), self.filter.sample_rate());
+        let resampler = Resampler::new(self.resampler.samples_per_frame());
+        *self = Self {
+            filter,
+            resampler,
+            ..Self::default()
+        };
+    }
+
+    fn step(&mut self, cycles: u32) -> StepOutput {
+        for _ in 0..cycles {
+            self.channel3.step(
+                self.channel3_stop_wave_ram_select,
+                self.channel3_frequency_control,
+            );
+
+            if self.resampler.step() {
+                let sample = self.mix(ChannelSamples {
+                    psg: [None, None, self.channel3_sample(), None],
+                    fifo: [None; 2],
+                });
+                self.resampler.push(sample);
+            }
+        }
+
+        StepOutput::default()
+    }
+
+    fn on_read(&self, address: usize) -> Option<u8> {
+        let value = match address {
+            0x0400_0060 => self.channel1_sweep.get_byte(0),
+            0x0400_0061 => self.channel1_sweep.get_byte(1),
+            0x0400_0062 => self.channel1_duty_length_envelope.get_byte(0),
+            0x0400_0063 => self.channel1_duty_length_envelope.get_byte(1),
+            0x0400_0064 => self.channel1_frequency_control.get_byte(0),
+            0x0400_0065 => self.channel1_frequency_control.get_byte(1),
+            0x0400_0068 => self.channel2_duty_length_envelope.get_byte(0),
+            0x0400_0069 => self.channel2_duty_length_envelope.get_byte(1),
+            0x0400_006C => self.channel2_frequency_control.get_byte(0),
+            0x0400_006D => self.channel2_frequency_control.get_byte(1),
+            0x0400_0070 => self.channel3_stop_wave_ram_select.get_byte(0),
+            0x0400_0071 => self.channel3_stop_wave_ram_select.get_byte(1),
+            0x0400_0072 => self.channel3_length_volume.get_byte(0),
+            0x0400_0073 => self.channel3_length_volume.get_byte(1),
+            0x0400_0074 => self.channel3_frequency_control.get_byte(0),
+            0x0400_0075 => self.channel3_frequency_control.get_byte(1),
+            0x0400_0078 => self.channel4_length_envelope.get_byte(0),
+            0x0400_0079 => self.channel4_length_envelope.get_byte(1),
+            0x0400_007C => self.channel4_frequency_control.get_byte(0),
+            0x0400_007D => self.channel4_frequency_control.get_byte(1),
+            0x0400_0080 => self.control_stereo_volume_enable.get_byte(0),
+            0x0400_0081 => self.control_stereo_volume_enable.get_byte(1),
+            0x0400_0082 => self.control_mixing_dma_control.get_byte(0),
+            0x0400_0083 => self.control_mixing_dma_control.get_byte(1),
+            0x0400_0084 => self.control_sound_on_off.get_byte(0),
+            0x0400_0085 => self.control_sound_on_off.get_byte(1),
+            0x0400_0088 => self.sound_pwm_control.get_byte(0),
+            0x0400_0089 => self.sound_pwm_control.get_byte(1),
+            0x0400_0090..=0x0400_009F => self.read_wave_ram(address - 0x0400_0090),
+            0x0400_00A0..=0x0400_00A7 => panic!("Reading a write-only Sound I/O register"),
+            0x0400_0066..=0x0400_0067
+            | 0x0400_006A..=0x0400_006B
+            | 0x0400_006E..=0x0400_006F
+            | 0x0400_0076..=0x0400_0077
+            | 0x0400_007A..=0x0400_007B
+            | 0x0400_007E..=0x0400_007F
+            | 0x0400_0086..=0x0400_0087
+            | 0x0400_008A..=0x0400_008F
+            | 0x0400_00A8..=0x0400_00AF => return None,
+            _ => panic!("Sound read address is out of bound"),
+        };
+
+        Some(value)
+    }
+
+    fn on_write(&mut self, address: usize, value: u8) -> bool {
+        match address {
+            0x0400_0060 => self.channel1_sweep.set_byte(0, value),
+            0x0400_0061 => self.channel1_sweep.set_byte(1, value),
+            0x0400_0062 => self.channel1_duty_length_envelope.set_byte(0, value),
+            0x0400_0063 => self.channel1_duty_length_envelope.set_byte(1, value),
+            0x0400_0064 => self.channel1_frequency_control.set_byte(0, value),
+            0x0400_0065 => self.channel1_frequency_control.set_byte(1, value),
+            0x0400_0068 => self.channel2_duty_length_envelope.set_byte(0, value),
+            0x0400_0069 => self.channel2_duty_length_envelope.set_byte(1, value),
+            0x0400_006C => self.channel2_frequency_control.set_byte(0, value),
+            0x0400_006D => self.channel2_frequency_control.set_byte(1, value),
+            0x0400_0070 => self.channel3_stop_wave_ram_select.set_byte(0, value),
+            0x0400_0071 => self.channel3_stop_wave_ram_select.set_byte(1, value),
+            0x0400_0072 => self.channel3_length_volume.set_byte(0, value),
+            0x0400_0073 => self.channel3_length_volume.set_byte(1, value),
+            0x0400_0074 => self.channel3_frequency_control.set_byte(0, value),
+            0x0400_0075 => {
+                self.channel3_frequency_control.set_byte(1, value);
+                if self.channel3_frequency_control.restart() {
+                    self.restart_channel3();
+                }
+            }
+            0x0400_0078 => self.channel4_length_envelope.set_byte(0, value),
+            0x0400_0079 => self.channel4_length_envelope.set_byte(1, value),
+            0x0400_007C => self.channel4_frequency_control.set_byte(0, value),
+            0x0400_007D => self.channel4_frequency_control.set_byte(1, value),
+            0x0400_0080 => self.control_stereo_volume_enable.set_byte(0, value),
+            0x0400_0081 => self.control_stereo_volume_enable.set_byte(1, value),
+            0x0400_0082 => self.control_mixing_dma_control.set_byte(0, value),
+            0x0400_0083 => self.control_mixing_dma_control.set_byte(1, value),
+            0x0400_0084 => self.control_sound_on_off.set_byte(0, value),
+            0x0400_0085 => self.control_sound_on_off.set_byte(1, value),
+            0x0400_0088 => self.sound_pwm_control.set_byte(0, value),
+            0x0400_0089 => self.sound_pwm_control.set_byte(1, value),
+            0x0400_0090..=0x0400_009F => {
+                self.write_wave_ram(address - 0x0400_0090, value);
+            }
+            0x0400_00A0 => self.channel_a_fifo.set_byte(0, value),
+            0x0400_00A1 => self.channel_a_fifo.set_byte(1, value),
+            0x0400_00A2 => self.channel_a_fifo.set_byte(2, value),
+            0x0400_00A3 => self.channel_a_fifo.set_byte(3, value),
+            0x0400_00A4 => self.channel_b_fifo.set_byte(0, value),
+            0x0400_00A5 => self.channel_b_fifo.set_byte(1, value),
+            0x0400_00A6 => self.channel_b_fifo.set_byte(2, value),
+            0x0400_00A7 => self.channel_b_fifo.set_byte(3, value),
+            0x0400_0066..=0x0400_0067
+            | 0x0400_006A..=0x0400_006B
+            | 0x0400_006E..=0x0400_006F
+            | 0x0400_0076..=0x0400_0077
+            | 0x0400_007A..=0x0400_007B
+            | 0x0400_007E..=0x0400_007F
+            | 0x0400_0086..=0x0400_0087
+            | 0x0400_008A..=0x0400_008F
+            | 0x0400_00A8..=0x0400_00AF => return false,
+            _ => panic!("Sound write address is out of bound"),
+        }
+
+        true
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> bincode::Result<Vec<u8>> {
+        bincode::serialize(self)
+    }
+
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        let filter = OutputFilter::new(self.filter.settings(), self.filter.sample_rate());
+        let resampler = Resampler::new(self.resampler.samples_per_frame());
+        *self = bincode::deserialize(data)?;
+        self.filter = filter;
+        self.resampler = resampler;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Channel 3 on, playing bank 1.
+    const PLAY_BANK_1: Sound3CntL = Sound3CntL(1 << 7 | 1 << 6);
+    /// Channel 3 on, playing both banks starting from bank 1.
+    const PLAY_BOTH_BANKS: Sound3CntL = Sound3CntL(1 << 7 | 1 << 6 | 1 << 5);
+    const VOLUME_100: Sound3CntH = Sound3CntH(1 << 13);
+    /// The fastest rate, a digit every 8 cycles.
+    const RATE: Sound3CntX = Sound3CntX(2047);
+
+    /// Bank 0 ramps up from 0 to 15 and back, bank 1 is a square wave.
+    fn sound_with_waves() -> Sound {
+        let mut sound = Sound {
+            channel3_length_volume: VOLUME_100,
+            channel3_frequency_control: RATE,
+            ..Default::default()
+        };
+
+        // The CPU writes to the bank that isn't selected.
+        sound.channel3_stop_wave_ram_select = PLAY_BANK_1;
+        for (offset, value) in [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF]
+            .into_iter()
+            .enumerate()
+        {
+            sound.write_wave_ram(offset, value);
+            sound.write_wave_ram(15 - offset, value.rotate_left(4));
+        }
+
+        sound.channel3_stop_wave_ram_select = Sound3CntL(0);
+        for offset in 0..16 {
+            sound.write_wave_ram(offset, if offset < 8 { 0xFF } else { 0x00 });
+        }
+
+        sound
+    }
+
+    fn waveform(sound: &mut Sound, digits: usize) -> Vec<i8> {
+        let mut samples = Vec::new();
+        for _ in 0..digits {
+            samples.push(sound.channel3_sample().unwrap());
+            sound.step(8);
+        }
+
+        samples
+    }
+
+    fn ramp() -> Vec<i8> {
+        (-8..=7).chain((-8..=7).rev()).collect()
+    }
+
+    fn square() -> Vec<i8> {
+        [vec![7; 16], vec![-8; 16]].concat()
+    }
+
+    #[test]
+    fn wave_ram_accesses_the_bank_not_played() {
+        let mut sound = sound_with_waves();
+
+        assert_eq!(sound.channel3_wave_ram[0][..2], [0x01, 0x23]);
+        assert_eq!(sound.channel3_wave_ram[1][..2], [0xFF, 0xFF]);
+
+        sound.channel3_stop_wave_ram_select = PLAY_BANK_1;
+        assert_eq!(sound.read_wave_ram(1), 0x23);
+        sound.channel3_stop_wave_ram_select = Sound3CntL(0);
+        assert_eq!(sound.read_wave_ram(1), 0xFF);
+    }
+
+    #[test]
+    fn single_bank_waveforms() {
+        let mut sound = sound_with_waves();
+        assert_eq!(sound.channel3_sample(), None);
+
+        sound.channel3_stop_wave_ram_select = Sound3CntL(1 << 7);
+        sound.restart_channel3();
+        assert_eq!(waveform(&mut sound, 64), [ramp(), ramp()].concat());
+
+        // Switching bank goes on from the same digit of the other bank.
+        sound.channel3_stop_wave_ram_select = PLAY_BANK_1;
+        assert_eq!(waveform(&mut sound, 32), square());
+
+        // Restarting goes back to the first digit.
+        waveform(&mut sound, 5);
+        sound.restart_channel3();
+        assert_eq!(waveform(&mut sound, 32), square());
+
+        sound.channel3_stop_wave_ram_select = Sound3CntL(0);
+        sound.step(1);
+        assert_eq!(sound.channel3_sample(), None);
+    }
+
+    #[test]
+    fn two_banks_play_64_digits() {
+        let mut sound = sound_with_waves();
+
+        sound.channel3_stop_wave_ram_select = PLAY_BOTH_BANKS;
+        sound.restart_channel3();
+        assert_eq!(
+            waveform(&mut sound, 128),
+            [square(), ramp(), square(), ramp()].concat()
+        );
+    }
+
+    #[test]
+    fn channel3_volume() {
+        let mut sound = sound_with_waves();
+        sound.channel3_stop_wave_ram_select = PLAY_BANK_1;
+        sound.restart_channel3();
+
+        sound.channel3_length_volume = Sound3CntH(2 << 13);
+        assert_eq!(sound.channel3_sample(), Some(3));
+        sound.channel3_length_volume = Sound3CntH(3 << 13);
+        assert_eq!(sound.channel3_sample(), Some(1));
+        // Bit 15 forces 75% whatever the volume.
+        sound.channel3_length_volume = Sound3CntH(1 << 15);
+        assert_eq!(sound.channel3_sample(), Some(5));
+        sound.channel3_length_volume = Sound3CntH(0);
+        assert_eq!(sound.channel3_sample(), Some(0));
+    }
 }
@@ -0,0 +1,205 @@
+//! Analog stage between the mixer and the speaker: a capacitor blocks the DC offset of
+//! the output, and the PWM output and the amplifier cut the highest frequencies.
+
+use super::mixer::StereoSample;
+
+/// Rate of the mixed samples with the default `SOUNDBIAS` resolution.
+pub const DEFAULT_SAMPLE_RATE: u32 = 32_768;
+
+/// Cutoff frequencies of the filters, in Hz. `None` leaves the filter out of the chain.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FilterSettings {
+    pub high_pass: Option<f64>,
+    pub low_pass: Option<f64>,
+}
+
+impl FilterSettings {
+    /// Approximates the output of the hardware.
+    pub const HARDWARE: Self = Self {
+        high_pass: Some(20.0),
+        low_pass: Some(8_000.0),
+    };
+
+    /// The mix as it is, for recordings without the hardware coloration.
+    pub const BYPASS: Self = Self {
+        high_pass: None,
+        low_pass: None,
+    };
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        Self::HARDWARE
+    }
+}
+
+/// Single pole filter, one state per side.
+#[derive(Clone, Copy, Debug)]
+struct Pole {
+    alpha: f64,
+    last_input: [f64; 2],
+    last_output: [f64; 2],
+}
+
+impl Pole {
+    fn high_pass(cutoff: f64, sample_rate: u32) -> Self {
+        let (rc, dt) = time_constants(cutoff, sample_rate);
+
+        Self::with_alpha(rc / (rc + dt))
+    }
+
+    fn low_pass(cutoff: f64, sample_rate: u32) -> Self {
+        let (rc, dt) = time_constants(cutoff, sample_rate);
+
+        Self::with_alpha(dt / (rc + dt))
+    }
+
+    const fn with_alpha(alpha: f64) -> Self {
+        Self {
+            alpha,
+            last_input: [0.0; 2],
+            last_output: [0.0; 2],
+        }
+    }
+
+    fn apply_high_pass(&mut self, side: usize, input: f64) -> f64 {
+        let output = self.alpha * (self.last_output[side] + input - self.last_input[side]);
+        self.last_input[side] = input;
+        self.last_output[side] = output;
+
+        output
+    }
+
+    fn apply_low_pass(&mut self, side: usize, input: f64) -> f64 {
+        let output = self
+            .alpha
+            .mul_add(input - self.last_output[side], self.last_output[side]);
+        self.last_output[side] = output;
+
+        output
+    }
+}
+
+fn time_constants(cutoff: f64, sample_rate: u32) -> (f64, f64) {
+    (
+        1.0 / (2.0 * std::f64::consts::PI * cutoff),
+        1.0 / f64::from(sample_rate),
+    )
+}
+
+/// Runs the mixed samples through the high-pass then the low-pass filter.
+#[derive(Clone, Debug)]
+pub struct OutputFilter {
+    settings: FilterSettings,
+    sample_rate: u32,
+    high_pass: Option<Pole>,
+    low_pass: Option<Pole>,
+}
+
+impl OutputFilter {
+    #[must_use]
+    pub fn new(settings: FilterSettings, sample_rate: u32) -> Self {
+        Self {
+            settings,
+            sample_rate,
+            high_pass: settings
+                .high_pass
+                .map(|cutoff| Pole::high_pass(cutoff, sample_rate)),
+            low_pass: settings
+                .low_pass
+                .map(|cutoff| Pole::low_pass(cutoff, sample_rate)),
+        }
+    }
+
+    #[must_use]
+    pub const fn settings(&self) -> FilterSettings {
+        self.settings
+    }
+
+    #[must_use]
+    pub const fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn apply(&mut self, sample: StereoSample) -> StereoSample {
+        StereoSample {
+            left: self.apply_side(0, sample.left),
+            right: self.apply_side(1, sample.right),
+        }
+    }
+
+    fn apply_side(&mut self, side: usize, value: i16) -> i16 {
+        let mut value = f64::from(value);
+        if let Some(pole) = &mut self.high_pass {
+            value = pole.apply_high_pass(side, value);
+        }
+        if let Some(pole) = &mut self.low_pass {
+            value = pole.apply_low_pass(side, value);
+        }
+
+        // The conversion saturates.
+        value.round() as i16
+    }
+}
+
+impl Default for OutputFilter {
+    fn default() -> Self {
+        Self::new(FilterSettings::default(), DEFAULT_SAMPLE_RATE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn constant(value: i16) -> StereoSample {
+        StereoSample {
+            left: value,
+            right: -value,
+        }
+    }
+
+    #[test]
+    fn bypass_keeps_the_mix() {
+        let mut filter = OutputFilter::new(FilterSettings::BYPASS, DEFAULT_SAMPLE_RATE);
+
+        for value in [0, 1000, -32767, 32767, 5] {
+            assert_eq!(filter.apply(constant(value)), constant(value));
+        }
+    }
+
+    #[test]
+    fn high_pass_removes_dc_offset() {
+        let settings = FilterSettings {
+            low_pass: None,
+            ..FilterSettings::HARDWARE
+        };
+        let mut filter = OutputFilter::new(settings, DEFAULT_SAMPLE_RATE);
+
+        // The step goes through, then decays.
+        assert!(filter.apply(constant(8000)).left > 7900);
+        let mut output = StereoSample::default();
+        for _ in 0..DEFAULT_SAMPLE_RATE {
+            output = filter.apply(constant(8000));
+        }
+        assert_eq!(output, StereoSample::default());
+    }
+
+    #[test]
+    fn low_pass_smooths_steps() {
+        let settings = FilterSettings {
+            high_pass: None,
+            ..FilterSettings::HARDWARE
+        };
+        let mut filter = OutputFilter::new(settings, DEFAULT_SAMPLE_RATE);
+
+        let levels = (0..16)
+            .map(|_| filter.apply(constant(8000)).left)
+            .collect::<Vec<_>>();
+        assert!(levels[0] > 0 && levels[0] < 8000);
+        assert!(levels.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(levels[15], 8000);
+    }
+}
@@ -0,0 +1,228 @@
+//! Last stage of the sound output: routes every channel to the left and right
+//! speakers and applies the volumes selected in SOUNDCNT.
+
+use super::registers::{SoundCntH, SoundCntL, SoundCntX};
+
+/// Samples needed by a channel to fade in or out when it starts or stops, instead of
+/// jumping straight to its level and clicking.
+pub const RAMP_LENGTH: i32 = 32;
+
+/// The loudest mix (PSG and both FIFOs at full volume) is 768, scaled to fit `i16`.
+const OUTPUT_SCALE: i32 = 32;
+
+const LEFT: usize = 0;
+const RIGHT: usize = 1;
+
+/// Channels 1 to 4, then FIFO A and FIFO B.
+const CHANNELS: usize = 6;
+
+/// One sample of every channel, `None` when the channel isn't playing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChannelSamples {
+    /// Channels 1 to 4, centered: from -8 to 7.
+    pub psg: [Option<i8>; 4],
+    /// FIFO A and FIFO B.
+    pub fifo: [Option<i8>; 2],
+}
+
+impl ChannelSamples {
+    const fn channels(self) -> [Option<i8>; CHANNELS] {
+        let [psg1, psg2, psg3, psg4] = self.psg;
+        let [fifo_a, fifo_b] = self.fifo;
+
+        [psg1, psg2, psg3, psg4, fifo_a, fifo_b]
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StereoSample {
+    pub left: i16,
+    pub right: i16,
+}
+
+/// What `SOUNDCNT_L`, `SOUNDCNT_H` and `SOUNDCNT_X` select, indexed by side.
+struct Routing {
+    enabled: [[bool; 2]; CHANNELS],
+    /// From 1 to 8.
+    master_volume: [i32; 2],
+    /// 25%, 50% and 100% of the PSG volume.
+    psg_shift: u32,
+    fifo_full_volume: [bool; 2],
+}
+
+impl Routing {
+    fn new(
+        stereo_volume_enable: SoundCntL,
+        mixing_dma_control: SoundCntH,
+        sound_on_off: SoundCntX,
+    ) -> Self {
+        let master_enable = sound_on_off.master_enable();
+        let mut enabled = [[false; 2]; CHANNELS];
+
+        for (channel, sides) in enabled.iter_mut().enumerate().take(4) {
+            let channel = channel as u8;
+            sides[LEFT] = master_enable && stereo_volume_enable.left_enabled(channel);
+            sides[RIGHT] = master_enable && stereo_volume_enable.right_enabled(channel);
+        }
+        enabled[4][LEFT] = master_enable && mixing_dma_control.fifo_a_left();
+        enabled[4][RIGHT] = master_enable && mixing_dma_control.fifo_a_right();
+        enabled[5][LEFT] = master_enable && mixing_dma_control.fifo_b_left();
+        enabled[5][RIGHT] = master_enable && mixing_dma_control.fifo_b_right();
+
+        Self {
+            enabled,
+            master_volume: [
+                i32::from(stereo_volume_enable.left_volume()) + 1,
+                i32::from(stereo_volume_enable.right_volume()) + 1,
+            ],
+            // 3 is prohibited, it's played as 100%.
+            psg_shift: 2 - u32::from(mixing_dma_control.psg_volume().min(2)),
+            fifo_full_volume: [
+                mixing_dma_control.fifo_a_full_volume(),
+                mixing_dma_control.fifo_b_full_volume(),
+            ],
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Mixer {
+    /// From 0 (silent) to [`RAMP_LENGTH`] (full level).
+    gains: [[i32; 2]; CHANNELS],
+    /// Fading out channels keep playing their last sample.
+    last_samples: [i32; CHANNELS],
+}
+
+impl Mixer {
+    pub(super) fn mix(
+        &mut self,
+        stereo_volume_enable: SoundCntL,
+        mixing_dma_control: SoundCntH,
+        sound_on_off: SoundCntX,
+        samples: ChannelSamples,
+    ) -> StereoSample {
+        let routing = Routing::new(stereo_volume_enable, mixing_dma_control, sound_on_off);
+        let mut psg = [0; 2];
+        let mut fifo = [0; 2];
+
+        for (channel, sample) in samples.channels().into_iter().enumerate() {
+            if let Some(sample) = sample {
+                self.last_samples[channel] = i32::from(sample);
+            }
+
+            for side in [LEFT, RIGHT] {
+                let target = if sample.is_some() && routing.enabled[channel][side] {
+                    RAMP_LENGTH
+                } else {
+                    0
+                };
+
+                let gain = &mut self.gains[channel][side];
+                *gain += (target - *gain).signum();
+
+                let level = self.last_samples[channel] * *gain;
+                if channel < 4 {
+                    psg[side] += level;
+                } else if routing.fifo_full_volume[channel - 4] {
+                    fifo[side] += level * 2;
+                } else {
+                    fifo[side] += level;
+                }
+            }
+        }
+
+        let [left, right] = [LEFT, RIGHT].map(|side| {
+            let psg = (psg[side] * routing.master_volume[side]) >> routing.psg_shift;
+            let output = (psg + fifo[side]) * OUTPUT_SCALE / RAMP_LENGTH;
+
+            output.clamp(i16::MIN.into(), i16::MAX.into()) as i16
+        });
+
+        StereoSample { left, right }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const MASTER_ENABLE: SoundCntX = SoundCntX(1 << 7);
+    const PSG_100: SoundCntH = SoundCntH(0b10);
+
+    fn settle(
+        mixer: &mut Mixer,
+        cnt_l: SoundCntL,
+        cnt_h: SoundCntH,
+        samples: ChannelSamples,
+    ) -> StereoSample {
+        let mut output = StereoSample::default();
+        for _ in 0..RAMP_LENGTH {
+            output = mixer.mix(cnt_l, cnt_h, MASTER_ENABLE, samples);
+        }
+
+        output
+    }
+
+    #[test]
+    fn panning_and_master_volume() {
+        let mut mixer = Mixer::default();
+        let samples = ChannelSamples {
+            psg: [Some(7), Some(-4), None, None],
+            fifo: [Some(100), Some(-100)],
+        };
+
+        // Channel 1 left at master volume 8, channel 2 right at master volume 2.
+        let cnt_l = SoundCntL(1 << 12 | 1 << 9 | 7 << 4 | 1);
+        let output = settle(&mut mixer, cnt_l, PSG_100, samples);
+        assert_eq!(
+            output,
+            StereoSample {
+                left: 7 * 8 * OUTPUT_SCALE as i16,
+                right: -4 * 2 * OUTPUT_SCALE as i16,
+            }
+        );
+
+        // FIFO A left at 100%, FIFO B right at 50%, the PSG at 25%.
+        let cnt_h = SoundCntH(1 << 12 | 1 << 9 | 1 << 2);
+        let output = settle(&mut mixer, cnt_l, cnt_h, samples);
+        assert_eq!(
+            output,
+            StereoSample {
+                left: (((7 * 8) >> 2) + 200) * OUTPUT_SCALE as i16,
+                right: (((-4 * 2) >> 2) - 100) * OUTPUT_SCALE as i16,
+            }
+        );
+    }
+
+    #[test]
+    fn channels_ramp_when_starting_and_stopping() {
+        let mut mixer = Mixer::default();
+        let cnt_l = SoundCntL(1 << 12 | 7 << 4);
+        let playing = ChannelSamples {
+            psg: [Some(7), None, None, None],
+            ..Default::default()
+        };
+
+        let levels = (0..RAMP_LENGTH)
+            .map(|_| mixer.mix(cnt_l, PSG_100, MASTER_ENABLE, playing).left)
+            .collect::<Vec<i16>>();
+        assert!(levels.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(levels[0], 7 * 8 * OUTPUT_SCALE as i16 / RAMP_LENGTH as i16);
+        assert_eq!(levels[31], 7 * 8 * OUTPUT_SCALE as i16);
+
+        // Once stopped the last sample fades out.
+        let stopped = ChannelSamples::default();
+        let levels = (0..RAMP_LENGTH)
+            .map(|_| mixer.mix(cnt_l, PSG_100, MASTER_ENABLE, stopped).left)
+            .collect::<Vec<i16>>();
+        assert!(levels.windows(2).all(|pair| pair[0] > pair[1]));
+        assert_eq!(levels[31], 0);
+
+        // Turning the master enable off fades out as well.
+        settle(&mut mixer, cnt_l, PSG_100, playing);
+        let output = mixer.mix(cnt_l, PSG_100, SoundCntX(0), playing);
+        assert!(output.left > 0 && output.left < 7 * 8 * OUTPUT_SCALE as i16);
+    }
+}
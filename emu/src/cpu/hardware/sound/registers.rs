@@ -0,0 +1,82 @@
+use crate::cpu::hardware::bitfield::bitfield;
+
+bitfield! {
+    /// `SOUNDCNT_L`, PSG channels output. The enables have one bit per channel.
+    pub struct SoundCntL(u16) {
+        /// From 0 to 7, played as 1/8 to 8/8.
+        right_volume: 0..=2;
+        left_volume: 4..=6;
+        right_enables: 8..=11;
+        left_enables: 12..=15;
+    }
+}
+
+impl SoundCntL {
+    /// Whether PSG channel `channel`, counted from 0, is sent to the right speaker.
+    #[must_use]
+    pub const fn right_enabled(self, channel: u8) -> bool {
+        self.right_enables() & (1 << channel) != 0
+    }
+
+    #[must_use]
+    pub const fn left_enabled(self, channel: u8) -> bool {
+        self.left_enables() & (1 << channel) != 0
+    }
+}
+
+bitfield! {
+    /// `SOUNDCNT_H`, FIFOs output and PSG volume.
+    pub struct SoundCntH(u16) {
+        /// 25%, 50% and 100%, 3 is prohibited.
+        psg_volume: 0..=1;
+        /// 100%, 50% otherwise.
+        fifo_a_full_volume: 2;
+        fifo_b_full_volume: 3;
+        fifo_a_right: 8;
+        fifo_a_left: 9;
+        fifo_a_timer: 10;
+        fifo_a_reset: 11;
+        fifo_b_right: 12;
+        fifo_b_left: 13;
+        fifo_b_timer: 14;
+        fifo_b_reset: 15;
+    }
+}
+
+bitfield! {
+    /// `SOUNDCNT_X`, the PSG flags are set by the channels.
+    pub struct SoundCntX(u16) {
+        psg_on_flags: 0..=3;
+        master_enable: 7;
+    }
+}
+
+bitfield! {
+    /// `SOUND3CNT_L`, see [`super::wave`].
+    pub struct Sound3CntL(u16) {
+        two_banks: 5;
+        bank: 6;
+        playing: 7;
+    }
+}
+
+bitfield! {
+    /// `SOUND3CNT_H`
+    pub struct Sound3CntH(u16) {
+        length: 0..=7;
+        /// Mute, 100%, 50% and 25%.
+        volume: 13..=14;
+        /// 75% whatever the volume.
+        force_volume: 15;
+    }
+}
+
+bitfield! {
+    /// `SOUND3CNT_X`
+    pub struct Sound3CntX(u16) {
+        /// A digit lasts `(2048 - rate) * 8` cycles.
+        rate: 0..=10;
+        length_enabled: 14;
+        restart: 15;
+    }
+}
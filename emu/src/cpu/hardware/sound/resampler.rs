@@ -0,0 +1,162 @@
+//! Takes the mix at a fixed number of samples per frame.
+//!
+//! The samples are spread over the cycles of the frame with a fractional carry, and the
+//! frames are delimited by Vblank, so every frame has the same count: a frontend can
+//! queue them in a plain ring buffer without compensating for drift, and a movie can
+//! store them as they are.
+
+use crate::clock::{CPU_FREQUENCY, CYCLES_PER_FRAME};
+
+use super::mixer::StereoSample;
+
+/// 549 samples per frame is 32,790 Hz, the closest to the rate of the hardware with the
+/// default `SOUNDBIAS` resolution.
+pub const DEFAULT_SAMPLES_PER_FRAME: u32 = 549;
+
+pub struct Resampler {
+    samples_per_frame: u32,
+    /// Progress towards the next sample, in samples per frame for every cycle: a sample
+    /// is due each time it reaches [`CYCLES_PER_FRAME`].
+    phase: u64,
+    current: Vec<StereoSample>,
+    completed: Vec<StereoSample>,
+}
+
+impl Resampler {
+    /// # Panics
+    /// If `samples_per_frame` is 0 or more than one sample per cycle.
+    #[must_use]
+    pub fn new(samples_per_frame: u32) -> Self {
+        assert!(
+            samples_per_frame > 0 && u64::from(samples_per_frame) <= CYCLES_PER_FRAME,
+            "invalid number of samples per frame"
+        );
+
+        Self {
+            samples_per_frame,
+            phase: 0,
+            current: Vec::with_capacity(samples_per_frame as usize),
+            completed: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub const fn samples_per_frame(&self) -> u32 {
+        self.samples_per_frame
+    }
+
+    /// Samples per second at full speed, rounded down.
+    #[must_use]
+    pub const fn sample_rate(&self) -> u32 {
+        (self.samples_per_frame as u64 * CPU_FREQUENCY / CYCLES_PER_FRAME) as u32
+    }
+
+    /// Advances by one cycle, `true` when a sample has to be taken.
+    pub fn step(&mut self) -> bool {
+        self.phase += u64::from(self.samples_per_frame);
+        if self.phase < CYCLES_PER_FRAME {
+            return false;
+        }
+
+        self.phase -= CYCLES_PER_FRAME;
+        true
+    }
+
+    pub fn push(&mut self, sample: StereoSample) {
+        self.current.push(sample);
+    }
+
+    /// Called when the LCD enters Vblank: the samples taken since the last call make a
+    /// frame. The frames cut short, at power-on or when a save-state is loaded, are
+    /// padded with their last sample.
+    pub fn end_frame(&mut self) {
+        let last = self.current.last().copied().unwrap_or_default();
+        self.current.resize(self.samples_per_frame as usize, last);
+
+        self.completed = std::mem::replace(
+            &mut self.current,
+            Vec::with_capacity(self.samples_per_frame as usize),
+        );
+        self.phase = 0;
+    }
+
+    /// Samples of the last completed frame, only the last one is kept. Empty if it was
+    /// already taken or no frame was completed yet.
+    pub fn take_frame(&mut self) -> Vec<StereoSample> {
+        std::mem::take(&mut self.completed)
+    }
+}
+
+impl Default for Resampler {
+    fn default() -> Self {
+        Self::new(DEFAULT_SAMPLES_PER_FRAME)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn run_frame(resampler: &mut Resampler, cycles: u64) {
+        for cycle in 0..cycles {
+            if resampler.step() {
+                let value = i16::try_from(cycle % 1000).unwrap();
+                resampler.push(StereoSample {
+                    left: value,
+                    right: -value,
+                });
+            }
+        }
+        resampler.end_frame();
+    }
+
+    #[test]
+    fn every_frame_has_the_same_count() {
+        for samples_per_frame in [1, 548, 549, 800, 803, 1024] {
+            let mut resampler = Resampler::new(samples_per_frame);
+
+            for _ in 0..3 {
+                run_frame(&mut resampler, CYCLES_PER_FRAME);
+                assert_eq!(
+                    resampler.take_frame().len(),
+                    samples_per_frame as usize,
+                    "{samples_per_frame}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn samples_are_spread_over_the_frame() {
+        let mut resampler = Resampler::new(4);
+        let mut due = Vec::new();
+
+        for cycle in 0..CYCLES_PER_FRAME {
+            if resampler.step() {
+                due.push(cycle);
+            }
+        }
+
+        assert_eq!(due, [70_223, 140_447, 210_671, 280_895]);
+    }
+
+    #[test]
+    fn short_frames_are_padded() {
+        let mut resampler = Resampler::default();
+
+        run_frame(&mut resampler, CYCLES_PER_FRAME / 2);
+        let samples = resampler.take_frame();
+
+        assert_eq!(samples.len(), DEFAULT_SAMPLES_PER_FRAME as usize);
+        assert_eq!(samples[274], samples[548]);
+        assert!(resampler.take_frame().is_empty());
+    }
+
+    #[test]
+    fn sample_rate() {
+        assert_eq!(Resampler::default().sample_rate(), 32_790);
+        assert_eq!(Resampler::new(804).sample_rate(), 48_020);
+    }
+}
@@ -0,0 +1,106 @@
+//! Channel 3 plays 4-bit digits from the wave RAM.
+//!
+//! The wave RAM holds two banks of 32 digits: one is played (selected by bit 6 of
+//! `SOUND3CNT_L`) while the CPU reads and writes the other one. With bit 5 set the
+//! channel plays both banks as a single 64 digits wave, starting from the selected one.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::registers::{Sound3CntH, Sound3CntL, Sound3CntX};
+
+const DIGITS_PER_BANK: u8 = 32;
+
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WaveChannel {
+    playing: bool,
+    /// Digit being played, counted from the start of the selected bank.
+    position: u8,
+    /// CPU cycles left before the next digit.
+    timer: u32,
+}
+
+/// CPU cycles a digit is played for, the rate is in bits 0-10 of `SOUND3CNT_X`.
+fn digit_period(frequency_control: Sound3CntX) -> u32 {
+    (2048 - u32::from(frequency_control.rate())) * 8
+}
+
+/// Bank played: the other one is the one seen by the CPU.
+pub(super) fn selected_bank(stop_wave_ram_select: Sound3CntL) -> usize {
+    stop_wave_ram_select.bank().into()
+}
+
+impl WaveChannel {
+    /// Bit 15 of `SOUND3CNT_X` was written: the wave starts over.
+    pub(super) fn restart(
+        &mut self,
+        stop_wave_ram_select: Sound3CntL,
+        frequency_control: Sound3CntX,
+    ) {
+        self.playing = stop_wave_ram_select.playing();
+        self.position = 0;
+        self.timer = digit_period(frequency_control);
+    }
+
+    pub(super) fn step(&mut self, stop_wave_ram_select: Sound3CntL, frequency_control: Sound3CntX) {
+        if !stop_wave_ram_select.playing() {
+            self.playing = false;
+        }
+
+        if !self.playing {
+            return;
+        }
+
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            let length = if stop_wave_ram_select.two_banks() {
+                DIGITS_PER_BANK * 2
+            } else {
+                DIGITS_PER_BANK
+            };
+
+            self.position = (self.position + 1) % length;
+            self.timer = digit_period(frequency_control);
+        }
+    }
+
+    /// Digit being played, centered (from -8 to 7) and scaled by the volume in
+    /// `SOUND3CNT_H`.
+    pub(super) fn sample(
+        &self,
+        wave_ram: &[[u8; 16]; 2],
+        stop_wave_ram_select: Sound3CntL,
+        length_volume: Sound3CntH,
+    ) -> Option<i8> {
+        if !self.playing {
+            return None;
+        }
+
+        // Past the first 32 digits (in 64 digits mode) the other bank is played.
+        let bank = selected_bank(stop_wave_ram_select) ^ usize::from(self.position >= 32);
+        let index = self.position % DIGITS_PER_BANK;
+        let byte = wave_ram[bank][usize::from(index / 2)];
+
+        // The upper digit of every byte is played first.
+        let digit = if index.is_multiple_of(2) {
+            byte >> 4
+        } else {
+            byte & 0xF
+        };
+        let centered = i8::try_from(digit).unwrap() - 8;
+
+        let sample = if length_volume.force_volume() {
+            (centered * 3) >> 2
+        } else {
+            match length_volume.volume() {
+                0 => 0,
+                1 => centered,
+                2 => centered >> 1,
+                _ => centered >> 2,
+            }
+        };
+
+        Some(sample)
+    }
+}
@@ -1,21 +1,107 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Serialize, Deserialize)]
+use crate::bitwise::Bits;
+use crate::cpu::hardware::bitfield::bitfield;
+use crate::cpu::hardware::component::HardwareComponent;
+
+bitfield! {
+    /// `TMxCNT_H`
+    pub struct TimerCnt(u16) {
+        /// Counts every 1, 64, 256 or 1024 cycles.
+        prescaler: 0..=1;
+        /// Counts when the previous timer overflows, ignoring the prescaler.
+        count_up: 2;
+        irq_enable: 6;
+        enabled: 7;
+    }
+}
+
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Timers {
     /// Timer 0 Counter/Reload
     pub tm0cnt_l: u16,
     /// Timer 0 Control
-    pub tm0cnt_h: u16,
+    pub tm0cnt_h: TimerCnt,
     /// Timer 1 Counter/Reload
     pub tm1cnt_l: u16,
     /// Timer 1 Control
-    pub tm1cnt_h: u16,
+    pub tm1cnt_h: TimerCnt,
     /// Timer 2 Counter/Reload
     pub tm2cnt_l: u16,
     /// Timer 2 Control
-    pub tm2cnt_h: u16,
+    pub tm2cnt_h: TimerCnt,
     /// Timer 3 Counter/Reload
     pub tm3cnt_l: u16,
     /// Timer 3 Control
-    pub tm3cnt_h: u16,
+    pub tm3cnt_h: TimerCnt,
+}
+
+impl HardwareComponent for Timers {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn on_read(&self, address: usize) -> Option<u8> {
+        let value = match address {
+            0x0400_0100 => self.tm0cnt_l.get_byte(0),
+            0x0400_0101 => self.tm0cnt_l.get_byte(1),
+            0x0400_0102 => self.tm0cnt_h.get_byte(0),
+            0x0400_0103 => self.tm0cnt_h.get_byte(1),
+            0x0400_0104 => self.tm1cnt_l.get_byte(0),
+            0x0400_0105 => self.tm1cnt_l.get_byte(1),
+            0x0400_0106 => self.tm1cnt_h.get_byte(0),
+            0x0400_0107 => self.tm1cnt_h.get_byte(1),
+            0x0400_0108 => self.tm2cnt_l.get_byte(0),
+            0x0400_0109 => self.tm2cnt_l.get_byte(1),
+            0x0400_010A => self.tm2cnt_h.get_byte(0),
+            0x0400_010B => self.tm2cnt_h.get_byte(1),
+            0x0400_010C => self.tm3cnt_l.get_byte(0),
+            0x0400_010D => self.tm3cnt_l.get_byte(1),
+            0x0400_010E => self.tm3cnt_h.get_byte(0),
+            0x0400_010F => self.tm3cnt_h.get_byte(1),
+            0x0400_0110..=0x0400_011F => return None,
+            _ => panic!("Timers read address is out of bound"),
+        };
+
+        Some(value)
+    }
+
+    fn on_write(&mut self, address: usize, value: u8) -> bool {
+        match address {
+            0x0400_0100 => self.tm0cnt_l.set_byte(0, value),
+            0x0400_0101 => self.tm0cnt_l.set_byte(1, value),
+            0x0400_0102 => self.tm0cnt_h.set_byte(0, value),
+            0x0400_0103 => self.tm0cnt_h.set_byte(1, value),
+            0x0400_0104 => self.tm1cnt_l.set_byte(0, value),
+            0x0400_0105 => self.tm1cnt_l.set_byte(1, value),
+            0x0400_0106 => self.tm1cnt_h.set_byte(0, value),
+            0x0400_0107 => self.tm1cnt_h.set_byte(1, value),
+            0x0400_0108 => self.tm2cnt_l.set_byte(0, value),
+            0x0400_0109 => self.tm2cnt_l.set_byte(1, value),
+            0x0400_010A => self.tm2cnt_h.set_byte(0, value),
+            0x0400_010B => self.tm2cnt_h.set_byte(1, value),
+            0x0400_010C => self.tm3cnt_l.set_byte(0, value),
+            0x0400_010D => self.tm3cnt_l.set_byte(1, value),
+            0x0400_010E => self.tm3cnt_h.set_byte(0, value),
+            0x0400_010F => self.tm3cnt_h.set_byte(1, value),
+            0x0400_0110..=0x0400_011F => return false,
+            _ => panic!("Timers write address is out of bound"),
+        }
+
+        true
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_state(&self) -> bincode::Result<Vec<u8>> {
+        bincode::serialize(self)
+    }
+
+    #[cfg(feature = "serde")]
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        *self = bincode::deserialize(data)?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,82 @@
+//! Hooks to observe the execution from outside the core: profilers, coverage tools and
+//! scripts register a [`CpuObserver`] with
+//! [`Arm7tdmi::add_observer`](crate::cpu::arm7tdmi::Arm7tdmi::add_observer).
+//!
+//! Observers only see what happened, they can't change it. They are called from the
+//! emulation thread in the middle of a step: they should be quick, and send anything
+//! heavier elsewhere. Blocks run by the JIT don't report their instructions.
+
+use crate::cpu::arm7tdmi::ExceptionType;
+use crate::cpu::cpu_modes::Mode;
+
+/// An instruction that just went through the execute stage, its condition passed or not.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExecutedInstruction {
+    pub address: u32,
+    pub opcode: u32,
+    pub thumb: bool,
+    /// Bus cycles of the step, its fetch included.
+    pub cycles: u128,
+}
+
+/// The CPU entering an exception handler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExceptionEntry {
+    pub exception: ExceptionType,
+    /// The link register given to the handler.
+    pub return_address: u32,
+}
+
+/// Every method does nothing by default, observers implement the ones they need.
+pub trait CpuObserver: Send {
+    fn on_instruction_executed(&mut self, _instruction: &ExecutedInstruction) {}
+
+    fn on_exception(&mut self, _entry: &ExceptionEntry) {}
+
+    /// The mode changed by an exception, a write to CPSR or an exception return.
+    fn on_mode_change(&mut self, _from: &Mode, _to: &Mode) {}
+
+    /// A `SWI` with the BIOS function `number`, before the BIOS or its emulation runs it.
+    fn on_swi(&mut self, _number: u8) {}
+}
+
+/// Handle to remove an observer, see
+/// [`Arm7tdmi::remove_observer`](crate::cpu::arm7tdmi::Arm7tdmi::remove_observer).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObserverId(pub(crate) u64);
+
+/// The registered observers, called in the order they were added.
+#[derive(Default)]
+pub(crate) struct Observers {
+    next_id: u64,
+    observers: Vec<(ObserverId, Box<dyn CpuObserver>)>,
+}
+
+impl Observers {
+    pub(crate) fn add(&mut self, observer: Box<dyn CpuObserver>) -> ObserverId {
+        let id = ObserverId(self.next_id);
+        self.next_id += 1;
+        self.observers.push((id, observer));
+
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: ObserverId) -> Option<Box<dyn CpuObserver>> {
+        let index = self
+            .observers
+            .iter()
+            .position(|(observer_id, _)| *observer_id == id)?;
+
+        Some(self.observers.remove(index).1)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    pub(crate) fn each(&mut self, mut f: impl FnMut(&mut dyn CpuObserver)) {
+        for (_, observer) in &mut self.observers {
+            f(observer.as_mut());
+        }
+    }
+}
@@ -0,0 +1,861 @@
+//! Experimental compilation of code to host code with Cranelift.
+//!
+//! Enabled by the `jit` feature. The interpreter stays the reference implementation,
+//! only the simplest instructions are compiled and everything else falls back to it:
+//!
+//! - a block is a run of instructions that only read and write registers other than PC:
+//!   data processing without S in ARM, `ADD`/`MOV` on high registers and the additions
+//!   to SP in Thumb. No flags, no memory access, so no I/O access either;
+//! - blocks are only compiled from the BIOS and the cartridge ROM. Code running from RAM
+//!   can be rewritten at any time, so it's always interpreted. [`Jit::invalidate`] drops
+//!   every block when a debugging tool patches the memory.
+//!
+//! [`JitStats`] counts the lookups, the compiled blocks and the invalidations.
+//!
+//! Compiled instructions don't go through the disassembler and the logger.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use cranelift_codegen::ir::{types, AbiParam, InstBuilder, MemFlags, Value};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Module};
+
+use crate::bitwise::Bits;
+use crate::bus::Bus;
+use crate::cpu::arm;
+use crate::cpu::arm::alu_instruction::{AluSecondOperandInfo, ArmModeAluInstr, ShiftOperator};
+use crate::cpu::arm::instructions::ArmModeInstruction;
+use crate::cpu::condition::Condition;
+use crate::cpu::flags::ShiftKind;
+use crate::cpu::registers::{REG_PROGRAM_COUNTER, REG_SP};
+use crate::cpu::thumb;
+use crate::cpu::thumb::alu_instructions::ThumbHighRegisterOperation;
+use crate::cpu::thumb::instruction::Instruction;
+use crate::memory_map::{BIOS_SIZE, ROM_END, ROM_START};
+
+/// Interrupts are only taken between blocks, so they wait at most this many instructions.
+const MAX_BLOCK_LENGTH: u32 = 32;
+
+/// Entering compiled code costs more than interpreting a single instruction.
+const MIN_BLOCK_LENGTH: u32 = 2;
+
+/// Second operand, only shifted by an immediate amount.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    Immediate(u32),
+    Register {
+        rm: u32,
+        kind: ShiftKind,
+        amount: u32,
+    },
+}
+
+/// ARM data processing operation without flags, Thumb instructions are translated to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct RegisterOp {
+    alu: ArmModeAluInstr,
+    rd: u32,
+    rn: u32,
+    operand: Operand,
+}
+
+/// Checks the bits before decoding: past the end of the code a block can run into data,
+/// and the decoder panics on some encodings.
+fn arm_op(op_code: u32) -> Option<RegisterOp> {
+    let always = op_code.get_bits(28..=31) == Condition::AL as u32;
+    let data_processing =
+        op_code.get_bits(26..=27) == 0 && (op_code.get_bit(25) || !op_code.get_bit(4));
+    // Without flags, the test operations are PSR transfers and the ones with carry read C.
+    let without_flags = !op_code.get_bit(20) && matches!(op_code.get_bits(21..=24), 0..=4 | 0xC..);
+    if !(always && data_processing && without_flags) {
+        return None;
+    }
+
+    let ArmModeInstruction::DataProcessing {
+        alu_instruction,
+        rn,
+        destination,
+        op2,
+        ..
+    } = arm::lut::decode(op_code).instruction
+    else {
+        return None;
+    };
+
+    let operand = match op2 {
+        AluSecondOperandInfo::Immediate { base, shift } => {
+            Operand::Immediate(base.rotate_right(shift))
+        }
+        AluSecondOperandInfo::Register {
+            shift_op: ShiftOperator::Immediate(amount),
+            shift_kind,
+            register,
+        } => {
+            // ROR #0 is RRX, it reads C.
+            if register == REG_PROGRAM_COUNTER || (shift_kind == ShiftKind::Ror && amount == 0) {
+                return None;
+            }
+
+            Operand::Register {
+                rm: register,
+                kind: shift_kind,
+                amount,
+            }
+        }
+        AluSecondOperandInfo::Register { .. } => return None,
+    };
+
+    let reads_rn = !matches!(alu_instruction, ArmModeAluInstr::Mov | ArmModeAluInstr::Mvn);
+    if destination == REG_PROGRAM_COUNTER || (reads_rn && rn == REG_PROGRAM_COUNTER) {
+        return None;
+    }
+
+    Some(RegisterOp {
+        alu: alu_instruction,
+        rd: destination,
+        rn,
+        operand,
+    })
+}
+
+/// Same as [`arm_op`], the bits are checked before decoding.
+fn thumb_op(op_code: u16) -> Option<RegisterOp> {
+    let high_register = op_code.get_bits(10..=15) == 0b01_0001;
+    let add_offset_sp = op_code.get_bits(8..=15) == 0b1011_0000;
+    let load_address = op_code.get_bits(12..=15) == 0b1010;
+    if !(high_register || add_offset_sp || load_address) {
+        return None;
+    }
+
+    let sp = REG_SP as u32;
+    let op = match thumb::lut::decode(op_code).instruction {
+        Instruction::HiRegisterOpBX {
+            register_operation:
+                operation @ (ThumbHighRegisterOperation::Add | ThumbHighRegisterOperation::Mov),
+            source_register,
+            destination_register,
+        } => {
+            let rd = u32::from(destination_register);
+            let rm = u32::from(source_register);
+            if rd == REG_PROGRAM_COUNTER || rm == REG_PROGRAM_COUNTER {
+                return None;
+            }
+
+            RegisterOp {
+                alu: if operation == ThumbHighRegisterOperation::Add {
+                    ArmModeAluInstr::Add
+                } else {
+                    ArmModeAluInstr::Mov
+                },
+                rd,
+                rn: rd,
+                operand: Operand::Register {
+                    rm,
+                    kind: ShiftKind::Lsl,
+                    amount: 0,
+                },
+            }
+        }
+        Instruction::AddOffsetSP { s, word7 } => RegisterOp {
+            alu: if s {
+                ArmModeAluInstr::Sub
+            } else {
+                ArmModeAluInstr::Add
+            },
+            rd: sp,
+            rn: sp,
+            operand: Operand::Immediate(word7.into()),
+        },
+        Instruction::LoadAddress {
+            sp: true,
+            destination_register,
+            offset,
+        } => RegisterOp {
+            alu: ArmModeAluInstr::Add,
+            rd: destination_register,
+            rn: sp,
+            operand: Operand::Immediate(offset),
+        },
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+const fn is_read_only(address: u32) -> bool {
+    (address as usize) < BIOS_SIZE || (address >= ROM_START && address <= ROM_END)
+}
+
+/// The supported instructions from `address` on, up to [`MAX_BLOCK_LENGTH`].
+fn scan(bus: &Bus, address: u32, thumb: bool) -> Vec<RegisterOp> {
+    let size = if thumb { 2 } else { 4 };
+    let read = |address: u32| {
+        (0..size).fold(0, |value, byte| {
+            value | u32::from(bus.read_raw(address + byte)) << (8 * byte)
+        })
+    };
+
+    let mut ops = Vec::new();
+    let mut address = address;
+    while ops.len() < MAX_BLOCK_LENGTH as usize && is_read_only(address + size - 1) {
+        let op = if thumb {
+            thumb_op(read(address).get_bits(0..=15).try_into().unwrap())
+        } else {
+            arm_op(read(address))
+        };
+        let Some(op) = op else {
+            break;
+        };
+
+        ops.push(op);
+        address += size;
+    }
+
+    ops
+}
+
+/// Values of the registers in the compiled function, loaded on first use and stored
+/// back at the end when written.
+struct RegisterFile {
+    base: Value,
+    values: [Option<Value>; 16],
+    written: [bool; 16],
+}
+
+impl RegisterFile {
+    const fn offset(register: usize) -> i32 {
+        register as i32 * 4
+    }
+
+    fn read(&mut self, builder: &mut FunctionBuilder, register: u32) -> Value {
+        let register = register as usize;
+        let base = self.base;
+
+        *self.values[register].get_or_insert_with(|| {
+            builder.ins().load(
+                types::I32,
+                MemFlags::trusted(),
+                base,
+                Self::offset(register),
+            )
+        })
+    }
+
+    const fn write(&mut self, register: u32, value: Value) {
+        self.values[register as usize] = Some(value);
+        self.written[register as usize] = true;
+    }
+
+    fn store_written(&self, builder: &mut FunctionBuilder) {
+        for (register, value) in self.values.iter().enumerate() {
+            if let (Some(value), true) = (value, self.written[register]) {
+                builder.ins().store(
+                    MemFlags::trusted(),
+                    *value,
+                    self.base,
+                    Self::offset(register),
+                );
+            }
+        }
+    }
+}
+
+fn translate(builder: &mut FunctionBuilder, registers: &mut RegisterFile, op: RegisterOp) {
+    let operand = match op.operand {
+        Operand::Immediate(value) => builder.ins().iconst(types::I32, i64::from(value)),
+        Operand::Register { rm, kind, amount } => {
+            let rm = registers.read(builder, rm);
+            let amount = i64::from(amount);
+
+            // An amount of 0 encodes a shift by 32 for LSR and ASR.
+            match (kind, amount) {
+                (ShiftKind::Lsl, 0) => rm,
+                (ShiftKind::Lsl, _) => builder.ins().ishl_imm(rm, amount),
+                (ShiftKind::Lsr, 0) => builder.ins().iconst(types::I32, 0),
+                (ShiftKind::Lsr, _) => builder.ins().ushr_imm(rm, amount),
+                (ShiftKind::Asr, 0) => builder.ins().sshr_imm(rm, 31),
+                (ShiftKind::Asr, _) => builder.ins().sshr_imm(rm, amount),
+                (ShiftKind::Ror, _) => builder.ins().rotr_imm(rm, amount),
+            }
+        }
+    };
+
+    let result = match op.alu {
+        ArmModeAluInstr::Mov => operand,
+        ArmModeAluInstr::Mvn => builder.ins().bnot(operand),
+        alu => {
+            let rn = registers.read(builder, op.rn);
+            match alu {
+                ArmModeAluInstr::And => builder.ins().band(rn, operand),
+                ArmModeAluInstr::Eor => builder.ins().bxor(rn, operand),
+                ArmModeAluInstr::Sub => builder.ins().isub(rn, operand),
+                ArmModeAluInstr::Rsb => builder.ins().isub(operand, rn),
+                ArmModeAluInstr::Add => builder.ins().iadd(rn, operand),
+                ArmModeAluInstr::Orr => builder.ins().bor(rn, operand),
+                ArmModeAluInstr::Bic => builder.ins().band_not(rn, operand),
+                _ => unreachable!("{alu} is never compiled"),
+            }
+        }
+    };
+
+    registers.write(op.rd, result);
+}
+
+type BlockFn = unsafe extern "C" fn(*mut u32);
+
+/// Compiled instructions, run on the 16 visible registers.
+#[derive(Clone, Copy)]
+pub struct Block {
+    function: BlockFn,
+    length: u32,
+}
+
+impl Block {
+    /// Number of instructions.
+    #[must_use]
+    pub const fn length(self) -> u32 {
+        self.length
+    }
+
+    pub fn run(self, registers: &mut [u32; 16]) {
+        // SAFETY: the function only accesses the 16 registers it's given, and it was
+        // compiled by the module still owned by the `Jit` this block comes from.
+        unsafe { (self.function)(registers.as_mut_ptr()) }
+    }
+}
+
+/// Counters of the block cache, to find the games defeating it and tune the block length.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct JitStats {
+    /// Lookups of an address already scanned, compiled or not.
+    pub hits: u64,
+    /// Lookups of an address scanned for the first time.
+    pub misses: u64,
+    /// Times compiled blocks were dropped because the code may have changed.
+    pub invalidations: u64,
+    pub compiled_blocks: u64,
+    /// Instructions in the compiled blocks, [`Self::compiled_blocks`] long on average.
+    pub compiled_instructions: u64,
+    /// Addresses scanned without a block long enough to compile.
+    pub uncompilable: u64,
+    /// Instructions run from compiled blocks.
+    pub executed_instructions: u64,
+}
+
+impl JitStats {
+    /// Share of the lookups finding the address already scanned, from 0 to 1.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+impl fmt::Display for JitStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1}% hits ({} misses), {} blocks of {} instructions compiled, {} uncompilable, \
+             {} invalidations, {} instructions run compiled",
+            self.hit_rate() * 100.0,
+            self.misses,
+            self.compiled_blocks,
+            self.compiled_instructions,
+            self.uncompilable,
+            self.invalidations,
+            self.executed_instructions,
+        )
+    }
+}
+
+/// Cache of the compiled blocks by start address.
+pub struct Jit {
+    enabled: bool,
+    stats: JitStats,
+    /// Created with the first block.
+    module: Option<JITModule>,
+    context: FunctionBuilderContext,
+    /// `None` where the instructions can't be compiled, so that they are scanned once.
+    blocks: HashMap<(u32, bool), Option<Block>>,
+}
+
+impl Default for Jit {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            stats: JitStats::default(),
+            module: None,
+            context: FunctionBuilderContext::new(),
+            blocks: HashMap::new(),
+        }
+    }
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        self.invalidate();
+    }
+}
+
+impl Jit {
+    #[must_use]
+    pub const fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Disabled, every instruction goes through the interpreter.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.invalidate();
+        }
+    }
+
+    /// Counters since the creation of the cache.
+    #[must_use]
+    pub const fn stats(&self) -> JitStats {
+        self.stats
+    }
+
+    /// Drops every compiled block, for when the code may have changed.
+    pub fn invalidate(&mut self) {
+        if !self.blocks.is_empty() {
+            self.stats.invalidations += 1;
+        }
+        self.blocks.clear();
+        if let Some(module) = self.module.take() {
+            // SAFETY: the blocks pointing to the code of the module were just dropped.
+            unsafe { module.free_memory() };
+        }
+    }
+
+    /// The block starting at `address`, compiled on the first call.
+    ///
+    /// # Panics
+    /// If Cranelift doesn't support the host.
+    pub fn block(&mut self, bus: &Bus, address: u32, thumb: bool) -> Option<Block> {
+        if !self.enabled {
+            return None;
+        }
+
+        let block = if let Some(block) = self.blocks.get(&(address, thumb)) {
+            self.stats.hits += 1;
+            *block
+        } else {
+            self.stats.misses += 1;
+            let ops = scan(bus, address, thumb);
+            let block = (ops.len() >= MIN_BLOCK_LENGTH as usize).then(|| self.compile(&ops));
+            if let Some(block) = block {
+                self.stats.compiled_blocks += 1;
+                self.stats.compiled_instructions += u64::from(block.length);
+            } else {
+                self.stats.uncompilable += 1;
+            }
+            self.blocks.insert((address, thumb), block);
+            block
+        };
+        if let Some(block) = block {
+            self.stats.executed_instructions += u64::from(block.length);
+        }
+
+        block
+    }
+
+    fn compile(&mut self, ops: &[RegisterOp]) -> Block {
+        let module = self.module.get_or_insert_with(new_module);
+
+        let mut context = module.make_context();
+        let pointer = module.target_config().pointer_type();
+        context.func.signature.params.push(AbiParam::new(pointer));
+
+        let mut builder = FunctionBuilder::new(&mut context.func, &mut self.context);
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+
+        let mut registers = RegisterFile {
+            base: builder.block_params(entry)[0],
+            values: [None; 16],
+            written: [false; 16],
+        };
+        for op in ops {
+            translate(&mut builder, &mut registers, *op);
+        }
+        registers.store_written(&mut builder);
+        builder.ins().return_(&[]);
+        builder.finalize();
+
+        let id = module
+            .declare_anonymous_function(&context.func.signature)
+            .unwrap();
+        module.define_function(id, &mut context).unwrap();
+        module.clear_context(&mut context);
+        module.finalize_definitions().unwrap();
+
+        // SAFETY: the function was declared with the signature of `BlockFn`.
+        let function =
+            unsafe { std::mem::transmute::<*const u8, BlockFn>(module.get_finalized_function(id)) };
+
+        Block {
+            function,
+            length: ops.len() as u32,
+        }
+    }
+}
+
+fn new_module() -> JITModule {
+    let mut flags = settings::builder();
+    flags.set("opt_level", "speed").unwrap();
+    let isa = cranelift_native::builder()
+        .expect("host not supported by Cranelift")
+        .finish(settings::Flags::new(flags))
+        .unwrap();
+
+    JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::cpu::arm7tdmi::Arm7tdmi;
+    use crate::cpu::asm::{ArmAsm, ThumbAsm};
+    use crate::cpu::hardware::internal_memory::InternalMemory;
+    use crate::cpu::psr::CpuState;
+    use crate::cpu::thumb::alu_instructions::ThumbModeAluInstruction;
+
+    #[test]
+    fn arm_translation() {
+        assert_eq!(
+            arm_op(ArmAsm::add(1, 2).reg(3).shift(ShiftKind::Lsr, 4).encode()),
+            Some(RegisterOp {
+                alu: ArmModeAluInstr::Add,
+                rd: 1,
+                rn: 2,
+                operand: Operand::Register {
+                    rm: 3,
+                    kind: ShiftKind::Lsr,
+                    amount: 4
+                },
+            })
+        );
+        assert_eq!(
+            arm_op(ArmAsm::mov(0).imm(0xFF00).encode()).map(|op| op.operand),
+            Some(Operand::Immediate(0xFF00))
+        );
+
+        for op_code in [
+            ArmAsm::add(1, 2).imm(1).set_flags(),
+            ArmAsm::add(1, 2).imm(1).cond(Condition::EQ),
+            ArmAsm::alu(ArmModeAluInstr::Adc, 1, 2).imm(1),
+            ArmAsm::cmp(1).imm(1),
+            ArmAsm::mrs(1),
+            ArmAsm::mov(15).reg(14),
+            ArmAsm::add(1, 15).imm(4),
+            ArmAsm::mov(1).reg(15),
+            ArmAsm::mov(1).reg(2).shift(ShiftKind::Ror, 0),
+            ArmAsm::mov(1).reg(2).shift_reg(ShiftKind::Lsl, 3),
+            ArmAsm::ldr(1).base(2),
+            ArmAsm::b(8),
+        ] {
+            assert_eq!(arm_op(op_code.encode()), None, "{:08X}", op_code.encode());
+        }
+    }
+
+    #[test]
+    fn thumb_translation() {
+        assert_eq!(
+            thumb_op(ThumbAsm::add_sp(-16).encode()),
+            Some(RegisterOp {
+                alu: ArmModeAluInstr::Sub,
+                rd: 13,
+                rn: 13,
+                operand: Operand::Immediate(16),
+            })
+        );
+        assert_eq!(
+            thumb_op(ThumbAsm::high(ThumbHighRegisterOperation::Mov, 8, 1).encode())
+                .map(|op| (op.alu, op.rd)),
+            Some((ArmModeAluInstr::Mov, 8))
+        );
+
+        for op_code in [
+            ThumbAsm::high(ThumbHighRegisterOperation::Cmp, 8, 1),
+            ThumbAsm::high(ThumbHighRegisterOperation::Mov, 15, 1),
+            ThumbAsm::high(ThumbHighRegisterOperation::Add, 1, 15),
+            ThumbAsm::adr(1, 8),
+            ThumbAsm::add_imm(1, 1),
+            ThumbAsm::push(1),
+        ] {
+            assert_eq!(thumb_op(op_code.encode()), None, "{:04X}", op_code.encode());
+        }
+    }
+
+    /// Runs the code with the interpreter only and with compiled blocks, until the last
+    /// instruction is reached. Registers and cycles must match.
+    fn compare_with_interpreter(code: &[u8], thumb: bool) {
+        let size = if thumb { 2 } else { 4 };
+        let end = ROM_START + code.len() as u32 - size;
+
+        let run = |jit: bool| {
+            let mut cpu = Arm7tdmi::new(Bus::with_memory(InternalMemory::new(
+                [0; BIOS_SIZE],
+                code.to_vec(),
+            )));
+            cpu.set_jit_enabled(jit);
+            if thumb {
+                cpu.cpsr.set_cpu_state(CpuState::Thumb);
+            }
+            cpu.registers.set_program_counter(ROM_START);
+
+            while cpu.registers.program_counter() as u32 != end + 2 * size {
+                cpu.step();
+            }
+
+            (
+                cpu.registers.to_vec(),
+                u32::from(cpu.cpsr),
+                cpu.current_cycle,
+            )
+        };
+
+        assert_eq!(run(true), run(false));
+    }
+
+    /// Eight `ADD R1, R1, #1` branching back to the first, compiled as a single block.
+    fn looping_block() -> Arm7tdmi {
+        let mut code = Vec::new();
+        for _ in 0..8 {
+            code.extend(ArmAsm::add(1, 1).imm(1).encode().to_le_bytes());
+        }
+        code.extend(ArmAsm::b(-40).encode().to_le_bytes());
+
+        let mut cpu = Arm7tdmi::new(Bus::with_memory(InternalMemory::new([0; BIOS_SIZE], code)));
+        cpu.registers.set_program_counter(ROM_START);
+
+        cpu
+    }
+
+    /// Instructions executed by each of `steps` steps.
+    fn instructions_per_step(cpu: &mut Arm7tdmi, steps: usize) -> Vec<u64> {
+        (0..steps)
+            .map(|_| {
+                let start = cpu.instructions();
+                cpu.step();
+                cpu.instructions() - start
+            })
+            .collect()
+    }
+
+    #[test]
+    fn blocks_run_in_a_single_step() {
+        let mut cpu = looping_block();
+        assert!(instructions_per_step(&mut cpu, 4).contains(&8));
+    }
+
+    #[test]
+    fn stats_count_the_lookups_and_the_blocks() {
+        let mut cpu = looping_block();
+        let laps = 10;
+        for _ in 0..laps * 4 {
+            cpu.step();
+        }
+
+        let stats = cpu.jit_stats();
+        assert_eq!(stats.compiled_blocks, 1);
+        assert_eq!(stats.compiled_instructions, 8);
+        assert!(stats.hits > stats.misses);
+        assert!(stats.executed_instructions >= 8 * (laps - 1));
+        assert_eq!(stats.invalidations, 0);
+
+        cpu.set_jit_enabled(false);
+        cpu.set_jit_enabled(true);
+        assert_eq!(cpu.jit_stats().invalidations, 1);
+    }
+
+    #[test]
+    fn abort_on_invalid_access_interprets_every_instruction() {
+        let mut cpu = looping_block();
+        cpu.set_abort_on_invalid_access(true);
+        assert!(instructions_per_step(&mut cpu, 32)
+            .iter()
+            .all(|&count| count <= 1));
+        assert_eq!(cpu.registers.register_at(1), 24);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-hooks")]
+    fn breakpoints_inside_a_block_are_hit() {
+        use crate::cpu::breakpoints::{Breakpoint, StateMask, StepResult};
+
+        let mut cpu = looping_block();
+        cpu.add_breakpoint(Breakpoint::new(ROM_START + 12, StateMask::ARM));
+
+        let hit = (0..32)
+            .map(|_| cpu.step())
+            .find(|&result| result != StepResult::Normal);
+        assert_eq!(hit, Some(StepResult::BreakpointHit(ROM_START + 12)));
+        assert_eq!(cpu.registers.register_at(1), 3);
+
+        // Without breakpoints, the blocks run again.
+        cpu.clear_breakpoints();
+        assert!(instructions_per_step(&mut cpu, 16).contains(&8));
+    }
+
+    #[test]
+    #[cfg(feature = "debug-hooks")]
+    fn observers_see_every_instruction_of_a_block() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::cpu::instrumentation::{CpuObserver, ExecutedInstruction};
+
+        struct Recorder(Arc<Mutex<Vec<u32>>>);
+
+        impl CpuObserver for Recorder {
+            fn on_instruction_executed(&mut self, instruction: &ExecutedInstruction) {
+                self.0.lock().unwrap().push(instruction.address);
+            }
+        }
+
+        let mut cpu = looping_block();
+        let addresses = Arc::new(Mutex::new(Vec::new()));
+        let id = cpu.add_observer(Box::new(Recorder(Arc::clone(&addresses))));
+        for _ in 0..10 {
+            cpu.step();
+        }
+        assert!(cpu.remove_observer(id).is_some());
+
+        let expected = (0..8)
+            .map(|index| ROM_START + 4 * index)
+            .collect::<Vec<_>>();
+        assert_eq!(*addresses.lock().unwrap(), expected);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-hooks")]
+    fn cpu_trace_records_every_instruction_of_a_block() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::cpu_trace::{CpuTraceEntry, CpuTraceWriter};
+
+        struct Recorder(Arc<Mutex<Vec<u32>>>);
+
+        impl CpuTraceWriter for Recorder {
+            fn record(&mut self, entry: &CpuTraceEntry) -> std::io::Result<()> {
+                self.0.lock().unwrap().push(entry.address);
+                Ok(())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut cpu = looping_block();
+        let addresses = Arc::new(Mutex::new(Vec::new()));
+        cpu.set_cpu_trace(Some(Box::new(Recorder(Arc::clone(&addresses)))));
+        for _ in 0..10 {
+            cpu.step();
+        }
+        assert!(cpu.set_cpu_trace(None).is_some());
+
+        let expected = (0..8)
+            .map(|index| ROM_START + 4 * index)
+            .collect::<Vec<_>>();
+        assert_eq!(*addresses.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn arm_blocks_match_the_interpreter() {
+        let mut rng = StdRng::seed_from_u64(0x0A12_B10C);
+        let alus = [
+            ArmModeAluInstr::And,
+            ArmModeAluInstr::Eor,
+            ArmModeAluInstr::Sub,
+            ArmModeAluInstr::Rsb,
+            ArmModeAluInstr::Add,
+            ArmModeAluInstr::Adc,
+            ArmModeAluInstr::Orr,
+            ArmModeAluInstr::Mov,
+            ArmModeAluInstr::Bic,
+            ArmModeAluInstr::Mvn,
+        ];
+        let shifts = [
+            ShiftKind::Lsl,
+            ShiftKind::Lsr,
+            ShiftKind::Asr,
+            ShiftKind::Ror,
+        ];
+
+        let mut code = Vec::new();
+        for _ in 0..2000 {
+            let alu = alus[rng.gen_range(0..alus.len())];
+            let instruction = ArmAsm::alu(alu, rng.gen_range(0..15), rng.gen_range(0..15));
+            let instruction = match rng.gen_range(0..4) {
+                0 => instruction.imm(rng.gen::<u8>().into()),
+                1 => instruction
+                    .reg(rng.gen_range(0..15))
+                    .shift_reg(shifts[rng.gen_range(0..4)], rng.gen_range(0..15)),
+                _ => instruction
+                    .reg(rng.gen_range(0..15))
+                    .shift(shifts[rng.gen_range(0..4)], rng.gen_range(0..32)),
+            };
+            // Some flags for the interpreted instructions to read.
+            let instruction = match rng.gen_range(0..8) {
+                0 => instruction.set_flags(),
+                1 => instruction.cond(Condition::CS),
+                _ => instruction,
+            };
+
+            code.extend(instruction.encode().to_le_bytes());
+        }
+        code.extend(ArmAsm::b(-8).encode().to_le_bytes());
+
+        compare_with_interpreter(&code, false);
+    }
+
+    #[test]
+    fn thumb_blocks_match_the_interpreter() {
+        let mut rng = StdRng::seed_from_u64(0x07B0_B10C);
+
+        let mut code = Vec::new();
+        for _ in 0..2000 {
+            let instruction = match rng.gen_range(0..6) {
+                0 => ThumbAsm::high(
+                    ThumbHighRegisterOperation::Add,
+                    rng.gen_range(0..15),
+                    rng.gen_range(0..15),
+                ),
+                1 => ThumbAsm::high(
+                    ThumbHighRegisterOperation::Mov,
+                    rng.gen_range(0..15),
+                    rng.gen_range(0..15),
+                ),
+                2 => ThumbAsm::add_sp(rng.gen_range(-127..=127) * 4),
+                3 => ThumbAsm::adr(rng.gen_range(0..8), rng.gen_range(0..=255) * 4).sp(),
+                4 => ThumbAsm::add_imm(rng.gen_range(0..8), rng.gen::<u8>().into()),
+                _ => ThumbAsm::alu(
+                    ThumbModeAluInstruction::Eor,
+                    rng.gen_range(0..8),
+                    rng.gen_range(0..8),
+                ),
+            };
+
+            code.extend(instruction.encode().to_le_bytes());
+        }
+        code.extend(ThumbAsm::b(-4).encode().to_le_bytes());
+
+        compare_with_interpreter(&code, true);
+    }
+}
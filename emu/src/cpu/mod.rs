@@ -1,18 +1,38 @@
 mod arm;
 
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[allow(clippy::missing_panics_doc)]
+pub mod asm;
+
 #[allow(clippy::cast_lossless)]
 #[allow(clippy::cast_possible_truncation)]
 #[allow(clippy::large_stack_frames)]
 #[allow(clippy::module_name_repetitions)]
 pub mod arm7tdmi;
+
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_possible_wrap)]
+#[allow(clippy::cast_sign_loss)]
+pub mod bios_hle;
+pub mod breakpoint_condition;
+pub mod breakpoints;
 mod condition;
-mod cpu_modes;
+pub mod cpu_modes;
+pub mod execution_trap;
 
 #[allow(clippy::cast_possible_truncation)]
 mod flags;
 
 #[allow(clippy::cast_possible_truncation)]
 pub mod hardware;
+#[cfg(feature = "debug-hooks")]
+pub mod instrumentation;
+
+#[cfg(feature = "jit")]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_possible_wrap)]
+pub mod jit;
 mod psr;
 mod register_bank;
 mod registers;
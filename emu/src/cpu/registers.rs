@@ -1,3 +1,4 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 pub const REG_SP: usize = 0xD;
@@ -6,7 +7,8 @@ pub const REG_PROGRAM_COUNTER: u32 = 0xF;
 
 /// Contains the 16 registers for the CPU, latest (R15) is special because
 /// is the program counter.
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Registers([u32; 16]);
 
 impl Registers {
@@ -30,6 +32,12 @@ impl Registers {
         self.0[reg]
     }
 
+    /// For the compiled blocks, which read and write the registers directly.
+    #[cfg(feature = "jit")]
+    pub const fn as_mut_array(&mut self) -> &mut [u32; 16] {
+        &mut self.0
+    }
+
     pub fn to_vec(&self) -> Vec<u32> {
         self.0.as_slice().to_vec()
     }
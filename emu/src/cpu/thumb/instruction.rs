@@ -5,9 +5,11 @@ use crate::cpu::flags::{LoadStoreKind, OperandKind, Operation, ReadWriteKind, Sh
 use crate::cpu::registers::REG_PROGRAM_COUNTER;
 use crate::cpu::thumb::alu_instructions::{ThumbHighRegisterOperation, ThumbModeAluInstruction};
 use logger::log;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Instruction {
     MoveShiftedRegister {
         shift_operation: ShiftKind,
@@ -90,7 +92,9 @@ pub enum Instruction {
         condition: Condition,
         immediate_offset: i32,
     },
-    Swi,
+    Swi {
+        comment: u8,
+    },
     UncondBranch {
         offset: u32,
     },
@@ -101,140 +105,47 @@ pub enum Instruction {
 }
 
 impl From<u16> for Instruction {
-    #[allow(clippy::too_many_lines)]
     fn from(op_code: u16) -> Self {
-        use Instruction::{
-            AddOffsetSP, AddSubtract, AluOp, CondBranch, HiRegisterOpBX, LoadAddress,
-            LoadStoreHalfword, LoadStoreImmOffset, LoadStoreRegisterOffset,
-            LoadStoreSignExtByteHalfword, LongBranchLink, MoveCompareAddSubtractImm,
-            MoveShiftedRegister, MultipleLoadStore, PCRelativeLoad, PushPopReg,
-            SPRelativeLoadStore, Swi, UncondBranch,
-        };
-
         if op_code.get_bits(8..=15) == 0b1101_1111 {
-            Swi
-        } else if op_code.get_bits(8..=15) == 0b1011_0000 {
-            AddOffsetSP {
-                // 0 - positive, 1 - negative TODO
-                s: op_code.get_bit(7),
-                // The offset supplied in #Imm is a full 10-bit address,
-                // but must always be word-aligned (ie bits 1:0 set to 0),
-                // since the assembler places #Imm >> 2 in the Word8 field.
-                word7: op_code.get_bits(0..=6) << 2,
+            Self::Swi {
+                comment: op_code.get_bits(0..=7) as u8,
             }
+        } else if op_code.get_bits(8..=15) == 0b1011_0000 {
+            Self::add_offset_sp(op_code)
         } else if op_code.get_bits(10..=15) == 0b01_0000 {
-            AluOp {
-                alu_operation: op_code.get_bits(6..=9).into(),
-                source_register: op_code.get_bits(3..=5),
-                destination_register: op_code.get_bits(0..=2),
-            }
+            Self::alu_op(op_code)
         } else if op_code.get_bits(10..=15) == 0b01_0001 {
-            let h1 = op_code.get_bit(7);
-            let rd_hd = op_code.get_bits(0..=2);
-            let destination_register = if h1 { rd_hd | (1 << 3) } else { rd_hd };
-
-            HiRegisterOpBX {
-                register_operation: op_code.get_bits(8..=9).into(),
-                source_register: op_code.get_bits(3..=6),
-                destination_register,
-            }
+            Self::hi_register_op_bx(op_code)
         } else if op_code.get_bits(12..=15) == 0b1011 && op_code.get_bits(9..=10) == 0b10 {
-            PushPopReg {
-                load_store: op_code.get_bit(11).into(),
-                pc_lr: op_code.get_bit(8),
-                register_list: op_code.get_bits(0..=7),
-            }
+            Self::push_pop_reg(op_code)
         } else if op_code.get_bits(11..=15) == 0b00011 {
-            AddSubtract {
-                operation_kind: op_code.get_bit(10).into(),
-                // 0 - Add, 1 - Sub TODO
-                op: op_code.get_bit(9),
-                rn_offset3: op_code.get_bits(6..=8),
-                source_register: op_code.get_bits(3..=5),
-                destination_register: op_code.get_bits(0..=2),
-            }
+            Self::add_subtract(op_code)
         } else if op_code.get_bits(11..=15) == 0b01001 {
-            PCRelativeLoad {
-                destination_register: op_code.get_bits(8..=10),
-                immediate_value: op_code.get_bits(0..=7) << 2,
-            }
+            Self::pc_relative_load(op_code)
         } else if op_code.get_bits(12..=15) == 0b0101 && !op_code.get_bit(9) {
-            LoadStoreRegisterOffset {
-                load_store: op_code.get_bit(11).into(),
-                byte_word: op_code.get_bit(10).into(),
-                ro: op_code.get_bits(6..=8),
-                base_register: op_code.get_bits(3..=5),
-                destination_register: op_code.get_bits(0..=2),
-            }
+            Self::load_store_register_offset(op_code)
         } else if op_code.get_bits(12..=15) == 0b0101 && op_code.get_bit(9) {
-            LoadStoreSignExtByteHalfword {
-                h: op_code.get_bit(11),
-                sign_extend_flag: op_code.get_bit(10),
-                offset_register: op_code.get_bits(6..=8) as u32,
-                base_register: op_code.get_bits(3..=5) as u32,
-                destination_register: op_code.get_bits(0..=2) as u32,
-            }
+            Self::load_store_sign_ext_byte_halfword(op_code)
         } else if op_code.get_bits(11..=15) == 0b11100 {
-            UncondBranch {
-                offset: (op_code.get_bits(0..=10) << 1) as u32,
-            }
+            Self::uncond_branch(op_code)
         } else if op_code.get_bits(12..=15) == 0b1000 {
-            LoadStoreHalfword {
-                load_store: op_code.get_bit(11).into(),
-                offset: op_code.get_bits(6..=10) << 1,
-                base_register: op_code.get_bits(3..=5),
-                source_destination_register: op_code.get_bits(0..=2),
-            }
+            Self::load_store_halfword(op_code)
         } else if op_code.get_bits(12..=15) == 0b1001 {
-            SPRelativeLoadStore {
-                load_store: op_code.get_bit(11).into(),
-                destination_register: op_code.get_bits(8..=10),
-                // The offset supplied in #Imm is a full 10-bit address,
-                // but must always be word-aligned (ie bits 1:0 set to 0),
-                // since the assembler places #Imm >> 2 in the Word8 field.
-                word8: op_code.get_bits(0..=7) << 2,
-            }
+            Self::sp_relative_load_store(op_code)
         } else if op_code.get_bits(12..=15) == 0b1010 {
-            LoadAddress {
-                sp: op_code.get_bit(11),
-                destination_register: op_code.get_bits(8..=10) as u32,
-                offset: (op_code.get_bits(0..=7) as u32) << 2,
-            }
+            Self::load_address(op_code)
         } else if op_code.get_bits(12..=15) == 0b1100 {
-            MultipleLoadStore {
-                load_store: op_code.get_bit(11).into(),
-                base_register: op_code.get_bits(8..=10),
-                register_list: op_code.get_bits(0..=7),
-            }
+            Self::multiple_load_store(op_code)
         } else if op_code.get_bits(12..=15) == 0b1101 {
-            // 9 bits signed offset (assembler puts `label` >> 1 in this field so we should <<1)
-            let offset = (op_code.get_bits(0..=7) << 1) as u32;
-            let immediate_offset = offset.sign_extended(9) as i32;
-
-            CondBranch {
-                condition: Condition::from(op_code.get_bits(8..=11) as u8),
-                immediate_offset,
-            }
+            Self::cond_branch(op_code)
         } else if op_code.get_bits(12..=15) == 0b1111 {
-            LongBranchLink {
-                h: op_code.get_bit(11),
-                offset: op_code.get_bits(0..=10) as u32,
-            }
+            Self::long_branch_link(op_code)
         } else if op_code.get_bits(13..=15) == 0b000 {
-            MoveShiftedRegister {
-                shift_operation: op_code.get_bits(11..=12).into(),
-                offset5: op_code.get_bits(6..=10),
-                source_register: op_code.get_bits(3..=5),
-                destination_register: op_code.get_bits(0..=2),
-            }
+            Self::move_shifted_register(op_code)
         } else if op_code.get_bits(13..=15) == 0b001 {
-            MoveCompareAddSubtractImm {
-                operation: op_code.get_bits(11..=12).into(),
-                destination_register: op_code.get_bits(8..=10),
-                offset: op_code.get_bits(0..=7).into(),
-            }
+            Self::move_compare_add_subtract_imm(op_code)
         } else if op_code.get_bits(13..=15) == 0b011 {
-            LoadStoreImmOffset
+            Self::LoadStoreImmOffset
         } else {
             log(format!("not identified instruction {op_code} "));
             unimplemented!()
@@ -242,6 +153,181 @@ impl From<u16> for Instruction {
     }
 }
 
+/// Decoding of each kind of instruction, once the kind is known. They are shared by
+/// [`Instruction::from`] and the lookup table of the CPU.
+impl Instruction {
+    #[must_use]
+    pub fn add_offset_sp(op_code: u16) -> Self {
+        Self::AddOffsetSP {
+            // 0 - positive, 1 - negative TODO
+            s: op_code.get_bit(7),
+            // The offset supplied in #Imm is a full 10-bit address,
+            // but must always be word-aligned (ie bits 1:0 set to 0),
+            // since the assembler places #Imm >> 2 in the Word8 field.
+            word7: op_code.get_bits(0..=6) << 2,
+        }
+    }
+
+    #[must_use]
+    pub fn alu_op(op_code: u16) -> Self {
+        Self::AluOp {
+            alu_operation: op_code.get_bits(6..=9).into(),
+            source_register: op_code.get_bits(3..=5),
+            destination_register: op_code.get_bits(0..=2),
+        }
+    }
+
+    #[must_use]
+    pub fn hi_register_op_bx(op_code: u16) -> Self {
+        let h1 = op_code.get_bit(7);
+        let rd_hd = op_code.get_bits(0..=2);
+        let destination_register = if h1 { rd_hd | (1 << 3) } else { rd_hd };
+
+        Self::HiRegisterOpBX {
+            register_operation: op_code.get_bits(8..=9).into(),
+            source_register: op_code.get_bits(3..=6),
+            destination_register,
+        }
+    }
+
+    #[must_use]
+    pub fn push_pop_reg(op_code: u16) -> Self {
+        Self::PushPopReg {
+            load_store: op_code.get_bit(11).into(),
+            pc_lr: op_code.get_bit(8),
+            register_list: op_code.get_bits(0..=7),
+        }
+    }
+
+    #[must_use]
+    pub fn add_subtract(op_code: u16) -> Self {
+        Self::AddSubtract {
+            operation_kind: op_code.get_bit(10).into(),
+            // 0 - Add, 1 - Sub TODO
+            op: op_code.get_bit(9),
+            rn_offset3: op_code.get_bits(6..=8),
+            source_register: op_code.get_bits(3..=5),
+            destination_register: op_code.get_bits(0..=2),
+        }
+    }
+
+    #[must_use]
+    pub fn pc_relative_load(op_code: u16) -> Self {
+        Self::PCRelativeLoad {
+            destination_register: op_code.get_bits(8..=10),
+            immediate_value: op_code.get_bits(0..=7) << 2,
+        }
+    }
+
+    #[must_use]
+    pub fn load_store_register_offset(op_code: u16) -> Self {
+        Self::LoadStoreRegisterOffset {
+            load_store: op_code.get_bit(11).into(),
+            byte_word: op_code.get_bit(10).into(),
+            ro: op_code.get_bits(6..=8),
+            base_register: op_code.get_bits(3..=5),
+            destination_register: op_code.get_bits(0..=2),
+        }
+    }
+
+    #[must_use]
+    pub fn load_store_sign_ext_byte_halfword(op_code: u16) -> Self {
+        Self::LoadStoreSignExtByteHalfword {
+            h: op_code.get_bit(11),
+            sign_extend_flag: op_code.get_bit(10),
+            offset_register: op_code.get_bits(6..=8) as u32,
+            base_register: op_code.get_bits(3..=5) as u32,
+            destination_register: op_code.get_bits(0..=2) as u32,
+        }
+    }
+
+    #[must_use]
+    pub fn uncond_branch(op_code: u16) -> Self {
+        Self::UncondBranch {
+            offset: (op_code.get_bits(0..=10) << 1) as u32,
+        }
+    }
+
+    #[must_use]
+    pub fn load_store_halfword(op_code: u16) -> Self {
+        Self::LoadStoreHalfword {
+            load_store: op_code.get_bit(11).into(),
+            offset: op_code.get_bits(6..=10) << 1,
+            base_register: op_code.get_bits(3..=5),
+            source_destination_register: op_code.get_bits(0..=2),
+        }
+    }
+
+    #[must_use]
+    pub fn sp_relative_load_store(op_code: u16) -> Self {
+        Self::SPRelativeLoadStore {
+            load_store: op_code.get_bit(11).into(),
+            destination_register: op_code.get_bits(8..=10),
+            // The offset supplied in #Imm is a full 10-bit address,
+            // but must always be word-aligned (ie bits 1:0 set to 0),
+            // since the assembler places #Imm >> 2 in the Word8 field.
+            word8: op_code.get_bits(0..=7) << 2,
+        }
+    }
+
+    #[must_use]
+    pub fn load_address(op_code: u16) -> Self {
+        Self::LoadAddress {
+            sp: op_code.get_bit(11),
+            destination_register: op_code.get_bits(8..=10) as u32,
+            offset: (op_code.get_bits(0..=7) as u32) << 2,
+        }
+    }
+
+    #[must_use]
+    pub fn multiple_load_store(op_code: u16) -> Self {
+        Self::MultipleLoadStore {
+            load_store: op_code.get_bit(11).into(),
+            base_register: op_code.get_bits(8..=10),
+            register_list: op_code.get_bits(0..=7),
+        }
+    }
+
+    #[must_use]
+    pub fn cond_branch(op_code: u16) -> Self {
+        // 9 bits signed offset (assembler puts `label` >> 1 in this field so we should <<1)
+        let offset = (op_code.get_bits(0..=7) << 1) as u32;
+        let immediate_offset = offset.sign_extended(9) as i32;
+
+        Self::CondBranch {
+            condition: Condition::from(op_code.get_bits(8..=11) as u8),
+            immediate_offset,
+        }
+    }
+
+    #[must_use]
+    pub fn long_branch_link(op_code: u16) -> Self {
+        Self::LongBranchLink {
+            h: op_code.get_bit(11),
+            offset: op_code.get_bits(0..=10) as u32,
+        }
+    }
+
+    #[must_use]
+    pub fn move_shifted_register(op_code: u16) -> Self {
+        Self::MoveShiftedRegister {
+            shift_operation: op_code.get_bits(11..=12).into(),
+            offset5: op_code.get_bits(6..=10),
+            source_register: op_code.get_bits(3..=5),
+            destination_register: op_code.get_bits(0..=2),
+        }
+    }
+
+    #[must_use]
+    pub fn move_compare_add_subtract_imm(op_code: u16) -> Self {
+        Self::MoveCompareAddSubtractImm {
+            operation: op_code.get_bits(11..=12).into(),
+            destination_register: op_code.get_bits(8..=10),
+            offset: op_code.get_bits(0..=7).into(),
+        }
+    }
+}
+
 impl std::fmt::Display for Instruction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{self:?}")
@@ -457,7 +543,7 @@ impl Instruction {
             } => {
                 format!("B{condition} #{immediate_offset}")
             }
-            Self::Swi => panic!("not implemented"),
+            Self::Swi { comment } => format!("SWI #0x{comment:X}"),
             Self::UncondBranch { offset } => {
                 format!("B #{offset}")
             }
@@ -526,6 +612,13 @@ mod test {
         assert_eq!("B #606", output.disassembler()); // FIXME: Should this be decimal or hex?
     }
 
+    #[test]
+    fn decode_swi() {
+        let output = Instruction::from(0b1101_1111_0000_0101);
+        assert_eq!(Instruction::Swi { comment: 5 }, output);
+        assert_eq!("SWI #0x5", output.disassembler());
+    }
+
     #[test]
     fn decode_hi_reg_operation() {
         let output = Instruction::from(0b0100_0111_0111_0000);
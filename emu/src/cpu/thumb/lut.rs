@@ -0,0 +1,343 @@
+//! Execution by lookup table. Bits 15-6 of an instruction are enough to tell its kind,
+//! so the 1024 combinations are resolved at compile time to the handler of that kind,
+//! which reads its fields and runs it, instead of testing every kind in turn as
+//! [`Instruction::from`] does and matching on the result.
+//!
+//! The enum is only built for the disassembler, the logger and the save-states, with a
+//! table of the decoders of each kind.
+
+use crate::bitwise::Bits;
+use crate::cpu::arm7tdmi::Arm7tdmi;
+use crate::cpu::condition::Condition;
+use crate::cpu::thumb::instruction::Instruction;
+use crate::cpu::thumb::mode::ThumbModeOpcode;
+
+type Handler = fn(&mut Arm7tdmi, u16);
+type Decoder = fn(u16) -> Instruction;
+
+static HANDLERS: [Handler; 1024] = handlers();
+static DECODERS: [Decoder; 1024] = decoders();
+
+const fn bits(value: u16, low: u16, high: u16) -> u16 {
+    (value >> low) & ((1 << (high - low + 1)) - 1)
+}
+
+/// What bits 15-6 tell of an instruction.
+#[derive(Clone, Copy)]
+enum Kind {
+    /// Kinds without fields, and the encodings that aren't instructions.
+    Other,
+    AddOffsetSp,
+    AluOp,
+    HiRegisterOpBx,
+    PushPopReg,
+    AddSubtract,
+    PcRelativeLoad,
+    LoadStoreRegisterOffset,
+    LoadStoreSignExtByteHalfword,
+    UncondBranch,
+    LoadStoreHalfword,
+    SpRelativeLoadStore,
+    LoadAddress,
+    MultipleLoadStore,
+    CondBranch,
+    LongBranchLink,
+    MoveShiftedRegister,
+    MoveCompareAddSubtractImm,
+}
+
+impl Kind {
+    /// Same order as [`Instruction::from`].
+    const fn of(index: usize) -> Self {
+        let op_code = (index as u16) << 6;
+
+        if bits(op_code, 8, 15) == 0b1101_1111 {
+            Self::Other
+        } else if bits(op_code, 8, 15) == 0b1011_0000 {
+            Self::AddOffsetSp
+        } else if bits(op_code, 10, 15) == 0b01_0000 {
+            Self::AluOp
+        } else if bits(op_code, 10, 15) == 0b01_0001 {
+            Self::HiRegisterOpBx
+        } else if bits(op_code, 12, 15) == 0b1011 && bits(op_code, 9, 10) == 0b10 {
+            Self::PushPopReg
+        } else if bits(op_code, 11, 15) == 0b00011 {
+            Self::AddSubtract
+        } else if bits(op_code, 11, 15) == 0b01001 {
+            Self::PcRelativeLoad
+        } else if bits(op_code, 12, 15) == 0b0101 && bits(op_code, 9, 9) == 0 {
+            Self::LoadStoreRegisterOffset
+        } else if bits(op_code, 12, 15) == 0b0101 {
+            Self::LoadStoreSignExtByteHalfword
+        } else if bits(op_code, 11, 15) == 0b11100 {
+            Self::UncondBranch
+        } else {
+            match bits(op_code, 12, 15) {
+                0b1000 => Self::LoadStoreHalfword,
+                0b1001 => Self::SpRelativeLoadStore,
+                0b1010 => Self::LoadAddress,
+                0b1100 => Self::MultipleLoadStore,
+                0b1101 => Self::CondBranch,
+                0b1111 => Self::LongBranchLink,
+                0b0000 | 0b0001 => Self::MoveShiftedRegister,
+                0b0010 | 0b0011 => Self::MoveCompareAddSubtractImm,
+                _ => Self::Other,
+            }
+        }
+    }
+
+    const fn handler(self) -> Handler {
+        match self {
+            Self::Other => execute_fully,
+            Self::AddOffsetSp => add_offset_sp,
+            Self::AluOp => alu_op,
+            Self::HiRegisterOpBx => hi_register_op_bx,
+            Self::PushPopReg => push_pop_reg,
+            Self::AddSubtract => add_subtract,
+            Self::PcRelativeLoad => pc_relative_load,
+            Self::LoadStoreRegisterOffset => load_store_register_offset,
+            Self::LoadStoreSignExtByteHalfword => load_store_sign_ext_byte_halfword,
+            Self::UncondBranch => uncond_branch,
+            Self::LoadStoreHalfword => load_store_halfword,
+            Self::SpRelativeLoadStore => sp_relative_load_store,
+            Self::LoadAddress => load_address,
+            Self::MultipleLoadStore => multiple_load_store,
+            Self::CondBranch => cond_branch,
+            Self::LongBranchLink => long_branch_link,
+            Self::MoveShiftedRegister => move_shifted_register,
+            Self::MoveCompareAddSubtractImm => move_compare_add_subtract_imm,
+        }
+    }
+
+    const fn decoder(self) -> Decoder {
+        match self {
+            Self::Other => decode_fully,
+            Self::AddOffsetSp => Instruction::add_offset_sp,
+            Self::AluOp => Instruction::alu_op,
+            Self::HiRegisterOpBx => Instruction::hi_register_op_bx,
+            Self::PushPopReg => Instruction::push_pop_reg,
+            Self::AddSubtract => Instruction::add_subtract,
+            Self::PcRelativeLoad => Instruction::pc_relative_load,
+            Self::LoadStoreRegisterOffset => Instruction::load_store_register_offset,
+            Self::LoadStoreSignExtByteHalfword => Instruction::load_store_sign_ext_byte_halfword,
+            Self::UncondBranch => Instruction::uncond_branch,
+            Self::LoadStoreHalfword => Instruction::load_store_halfword,
+            Self::SpRelativeLoadStore => Instruction::sp_relative_load_store,
+            Self::LoadAddress => Instruction::load_address,
+            Self::MultipleLoadStore => Instruction::multiple_load_store,
+            Self::CondBranch => Instruction::cond_branch,
+            Self::LongBranchLink => Instruction::long_branch_link,
+            Self::MoveShiftedRegister => Instruction::move_shifted_register,
+            Self::MoveCompareAddSubtractImm => Instruction::move_compare_add_subtract_imm,
+        }
+    }
+}
+
+const fn handlers() -> [Handler; 1024] {
+    let mut table: [Handler; 1024] = [execute_fully; 1024];
+
+    let mut index = 0;
+    while index < table.len() {
+        table[index] = Kind::of(index).handler();
+        index += 1;
+    }
+
+    table
+}
+
+const fn decoders() -> [Decoder; 1024] {
+    let mut table: [Decoder; 1024] = [decode_fully; 1024];
+
+    let mut index = 0;
+    while index < table.len() {
+        table[index] = Kind::of(index).decoder();
+        index += 1;
+    }
+
+    table
+}
+
+/// Runs `op_code` with the handler of its kind.
+pub fn execute(cpu: &mut Arm7tdmi, op_code: u16) {
+    HANDLERS[usize::from(op_code >> 6)](cpu, op_code);
+}
+
+/// The instruction for the disassembler, the logger and the save-states.
+#[cfg_attr(
+    not(any(feature = "serde", feature = "disassembler", feature = "logger")),
+    allow(dead_code)
+)]
+pub fn decode(op_code: u16) -> ThumbModeOpcode {
+    ThumbModeOpcode {
+        instruction: DECODERS[usize::from(op_code >> 6)](op_code),
+        raw: op_code,
+    }
+}
+
+fn decode_fully(op_code: u16) -> Instruction {
+    Instruction::from(op_code)
+}
+
+fn execute_fully(cpu: &mut Arm7tdmi, op_code: u16) {
+    match Instruction::from(op_code) {
+        Instruction::Swi { comment } => cpu.software_interrupt(comment),
+        Instruction::LoadStoreImmOffset => cpu.load_store_immediate_offset(op_code),
+        _ => unreachable!("{op_code:04X} has a handler of its own"),
+    }
+}
+
+fn add_offset_sp(cpu: &mut Arm7tdmi, op_code: u16) {
+    // The offset is word-aligned, the assembler places #Imm >> 2 in the field.
+    cpu.add_offset_sp(op_code.get_bit(7), op_code.get_bits(0..=6) << 2);
+}
+
+fn alu_op(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.alu_op(
+        op_code.get_bits(6..=9).into(),
+        op_code.get_bits(3..=5),
+        op_code.get_bits(0..=2),
+    );
+}
+
+fn hi_register_op_bx(cpu: &mut Arm7tdmi, op_code: u16) {
+    let h1 = u16::from(op_code.get_bit(7)) << 3;
+    cpu.hi_reg_operation_branch_ex(
+        op_code.get_bits(8..=9).into(),
+        op_code.get_bits(3..=6),
+        op_code.get_bits(0..=2) | h1,
+    );
+}
+
+fn push_pop_reg(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.push_pop_register(
+        op_code.get_bit(11).into(),
+        op_code.get_bit(8),
+        op_code.get_bits(0..=7),
+    );
+}
+
+fn add_subtract(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.add_subtract(
+        op_code.get_bit(10).into(),
+        op_code.get_bit(9),
+        op_code.get_bits(6..=8),
+        op_code.get_bits(3..=5),
+        op_code.get_bits(0..=2),
+    );
+}
+
+fn pc_relative_load(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.pc_relative_load(op_code.get_bits(8..=10), op_code.get_bits(0..=7) << 2);
+}
+
+fn load_store_register_offset(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.load_store_register_offset(
+        op_code.get_bit(11).into(),
+        op_code.get_bit(10).into(),
+        op_code.get_bits(6..=8),
+        op_code.get_bits(3..=5),
+        op_code.get_bits(0..=2),
+    );
+}
+
+fn load_store_sign_ext_byte_halfword(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.load_store_sign_extend_byte_halfword(
+        op_code.get_bit(11),
+        op_code.get_bit(10),
+        op_code.get_bits(6..=8).into(),
+        op_code.get_bits(3..=5).into(),
+        op_code.get_bits(0..=2).into(),
+    );
+}
+
+fn uncond_branch(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.uncond_branch(u32::from(op_code.get_bits(0..=10)) << 1);
+}
+
+fn load_store_halfword(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.load_store_halfword(
+        op_code.get_bit(11).into(),
+        op_code.get_bits(6..=10) << 1,
+        op_code.get_bits(3..=5),
+        op_code.get_bits(0..=2),
+    );
+}
+
+fn sp_relative_load_store(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.sp_relative_load_store(
+        op_code.get_bit(11).into(),
+        op_code.get_bits(8..=10),
+        op_code.get_bits(0..=7) << 2,
+    );
+}
+
+fn load_address(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.load_address(
+        op_code.get_bit(11),
+        op_code.get_bits(8..=10).into(),
+        u32::from(op_code.get_bits(0..=7)) << 2,
+    );
+}
+
+fn multiple_load_store(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.multiple_load_store(
+        op_code.get_bit(11).into(),
+        op_code.get_bits(8..=10).into(),
+        op_code.get_bits(0..=7),
+    );
+}
+
+#[allow(clippy::cast_possible_wrap)]
+fn cond_branch(cpu: &mut Arm7tdmi, op_code: u16) {
+    // 9 bits signed offset, the assembler puts `label` >> 1 in the field.
+    let offset = u32::from(op_code.get_bits(0..=7) << 1).sign_extended(9) as i32;
+    cpu.cond_branch(Condition::from(op_code.get_bits(8..=11) as u8), offset);
+}
+
+fn long_branch_link(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.long_branch_link(op_code.get_bit(11), op_code.get_bits(0..=10).into());
+}
+
+fn move_shifted_register(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.move_shifted_reg(
+        op_code.get_bits(11..=12).into(),
+        op_code.get_bits(6..=10),
+        op_code.get_bits(3..=5),
+        op_code.get_bits(0..=2),
+    );
+}
+
+fn move_compare_add_subtract_imm(cpu: &mut Arm7tdmi, op_code: u16) {
+    cpu.move_compare_add_sub_imm(
+        op_code.get_bits(11..=12).into(),
+        op_code.get_bits(8..=10),
+        op_code.get_bits(0..=7).into(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn table_matches_full_decoding() {
+        for op_code in 0..=u16::MAX {
+            // Encodings that aren't Thumb instructions on the ARMv4.
+            let undefined = (op_code.get_bits(12..=15) == 0b1011
+                && op_code.get_bits(9..=10) != 0b10
+                && op_code.get_bits(8..=15) != 0b1011_0000)
+                || op_code.get_bits(11..=15) == 0b11101;
+            if undefined {
+                continue;
+            }
+
+            assert_eq!(
+                decode(op_code).instruction,
+                Instruction::from(op_code),
+                "{op_code:04X}"
+            );
+        }
+    }
+}
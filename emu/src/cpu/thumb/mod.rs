@@ -4,6 +4,10 @@ pub mod alu_instructions;
 #[allow(clippy::cast_possible_wrap)]
 #[allow(clippy::cast_lossless)]
 pub mod instruction;
+
+#[allow(clippy::cast_possible_truncation)]
+pub mod lut;
+
 pub mod mode;
 
 #[allow(clippy::cast_possible_truncation)]
@@ -1,8 +1,10 @@
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use crate::cpu::thumb::instruction::Instruction;
 
-#[derive(Copy, Clone, Serialize, Deserialize)]
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ThumbModeOpcode {
     pub instruction: Instruction,
     pub raw: u16,
@@ -56,7 +58,7 @@ impl std::fmt::Display for ThumbModeOpcode {
             Instruction::PushPopReg { .. } => "FMT: |1_0_1_1|L|1_0|R|_____Rlist_____|",
             Instruction::MultipleLoadStore { .. } => "FMT: |1_1_0_0|L|_Rb__|_____Rlist_____|",
             Instruction::CondBranch { .. } => "FMT: |1_1_0_1|_Cond__|_____Offset____|",
-            Instruction::Swi => todo!(),
+            Instruction::Swi { .. } => "FMT: |1_1_0_1_1_1_1_1|_____Value8____|",
             Instruction::UncondBranch { .. } => "FMT: |1_1_1_0_0|________Offset11_____|",
             Instruction::LongBranchLink { .. } => "FMT: |1_1_1_1|H|_______Offset________|",
         };
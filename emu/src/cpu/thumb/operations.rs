@@ -1,14 +1,17 @@
 use crate::bitwise::Bits;
 use crate::cpu::arm::alu_instruction::shift; // TODO: Move this to a more appropriate location, extract common code in "alu" module for example
+use crate::cpu::arm::operations::multiply_cycles;
 use crate::cpu::arm7tdmi::Arm7tdmi;
 use crate::cpu::condition::Condition;
 use crate::cpu::flags::{LoadStoreKind, OperandKind, Operation, ReadWriteKind, ShiftKind};
 use crate::cpu::registers::{REG_LR, REG_PROGRAM_COUNTER, REG_SP};
 use crate::cpu::thumb;
 use crate::cpu::thumb::alu_instructions::{ThumbHighRegisterOperation, ThumbModeAluInstruction};
-use crate::cpu::thumb::mode::ThumbModeOpcode;
 use std::ops::Mul;
 
+#[cfg(feature = "debug-hooks")]
+use logger::log;
+
 pub const SIZE_OF_INSTRUCTION: u32 = 2;
 
 impl Arm7tdmi {
@@ -90,6 +93,17 @@ impl Arm7tdmi {
 
     pub fn alu_op(&mut self, op: ThumbModeAluInstruction, rs: u16, rd: u16) {
         let rs = self.registers.register_at(rs.into());
+
+        // Shifting by a register takes an internal cycle.
+        if matches!(
+            op,
+            ThumbModeAluInstruction::Lsl
+                | ThumbModeAluInstruction::Lsr
+                | ThumbModeAluInstruction::Asr
+                | ThumbModeAluInstruction::Ror
+        ) {
+            self.bus.idle(1);
+        }
         match op {
             ThumbModeAluInstruction::And => {
                 self.and(rd.into(), self.registers.register_at(rd.into()), rs, true);
@@ -234,10 +248,11 @@ impl Arm7tdmi {
         // word alignment
         pc.set_bit_off(1);
         pc.set_bit_off(0);
-        let address = pc.wrapping_add(immediate_value as u32) as usize;
+        let address = pc.wrapping_add(immediate_value as u32);
         let value = self.read_word(address);
         let dest = r_destination.into();
         self.registers.set_register_at(dest, value);
+        self.bus.idle(1);
     }
 
     pub fn load_store_register_offset(
@@ -250,7 +265,7 @@ impl Arm7tdmi {
     ) {
         let ro = self.registers.register_at(offset_register.into());
         let rb = self.registers.register_at(base_register.into());
-        let address: usize = rb.wrapping_add(ro).try_into().unwrap();
+        let address = rb.wrapping_add(ro);
         let rd: usize = source_destination_register.into();
 
         match (load_store, byte_word) {
@@ -271,6 +286,10 @@ impl Arm7tdmi {
                 self.registers.set_register_at(rd, value);
             }
         };
+
+        if load_store == LoadStoreKind::Load {
+            self.bus.idle(1);
+        }
     }
 
     pub fn load_store_sign_extend_byte_halfword(
@@ -283,7 +302,7 @@ impl Arm7tdmi {
     ) {
         let offset = self.registers.register_at(r_offset.try_into().unwrap());
         let base = self.registers.register_at(r_base.try_into().unwrap());
-        let address: usize = base.wrapping_add(offset).try_into().unwrap();
+        let address = base.wrapping_add(offset);
 
         match (sign_extend_flag, h_flag) {
             // Store halfword
@@ -310,9 +329,13 @@ impl Arm7tdmi {
                     .set_register_at(r_destination.try_into().unwrap(), value);
             }
         }
+
+        if sign_extend_flag || h_flag {
+            self.bus.idle(1);
+        }
     }
 
-    pub fn load_store_immediate_offset(&mut self, op_code: ThumbModeOpcode) {
+    pub fn load_store_immediate_offset(&mut self, op_code: u16) {
         let byte_word: ReadWriteKind = op_code.get_bit(12).into();
         let load_store: LoadStoreKind = op_code.get_bit(11).into();
         let offset5 = op_code.get_bits(6..=10) as u32;
@@ -326,7 +349,7 @@ impl Arm7tdmi {
         let rd = op_code.get_bits(0..=2).into();
 
         let base = self.registers.register_at(rb.into());
-        let address = base.wrapping_add(offset).try_into().unwrap();
+        let address = base.wrapping_add(offset);
 
         match (load_store, byte_word) {
             (LoadStoreKind::Store, ReadWriteKind::Word) => {
@@ -346,6 +369,10 @@ impl Arm7tdmi {
                 self.registers.set_register_at(rd, v as u32);
             }
         }
+
+        if load_store == LoadStoreKind::Load {
+            self.bus.idle(1);
+        }
     }
 
     pub fn load_store_halfword(
@@ -356,7 +383,7 @@ impl Arm7tdmi {
         source_destination_register: u16,
     ) {
         let rb = self.registers.register_at(base_register.into());
-        let address: usize = rb.wrapping_add(offset as u32).try_into().unwrap();
+        let address = rb.wrapping_add(offset as u32);
 
         match load_store {
             LoadStoreKind::Load => {
@@ -364,6 +391,7 @@ impl Arm7tdmi {
 
                 self.registers
                     .set_register_at(source_destination_register as usize, value);
+                self.bus.idle(1);
             }
             LoadStoreKind::Store => {
                 self.bus.write_half_word(
@@ -382,18 +410,21 @@ impl Arm7tdmi {
         r_destination: u16,
         word8: u16,
     ) {
-        let address = self.registers.register_at(REG_SP) + (word8 as u32);
+        let address = self
+            .registers
+            .register_at(REG_SP)
+            .wrapping_add(word8 as u32);
 
         let rd = r_destination.into();
         match load_store {
             LoadStoreKind::Load => {
-                let value = self.read_word(address.try_into().unwrap());
+                let value = self.read_word(address);
 
                 self.registers.set_register_at(rd, value);
+                self.bus.idle(1);
             }
             LoadStoreKind::Store => {
-                self.bus
-                    .write_word(address.try_into().unwrap(), self.registers.register_at(rd));
+                self.bus.write_word(address, self.registers.register_at(rd));
             }
         }
     }
@@ -419,6 +450,9 @@ impl Arm7tdmi {
         self.registers.set_register_at(REG_SP, new_sp as u32);
     }
 
+    /// PUSH and POP. As on every `ARMv4` core, popping PC never changes the state: bit 0 of
+    /// the value is ignored. With an empty list PC is transferred and SP moves by 0x40,
+    /// as if the 16 registers of an ARM block transfer were.
     pub fn push_pop_register(
         &mut self,
         load_store: LoadStoreKind,
@@ -426,50 +460,71 @@ impl Arm7tdmi {
         register_list: u16,
     ) {
         let mut reg_sp = self.registers.register_at(REG_SP);
+        let empty = register_list == 0 && !pc_lr;
+
+        // Words are accessed aligned anyway, a misaligned SP usually means a corrupted stack.
+        #[cfg(feature = "debug-hooks")]
+        if reg_sp & 0b11 != 0 {
+            log(format!(
+                "PUSH/POP with misaligned SP 0x{reg_sp:08X} at 0x{:08X}",
+                self.registers.program_counter().wrapping_sub(4)
+            ));
+        }
 
         match load_store {
+            LoadStoreKind::Store if empty => {
+                reg_sp = reg_sp.wrapping_sub(0x40);
+                let pc = self.registers.program_counter() as u32 + SIZE_OF_INSTRUCTION;
+                self.bus.write_word(reg_sp, pc);
+            }
             LoadStoreKind::Store => {
                 if pc_lr {
-                    reg_sp -= 4;
-                    self.bus.write_word(
-                        reg_sp.try_into().unwrap(),
-                        self.registers.register_at(REG_LR),
-                    );
+                    reg_sp = reg_sp.wrapping_sub(4);
+                    self.bus
+                        .write_word(reg_sp, self.registers.register_at(REG_LR));
                 }
 
                 for r in (0..=7).rev() {
                     if register_list.get_bit(r) {
-                        reg_sp -= 4;
-                        self.bus.write_word(
-                            reg_sp.try_into().unwrap(),
-                            self.registers.register_at(r.into()),
-                        );
+                        reg_sp = reg_sp.wrapping_sub(4);
+                        self.bus
+                            .write_word(reg_sp, self.registers.register_at(r.into()));
                     }
                 }
             }
+            LoadStoreKind::Load if empty => {
+                let value = self.read_word(reg_sp);
+                self.registers.set_program_counter(value & !1);
+
+                reg_sp = reg_sp.wrapping_add(0x40);
+            }
             LoadStoreKind::Load => {
                 for r in 0..=7 {
                     if register_list.get_bit(r) {
-                        let value = self.read_word(reg_sp.try_into().unwrap());
+                        let value = self.read_word(reg_sp);
 
                         self.registers.set_register_at(r.into(), value);
 
-                        reg_sp += 4;
+                        reg_sp = reg_sp.wrapping_add(4);
                     }
                 }
 
                 if pc_lr {
-                    let value = self.read_word(reg_sp.try_into().unwrap());
-                    self.registers.set_program_counter(value);
+                    let value = self.read_word(reg_sp);
+                    self.registers.set_program_counter(value & !1);
 
-                    reg_sp += 4;
+                    reg_sp = reg_sp.wrapping_add(4);
                 }
             }
         }
 
         self.registers.set_register_at(REG_SP, reg_sp);
 
-        if load_store == LoadStoreKind::Load && pc_lr {
+        if load_store == LoadStoreKind::Load {
+            self.bus.idle(1);
+        }
+
+        if load_store == LoadStoreKind::Load && (pc_lr || empty) {
             self.flush_pipeline();
         }
     }
@@ -517,26 +572,30 @@ impl Arm7tdmi {
 
                         first_written = true;
 
-                        self.bus.write_word(address as usize, value);
+                        self.bus.write_word(address, value);
 
-                        address += 4;
+                        address = address.wrapping_add(4);
                     }
                 }
             }
             LoadStoreKind::Load => {
                 for r in 0..=15 {
                     if register_list.get_bit(r) {
-                        let value = self.read_word(address as usize);
+                        let value = self.read_word(address);
                         self.registers.set_register_at(r as usize, value);
 
-                        address += 4;
+                        address = address.wrapping_add(4);
                     }
                 }
             }
         }
 
         self.registers
-            .set_register_at(base_register, base_address + register_count * 4);
+            .set_register_at(base_register, base_address.wrapping_add(register_count * 4));
+
+        if load_store == LoadStoreKind::Load {
+            self.bus.idle(1);
+        }
 
         if load_store == LoadStoreKind::Load && register_list.is_bit_on(15) {
             self.flush_pipeline();
@@ -584,6 +643,8 @@ impl Arm7tdmi {
     }
 
     pub fn thumb_mul(&mut self, reg_result: usize, op1: u32, op2: u32) {
+        self.bus.idle(multiply_cycles(op2, true));
+
         let result = op1 as u64 * op2 as u64;
 
         self.registers.set_register_at(reg_result, result as u32);
@@ -613,7 +674,7 @@ mod tests {
             op_code.instruction,
         );
 
-        cpu.execute_thumb(op_code);
+        cpu.execute_thumb(op_code.raw);
 
         assert!(!cpu.cpsr.carry_flag());
         assert!(!cpu.cpsr.sign_flag());
@@ -0,0 +1,114 @@
+//! Trace of the executed instructions, one line per instruction, to find where a run
+//! diverges from a reference emulator with a plain `diff`.
+//!
+//! Lines use the register dump layout of the mGBA and `NanoBoyAdvance` trace logs: R0 to
+//! R15 as the instruction sees them (R15 is its address plus 8 in ARM, plus 4 in Thumb),
+//! the CPSR, then the address and the opcode of the instruction, Thumb opcodes padded
+//! to keep the columns aligned:
+//! ```text
+//! 00000000 00000000 ... 08000008 cpsr: 0000001F | 08000000: E3A00012
+//! 00000012 00000000 ... 08000106 cpsr: 0000003F | 08000102:     2001
+//! ```
+
+use std::fmt;
+use std::io::{self, Write};
+
+/// State of the CPU before an instruction executes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuTraceEntry {
+    /// R0 to R15 of the current mode.
+    pub registers: [u32; 16],
+    pub cpsr: u32,
+    /// Address of the instruction.
+    pub address: u32,
+    pub opcode: u32,
+    pub thumb: bool,
+}
+
+impl fmt::Display for CpuTraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for register in self.registers {
+            write!(f, "{register:08X} ")?;
+        }
+
+        write!(f, "cpsr: {:08X} | {:08X}: ", self.cpsr, self.address)?;
+        if self.thumb {
+            write!(f, "    {:04X}", self.opcode)
+        } else {
+            write!(f, "{:08X}", self.opcode)
+        }
+    }
+}
+
+/// Destination of the executed instructions, see
+/// [`Arm7tdmi::set_cpu_trace`](crate::cpu::arm7tdmi::Arm7tdmi::set_cpu_trace).
+pub trait CpuTraceWriter: Send {
+    /// Appends `entry` to the trace.
+    ///
+    /// # Errors
+    /// It fails if the underlying writer fails, the CPU stops tracing in this case.
+    fn record(&mut self, entry: &CpuTraceEntry) -> io::Result<()>;
+
+    /// Flushes the underlying writer.
+    ///
+    /// # Errors
+    /// It fails if the underlying writer fails.
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// Writes the entries as text lines in the layout of the module documentation.
+pub struct TextTrace<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> TextTrace<W> {
+    pub const fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send> CpuTraceWriter for TextTrace<W> {
+    fn record(&mut self, entry: &CpuTraceEntry) -> io::Result<()> {
+        writeln!(self.writer, "{entry}")
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_lines() {
+        let mut registers = [0; 16];
+        registers[0] = 0x12;
+        registers[15] = 0x0800_0106;
+
+        let mut trace = TextTrace::new(Vec::new());
+        trace
+            .record(&CpuTraceEntry {
+                registers,
+                cpsr: 0x3F,
+                address: 0x0800_0102,
+                opcode: 0x2001,
+                thumb: true,
+            })
+            .unwrap();
+
+        let text = String::from_utf8(trace.into_inner()).unwrap();
+        assert_eq!(
+            text,
+            format!(
+                "00000012 {}08000106 cpsr: 0000003F | 08000102:     2001\n",
+                "00000000 ".repeat(14)
+            )
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Determinism check: runs a ROM with the same inputs under two configurations of the
+//! emulator and compares their whole state after every frame.
+//!
+//! Performance features, like the JIT, must never change what is emulated. Comparing
+//! them against the plain interpreter on a real game catches a divergence at the frame
+//! it happens, and names the components that differ:
+//! ```no_run
+//! use emu::{cartridge_header::CartridgeHeader, determinism, gba::Gba};
+//!
+//! let bios = std::fs::read("gba_bios.bin").unwrap().try_into().unwrap();
+//! let rom = std::fs::read("game.gba").unwrap();
+//! let new = || Gba::new(CartridgeHeader::new(&rom).unwrap(), bios, rom.clone());
+//!
+//! let mut reference = new();
+//! #[cfg(feature = "jit")]
+//! reference.cpu.set_jit_enabled(false);
+//! let candidate = new();
+//!
+//! let inputs = [0; 600];
+//! if let Some(divergence) = determinism::compare(reference, candidate, &inputs).unwrap() {
+//!     println!("{divergence}");
+//! }
+//! ```
+//!
+//! A run must also give the same states every time, which [`verify_movie`] checks by
+//! playing a [`Movie`] twice and comparing hashes taken every few frames. Hashes of runs
+//! on two instances or two machines are compared with [`StateTrace::first_divergence`]:
+//! state read before being written, or changed by the frontend's thread, would desync
+//! the movies and the netplay sessions.
+
+use std::fmt;
+
+use crate::{
+    cpu::arm7tdmi::Arm7tdmi,
+    gba::Gba,
+    movie::{Movie, MovieError},
+    save_state::{crc32, SaveStateError, Section},
+};
+
+/// CRC-32 of each component, see [`state_hashes`].
+pub type StateHashes = [u32; Section::ALL.len()];
+
+/// First frame after which the two runs weren't in the same state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Frames completed by both runs, 0 if they differ before the first one ends.
+    pub frame: usize,
+    /// The last frame the states were compared equal, `None` if they never were. Runs
+    /// compared every few frames diverged in between.
+    pub since: Option<usize>,
+    /// The components whose state differs.
+    pub sections: Vec<Section>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.since {
+            Some(since) if since + 1 < self.frame => write!(
+                f,
+                "runs diverged between frames {since} and {}:",
+                self.frame
+            )?,
+            _ => write!(f, "runs diverged after frame {}:", self.frame)?,
+        }
+        for section in &self.sections {
+            write!(f, " {section}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// CRC-32 of the save-state section of every component, in the order of [`Section::ALL`].
+///
+/// # Errors
+/// It fails if one of the components can't be serialized.
+pub fn state_hashes(cpu: &Arm7tdmi) -> Result<StateHashes, SaveStateError> {
+    let mut hashes = [0; Section::ALL.len()];
+    for (hash, section) in hashes.iter_mut().zip(Section::ALL) {
+        let payload = cpu
+            .encode_section(section)
+            .map_err(SaveStateError::Encode)?;
+        *hash = crc32(&payload);
+    }
+
+    Ok(hashes)
+}
+
+fn differing_sections(reference: &StateHashes, candidate: &StateHashes) -> Vec<Section> {
+    Section::ALL
+        .into_iter()
+        .zip(reference.iter().zip(candidate))
+        .filter(|(_, (reference, candidate))| reference != candidate)
+        .map(|(section, _)| section)
+        .collect()
+}
+
+/// Runs `reference` and `candidate` side by side and returns the first frame after
+/// which their states differ.
+///
+/// Every entry of `inputs` is a frame, and the buttons pressed during it: one bit per
+/// [`Key`](crate::cpu::hardware::keypad::Key). Both emulators are expected to start from the same state, with the same ROM.
+///
+/// # Errors
+/// It fails if the state of one of the emulators can't be serialized.
+pub fn compare(
+    mut reference: Gba,
+    mut candidate: Gba,
+    inputs: &[u16],
+) -> Result<Option<Divergence>, SaveStateError> {
+    let diverged = |frame: usize, reference: &Gba, candidate: &Gba| {
+        let sections = differing_sections(
+            &state_hashes(&reference.cpu)?,
+            &state_hashes(&candidate.cpu)?,
+        );
+        Ok::<_, SaveStateError>((!sections.is_empty()).then(|| Divergence {
+            frame,
+            since: frame.checked_sub(1),
+            sections,
+        }))
+    };
+
+    if let Some(divergence) = diverged(0, &reference, &candidate)? {
+        return Ok(Some(divergence));
+    }
+
+    for (frame, &keys) in (1..).zip(inputs) {
+        for gba in [&mut reference, &mut candidate] {
+            gba.keypad_input().set_pressed_keys(keys);
+            // A deterministic game overruns in both runs at the same point.
+            let _ = gba.run_frame();
+        }
+
+        if let Some(divergence) = diverged(frame, &reference, &candidate)? {
+            return Ok(Some(divergence));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Hashes of the state taken while playing a movie, see [`trace_movie`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateTrace {
+    /// The frames completed and the hashes of the state then, from the start of the
+    /// movie to its end.
+    pub checkpoints: Vec<(usize, StateHashes)>,
+}
+
+impl StateTrace {
+    /// The first checkpoint of both traces where the states differ.
+    #[must_use]
+    pub fn first_divergence(&self, other: &Self) -> Option<Divergence> {
+        let mut since = None;
+        for ((frame, reference), (other_frame, candidate)) in
+            self.checkpoints.iter().zip(&other.checkpoints)
+        {
+            if frame != other_frame {
+                continue;
+            }
+
+            let sections = differing_sections(reference, candidate);
+            if !sections.is_empty() {
+                return Some(Divergence {
+                    frame: *frame,
+                    since,
+                    sections,
+                });
+            }
+            since = Some(*frame);
+        }
+
+        None
+    }
+}
+
+/// Plays `movie` from its start to its end, hashing the state every `interval` frames
+/// and after the last one.
+///
+/// # Panics
+/// If `interval` is 0.
+///
+/// # Errors
+/// It fails if the movie can't be played on `gba`, see [`Gba::play_movie`], or the
+/// state can't be serialized.
+pub fn trace_movie(
+    gba: &mut Gba,
+    movie: &Movie,
+    interval: usize,
+) -> Result<StateTrace, MovieError> {
+    assert!(interval > 0, "empty interval");
+
+    gba.play_movie(movie)?;
+    let mut checkpoints = vec![(0, state_hashes(&gba.cpu)?)];
+    let frames = movie.frames.len();
+    for frame in 1..=frames {
+        // A deterministic game overruns in both runs at the same point.
+        let _ = gba.run_frame();
+        if frame % interval == 0 || frame == frames {
+            checkpoints.push((frame, state_hashes(&gba.cpu)?));
+        }
+    }
+
+    Ok(StateTrace { checkpoints })
+}
+
+/// Plays `movie` twice on `gba` and returns the first checkpoint where the runs
+/// differ, see [`trace_movie`].
+///
+/// # Panics
+/// If `interval` is 0.
+///
+/// # Errors
+/// It fails if the movie can't be played on `gba` or the state can't be serialized.
+pub fn verify_movie(
+    gba: &mut Gba,
+    movie: &Movie,
+    interval: usize,
+) -> Result<Option<Divergence>, MovieError> {
+    let first = trace_movie(gba, movie, interval)?;
+    let second = trace_movie(gba, movie, interval)?;
+
+    Ok(first.first_divergence(&second))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movie::MovieStart;
+    use crate::test_rom::TestRom;
+
+    /// Counts in R0 and stores the count in work RAM, forever.
+    fn counter() -> Gba {
+        TestRom::new(0x100).counter().gba()
+    }
+
+    #[test]
+    fn same_configuration_never_diverges() {
+        assert_eq!(compare(counter(), counter(), &[0, 1, 0]).unwrap(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "jit")]
+    fn jit_matches_the_interpreter() {
+        let mut interpreter = counter();
+        interpreter.cpu.set_jit_enabled(false);
+
+        assert_eq!(compare(interpreter, counter(), &[0; 3]).unwrap(), None);
+    }
+
+    #[test]
+    fn divergence_names_the_components() {
+        let mut candidate = counter();
+        for _ in 0..100 {
+            candidate.step();
+        }
+
+        let divergence = compare(counter(), candidate, &[0; 3]).unwrap().unwrap();
+        assert_eq!(divergence.frame, 0);
+        assert_eq!(divergence.since, None);
+        assert!(divergence.sections.contains(&Section::Cpu));
+    }
+
+    #[test]
+    fn movie_plays_the_same_twice() {
+        let mut gba = counter();
+        gba.start_recording(MovieStart::Now).unwrap();
+        for _ in 0..5 {
+            gba.run_frame().unwrap();
+        }
+        let movie = gba.stop_recording().unwrap();
+
+        let trace = trace_movie(&mut gba, &movie, 2).unwrap();
+        let frames: Vec<usize> = trace.checkpoints.iter().map(|(frame, _)| *frame).collect();
+        assert_eq!(frames, [0, 2, 4, 5]);
+        assert_eq!(verify_movie(&mut gba, &movie, 2).unwrap(), None);
+
+        let mut desynced = trace.clone();
+        desynced.checkpoints[2].1[0] ^= 1;
+        let divergence = trace.first_divergence(&desynced).unwrap();
+        assert_eq!(divergence.frame, 4);
+        assert_eq!(divergence.since, Some(2));
+        assert_eq!(divergence.sections, [Section::Cpu]);
+        assert_eq!(
+            divergence.to_string(),
+            "runs diverged between frames 2 and 4: cpu"
+        );
+    }
+}
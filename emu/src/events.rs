@@ -0,0 +1,159 @@
+//! Callbacks on the hardware events, for the frontends and the scripts that react to the
+//! emulation instead of polling it.
+//!
+//! The callbacks run on the emulation thread, in the middle of [`Gba::step`]: they only
+//! see the [`Event`], and should hand the work over rather than do it there.
+//!
+//! ```
+//! use std::sync::{
+//!     atomic::{AtomicU64, Ordering},
+//!     Arc,
+//! };
+//!
+//! use emu::events::{Event, EventKind};
+//! # fn run(gba: &mut emu::gba::Gba) {
+//! let last_frame = Arc::new(AtomicU64::new(0));
+//! let frame = Arc::clone(&last_frame);
+//! let id = gba.subscribe(EventKind::FrameComplete, move |event| {
+//!     if let Event::FrameComplete { frame: number, .. } = event {
+//!         frame.store(*number, Ordering::Relaxed);
+//!     }
+//! });
+//! // ...
+//! gba.unsubscribe(id);
+//! # }
+//! ```
+//!
+//! [`Gba::step`]: crate::gba::Gba::step
+
+use std::fmt;
+
+/// Something the hardware did during a step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The LCD entered Vblank, whether or not its interrupt is enabled.
+    VBlank,
+    /// The LCD entered the Hblank of `scanline`, Vblank scanlines included.
+    HBlank { scanline: u16 },
+    /// A serial transfer completed, whether or not its interrupt is enabled.
+    SerialTransfer,
+    /// A frame is complete, after the input of the next one was set. `frame` counts the
+    /// frames since power-on as [`RasterPosition::frame`] does, `drawn` is `false` for
+    /// the frames skipped during fast-forward: the frame output still holds the previous
+    /// picture.
+    ///
+    /// [`RasterPosition::frame`]: crate::cpu::hardware::lcd::RasterPosition::frame
+    FrameComplete { frame: u64, drawn: bool },
+}
+
+impl Event {
+    #[must_use]
+    pub const fn kind(&self) -> EventKind {
+        match self {
+            Self::VBlank => EventKind::VBlank,
+            Self::HBlank { .. } => EventKind::HBlank,
+            Self::SerialTransfer => EventKind::SerialTransfer,
+            Self::FrameComplete { .. } => EventKind::FrameComplete,
+        }
+    }
+}
+
+/// What a callback subscribes to, see [`Gba::subscribe`](crate::gba::Gba::subscribe).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    VBlank,
+    HBlank,
+    SerialTransfer,
+    FrameComplete,
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::VBlank => write!(f, "Vblank"),
+            Self::HBlank => write!(f, "Hblank"),
+            Self::SerialTransfer => write!(f, "serial transfer"),
+            Self::FrameComplete => write!(f, "frame complete"),
+        }
+    }
+}
+
+/// Names a callback to remove it with [`Gba::unsubscribe`](crate::gba::Gba::unsubscribe).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type EventCallback = Box<dyn FnMut(&Event) + Send>;
+
+/// The callbacks of a [`Gba`](crate::gba::Gba), called in the order they subscribed.
+#[derive(Default)]
+pub(crate) struct EventHub {
+    next_id: u64,
+    subscribers: Vec<(SubscriptionId, EventKind, EventCallback)>,
+}
+
+impl EventHub {
+    pub(crate) fn subscribe(&mut self, kind: EventKind, callback: EventCallback) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        self.subscribers.push((id, kind, callback));
+
+        id
+    }
+
+    /// Whether `id` was subscribed.
+    pub(crate) fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let before = self.subscribers.len();
+        self.subscribers
+            .retain(|(subscribed, ..)| *subscribed != id);
+
+        self.subscribers.len() != before
+    }
+
+    /// Whether a callback waits for the events the bus collects, all but
+    /// [`EventKind::FrameComplete`].
+    pub(crate) fn listens_to_hardware(&self) -> bool {
+        self.subscribers
+            .iter()
+            .any(|(_, kind, _)| *kind != EventKind::FrameComplete)
+    }
+
+    pub(crate) fn dispatch(&mut self, event: &Event) {
+        let kind = event.kind();
+        for (_, subscribed, callback) in &mut self.subscribers {
+            if *subscribed == kind {
+                callback(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn callbacks_get_the_events_of_their_kind() {
+        let mut hub = EventHub::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let vblanks = Arc::clone(&received);
+        let id = hub.subscribe(
+            EventKind::VBlank,
+            Box::new(move |event| vblanks.lock().unwrap().push(*event)),
+        );
+        assert!(hub.listens_to_hardware());
+
+        hub.dispatch(&Event::HBlank { scanline: 3 });
+        hub.dispatch(&Event::VBlank);
+        assert!(hub.unsubscribe(id));
+        assert!(!hub.unsubscribe(id));
+        hub.dispatch(&Event::VBlank);
+
+        assert_eq!(*received.lock().unwrap(), [Event::VBlank]);
+        assert!(!hub.listens_to_hardware());
+    }
+}
@@ -0,0 +1,131 @@
+//! Fast-forward: the frontend runs the emulation several times faster than the hardware,
+//! the core skips drawing most frames and fits the sound to the host time.
+//!
+//! The speed itself is set on the frontend's [`Pacer`](crate::clock::Pacer), the frames
+//! are emulated as usual: only the picture and the sound handed out change. With
+//! `speed` times more frames than the display shows, drawing all of them would make the
+//! renderer the limit, and their sound would pile up in the audio queue.
+
+use std::fmt;
+
+use crate::cpu::hardware::sound::mixer::StereoSample;
+
+/// Speed of [`FastForward::default`].
+pub const DEFAULT_SPEED: f64 = 5.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FastForward {
+    /// How many times faster than the hardware, above 1.
+    pub speed: f64,
+    /// Frames not drawn after each one drawn, see
+    /// [`Lcd::set_frame_skip`](crate::cpu::hardware::lcd::Lcd::set_frame_skip).
+    pub frame_skip: u32,
+    pub audio: TurboAudio,
+}
+
+impl Default for FastForward {
+    fn default() -> Self {
+        Self::new(DEFAULT_SPEED)
+    }
+}
+
+impl FastForward {
+    /// Fast-forward at `speed`, drawing about as many frames as the display shows and
+    /// keeping the pitch of the sound.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub const fn new(speed: f64) -> Self {
+        Self {
+            speed,
+            frame_skip: speed.ceil().max(1.0) as u32 - 1,
+            audio: TurboAudio::PitchCorrected,
+        }
+    }
+}
+
+/// What becomes of the sound during fast-forward.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TurboAudio {
+    /// Silence, for the frontends that don't want the chopped sound.
+    Mute,
+    /// Pieces of each frame at the normal pitch, as much as the host time allows.
+    #[default]
+    PitchCorrected,
+}
+
+impl fmt::Display for TurboAudio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mute => write!(f, "muted"),
+            Self::PitchCorrected => write!(f, "pitch-corrected"),
+        }
+    }
+}
+
+/// Fits the audio frames to the host time: a frame at `speed` lasts `1 / speed` of a
+/// frame on the host, it's cut to as many samples.
+#[derive(Default)]
+pub(crate) struct TurboAudioFit {
+    /// Fraction of a sample carried to the next frame, so that the frames add up.
+    carry: f64,
+}
+
+impl TurboAudioFit {
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    pub(crate) fn fit(
+        &mut self,
+        fast_forward: &FastForward,
+        mut frame: Vec<StereoSample>,
+    ) -> Vec<StereoSample> {
+        let samples = frame.len() as f64 / fast_forward.speed.max(1.0) + self.carry;
+        let kept = (samples as usize).min(frame.len());
+        self.carry = samples.fract();
+
+        frame.truncate(kept);
+        if fast_forward.audio == TurboAudio::Mute {
+            frame.fill(StereoSample::default());
+        }
+
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn frame(samples: i16) -> Vec<StereoSample> {
+        (0..samples)
+            .map(|i| StereoSample { left: i, right: -i })
+            .collect()
+    }
+
+    #[test]
+    fn frames_are_cut_to_the_host_time() {
+        let mut fit = TurboAudioFit::default();
+        let fast_forward = FastForward::new(3.0);
+        assert_eq!(fast_forward.frame_skip, 2);
+
+        let lengths: Vec<usize> = (0..3)
+            .map(|_| fit.fit(&fast_forward, frame(800)).len())
+            .collect();
+        assert_eq!(lengths.iter().sum::<usize>(), 800);
+        // The start of the frame is kept as it is.
+        assert_eq!(fit.fit(&fast_forward, frame(800))[..10], frame(10));
+
+        let muted = FastForward {
+            audio: TurboAudio::Mute,
+            ..fast_forward
+        };
+        let silence = fit.fit(&muted, frame(800));
+        assert!(silence
+            .iter()
+            .all(|sample| *sample == StereoSample::default()));
+    }
+}
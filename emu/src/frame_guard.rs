@@ -0,0 +1,219 @@
+//! Guard against frames that never end.
+//!
+//! A frame lasts [`CYCLES_PER_FRAME`] cycles on hardware, whatever the game does. One
+//! running far longer, or steps that stop advancing the clock, mean the emulation is
+//! stuck on a bug: the guard reports it with the last executed instructions instead of
+//! letting the frontend wait forever for the next frame.
+
+use std::fmt;
+
+use crate::clock::CYCLES_PER_FRAME;
+
+/// Frames' worth of cycles, and of steps, a frame can last before it's reported.
+pub const DEFAULT_OVERRUN_FACTOR: u32 = 8;
+
+/// Instructions kept in [`FrameOverrun::recent_instructions`].
+const TRACE_LENGTH: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameOverrun {
+    /// Frame that didn't end, as counted by the frame output.
+    pub frame: u64,
+    /// Bus cycles and CPU steps since the frame began.
+    pub cycles: u128,
+    pub steps: u64,
+    /// Addresses of the last executed instructions, oldest first.
+    pub recent_instructions: Vec<u32>,
+}
+
+impl fmt::Display for FrameOverrun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "frame {} didn't end after {} cycles and {} steps",
+            self.frame, self.cycles, self.steps
+        )?;
+
+        if !self.recent_instructions.is_empty() {
+            write!(f, ", last instructions:")?;
+        }
+        for address in &self.recent_instructions {
+            write!(f, " 0x{address:08X}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts the cycles and steps of the current frame, see [`Self::check`].
+#[derive(Clone, Debug)]
+pub struct FrameGuard {
+    factor: Option<u32>,
+    frame: u64,
+    frame_start: u128,
+    steps: u64,
+    /// Ring buffer of the last executed instructions, `next` being the oldest once full.
+    trace: Vec<u32>,
+    next: usize,
+    /// Only one overrun is reported per frame.
+    reported: bool,
+}
+
+impl FrameGuard {
+    #[must_use]
+    pub fn new(factor: Option<u32>) -> Self {
+        Self {
+            factor,
+            frame: 0,
+            frame_start: 0,
+            steps: 0,
+            trace: Vec::with_capacity(TRACE_LENGTH),
+            next: 0,
+            reported: false,
+        }
+    }
+
+    #[must_use]
+    pub const fn factor(&self) -> Option<u32> {
+        self.factor
+    }
+
+    /// Counts from the next step, for when the clock jumps: reset or state loaded.
+    pub fn restart(&mut self) {
+        *self = Self::new(self.factor);
+    }
+
+    /// Called after every step with the frame count, the bus cycles and the address of
+    /// the instruction executed, if any. Returns the overrun the first time the frame
+    /// goes over its budget.
+    pub fn check(
+        &mut self,
+        frame: u64,
+        cycle: u128,
+        instruction: Option<u32>,
+    ) -> Option<FrameOverrun> {
+        if frame != self.frame || cycle < self.frame_start {
+            self.frame = frame;
+            self.frame_start = cycle;
+            self.steps = 0;
+            self.reported = false;
+        }
+
+        self.steps += 1;
+        if let Some(address) = instruction {
+            self.record(address);
+        }
+
+        let budget = u64::from(self.factor?) * CYCLES_PER_FRAME;
+        let cycles = cycle - self.frame_start;
+        if self.reported || (cycles <= u128::from(budget) && self.steps <= budget) {
+            return None;
+        }
+
+        self.reported = true;
+        Some(FrameOverrun {
+            frame,
+            cycles,
+            steps: self.steps,
+            recent_instructions: self.recent_instructions(),
+        })
+    }
+
+    fn record(&mut self, address: u32) {
+        if self.trace.len() < TRACE_LENGTH {
+            self.trace.push(address);
+        } else {
+            self.trace[self.next] = address;
+        }
+        self.next = (self.next + 1) % TRACE_LENGTH;
+    }
+
+    fn recent_instructions(&self) -> Vec<u32> {
+        if self.trace.len() < TRACE_LENGTH {
+            return self.trace.clone();
+        }
+
+        let (newest, oldest) = self.trace.split_at(self.next);
+        [oldest, newest].concat()
+    }
+}
+
+impl Default for FrameGuard {
+    fn default() -> Self {
+        Self::new(Some(DEFAULT_OVERRUN_FACTOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn normal_frames_pass() {
+        let mut guard = FrameGuard::new(Some(1));
+
+        for frame in 0..3 {
+            for cycle in 0..CYCLES_PER_FRAME {
+                let cycle = u128::from(frame * CYCLES_PER_FRAME + cycle);
+                assert_eq!(guard.check(frame, cycle, Some(0x0800_0000)), None);
+            }
+        }
+    }
+
+    #[test]
+    fn long_frame_is_reported_once() {
+        let mut guard = FrameGuard::new(Some(1));
+        let budget = u128::from(CYCLES_PER_FRAME);
+
+        assert_eq!(guard.check(0, 0, None), None);
+        assert_eq!(guard.check(0, budget, None), None);
+        let overrun = guard.check(0, budget + 1, Some(0x0800_0004)).unwrap();
+        assert_eq!(overrun.cycles, budget + 1);
+        assert_eq!(overrun.steps, 3);
+        assert_eq!(overrun.recent_instructions, [0x0800_0004]);
+        assert_eq!(guard.check(0, budget + 2, None), None);
+
+        // The next frame starts a new count.
+        assert_eq!(guard.check(1, budget + 3, None), None);
+    }
+
+    #[test]
+    fn steps_without_cycles_are_reported() {
+        let mut guard = FrameGuard::new(Some(1));
+
+        let overrun = (0..)
+            .find_map(|step| guard.check(0, 0, Some(0x0300_0000 + 4 * (step % 64))))
+            .unwrap();
+
+        assert_eq!(overrun.steps, CYCLES_PER_FRAME + 1);
+        assert_eq!(overrun.recent_instructions.len(), TRACE_LENGTH);
+        assert_eq!(overrun.recent_instructions[0], 0x0300_0084);
+        assert_eq!(overrun.recent_instructions[31], 0x0300_0000);
+    }
+
+    #[test]
+    fn disabled() {
+        let mut guard = FrameGuard::new(None);
+
+        assert_eq!(guard.check(0, 0, None), None);
+        assert_eq!(guard.check(0, u128::MAX, None), None);
+    }
+
+    #[test]
+    fn display() {
+        let overrun = FrameOverrun {
+            frame: 12,
+            cycles: 2_247_169,
+            steps: 900_000,
+            recent_instructions: vec![0x0800_01A0, 0x0800_01A4],
+        };
+
+        assert_eq!(
+            overrun.to_string(),
+            "frame 12 didn't end after 2247169 cycles and 900000 steps, last instructions: \
+             0x080001A0 0x080001A4"
+        );
+    }
+}
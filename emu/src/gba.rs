@@ -1,39 +1,1333 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{
+    atomic_file::DEFAULT_BACKUPS,
+    battery_save::{self, BatterySaveError, SaveLayout},
     bus::Bus,
+    capture::{Capture, CaptureReason, FrameCapture},
     cartridge_header::CartridgeHeader,
-    cpu::{arm7tdmi::Arm7tdmi, hardware::internal_memory::InternalMemory},
-    render::gba_lcd::GbaLcd,
+    cartridge_info::{CartridgeHardware, CartridgeInfo, SaveType},
+    cheats::Cheat,
+    clock::{self, ClockSample, FramePacing, FramePacingStats},
+    config::{EmuConfig, MemoryProfile, Overclock},
+    cpu::{
+        arm7tdmi::Arm7tdmi,
+        bios_hle::HleShortcut,
+        breakpoints::StepResult,
+        hardware::{
+            dma::BusMasters,
+            flash::{Flash, FlashTiming},
+            gpio::{GpioDevice, GpioPort},
+            internal_memory::InternalMemory,
+            keypad::{KeySampling, KeypadInput},
+            lcd::{Frame, FrameOutput},
+            peripheral::CartridgePeripheral,
+            rtc::{self, Rtc, RtcClock},
+            solar::{self, SolarLight, SolarSensor},
+            sound::{filter::FilterSettings, mixer::StereoSample},
+        },
+    },
+    events::{Event, EventHub, EventKind, SubscriptionId},
+    fast_forward::{FastForward, TurboAudioFit},
+    frame_guard::{FrameGuard, FrameOverrun},
+    input_macro::{InputMacro, MacroPlayer},
+    memory_budget::MemoryUsage,
+    memory_edit::{EditValue, MemoryEditError},
+    memory_map::{BIOS_SIZE, POST_BOOT_FLAG},
+    notifications::{Notification, NotificationKind, Notifications},
+    run_report::RunReport,
+    save_profiles::{SaveProfiles, DEFAULT_PROFILE},
+};
+
+#[cfg(feature = "jit")]
+use crate::cpu::jit::JitStats;
+#[cfg(feature = "debug-hooks")]
+use crate::{cpu_trace::CpuTraceWriter, io_trace::IoTraceWriter};
+#[cfg(feature = "serde")]
+use crate::{
+    movie::{Movie, MovieAnchor, MovieError, MovieSession, MovieStart, MovieStatus},
+    rewind::{RewindBuffer, RewindError},
+    save_state::{self, Attachment, LoadReport, SaveStateError},
+    step_history::{ReverseStepError, StepHistory},
 };
 
 pub struct Gba {
     pub cpu: Arm7tdmi,
 
     pub cartridge_header: CartridgeHeader,
-    pub lcd: Arc<Mutex<Box<GbaLcd>>>,
+
+    config: EmuConfig,
+
+    notifications: Notifications,
+
+    frame_guard: FrameGuard,
+    frame_overrun: Option<FrameOverrun>,
+
+    frame_pacing: FramePacing,
+
+    #[cfg(feature = "serde")]
+    step_history: Option<StepHistory>,
+    #[cfg(feature = "serde")]
+    rewind: Option<RewindBuffer>,
+    #[cfg(feature = "serde")]
+    movie: Option<MovieSession>,
+    capture: Option<FrameCapture>,
+
+    /// Cycles [`Self::run_cycles`] ran past its last target, taken from the next one.
+    cycles_ahead: u128,
+
+    input_macro: Option<MacroPlayer>,
+
+    execution: ExecutionState,
+
+    fast_forward: Option<FastForward>,
+    turbo_audio: TurboAudioFit,
+
+    events: EventHub,
+
+    /// Time of the cartridge's real-time clock, if it has one.
+    rtc: Option<RtcClock>,
+    /// Light on the cartridge's solar sensor, if it has one.
+    solar_light: Option<SolarLight>,
+}
+
+/// How [`Gba::reset`] restarts the game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetKind {
+    /// The soft reset of the BIOS that games run on A+B+Select+Start, see
+    /// [`Arm7tdmi::soft_reset`]: only the CPU restarts, the hardware and the work RAMs
+    /// stay as they are.
+    Soft,
+    /// Reboots through the BIOS with every component back to its power-on state, the
+    /// work RAMs and the save memory are kept.
+    Restart,
+    /// Like [`Self::Restart`], also clearing the work RAMs and erasing the save memory.
+    Hard,
+}
+
+/// Whether [`Gba::run_frame`] and [`Gba::run_cycles`] run the emulation, see
+/// [`Gba::pause`]. [`Gba::step`] always runs: the debugger steps a paused game.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExecutionState {
+    #[default]
+    Running,
+    Paused,
+    /// Runs to the end of the current frame, then pauses.
+    FrameStep,
+}
+
+/// What [`Gba::run_frame`] produced.
+pub struct FrameRun {
+    /// The frame completed by the Vblank the run stopped at, or the last one drawn
+    /// during fast-forward.
+    pub pixels: Arc<Frame>,
+    /// Always [`Gba::audio_samples_per_frame`] samples, fewer during fast-forward and
+    /// none while paused.
+    pub audio: Vec<StereoSample>,
 }
 
 impl Gba {
     #[must_use]
     pub fn new(
         cartridge_header: CartridgeHeader,
-        bios: [u8; 0x0000_4000],
+        bios: [u8; BIOS_SIZE],
         cartridge: Vec<u8>,
     ) -> Self {
-        let lcd = Arc::new(Mutex::new(Box::default()));
-        let memory = InternalMemory::new(bios, cartridge);
+        Self::with_config(cartridge_header, bios, cartridge, EmuConfig::default())
+    }
+
+    #[must_use]
+    pub fn with_config(
+        cartridge_header: CartridgeHeader,
+        bios: [u8; BIOS_SIZE],
+        cartridge: Vec<u8>,
+        config: EmuConfig,
+    ) -> Self {
+        let mut memory = InternalMemory::new(bios, cartridge);
+        if let Some(save_type) = config.save_type {
+            memory.flash = match save_type {
+                SaveType::Flash(size) => Some(Flash::new(size)),
+                SaveType::None | SaveType::Sram | SaveType::Eeprom => None,
+            };
+        }
+        let rtc = config
+            .rtc
+            .unwrap_or_else(|| rtc::detect(&cartridge_header.game_code))
+            .then(RtcClock::default);
+        let solar_light = config
+            .solar_sensor
+            .unwrap_or_else(|| solar::detect(&cartridge_header.game_code))
+            .then(SolarLight::default);
+        // Boktai has both, sharing the port.
+        let mut gpio_devices: Vec<Box<dyn GpioDevice>> = Vec::new();
+        if let Some(clock) = &rtc {
+            gpio_devices.push(Box::new(Rtc::new(clock.clone())));
+        }
+        if let Some(light) = &solar_light {
+            gpio_devices.push(Box::new(SolarSensor::new(light.clone())));
+        }
+        if !gpio_devices.is_empty() {
+            memory.add_peripheral(Box::new(GpioPort::new(gpio_devices)));
+        }
         let bus = Bus::with_memory(memory);
-        let arm = Arm7tdmi::new(bus);
+        let mut arm = Arm7tdmi::new(bus);
+        arm.set_bios_hle(config.bios_hle);
+        arm.set_max_accuracy(config.max_accuracy);
+        arm.set_abort_on_invalid_access(config.abort_on_invalid_access);
+        arm.bus.set_overclock(config.overclock);
+        arm.bus.set_key_sampling(config.key_sampling);
+        arm.bus.set_flash_timing(config.flash_timing);
+        arm.bus.connect_serial_peripheral(config.serial_peripheral);
+        if let Some(samples_per_frame) = config.audio_samples_per_frame {
+            arm.bus.set_audio_samples_per_frame(samples_per_frame);
+        }
+        if config.memory_profile == MemoryProfile::Bounded {
+            #[cfg(feature = "disassembler")]
+            arm.set_disassembly_enabled(false);
+            #[cfg(feature = "jit")]
+            arm.set_jit_enabled(false);
+        }
 
-        Self {
+        let mut gba = Self {
             cpu: arm,
             cartridge_header,
-            lcd,
+            config,
+            notifications: Notifications::default(),
+            frame_guard: FrameGuard::default(),
+            frame_overrun: None,
+            frame_pacing: FramePacing::default(),
+            #[cfg(feature = "serde")]
+            step_history: None,
+            #[cfg(feature = "serde")]
+            rewind: None,
+            #[cfg(feature = "serde")]
+            movie: None,
+            capture: None,
+            cycles_ahead: 0,
+            input_macro: None,
+            execution: ExecutionState::Running,
+            fast_forward: None,
+            turbo_audio: TurboAudioFit::default(),
+            events: EventHub::default(),
+            rtc,
+            solar_light,
+        };
+        if gba.config.skip_bios {
+            gba.skip_bios();
+        }
+        gba.load_save_profile();
+        gba.load_clock();
+
+        gba
+    }
+
+    /// The battery save profiles of the game, `None` without
+    /// [`EmuConfig::save_directory`].
+    #[must_use]
+    pub fn save_profiles(&self) -> Option<SaveProfiles> {
+        self.config.save_directory.as_deref().map(|root| {
+            SaveProfiles::new(root, &self.cartridge_header)
+                .with_backups(self.config.save_backups.unwrap_or(DEFAULT_BACKUPS))
+        })
+    }
+
+    /// Name of the battery save profile in use.
+    #[must_use]
+    pub fn save_profile(&self) -> &str {
+        self.config
+            .save_profile
+            .as_deref()
+            .unwrap_or(DEFAULT_PROFILE)
+    }
+
+    fn load_save_profile(&mut self) {
+        let Some(profiles) = self.save_profiles() else {
+            return;
+        };
+        if self.cpu.bus.internal_memory.flash.is_none() {
+            return;
+        }
+
+        let name = self.save_profile().to_string();
+        let result = match profiles.load(&name) {
+            Ok(Some(data)) => self.import_save(&data).map_err(|e| e.to_string()),
+            Ok(None) => Ok(SaveLayout::Raw),
+            Err(e) => Err(e.to_string()),
+        };
+
+        if let Err(e) = result {
+            self.notify(Notification::warning(
+                NotificationKind::SaveImported,
+                format!("Can't load save profile `{name}`: {e}"),
+            ));
+        }
+    }
+
+    fn load_clock(&mut self) {
+        let (Some(profiles), Some(clock)) = (self.save_profiles(), &self.rtc) else {
+            return;
+        };
+
+        match profiles.load_clock(self.save_profile()) {
+            Ok(offset) => {
+                clock.set_offset(offset.unwrap_or_default());
+                let _ = clock.take_changed();
+            }
+            Err(e) => self.notify(Notification::warning(
+                NotificationKind::SaveImported,
+                format!("Can't load the clock: {e}"),
+            )),
+        }
+    }
+
+    /// Writes the offset of the real-time clock next to the battery save, if the game
+    /// set the clock.
+    fn store_clock(&mut self) {
+        let (Some(profiles), Some(clock)) = (self.save_profiles(), &self.rtc) else {
+            return;
+        };
+        if !clock.take_changed() {
+            return;
+        }
+
+        if let Err(e) = profiles.store_clock(self.save_profile(), clock.offset()) {
+            self.notify(Notification::warning(
+                NotificationKind::SaveDataWritten,
+                e.to_string(),
+            ));
+        }
+    }
+
+    /// The real-time clock of the cartridge, `None` without one: the frontend can set
+    /// the time through it.
+    #[must_use]
+    pub const fn rtc_clock(&self) -> Option<&RtcClock> {
+        self.rtc.as_ref()
+    }
+
+    /// The light on the solar sensor of the cartridge, `None` without one: the frontend
+    /// sets it as the sun the player is in.
+    #[must_use]
+    pub const fn solar_light(&self) -> Option<&SolarLight> {
+        self.solar_light.as_ref()
+    }
+
+    /// Writes the battery save to its profile, if [`EmuConfig::save_directory`] is set.
+    fn store_save_profile(&mut self) {
+        let (Some(profiles), Some(data)) =
+            (self.save_profiles(), self.export_save(SaveLayout::Raw))
+        else {
+            return;
+        };
+
+        if let Err(e) = profiles.store(self.save_profile(), &data) {
+            self.notify(Notification::warning(
+                NotificationKind::SaveDataWritten,
+                e.to_string(),
+            ));
+        }
+    }
+
+    /// Runs a step of the CPU, see [`Arm7tdmi::step`].
+    pub fn step(&mut self) -> StepResult {
+        let instruction = self.cpu.next_instruction_address();
+
+        #[cfg(feature = "serde")]
+        if let Some(history) = &mut self.step_history {
+            // The state always serializes, a history that can't be recorded is dropped.
+            if history.before_step(&self.cpu).is_err() {
+                self.step_history = None;
+            }
+        }
+
+        if let hit @ StepResult::BreakpointHit(_) = self.cpu.step() {
+            return hit;
+        }
+
+        #[cfg(feature = "serde")]
+        if let Some(history) = &mut self.step_history {
+            history.after_step();
+        }
+
+        for event in self.cpu.bus.drain_events() {
+            self.events.dispatch(&event);
+        }
+
+        let frame = self.cpu.bus.lcd.raster_position().frame;
+        if frame != self.frame_pacing.frame() {
+            self.frame_pacing.record(frame, Instant::now());
+            self.next_macro_frame();
+            #[cfg(feature = "serde")]
+            self.next_movie_frame();
+            if self.execution == ExecutionState::FrameStep {
+                self.execution = ExecutionState::Paused;
+            }
+
+            #[cfg(feature = "serde")]
+            if let Some(rewind) = &mut self.rewind {
+                // Same as the step history, a buffer that can't be filled is dropped.
+                if rewind.on_frame(&self.cpu).is_err() {
+                    self.rewind = None;
+                }
+            }
+
+            self.events.dispatch(&Event::FrameComplete {
+                frame,
+                drawn: !self.cpu.bus.lcd.skipping(),
+            });
+        }
+        let overrun = self.frame_guard.check(frame, self.cycles(), instruction);
+        if self.capture.is_some() {
+            self.take_captures(overrun.as_ref());
+        }
+        if let Some(overrun) = overrun {
+            self.notify(Notification::error(
+                NotificationKind::FrameOverrun,
+                format!("Emulation stuck: {overrun}"),
+            ));
+            self.frame_overrun = Some(overrun);
+        }
+
+        StepResult::Normal
+    }
+
+    /// Takes screenshots and state dumps without the frontend, see
+    /// [`capture`](crate::capture). `None` stops capturing.
+    pub fn set_capture(&mut self, capture: Option<FrameCapture>) {
+        self.capture = capture;
+    }
+
+    #[must_use]
+    pub const fn capture(&self) -> Option<&FrameCapture> {
+        self.capture.as_ref()
+    }
+
+    /// Writes the captures due after a step.
+    fn take_captures(&mut self, overrun: Option<&FrameOverrun>) {
+        let Some(mut capture) = self.capture.take() else {
+            return;
+        };
+
+        let output = &self.cpu.bus.lcd.frame_output;
+        let frame = output.frame_count();
+        let mut reasons = Vec::new();
+        if capture.periodic_due(frame, self.cycles()) {
+            reasons.push(CaptureReason::Periodic);
+        }
+        if let Some(&trap) = self.cpu.execution_trap() {
+            if capture.new_trap(trap) {
+                reasons.push(CaptureReason::Trap(trap));
+            }
+        }
+        if let Some(overrun) = overrun.filter(|_| capture.policy().on_error) {
+            reasons.push(CaptureReason::Overrun(overrun.clone()));
+        }
+
+        if reasons.is_empty() {
+            self.capture = Some(capture);
+            return;
+        }
+
+        let pixels = output.load();
+        for reason in &reasons {
+            #[cfg(feature = "serde")]
+            let state = (*reason != CaptureReason::Periodic)
+                .then(|| save_state::encode(&self.cpu).ok())
+                .flatten();
+            #[cfg(not(feature = "serde"))]
+            let state: Option<Vec<u8>> = None;
+
+            let error = capture.write(&Capture {
+                frame,
+                reason,
+                pixels: &pixels,
+                state: state.as_deref(),
+            });
+            if let Some(e) = error {
+                self.notify(Notification::warning(
+                    NotificationKind::CaptureFailed,
+                    format!("Can't write the capture: {e}"),
+                ));
+            }
+        }
+
+        self.capture = Some(capture);
+    }
+
+    /// Records the following steps in `history` to step back with [`Self::step_back`],
+    /// or stops recording with `None`. Nothing is recorded with
+    /// [`MemoryProfile::Bounded`].
+    #[cfg(feature = "serde")]
+    pub fn set_step_history(&mut self, history: Option<StepHistory>) {
+        self.step_history =
+            history.filter(|_| self.config.memory_profile == MemoryProfile::Standard);
+    }
+
+    #[cfg(feature = "serde")]
+    #[must_use]
+    pub const fn step_history(&self) -> Option<&StepHistory> {
+        self.step_history.as_ref()
+    }
+
+    /// Goes back `steps` steps, as counted by [`Self::step`], breakpoint hits excluded.
+    ///
+    /// # Errors
+    /// It fails if the history isn't recorded or doesn't go back that far, the state is
+    /// untouched in these cases.
+    #[cfg(feature = "serde")]
+    pub fn step_back(&mut self, steps: u64) -> Result<(), ReverseStepError> {
+        let history = self
+            .step_history
+            .as_mut()
+            .ok_or(ReverseStepError::Disabled)?;
+        let (target, state) = history.rewind(steps)?;
+
+        if let Err(e) = save_state::decode(&mut self.cpu, &state) {
+            history.clear();
+            return Err(e.into());
+        }
+        while history.steps() < target {
+            history.before_step(&self.cpu)?;
+            if self.cpu.step() == StepResult::Normal {
+                history.after_step();
+            }
         }
+
+        self.frame_guard.restart();
+        self.frame_overrun = None;
+
+        Ok(())
+    }
+
+    /// Runs until the next Vblank and returns the frame with its audio. Breakpoints don't
+    /// stop the run, the instruction is executed at the next step. While paused, nothing
+    /// runs and the last frame is returned without audio, see [`Self::pause`].
+    ///
+    /// # Errors
+    /// It stops early if the frame lasts far longer than on hardware, see
+    /// [`Self::set_frame_guard`].
+    pub fn run_frame(&mut self) -> Result<FrameRun, FrameOverrun> {
+        let output = self.frame_output();
+        if self.execution == ExecutionState::Paused {
+            return Ok(FrameRun {
+                pixels: output.load(),
+                audio: Vec::new(),
+            });
+        }
+
+        let frame = output.frame_count();
+        while output.frame_count() == frame {
+            self.step();
+
+            if let Some(overrun) = self.take_frame_overrun() {
+                return Err(overrun);
+            }
+        }
+
+        let mut audio = self.cpu.bus.take_audio_frame();
+        if let Some(fast_forward) = &self.fast_forward {
+            audio = self.turbo_audio.fit(fast_forward, audio);
+        }
+
+        Ok(FrameRun {
+            pixels: output.load(),
+            audio,
+        })
+    }
+
+    /// Runs for `cycles` bus cycles and returns how many were run. An instruction isn't
+    /// cut in the middle: the cycles run past `cycles` are taken from the next call, so
+    /// that consecutive calls add up exactly. Breakpoints don't stop the run, a pause does:
+    /// the cycles left aren't taken from the next call then.
+    ///
+    /// # Errors
+    /// It stops early if a frame lasts far longer than on hardware, see
+    /// [`Self::set_frame_guard`].
+    pub fn run_cycles(&mut self, cycles: u64) -> Result<u128, FrameOverrun> {
+        let start = self.cycles();
+        let target = start + u128::from(cycles).saturating_sub(self.cycles_ahead);
+        while self.cycles() < target {
+            if self.execution == ExecutionState::Paused {
+                self.cycles_ahead = 0;
+                return Ok(self.cycles() - start);
+            }
+            self.step();
+
+            if let Some(overrun) = self.take_frame_overrun() {
+                self.cycles_ahead = 0;
+                return Err(overrun);
+            }
+        }
+        self.cycles_ahead =
+            (self.cycles() - target) + self.cycles_ahead.saturating_sub(cycles.into());
+
+        Ok(self.cycles() - start)
+    }
+
+    /// A frame lasting more than `factor` times its length on hardware, in cycles or in
+    /// steps, is reported once as a [`FrameOverrun`] with the last executed instructions,
+    /// and a notification. `None` disables the guard, the default factor is
+    /// [`DEFAULT_OVERRUN_FACTOR`](crate::frame_guard::DEFAULT_OVERRUN_FACTOR).
+    pub fn set_frame_guard(&mut self, factor: Option<u32>) {
+        self.frame_guard = FrameGuard::new(factor);
+    }
+
+    #[must_use]
+    pub const fn frame_guard(&self) -> Option<u32> {
+        self.frame_guard.factor()
+    }
+
+    /// Returns the pending [`FrameOverrun`], if any, clearing it. The frontend stops
+    /// running the emulation on one: the game won't recover.
+    pub const fn take_frame_overrun(&mut self) -> Option<FrameOverrun> {
+        self.frame_overrun.take()
+    }
+
+    /// Leaves the CPU and the hardware as the BIOS does after its boot animation, see
+    /// [`EmuConfig::skip_bios`]: the soft reset of the BIOS starts the game the same way,
+    /// the boot also sets the post boot flag.
+    fn skip_bios(&mut self) {
+        self.cpu.soft_reset();
+        self.cpu.bus.write_raw(POST_BOOT_FLAG, 1);
+    }
+
+    /// Restarts the game without recreating the emulator: the handles given to the
+    /// frontend keep working.
+    pub fn reset(&mut self, kind: ResetKind) {
+        match kind {
+            ResetKind::Soft => self.cpu.soft_reset(),
+            ResetKind::Restart => self.cpu.reset(false),
+            ResetKind::Hard => self.cpu.reset(true),
+        }
+        if kind != ResetKind::Soft && self.config.skip_bios {
+            self.skip_bios();
+        }
+        #[cfg(feature = "serde")]
+        if let Some(history) = &mut self.step_history {
+            history.clear();
+        }
+        #[cfg(feature = "serde")]
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+        self.frame_guard.restart();
+        self.frame_overrun = None;
+        self.frame_pacing.restart();
+        self.cycles_ahead = 0;
+
+        let message = match kind {
+            ResetKind::Soft => "Soft reset",
+            ResetKind::Restart => "Reset",
+            ResetKind::Hard => "Hard reset",
+        };
+        self.notify(Notification::info(NotificationKind::Reset, message));
+    }
+
+    /// Calls `callback` on every following event of `kind` until [`Self::unsubscribe`],
+    /// see [`events`](crate::events).
+    pub fn subscribe(
+        &mut self,
+        kind: EventKind,
+        callback: impl FnMut(&Event) + Send + 'static,
+    ) -> SubscriptionId {
+        let id = self.events.subscribe(kind, Box::new(callback));
+        self.cpu
+            .bus
+            .set_event_recording(self.events.listens_to_hardware());
+
+        id
+    }
+
+    /// Removes the callback, `false` if it was already removed.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let removed = self.events.unsubscribe(id);
+        self.cpu
+            .bus
+            .set_event_recording(self.events.listens_to_hardware());
+
+        removed
+    }
+
+    /// Queues a message for the user, for events the frontend handles itself such as a
+    /// change of the emulation speed.
+    pub fn notify(&mut self, notification: Notification) {
+        self.notifications.push(notification);
+    }
+
+    /// Messages for the user since the last call, oldest first.
+    pub fn take_notifications(&mut self) -> Vec<Notification> {
+        let save_written = self
+            .cpu
+            .bus
+            .internal_memory
+            .flash
+            .as_mut()
+            .is_some_and(Flash::take_written);
+        if save_written {
+            self.notify(Notification::info(
+                NotificationKind::SaveDataWritten,
+                "Save data written",
+            ));
+            self.store_save_profile();
+        }
+        self.store_clock();
+
+        self.notifications.drain().collect()
+    }
+
+    /// Keeps writing `value` at `address`, see [`Bus::freeze`].
+    ///
+    /// # Errors
+    /// It fails if the address can't be written.
+    pub fn freeze(&mut self, address: u32, value: EditValue) -> Result<(), MemoryEditError> {
+        self.cpu.bus.freeze(address, value)?;
+        self.notify(Notification::info(
+            NotificationKind::CheatToggled,
+            format!("Cheat on at 0x{address:08X}"),
+        ));
+
+        Ok(())
+    }
+
+    /// Stops a value frozen with [`Self::freeze`], returning it.
+    pub fn unfreeze(&mut self, address: u32) -> Option<EditValue> {
+        let value = self.cpu.bus.unfreeze(address)?;
+        self.notify(Notification::info(
+            NotificationKind::CheatToggled,
+            format!("Cheat off at 0x{address:08X}"),
+        ));
+
+        Some(value)
+    }
+
+    /// Runs `cheat` at every Vblank from now on, see [`cheats`](crate::cheats). A warning
+    /// tells when its codes were made for another game, it's added all the same.
+    pub fn add_cheat(&mut self, cheat: Cheat) {
+        if !cheat.made_for(&self.cpu.bus.internal_memory.rom) {
+            self.notify(Notification::warning(
+                NotificationKind::CheatToggled,
+                format!("\"{}\" seems made for another game", cheat.description),
+            ));
+        }
+        self.cpu.bus.cheats_mut().push(cheat);
+    }
+
+    #[must_use]
+    pub fn cheats(&self) -> &[Cheat] {
+        self.cpu.bus.cheats()
+    }
+
+    /// Turns the cheat at `index` on or off, `false` if there's none.
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        let Some(cheat) = self.cpu.bus.cheats_mut().get_mut(index) else {
+            return false;
+        };
+        cheat.enabled = enabled;
+
+        let message = format!(
+            "Cheat {}: {}",
+            if enabled { "on" } else { "off" },
+            cheat.description
+        );
+        self.notify(Notification::info(NotificationKind::CheatToggled, message));
+
+        true
+    }
+
+    pub fn remove_cheat(&mut self, index: usize) -> Option<Cheat> {
+        let cheats = self.cpu.bus.cheats_mut();
+        (index < cheats.len()).then(|| cheats.remove(index))
+    }
+
+    /// Bus cycles since power-on or the last reset. I/O trace events and execution
+    /// traps are timestamped with the same counter.
+    #[must_use]
+    pub const fn cycles(&self) -> u128 {
+        self.cpu.bus.cycles()
+    }
+
+    /// Time [`Self::cycles`] last on hardware.
+    #[must_use]
+    pub fn emulated_time(&self) -> Duration {
+        clock::cycles_to_duration(self.cycles())
+    }
+
+    /// Current cycle count paired with the host clock. Comparing two samples gives the
+    /// emulation speed over that span, see [`ClockSample::speed_since`].
+    #[must_use]
+    pub fn clock_sample(&self) -> ClockSample {
+        ClockSample {
+            cycles: self.cycles(),
+            host: Instant::now(),
+        }
+    }
+
+    /// Host time between the last completed frames, see [`FramePacing`]. `None` until
+    /// two frames were completed since the emulation was created, reset or resumed.
+    #[must_use]
+    pub fn frame_pacing(&self) -> Option<FramePacingStats> {
+        self.frame_pacing.stats()
+    }
+
+    /// Cycles of the CPU and of each DMA channel during the last complete frame, and the
+    /// longest the CPU waited for the transfers, see [`BusMasters`].
+    #[must_use]
+    pub const fn bus_masters(&self) -> BusMasters {
+        self.cpu.bus.bus_masters()
+    }
+
+    /// Stops [`Self::run_frame`] and [`Self::run_cycles`] until [`Self::resume`] or
+    /// [`Self::frame_advance`], for the frontend and the movie tools alike.
+    pub const fn pause(&mut self) {
+        self.execution = ExecutionState::Paused;
+    }
+
+    pub fn resume(&mut self) {
+        if self.execution != ExecutionState::Running {
+            self.execution = ExecutionState::Running;
+            self.frame_pacing.restart();
+        }
+    }
+
+    /// Runs to the end of the current frame, then pauses.
+    pub fn frame_advance(&mut self) {
+        self.execution = ExecutionState::FrameStep;
+        self.frame_pacing.restart();
+    }
+
+    #[must_use]
+    pub const fn execution_state(&self) -> ExecutionState {
+        self.execution
+    }
+
+    /// To be called when the emulation resumes after a pause, that isn't a frame
+    /// lasting longer.
+    pub fn restart_frame_pacing(&mut self) {
+        self.frame_pacing.restart();
+    }
+
+    /// Handle to press and release the buttons, it can be updated without locking the
+    /// emulator.
+    #[must_use]
+    pub fn keypad_input(&self) -> KeypadInput {
+        self.cpu.bus.keypad_input()
+    }
+
+    /// Plays `input_macro` from now on, in place of the one playing, see
+    /// [`input_macro`](crate::input_macro).
+    pub fn play_macro(&mut self, input_macro: InputMacro) {
+        self.stop_macro();
+        self.input_macro = Some(MacroPlayer::start(input_macro, &self.keypad_input()));
+    }
+
+    /// Stops the macro playing, releasing the keys it pressed.
+    pub fn stop_macro(&mut self) {
+        if let Some(mut player) = self.input_macro.take() {
+            player.stop(&self.keypad_input());
+        }
+    }
+
+    #[must_use]
+    pub const fn macro_playing(&self) -> bool {
+        self.input_macro.is_some()
+    }
+
+    fn next_macro_frame(&mut self) {
+        let input = self.keypad_input();
+        if let Some(player) = &mut self.input_macro {
+            if player.next_frame(&input) {
+                self.input_macro = None;
+            }
+        }
+    }
+
+    /// Chooses between latching the buttons once per frame (the default, deterministic)
+    /// and sampling them on every read (lower latency).
+    pub const fn set_key_sampling(&mut self, sampling: KeySampling) {
+        self.config.key_sampling = sampling;
+        self.cpu.bus.set_key_sampling(sampling);
+    }
+
+    /// Chooses between completing Flash erase and program commands right away (the
+    /// default) and keeping the chip busy as long as real ones, for games that poll it.
+    pub const fn set_flash_timing(&mut self, timing: FlashTiming) {
+        self.config.flash_timing = timing;
+        self.cpu.bus.set_flash_timing(timing);
+    }
+
+    /// Replaces the backup memory of the cartridge with a `.sav` file, its layout is
+    /// detected from its size, see [`battery_save::import`].
+    ///
+    /// # Errors
+    /// It fails if the cartridge has no backup memory or the file doesn't fit it.
+    pub fn import_save(&mut self, file: &[u8]) -> Result<SaveLayout, BatterySaveError> {
+        let flash = self
+            .cpu
+            .bus
+            .internal_memory
+            .flash
+            .as_mut()
+            .ok_or(BatterySaveError::NoBackupMemory)?;
+
+        let imported = battery_save::import(file, flash.data().len())?;
+        flash.load_data(&imported.data);
+
+        self.notify(Notification::info(
+            NotificationKind::SaveImported,
+            format!("Save imported ({})", imported.layout),
+        ));
+
+        Ok(imported.layout)
+    }
+
+    /// Content of the backup memory of the cartridge as a `.sav` file in `layout`,
+    /// `None` if there is no backup memory.
+    #[must_use]
+    pub fn export_save(&self, layout: SaveLayout) -> Option<Vec<u8>> {
+        self.cpu
+            .bus
+            .internal_memory
+            .flash
+            .as_ref()
+            .map(|flash| battery_save::export(flash.data(), layout))
+    }
+
+    /// Plugs add-on hardware in the cartridge, see [`CartridgePeripheral`].
+    pub fn add_cartridge_peripheral(&mut self, peripheral: Box<dyn CartridgePeripheral>) {
+        self.cpu.bus.internal_memory.add_peripheral(peripheral);
+    }
+
+    /// Makes the accesses to unmapped addresses raise aborts, for debugging homebrew, see
+    /// [`Arm7tdmi::set_abort_on_invalid_access`].
+    pub const fn set_abort_on_invalid_access(&mut self, enabled: bool) {
+        self.config.abort_on_invalid_access = enabled;
+        self.cpu.set_abort_on_invalid_access(enabled);
+    }
+
+    /// Runs the emulated BIOS functions in place of the BIOS code, for games run without a
+    /// BIOS dump, see [`Arm7tdmi::set_bios_hle`].
+    pub const fn set_bios_hle(&mut self, enabled: bool) {
+        self.config.bios_hle = enabled;
+        self.cpu.set_bios_hle(enabled);
+    }
+
+    /// Takes no shortcut for speed, for timing bug reports: every SWI runs the BIOS code
+    /// even with [`Self::set_bios_hle`], see [`Arm7tdmi::set_max_accuracy`].
+    pub const fn set_max_accuracy(&mut self, enabled: bool) {
+        self.config.max_accuracy = enabled;
+        self.cpu.set_max_accuracy(enabled);
+    }
+
+    /// The BIOS functions emulated in Rust during the session, see
+    /// [`Arm7tdmi::hle_shortcuts`].
+    #[must_use]
+    pub fn hle_shortcuts(&self) -> Vec<HleShortcut> {
+        self.cpu.hle_shortcuts()
+    }
+
+    /// Counters of the compiled blocks, to diagnose the games defeating the cache, see
+    /// [`Arm7tdmi::jit_stats`].
+    #[cfg(feature = "jit")]
+    #[must_use]
+    pub const fn jit_stats(&self) -> JitStats {
+        self.cpu.jit_stats()
+    }
+
+    /// Runs the CPU faster than the hardware to remove the slowdown of some games, see
+    /// [`Overclock`].
+    pub const fn set_overclock(&mut self, overclock: Overclock) {
+        self.config.overclock = overclock;
+        self.cpu.bus.set_overclock(overclock);
+    }
+
+    /// Skips drawing frames and fits the sound to the host time while the frontend runs
+    /// the emulation at `fast_forward.speed`, see [`fast_forward`](crate::fast_forward).
+    /// `None` goes back to drawing every frame with the whole sound.
+    pub fn set_fast_forward(&mut self, fast_forward: Option<FastForward>) {
+        self.cpu
+            .bus
+            .lcd
+            .set_frame_skip(fast_forward.map_or(0, |fast_forward| fast_forward.frame_skip));
+        self.fast_forward = fast_forward;
+        self.turbo_audio = TurboAudioFit::default();
+    }
+
+    #[must_use]
+    pub const fn fast_forward(&self) -> Option<&FastForward> {
+        self.fast_forward.as_ref()
+    }
+
+    /// The options the emulator was created with, as changed since.
+    #[must_use]
+    pub const fn config(&self) -> &EmuConfig {
+        &self.config
+    }
+
+    /// Chooses between the filters of the hardware output (the default) and the mix as
+    /// it is, `sample_rate` being the rate the frontend mixes at.
+    pub fn set_output_filter(&mut self, settings: FilterSettings, sample_rate: u32) {
+        self.cpu.bus.set_output_filter(settings, sample_rate);
+    }
+
+    /// Chooses how many audio samples [`Self::run_frame`] returns, 549 by default. The
+    /// output filters follow the new rate, see [`Self::audio_sample_rate`].
+    pub fn set_audio_samples_per_frame(&mut self, samples_per_frame: u32) {
+        self.config.audio_samples_per_frame = Some(samples_per_frame);
+        self.cpu.bus.set_audio_samples_per_frame(samples_per_frame);
+    }
+
+    #[must_use]
+    pub const fn audio_samples_per_frame(&self) -> u32 {
+        self.cpu.bus.audio_samples_per_frame()
+    }
+
+    /// Rate of the audio at full speed: the samples per frame over the length of a frame.
+    #[must_use]
+    pub const fn audio_sample_rate(&self) -> u32 {
+        self.cpu.bus.audio_sample_rate()
+    }
+
+    /// Handle to the completed frames, it can be read without locking the emulator.
+    #[must_use]
+    pub fn frame_output(&self) -> FrameOutput {
+        self.cpu.bus.lcd.frame_output()
+    }
+
+    /// Bytes taken by each subsystem, see [`memory_budget`](crate::memory_budget).
+    #[must_use]
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let memory = &self.cpu.bus.internal_memory;
+        let video = &self.cpu.bus.lcd.memory;
+
+        MemoryUsage {
+            rom: memory.rom.len(),
+            system: memory.system_bytes(),
+            video: video.bg_palette_ram.len()
+                + video.obj_palette_ram.len()
+                + video.video_ram.len()
+                + video.obj_attributes.len(),
+            frames: 2 * std::mem::size_of::<Frame>(),
+            save: memory.flash.as_ref().map_or(0, |flash| flash.data().len()),
+            #[cfg(feature = "serde")]
+            step_history: self
+                .step_history
+                .as_ref()
+                .map_or(0, StepHistory::memory_usage),
+            #[cfg(not(feature = "serde"))]
+            step_history: 0,
+            #[cfg(feature = "serde")]
+            rewind: self.rewind.as_ref().map_or(0, RewindBuffer::memory_usage),
+            #[cfg(not(feature = "serde"))]
+            rewind: 0,
+        }
+    }
+
+    /// The header, save type and hardware of the cartridge, and what the emulated
+    /// cartridge is built with.
+    #[must_use]
+    pub fn cartridge_info(&self) -> CartridgeInfo {
+        let memory = &self.cpu.bus.internal_memory;
+        let rom = &memory.rom;
+        let header_checksum_valid =
+            rom.len() > 0xBD && CartridgeHeader::compute_complement_check(rom) == rom[0xBD];
+
+        CartridgeInfo {
+            header: self.cartridge_header.clone(),
+            rom_size: rom.len(),
+            header_checksum_valid,
+            fixed_value_valid: self.cartridge_header.fixed_value == [0x96],
+            save_type: SaveType::detect(rom),
+            hardware: CartridgeHardware::detect(rom, &self.cartridge_header.game_code),
+            flash: memory.flash.as_ref().map(Flash::size),
+            peripherals: memory
+                .peripherals
+                .iter()
+                .map(|peripheral| peripheral.name())
+                .collect(),
+        }
+    }
+
+    /// Summary of the session to attach to bug reports. `running_time` is how long the
+    /// frontend let the emulation run, pauses excluded.
+    #[must_use]
+    pub fn run_report(&self, running_time: Duration) -> RunReport {
+        RunReport {
+            game_title: self
+                .cartridge_header
+                .game_title
+                .trim_end_matches('\0')
+                .to_string(),
+            game_code: self.cartridge_header.game_code.clone(),
+            frames: self.frame_output().frame_count(),
+            running_time,
+            unmapped_io_writes: self.cpu.bus.unmapped_io_writes(),
+            hle_shortcuts: self.cpu.hle_shortcuts(),
+            execution_traps: self.cpu.trap_log().to_vec(),
+            misaligned_pc_count: self.cpu.misaligned_pc_count(),
+            first_misaligned_pc: self.cpu.first_misaligned_pc(),
+        }
+    }
+
+    /// Records every following executed instruction in `trace`, see
+    /// [`Arm7tdmi::set_cpu_trace`].
+    #[cfg(feature = "debug-hooks")]
+    pub fn set_cpu_trace(
+        &mut self,
+        trace: Option<Box<dyn CpuTraceWriter>>,
+    ) -> Option<Box<dyn CpuTraceWriter>> {
+        self.cpu.set_cpu_trace(trace)
+    }
+
+    /// Records every following I/O register access in `trace`, see [`Bus::set_io_trace`].
+    #[cfg(feature = "debug-hooks")]
+    pub fn set_io_trace(
+        &mut self,
+        trace: Option<Box<dyn IoTraceWriter>>,
+    ) -> Option<Box<dyn IoTraceWriter>> {
+        self.cpu.bus.set_io_trace(trace)
+    }
+
+    /// Logs the writes to the LCD registers with their raster position, see
+    /// [`Bus::set_ppu_write_log`].
+    #[cfg(feature = "debug-hooks")]
+    pub const fn set_ppu_write_log(&mut self, enabled: bool) {
+        self.cpu.bus.set_ppu_write_log(enabled);
+    }
+
+    #[cfg(feature = "debug-hooks")]
+    #[must_use]
+    pub const fn ppu_write_log(&self) -> bool {
+        self.cpu.bus.ppu_write_log()
+    }
+
+    /// Serializes the emulator state into a checksummed save-state.
+    ///
+    /// # Errors
+    /// It fails if one of the components can't be serialized.
+    #[cfg(feature = "serde")]
+    pub fn save_state(&mut self) -> Result<Vec<u8>, SaveStateError> {
+        self.save_state_with(&[])
+    }
+
+    /// Like [`Self::save_state`], with data of the frontend's tools such as a memory
+    /// search. They come back in the [`LoadReport`] of [`Self::load_state`].
+    ///
+    /// # Errors
+    /// It fails if one of the components can't be serialized, or an attachment has an
+    /// invalid name.
+    #[cfg(feature = "serde")]
+    pub fn save_state_with(
+        &mut self,
+        attachments: &[Attachment],
+    ) -> Result<Vec<u8>, SaveStateError> {
+        let data = save_state::encode_with(&self.cpu, attachments)?;
+        self.notify(Notification::info(
+            NotificationKind::StateSaved,
+            "State saved",
+        ));
+
+        Ok(data)
+    }
+
+    /// Loads a save-state, restoring every section that passes its integrity check.
+    /// The returned report lists the damaged sections that were left untouched.
+    ///
+    /// # Errors
+    /// It fails if `data` is not a save-state or its version is not supported.
+    #[cfg(feature = "serde")]
+    pub fn load_state(&mut self, data: &[u8]) -> Result<LoadReport, SaveStateError> {
+        let report = self.restore_state(data)?;
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+        self.notify(if report.is_complete() {
+            Notification::info(NotificationKind::StateLoaded, "State loaded")
+        } else {
+            Notification::warning(NotificationKind::StateLoaded, report.to_string())
+        });
+
+        Ok(report)
+    }
+
+    /// Decodes `data` into the CPU and restarts what measures the running emulation.
+    #[cfg(feature = "serde")]
+    fn restore_state(&mut self, data: &[u8]) -> Result<LoadReport, SaveStateError> {
+        let report = save_state::decode(&mut self.cpu, data)?;
+        if let Some(history) = &mut self.step_history {
+            history.clear();
+        }
+        self.frame_guard.restart();
+        self.frame_overrun = None;
+        self.frame_pacing.restart();
+        self.cycles_ahead = 0;
+
+        Ok(report)
+    }
+
+    /// Takes a snapshot every few frames to go back with [`Self::rewind`], or stops
+    /// with `None`. Nothing is taken with [`MemoryProfile::Bounded`].
+    #[cfg(feature = "serde")]
+    pub fn set_rewind(&mut self, buffer: Option<RewindBuffer>) {
+        self.rewind = buffer.filter(|_| self.config.memory_profile == MemoryProfile::Standard);
+    }
+
+    #[cfg(feature = "serde")]
+    #[must_use]
+    pub const fn rewind_buffer(&self) -> Option<&RewindBuffer> {
+        self.rewind.as_ref()
+    }
+
+    /// Goes back to the newest snapshot, [`RewindBuffer::interval`] frames at most. The
+    /// frontend calls it once per frame while the rewind key is held, running a frame
+    /// after each call to show the restored state.
+    ///
+    /// # Errors
+    /// It fails if the snapshots aren't taken or were all gone back to already.
+    #[cfg(feature = "serde")]
+    pub fn rewind(&mut self) -> Result<(), RewindError> {
+        let state = self
+            .rewind
+            .as_mut()
+            .ok_or(RewindError::Disabled)?
+            .pop()
+            .ok_or(RewindError::Empty)?;
+        self.restore_state(&state)?;
+
+        Ok(())
+    }
+
+    /// Records the keys latched on every frame from `start`, see
+    /// [`movie`](crate::movie). A movie recording or playing is stopped.
+    ///
+    /// # Errors
+    /// It fails if the state can't be serialized, when starting from it.
+    #[cfg(feature = "serde")]
+    pub fn start_recording(&mut self, start: MovieStart) -> Result<(), MovieError> {
+        self.stop_movie();
+        let anchor = match start {
+            MovieStart::PowerOn => {
+                self.reset(ResetKind::Hard);
+                MovieAnchor::PowerOn
+            }
+            MovieStart::Now => MovieAnchor::SaveState(save_state::encode(&self.cpu)?),
+        };
+
+        let memory = &self.cpu.bus.internal_memory;
+        let movie = Movie::new(&memory.rom, memory.bios(), anchor);
+        self.movie = Some(MovieSession::Recording(movie));
+        self.notify(Notification::info(
+            NotificationKind::Movie,
+            "Recording movie",
+        ));
+
+        Ok(())
+    }
+
+    /// Stops the recording and returns the movie.
+    ///
+    /// # Errors
+    /// It fails if no movie is being recorded.
+    #[cfg(feature = "serde")]
+    pub fn stop_recording(&mut self) -> Result<Movie, MovieError> {
+        match self.movie.take() {
+            Some(MovieSession::Recording(movie)) => Ok(movie),
+            session => {
+                self.movie = session;
+                Err(MovieError::NotRecording)
+            }
+        }
+    }
+
+    /// Goes back to the start of `movie` and plays its keys in place of the host input,
+    /// one frame at each Vblank. A movie recording or playing is stopped.
+    ///
+    /// # Errors
+    /// It fails if the movie was recorded with another ROM or BIOS, or its save-state
+    /// can't be loaded. The emulator is untouched in these cases.
+    #[cfg(feature = "serde")]
+    pub fn play_movie(&mut self, movie: &Movie) -> Result<(), MovieError> {
+        let memory = &self.cpu.bus.internal_memory;
+        movie.check(&memory.rom, memory.bios())?;
+
+        if let MovieAnchor::SaveState(state) = &movie.anchor {
+            save_state::decode(&mut Arm7tdmi::default(), state)?;
+        }
+        self.stop_movie();
+        match &movie.anchor {
+            MovieAnchor::PowerOn => self.reset(ResetKind::Hard),
+            MovieAnchor::SaveState(state) => {
+                self.restore_state(state)?;
+            }
+        }
+
+        let session = MovieSession::Playing {
+            movie: movie.clone(),
+            next: 0,
+        };
+        self.cpu.bus.set_forced_keys(session.forced_keys());
+        self.movie = Some(session);
+        self.notify(Notification::info(NotificationKind::Movie, "Playing movie"));
+
+        Ok(())
+    }
+
+    /// Stops recording or playing the movie, the host input is latched again. A movie
+    /// recorded is lost, see [`Self::stop_recording`].
+    #[cfg(feature = "serde")]
+    pub fn stop_movie(&mut self) {
+        self.movie = None;
+        self.cpu.bus.set_forced_keys(None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[must_use]
+    pub fn movie_status(&self) -> MovieStatus {
+        self.movie
+            .as_ref()
+            .map_or(MovieStatus::Idle, MovieSession::status)
+    }
+
+    /// Records or plays the keys of the Vblank just latched.
+    #[cfg(feature = "serde")]
+    fn next_movie_frame(&mut self) {
+        let latched = self.cpu.bus.latched_keys();
+        let Some(session) = &mut self.movie else {
+            return;
+        };
+
+        if session.next_frame(latched) {
+            self.stop_movie();
+            self.notify(Notification::info(NotificationKind::Movie, "Movie over"));
+        } else {
+            let keys = session.forced_keys();
+            self.cpu.bus.set_forced_keys(keys);
+        }
+    }
+
+    /// Saves a state to `path`, see [`save_state::write_file`].
+    ///
+    /// # Errors
+    /// It fails if a component can't be serialized or the file can't be written.
+    #[cfg(feature = "serde")]
+    pub fn save_state_to_file(&mut self, path: &std::path::Path) -> Result<(), SaveStateError> {
+        let data = self.save_state()?;
+        save_state::write_file(path, &data)
     }
 
-    pub fn step(&mut self) {
-        self.cpu.step();
+    /// Loads the state saved at `path`, see [`Self::load_state`].
+    ///
+    /// # Errors
+    /// It fails if the file can't be read, isn't a save-state or its version is not
+    /// supported.
+    #[cfg(feature = "serde")]
+    pub fn load_state_from_file(
+        &mut self,
+        path: &std::path::Path,
+    ) -> Result<LoadReport, SaveStateError> {
+        let data = save_state::read_file(path)?;
+        self.load_state(&data)
     }
 }
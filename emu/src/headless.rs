@@ -0,0 +1,120 @@
+//! Runs a game without a window for a number of frames, for CI jobs and servers: the
+//! exit status tells how the run went and the last frame can be saved as a screenshot.
+//!
+//! ```no_run
+//! use emu::{cartridge_header::CartridgeHeader, gba::Gba, headless};
+//!
+//! let bios = std::fs::read("gba_bios.bin").unwrap().try_into().unwrap();
+//! let rom = std::fs::read("game.gba").unwrap();
+//! let mut gba = Gba::new(CartridgeHeader::new(&rom).unwrap(), bios, rom);
+//!
+//! let options = headless::HeadlessOptions {
+//!     frames: 600,
+//!     screenshot: Some("last.ppm".into()),
+//!     ..Default::default()
+//! };
+//! let run = headless::run(&mut gba, &options).unwrap();
+//! std::process::exit(run.exit.code());
+//! ```
+
+use std::{fmt, fs, io, path::PathBuf, sync::Arc};
+
+use crate::{
+    capture::encode_ppm,
+    cpu::{execution_trap::ExecutionTrap, hardware::lcd::Frame},
+    frame_guard::FrameOverrun,
+    gba::Gba,
+    input_macro::InputMacro,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct HeadlessOptions {
+    pub frames: u64,
+    /// Where to write the last frame as a PPM image, see [`encode_ppm`].
+    pub screenshot: Option<PathBuf>,
+    /// Key presses played from the first frame.
+    pub input: Option<InputMacro>,
+}
+
+/// How a headless run ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HeadlessExit {
+    /// Every requested frame was emulated.
+    Completed,
+    /// A frame lasted far longer than on hardware, see
+    /// [`Gba::set_frame_guard`](crate::gba::Gba::set_frame_guard).
+    Stuck(FrameOverrun),
+    /// The CPU executed from a region it never runs code from.
+    Trapped(ExecutionTrap),
+}
+
+impl HeadlessExit {
+    /// Exit status of the process: 0 when completed, 3 when stuck and 4 when trapped.
+    /// 1 and 2 are left to invalid arguments and unreadable files.
+    #[must_use]
+    pub const fn code(&self) -> i32 {
+        match self {
+            Self::Completed => 0,
+            Self::Stuck(_) => 3,
+            Self::Trapped(_) => 4,
+        }
+    }
+}
+
+impl fmt::Display for HeadlessExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Completed => write!(f, "completed"),
+            Self::Stuck(overrun) => write!(f, "stuck: {overrun}"),
+            Self::Trapped(trap) => write!(f, "trapped: {trap}"),
+        }
+    }
+}
+
+pub struct HeadlessRun {
+    /// Frames completed before stopping.
+    pub frames: u64,
+    pub exit: HeadlessExit,
+    /// The last completed frame.
+    pub pixels: Arc<Frame>,
+}
+
+/// Runs `gba` for the frames of `options`, stopping early when it gets stuck or
+/// trapped. The screenshot is written in every case.
+///
+/// # Errors
+/// It fails if the screenshot can't be written, the run is over then.
+pub fn run(gba: &mut Gba, options: &HeadlessOptions) -> io::Result<HeadlessRun> {
+    if let Some(input) = &options.input {
+        gba.play_macro(input.clone());
+    }
+
+    let output = gba.frame_output();
+    let start = output.frame_count();
+    let mut exit = HeadlessExit::Completed;
+    // Stepped rather than run by frames: the CPU must stop right at a trap, before it
+    // runs the garbage it jumped to.
+    while output.frame_count() - start < options.frames {
+        gba.step();
+
+        if let Some(trap) = gba.cpu.take_execution_trap() {
+            exit = HeadlessExit::Trapped(trap);
+            break;
+        }
+        if let Some(overrun) = gba.take_frame_overrun() {
+            exit = HeadlessExit::Stuck(overrun);
+            break;
+        }
+    }
+
+    let pixels = output.load();
+    if let Some(path) = &options.screenshot {
+        fs::write(path, encode_ppm(&pixels))?;
+    }
+
+    Ok(HeadlessRun {
+        frames: output.frame_count() - start,
+        exit,
+        pixels,
+    })
+}
@@ -0,0 +1,273 @@
+//! Scripted key presses timed in emulated frames, to get through the menus of a game in
+//! an integration test or to repeat an action without holding the buttons.
+//!
+//! A macro is built step by step or parsed from a line of text, then played with
+//! [`Gba::play_macro`](crate::gba::Gba::play_macro). The keys go through the same
+//! [`KeypadInput`] as the frontend's: the game sees them at the Vblank latch like any
+//! other press, and the keys the user holds meanwhile aren't touched.
+//!
+//! ```
+//! use emu::cpu::hardware::keypad::Key;
+//! use emu::input_macro::InputMacro;
+//!
+//! // Press A for 2 frames, wait 10, hold Right for 30 frames with B pressed once.
+//! let text: InputMacro = "A*2 wait*10 +Right wait*29 B -Right".parse().unwrap();
+//! let built = InputMacro::new()
+//!     .press(&[Key::A], 2)
+//!     .wait(10)
+//!     .hold(&[Key::Right])
+//!     .wait(29)
+//!     .press(&[Key::B], 1)
+//!     .release(&[Key::Right]);
+//! assert_eq!(text, built);
+//! ```
+
+use std::{fmt, str::FromStr};
+
+use crate::cpu::hardware::keypad::{Key, KeypadInput};
+
+/// Keys of a step, one bit per [`Key`].
+type Keys = u16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    /// Keys pressed for some frames, then released.
+    Press(Keys, u32),
+    Wait(u32),
+    /// Keys pressed until released, or until the macro ends.
+    Hold(Keys),
+    Release(Keys),
+}
+
+/// A sequence of key presses, see the [module](self) documentation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputMacro {
+    steps: Vec<Step>,
+}
+
+fn mask(keys: &[Key]) -> Keys {
+    keys.iter().fold(0, |mask, key| mask | key.mask())
+}
+
+impl InputMacro {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { steps: Vec::new() }
+    }
+
+    /// Presses `keys` for `frames` frames, then releases them.
+    #[must_use]
+    pub fn press(mut self, keys: &[Key], frames: u32) -> Self {
+        self.steps.push(Step::Press(mask(keys), frames));
+        self
+    }
+
+    #[must_use]
+    pub fn wait(mut self, frames: u32) -> Self {
+        self.steps.push(Step::Wait(frames));
+        self
+    }
+
+    /// Presses `keys` until [`Self::release`], the following steps run meanwhile.
+    #[must_use]
+    pub fn hold(mut self, keys: &[Key]) -> Self {
+        self.steps.push(Step::Hold(mask(keys)));
+        self
+    }
+
+    #[must_use]
+    pub fn release(mut self, keys: &[Key]) -> Self {
+        self.steps.push(Step::Release(mask(keys)));
+        self
+    }
+
+    /// Frames the macro lasts.
+    #[must_use]
+    pub fn frames(&self) -> u64 {
+        self.steps
+            .iter()
+            .map(|step| match step {
+                Step::Press(_, frames) | Step::Wait(frames) => u64::from(*frames),
+                Step::Hold(_) | Step::Release(_) => 0,
+            })
+            .sum()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MacroParseError {
+    UnknownKey(String),
+    InvalidFrames(String),
+}
+
+impl fmt::Display for MacroParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownKey(key) => write!(f, "unknown key `{key}`"),
+            Self::InvalidFrames(step) => write!(f, "invalid frame count in `{step}`"),
+        }
+    }
+}
+
+impl std::error::Error for MacroParseError {}
+
+/// Steps separated by spaces: `A+B*3` presses A and B for 3 frames (1 without `*`),
+/// `wait*10` waits 10 frames, `+Right` holds Right and `-Right` releases it. Key names
+/// are those of [`Key`], in any case.
+impl FromStr for InputMacro {
+    type Err = MacroParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let keys = |names: &str| {
+            names.split('+').try_fold(0, |mask, name| {
+                Key::ALL
+                    .into_iter()
+                    .find(|key| format!("{key:?}").eq_ignore_ascii_case(name))
+                    .map(|key| mask | key.mask())
+                    .ok_or_else(|| MacroParseError::UnknownKey(name.to_string()))
+            })
+        };
+
+        let steps = text
+            .split_whitespace()
+            .map(|step| {
+                if let Some(names) = step.strip_prefix('+') {
+                    return Ok(Step::Hold(keys(names)?));
+                }
+                if let Some(names) = step.strip_prefix('-') {
+                    return Ok(Step::Release(keys(names)?));
+                }
+
+                let (action, frames) = step.split_once('*').unwrap_or((step, "1"));
+                let frames = frames
+                    .parse()
+                    .map_err(|_| MacroParseError::InvalidFrames(step.to_string()))?;
+                if action.eq_ignore_ascii_case("wait") {
+                    Ok(Step::Wait(frames))
+                } else {
+                    Ok(Step::Press(keys(action)?, frames))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { steps })
+    }
+}
+
+/// A macro being played: it goes on at the start of each frame.
+pub(crate) struct MacroPlayer {
+    steps: Vec<Step>,
+    next: usize,
+    /// Frames left to the running press or wait.
+    frames_left: u32,
+    /// Keys of the running press.
+    pressed: Keys,
+    /// Keys of the holds not released yet.
+    held: Keys,
+}
+
+impl MacroPlayer {
+    /// Starts playing `input_macro`, its first steps are applied right away.
+    pub(crate) fn start(input_macro: InputMacro, input: &KeypadInput) -> Self {
+        let mut player = Self {
+            steps: input_macro.steps,
+            next: 0,
+            frames_left: 0,
+            pressed: 0,
+            held: 0,
+        };
+        player.run_steps(input);
+
+        player
+    }
+
+    /// Goes on at the start of a frame, returns whether the macro is over.
+    pub(crate) fn next_frame(&mut self, input: &KeypadInput) -> bool {
+        self.frames_left = self.frames_left.saturating_sub(1);
+        self.run_steps(input)
+    }
+
+    /// Applies the steps up to the next one that lasts, returns whether the macro is
+    /// over.
+    fn run_steps(&mut self, input: &KeypadInput) -> bool {
+        if self.frames_left > 0 {
+            return false;
+        }
+        set_keys(input, self.pressed & !self.held, false);
+        self.pressed = 0;
+
+        while let Some(&step) = self.steps.get(self.next) {
+            self.next += 1;
+            match step {
+                Step::Press(keys, frames) => {
+                    set_keys(input, keys, true);
+                    self.pressed = keys;
+                    self.frames_left = frames;
+                }
+                Step::Wait(frames) => self.frames_left = frames,
+                Step::Hold(keys) => {
+                    set_keys(input, keys, true);
+                    self.held |= keys;
+                }
+                Step::Release(keys) => {
+                    set_keys(input, keys, false);
+                    self.held &= !keys;
+                }
+            }
+
+            if self.frames_left > 0 {
+                return false;
+            }
+            set_keys(input, self.pressed & !self.held, false);
+            self.pressed = 0;
+        }
+
+        self.stop(input);
+        true
+    }
+
+    /// Releases the keys the macro presses.
+    pub(crate) fn stop(&mut self, input: &KeypadInput) {
+        set_keys(input, self.pressed | self.held, false);
+        self.pressed = 0;
+        self.held = 0;
+        self.next = self.steps.len();
+    }
+}
+
+fn set_keys(input: &KeypadInput, keys: Keys, pressed: bool) {
+    for key in Key::ALL {
+        if keys & key.mask() != 0 {
+            input.set_pressed(key, pressed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let parsed: InputMacro = "start wait*3 a+B*2 +up -UP".parse().unwrap();
+        assert_eq!(
+            parsed,
+            InputMacro::new()
+                .press(&[Key::Start], 1)
+                .wait(3)
+                .press(&[Key::A, Key::B], 2)
+                .hold(&[Key::Up])
+                .release(&[Key::Up])
+        );
+        assert_eq!(parsed.frames(), 6);
+
+        assert_eq!(
+            "A X".parse::<InputMacro>(),
+            Err(MacroParseError::UnknownKey("X".to_string()))
+        );
+        assert_eq!(
+            "wait*many".parse::<InputMacro>(),
+            Err(MacroParseError::InvalidFrames("wait*many".to_string()))
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! Traces of the I/O register accesses, to look at how a game drives the peripherals
+//! over time.
+//!
+//! Two writers are available: [`BinaryTrace`], compact and meant to be processed by
+//! tools (see [`decode_binary_trace`]), and [`VcdTrace`], a Value Change Dump that
+//! waveform viewers such as `GTKWave` open directly.
+//!
+//! Binary layout (varints are unsigned LEB128):
+//! ```text
+//! magic "CLIO" | version: u8
+//! repeated: cycle delta: zigzag varint | address - 0x0400_0000: varint | flags: u8 | value
+//! flags: bit 0 set on writes, bits 1-2 log2 of the width
+//! value: `width` bytes, little endian
+//! ```
+
+use std::io::{self, Write};
+
+const MAGIC: &[u8; 4] = b"CLIO";
+
+/// Version of the binary layout, bumped on incompatible changes.
+pub const FORMAT_VERSION: u8 = 1;
+
+/// First address of the I/O registers.
+pub const IO_START: u32 = 0x0400_0000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoAccessKind {
+    Read,
+    Write,
+}
+
+/// A read or a write of an I/O register done through the bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IoAccess {
+    /// Bus cycle of the access.
+    pub cycle: u128,
+    pub address: u32,
+    /// Width of the access in bytes: 1, 2 or 4.
+    pub width: u8,
+    pub value: u32,
+    pub kind: IoAccessKind,
+}
+
+impl IoAccess {
+    /// Whether `address` is routed to the I/O registers (mirrors included).
+    #[must_use]
+    pub const fn is_io(address: usize) -> bool {
+        matches!(address, 0x0400_0000..=0x04FF_FFFF)
+    }
+}
+
+/// Destination of the accesses recorded by the bus, see [`crate::bus::Bus::set_io_trace`].
+pub trait IoTraceWriter: Send {
+    /// Appends `access` to the trace.
+    ///
+    /// # Errors
+    /// It fails if the underlying writer fails, the bus stops tracing in this case.
+    fn record(&mut self, access: &IoAccess) -> io::Result<()>;
+
+    /// Flushes the underlying writer.
+    ///
+    /// # Errors
+    /// It fails if the underlying writer fails.
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// Writes accesses in the compact binary format described in the module documentation.
+pub struct BinaryTrace<W: Write> {
+    writer: W,
+    last_cycle: u128,
+}
+
+impl<W: Write> BinaryTrace<W> {
+    /// Writes the header and returns the trace.
+    ///
+    /// # Errors
+    /// It fails if the header can't be written.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[FORMAT_VERSION])?;
+
+        Ok(Self {
+            writer,
+            last_cycle: 0,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send> IoTraceWriter for BinaryTrace<W> {
+    fn record(&mut self, access: &IoAccess) -> io::Result<()> {
+        // The delta is signed: cycles go back when a save-state is loaded.
+        let delta = access.cycle.wrapping_sub(self.last_cycle) as i128;
+        self.last_cycle = access.cycle;
+
+        let mut record = Vec::with_capacity(16);
+        write_varint(&mut record, ((delta << 1) ^ (delta >> 127)) as u128);
+        write_varint(&mut record, u128::from(access.address - IO_START));
+        record.push(
+            u8::from(access.kind == IoAccessKind::Write)
+                | (access.width.trailing_zeros() as u8) << 1,
+        );
+        record.extend_from_slice(&access.value.to_le_bytes()[..usize::from(access.width)]);
+
+        self.writer.write_all(&record)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    out.push(value as u8);
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_varint(data: &mut &[u8]) -> io::Result<u128> {
+    let mut value = 0;
+    for shift in (0..128).step_by(7) {
+        let (&byte, rest) = data
+            .split_first()
+            .ok_or_else(|| invalid_data("truncated trace"))?;
+        *data = rest;
+
+        value |= u128::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(invalid_data("varint too long"))
+}
+
+/// Decodes a trace written by [`BinaryTrace`].
+///
+/// # Errors
+/// It fails if `data` isn't a trace or is truncated.
+pub fn decode_binary_trace(data: &[u8]) -> io::Result<Vec<IoAccess>> {
+    let mut data = data
+        .strip_prefix(MAGIC.as_slice())
+        .ok_or_else(|| invalid_data("not a Clementine I/O trace"))?;
+
+    match data.split_first() {
+        Some((&FORMAT_VERSION, rest)) => data = rest,
+        _ => return Err(invalid_data("unsupported I/O trace version")),
+    }
+
+    let mut accesses = Vec::new();
+    let mut cycle = 0_u128;
+    while !data.is_empty() {
+        let zigzag = read_varint(&mut data)?;
+        let delta = (zigzag >> 1) as i128 ^ -((zigzag & 1) as i128);
+        cycle = cycle.wrapping_add(delta as u128);
+
+        let offset = u32::try_from(read_varint(&mut data)?)
+            .map_err(|_| invalid_data("address out of range"))?;
+
+        let (&flags, rest) = data
+            .split_first()
+            .ok_or_else(|| invalid_data("truncated trace"))?;
+        let width = 1_u8 << (flags >> 1 & 0b11);
+        if width > 4 || rest.len() < usize::from(width) {
+            return Err(invalid_data("truncated trace"));
+        }
+
+        let (value, rest) = rest.split_at(usize::from(width));
+        let mut bytes = [0; 4];
+        bytes[..value.len()].copy_from_slice(value);
+        data = rest;
+
+        accesses.push(IoAccess {
+            cycle,
+            address: IO_START + offset,
+            width,
+            value: u32::from_le_bytes(bytes),
+            kind: if flags & 1 == 0 {
+                IoAccessKind::Read
+            } else {
+                IoAccessKind::Write
+            },
+        });
+    }
+
+    Ok(accesses)
+}
+
+/// Length of a bus cycle in tenths of nanosecond, see [`crate::clock::CPU_FREQUENCY`].
+const CYCLE_DURATION: u128 = 596;
+
+/// Writes accesses as a Value Change Dump with the `address`, `data`, `width` and `write`
+/// signals and an `access` event for every access.
+///
+/// Times can't go back in a VCD: accesses recorded after loading an older save-state
+/// keep the time of the last one.
+pub struct VcdTrace<W: Write> {
+    writer: W,
+    last_cycle: Option<u128>,
+}
+
+impl<W: Write> VcdTrace<W> {
+    /// Writes the VCD header and returns the trace.
+    ///
+    /// # Errors
+    /// It fails if the header can't be written.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "$comment Clementine I/O bus trace $end")?;
+        writeln!(writer, "$timescale 100 ps $end")?;
+        writeln!(writer, "$scope module io $end")?;
+        writeln!(writer, "$var wire 32 a address $end")?;
+        writeln!(writer, "$var wire 32 d data $end")?;
+        writeln!(writer, "$var wire 3 w width $end")?;
+        writeln!(writer, "$var wire 1 r write $end")?;
+        writeln!(writer, "$var event 1 e access $end")?;
+        writeln!(writer, "$upscope $end")?;
+        writeln!(writer, "$enddefinitions $end")?;
+
+        Ok(Self {
+            writer,
+            last_cycle: None,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send> IoTraceWriter for VcdTrace<W> {
+    fn record(&mut self, access: &IoAccess) -> io::Result<()> {
+        if self.last_cycle.is_none_or(|last| access.cycle > last) {
+            writeln!(self.writer, "#{}", access.cycle * CYCLE_DURATION)?;
+            self.last_cycle = Some(access.cycle);
+        }
+
+        writeln!(self.writer, "b{:b} a", access.address)?;
+        writeln!(self.writer, "b{:b} d", access.value)?;
+        writeln!(self.writer, "b{:b} w", access.width)?;
+        writeln!(
+            self.writer,
+            "{}r",
+            u8::from(access.kind == IoAccessKind::Write)
+        )?;
+        writeln!(self.writer, "1e")
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn accesses() -> [IoAccess; 3] {
+        [
+            IoAccess {
+                cycle: 10,
+                address: 0x0400_0000,
+                width: 2,
+                value: 0x0403,
+                kind: IoAccessKind::Write,
+            },
+            IoAccess {
+                cycle: 300,
+                address: 0x0400_0130,
+                width: 2,
+                value: 0x03FF,
+                kind: IoAccessKind::Read,
+            },
+            // Loading a save-state moves the cycles back.
+            IoAccess {
+                cycle: 5,
+                address: 0x0400_0208,
+                width: 4,
+                value: 1,
+                kind: IoAccessKind::Write,
+            },
+        ]
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let mut trace = BinaryTrace::new(Vec::new()).unwrap();
+        for access in accesses() {
+            trace.record(&access).unwrap();
+        }
+        let data = trace.into_inner();
+
+        // Header, then the records: small deltas and offsets take few bytes.
+        assert_eq!(data.len(), 5 + 5 + 7 + 9);
+        assert_eq!(decode_binary_trace(&data).unwrap(), accesses());
+
+        assert!(decode_binary_trace(&data[..data.len() - 1]).is_err());
+        assert!(decode_binary_trace(b"CLMS").is_err());
+    }
+
+    #[test]
+    fn vcd() {
+        let mut trace = VcdTrace::new(Vec::new()).unwrap();
+        for access in accesses() {
+            trace.record(&access).unwrap();
+        }
+        let vcd = String::from_utf8(trace.into_inner()).unwrap();
+        let changes = vcd.split("$enddefinitions $end\n").nth(1).unwrap();
+
+        assert_eq!(
+            changes,
+            "#5960\n\
+             b100000000000000000000000000 a\nb10000000011 d\nb10 w\n1r\n1e\n\
+             #178800\n\
+             b100000000000000000100110000 a\nb1111111111 d\nb10 w\n0r\n1e\n\
+             b100000000000000001000001000 a\nb1 d\nb100 w\n1r\n1e\n"
+        );
+    }
+}
@@ -1,8 +1,36 @@
+//! The emulation core of Clementine, without any frontend.
+//!
+//! # Public API
+//! Frontends (the egui app, headless runners, libretro or wasm ports, external tools)
+//! should only depend on the stable API, which follows semver: while the crate is at
+//! `0.x`, a breaking change bumps the minor version.
+//!
+//! - [`gba::Gba`] and what its methods take and return: running, input, save-states,
+//!   battery saves, rewind, fast-forward, movies, resets and the configuration of
+//!   [`config`].
+//! - The services around it: [`headless`], [`input_macro`], [`notifications`],
+//!   [`events`], [`save_state`], [`rewind`], [`fast_forward`], [`movie`], [`save_profiles`],
+//!   [`battery_save`], [`atomic_file`], [`cartridge_header`], [`cartridge_info`],
+//!   [`replacement_bios`] and [`clock`].
+//!
+//! The other public modules, [`cpu`], [`bus`] and [`memory_map`] first, expose the
+//! internals for the debugger views and the tests: they may change in any release.
+//!
+//! `tests/public_api.rs` names every item and signature of the stable API: a change that
+//! breaks it must come with a version bump.
+
 #[allow(clippy::cast_possible_truncation)]
 #[allow(clippy::cast_sign_loss)]
 #[allow(clippy::cast_possible_wrap)]
 mod bitwise;
 
+pub mod atomic_file;
+pub mod battery_save;
+
+#[allow(clippy::cast_precision_loss)]
+pub mod bench;
+pub mod capture;
+
 #[allow(clippy::missing_panics_doc)]
 #[allow(clippy::cast_lossless)]
 #[allow(clippy::large_stack_frames)]
@@ -11,6 +39,65 @@ pub mod bus;
 
 #[allow(clippy::similar_names)]
 pub mod cartridge_header;
+pub mod cartridge_info;
+
+#[allow(clippy::cast_possible_truncation)]
+pub mod cheats;
+
+#[cfg(all(feature = "serde", feature = "debug-hooks"))]
+pub mod compatibility;
+
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_precision_loss)]
+#[allow(clippy::cast_sign_loss)]
+pub mod clock;
+
+pub mod config;
 pub mod cpu;
+pub mod cpu_trace;
+
+#[cfg(feature = "serde")]
+pub mod determinism;
+pub mod events;
+pub mod fast_forward;
+pub mod frame_guard;
 pub mod gba;
+pub mod headless;
+pub mod input_macro;
+
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_possible_wrap)]
+#[allow(clippy::cast_sign_loss)]
+pub mod io_trace;
+pub mod memory_budget;
+pub mod memory_edit;
+
+pub mod memory_map;
+#[cfg(feature = "serde")]
+pub mod movie;
+pub mod notifications;
+
+#[allow(clippy::cast_possible_truncation)]
+pub mod ram_search;
 pub mod render;
+
+#[allow(clippy::cast_possible_wrap)]
+pub mod replacement_bios;
+
+#[cfg(feature = "serde")]
+pub mod rewind;
+
+pub mod run_report;
+
+pub mod save_profiles;
+
+#[cfg(feature = "serde")]
+pub mod save_state;
+
+#[cfg(feature = "serde")]
+pub mod step_history;
+
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_possible_wrap)]
+#[doc(hidden)]
+pub mod test_rom;
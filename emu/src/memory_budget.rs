@@ -0,0 +1,72 @@
+//! Memory taken by the emulator, subsystem by subsystem, for the embeddings that can't
+//! afford much: the web and mobile frontends pick [`MemoryProfile::Bounded`].
+//!
+//! | Subsystem | Standard | Bounded |
+//! |---|---|---|
+//! | Cartridge ROM | size of the ROM, up to 32 MiB | same |
+//! | BIOS and work RAM | 304 KiB | same |
+//! | VRAM, palettes and OAM | 98 KiB | same |
+//! | Frames, the one drawn and the one published | 2 × 75 KiB | same |
+//! | Flash save memory | 64 or 128 KiB if the game saves to Flash | same |
+//! | Step history | about 600 KiB per save-state, 16 by default | none |
+//! | Rewind | about 600 KiB for the newest snapshot, a few KiB per older one and at most a save-state each, 360 by default | none |
+//! | Disassembly (`disassembler` feature) | the last 1000 instructions, about 40 KiB | none |
+//! | Compiled blocks (`jit` feature) | grows with the code run from ROM | none |
+//!
+//! The frames are already 16-bit, [`Color`](crate::cpu::hardware::lcd::Color) is the
+//! BGR555 value of the hardware. The debugging views of the frontends read the video
+//! memory when they are drawn, they don't keep copies of it in the emulator.
+//!
+//! Without the ROM, the bounded profile stays within [`BOUNDED_BUDGET`]: see
+//! [`Gba::memory_usage`](crate::gba::Gba::memory_usage).
+//!
+//! [`MemoryProfile::Bounded`]: crate::config::MemoryProfile::Bounded
+
+use std::fmt;
+
+/// Bytes the emulator takes with [`MemoryProfile::Bounded`], the ROM aside.
+///
+/// [`MemoryProfile::Bounded`]: crate::config::MemoryProfile::Bounded
+pub const BOUNDED_BUDGET: usize = 1024 * 1024;
+
+/// Bytes taken by each subsystem, see [`Gba::memory_usage`](crate::gba::Gba::memory_usage).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub rom: usize,
+    /// BIOS, work RAM, and the writes to unused addresses.
+    pub system: usize,
+    /// VRAM, palettes and OAM.
+    pub video: usize,
+    pub frames: usize,
+    /// Flash chip of the cartridge.
+    pub save: usize,
+    pub step_history: usize,
+    /// Snapshots of the [`RewindBuffer`](crate::rewind::RewindBuffer).
+    pub rewind: usize,
+}
+
+impl MemoryUsage {
+    /// Everything but the ROM, which the frontend loaded anyway.
+    #[must_use]
+    pub const fn without_rom(&self) -> usize {
+        self.system + self.video + self.frames + self.save + self.step_history + self.rewind
+    }
+}
+
+impl fmt::Display for MemoryUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kib = |bytes: usize| bytes.div_ceil(1024);
+
+        write!(
+            f,
+            "ROM {} KiB, system {} KiB, video {} KiB, frames {} KiB, save {} KiB, step history {} KiB, rewind {} KiB",
+            kib(self.rom),
+            kib(self.system),
+            kib(self.video),
+            kib(self.frames),
+            kib(self.save),
+            kib(self.step_history),
+            kib(self.rewind)
+        )
+    }
+}
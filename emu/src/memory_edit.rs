@@ -0,0 +1,104 @@
+//! Writes coming from debugging tools (memory viewer, cheats).
+//!
+//! Unlike the ones done by the emulated CPU, they don't take cycles, they can't touch the
+//! BIOS or the cartridge ROM and they are visible immediately, pipeline included.
+
+use std::fmt;
+
+/// Value written by a debug write, its width is the width of the access.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditValue {
+    Byte(u8),
+    HalfWord(u16),
+    Word(u32),
+}
+
+impl EditValue {
+    /// Bytes written, little endian.
+    #[must_use]
+    pub fn bytes(self) -> Vec<u8> {
+        match self {
+            Self::Byte(value) => vec![value],
+            Self::HalfWord(value) => value.to_le_bytes().to_vec(),
+            Self::Word(value) => value.to_le_bytes().to_vec(),
+        }
+    }
+
+    #[must_use]
+    pub const fn width(self) -> u32 {
+        match self {
+            Self::Byte(_) => 1,
+            Self::HalfWord(_) => 2,
+            Self::Word(_) => 4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryEditError {
+    /// The BIOS and the cartridge ROM can't be written.
+    ReadOnly(u32),
+    /// Nothing is mapped there (or it isn't emulated yet, like the cartridge SRAM).
+    Unmapped(u32),
+}
+
+impl fmt::Display for MemoryEditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ReadOnly(address) => write!(f, "0x{address:08X} is read-only"),
+            Self::Unmapped(address) => write!(f, "nothing is mapped at 0x{address:08X}"),
+        }
+    }
+}
+
+impl std::error::Error for MemoryEditError {}
+
+/// Checks that every byte written by `value` at `address` is writable.
+///
+/// # Errors
+/// It returns the first byte that can't be written.
+pub fn check(address: u32, value: EditValue) -> Result<(), MemoryEditError> {
+    for offset in 0..value.width() {
+        let address = address
+            .checked_add(offset)
+            .ok_or(MemoryEditError::Unmapped(address))?;
+
+        match address {
+            0x0000_0000..=0x0000_3FFF | 0x0800_0000..=0x0DFF_FFFF => {
+                return Err(MemoryEditError::ReadOnly(address));
+            }
+            // Work RAMs, I/O registers, palette, VRAM and OAM, mirrors included.
+            0x0200_0000..=0x07FF_FFFF => {}
+            _ => return Err(MemoryEditError::Unmapped(address)),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn writable_regions() {
+        assert_eq!(check(0x0200_0000, EditValue::Word(1)), Ok(()));
+        assert_eq!(check(0x0700_03FE, EditValue::HalfWord(1)), Ok(()));
+
+        assert_eq!(
+            check(0x0800_00C0, EditValue::Byte(1)),
+            Err(MemoryEditError::ReadOnly(0x0800_00C0))
+        );
+        // The word crosses into the cartridge ROM.
+        assert_eq!(
+            check(0x07FF_FFFE, EditValue::Word(1)),
+            Err(MemoryEditError::ReadOnly(0x0800_0000))
+        );
+        assert_eq!(
+            check(0x0E00_0000, EditValue::Byte(1)),
+            Err(MemoryEditError::Unmapped(0x0E00_0000))
+        );
+    }
+}
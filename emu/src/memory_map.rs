@@ -0,0 +1,444 @@
+//! Addresses of the GBA memory map, in a single place.
+//!
+//! Regions are given by their start address and size, mirrors excluded. The newtypes
+//! tell apart addresses of different regions: their constructors check the region, so
+//! an address typed wrong in a `const` item fails to compile.
+
+/// System ROM, 16 `KBytes`.
+pub const BIOS_START: u32 = 0x0000_0000;
+pub const BIOS_SIZE: usize = 0x4000;
+
+/// On-board work RAM, 256 `KBytes` mirrored up to 0x02FFFFFF.
+pub const EWRAM_START: u32 = 0x0200_0000;
+pub const EWRAM_SIZE: usize = 0x4_0000;
+
+/// On-chip work RAM, 32 `KBytes` mirrored up to 0x03FFFFFF.
+pub const IWRAM_START: u32 = 0x0300_0000;
+pub const IWRAM_SIZE: usize = 0x8000;
+
+/// Interrupts acknowledged by the IRQ handler of the game for `IntrWait`, which waits
+/// for them here rather than in `IF`.
+pub const BIOS_INTERRUPT_FLAGS: u32 = 0x0300_7FF8;
+/// Non-zero when the soft reset of the BIOS restarts a multiboot game from EWRAM, see
+/// [`Arm7tdmi::soft_reset`](crate::cpu::arm7tdmi::Arm7tdmi::soft_reset).
+pub const SOFT_RESET_TARGET: u32 = 0x0300_7FFA;
+/// The BIOS jumps to the address stored here when an interrupt is raised.
+pub const IRQ_HANDLER_ADDRESS: u32 = 0x0300_7FFC;
+
+pub const IO_START: u32 = 0x0400_0000;
+/// `POSTFLG`, set by the BIOS once it booted.
+pub const POST_BOOT_FLAG: u32 = 0x0400_0300;
+
+/// BG palette then OBJ palette, 512 bytes each, mirrored up to 0x05FFFFFF.
+pub const BG_PALETTE_START: u32 = 0x0500_0000;
+pub const OBJ_PALETTE_START: u32 = 0x0500_0200;
+pub const PALETTE_SIZE: usize = 0x200;
+
+/// 96 `KBytes`, see [`VramAddr::mirrored`] for the mirrors.
+pub const VRAM_START: u32 = 0x0600_0000;
+pub const VRAM_SIZE: usize = 0x1_8000;
+
+/// Object attributes, 1 `KByte` mirrored up to 0x07FFFFFF.
+pub const OAM_START: u32 = 0x0700_0000;
+pub const OAM_SIZE: usize = 0x400;
+
+/// The cartridge ROM is mapped three times, each one with its own waitstates.
+pub const ROM_START: u32 = 0x0800_0000;
+pub const ROM_END: u32 = 0x0DFF_FFFF;
+pub const ROM_MIRROR_SIZE: u32 = 0x0200_0000;
+
+/// Cartridge backup memory (SRAM or Flash), 64 `KBytes`.
+pub const SRAM_START: u32 = 0x0E00_0000;
+pub const SRAM_SIZE: usize = 0x1_0000;
+
+/// Addresses that no region decodes: the 16 KB after the BIOS and the top 4 bits of the
+/// address bus, which isn't connected. On hardware they read the open bus.
+#[must_use]
+pub const fn is_unmapped(address: u32) -> bool {
+    matches!(address, 0x0000_4000..=0x01FF_FFFF) || address >= 0x1000_0000
+}
+
+/// LCD registers, from `DISPCNT` to `BLDY`.
+pub const LCD_REGISTERS_END: IoAddr = IoAddr::new(0x0400_005F);
+/// Interrupt enable.
+pub const IE: IoAddr = IoAddr::new(0x0400_0200);
+/// Interrupt request flags.
+pub const IF: IoAddr = IoAddr::new(0x0400_0202);
+/// Waitstates of the cartridge.
+pub const WAITCNT: IoAddr = IoAddr::new(0x0400_0204);
+/// Interrupt master enable.
+pub const IME: IoAddr = IoAddr::new(0x0400_0208);
+/// Writing it halts the CPU until an interrupt, see [`crate::bus::Bus::halted`].
+pub const HALTCNT: IoAddr = IoAddr::new(0x0400_0301);
+
+/// Bits of an I/O register that can be read back and written, the others read as 0 and
+/// ignore writes. Shared by the bus, which masks byte accesses with it, and the I/O map.
+///
+/// Reading a write-only register returns the open bus instead, the last prefetched
+/// opcode, as do the addresses missing from [`IO_REGISTERS`]. A few unused half-words
+/// and write-only registers read 0, they are listed with [`Self::open_bus`] off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IoRegister {
+    pub address: IoAddr,
+    pub name: &'static str,
+    /// In bytes, 2 or 4.
+    pub size: u32,
+    pub readable: u32,
+    pub writable: u32,
+    /// Whether reading the register returns the open bus, only when none of its bits
+    /// are readable.
+    pub open_bus: bool,
+}
+
+impl IoRegister {
+    const fn new(address: u32, name: &'static str, readable: u16, writable: u16) -> Self {
+        Self {
+            address: IoAddr::new(address),
+            name,
+            size: 2,
+            readable: readable as u32,
+            writable: writable as u32,
+            open_bus: readable == 0,
+        }
+    }
+
+    /// Word registers are all write-only.
+    const fn write_only_word(address: u32, name: &'static str, writable: u32) -> Self {
+        Self {
+            address: IoAddr::new(address),
+            name,
+            size: 4,
+            readable: 0,
+            writable,
+            open_bus: true,
+        }
+    }
+
+    /// A half-word that no register uses but reads 0 instead of the open bus.
+    const fn unused(address: u32) -> Self {
+        Self {
+            open_bus: false,
+            ..Self::new(address, "unused", 0, 0)
+        }
+    }
+
+    /// A write-only register that reads 0 instead of the open bus.
+    const fn reads_zero(self) -> Self {
+        Self {
+            open_bus: false,
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn contains(&self, address: u32) -> bool {
+        address >= self.address.get() && address - self.address.get() < self.size
+    }
+
+    /// Readable bits of the byte at `address`.
+    #[must_use]
+    pub const fn readable_byte(&self, address: u32) -> u8 {
+        self.readable.to_le_bytes()[(address - self.address.get()) as usize]
+    }
+
+    /// Writable bits of the byte at `address`.
+    #[must_use]
+    pub const fn writable_byte(&self, address: u32) -> u8 {
+        self.writable.to_le_bytes()[(address - self.address.get()) as usize]
+    }
+}
+
+/// The I/O registers with unused, read-only or write-only bits, by address.
+pub const IO_REGISTERS: &[IoRegister] = &[
+    // LCD
+    IoRegister::new(0x0400_0000, "DISPCNT", 0xFFFF, 0xFFF7),
+    IoRegister::new(0x0400_0002, "GREENSWAP", 0xFFFF, 0xFFFF),
+    IoRegister::new(0x0400_0004, "DISPSTAT", 0xFF3F, 0xFF38),
+    IoRegister::new(0x0400_0006, "VCOUNT", 0x00FF, 0),
+    IoRegister::new(0x0400_0008, "BG0CNT", 0xDFFF, 0xDFFF),
+    IoRegister::new(0x0400_000A, "BG1CNT", 0xDFFF, 0xDFFF),
+    IoRegister::new(0x0400_000C, "BG2CNT", 0xFFFF, 0xFFFF),
+    IoRegister::new(0x0400_000E, "BG3CNT", 0xFFFF, 0xFFFF),
+    IoRegister::new(0x0400_0010, "BG0HOFS", 0, 0x01FF),
+    IoRegister::new(0x0400_0012, "BG0VOFS", 0, 0x01FF),
+    IoRegister::new(0x0400_0014, "BG1HOFS", 0, 0x01FF),
+    IoRegister::new(0x0400_0016, "BG1VOFS", 0, 0x01FF),
+    IoRegister::new(0x0400_0018, "BG2HOFS", 0, 0x01FF),
+    IoRegister::new(0x0400_001A, "BG2VOFS", 0, 0x01FF),
+    IoRegister::new(0x0400_001C, "BG3HOFS", 0, 0x01FF),
+    IoRegister::new(0x0400_001E, "BG3VOFS", 0, 0x01FF),
+    IoRegister::new(0x0400_0020, "BG2PA", 0, 0xFFFF),
+    IoRegister::new(0x0400_0022, "BG2PB", 0, 0xFFFF),
+    IoRegister::new(0x0400_0024, "BG2PC", 0, 0xFFFF),
+    IoRegister::new(0x0400_0026, "BG2PD", 0, 0xFFFF),
+    IoRegister::write_only_word(0x0400_0028, "BG2X", 0x0FFF_FFFF),
+    IoRegister::write_only_word(0x0400_002C, "BG2Y", 0x0FFF_FFFF),
+    IoRegister::new(0x0400_0030, "BG3PA", 0, 0xFFFF),
+    IoRegister::new(0x0400_0032, "BG3PB", 0, 0xFFFF),
+    IoRegister::new(0x0400_0034, "BG3PC", 0, 0xFFFF),
+    IoRegister::new(0x0400_0036, "BG3PD", 0, 0xFFFF),
+    IoRegister::write_only_word(0x0400_0038, "BG3X", 0x0FFF_FFFF),
+    IoRegister::write_only_word(0x0400_003C, "BG3Y", 0x0FFF_FFFF),
+    IoRegister::new(0x0400_0040, "WIN0H", 0, 0xFFFF),
+    IoRegister::new(0x0400_0042, "WIN1H", 0, 0xFFFF),
+    IoRegister::new(0x0400_0044, "WIN0V", 0, 0xFFFF),
+    IoRegister::new(0x0400_0046, "WIN1V", 0, 0xFFFF),
+    IoRegister::new(0x0400_0048, "WININ", 0x3F3F, 0x3F3F),
+    IoRegister::new(0x0400_004A, "WINOUT", 0x3F3F, 0x3F3F),
+    IoRegister::new(0x0400_004C, "MOSAIC", 0, 0xFFFF),
+    IoRegister::new(0x0400_0050, "BLDCNT", 0x3FFF, 0x3FFF),
+    IoRegister::new(0x0400_0052, "BLDALPHA", 0x1F1F, 0x1F1F),
+    IoRegister::new(0x0400_0054, "BLDY", 0, 0x001F),
+    // Sound
+    IoRegister::new(0x0400_0060, "SOUND1CNT_L", 0x007F, 0x007F),
+    IoRegister::new(0x0400_0062, "SOUND1CNT_H", 0xFFC0, 0xFFFF),
+    IoRegister::new(0x0400_0064, "SOUND1CNT_X", 0x4000, 0xC7FF),
+    IoRegister::unused(0x0400_0066),
+    IoRegister::new(0x0400_0068, "SOUND2CNT_L", 0xFFC0, 0xFFFF),
+    IoRegister::new(0x0400_006C, "SOUND2CNT_H", 0x4000, 0xC7FF),
+    IoRegister::unused(0x0400_006E),
+    IoRegister::new(0x0400_0070, "SOUND3CNT_L", 0x00E0, 0x00E0),
+    IoRegister::new(0x0400_0072, "SOUND3CNT_H", 0xE000, 0xE0FF),
+    IoRegister::new(0x0400_0074, "SOUND3CNT_X", 0x4000, 0xC7FF),
+    IoRegister::unused(0x0400_0076),
+    IoRegister::new(0x0400_0078, "SOUND4CNT_L", 0xFF00, 0xFF3F),
+    IoRegister::unused(0x0400_007A),
+    IoRegister::new(0x0400_007C, "SOUND4CNT_H", 0x40FF, 0xC0FF),
+    IoRegister::unused(0x0400_007E),
+    IoRegister::new(0x0400_0080, "SOUNDCNT_L", 0xFF77, 0xFF77),
+    IoRegister::new(0x0400_0082, "SOUNDCNT_H", 0x770F, 0xFF0F),
+    IoRegister::new(0x0400_0084, "SOUNDCNT_X", 0x008F, 0x0080),
+    IoRegister::unused(0x0400_0086),
+    IoRegister::new(0x0400_0088, "SOUNDBIAS", 0xC3FE, 0xC3FE),
+    IoRegister::unused(0x0400_008A),
+    IoRegister::write_only_word(0x0400_00A0, "FIFO_A", 0xFFFF_FFFF),
+    IoRegister::write_only_word(0x0400_00A4, "FIFO_B", 0xFFFF_FFFF),
+    // DMA
+    IoRegister::write_only_word(0x0400_00B0, "DMA0SAD", 0x07FF_FFFF),
+    IoRegister::write_only_word(0x0400_00B4, "DMA0DAD", 0x07FF_FFFF),
+    IoRegister::new(0x0400_00B8, "DMA0CNT_L", 0, 0x3FFF).reads_zero(),
+    IoRegister::new(0x0400_00BA, "DMA0CNT_H", 0xF7E0, 0xF7E0),
+    IoRegister::write_only_word(0x0400_00BC, "DMA1SAD", 0x0FFF_FFFF),
+    IoRegister::write_only_word(0x0400_00C0, "DMA1DAD", 0x07FF_FFFF),
+    IoRegister::new(0x0400_00C4, "DMA1CNT_L", 0, 0x3FFF).reads_zero(),
+    IoRegister::new(0x0400_00C6, "DMA1CNT_H", 0xF7E0, 0xF7E0),
+    IoRegister::write_only_word(0x0400_00C8, "DMA2SAD", 0x0FFF_FFFF),
+    IoRegister::write_only_word(0x0400_00CC, "DMA2DAD", 0x07FF_FFFF),
+    IoRegister::new(0x0400_00D0, "DMA2CNT_L", 0, 0x3FFF).reads_zero(),
+    IoRegister::new(0x0400_00D2, "DMA2CNT_H", 0xF7E0, 0xF7E0),
+    IoRegister::write_only_word(0x0400_00D4, "DMA3SAD", 0x0FFF_FFFF),
+    IoRegister::write_only_word(0x0400_00D8, "DMA3DAD", 0x0FFF_FFFF),
+    IoRegister::new(0x0400_00DC, "DMA3CNT_L", 0, 0xFFFF).reads_zero(),
+    IoRegister::new(0x0400_00DE, "DMA3CNT_H", 0xFFE0, 0xFFE0),
+    // Timers
+    IoRegister::new(0x0400_0100, "TM0CNT_L", 0xFFFF, 0xFFFF),
+    IoRegister::new(0x0400_0102, "TM0CNT_H", 0x00C3, 0x00C3),
+    IoRegister::new(0x0400_0104, "TM1CNT_L", 0xFFFF, 0xFFFF),
+    IoRegister::new(0x0400_0106, "TM1CNT_H", 0x00C7, 0x00C7),
+    IoRegister::new(0x0400_0108, "TM2CNT_L", 0xFFFF, 0xFFFF),
+    IoRegister::new(0x0400_010A, "TM2CNT_H", 0x00C7, 0x00C7),
+    IoRegister::new(0x0400_010C, "TM3CNT_L", 0xFFFF, 0xFFFF),
+    IoRegister::new(0x0400_010E, "TM3CNT_H", 0x00C7, 0x00C7),
+    // Keypad
+    IoRegister::new(0x0400_0130, "KEYINPUT", 0x03FF, 0),
+    IoRegister::new(0x0400_0132, "KEYCNT", 0xC3FF, 0xC3FF),
+    // Infrared port of the prototypes.
+    IoRegister::unused(0x0400_0136),
+    IoRegister::unused(0x0400_0142),
+    IoRegister::unused(0x0400_015A),
+    // Interrupt, waitstate and power-down control
+    IoRegister::new(IE.get(), "IE", 0x3FFF, 0x3FFF),
+    IoRegister::new(IF.get(), "IF", 0x3FFF, 0x3FFF),
+    IoRegister::new(WAITCNT.get(), "WAITCNT", 0xDFFF, 0x5FFF),
+    IoRegister::unused(0x0400_0206),
+    IoRegister::new(IME.get(), "IME", 0x0001, 0x0001),
+    IoRegister::unused(0x0400_020A),
+    // HALTCNT is write-only but reads 0.
+    IoRegister::new(0x0400_0300, "POSTFLG_HALTCNT", 0x0001, 0xFF01),
+];
+
+/// The register holding the byte at `address`, if it is in [`IO_REGISTERS`].
+#[must_use]
+pub fn io_register(address: u32) -> Option<&'static IoRegister> {
+    let index = IO_REGISTERS
+        .partition_point(|register| register.address.get() <= address)
+        .checked_sub(1)?;
+    let register = &IO_REGISTERS[index];
+
+    register.contains(address).then_some(register)
+}
+
+/// Address of an I/O register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IoAddr(u32);
+
+impl IoAddr {
+    /// # Panics
+    /// If `address` is not in the I/O region, at compile time in `const` items.
+    #[must_use]
+    pub const fn new(address: u32) -> Self {
+        assert!(address >> 24 == IO_START >> 24, "not an I/O address");
+
+        Self(address)
+    }
+
+    #[must_use]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Distance from [`IO_START`].
+    #[must_use]
+    pub const fn offset(self) -> usize {
+        (self.0 - IO_START) as usize
+    }
+}
+
+/// Address of a byte of VRAM, mirrors folded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VramAddr(u32);
+
+impl VramAddr {
+    /// # Panics
+    /// If `address` is not in the VRAM, mirrors excluded, at compile time in `const`
+    /// items.
+    #[must_use]
+    pub const fn new(address: u32) -> Self {
+        assert!(
+            address >= VRAM_START && ((address - VRAM_START) as usize) < VRAM_SIZE,
+            "not a VRAM address"
+        );
+
+        Self(address)
+    }
+
+    /// Folds the mirrors of the VRAM region: it repeats every 128 `KBytes`, and in each
+    /// repetition the last 32 `KBytes` mirror the 32 `KBytes` before them.
+    ///
+    /// # Panics
+    /// If `address` is not in the 0x06000000-0x06FFFFFF region.
+    #[must_use]
+    pub const fn mirrored(address: u32) -> Self {
+        assert!(address >> 24 == VRAM_START >> 24, "not a VRAM address");
+
+        let offset = address & 0x1_FFFF;
+        let offset = if offset as usize >= VRAM_SIZE {
+            offset - 0x8000
+        } else {
+            offset
+        };
+
+        Self(VRAM_START + offset)
+    }
+
+    #[must_use]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Index in the VRAM.
+    #[must_use]
+    pub const fn offset(self) -> usize {
+        (self.0 - VRAM_START) as usize
+    }
+}
+
+/// Address in one of the three cartridge ROM mirrors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RomAddr(u32);
+
+impl RomAddr {
+    /// # Panics
+    /// If `address` is not in the cartridge ROM, at compile time in `const` items.
+    #[must_use]
+    pub const fn new(address: u32) -> Self {
+        assert!(
+            address >= ROM_START && address <= ROM_END,
+            "not a cartridge ROM address"
+        );
+
+        Self(address)
+    }
+
+    #[must_use]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Index in the ROM, the same for the three mirrors.
+    #[must_use]
+    pub const fn offset(self) -> usize {
+        ((self.0 - ROM_START) % ROM_MIRROR_SIZE) as usize
+    }
+
+    /// Which of the three mirrors, selecting the waitstates of the access.
+    #[must_use]
+    pub const fn wait_state(self) -> usize {
+        ((self.0 - ROM_START) / ROM_MIRROR_SIZE) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn vram_mirrors() {
+        assert_eq!(VramAddr::mirrored(0x0601_7FFF).offset(), 0x1_7FFF);
+        assert_eq!(VramAddr::mirrored(0x0601_8000).offset(), 0x1_0000);
+        assert_eq!(VramAddr::mirrored(0x0602_0004).offset(), 0x4);
+        assert_eq!(VramAddr::mirrored(0x06FF_FFFF).offset(), 0x1_7FFF);
+    }
+
+    #[test]
+    fn rom_mirrors() {
+        let addresses = [0x0800_1234, 0x0A00_1234, 0x0C00_1234].map(RomAddr::new);
+
+        assert_eq!(addresses.map(RomAddr::offset), [0x1234; 3]);
+        assert_eq!(addresses.map(RomAddr::wait_state), [0, 1, 2]);
+    }
+
+    #[test]
+    fn unmapped_addresses() {
+        assert!(!is_unmapped(BIOS_START + 0x3FFC));
+        assert!(is_unmapped(0x0000_4000));
+        assert!(is_unmapped(0x01FF_FFFF));
+        assert!(!is_unmapped(EWRAM_START));
+        assert!(!is_unmapped(SRAM_START + 0x1_FFFF));
+        assert!(is_unmapped(0x1000_0000));
+        assert!(is_unmapped(0xFFFF_FFFC));
+    }
+
+    #[test]
+    fn io_registers_are_sorted_and_disjoint() {
+        for pair in IO_REGISTERS.windows(2) {
+            assert!(
+                pair[0].address.get() + pair[0].size <= pair[1].address.get(),
+                "{} and {}",
+                pair[0].name,
+                pair[1].name
+            );
+        }
+    }
+
+    #[test]
+    fn io_register_lookup() {
+        assert_eq!(io_register(0x0400_0005).map(|r| r.name), Some("DISPSTAT"));
+        assert_eq!(io_register(0x0400_002B).map(|r| r.name), Some("BG2X"));
+        assert_eq!(io_register(0x0400_004E), None);
+        assert_eq!(io_register(0x0400_0209).map(|r| r.name), Some("IME"));
+
+        let soundcnt_x = io_register(0x0400_0084).unwrap();
+        assert_eq!(soundcnt_x.readable_byte(0x0400_0084), 0x8F);
+        assert_eq!(soundcnt_x.writable_byte(0x0400_0084), 0x80);
+        assert_eq!(soundcnt_x.readable_byte(0x0400_0085), 0);
+    }
+
+    #[test]
+    #[should_panic = "not an I/O address"]
+    fn io_address_out_of_region() {
+        let _ = IoAddr::new(0x0300_0200);
+    }
+}
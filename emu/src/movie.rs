@@ -0,0 +1,381 @@
+//! Movies: the buttons pressed on every frame, recorded from a known start and played
+//! back to reproduce a run exactly, for tool-assisted runs, regression replays and
+//! desync hunting.
+//!
+//! The game only sees the keys latched when entering Vblank (see
+//! [`KeySampling::Latched`](crate::cpu::hardware::keypad::KeySampling::Latched)): a
+//! movie stores the keys of each latch. They are played back in place of the host input
+//! whatever the frontend does meanwhile, see
+//! [`Keypad::set_forced_keys`](crate::cpu::hardware::keypad::Keypad::set_forced_keys).
+//! Resetting, loading a state or rewinding isn't recorded: the movie desyncs from there.
+//!
+//! Layout (all integers little endian):
+//! ```text
+//! magic "CLMV" | version: u16 | ROM crc32: u32 | BIOS crc32: u32
+//! start: u8 (0 power-on, 1 save-state) | if a save-state: len: u32 | save-state
+//! frame count: u32 | repeated: keys: u16
+//! ```
+//!
+//! ```no_run
+//! use emu::{cartridge_header::CartridgeHeader, gba::Gba, movie::{Movie, MovieStart}};
+//!
+//! let bios = std::fs::read("gba_bios.bin").unwrap().try_into().unwrap();
+//! let rom = std::fs::read("game.gba").unwrap();
+//! let mut gba = Gba::new(CartridgeHeader::new(&rom).unwrap(), bios, rom);
+//!
+//! gba.start_recording(MovieStart::PowerOn).unwrap();
+//! for _ in 0..600 {
+//!     let _ = gba.run_frame();
+//! }
+//! let movie = gba.stop_recording().unwrap();
+//! movie.write_file("run.clmv".as_ref()).unwrap();
+//!
+//! gba.play_movie(&Movie::read_file("run.clmv".as_ref()).unwrap()).unwrap();
+//! ```
+
+use std::{fmt, fs, io, path::Path};
+
+use crate::{
+    atomic_file::{self, DEFAULT_BACKUPS},
+    save_state::{crc32, SaveStateError},
+};
+
+const MAGIC: &[u8; 4] = b"CLMV";
+
+/// Version of the movie layout, bumped on incompatible changes.
+pub const MOVIE_VERSION: u16 = 1;
+
+/// Where a movie starts from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieStart {
+    /// A hard reset, see [`ResetKind::Hard`](crate::gba::ResetKind::Hard): the save
+    /// memory is erased too.
+    PowerOn,
+    /// The state of the emulator when the recording starts, stored in the movie.
+    Now,
+}
+
+/// What a movie replays from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MovieAnchor {
+    PowerOn,
+    /// A save-state, see [`save_state`](crate::save_state).
+    SaveState(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    /// CRC-32 of the cartridge ROM it was recorded with.
+    pub rom_crc32: u32,
+    /// CRC-32 of the BIOS image it was recorded with.
+    pub bios_crc32: u32,
+    pub anchor: MovieAnchor,
+    /// The keys latched at each Vblank after the anchor, one bit per
+    /// [`Key`](crate::cpu::hardware::keypad::Key) set when pressed.
+    pub frames: Vec<u16>,
+}
+
+#[derive(Debug)]
+pub enum MovieError {
+    NotAMovie,
+    UnsupportedVersion(u16),
+    /// The file ends before its last frame.
+    Truncated,
+    /// The movie was recorded with another game, or another dump of it.
+    RomMismatch {
+        movie: u32,
+        loaded: u32,
+    },
+    /// The movie was recorded with another BIOS image, it would desync.
+    BiosMismatch {
+        movie: u32,
+        loaded: u32,
+    },
+    /// Nothing is being recorded.
+    NotRecording,
+    State(SaveStateError),
+    Io(io::Error),
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAMovie => write!(f, "not a movie"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported movie version {version}")
+            }
+            Self::Truncated => write!(f, "the movie is truncated"),
+            Self::RomMismatch { movie, loaded } => write!(
+                f,
+                "recorded with the ROM {movie:08x}, the loaded one is {loaded:08x}"
+            ),
+            Self::BiosMismatch { movie, loaded } => write!(
+                f,
+                "recorded with the BIOS {movie:08x}, the loaded one is {loaded:08x}"
+            ),
+            Self::NotRecording => write!(f, "no movie is being recorded"),
+            Self::State(e) => write!(f, "movie start: {e}"),
+            Self::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<SaveStateError> for MovieError {
+    fn from(e: SaveStateError) -> Self {
+        Self::State(e)
+    }
+}
+
+impl From<io::Error> for MovieError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Reads the integers of a movie, failing with [`MovieError::Truncated`].
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    const fn take(&mut self, len: usize) -> Result<&'a [u8], MovieError> {
+        if self.0.len() < len {
+            return Err(MovieError::Truncated);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, MovieError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, MovieError> {
+        Ok(u16::from_le_bytes([self.u8()?, self.u8()?]))
+    }
+
+    fn u32(&mut self) -> Result<u32, MovieError> {
+        Ok(u32::from_le_bytes([
+            self.u8()?,
+            self.u8()?,
+            self.u8()?,
+            self.u8()?,
+        ]))
+    }
+}
+
+impl Movie {
+    /// An empty movie recorded with `rom` and `bios`.
+    #[must_use]
+    pub fn new(rom: &[u8], bios: &[u8], anchor: MovieAnchor) -> Self {
+        Self {
+            rom_crc32: crc32(rom),
+            bios_crc32: crc32(bios),
+            anchor,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Checks that the movie was recorded with `rom` and `bios`.
+    ///
+    /// # Errors
+    /// It fails on the first of them that differs.
+    pub fn check(&self, rom: &[u8], bios: &[u8]) -> Result<(), MovieError> {
+        let loaded = crc32(rom);
+        if loaded != self.rom_crc32 {
+            return Err(MovieError::RomMismatch {
+                movie: self.rom_crc32,
+                loaded,
+            });
+        }
+        let loaded = crc32(bios);
+        if loaded != self.bios_crc32 {
+            return Err(MovieError::BiosMismatch {
+                movie: self.bios_crc32,
+                loaded,
+            });
+        }
+
+        Ok(())
+    }
+
+    // Movies and save-states are far below 4 GB.
+    #[allow(clippy::cast_possible_truncation)]
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(32 + self.frames.len() * 2);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&MOVIE_VERSION.to_le_bytes());
+        out.extend_from_slice(&self.rom_crc32.to_le_bytes());
+        out.extend_from_slice(&self.bios_crc32.to_le_bytes());
+        match &self.anchor {
+            MovieAnchor::PowerOn => out.push(0),
+            MovieAnchor::SaveState(state) => {
+                out.push(1);
+                out.extend_from_slice(&(state.len() as u32).to_le_bytes());
+                out.extend_from_slice(state);
+            }
+        }
+        out.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        for keys in &self.frames {
+            out.extend_from_slice(&keys.to_le_bytes());
+        }
+
+        out
+    }
+
+    /// # Errors
+    /// It fails if `data` isn't a whole movie of a supported version.
+    pub fn decode(data: &[u8]) -> Result<Self, MovieError> {
+        let mut reader = Reader(data);
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(MovieError::NotAMovie);
+        }
+        let version = reader.u16()?;
+        if version != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+
+        let rom_crc32 = reader.u32()?;
+        let bios_crc32 = reader.u32()?;
+        let anchor = match reader.u8()? {
+            0 => MovieAnchor::PowerOn,
+            1 => {
+                let len = reader.u32()? as usize;
+                MovieAnchor::SaveState(reader.take(len)?.to_vec())
+            }
+            _ => return Err(MovieError::NotAMovie),
+        };
+        let count = reader.u32()?;
+        let frames = (0..count).map(|_| reader.u16()).collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            rom_crc32,
+            bios_crc32,
+            anchor,
+            frames,
+        })
+    }
+
+    /// Writes the movie to `path`, keeping the previous versions as backups, see
+    /// [`atomic_file::write`].
+    ///
+    /// # Errors
+    /// It fails if the file can't be written.
+    pub fn write_file(&self, path: &Path) -> Result<(), MovieError> {
+        Ok(atomic_file::write(path, &self.encode(), DEFAULT_BACKUPS)?)
+    }
+
+    /// # Errors
+    /// It fails if the file can't be read or isn't a whole movie.
+    pub fn read_file(path: &Path) -> Result<Self, MovieError> {
+        Self::decode(&fs::read(path)?)
+    }
+}
+
+/// Where a movie stands, see [`Gba::movie_status`](crate::gba::Gba::movie_status).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieStatus {
+    Idle,
+    /// `frames` recorded so far.
+    Recording {
+        frames: usize,
+    },
+    /// `frame` frames of `frames` played so far.
+    Playing {
+        frame: usize,
+        frames: usize,
+    },
+}
+
+/// A movie being recorded or played, it goes on at each Vblank.
+pub(crate) enum MovieSession {
+    Recording(Movie),
+    Playing { movie: Movie, next: usize },
+}
+
+impl MovieSession {
+    /// Keys to latch at the next Vblank, `None` when the host input is.
+    pub(crate) fn forced_keys(&self) -> Option<u16> {
+        match self {
+            Self::Recording(_) => None,
+            Self::Playing { movie, next } => movie.frames.get(*next).copied(),
+        }
+    }
+
+    /// Goes on after the Vblank latched `latched`, returns whether a movie playing is
+    /// over.
+    pub(crate) fn next_frame(&mut self, latched: u16) -> bool {
+        match self {
+            Self::Recording(movie) => {
+                movie.frames.push(latched);
+                false
+            }
+            Self::Playing { movie, next } => {
+                *next += 1;
+                *next >= movie.frames.len()
+            }
+        }
+    }
+
+    pub(crate) const fn status(&self) -> MovieStatus {
+        match self {
+            Self::Recording(movie) => MovieStatus::Recording {
+                frames: movie.frames.len(),
+            },
+            Self::Playing { movie, next } => MovieStatus::Playing {
+                frame: *next,
+                frames: movie.frames.len(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn encode_decode() {
+        for anchor in [MovieAnchor::PowerOn, MovieAnchor::SaveState(vec![1, 2, 3])] {
+            let movie = Movie {
+                frames: vec![0, 1, 0x3FF],
+                ..Movie::new(b"rom", b"bios", anchor)
+            };
+            let data = movie.encode();
+            assert_eq!(Movie::decode(&data).unwrap(), movie);
+
+            assert!(matches!(
+                Movie::decode(&data[..data.len() - 1]),
+                Err(MovieError::Truncated)
+            ));
+        }
+
+        assert!(matches!(
+            Movie::decode(b"CLMS\x0b\x00"),
+            Err(MovieError::NotAMovie)
+        ));
+        assert!(matches!(
+            Movie::decode(b"CLMV\x09\x00"),
+            Err(MovieError::UnsupportedVersion(9))
+        ));
+    }
+
+    #[test]
+    fn check_names_what_differs() {
+        let movie = Movie::new(b"rom", b"bios", MovieAnchor::PowerOn);
+        assert!(movie.check(b"rom", b"bios").is_ok());
+        assert!(matches!(
+            movie.check(b"other", b"bios"),
+            Err(MovieError::RomMismatch { .. })
+        ));
+        assert!(matches!(
+            movie.check(b"rom", b"other"),
+            Err(MovieError::BiosMismatch { .. })
+        ));
+    }
+}
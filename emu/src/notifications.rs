@@ -0,0 +1,131 @@
+//! Messages for the user about what the emulator did: a state was saved, the game wrote
+//! its save data, a cheat was toggled...
+//!
+//! The core only queues them, with hints on how to show them: every frontend renders
+//! them its own way (toasts, on-screen text, a status bar) and the feedback stays the
+//! same everywhere.
+
+use std::{collections::VecDeque, time::Duration};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// What a notification is about. A new notification replaces a pending one of the
+/// same kind, frontends can do the same with those on screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotificationKind {
+    StateSaved,
+    StateLoaded,
+    SaveDataWritten,
+    SaveImported,
+    CheatToggled,
+    SpeedChanged,
+    Reset,
+    FrameOverrun,
+    MisalignedPc,
+    CaptureFailed,
+    Movie,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub severity: Severity,
+    pub message: String,
+    /// How long to show it, `None` until the user dismisses it.
+    pub timeout: Option<Duration>,
+}
+
+impl Notification {
+    /// An informative message, shown for a couple of seconds.
+    #[must_use]
+    pub fn info(kind: NotificationKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            severity: Severity::Info,
+            message: message.into(),
+            timeout: Some(Duration::from_secs(2)),
+        }
+    }
+
+    /// Something the user should read: shown longer than [`Self::info`].
+    #[must_use]
+    pub fn warning(kind: NotificationKind, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            timeout: Some(Duration::from_secs(6)),
+            ..Self::info(kind, message)
+        }
+    }
+
+    /// Shown until dismissed.
+    #[must_use]
+    pub fn error(kind: NotificationKind, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            timeout: None,
+            ..Self::info(kind, message)
+        }
+    }
+}
+
+/// Notifications waiting for the frontend, oldest first.
+#[derive(Debug, Default)]
+pub struct Notifications {
+    pending: VecDeque<Notification>,
+}
+
+impl Notifications {
+    pub fn push(&mut self, notification: Notification) {
+        self.pending
+            .retain(|pending| pending.kind != notification.kind);
+        self.pending.push_back(notification);
+    }
+
+    /// Takes every pending notification.
+    pub fn drain(&mut self) -> impl Iterator<Item = Notification> + '_ {
+        self.pending.drain(..)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn same_kind_replaces_pending() {
+        let mut notifications = Notifications::default();
+        notifications.push(Notification::info(NotificationKind::StateSaved, "first"));
+        notifications.push(Notification::info(NotificationKind::CheatToggled, "cheat"));
+        notifications.push(Notification::warning(
+            NotificationKind::StateSaved,
+            "second",
+        ));
+
+        let drained = notifications.drain().collect::<Vec<_>>();
+        assert_eq!(
+            drained,
+            [
+                Notification::info(NotificationKind::CheatToggled, "cheat"),
+                Notification {
+                    kind: NotificationKind::StateSaved,
+                    severity: Severity::Warning,
+                    message: "second".to_string(),
+                    timeout: Some(Duration::from_secs(6)),
+                },
+            ]
+        );
+        assert!(notifications.is_empty());
+    }
+}
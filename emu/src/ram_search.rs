@@ -0,0 +1,236 @@
+//! Searching the work RAMs for where a game keeps a value, to make cheats.
+//!
+//! The search starts from every address of EWRAM and IWRAM, then each filter keeps those
+//! whose value meets it, again and again while playing until a few are left.
+//!
+//! The candidates and their values stay in the core, the frontend only asks for the few
+//! it shows: nothing is copied out of the emulator while the game runs. The search goes
+//! along with a save-state as an [`Attachment`], to carry on after loading it.
+//!
+//! ```
+//! use emu::ram_search::{Comparison, RamSearch, SearchFilter, SearchWidth};
+//! # fn run(gba: &mut emu::gba::Gba) {
+//! // The lives are 3.
+//! let mut search = RamSearch::new(gba, SearchWidth::Byte);
+//! search.filter(gba, SearchFilter::Value(Comparison::Equal, 3));
+//! // ... a life is lost.
+//! search.filter(gba, SearchFilter::ChangedBy(-1));
+//! let found = search.candidates(gba, 10);
+//! # }
+//! ```
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::gba::Gba;
+#[cfg(feature = "serde")]
+use crate::save_state::{Attachment, SaveStateError};
+
+/// Name of the [`Attachment`] holding a search, see [`RamSearch::to_attachment`].
+#[cfg(feature = "serde")]
+pub const ATTACHMENT_NAME: &str = "ram_search";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SearchWidth {
+    #[default]
+    Byte,
+    HalfWord,
+    Word,
+}
+
+impl SearchWidth {
+    #[must_use]
+    pub const fn bytes(self) -> usize {
+        match self {
+            Self::Byte => 1,
+            Self::HalfWord => 2,
+            Self::Word => 4,
+        }
+    }
+
+    /// Bits of the values that fit in the width.
+    const fn mask(self) -> u32 {
+        match self {
+            Self::Byte => 0xFF,
+            Self::HalfWord => 0xFFFF,
+            Self::Word => 0xFFFF_FFFF,
+        }
+    }
+}
+
+impl fmt::Display for SearchWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bits", self.bytes() * 8)
+    }
+}
+
+/// Comparisons of unsigned values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+}
+
+impl Comparison {
+    const fn holds(self, value: u32, other: u32) -> bool {
+        match self {
+            Self::Equal => value == other,
+            Self::NotEqual => value != other,
+            Self::Greater => value > other,
+            Self::Less => value < other,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchFilter {
+    /// The value compared with a constant.
+    Value(Comparison, u32),
+    /// The value compared with the one it had at the last filter, or at the start.
+    Previous(Comparison),
+    /// The value changed by exactly this much since the last filter, wrapping around
+    /// the width.
+    ChangedBy(i32),
+}
+
+/// An address still in the search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    pub address: u32,
+    pub value: u32,
+    /// The value at the last filter.
+    pub previous: u32,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RamSearch {
+    width: SearchWidth,
+    /// Addresses left and their value at the last filter, by address.
+    candidates: Vec<(u32, u32)>,
+}
+
+impl RamSearch {
+    /// Starts a search over every address of EWRAM and IWRAM aligned to `width`, taking
+    /// their values as they are now.
+    #[must_use]
+    pub fn new(gba: &Gba, width: SearchWidth) -> Self {
+        let candidates = gba
+            .cpu
+            .bus
+            .internal_memory
+            .work_rams()
+            .into_iter()
+            .flat_map(|(start, ram)| {
+                (0..)
+                    .zip(ram.chunks_exact(width.bytes()))
+                    .map(move |(index, bytes)| {
+                        let offset = index * width.bytes() as u32;
+                        (start + offset, value_of(bytes))
+                    })
+            })
+            .collect();
+
+        Self { width, candidates }
+    }
+
+    #[must_use]
+    pub const fn width(&self) -> SearchWidth {
+        self.width
+    }
+
+    /// Addresses left.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    /// Keeps the addresses whose value meets `filter`, taking their values as they are
+    /// now for the next one. Returns how many are left.
+    pub fn filter(&mut self, gba: &Gba, filter: SearchFilter) -> usize {
+        let rams = gba.cpu.bus.internal_memory.work_rams();
+        let mask = self.width.mask();
+        self.candidates.retain_mut(|(address, previous)| {
+            let value = read(&rams, *address, self.width);
+            let kept = match filter {
+                SearchFilter::Value(comparison, other) => comparison.holds(value, other & mask),
+                SearchFilter::Previous(comparison) => comparison.holds(value, *previous),
+                SearchFilter::ChangedBy(delta) => {
+                    value == (previous.wrapping_add_signed(delta) & mask)
+                }
+            };
+            *previous = value;
+
+            kept
+        });
+
+        self.candidates.len()
+    }
+
+    /// The first `limit` addresses left, with their value now and at the last filter.
+    #[must_use]
+    pub fn candidates(&self, gba: &Gba, limit: usize) -> Vec<Candidate> {
+        let rams = gba.cpu.bus.internal_memory.work_rams();
+        self.candidates
+            .iter()
+            .take(limit)
+            .map(|&(address, previous)| Candidate {
+                address,
+                value: read(&rams, address, self.width),
+                previous,
+            })
+            .collect()
+    }
+
+    /// The width and the candidates with their values at the last filter, to save with
+    /// [`Gba::save_state_with`].
+    ///
+    /// # Errors
+    /// It fails if the search can't be serialized.
+    #[cfg(feature = "serde")]
+    pub fn to_attachment(&self) -> Result<Attachment, SaveStateError> {
+        Ok(Attachment {
+            name: ATTACHMENT_NAME.to_string(),
+            data: bincode::serialize(self).map_err(SaveStateError::Encode)?,
+        })
+    }
+
+    /// The search saved by [`Self::to_attachment`] among the `attachments` of a loaded
+    /// state. `None` if there is none, or it can't be decoded.
+    #[cfg(feature = "serde")]
+    #[must_use]
+    pub fn from_attachments(attachments: &[Attachment]) -> Option<Self> {
+        let attachment = attachments
+            .iter()
+            .find(|attachment| attachment.name == ATTACHMENT_NAME)?;
+
+        bincode::deserialize(&attachment.data).ok()
+    }
+}
+
+/// The value at `address`, which is in one of `rams`.
+fn read(rams: &[(u32, &[u8]); 2], address: u32, width: SearchWidth) -> u32 {
+    rams.iter()
+        .find_map(|(start, ram)| {
+            let offset = address.checked_sub(*start)? as usize;
+            ram.get(offset..offset + width.bytes())
+        })
+        .map_or(0, value_of)
+}
+
+/// `bytes` as a little endian value.
+fn value_of(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, byte| value << 8 | u32::from(*byte))
+}
@@ -0,0 +1,154 @@
+//! Frame comparison for golden-frame tests.
+//!
+//! Hashing a frame only tells whether it changed: filters applied by a frontend (color
+//! correction, smoothing of the edges...) make every pixel differ slightly. Comparing
+//! with a tolerance on every color channel keeps the tests meaningful.
+
+use std::fmt;
+
+use crate::{
+    cpu::hardware::lcd::{Color, Frame},
+    render::{LCD_HEIGHT, LCD_WIDTH},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompareOptions {
+    /// Largest difference allowed on each of the red, green and blue channels, from 0
+    /// (exact match) to 31.
+    pub tolerance: u8,
+    /// Pixels skipped on every edge of the frame, where filters sample outside of it.
+    pub ignore_border: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameDiff {
+    /// Pixels with a channel differing by more than the tolerance.
+    pub differing_pixels: usize,
+    /// Largest channel difference among the compared pixels, even within the tolerance.
+    pub max_channel_difference: u8,
+    /// Coordinates (x, y) of the first differing pixel, row by row.
+    pub first_difference: Option<(usize, usize)>,
+}
+
+impl FrameDiff {
+    #[must_use]
+    pub const fn is_match(&self) -> bool {
+        self.differing_pixels == 0
+    }
+}
+
+impl fmt::Display for FrameDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.first_difference {
+            None => write!(f, "frames match"),
+            Some((x, y)) => write!(
+                f,
+                "{} pixels differ, the first one at ({x}, {y}), by up to {} per channel",
+                self.differing_pixels, self.max_channel_difference
+            ),
+        }
+    }
+}
+
+fn channel_difference(expected: Color, actual: Color) -> u8 {
+    [
+        expected.red().abs_diff(actual.red()),
+        expected.green().abs_diff(actual.green()),
+        expected.blue().abs_diff(actual.blue()),
+    ]
+    .into_iter()
+    .max()
+    .unwrap_or_default()
+}
+
+/// Compares `actual` against the `expected` golden frame.
+#[must_use]
+pub fn compare_frames(expected: &Frame, actual: &Frame, options: &CompareOptions) -> FrameDiff {
+    let border = options.ignore_border;
+    let mut diff = FrameDiff::default();
+
+    for y in border..LCD_HEIGHT.saturating_sub(border) {
+        for x in border..LCD_WIDTH.saturating_sub(border) {
+            let difference = channel_difference(expected[y][x], actual[y][x]);
+            diff.max_channel_difference = diff.max_channel_difference.max(difference);
+
+            if difference > options.tolerance {
+                diff.differing_pixels += 1;
+                diff.first_difference.get_or_insert((x, y));
+            }
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn frame(color: Color) -> Box<Frame> {
+        vec![[color; LCD_WIDTH]; LCD_HEIGHT]
+            .into_boxed_slice()
+            .try_into()
+            .unwrap_or_else(|_| unreachable!())
+    }
+
+    #[test]
+    fn tolerance_per_channel() {
+        let expected = frame(Color::from_rgb(10, 20, 30));
+        let mut actual = frame(Color::from_rgb(12, 19, 30));
+
+        let exact = compare_frames(&expected, &actual, &CompareOptions::default());
+        assert_eq!(exact.differing_pixels, LCD_WIDTH * LCD_HEIGHT);
+        assert_eq!(exact.first_difference, Some((0, 0)));
+        assert_eq!(exact.max_channel_difference, 2);
+
+        let options = CompareOptions {
+            tolerance: 2,
+            ..Default::default()
+        };
+        assert!(compare_frames(&expected, &actual, &options).is_match());
+
+        // Differences don't add up across channels.
+        actual[7][5] = Color::from_rgb(8, 22, 28);
+        assert!(compare_frames(&expected, &actual, &options).is_match());
+
+        actual[7][5] = Color::from_rgb(10, 20, 27);
+        let diff = compare_frames(&expected, &actual, &options);
+        assert_eq!(
+            diff,
+            FrameDiff {
+                differing_pixels: 1,
+                max_channel_difference: 3,
+                first_difference: Some((5, 7)),
+            }
+        );
+        assert_eq!(
+            diff.to_string(),
+            "1 pixels differ, the first one at (5, 7), by up to 3 per channel"
+        );
+    }
+
+    #[test]
+    fn ignore_border() {
+        let expected = frame(Color::default());
+        let mut actual = frame(Color::default());
+        actual[0][0] = Color::from_rgb(31, 31, 31);
+        actual[LCD_HEIGHT - 2][LCD_WIDTH - 2] = Color::from_rgb(31, 31, 31);
+
+        let options = CompareOptions {
+            ignore_border: 1,
+            ..Default::default()
+        };
+        let diff = compare_frames(&expected, &actual, &options);
+        assert_eq!(diff.first_difference, Some((LCD_WIDTH - 2, LCD_HEIGHT - 2)));
+
+        let options = CompareOptions {
+            ignore_border: 2,
+            ..Default::default()
+        };
+        assert!(compare_frames(&expected, &actual, &options).is_match());
+    }
+}
@@ -1,5 +1,6 @@
 /// This module contains all the data structures used to render the GBA display.
 pub mod color;
+pub mod compare;
 pub mod gba_lcd;
 
 /// GBA display width
@@ -22,11 +23,3 @@ pub const MAX_COLORS_FULL_PALETTE: usize = 256;
 
 /// Number of max palettes both for BG and OBG
 pub const MAX_PALETTES_BY_TYPE: usize = 16;
-
-/// Memory info about BG palette
-pub const BG_PALETTE_ADDRESS: u32 = 0x0500_0000;
-pub const _BG_PALETTE_SIZE: usize = 0x200;
-
-/// Memory info about OBJ palette
-pub const OBJ_PALETTE_ADDRESS: u32 = 0x0500_0200;
-pub const _OBJ_PALETTE_SIZE: usize = 0x200;
@@ -0,0 +1,144 @@
+//! Replacement for the BIOS image, generated at startup, to boot games when
+//! `gba_bios.bin` isn't around.
+//!
+//! It only does what the hardware needs from the BIOS: the exception vectors, the
+//! stacks and the IRQ handler that calls the game's one from 0x03007FFC, then it jumps
+//! to the cartridge without the boot animation. The functions of the BIOS are left to
+//! the HLE (see [`EmuConfig::bios_hle`](crate::config::EmuConfig::bios_hle)), the SWIs
+//! it doesn't emulate return right away.
+
+use crate::config::EmuConfig;
+use crate::cpu::asm::ArmAsm;
+use crate::memory_map::BIOS_SIZE;
+
+const RESET: u32 = 0x20;
+const IRQ: u32 = 0x80;
+const EXCEPTION_RETURN: u32 = 0xC0;
+const ABORT_RETURN: u32 = 0xC4;
+
+/// Entry points of the eight exception vectors, from 0x00 to 0x1C.
+const VECTORS: [u32; 8] = [
+    RESET,
+    EXCEPTION_RETURN,
+    EXCEPTION_RETURN,
+    ABORT_RETURN,
+    ABORT_RETURN,
+    RESET,
+    IRQ,
+    ABORT_RETURN,
+];
+
+/// R0-R3, R12 and LR, saved around the game's IRQ handler.
+const IRQ_SAVED_REGISTERS: u16 = 0b0101_0000_0000_1111;
+
+/// Builds the replacement BIOS image.
+#[must_use]
+pub fn replacement_bios() -> [u8; BIOS_SIZE] {
+    let mut bios = [0; BIOS_SIZE];
+
+    // Every vector branches: the instruction after the vector isn't part of the handler.
+    for (address, target) in (0..).step_by(4).zip(VECTORS) {
+        place(&mut bios, address, &[branch(address, target)]);
+    }
+
+    place(
+        &mut bios,
+        RESET,
+        &[
+            // IRQ, Supervisor then System mode stacks, as left by the BIOS.
+            ArmAsm::mov(0).imm(0xD2),
+            ArmAsm::msr(0),
+            ArmAsm::mov(13).imm(0x0300_0000),
+            ArmAsm::add(13, 13).imm(0x7F00),
+            ArmAsm::add(13, 13).imm(0xA0),
+            ArmAsm::mov(0).imm(0xD3),
+            ArmAsm::msr(0),
+            ArmAsm::mov(13).imm(0x0300_0000),
+            ArmAsm::add(13, 13).imm(0x7F00),
+            ArmAsm::add(13, 13).imm(0xE0),
+            ArmAsm::mov(0).imm(0x1F),
+            ArmAsm::msr(0),
+            ArmAsm::mov(13).imm(0x0300_0000),
+            ArmAsm::add(13, 13).imm(0x7F00),
+            ArmAsm::mov(0).imm(0),
+            ArmAsm::mov(14).imm(0x0800_0000),
+            ArmAsm::bx(14),
+        ],
+    );
+
+    place(
+        &mut bios,
+        IRQ,
+        &[
+            ArmAsm::stm(13, IRQ_SAVED_REGISTERS)
+                .pre()
+                .down()
+                .write_back(),
+            ArmAsm::mov(0).imm(0x0300_0000),
+            ArmAsm::add(0, 0).imm(0x8000),
+            // Returns to the next-but-one instruction, after the jump.
+            ArmAsm::add(14, 15).imm(0),
+            ArmAsm::ldr(15).base(0).offset(-4),
+            ArmAsm::ldm(13, IRQ_SAVED_REGISTERS).write_back(),
+            ArmAsm::sub(15, 14).imm(4).set_flags(),
+        ],
+    );
+
+    place(
+        &mut bios,
+        EXCEPTION_RETURN,
+        &[ArmAsm::mov(15).reg(14).set_flags()],
+    );
+    // Aborts skip the instruction that failed instead of retrying it forever.
+    place(
+        &mut bios,
+        ABORT_RETURN,
+        &[ArmAsm::sub(15, 14).imm(4).set_flags()],
+    );
+
+    bios
+}
+
+/// Options to run the replacement BIOS: it needs the HLE of the BIOS functions.
+#[must_use]
+pub fn replacement_config() -> EmuConfig {
+    EmuConfig {
+        bios_hle: true,
+        ..EmuConfig::default()
+    }
+}
+
+const fn branch(address: u32, target: u32) -> ArmAsm {
+    ArmAsm::b(target as i32 - (address as i32 + 8))
+}
+
+fn place(bios: &mut [u8], address: u32, instructions: &[ArmAsm]) {
+    for (index, instruction) in instructions.iter().enumerate() {
+        let start = address as usize + index * 4;
+        bios[start..start + 4].copy_from_slice(&instruction.encode().to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::Bus;
+    use crate::cpu::arm7tdmi::Arm7tdmi;
+    use crate::cpu::hardware::internal_memory::InternalMemory;
+
+    #[test]
+    fn boots_into_the_cartridge() {
+        let memory = InternalMemory::new(replacement_bios(), vec![0; 0x200]);
+        let mut cpu = Arm7tdmi::new(Bus::with_memory(memory));
+
+        for _ in 0..40 {
+            cpu.step();
+            if cpu.registers.program_counter() >= 0x0800_0000 {
+                break;
+            }
+        }
+
+        assert!((0x0800_0000..0x0800_0010).contains(&cpu.registers.program_counter()));
+        assert_eq!(cpu.registers.register_at(13), 0x0300_7F00);
+    }
+}
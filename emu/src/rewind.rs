@@ -0,0 +1,220 @@
+//! Rewind: save-states taken every few frames while playing, to go back in time while a
+//! key is held.
+//!
+//! A minute of snapshots would take over a hundred megabytes as they are. Only the newest
+//! one is kept whole, every older one is stored as its difference with the following
+//! one: the XOR of two states a few frames apart is mostly zeros, which the run-length
+//! encoding of the save-states shrinks to a few kilobytes. Going back undoes the
+//! differences from the newest, and dropping the oldest snapshot needs none of the
+//! others.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::cpu::arm7tdmi::Arm7tdmi;
+use crate::save_state::{self, SaveStateError};
+
+/// Frames between two snapshots by default.
+pub const DEFAULT_INTERVAL: u32 = 10;
+
+/// Snapshots kept by default, a minute at the default interval.
+pub const DEFAULT_CAPACITY: usize = 360;
+
+#[derive(Debug)]
+pub enum RewindError {
+    /// The snapshots aren't taken, see [`Gba::set_rewind`](crate::gba::Gba::set_rewind).
+    Disabled,
+    /// Every snapshot was already gone back to.
+    Empty,
+    State(SaveStateError),
+}
+
+impl fmt::Display for RewindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disabled => write!(f, "rewind is disabled"),
+            Self::Empty => write!(f, "can't rewind further"),
+            Self::State(e) => write!(f, "can't rewind: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RewindError {}
+
+impl From<SaveStateError> for RewindError {
+    fn from(e: SaveStateError) -> Self {
+        Self::State(e)
+    }
+}
+
+pub struct RewindBuffer {
+    interval: u32,
+    capacity: usize,
+    /// Frames since the newest snapshot.
+    frames: u32,
+    newest: Option<Vec<u8>>,
+    /// Differences giving back the older snapshots, oldest first, see [`delta`].
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl Default for RewindBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_INTERVAL, DEFAULT_CAPACITY)
+    }
+}
+
+impl RewindBuffer {
+    /// Keeps up to `capacity` snapshots, one every `interval` frames.
+    ///
+    /// # Panics
+    /// If `interval` or `capacity` is 0.
+    #[must_use]
+    pub fn new(interval: u32, capacity: usize) -> Self {
+        assert!(interval > 0 && capacity > 0, "empty rewind buffer");
+
+        Self {
+            interval,
+            capacity,
+            frames: 0,
+            newest: None,
+            deltas: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    #[must_use]
+    pub const fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// Snapshots that can be gone back to.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.newest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    /// Bytes taken by the snapshots.
+    #[must_use]
+    pub fn memory_usage(&self) -> usize {
+        self.newest.as_ref().map_or(0, Vec::len) + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames = 0;
+        self.newest = None;
+        self.deltas.clear();
+    }
+
+    /// To be called at the start of each frame, it takes the snapshots.
+    ///
+    /// # Errors
+    /// It fails if the state of the CPU can't be serialized.
+    pub(crate) fn on_frame(&mut self, cpu: &Arm7tdmi) -> Result<(), SaveStateError> {
+        self.frames += 1;
+        if self.frames < self.interval && self.newest.is_some() {
+            return Ok(());
+        }
+        self.frames = 0;
+
+        let state = save_state::encode_uncompressed(cpu)?;
+        if let Some(previous) = &self.newest {
+            if self.deltas.len() + 1 == self.capacity {
+                self.deltas.pop_front();
+            }
+            self.deltas
+                .push_back(save_state::compress(&delta(previous, &state)));
+        }
+        self.newest = Some(state);
+
+        Ok(())
+    }
+
+    /// Takes the newest snapshot out, the one before becomes the newest. The next
+    /// snapshot is taken a whole interval later, so that running a frame to show the
+    /// restored state doesn't take it again.
+    pub(crate) fn pop(&mut self) -> Option<Vec<u8>> {
+        let newest = self.newest.take()?;
+        self.newest = self
+            .deltas
+            .pop_back()
+            .and_then(|delta| save_state::decompress(&delta))
+            .and_then(|delta| undo_delta(&newest, &delta));
+        if self.newest.is_none() {
+            // A damaged delta can't give the older snapshots back either.
+            self.deltas.clear();
+        }
+        self.frames = 0;
+
+        Some(newest)
+    }
+}
+
+/// What gives `older` back from `newer`: the length of `older`, then the XOR of both,
+/// the shorter one padded with zeros.
+// Save-states are far below 4 GB.
+#[allow(clippy::cast_possible_truncation)]
+fn delta(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let len = older.len().max(newer.len());
+    let mut out = Vec::with_capacity(4 + len);
+    out.extend_from_slice(&(older.len() as u32).to_le_bytes());
+    out.extend((0..len).map(|i| older.get(i).unwrap_or(&0) ^ newer.get(i).unwrap_or(&0)));
+
+    out
+}
+
+/// Reverses [`delta`], `None` if it's too short.
+fn undo_delta(newer: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let (len, xor) = delta.split_first_chunk::<4>()?;
+    let len = u32::from_le_bytes(*len) as usize;
+    if xor.len() < len {
+        return None;
+    }
+
+    Some(
+        xor[..len]
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ newer.get(i).unwrap_or(&0))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn deltas_give_the_older_state_back() {
+        let older = [1, 2, 3, 4, 5];
+        for newer in [&[1, 2, 9, 4, 5][..], &[1, 2], &[1, 2, 3, 4, 5, 6, 7]] {
+            assert_eq!(undo_delta(newer, &delta(&older, newer)).unwrap(), older);
+        }
+        assert_eq!(undo_delta(&older, &[1, 0]), None);
+    }
+
+    #[test]
+    fn keeps_the_last_snapshots() {
+        let mut buffer = RewindBuffer::new(2, 3);
+        let mut cpu = Arm7tdmi::default();
+        for frame in 0..10 {
+            cpu.registers.set_register_at(0, frame);
+            buffer.on_frame(&cpu).unwrap();
+        }
+
+        // Taken at the frames 0, 2, 4, 6 and 8, the last 3 kept.
+        assert_eq!(buffer.len(), 3);
+        for frame in [8, 6, 4] {
+            let mut restored = Arm7tdmi::default();
+            save_state::decode(&mut restored, &buffer.pop().unwrap()).unwrap();
+            assert_eq!(restored.registers.register_at(0), frame);
+        }
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.pop(), None);
+    }
+}
@@ -0,0 +1,211 @@
+//! Summary of an emulation session, formatted to be pasted in a GitHub issue.
+//!
+//! Nothing is sent anywhere: the frontend shows or prints the report and the user
+//! decides what to share.
+
+use std::{fmt, time::Duration};
+
+use crate::{
+    clock::{self, CYCLES_PER_FRAME},
+    cpu::{
+        bios_hle::HleShortcut,
+        execution_trap::{ExecutionTrap, MisalignedPc},
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunReport {
+    pub game_title: String,
+    pub game_code: String,
+    /// Frames completed since the emulator was created, save-state loads don't reset it.
+    pub frames: u64,
+    /// Time the emulation actually ran for, measured by the frontend.
+    pub running_time: Duration,
+    /// I/O addresses written by the game that no register is mapped to yet.
+    pub unmapped_io_writes: Vec<usize>,
+    /// BIOS functions run in Rust instead of the BIOS code, without its timing.
+    pub hle_shortcuts: Vec<HleShortcut>,
+    /// The first execution traps, empty without the `debug-hooks` feature.
+    pub execution_traps: Vec<ExecutionTrap>,
+    /// Fetches whose program counter had to be aligned, and the first of them.
+    pub misaligned_pc_count: u64,
+    pub first_misaligned_pc: Option<MisalignedPc>,
+}
+
+impl RunReport {
+    /// Time the completed frames last on hardware.
+    #[must_use]
+    pub fn emulated_time(&self) -> Duration {
+        clock::cycles_to_duration(u128::from(self.frames) * u128::from(CYCLES_PER_FRAME))
+    }
+
+    /// Emulation speed compared to hardware (1.0 is full speed), `None` if the
+    /// emulation never ran.
+    #[must_use]
+    pub fn average_speed(&self) -> Option<f64> {
+        if self.running_time.is_zero() {
+            return None;
+        }
+
+        Some(self.emulated_time().as_secs_f64() / self.running_time.as_secs_f64())
+    }
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "### Run report")?;
+        writeln!(f)?;
+        writeln!(f, "- **ROM:** {} ({})", self.game_title, self.game_code)?;
+        writeln!(
+            f,
+            "- **Emulator:** clementine {} on {} {}",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        )?;
+        writeln!(
+            f,
+            "- **Frames:** {} ({:.1}s emulated)",
+            self.frames,
+            self.emulated_time().as_secs_f64()
+        )?;
+
+        match self.average_speed() {
+            Some(speed) => writeln!(
+                f,
+                "- **Average speed:** {:.0}% over {:.1}s",
+                speed * 100.0,
+                self.running_time.as_secs_f64()
+            )?,
+            None => writeln!(f, "- **Average speed:** not run")?,
+        }
+
+        if self.unmapped_io_writes.is_empty() {
+            writeln!(f, "- **Unmapped I/O writes:** none")?;
+        } else {
+            let addresses = self
+                .unmapped_io_writes
+                .iter()
+                .map(|address| format!("`0x{address:08X}`"))
+                .collect::<Vec<_>>();
+            writeln!(f, "- **Unmapped I/O writes:** {}", addresses.join(", "))?;
+        }
+
+        if self.hle_shortcuts.is_empty() {
+            writeln!(f, "- **HLE shortcuts:** none")?;
+        } else {
+            let shortcuts = self
+                .hle_shortcuts
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            writeln!(f, "- **HLE shortcuts:** {}", shortcuts.join(", "))?;
+        }
+
+        if self.execution_traps.is_empty() {
+            writeln!(f, "- **Execution traps:** none")?;
+        } else {
+            writeln!(f, "- **Execution traps:**")?;
+            for trap in &self.execution_traps {
+                writeln!(f, "  - {trap}")?;
+            }
+        }
+
+        match self.first_misaligned_pc {
+            Some(first) => writeln!(
+                f,
+                "- **Misaligned PC:** {} fetches, first {first}",
+                self.misaligned_pc_count
+            ),
+            None => writeln!(f, "- **Misaligned PC:** none"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::cpu::execution_trap::NonExecutableRegion;
+
+    #[test]
+    fn markdown() {
+        let mut report = RunReport {
+            game_title: "TEST".to_string(),
+            game_code: "ATSE".to_string(),
+            frames: 0,
+            running_time: Duration::ZERO,
+            unmapped_io_writes: Vec::new(),
+            hle_shortcuts: Vec::new(),
+            execution_traps: Vec::new(),
+            misaligned_pc_count: 0,
+            first_misaligned_pc: None,
+        };
+        let emulator = format!(
+            "clementine {} on {} {}",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "### Run report\n\n\
+                 - **ROM:** TEST (ATSE)\n\
+                 - **Emulator:** {emulator}\n\
+                 - **Frames:** 0 (0.0s emulated)\n\
+                 - **Average speed:** not run\n\
+                 - **Unmapped I/O writes:** none\n\
+                 - **HLE shortcuts:** none\n\
+                 - **Execution traps:** none\n\
+                 - **Misaligned PC:** none\n"
+            )
+        );
+
+        // About 60 frames per second, emulated in twice the time.
+        report.frames = 600;
+        report.running_time = Duration::from_secs(20);
+        report.unmapped_io_writes = vec![0x0400_0060, 0x0400_0062];
+        report.hle_shortcuts = vec![
+            HleShortcut {
+                swi: 0x05,
+                calls: 60,
+            },
+            HleShortcut {
+                swi: 0x0B,
+                calls: 2,
+            },
+        ];
+        report.execution_traps = vec![ExecutionTrap {
+            address: 0x0400_0000,
+            region: NonExecutableRegion::Io,
+            jump_source: Some(0x0800_0100),
+            cycle: 42,
+        }];
+        report.misaligned_pc_count = 3;
+        report.first_misaligned_pc = Some(MisalignedPc {
+            address: 0x0800_0102,
+            thumb: false,
+            jump_source: Some(0x0800_00F0),
+            cycle: 40,
+        });
+
+        let markdown = report.to_string();
+        let lines = markdown.lines().skip(4).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "- **Frames:** 600 (10.0s emulated)",
+                "- **Average speed:** 50% over 20.0s",
+                "- **Unmapped I/O writes:** `0x04000060`, `0x04000062`",
+                "- **HLE shortcuts:** VBlankIntrWait (SWI 0x05) x60, CpuSet (SWI 0x0B) x2",
+                "- **Execution traps:**",
+                "  - executing from I/O registers at 0x04000000 (cycle 42), jumped from 0x08000100",
+                "- **Misaligned PC:** 3 fetches, first fetching ARM code from misaligned \
+                 0x08000102 (cycle 40), jumped from 0x080000F0",
+            ]
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! Battery save profiles: several independent saves of the same game, such as a
+//! speedrun practice file next to the main one.
+//!
+//! Every game gets a directory named after its title and code, holding a raw `.sav`
+//! file per profile:
+//! ```text
+//! saves/POKEMON_RUBY-AXVE/default.sav
+//! saves/POKEMON_RUBY-AXVE/speedrun practice.sav
+//! ```
+//! The profile used by a run is chosen when the cartridge is inserted, see
+//! [`EmuConfig::save_profile`](crate::config::EmuConfig::save_profile).
+//!
+//! The games with a real-time clock get a `.rtc` file next to the `.sav`, with how far
+//! the clock is from the host time, see [`RtcClock`](crate::cpu::hardware::rtc::RtcClock).
+//!
+//! Profiles are written atomically with their previous versions kept next to them, see
+//! [`atomic_file`].
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    atomic_file::{self, DEFAULT_BACKUPS},
+    cartridge_header::CartridgeHeader,
+};
+
+/// Profile used when none is chosen.
+pub const DEFAULT_PROFILE: &str = "default";
+
+const EXTENSION: &str = "sav";
+const CLOCK_EXTENSION: &str = "rtc";
+
+#[derive(Debug)]
+pub enum ProfileError {
+    /// The name is empty, too long, or has characters that can't be in a file name.
+    InvalidName(String),
+    NotFound(String),
+    AlreadyExists(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(f, "invalid save profile name `{name}`"),
+            Self::NotFound(name) => write!(f, "no save profile `{name}`"),
+            Self::AlreadyExists(name) => write!(f, "save profile `{name}` already exists"),
+            Self::Io(e) => write!(f, "can't access the save profiles: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+impl From<io::Error> for ProfileError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Whether `name` can be used as a profile: letters, digits, spaces, `-` and `_`, up to
+/// 64 characters.
+#[must_use]
+pub fn is_valid_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+}
+
+/// The save profiles of a game.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SaveProfiles {
+    dir: PathBuf,
+    /// Previous versions kept of each profile.
+    backups: usize,
+}
+
+impl SaveProfiles {
+    /// Profiles of the game of `header`, in its directory under `root`.
+    #[must_use]
+    pub fn new(root: &Path, header: &CartridgeHeader) -> Self {
+        let sanitize = |text: &str| {
+            text.trim_end_matches('\0')
+                .trim()
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect::<String>()
+        };
+
+        Self {
+            dir: root.join(format!(
+                "{}-{}",
+                sanitize(&header.game_title),
+                sanitize(&header.game_code)
+            )),
+            backups: DEFAULT_BACKUPS,
+        }
+    }
+
+    /// Keeps `backups` previous versions of each profile, [`DEFAULT_BACKUPS`] otherwise.
+    #[must_use]
+    pub const fn with_backups(mut self, backups: usize) -> Self {
+        self.backups = backups;
+        self
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf, ProfileError> {
+        if !is_valid_name(name) {
+            return Err(ProfileError::InvalidName(name.to_string()));
+        }
+
+        Ok(self.dir.join(name).with_extension(EXTENSION))
+    }
+
+    fn clock_path(&self, name: &str) -> Result<PathBuf, ProfileError> {
+        Ok(self.path(name)?.with_extension(CLOCK_EXTENSION))
+    }
+
+    fn existing_path(&self, name: &str) -> Result<PathBuf, ProfileError> {
+        let path = self.path(name)?;
+        if !path.is_file() {
+            return Err(ProfileError::NotFound(name.to_string()));
+        }
+
+        Ok(path)
+    }
+
+    fn new_path(&self, name: &str) -> Result<PathBuf, ProfileError> {
+        let path = self.path(name)?;
+        if path.exists() {
+            return Err(ProfileError::AlreadyExists(name.to_string()));
+        }
+
+        Ok(path)
+    }
+
+    /// Names of the profiles, sorted. A game never saved has none.
+    ///
+    /// # Errors
+    /// It fails if the directory of the game can't be listed.
+    pub fn list(&self) -> Result<Vec<String>, ProfileError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut names = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == EXTENSION)
+            {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+
+        Ok(names)
+    }
+
+    /// Content of the profile, `None` if it was never saved.
+    ///
+    /// # Errors
+    /// It fails if the name is invalid or the file can't be read.
+    pub fn load(&self, name: &str) -> Result<Option<Vec<u8>>, ProfileError> {
+        match fs::read(self.path(name)?) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Content of the backup `generation` of the profile, 1 for the version before the
+    /// last write, `None` if there's no such backup.
+    ///
+    /// # Errors
+    /// It fails if the name is invalid or the file can't be read.
+    pub fn load_backup(
+        &self,
+        name: &str,
+        generation: usize,
+    ) -> Result<Option<Vec<u8>>, ProfileError> {
+        match fs::read(atomic_file::backup_path(&self.path(name)?, generation)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the content of the profile, creating it if needed. The previous content is
+    /// kept as the latest backup.
+    ///
+    /// # Errors
+    /// It fails if the name is invalid or the file can't be written, the profile then
+    /// keeps its previous content.
+    pub fn store(&self, name: &str, data: &[u8]) -> Result<(), ProfileError> {
+        let path = self.path(name)?;
+        fs::create_dir_all(&self.dir)?;
+        atomic_file::write(&path, data, self.backups)?;
+
+        Ok(())
+    }
+
+    /// Offset of the real-time clock of the profile, `None` if the game never set it.
+    ///
+    /// # Errors
+    /// It fails if the name is invalid or the file can't be read.
+    pub fn load_clock(&self, name: &str) -> Result<Option<i64>, ProfileError> {
+        match fs::read_to_string(self.clock_path(name)?) {
+            Ok(offset) => offset.trim().parse().map(Some).map_err(|_| {
+                ProfileError::Io(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid clock offset `{}`", offset.trim()),
+                ))
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the offset of the real-time clock of the profile.
+    ///
+    /// # Errors
+    /// It fails if the name is invalid or the file can't be written.
+    pub fn store_clock(&self, name: &str, offset: i64) -> Result<(), ProfileError> {
+        let path = self.clock_path(name)?;
+        fs::create_dir_all(&self.dir)?;
+        atomic_file::write(&path, offset.to_string().as_bytes(), 0)?;
+
+        Ok(())
+    }
+
+    /// Creates the profile `to` with the content of `from`, and its clock.
+    ///
+    /// # Errors
+    /// It fails if `from` doesn't exist or `to` does.
+    pub fn copy(&self, from: &str, to: &str) -> Result<(), ProfileError> {
+        fs::copy(self.existing_path(from)?, self.new_path(to)?)?;
+        let clock = self.clock_path(from)?;
+        if clock.is_file() {
+            fs::copy(clock, self.clock_path(to)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// The backups and the clock are renamed with the profile.
+    ///
+    /// # Errors
+    /// It fails if `from` doesn't exist or `to` does.
+    pub fn rename(&self, from: &str, to: &str) -> Result<(), ProfileError> {
+        let clocks = (self.clock_path(from)?, self.clock_path(to)?);
+        let (from, to) = (self.existing_path(from)?, self.new_path(to)?);
+        if clocks.0.is_file() {
+            fs::rename(clocks.0, clocks.1)?;
+        }
+        for (generation, backup) in atomic_file::existing_backups(&from).iter().enumerate() {
+            fs::rename(backup, atomic_file::backup_path(&to, generation + 1))?;
+        }
+        fs::rename(from, to)?;
+
+        Ok(())
+    }
+
+    /// The backups and the clock are removed with the profile.
+    ///
+    /// # Errors
+    /// It fails if the profile doesn't exist or can't be removed.
+    pub fn delete(&self, name: &str) -> Result<(), ProfileError> {
+        let path = self.existing_path(name)?;
+        let clock = self.clock_path(name)?;
+        if clock.is_file() {
+            fs::remove_file(clock)?;
+        }
+        for backup in atomic_file::existing_backups(&path) {
+            fs::remove_file(backup)?;
+        }
+        fs::remove_file(path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiles(test: &str) -> SaveProfiles {
+        let root = std::env::temp_dir().join(format!("clementine-profiles-{test}"));
+        let _ = fs::remove_dir_all(&root);
+
+        SaveProfiles {
+            dir: root.join("TEST-0000"),
+            backups: DEFAULT_BACKUPS,
+        }
+    }
+
+    #[test]
+    fn names() {
+        assert!(is_valid_name("speedrun practice"));
+        assert!(is_valid_name("kid_2"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("  "));
+        assert!(!is_valid_name("../escape"));
+        assert!(!is_valid_name("a/b"));
+        assert!(!is_valid_name(&"a".repeat(65)));
+    }
+
+    #[test]
+    fn manage_profiles() {
+        let profiles = profiles("manage");
+        assert!(profiles.list().unwrap().is_empty());
+        assert!(profiles.load(DEFAULT_PROFILE).unwrap().is_none());
+
+        profiles.store(DEFAULT_PROFILE, &[1, 2, 3]).unwrap();
+        profiles.copy(DEFAULT_PROFILE, "practice").unwrap();
+        profiles.store("practice", &[4]).unwrap();
+        assert_eq!(profiles.list().unwrap(), ["default", "practice"]);
+        assert_eq!(profiles.load(DEFAULT_PROFILE).unwrap(), Some(vec![1, 2, 3]));
+
+        assert!(matches!(
+            profiles.rename("practice", DEFAULT_PROFILE),
+            Err(ProfileError::AlreadyExists(_))
+        ));
+        profiles.rename("practice", "kid").unwrap();
+        assert_eq!(profiles.load("kid").unwrap(), Some(vec![4]));
+        assert_eq!(profiles.load_backup("kid", 1).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(profiles.load_backup("kid", 2).unwrap(), None);
+
+        profiles.delete(DEFAULT_PROFILE).unwrap();
+        assert!(matches!(
+            profiles.delete(DEFAULT_PROFILE),
+            Err(ProfileError::NotFound(_))
+        ));
+        assert_eq!(profiles.list().unwrap(), ["kid"]);
+        assert!(matches!(
+            profiles.store("../kid", &[]),
+            Err(ProfileError::InvalidName(_))
+        ));
+
+        fs::remove_dir_all(profiles.dir().parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn clock_follows_the_profile() {
+        let profiles = profiles("clock");
+        assert!(profiles.load_clock(DEFAULT_PROFILE).unwrap().is_none());
+
+        profiles.store(DEFAULT_PROFILE, &[1]).unwrap();
+        profiles.store_clock(DEFAULT_PROFILE, -3600).unwrap();
+        assert_eq!(profiles.load_clock(DEFAULT_PROFILE).unwrap(), Some(-3600));
+        assert_eq!(profiles.list().unwrap(), [DEFAULT_PROFILE]);
+
+        profiles.copy(DEFAULT_PROFILE, "copy").unwrap();
+        profiles.rename(DEFAULT_PROFILE, "renamed").unwrap();
+        assert_eq!(profiles.load_clock("copy").unwrap(), Some(-3600));
+        assert_eq!(profiles.load_clock("renamed").unwrap(), Some(-3600));
+        assert!(profiles.load_clock(DEFAULT_PROFILE).unwrap().is_none());
+
+        profiles.delete("copy").unwrap();
+        assert!(profiles.load_clock("copy").unwrap().is_none());
+
+        fs::remove_dir_all(profiles.dir().parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,756 @@
+//! Sectioned save-state container.
+//!
+//! A save-state is made of a small header followed by one section per
+//! emulated component. Every section carries its own CRC-32 so that a
+//! truncated or damaged file can still be partially recovered: intact
+//! sections are restored and the damaged ones are reported back to the
+//! caller instead of being deserialized into the CPU.
+//!
+//! Layout (all integers little endian):
+//! ```text
+//! magic "CLMS" | version: u16 | section count: u16
+//! repeated: name len: u8 | name | payload len: u32 | crc32: u32 | payload
+//! ```
+//!
+//! The payloads of the sections are compressed with a run-length encoding, the work
+//! RAMs and VRAM being mostly runs of the same byte. The checksum covers the stored,
+//! compressed bytes. States written by older versions of the format are upgraded when
+//! loaded, see [`OLDEST_VERSION`].
+//!
+//! Tools can store their own data as [`Attachment`]s, sections with names of their own
+//! that the emulator doesn't read: a memory search keeps its candidates across state
+//! loads and restarts this way.
+
+use std::{borrow::Cow, fmt, fs, io, path::Path};
+
+use crate::{
+    atomic_file::{self, DEFAULT_BACKUPS},
+    cpu::{arm7tdmi::Arm7tdmi, hardware::dma::Dma},
+};
+
+const MAGIC: &[u8; 4] = b"CLMS";
+
+/// Version of the container layout, bumped on incompatible changes.
+pub const FORMAT_VERSION: u16 = 13;
+
+/// Oldest version that can still be loaded, its sections are upgraded when read.
+pub const OLDEST_VERSION: u16 = 10;
+
+/// The last version storing the payloads uncompressed.
+const UNCOMPRESSED_VERSION: u16 = 10;
+
+/// Set in the version written by [`encode_uncompressed`], whose payloads are stored as
+/// they are.
+const UNCOMPRESSED_FLAG: u16 = 0x8000;
+
+/// The first version storing the transfers of the DMA channels, not only their registers.
+const DMA_TRANSFERS_VERSION: u16 = 12;
+
+/// The first version storing whether the CPU is in an emulated `IntrWait`.
+const INTR_WAIT_VERSION: u16 = 13;
+
+/// Every component stored in its own section of the save-state.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Section {
+    Cpu,
+    Bus,
+    InternalMemory,
+    Peripherals,
+    Lcd,
+    Sound,
+    Dma,
+    Timers,
+    Serial,
+    Keypad,
+    InterruptControl,
+}
+
+impl Section {
+    pub const ALL: [Self; 11] = [
+        Self::Cpu,
+        Self::Bus,
+        Self::InternalMemory,
+        Self::Peripherals,
+        Self::Lcd,
+        Self::Sound,
+        Self::Dma,
+        Self::Timers,
+        Self::Serial,
+        Self::Keypad,
+        Self::InterruptControl,
+    ];
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Cpu => "cpu",
+            Self::Bus => "bus",
+            Self::InternalMemory => "internal_memory",
+            Self::Peripherals => "peripherals",
+            Self::Lcd => "lcd",
+            Self::Sound => "sound",
+            Self::Dma => "dma",
+            Self::Timers => "timers",
+            Self::Serial => "serial",
+            Self::Keypad => "keypad",
+            Self::InterruptControl => "interrupt_control",
+        }
+    }
+
+    fn from_name(name: &[u8]) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|section| section.name().as_bytes() == name)
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Why a section could not be restored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SectionDamage {
+    /// The stored checksum doesn't match the payload.
+    ChecksumMismatch,
+    /// The file ends in the middle of the section.
+    Truncated,
+    /// The section is not present at all.
+    Missing,
+    /// The checksum is fine but the payload can't be decoded.
+    Undecodable,
+}
+
+impl fmt::Display for SectionDamage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ChecksumMismatch => write!(f, "checksum mismatch"),
+            Self::Truncated => write!(f, "truncated"),
+            Self::Missing => write!(f, "missing"),
+            Self::Undecodable => write!(f, "undecodable"),
+        }
+    }
+}
+
+/// Data of a tool stored with the emulator in a save-state, see [`encode_with`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attachment {
+    /// Up to 255 bytes, and not the name of a [`Section`].
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Outcome of a load: which sections were applied and which were left untouched.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    pub restored: Vec<Section>,
+    pub damaged: Vec<(Section, SectionDamage)>,
+    /// The intact attachments, the damaged ones are dropped.
+    pub attachments: Vec<Attachment>,
+}
+
+impl LoadReport {
+    #[must_use]
+    pub const fn is_complete(&self) -> bool {
+        self.damaged.is_empty()
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_complete() {
+            return write!(f, "state loaded");
+        }
+
+        write!(f, "state partially loaded, damaged sections:")?;
+        for (section, damage) in &self.damaged {
+            write!(f, " {section} ({damage})")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveStateError {
+    /// The data doesn't start with the save-state magic.
+    NotASaveState,
+    /// The container was written with an unknown layout version.
+    UnsupportedVersion(u16),
+    /// A component could not be serialized.
+    Encode(bincode::Error),
+    /// The name of the attachment is too long or is the name of a section.
+    InvalidAttachment(String),
+    /// The file can't be read or written.
+    Io(io::Error),
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotASaveState => write!(f, "not a Clementine save-state"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported save-state version {v}"),
+            Self::Encode(e) => write!(f, "can't encode save-state: {e}"),
+            Self::InvalidAttachment(name) => write!(f, "invalid save-state attachment `{name}`"),
+            Self::Io(e) => write!(f, "can't access save-state file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+impl From<io::Error> for SaveStateError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Serializes the whole CPU (bus and hardware included) into a save-state container.
+///
+/// # Errors
+/// It fails if one of the components can't be serialized.
+pub fn encode(cpu: &Arm7tdmi) -> Result<Vec<u8>, SaveStateError> {
+    encode_with(cpu, &[])
+}
+
+/// Like [`encode`], with the `attachments` of the tools after the sections.
+///
+/// # Errors
+/// It fails if one of the components can't be serialized, or an attachment has an
+/// invalid name.
+pub fn encode_with(cpu: &Arm7tdmi, attachments: &[Attachment]) -> Result<Vec<u8>, SaveStateError> {
+    write_container(cpu, attachments, true)
+}
+
+/// Like [`encode`] without compressing the sections, for the snapshots kept in memory
+/// that are compressed their own way, see [`crate::rewind`]. It loads like any other.
+pub(crate) fn encode_uncompressed(cpu: &Arm7tdmi) -> Result<Vec<u8>, SaveStateError> {
+    write_container(cpu, &[], false)
+}
+
+// Section count, names and payloads are all far below the size of the fields storing them.
+#[allow(clippy::cast_possible_truncation)]
+fn write_container(
+    cpu: &Arm7tdmi,
+    attachments: &[Attachment],
+    compressed: bool,
+) -> Result<Vec<u8>, SaveStateError> {
+    let version = if compressed {
+        FORMAT_VERSION
+    } else {
+        FORMAT_VERSION | UNCOMPRESSED_FLAG
+    };
+    let count = Section::ALL.len() + attachments.len();
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&version.to_le_bytes());
+    out.extend_from_slice(&(count as u16).to_le_bytes());
+
+    let mut write_section = |name: &[u8], payload: &[u8]| {
+        let payload = if compressed {
+            Cow::Owned(compress(payload))
+        } else {
+            Cow::Borrowed(payload)
+        };
+        out.push(name.len() as u8);
+        out.extend_from_slice(name);
+        out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        out.extend_from_slice(&crc32(&payload).to_le_bytes());
+        out.extend_from_slice(&payload);
+    };
+
+    for section in Section::ALL {
+        let payload = cpu
+            .encode_section(section)
+            .map_err(SaveStateError::Encode)?;
+        write_section(section.name().as_bytes(), &payload);
+    }
+
+    for attachment in attachments {
+        let name = attachment.name.as_bytes();
+        if name.len() > usize::from(u8::MAX) || Section::from_name(name).is_some() {
+            return Err(SaveStateError::InvalidAttachment(attachment.name.clone()));
+        }
+        write_section(name, &attachment.data);
+    }
+
+    Ok(out)
+}
+
+/// Restores every intact section of `data` into `cpu`.
+/// Damaged sections are skipped (the component keeps its current state) and listed in the report.
+///
+/// # Errors
+/// It fails only when the header itself is not valid, nothing is touched in that case.
+pub fn decode(cpu: &mut Arm7tdmi, data: &[u8]) -> Result<LoadReport, SaveStateError> {
+    let version = check_header(data)?;
+    let count = u16::from_le_bytes([data[6], data[7]]);
+    let mut reader = Reader { data, pos: 8 };
+    let mut report = LoadReport::default();
+
+    for _ in 0..count {
+        let Some(name) = reader.take_short() else {
+            break;
+        };
+        let section = Section::from_name(name);
+
+        let (Some(len), Some(checksum)) = (reader.take_u32(), reader.take_u32()) else {
+            if let Some(section) = section {
+                report.damaged.push((section, SectionDamage::Truncated));
+            }
+            break;
+        };
+
+        let Some(payload) = reader.take(len as usize) else {
+            if let Some(section) = section {
+                report.damaged.push((section, SectionDamage::Truncated));
+            }
+            break;
+        };
+
+        let intact = crc32(payload) == checksum;
+        let payload = if intact {
+            upgrade_payload(version, section, payload)
+        } else {
+            None
+        };
+
+        // The other sections are attachments, or come from a newer writer: they are not
+        // an error.
+        let Some(section) = section else {
+            if let Some(payload) = payload {
+                report.attachments.push(Attachment {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    data: payload.into_owned(),
+                });
+            }
+            continue;
+        };
+
+        if !intact {
+            report
+                .damaged
+                .push((section, SectionDamage::ChecksumMismatch));
+        } else if payload.is_none_or(|payload| cpu.decode_section(section, &payload).is_err()) {
+            report.damaged.push((section, SectionDamage::Undecodable));
+        } else {
+            report.restored.push(section);
+        }
+    }
+
+    for section in Section::ALL {
+        let seen = report.restored.contains(&section)
+            || report
+                .damaged
+                .iter()
+                .any(|(damaged, _)| *damaged == section);
+
+        if !seen {
+            report.damaged.push((section, SectionDamage::Missing));
+        }
+    }
+
+    Ok(report)
+}
+
+/// Writes a save-state to `path`, keeping the previous ones as backups, see
+/// [`atomic_file`].
+///
+/// # Errors
+/// It fails if the file can't be written, the previous state is left in place then.
+pub fn write_file(path: &Path, data: &[u8]) -> Result<(), SaveStateError> {
+    Ok(atomic_file::write(path, data, DEFAULT_BACKUPS)?)
+}
+
+/// Reads the save-state at `path`, checking that it is one.
+///
+/// # Errors
+/// It fails if the file can't be read or doesn't start like a save-state of a
+/// supported version.
+pub fn read_file(path: &Path) -> Result<Vec<u8>, SaveStateError> {
+    let data = fs::read(path)?;
+    check_header(&data)?;
+
+    Ok(data)
+}
+
+/// Returns the format version of `data`, with the [`UNCOMPRESSED_FLAG`].
+fn check_header(data: &[u8]) -> Result<u16, SaveStateError> {
+    if data.len() < 8 || &data[0..4] != MAGIC {
+        return Err(SaveStateError::NotASaveState);
+    }
+
+    let version = u16::from_le_bytes([data[4], data[5]]);
+    if !(OLDEST_VERSION..=FORMAT_VERSION).contains(&(version & !UNCOMPRESSED_FLAG)) {
+        return Err(SaveStateError::UnsupportedVersion(version));
+    }
+
+    Ok(version)
+}
+
+/// Turns a payload of `section` stored with the format `version` into what the
+/// components read, `None` if it can't be. Every change to the format adds its upgrade
+/// here.
+fn upgrade_payload(version: u16, section: Option<Section>, stored: &[u8]) -> Option<Cow<'_, [u8]>> {
+    let payload = if version == UNCOMPRESSED_VERSION || version & UNCOMPRESSED_FLAG != 0 {
+        Cow::Borrowed(stored)
+    } else {
+        Cow::Owned(decompress(stored)?)
+    };
+
+    let version = version & !UNCOMPRESSED_FLAG;
+    match section {
+        Some(Section::Dma) if version < DMA_TRANSFERS_VERSION => {
+            Dma::upgrade_registers_only(&payload).ok().map(Cow::Owned)
+        }
+        // Older states were never saved waiting, the wait is the last field.
+        Some(Section::Cpu) if version < INTR_WAIT_VERSION => {
+            let mut payload = payload.into_owned();
+            payload.extend_from_slice(&bincode::serialize(&false).ok()?);
+            Some(Cow::Owned(payload))
+        }
+        _ => Some(payload),
+    }
+}
+
+/// Longest run of a control byte of [`compress`].
+const MAX_RUN: usize = 128;
+/// Shortest repetition worth a run, shorter ones are stored as literals.
+const MIN_REPEAT: usize = 3;
+
+/// Run-length encoding in the style of `PackBits`: a control byte below 0x80 is followed
+/// by that many bytes plus 1 copied as they are, one from 0x80 by a byte repeated that
+/// many times minus 0x80 plus [`MIN_REPEAT`].
+// The runs are at most `MAX_RUN` long, their lengths fit in the control byte.
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 4);
+    let mut literals = 0..0;
+    let flush = |out: &mut Vec<u8>, literals: &mut std::ops::Range<usize>| {
+        for chunk in data[literals.clone()].chunks(MAX_RUN) {
+            out.push((chunk.len() - 1) as u8);
+            out.extend_from_slice(chunk);
+        }
+        *literals = literals.end..literals.end;
+    };
+
+    let mut position = 0;
+    while position < data.len() {
+        let byte = data[position];
+        let repeat = data[position..]
+            .iter()
+            .take(MAX_RUN + MIN_REPEAT - 1)
+            .take_while(|&&other| other == byte)
+            .count();
+
+        if repeat >= MIN_REPEAT {
+            flush(&mut out, &mut literals);
+            out.push(0x80 | (repeat - MIN_REPEAT) as u8);
+            out.push(byte);
+            position += repeat;
+            literals = position..position;
+        } else {
+            position += 1;
+            literals.end = position;
+        }
+    }
+    flush(&mut out, &mut literals);
+
+    out
+}
+
+/// Reverses [`compress`], `None` if `data` ends in the middle of a run.
+pub(crate) fn decompress(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() * 4);
+    let mut reader = Reader { data, pos: 0 };
+    while let Some(&[control]) = reader.take(1) {
+        let control = usize::from(control);
+        if control < 0x80 {
+            out.extend_from_slice(reader.take(control + 1)?);
+        } else {
+            let byte = reader.take(1)?[0];
+            out.resize(out.len() + control - 0x80 + MIN_REPEAT, byte);
+        }
+    }
+
+    Some(out)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(len)?;
+        let slice = self.data.get(self.pos..end)?;
+        self.pos = end;
+
+        Some(slice)
+    }
+
+    /// Takes a slice prefixed by its length stored in a single byte.
+    fn take_short(&mut self) -> Option<&'a [u8]> {
+        let len = self.take(1)?[0];
+        self.take(len.into())
+    }
+
+    fn take_u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0_u32; 256];
+    let mut i: u32 = 0;
+    while i < 256 {
+        let mut crc = i;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i as usize] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE 802.3), the same used by zip and png.
+#[must_use]
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0_u32, |crc, &byte| {
+        CRC32_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::cpu::hardware::dma;
+
+    #[test]
+    fn check_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn compression_roundtrip() {
+        let mut data = vec![0; 1000];
+        data.extend((0..=255).cycle().take(300));
+        data.extend([7, 7, 1, 7, 7, 7]);
+
+        for data in [&data[..], &[], &[5], &[0; 130], &[0; 131]] {
+            let compressed = compress(data);
+            assert_eq!(decompress(&compressed).unwrap(), data);
+        }
+        assert!(compress(&data).len() < 400);
+        assert_eq!(decompress(&[0x05, 1]), None);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.registers.set_register_at(3, 0xCAFE);
+        cpu.bus.write_raw(0x0300_0010, 42);
+        let data = encode(&cpu).unwrap();
+
+        let mut restored = Arm7tdmi::default();
+        let report = decode(&mut restored, &data).unwrap();
+
+        assert!(report.is_complete());
+        assert_eq!(report.restored, Section::ALL.to_vec());
+        assert_eq!(restored.registers.register_at(3), 0xCAFE);
+        assert_eq!(restored.bus.read_raw(0x0300_0010), 42);
+    }
+
+    #[test]
+    fn cartridge_is_not_stored() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.bus.internal_memory.rom = vec![0xAA; 0x10_0000];
+        let data = encode(&cpu).unwrap();
+        assert!(data.len() < 0x10_0000);
+
+        let mut restored = Arm7tdmi::default();
+        restored.bus.internal_memory.rom = vec![1, 2, 3];
+        decode(&mut restored, &data).unwrap();
+        assert_eq!(restored.bus.internal_memory.rom, vec![1, 2, 3]);
+    }
+
+    /// Version 2 left the cartridge out of the internal memory section, version 1 states
+    /// still hold it. They are older than [`OLDEST_VERSION`]: refused before a section
+    /// is read, the emulator keeps running as it was.
+    #[test]
+    fn version_1_states_are_refused() {
+        // Only the version is read before refusing, the sections don't matter.
+        let mut data = encode(&Arm7tdmi::default()).unwrap();
+        data[4..6].copy_from_slice(&1_u16.to_le_bytes());
+
+        let mut cpu = Arm7tdmi::default();
+        cpu.registers.set_register_at(3, 0xCAFE);
+        cpu.bus.internal_memory.rom = vec![1, 2, 3];
+        assert!(matches!(
+            decode(&mut cpu, &data),
+            Err(SaveStateError::UnsupportedVersion(1))
+        ));
+        assert_eq!(cpu.registers.register_at(3), 0xCAFE);
+        assert_eq!(cpu.bus.internal_memory.rom, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn attachments_come_back() {
+        let cpu = Arm7tdmi::default();
+        let attachment = Attachment {
+            name: "memory_search".to_string(),
+            data: vec![1, 2, 3],
+        };
+        let data = encode_with(&cpu, std::slice::from_ref(&attachment)).unwrap();
+
+        let report = decode(&mut Arm7tdmi::default(), &data).unwrap();
+        assert!(report.is_complete());
+        assert_eq!(report.attachments, [attachment]);
+
+        let shadowing = Attachment {
+            name: "cpu".to_string(),
+            data: Vec::new(),
+        };
+        assert!(matches!(
+            encode_with(&cpu, &[shadowing]),
+            Err(SaveStateError::InvalidAttachment(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        let mut cpu = Arm7tdmi::default();
+
+        assert!(matches!(
+            decode(&mut cpu, b"definitely not a state"),
+            Err(SaveStateError::NotASaveState)
+        ));
+
+        let mut data = encode(&cpu).unwrap();
+        data[4] = 0xFF;
+        assert!(matches!(
+            decode(&mut cpu, &data),
+            Err(SaveStateError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn recovers_intact_sections() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.registers.set_register_at(3, 0xCAFE);
+        let mut data = encode(&cpu).unwrap();
+
+        // The cpu section is the first one: flip a byte in its payload.
+        let cpu_payload = 8 + 1 + "cpu".len() + 4 + 4;
+        data[cpu_payload] ^= 0xFF;
+        // And cut the file in the middle of the last section.
+        data.truncate(data.len() - 2);
+
+        let mut restored = Arm7tdmi::default();
+        restored.registers.set_register_at(3, 7);
+        let report = decode(&mut restored, &data).unwrap();
+
+        assert_eq!(
+            report.damaged,
+            vec![
+                (Section::Cpu, SectionDamage::ChecksumMismatch),
+                (Section::InterruptControl, SectionDamage::Truncated),
+            ]
+        );
+        assert_eq!(report.restored.len(), Section::ALL.len() - 2);
+        // The damaged cpu section was not applied.
+        assert_eq!(restored.registers.register_at(3), 7);
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation)]
+    fn upgrades_uncompressed_states() {
+        let mut cpu = Arm7tdmi::default();
+        cpu.registers.set_register_at(3, 0xCAFE);
+        let data = encode(&cpu).unwrap();
+
+        // Rewrites the state as version 10 did, without compression.
+        let mut old = data[..8].to_vec();
+        old[4..6].copy_from_slice(&10_u16.to_le_bytes());
+        let mut reader = Reader {
+            data: &data,
+            pos: 8,
+        };
+        while let Some(name) = reader.take_short() {
+            let len = reader.take_u32().unwrap();
+            reader.take_u32().unwrap();
+            let mut payload = decompress(reader.take(len as usize).unwrap()).unwrap();
+            // Only the registers of the DMA channels were stored, and not the wait.
+            if name == Section::Dma.name().as_bytes() {
+                let channels: [dma::Registers; 4] = bincode::deserialize(&payload).unwrap();
+                payload = bincode::serialize(&channels).unwrap();
+            } else if name == Section::Cpu.name().as_bytes() {
+                payload.pop();
+            }
+            old.push(name.len() as u8);
+            old.extend_from_slice(name);
+            old.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            old.extend_from_slice(&crc32(&payload).to_le_bytes());
+            old.extend_from_slice(&payload);
+        }
+        assert!(old.len() > data.len());
+
+        let mut restored = Arm7tdmi::default();
+        assert!(decode(&mut restored, &old).unwrap().is_complete());
+        assert_eq!(restored.registers.register_at(3), 0xCAFE);
+    }
+
+    #[test]
+    fn files() {
+        let dir = std::env::temp_dir().join("clementine-save-state-files");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.clm");
+
+        let data = encode(&Arm7tdmi::default()).unwrap();
+        write_file(&path, &data).unwrap();
+        assert_eq!(read_file(&path).unwrap(), data);
+
+        fs::write(&path, b"something else").unwrap();
+        assert!(matches!(
+            read_file(&path),
+            Err(SaveStateError::NotASaveState)
+        ));
+        assert!(matches!(
+            read_file(&dir.join("missing.clm")),
+            Err(SaveStateError::Io(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reports_missing_sections() {
+        let cpu = Arm7tdmi::default();
+        let data = encode(&cpu).unwrap();
+
+        let mut restored = Arm7tdmi::default();
+        let report = decode(&mut restored, &data[..8]).unwrap();
+
+        assert!(report.restored.is_empty());
+        assert_eq!(report.damaged.len(), Section::ALL.len());
+        assert!(report
+            .damaged
+            .iter()
+            .all(|(_, damage)| *damage == SectionDamage::Missing));
+    }
+}
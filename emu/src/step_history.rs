@@ -0,0 +1,172 @@
+//! History of the recent steps, to step backwards in the debugger.
+//!
+//! Keeping the state of every step would cost far too much memory. The history keeps a
+//! save-state every [`StepHistory::interval`] steps instead: stepping back loads the last
+//! save-state before the target and runs again up to it, which lands on the same state
+//! since the emulation is deterministic.
+//!
+//! The buttons are read from the host again while running up to the target: the history
+//! is meant for a paused debugger, where they don't change.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::cpu::arm7tdmi::Arm7tdmi;
+use crate::save_state::{self, SaveStateError};
+
+/// Steps between two save-states by default, about a third of a frame.
+pub const DEFAULT_INTERVAL: u64 = 100_000;
+
+/// Save-states kept by default, about 5 frames of history.
+pub const DEFAULT_CAPACITY: usize = 16;
+
+#[derive(Debug)]
+pub enum ReverseStepError {
+    /// The history isn't recorded, see
+    /// [`Gba::set_step_history`](crate::gba::Gba::set_step_history).
+    Disabled,
+    /// The history doesn't go back that far, only `available` steps.
+    TooFar {
+        available: u64,
+    },
+    State(SaveStateError),
+}
+
+impl fmt::Display for ReverseStepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disabled => write!(f, "the step history isn't recorded"),
+            Self::TooFar { available } => {
+                write!(f, "the step history only goes back {available} steps")
+            }
+            Self::State(e) => write!(f, "can't restore the step history: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ReverseStepError {}
+
+impl From<SaveStateError> for ReverseStepError {
+    fn from(e: SaveStateError) -> Self {
+        Self::State(e)
+    }
+}
+
+pub struct StepHistory {
+    interval: u64,
+    capacity: usize,
+    /// Steps executed since the history started.
+    steps: u64,
+    /// Save-states taken before the step of the given index, oldest first.
+    snapshots: VecDeque<(u64, Vec<u8>)>,
+}
+
+impl Default for StepHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_INTERVAL, DEFAULT_CAPACITY)
+    }
+}
+
+impl StepHistory {
+    /// Keeps up to `capacity` save-states, one every `interval` steps: the history goes
+    /// back at least `interval * (capacity - 1)` steps.
+    ///
+    /// # Panics
+    /// If `interval` or `capacity` is 0.
+    #[must_use]
+    pub fn new(interval: u64, capacity: usize) -> Self {
+        assert!(interval > 0 && capacity > 0, "empty step history");
+
+        Self {
+            interval,
+            capacity,
+            steps: 0,
+            snapshots: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    #[must_use]
+    pub const fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// Steps recorded since the history started.
+    #[must_use]
+    pub const fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// How many steps back the history goes.
+    #[must_use]
+    pub fn available(&self) -> u64 {
+        self.snapshots
+            .front()
+            .map_or(0, |&(index, _)| self.steps - index)
+    }
+
+    /// Bytes taken by the save-states.
+    #[must_use]
+    pub fn memory_usage(&self) -> usize {
+        self.snapshots.iter().map(|(_, state)| state.len()).sum()
+    }
+
+    /// Drops the history, after the state changed in another way than by stepping.
+    pub fn clear(&mut self) {
+        self.steps = 0;
+        self.snapshots.clear();
+    }
+
+    /// To be called before each step, it takes the save-states.
+    ///
+    /// # Errors
+    /// It fails if the state of the CPU can't be serialized.
+    pub(crate) fn before_step(&mut self, cpu: &Arm7tdmi) -> Result<(), SaveStateError> {
+        let taken = self.snapshots.back().map(|&(index, _)| index);
+        if !self.steps.is_multiple_of(self.interval) || taken == Some(self.steps) {
+            return Ok(());
+        }
+
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots
+            .push_back((self.steps, save_state::encode(cpu)?));
+
+        Ok(())
+    }
+
+    /// To be called after each step that executed, a breakpoint hit doesn't count.
+    pub(crate) const fn after_step(&mut self) {
+        self.steps += 1;
+    }
+
+    /// Goes back to the last save-state taken `steps` steps ago or before, returning it:
+    /// the caller loads it and runs up to [`Self::steps`] minus `steps`. The following
+    /// save-states are dropped, they are taken again while running.
+    ///
+    /// # Errors
+    /// It fails if the history doesn't go back that far.
+    pub(crate) fn rewind(&mut self, steps: u64) -> Result<(u64, Vec<u8>), ReverseStepError> {
+        let available = self.available();
+        if steps > available {
+            return Err(ReverseStepError::TooFar { available });
+        }
+
+        let target = self.steps - steps;
+        while self
+            .snapshots
+            .back()
+            .is_some_and(|&(index, _)| index > target)
+        {
+            self.snapshots.pop_back();
+        }
+
+        let (index, state) = self
+            .snapshots
+            .pop_back()
+            .ok_or(ReverseStepError::TooFar { available })?;
+        self.steps = index;
+
+        Ok((target, state))
+    }
+}
@@ -0,0 +1,103 @@
+//! Small cartridges for the tests and the benchmarks: a valid header and the code to run.
+//! Public only for the integration tests and the benchmarks, it's hidden from the
+//! documentation and isn't part of the API.
+//!
+//! ```
+//! use emu::{cpu::asm::ArmAsm, test_rom::TestRom};
+//!
+//! // Loops forever on the replacement BIOS.
+//! let gba = TestRom::new(0x100).code(&[ArmAsm::b(-8)]).gba();
+//! ```
+
+use crate::{
+    cartridge_header::CartridgeHeader,
+    config::EmuConfig,
+    cpu::asm::ArmAsm,
+    gba::Gba,
+    replacement_bios::{replacement_bios, replacement_config},
+};
+
+/// Where [`TestRom::code`] writes, right after the header.
+pub const CODE_OFFSET: usize = 0xC0;
+
+/// Builds the bytes of a cartridge, the complement check of the header is written by
+/// [`TestRom::build`] whatever the header holds.
+#[derive(Clone, Debug)]
+pub struct TestRom {
+    rom: Vec<u8>,
+}
+
+impl TestRom {
+    /// `size` bytes of zeros, but the entry point branching to [`CODE_OFFSET`].
+    #[must_use]
+    pub fn new(size: usize) -> Self {
+        Self::from_bytes(vec![0; size]).words(0, &[ArmAsm::b(CODE_OFFSET as i32 - 8).encode()])
+    }
+
+    /// Starts from `rom`, which must hold a whole header.
+    #[must_use]
+    pub const fn from_bytes(rom: Vec<u8>) -> Self {
+        Self { rom }
+    }
+
+    /// Writes `bytes` at `offset`.
+    #[must_use]
+    pub fn bytes(mut self, offset: usize, bytes: &[u8]) -> Self {
+        self.rom[offset..offset + bytes.len()].copy_from_slice(bytes);
+        self
+    }
+
+    /// Writes the encoded instructions `words` from `offset`.
+    #[must_use]
+    pub fn words(mut self, offset: usize, words: &[u32]) -> Self {
+        for (index, word) in words.iter().enumerate() {
+            let start = offset + index * 4;
+            self.rom[start..start + 4].copy_from_slice(&word.to_le_bytes());
+        }
+        self
+    }
+
+    /// Writes `code` from [`CODE_OFFSET`], where the entry point goes.
+    #[must_use]
+    pub fn code(self, code: &[ArmAsm]) -> Self {
+        let words = code
+            .iter()
+            .map(|instruction| instruction.encode())
+            .collect::<Vec<_>>();
+        self.words(CODE_OFFSET, &words)
+    }
+
+    /// Code counting in R0 and storing the count at the start of the work RAM, forever.
+    #[must_use]
+    pub fn counter(self) -> Self {
+        self.code(&[
+            ArmAsm::mov(1).imm(0x0300_0000),
+            ArmAsm::add(0, 0).imm(1),
+            ArmAsm::str(0).base(1),
+            ArmAsm::b(-16),
+        ])
+    }
+
+    #[must_use]
+    pub fn build(mut self) -> Vec<u8> {
+        self.rom[0xBD] = CartridgeHeader::compute_complement_check(&self.rom);
+        self.rom
+    }
+
+    /// Runs the cartridge on the replacement BIOS, see [`replacement_config`].
+    #[must_use]
+    pub fn gba(self) -> Gba {
+        self.gba_with(replacement_config())
+    }
+
+    /// Runs the cartridge on the replacement BIOS with `config`.
+    ///
+    /// # Panics
+    /// It panics if the ROM is too small to hold a header.
+    #[must_use]
+    pub fn gba_with(self, config: EmuConfig) -> Gba {
+        let rom = self.build();
+        let header = CartridgeHeader::new(&rom).unwrap();
+        Gba::with_config(header, replacement_bios(), rom, config)
+    }
+}
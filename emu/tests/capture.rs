@@ -0,0 +1,92 @@
+//! The core captures screenshots and state dumps by itself.
+
+#![cfg(all(feature = "serde", feature = "debug-hooks"))]
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use emu::{
+    capture::{Capture, CapturePolicy, CaptureReason, CaptureSink, FrameCapture},
+    cpu::asm::ArmAsm,
+    gba::Gba,
+    test_rom::TestRom,
+};
+
+/// Keeps the reason and whether a state came along.
+#[derive(Clone, Default)]
+struct Captures(Arc<Mutex<Vec<(u64, CaptureReason, bool)>>>);
+
+impl CaptureSink for Captures {
+    fn capture(&mut self, capture: &Capture<'_>) -> io::Result<()> {
+        self.0.lock().unwrap().push((
+            capture.frame,
+            capture.reason.clone(),
+            capture.state.is_some(),
+        ));
+
+        Ok(())
+    }
+}
+
+/// Runs `code` from 0x080000C0.
+fn gba(code: &[ArmAsm]) -> Gba {
+    TestRom::new(0x100).code(code).gba()
+}
+
+#[test]
+fn periodic_screenshots_on_whole_frames() {
+    let mut gba = gba(&[ArmAsm::b(-8)]);
+    let captures = Captures::default();
+    let policy = CapturePolicy {
+        // A bit less than 3 frames.
+        every: Some(Duration::from_millis(45)),
+        on_error: true,
+    };
+    gba.set_capture(Some(FrameCapture::new(policy, Box::new(captures.clone()))));
+
+    for _ in 0..10 {
+        gba.run_frame().unwrap();
+    }
+
+    let captures = captures.0.lock().unwrap().clone();
+    let frames = captures
+        .iter()
+        .map(|(frame, reason, state)| {
+            assert_eq!(*reason, CaptureReason::Periodic);
+            assert!(!state);
+            *frame
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(frames, [1, 4, 7, 10]);
+}
+
+#[test]
+fn trap_is_captured_once_with_a_state() {
+    let mut gba = gba(&[ArmAsm::mov(15).imm(0x0400_0000)]);
+    let captures = Captures::default();
+    let policy = CapturePolicy {
+        every: None,
+        on_error: true,
+    };
+    gba.set_capture(Some(FrameCapture::new(policy, Box::new(captures.clone()))));
+
+    // The BIOS boots first. Running the I/O registers as code ends in a panic, the trap
+    // stays pending for a couple of steps only.
+    let mut steps = 0;
+    while gba.cpu.trap_log().is_empty() {
+        gba.step();
+        steps += 1;
+        assert!(steps < 1000, "no trap");
+    }
+    gba.step();
+    gba.step();
+
+    let captures = captures.0.lock().unwrap().clone();
+    assert_eq!(captures.len(), 1);
+    let (_, reason, state) = &captures[0];
+    assert!(matches!(reason, CaptureReason::Trap(trap) if trap.address == 0x0400_0000));
+    assert!(state);
+}
@@ -0,0 +1,60 @@
+//! Cheat codes applied while the game runs.
+
+mod common;
+
+use common::idle;
+use emu::{cheats::codebreaker, cpu::hardware::keypad::Key, gba::Gba, notifications::Severity};
+
+fn half_word(gba: &Gba, address: u32) -> u16 {
+    u16::from_le_bytes([
+        gba.cpu.bus.read_raw(address),
+        gba.cpu.bus.read_raw(address + 1),
+    ])
+}
+
+#[test]
+fn codebreaker_codes_run_at_every_vblank() {
+    let mut gba = idle();
+    // Adds 2 to a counter while A is held, another counter keeps going up.
+    let codes = "D0000020 0001\n\
+                 E2000000 0002\n\
+                 E2000002 0001";
+    gba.add_cheat(codebreaker::parse("Counters", codes).unwrap());
+
+    for _ in 0..3 {
+        gba.run_frame().unwrap();
+    }
+    assert_eq!(half_word(&gba, 0x0200_0000), 0);
+    assert_eq!(half_word(&gba, 0x0200_0002), 3);
+
+    gba.keypad_input().set_pressed(Key::A, true);
+    for _ in 0..2 {
+        gba.run_frame().unwrap();
+    }
+    assert_eq!(half_word(&gba, 0x0200_0000), 4);
+    assert_eq!(half_word(&gba, 0x0200_0002), 5);
+
+    assert!(gba.set_cheat_enabled(0, false));
+    gba.run_frame().unwrap();
+    assert_eq!(half_word(&gba, 0x0200_0002), 5);
+
+    assert!(gba.remove_cheat(0).is_some());
+    assert!(gba.cheats().is_empty());
+}
+
+#[test]
+fn codes_for_another_game_are_flagged() {
+    let mut gba = idle();
+    let checksum = codebreaker::game_checksum(&gba.cpu.bus.internal_memory.rom);
+
+    let own = format!("0000{checksum:04X} 0001\n82000000 0001");
+    gba.add_cheat(codebreaker::parse("Own", &own).unwrap());
+    assert!(gba.take_notifications().is_empty());
+
+    let other = format!("0000{:04X} 0001\n82000000 0001", checksum ^ 1);
+    gba.add_cheat(codebreaker::parse("Other", &other).unwrap());
+    let notifications = gba.take_notifications();
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0].severity, Severity::Warning);
+    assert_eq!(gba.cheats().len(), 2);
+}
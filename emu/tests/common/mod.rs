@@ -0,0 +1,8 @@
+//! Helpers shared by the integration tests.
+
+use emu::{cpu::asm::ArmAsm, gba::Gba, test_rom::TestRom};
+
+/// Loops forever without touching memory.
+pub fn idle() -> Gba {
+    TestRom::new(0x100).code(&[ArmAsm::b(-8)]).gba()
+}
@@ -0,0 +1,51 @@
+//! Running games without a window, as CI jobs do.
+
+use emu::{
+    cpu::asm::ArmAsm,
+    gba::Gba,
+    headless::{self, HeadlessExit, HeadlessOptions},
+    test_rom::TestRom,
+};
+
+fn gba(code: &[ArmAsm]) -> Gba {
+    TestRom::new(0x100).code(code).gba()
+}
+
+#[test]
+fn runs_the_frames_and_writes_the_screenshot() {
+    let mut gba = gba(&[ArmAsm::b(-8)]);
+    let screenshot = std::env::temp_dir().join("clementine-headless.ppm");
+    let _ = std::fs::remove_file(&screenshot);
+
+    let options = HeadlessOptions {
+        frames: 3,
+        screenshot: Some(screenshot.clone()),
+        input: Some("A*2".parse().unwrap()),
+    };
+    let run = headless::run(&mut gba, &options).unwrap();
+
+    assert_eq!(run.exit, HeadlessExit::Completed);
+    assert_eq!(run.exit.code(), 0);
+    assert_eq!(run.frames, 3);
+    assert!(std::fs::read(&screenshot)
+        .unwrap()
+        .starts_with(b"P6\n240 160\n"));
+    std::fs::remove_file(&screenshot).unwrap();
+}
+
+#[cfg(feature = "debug-hooks")]
+#[test]
+fn stops_on_a_trap() {
+    // Jumps to the I/O registers.
+    let mut gba = gba(&[ArmAsm::mov(15).imm(0x0400_0000)]);
+
+    let options = HeadlessOptions {
+        frames: 10,
+        ..HeadlessOptions::default()
+    };
+    let run = headless::run(&mut gba, &options).unwrap();
+
+    assert!(matches!(run.exit, HeadlessExit::Trapped(_)), "{}", run.exit);
+    assert_eq!(run.exit.code(), 4);
+    assert!(run.frames < 10);
+}
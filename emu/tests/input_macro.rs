@@ -0,0 +1,52 @@
+//! Macros press the keys frame by frame, through the frontend's input.
+
+mod common;
+
+use common::idle;
+use emu::{cpu::hardware::keypad::Key, input_macro::InputMacro};
+
+#[test]
+fn macro_presses_keys_frame_by_frame() {
+    let mut gba = idle();
+    let input = gba.keypad_input();
+    // Held by the user, the macro leaves it alone.
+    input.set_pressed(Key::L, true);
+
+    gba.play_macro("A*2 wait +Right B -Right".parse().unwrap());
+    let keys = |names: &[Key]| {
+        names
+            .iter()
+            .fold(1 << Key::L as u16, |mask, key| mask | 1 << *key as u16)
+    };
+    assert_eq!(input.pressed_keys(), keys(&[Key::A]));
+
+    let mut pressed = Vec::new();
+    for _ in 0..5 {
+        gba.run_frame().unwrap();
+        pressed.push(input.pressed_keys());
+    }
+    assert_eq!(
+        pressed,
+        [
+            keys(&[Key::A]),
+            keys(&[]),
+            keys(&[Key::Right, Key::B]),
+            keys(&[]),
+            keys(&[]),
+        ]
+    );
+    assert!(!gba.macro_playing());
+}
+
+#[test]
+fn stopped_macro_releases_its_keys() {
+    let mut gba = idle();
+    gba.play_macro(InputMacro::new().hold(&[Key::Start]).wait(100));
+    gba.run_frame().unwrap();
+    assert!(gba.macro_playing());
+    assert_eq!(gba.keypad_input().pressed_keys(), 1 << Key::Start as u16);
+
+    gba.stop_macro();
+    assert!(!gba.macro_playing());
+    assert_eq!(gba.keypad_input().pressed_keys(), 0);
+}
@@ -0,0 +1,53 @@
+//! The bounded memory profile keeps the emulator within its budget.
+
+#![cfg(feature = "serde")]
+
+use emu::{
+    config::{EmuConfig, MemoryProfile},
+    gba::Gba,
+    memory_budget::BOUNDED_BUDGET,
+    replacement_bios::replacement_config,
+    step_history::StepHistory,
+    test_rom::TestRom,
+};
+
+/// Counts in work RAM forever, on a cartridge that saves to Flash.
+fn gba(memory_profile: MemoryProfile) -> Gba {
+    TestRom::new(0x400)
+        .counter()
+        // The biggest save memory.
+        .bytes(0x200, b"FLASH1M_V1\0")
+        .gba_with(EmuConfig {
+            memory_profile,
+            ..replacement_config()
+        })
+}
+
+#[test]
+fn bounded_profile_stays_within_budget() {
+    let mut gba = gba(MemoryProfile::Bounded);
+    gba.set_step_history(Some(StepHistory::new(10, 4)));
+    assert!(gba.step_history().is_none());
+
+    for _ in 0..1000 {
+        gba.step();
+    }
+
+    let usage = gba.memory_usage();
+    assert_eq!(usage.rom, 0x400);
+    assert_eq!(usage.save, 0x2_0000);
+    assert_eq!(usage.step_history, 0);
+    assert!(usage.without_rom() <= BOUNDED_BUDGET, "{usage}");
+}
+
+#[test]
+fn standard_profile_counts_the_step_history() {
+    let mut gba = gba(MemoryProfile::Standard);
+    gba.set_step_history(Some(StepHistory::new(10, 4)));
+
+    for _ in 0..100 {
+        gba.step();
+    }
+
+    assert!(gba.memory_usage().step_history > 0);
+}
@@ -0,0 +1,84 @@
+//! Movies replay the recorded keys exactly, whatever the host input meanwhile.
+
+#![cfg(feature = "serde")]
+
+use emu::{
+    cpu::{asm::ArmAsm, hardware::keypad::Key},
+    determinism::state_hashes,
+    gba::Gba,
+    movie::{Movie, MovieError, MovieStart, MovieStatus},
+    test_rom::TestRom,
+};
+
+/// Adds KEYINPUT to R0 and stores the sum in work RAM, forever.
+fn key_reader(title: u8) -> Gba {
+    TestRom::new(0x100)
+        .bytes(0xA0, &[title])
+        .code(&[
+            ArmAsm::mov(1).imm(0x0300_0000),
+            ArmAsm::mov(2).imm(0x0400_0000),
+            ArmAsm::add(2, 2).imm(0x100),
+            ArmAsm::ldrh(3).base(2).offset(0x30),
+            ArmAsm::add(0, 0).reg(3),
+            ArmAsm::str(0).base(1),
+            ArmAsm::b(-20),
+        ])
+        .gba()
+}
+
+/// Records 12 frames pressing A then B every few frames, returns the movie and the
+/// state at the end.
+fn record(gba: &mut Gba, start: MovieStart) -> (Movie, [u32; 11]) {
+    gba.start_recording(start).unwrap();
+    let input = gba.keypad_input();
+    for frame in 0..12 {
+        input.set_pressed(Key::A, frame % 3 == 0);
+        input.set_pressed(Key::B, frame % 5 == 0);
+        gba.run_frame().unwrap();
+    }
+    assert_eq!(gba.movie_status(), MovieStatus::Recording { frames: 12 });
+    let hashes = state_hashes(&gba.cpu).unwrap();
+
+    (gba.stop_recording().unwrap(), hashes)
+}
+
+#[test]
+fn playback_reaches_the_recorded_state() {
+    for start in [MovieStart::PowerOn, MovieStart::Now] {
+        let mut gba = key_reader(0);
+        gba.run_frame().unwrap();
+        let (movie, recorded) = record(&mut gba, start);
+        assert_eq!(movie.frames.len(), 12);
+        assert_eq!(movie.frames[0], 0b11);
+        let movie = Movie::decode(&movie.encode()).unwrap();
+
+        // The host input isn't seen while the movie plays.
+        gba.keypad_input().set_pressed(Key::Start, true);
+        gba.play_movie(&movie).unwrap();
+        for frame in 0..12 {
+            assert_eq!(
+                gba.movie_status(),
+                MovieStatus::Playing { frame, frames: 12 }
+            );
+            gba.run_frame().unwrap();
+        }
+
+        assert_eq!(state_hashes(&gba.cpu).unwrap(), recorded, "{start:?}");
+        assert_eq!(gba.movie_status(), MovieStatus::Idle);
+    }
+}
+
+#[test]
+fn movie_of_another_rom_is_refused() {
+    let (movie, _) = record(&mut key_reader(0), MovieStart::PowerOn);
+
+    let mut other = key_reader(b'X');
+    assert!(matches!(
+        other.play_movie(&movie),
+        Err(MovieError::RomMismatch { .. })
+    ));
+    assert!(matches!(
+        other.stop_recording(),
+        Err(MovieError::NotRecording)
+    ));
+}
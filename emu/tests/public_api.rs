@@ -0,0 +1,139 @@
+//! Snapshot of the stable public API, see the crate documentation.
+//!
+//! Every item is named by its path and every function is coerced to its exact signature:
+//! a breaking change to the API fails to compile here. Fix the snapshot together with a
+//! version bump of the crate, never alone.
+
+#![cfg(feature = "serde")]
+#![allow(clippy::type_complexity)]
+
+use std::{path::Path, sync::Arc, time::Duration};
+
+use emu::{
+    atomic_file,
+    battery_save::{BatterySaveError, SaveLayout},
+    cartridge_header::{CartridgeError, CartridgeHeader},
+    cartridge_info::CartridgeInfo,
+    clock::{Pacer, PacingStrategy},
+    config::{BiosError, EmuConfig, Overclock},
+    cpu::{
+        breakpoints::StepResult,
+        hardware::{
+            keypad::{Key, KeypadInput},
+            lcd::{Frame, FrameOutput},
+            sound::mixer::StereoSample,
+        },
+    },
+    events::{Event, EventKind, SubscriptionId},
+    fast_forward::{FastForward, TurboAudio},
+    frame_guard::FrameOverrun,
+    gba::{ExecutionState, FrameRun, Gba, ResetKind},
+    headless::{self, HeadlessExit, HeadlessOptions, HeadlessRun},
+    input_macro::{InputMacro, MacroParseError},
+    movie::{Movie, MovieError, MovieStart, MovieStatus},
+    notifications::Notification,
+    replacement_bios::{replacement_bios, replacement_config},
+    rewind::{RewindBuffer, RewindError},
+    save_state::{self, Attachment, LoadReport, SaveStateError},
+};
+
+#[test]
+fn gba() {
+    let _: fn(CartridgeHeader, [u8; 0x4000], Vec<u8>) -> Gba = Gba::new;
+    let _: fn(CartridgeHeader, [u8; 0x4000], Vec<u8>, EmuConfig) -> Gba = Gba::with_config;
+    let _: fn(&mut Gba) -> StepResult = Gba::step;
+    let _: fn(&mut Gba) -> Result<FrameRun, FrameOverrun> = Gba::run_frame;
+    let _: fn(&mut Gba, u64) -> Result<u128, FrameOverrun> = Gba::run_cycles;
+    let _: fn(&mut Gba, ResetKind) = Gba::reset;
+    let _: fn(&mut Gba) = Gba::pause;
+    let _: fn(&mut Gba) = Gba::resume;
+    let _: fn(&mut Gba) = Gba::frame_advance;
+    let _: fn(&Gba) -> ExecutionState = Gba::execution_state;
+    let _: fn(&Gba) -> u128 = Gba::cycles;
+    let _: fn(&Gba) -> Duration = Gba::emulated_time;
+    let _: fn(&Gba) -> FrameOutput = Gba::frame_output;
+    let _: fn(&Gba) -> u32 = Gba::audio_samples_per_frame;
+    let _: fn(&mut Gba) -> Vec<Notification> = Gba::take_notifications;
+    let _: fn(&Gba) -> &EmuConfig = Gba::config;
+    let _: fn(&mut Gba, Overclock) = Gba::set_overclock;
+    let _: fn(&mut Gba, Option<u32>) = Gba::set_frame_guard;
+    let _: fn(&Gba) -> CartridgeInfo = Gba::cartridge_info;
+    let _: fn(&mut Gba, Option<FastForward>) = Gba::set_fast_forward;
+    let _: fn(&mut Gba, EventKind, fn(&Event)) -> SubscriptionId = Gba::subscribe;
+    let _: fn(&mut Gba, SubscriptionId) -> bool = Gba::unsubscribe;
+    let _: fn(&Event) -> EventKind = Event::kind;
+    let _: fn(f64) -> FastForward = FastForward::new;
+
+    let _: fn(FrameRun) -> (Arc<Frame>, Vec<StereoSample>) =
+        |FrameRun { pixels, audio }| (pixels, audio);
+    let _ = [ResetKind::Soft, ResetKind::Restart, ResetKind::Hard];
+    let _: fn(f64, u32, TurboAudio) -> FastForward = |speed, frame_skip, audio| FastForward {
+        speed,
+        frame_skip,
+        audio,
+    };
+    let _ = [TurboAudio::Mute, TurboAudio::PitchCorrected];
+    let _ = [
+        Event::VBlank,
+        Event::HBlank { scanline: 0 },
+        Event::SerialTransfer,
+        Event::FrameComplete {
+            frame: 0,
+            drawn: true,
+        },
+    ];
+    let _ = [
+        ExecutionState::Running,
+        ExecutionState::Paused,
+        ExecutionState::FrameStep,
+    ];
+}
+
+#[test]
+fn input() {
+    let _: fn(&Gba) -> KeypadInput = Gba::keypad_input;
+    let _: fn(&KeypadInput, Key, bool) = KeypadInput::set_pressed;
+    let _: fn(&mut Gba, InputMacro) = Gba::play_macro;
+    let _: fn(&mut Gba) = Gba::stop_macro;
+    let _: fn(&Gba) -> bool = Gba::macro_playing;
+    let _: fn(InputMacro, &[Key], u32) -> InputMacro = InputMacro::press;
+    let _: fn(InputMacro, u32) -> InputMacro = InputMacro::wait;
+    let _: Result<InputMacro, MacroParseError> = "A wait*2".parse();
+}
+
+#[test]
+fn saves() {
+    let _: fn(&mut Gba) -> Result<Vec<u8>, SaveStateError> = Gba::save_state;
+    let _: fn(&mut Gba, &[Attachment]) -> Result<Vec<u8>, SaveStateError> = Gba::save_state_with;
+    let _: fn(&mut Gba, &[u8]) -> Result<LoadReport, SaveStateError> = Gba::load_state;
+    let _: fn(&mut Gba, &Path) -> Result<(), SaveStateError> = Gba::save_state_to_file;
+    let _: fn(&mut Gba, &Path) -> Result<LoadReport, SaveStateError> = Gba::load_state_from_file;
+    let _: fn(&Path, &[u8]) -> Result<(), SaveStateError> = save_state::write_file;
+    let _: fn(&Path) -> Result<Vec<u8>, SaveStateError> = save_state::read_file;
+    let _: fn(&mut Gba, Option<RewindBuffer>) = Gba::set_rewind;
+    let _: fn(&mut Gba) -> Result<(), RewindError> = Gba::rewind;
+    let _: fn(u32, usize) -> RewindBuffer = RewindBuffer::new;
+    let _: fn(&mut Gba, MovieStart) -> Result<(), MovieError> = Gba::start_recording;
+    let _: fn(&mut Gba) -> Result<Movie, MovieError> = Gba::stop_recording;
+    let _: fn(&mut Gba, &Movie) -> Result<(), MovieError> = Gba::play_movie;
+    let _: fn(&mut Gba) = Gba::stop_movie;
+    let _: fn(&Gba) -> MovieStatus = Gba::movie_status;
+    let _: fn(&Movie) -> Vec<u8> = Movie::encode;
+    let _: fn(&[u8]) -> Result<Movie, MovieError> = Movie::decode;
+    let _: fn(&Movie, &Path) -> Result<(), MovieError> = Movie::write_file;
+    let _: fn(&Path) -> Result<Movie, MovieError> = Movie::read_file;
+    let _: fn(&mut Gba, &[u8]) -> Result<SaveLayout, BatterySaveError> = Gba::import_save;
+    let _: fn(&Gba, SaveLayout) -> Option<Vec<u8>> = Gba::export_save;
+    let _: fn(&Path, &[u8], usize) -> std::io::Result<()> = atomic_file::write;
+}
+
+#[test]
+fn services() {
+    let _: fn(&[u8]) -> Result<CartridgeHeader, CartridgeError> = CartridgeHeader::new;
+    let _: fn() -> [u8; 0x4000] = replacement_bios;
+    let _: fn() -> EmuConfig = replacement_config;
+    let _: fn(&mut EmuConfig) -> Result<[u8; 0x4000], BiosError> = EmuConfig::load_bios;
+    let _: fn(&mut Gba, &HeadlessOptions) -> std::io::Result<HeadlessRun> = headless::run;
+    let _: fn(&HeadlessExit) -> i32 = HeadlessExit::code;
+    let _: fn(&mut Pacer, PacingStrategy) = Pacer::set_strategy;
+}
@@ -0,0 +1,98 @@
+//! Finding where a game keeps a value.
+
+mod common;
+
+use common::idle;
+use emu::{
+    memory_edit::EditValue,
+    ram_search::{Candidate, Comparison, RamSearch, SearchFilter, SearchWidth},
+};
+
+#[test]
+fn filters_narrow_the_search_down() {
+    let mut gba = idle();
+    let lives = 0x0300_0010;
+    gba.cpu.bus.debug_write(lives, EditValue::Byte(3)).unwrap();
+    gba.cpu
+        .bus
+        .debug_write(0x0200_0100, EditValue::Byte(3))
+        .unwrap();
+
+    let mut search = RamSearch::new(&gba, SearchWidth::Byte);
+    assert_eq!(search.len(), 0x4_0000 + 0x8000);
+    assert_eq!(
+        search.filter(&gba, SearchFilter::Value(Comparison::Equal, 3)),
+        2
+    );
+
+    gba.cpu.bus.debug_write(lives, EditValue::Byte(2)).unwrap();
+    assert_eq!(search.filter(&gba, SearchFilter::ChangedBy(-1)), 1);
+    assert_eq!(
+        search.candidates(&gba, 10),
+        [Candidate {
+            address: lives,
+            value: 2,
+            previous: 2,
+        }]
+    );
+
+    gba.cpu.bus.debug_write(lives, EditValue::Byte(0)).unwrap();
+    assert_eq!(
+        search.candidates(&gba, 10)[0],
+        Candidate {
+            address: lives,
+            value: 0,
+            previous: 2,
+        }
+    );
+    // Wraps around the width.
+    gba.cpu
+        .bus
+        .debug_write(lives, EditValue::Byte(0xFF))
+        .unwrap();
+    search.filter(&gba, SearchFilter::Previous(Comparison::NotEqual));
+    assert_eq!(search.filter(&gba, SearchFilter::ChangedBy(0)), 1);
+    gba.cpu.bus.debug_write(lives, EditValue::Byte(0)).unwrap();
+    assert_eq!(search.filter(&gba, SearchFilter::ChangedBy(1)), 1);
+}
+
+#[test]
+fn wider_values_are_aligned() {
+    let mut gba = idle();
+    gba.cpu
+        .bus
+        .debug_write(0x0200_0004, EditValue::Word(0x1234_5678))
+        .unwrap();
+
+    let mut search = RamSearch::new(&gba, SearchWidth::Word);
+    assert_eq!(search.len(), (0x4_0000 + 0x8000) / 4);
+    assert_eq!(
+        search.filter(&gba, SearchFilter::Value(Comparison::Greater, 0x1234_0000)),
+        1
+    );
+    assert_eq!(search.candidates(&gba, 1)[0].address, 0x0200_0004);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn the_search_survives_a_state_load() {
+    let mut gba = idle();
+    let lives = 0x0300_0010;
+    gba.cpu.bus.debug_write(lives, EditValue::Byte(3)).unwrap();
+
+    let mut search = RamSearch::new(&gba, SearchWidth::Byte);
+    search.filter(&gba, SearchFilter::Value(Comparison::Equal, 3));
+    let candidates = search.candidates(&gba, usize::MAX);
+    let state = gba
+        .save_state_with(&[search.to_attachment().unwrap()])
+        .unwrap();
+
+    // The loaded state puts the value back.
+    gba.cpu.bus.debug_write(lives, EditValue::Byte(7)).unwrap();
+    let report = gba.load_state(&state).unwrap();
+
+    let loaded = RamSearch::from_attachments(&report.attachments).unwrap();
+    assert_eq!(loaded.width(), SearchWidth::Byte);
+    assert_eq!(loaded.candidates(&gba, usize::MAX), candidates);
+    assert!(RamSearch::from_attachments(&[]).is_none());
+}
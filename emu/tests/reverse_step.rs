@@ -0,0 +1,49 @@
+//! Stepping back with the step history lands on the state the emulator had then.
+
+#![cfg(feature = "serde")]
+
+use emu::{
+    determinism::state_hashes,
+    gba::Gba,
+    step_history::{ReverseStepError, StepHistory},
+    test_rom::TestRom,
+};
+
+/// Counts in R0 and stores the count in work RAM, forever.
+fn counter() -> Gba {
+    TestRom::new(0x100).counter().gba()
+}
+
+#[test]
+fn step_back_restores_the_past_state() {
+    let mut gba = counter();
+    assert!(matches!(gba.step_back(1), Err(ReverseStepError::Disabled)));
+
+    gba.set_step_history(Some(StepHistory::new(50, 4)));
+    // Hashing is slow in debug builds, only the states compared are.
+    let mut hashes = vec![None; 170];
+    for (step, hash) in hashes.iter_mut().enumerate() {
+        if (112..140).contains(&step) || step == 169 {
+            *hash = Some(state_hashes(&gba.cpu).unwrap());
+        }
+        gba.step();
+    }
+    assert_eq!(gba.step_history().unwrap().available(), 170);
+
+    gba.step_back(1).unwrap();
+    assert_eq!(Some(state_hashes(&gba.cpu).unwrap()), hashes[169]);
+
+    gba.step_back(57).unwrap();
+    assert_eq!(Some(state_hashes(&gba.cpu).unwrap()), hashes[112]);
+
+    // Stepping forward again goes through the same states.
+    for hash in &hashes[112..140] {
+        assert_eq!(Some(state_hashes(&gba.cpu).unwrap()), *hash);
+        gba.step();
+    }
+
+    assert!(matches!(
+        gba.step_back(1000),
+        Err(ReverseStepError::TooFar { available: 140 })
+    ));
+}
@@ -0,0 +1,64 @@
+//! Rewinding goes back to the snapshots taken while playing, newest first.
+
+#![cfg(feature = "serde")]
+
+use emu::{
+    config::{EmuConfig, MemoryProfile},
+    gba::Gba,
+    replacement_bios::replacement_config,
+    rewind::{RewindBuffer, RewindError},
+    test_rom::TestRom,
+};
+
+/// Counts in R0 and stores the count in work RAM, forever.
+fn counter(memory_profile: MemoryProfile) -> Gba {
+    TestRom::new(0x100).counter().gba_with(EmuConfig {
+        memory_profile,
+        ..replacement_config()
+    })
+}
+
+const fn count(gba: &Gba) -> u32 {
+    gba.cpu.registers.register_at(0)
+}
+
+#[test]
+fn rewind_goes_back_snapshot_by_snapshot() {
+    let mut gba = counter(MemoryProfile::Standard);
+    assert!(matches!(gba.rewind(), Err(RewindError::Disabled)));
+
+    gba.set_rewind(Some(RewindBuffer::new(2, 3)));
+    let mut counts = Vec::new();
+    for _ in 0..10 {
+        gba.run_frame().unwrap();
+        counts.push(count(&gba));
+    }
+    assert_eq!(gba.rewind_buffer().unwrap().len(), 3);
+    assert!(gba.memory_usage().rewind > 0);
+
+    // Taken as the frames 0, 2, 4, 6 and 8 ended, the last 3 kept.
+    for frame in [8, 6, 4] {
+        gba.rewind().unwrap();
+        assert_eq!(count(&gba), counts[frame]);
+    }
+    assert!(matches!(gba.rewind(), Err(RewindError::Empty)));
+
+    // Playing on from there takes snapshots again, as the frames 5 and 7 end.
+    for expected in &counts[5..8] {
+        gba.run_frame().unwrap();
+        assert_eq!(count(&gba), *expected);
+    }
+    for frame in [7, 5] {
+        gba.rewind().unwrap();
+        assert_eq!(count(&gba), counts[frame]);
+    }
+}
+
+#[test]
+fn bounded_profile_takes_no_snapshot() {
+    let mut gba = counter(MemoryProfile::Bounded);
+    gba.set_rewind(Some(RewindBuffer::default()));
+
+    assert!(gba.rewind_buffer().is_none());
+    assert!(matches!(gba.rewind(), Err(RewindError::Disabled)));
+}
@@ -0,0 +1,35 @@
+@ ARM data processing: flags, carry in and shifted operands.
+@
+@ expect R0 = 0
+@ expect R2 = 0
+@ expect R3 = 6
+@ expect R4 = 0xF0000000
+@ expect R5 = 0x40
+@ expect R6 = 0x0000FF00
+@ expect R7 = 1
+@ expect R8 = 0x70000000
+
+start:
+    MOV R0, #0
+    MOV R1, #0x80000000
+    @ 0x80000000 + 0x80000000: zero, carry and overflow.
+    ADDS R2, R1, R1
+    ADC R3, R0, #5
+    MRS R9, CPSR
+    MOV R8, R9, LSR #28
+    MOV R8, R8, LSL #28
+    @ Arithmetic shift keeps the sign.
+    MOV R4, R1, ASR #3
+    MOV R5, #1
+    MOV R5, R5, LSL #6
+    MVN R6, #0xFF
+    BIC R6, R6, #0xFF0000
+    BIC R6, R6, #0xFF000000
+    @ Conditions: R7 ends up 1 only if both are taken correctly.
+    MOV R7, #0
+    CMP R5, #0x40
+    ADDEQ R7, R7, #1
+    ADDNE R7, R7, #0x10
+
+end:
+    B end
@@ -0,0 +1,47 @@
+@ ARM loads and stores: widths, sign extension, indexing and block transfers.
+@
+@ expect R1 = 0x03000010
+@ expect R2 = 0xFFFFFF80
+@ expect R3 = 0x00008180
+@ expect R4 = 0xFFFF8180
+@ expect R5 = 0x03000004
+@ expect R6 = 0x80818281
+@ expect R10 = 0x11
+@ expect R11 = 0x22
+@ expect R12 = 0x33
+@ expect [0x03000000] = 0x81828180
+@ expect [0x03000100] = 0x11
+@ expect [0x03000108] = 0x33
+
+start:
+    MOV R0, #0x03000000
+    MOV R1, #0x03000000
+    MOV R7, #0x80
+    STRB R7, [R0]
+    ADD R7, R7, #1
+    STRB R7, [R0, #1]
+    MOV R7, #0x82
+    ADD R7, R7, #0x8100
+    STRH R7, [R0, #2]
+    LDRSB R2, [R0]
+    LDRH R3, [R0]
+    LDRSH R4, [R0]
+    @ Post-indexed: the load uses the base, then the base moves.
+    MOV R5, R0
+    LDR R8, [R5], #4
+    @ An unaligned word load rotates the word.
+    LDR R6, [R0, #1]
+    @ Pre-indexed with write-back.
+    LDR R9, [R1, #0x10]!
+    ADD R9, R0, #0x100
+    MOV R10, #0x11
+    MOV R11, #0x22
+    MOV R12, #0x33
+    STMIA R9, {R10-R12}
+    MOV R10, #0
+    MOV R11, #0
+    MOV R12, #0
+    LDMIA R9, {R10-R12}
+
+end:
+    B end
@@ -0,0 +1,35 @@
+@ Thumb: switching state, a counting loop, a call and the stack.
+@
+@ expect R0 = 55
+@ expect R1 = 0
+@ expect R2 = 110
+@ expect R13 = 0x03007F00
+@ expect [0x03007EFC] = 110
+
+start:
+    MOV R13, #0x03000000
+    ADD R13, R13, #0x7F00
+    @ Thumb code starts after the NOP: the pipeline already decoded it as ARM.
+    ADD R0, PC, #5
+    BX R0
+    NOP
+
+.thumb
+    @ R0 = 10 + 9 + ... + 1
+    MOV R0, #0
+    MOV R1, #10
+loop:
+    ADD R0, R0, R1
+    SUB R1, R1, #1
+    BNE loop
+    BL double
+    PUSH {R2}
+    ADD SP, #4
+
+end:
+    B end
+
+double:
+    MOV R2, R0
+    ADD R2, R2, R0
+    BX LR
@@ -0,0 +1,194 @@
+//! Driving the emulator by frames or by cycles, as headless runners and frontends do.
+
+use emu::{
+    cartridge_info::SaveType,
+    config::EmuConfig,
+    cpu::hardware::{flash::FlashSize, keypad::KeySampling, serial::SerialPeripheral},
+    events::{Event, EventKind},
+    fast_forward::FastForward,
+    gba::{ExecutionState, Gba, ResetKind},
+    replacement_bios::replacement_config,
+    test_rom::TestRom,
+};
+
+use std::sync::{Arc, Mutex};
+
+/// Counts in work RAM forever.
+fn counter() -> Gba {
+    counter_with(replacement_config())
+}
+
+fn counter_with(config: EmuConfig) -> Gba {
+    TestRom::new(0x100).counter().gba_with(config)
+}
+
+#[test]
+fn run_frame_returns_the_frame_and_its_audio() {
+    let mut gba = counter();
+
+    for frame in 1..=3 {
+        let run = gba.run_frame().unwrap();
+        assert_eq!(gba.frame_output().frame_count(), frame);
+        assert_eq!(run.pixels.len(), 160);
+        assert_eq!(run.audio.len(), gba.audio_samples_per_frame() as usize);
+    }
+}
+
+#[test]
+fn fast_forward_shortens_the_audio_of_every_frame() {
+    let mut gba = counter();
+    gba.set_fast_forward(Some(FastForward::new(4.0)));
+
+    let mut samples = 0;
+    for frame in 1..=4 {
+        let run = gba.run_frame().unwrap();
+        assert_eq!(gba.frame_output().frame_count(), frame);
+        samples += run.audio.len();
+    }
+    // Four frames in the host time of one.
+    assert_eq!(samples, gba.audio_samples_per_frame() as usize);
+
+    gba.set_fast_forward(None);
+    let run = gba.run_frame().unwrap();
+    assert_eq!(run.audio.len(), gba.audio_samples_per_frame() as usize);
+}
+
+#[test]
+fn run_cycles_adds_up_exactly() {
+    let mut gba = counter();
+    let start = gba.cycles();
+
+    let mut run = 0;
+    for _ in 0..100 {
+        run += gba.run_cycles(1000).unwrap();
+    }
+    assert_eq!(run, gba.cycles() - start);
+    // The cycles run past a target are taken from the next one: the total is never
+    // more than an instruction ahead.
+    assert!((100_000..100_020).contains(&run), "{run}");
+
+    assert_eq!(gba.run_cycles(0).unwrap(), 0);
+    gba.run_cycles(1000).unwrap();
+    let run = gba.cycles() - start;
+    assert!((101_000..101_020).contains(&run), "{run}");
+}
+
+#[test]
+fn paused_until_resumed_or_advanced_by_a_frame() {
+    let mut gba = counter();
+    gba.run_frame().unwrap();
+
+    gba.pause();
+    let run = gba.run_frame().unwrap();
+    assert_eq!(gba.frame_output().frame_count(), 1);
+    assert!(run.audio.is_empty());
+    assert_eq!(gba.run_cycles(1000).unwrap(), 0);
+
+    // The debugger still steps a paused game.
+    let cycles = gba.cycles();
+    gba.step();
+    assert!(gba.cycles() > cycles);
+
+    gba.frame_advance();
+    assert_eq!(gba.execution_state(), ExecutionState::FrameStep);
+    gba.run_cycles(10_000_000).unwrap();
+    assert_eq!(gba.frame_output().frame_count(), 2);
+    assert_eq!(gba.execution_state(), ExecutionState::Paused);
+
+    gba.resume();
+    gba.run_frame().unwrap();
+    gba.run_frame().unwrap();
+    assert_eq!(gba.frame_output().frame_count(), 4);
+}
+
+#[test]
+fn subscribers_get_the_events_of_each_frame() {
+    let mut gba = counter();
+    gba.set_fast_forward(Some(FastForward::new(2.0)));
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let ids: Vec<_> = [
+        EventKind::FrameComplete,
+        EventKind::VBlank,
+        EventKind::HBlank,
+    ]
+    .into_iter()
+    .map(|kind| {
+        let events = Arc::clone(&events);
+        gba.subscribe(kind, move |event| events.lock().unwrap().push(*event))
+    })
+    .collect();
+
+    for _ in 0..2 {
+        gba.run_frame().unwrap();
+    }
+    let events = std::mem::take(&mut *events.lock().unwrap());
+    let frames: Vec<_> = events
+        .iter()
+        .filter(|event| event.kind() == EventKind::FrameComplete)
+        .collect();
+    // Every other frame is skipped.
+    assert_eq!(
+        frames,
+        [
+            &Event::FrameComplete {
+                frame: 1,
+                drawn: true
+            },
+            &Event::FrameComplete {
+                frame: 2,
+                drawn: false
+            },
+        ]
+    );
+    // The Vblank follows the Hblank of the last line drawn, the frame completes with it.
+    let vblank = events.iter().position(|event| *event == Event::VBlank);
+    assert_eq!(events[vblank.unwrap() - 1], Event::HBlank { scanline: 159 });
+    assert_eq!(events[vblank.unwrap() + 1].kind(), EventKind::FrameComplete);
+
+    for id in ids {
+        assert!(gba.unsubscribe(id));
+    }
+}
+
+#[test]
+fn config_is_applied_from_the_start() {
+    let mut gba = counter_with(EmuConfig {
+        skip_bios: true,
+        save_type: Some(SaveType::Flash(FlashSize::Flash64K)),
+        key_sampling: KeySampling::Immediate,
+        audio_samples_per_frame: Some(800),
+        serial_peripheral: SerialPeripheral::WirelessAdapter,
+        rtc: Some(true),
+        solar_sensor: Some(true),
+        ..replacement_config()
+    });
+
+    assert_eq!(gba.cpu.registers.program_counter(), 0x0800_0000);
+    assert_eq!(gba.cpu.bus.read_raw(0x0400_0300), 1);
+    assert!(gba.cpu.bus.internal_memory.flash.is_some());
+    assert_eq!(gba.run_frame().unwrap().audio.len(), 800);
+    // The adapter keeps SI low.
+    assert_eq!(gba.cpu.bus.read_raw(0x0400_0128) & 0b100, 0);
+    // The GPIO port of the clock reads back once readable.
+    assert!(gba.rtc_clock().is_some());
+    gba.cpu.bus.write_raw(0x0800_00C6, 0b111);
+    assert_ne!(gba.cpu.bus.read_raw(0x0800_00C6), 0b111);
+    gba.cpu.bus.write_raw(0x0800_00C8, 1);
+    assert_eq!(gba.cpu.bus.read_raw(0x0800_00C6), 0b111);
+    // The solar sensor shares it: in the full sun, the flag rises after 56 clocks.
+    gba.solar_light().unwrap().set_level(10);
+    gba.cpu.bus.write_raw(0x0800_00C4, 0b010);
+    for _ in 0..56 {
+        assert_eq!(gba.cpu.bus.read_raw(0x0800_00C4) & 0b1000, 0);
+        gba.cpu.bus.write_raw(0x0800_00C4, 0b001);
+        gba.cpu.bus.write_raw(0x0800_00C4, 0);
+    }
+    assert_ne!(gba.cpu.bus.read_raw(0x0800_00C4) & 0b1000, 0);
+
+    gba.set_key_sampling(KeySampling::Latched);
+    assert_eq!(gba.config().key_sampling, KeySampling::Latched);
+
+    gba.reset(ResetKind::Restart);
+    assert_eq!(gba.cpu.registers.program_counter(), 0x0800_0000);
+}
@@ -0,0 +1,54 @@
+//! Battery save profiles chosen when the cartridge is inserted: the profile is loaded
+//! into the Flash chip, and written back when the game saves.
+
+use std::fs;
+
+use emu::{
+    cartridge_header::CartridgeHeader, config::EmuConfig, gba::Gba, save_profiles::SaveProfiles,
+    test_rom::TestRom,
+};
+
+fn flash_rom() -> Vec<u8> {
+    TestRom::new(0x400).bytes(0x200, b"FLASH_V126\0").build()
+}
+
+#[test]
+fn profile_is_loaded_and_written_back() {
+    let root = std::env::temp_dir().join("clementine-save-profiles-test");
+    let _ = fs::remove_dir_all(&root);
+
+    let rom = flash_rom();
+    let header = CartridgeHeader::new(&rom).unwrap();
+    let profiles = SaveProfiles::new(&root, &header);
+    profiles.store("kid", &[0x11; 0x10]).unwrap();
+
+    let config = EmuConfig {
+        save_directory: Some(root.clone()),
+        save_profile: Some("kid".to_string()),
+        ..EmuConfig::default()
+    };
+    let mut gba = Gba::with_config(header, [0; 0x4000], rom, config);
+    assert_eq!(gba.save_profiles(), Some(profiles.clone()));
+
+    let flash = gba.cpu.bus.internal_memory.flash.as_ref().unwrap();
+    assert_eq!(flash.data()[..0x10], [0x11; 0x10]);
+    assert_eq!(flash.data()[0x10], 0xFF);
+
+    // Programs 0x42 at the start of the chip.
+    for (address, value) in [(0x5555, 0xAA), (0x2AAA, 0x55), (0x5555, 0xA0), (0, 0x42)] {
+        gba.cpu.bus.write_byte(0x0E00_0000 + address, value);
+    }
+    gba.take_notifications();
+
+    let saved = profiles.load("kid").unwrap().unwrap();
+    assert_eq!(saved.len(), 0x1_0000);
+    assert_eq!(saved[..2], [0x11 & 0x42, 0x11]);
+    assert_eq!(profiles.load("default").unwrap(), None);
+    // The previous content is kept in case the write corrupted the save.
+    assert_eq!(
+        profiles.load_backup("kid", 1).unwrap(),
+        Some(vec![0x11; 0x10])
+    );
+
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,208 @@
+//! Tiny test ROMs assembled from the fixtures in `tests/roms/` with the emulator's own
+//! assembler, so that regression tests don't need any toolchain or copyrighted ROM.
+//!
+//! A fixture is a list of instructions, one per line, starting in ARM state at
+//! `0x080000C0` right after the cartridge header. It supports:
+//! - labels (`loop:`), usable wherever the assembler takes an address;
+//! - `.arm` and `.thumb` to assemble the following lines in that state, the switch
+//!   itself being up to the code (`BX`);
+//! - `.word` to place a 32-bit value;
+//! - `@ expect R3 = 0x1234` and `@ expect [0x03000000] = 5` to check a register or a
+//!   word in memory once the program reached its `end` label, which must loop on itself.
+
+use std::collections::HashMap;
+
+use emu::{
+    cartridge_header::CartridgeHeader,
+    cpu::asm::{assemble_arm, assemble_thumb, ArmAsm},
+    gba::Gba,
+    test_rom::TestRom,
+};
+
+const ENTRY_POINT: u32 = 0x0800_00C0;
+
+/// Fixtures are small, they reach their end well before this.
+const MAX_STEPS: usize = 10_000;
+
+enum Location {
+    Register(usize),
+    Memory(u32),
+}
+
+struct Fixture {
+    rom: Vec<u8>,
+    end: u32,
+    expectations: Vec<(Location, u32)>,
+}
+
+fn number(text: &str) -> u32 {
+    let text = text.trim();
+    let parsed = text.strip_prefix("0x").map_or_else(
+        || text.parse(),
+        |hex| u32::from_str_radix(&hex.replace('_', ""), 16),
+    );
+
+    parsed.unwrap_or_else(|_| panic!("invalid number `{text}`"))
+}
+
+fn expectation(text: &str) -> (Location, u32) {
+    let (location, value) = text.split_once('=').expect("`@ expect location = value`");
+    let location = location.trim();
+
+    let location = location
+        .strip_prefix('[')
+        .and_then(|location| location.strip_suffix(']'))
+        .map_or_else(
+            || {
+                let register = location
+                    .strip_prefix(['R', 'r'])
+                    .unwrap_or_else(|| panic!("invalid register `{location}`"));
+                Location::Register(number(register) as usize)
+            },
+            |address| Location::Memory(number(address)),
+        );
+
+    (location, number(value))
+}
+
+/// Size of an instruction line, Thumb `BL` being the only 32-bit Thumb instruction.
+fn size(line: &str, thumb: bool) -> u32 {
+    let is_bl = line
+        .split_whitespace()
+        .next()
+        .is_some_and(|mnemonic| mnemonic.eq_ignore_ascii_case("BL"));
+
+    if thumb && !is_bl {
+        2
+    } else {
+        4
+    }
+}
+
+fn assemble(source: &str) -> Fixture {
+    let mut expectations = Vec::new();
+    let mut labels = HashMap::new();
+    let mut lines = Vec::new();
+
+    // First pass: the address of every label.
+    let mut address = ENTRY_POINT;
+    let mut thumb = false;
+    for line in source.lines() {
+        if let Some(expected) = line.trim().strip_prefix("@ expect ") {
+            expectations.push(expectation(expected));
+            continue;
+        }
+
+        let line = line.split('@').next().unwrap_or_default().trim();
+        let line = line.split_once(':').map_or(line, |(label, rest)| {
+            labels.insert(label.trim().to_string(), address);
+            rest.trim()
+        });
+
+        match line {
+            "" => {}
+            ".arm" => thumb = false,
+            ".thumb" => thumb = true,
+            _ => {
+                lines.push((address, thumb, line.to_string()));
+                address += size(line, thumb);
+            }
+        }
+    }
+
+    // Second pass: labels replaced by their address.
+    let mut program = Vec::new();
+    for (address, thumb, line) in lines {
+        let line = line
+            .split_inclusive(|c: char| !c.is_alphanumeric() && c != '_')
+            .map(|token| {
+                let name = token.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '_');
+                labels.get(name).map_or_else(
+                    || token.to_string(),
+                    |target| token.replacen(name, &format!("{target:#X}"), 1),
+                )
+            })
+            .collect::<String>();
+
+        let bytes = match (line.strip_prefix(".word"), thumb) {
+            (Some(value), _) => number(value).to_le_bytes().to_vec(),
+            (None, true) => assemble_thumb(&line, address)
+                .unwrap_or_else(|err| panic!("`{line}`: {err}"))
+                .into_iter()
+                .flat_map(u16::to_le_bytes)
+                .collect(),
+            (None, false) => assemble_arm(&line, address)
+                .unwrap_or_else(|err| panic!("`{line}`: {err}"))
+                .to_le_bytes()
+                .to_vec(),
+        };
+        program.extend(bytes);
+    }
+
+    // The header: a branch to the entry point and a valid complement check.
+    let branch = assemble_arm(&format!("B {ENTRY_POINT:#X}"), 0x0800_0000).unwrap();
+    let mut rom = TestRom::new((ENTRY_POINT - 0x0800_0000) as usize)
+        .words(0, &[branch])
+        .build();
+    rom.extend(program);
+
+    let end = *labels.get("end").expect("an `end` label");
+
+    Fixture {
+        rom,
+        end,
+        expectations,
+    }
+}
+
+fn run(source: &str) {
+    let fixture = assemble(source);
+
+    let mut bios = [0; 0x4000];
+    bios[..4].copy_from_slice(&ArmAsm::mov(15).imm(0x0800_0000).encode().to_le_bytes());
+    let mut gba = Gba::new(
+        CartridgeHeader::new(&fixture.rom).unwrap(),
+        bios,
+        fixture.rom,
+    );
+
+    for _ in 0..MAX_STEPS {
+        gba.step();
+    }
+
+    // Looping on `end`, the program counter is at most two instructions ahead.
+    let pc = u32::try_from(gba.cpu.registers.program_counter()).unwrap();
+    assert!(
+        (fixture.end..=fixture.end + 8).contains(&pc),
+        "stuck at {pc:#010X} instead of {:#010X}",
+        fixture.end
+    );
+
+    for (location, expected) in fixture.expectations {
+        match location {
+            Location::Register(register) => {
+                let value = gba.cpu.registers.register_at(register);
+                assert_eq!(value, expected, "R{register} is {value:#X}");
+            }
+            Location::Memory(address) => {
+                let value = gba.cpu.bus.read_word(address);
+                assert_eq!(value, expected, "[{address:#010X}] is {value:#X}");
+            }
+        }
+    }
+}
+
+#[test]
+fn arm_data_processing() {
+    run(include_str!("roms/arm_data_processing.s"));
+}
+
+#[test]
+fn arm_load_store() {
+    run(include_str!("roms/arm_load_store.s"));
+}
+
+#[test]
+fn thumb() {
+    run(include_str!("roms/thumb.s"));
+}
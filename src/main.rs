@@ -1,6 +1,20 @@
+extern crate emu;
 extern crate logger;
 extern crate ui;
+use emu::bench;
+use emu::cartridge_header::CartridgeHeader;
+use emu::compatibility::{self, SweepOptions};
+use emu::config::EmuConfig;
+use emu::cpu::hardware::serial::{
+    tcp_link::TcpLink,
+    uart::{PipeUart, UartHost},
+};
+use emu::determinism;
+use emu::gba::Gba;
+use emu::headless::{self, HeadlessOptions};
+use emu::movie::Movie;
 use logger::log;
+use ui::app::SerialCable;
 
 #[cfg(feature = "logger")]
 use logger::{init_logger, LogKind};
@@ -8,6 +22,20 @@ use logger::{init_logger, LogKind};
 fn main() {
     let args = std::env::args().skip(1).collect::<Vec<String>>();
 
+    if args.first().map(String::as_str) == Some("--sweep") {
+        sweep(&args[1..]);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("--headless") {
+        run_headless(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("--verify-movie") {
+        verify_movie(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("--bench") {
+        bench(&args[1..]);
+    }
+
     #[cfg(feature = "logger")]
     if args.len() > 1 {
         if args.last().unwrap().as_str() == "--log-on-file" {
@@ -28,6 +56,8 @@ fn main() {
         },
     );
 
+    let serial = serial_cable(&args);
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1200.0, 800.0])
@@ -38,7 +68,246 @@ fn main() {
     eframe::run_native(
         "Clementine - A GBA Emulator",
         options,
-        Box::new(|_cc| Ok(Box::new(ui::app::App::new(cartridge_name)))),
+        Box::new(|cc| {
+            Ok(Box::new(ui::app::App::new(
+                cartridge_name,
+                &cc.egui_ctx,
+                serial,
+            )))
+        }),
     )
     .ok();
 }
+
+/// `--link-host <address>` waits for another Clementine to link to, as the parent of
+/// the link cable, `--link-join <address>` links to the one hosting there. `--uart
+/// <pipe>` connects the UART to a host pipe instead, see [`uart_host`], and
+/// `--wireless-adapter` plugs the wireless adapter.
+fn serial_cable(args: &[String]) -> Option<SerialCable> {
+    if args.iter().any(|arg| arg == "--wireless-adapter") {
+        return Some(SerialCable::WirelessAdapter);
+    }
+    let position = args
+        .iter()
+        .position(|arg| arg == "--link-host" || arg == "--link-join" || arg == "--uart")?;
+    let Some(address) = args.get(position + 1) else {
+        eprintln!("missing value after {}", args[position]);
+        std::process::exit(1)
+    };
+
+    if args[position] == "--uart" {
+        return Some(SerialCable::Uart(uart_host(address)));
+    }
+    let link = if args[position] == "--link-host" {
+        println!("waiting for the other player on {address}");
+        TcpLink::host(address.as_str())
+    } else {
+        TcpLink::join(address.as_str())
+    };
+    match link {
+        Ok(link) => Some(SerialCable::Link(Box::new(link))),
+        Err(e) => {
+            eprintln!("can't link to {address}: {e}");
+            std::process::exit(2)
+        }
+    }
+}
+
+/// The host pipe the UART is connected to: `stdio` for the standard input and output,
+/// else the path of a pseudo-terminal or a named pipe.
+fn uart_host(pipe: &str) -> Box<dyn UartHost> {
+    if pipe == "stdio" {
+        return Box::new(PipeUart::stdio());
+    }
+
+    match PipeUart::open(pipe.as_ref()) {
+        Ok(uart) => Box::new(uart),
+        Err(e) => {
+            eprintln!("can't open {pipe}: {e}");
+            std::process::exit(2)
+        }
+    }
+}
+
+/// `--sweep <directory> [frames]`: runs every ROM of the directory headlessly and prints
+/// the compatibility report as JSON.
+fn sweep(args: &[String]) {
+    let Some(directory) = args.first() else {
+        eprintln!("usage: clementine --sweep <directory> [frames]");
+        std::process::exit(1)
+    };
+
+    let mut options = SweepOptions::default();
+    if let Some(frames) = args.get(1) {
+        options.frames = frames.parse().unwrap_or_else(|_| {
+            eprintln!("invalid number of frames: {frames}");
+            std::process::exit(1)
+        });
+    }
+
+    let (bios, config) = load_bios();
+    options.config = config;
+
+    match compatibility::sweep(directory.as_ref(), &bios, &options) {
+        Ok(report) => println!("{}", report.to_json()),
+        Err(e) => {
+            eprintln!("can't read {directory}: {e}");
+            std::process::exit(2)
+        }
+    }
+}
+
+/// `--headless <rom> <frames> [--screenshot <file.ppm>] [--input <macro>] [--uart <pipe>]`:
+/// runs the ROM without a window and exits with the status of
+/// [`headless::HeadlessExit::code`].
+fn run_headless(args: &[String]) -> ! {
+    let (Some(rom_path), Some(frames)) = (args.first(), args.get(1)) else {
+        eprintln!(
+            "usage: clementine --headless <rom> <frames> [--screenshot <file.ppm>] [--input <macro>] [--uart <pipe>]"
+        );
+        std::process::exit(1)
+    };
+
+    let mut options = HeadlessOptions {
+        frames: frames.parse().unwrap_or_else(|_| {
+            eprintln!("invalid number of frames: {frames}");
+            std::process::exit(1)
+        }),
+        ..HeadlessOptions::default()
+    };
+    let mut uart = None;
+    let mut rest = args[2..].iter();
+    while let Some(flag) = rest.next() {
+        let Some(value) = rest.next() else {
+            eprintln!("missing value after {flag}");
+            std::process::exit(1)
+        };
+        match flag.as_str() {
+            "--screenshot" => options.screenshot = Some(value.into()),
+            "--uart" => uart = Some(uart_host(value)),
+            "--input" => {
+                options.input = Some(value.parse().unwrap_or_else(|e| {
+                    eprintln!("invalid input macro: {e}");
+                    std::process::exit(1)
+                }));
+            }
+            _ => {
+                eprintln!("unknown option {flag}");
+                std::process::exit(1)
+            }
+        }
+    }
+
+    let mut gba = load_rom(rom_path);
+    gba.cpu
+        .bus
+        .set_debug_output(Some(Box::new(|message| eprintln!("{message}"))));
+    if let Some(host) = uart {
+        gba.cpu.bus.connect_uart(host);
+    }
+
+    match headless::run(&mut gba, &options) {
+        Ok(run) => {
+            println!("{} frames, {}", run.frames, run.exit);
+            std::process::exit(run.exit.code())
+        }
+        Err(e) => {
+            eprintln!("can't write the screenshot: {e}");
+            std::process::exit(2)
+        }
+    }
+}
+
+/// `--bench <rom> <frames>`: runs the ROM as fast as possible and prints the speed
+/// reached with the time spent in each subsystem, see [`bench::run`]. Exits with 3 if
+/// the game got stuck before the end.
+fn bench(args: &[String]) -> ! {
+    let (Some(rom_path), Some(frames)) = (args.first(), args.get(1)) else {
+        eprintln!("usage: clementine --bench <rom> <frames>");
+        std::process::exit(1)
+    };
+    let frames = frames.parse().unwrap_or_else(|_| {
+        eprintln!("invalid number of frames: {frames}");
+        std::process::exit(1)
+    });
+
+    let mut gba = load_rom(rom_path);
+    let report = bench::run(&mut gba, frames);
+    print!("{report}");
+    std::process::exit(if report.overrun.is_some() { 3 } else { 0 })
+}
+
+/// `--verify-movie <rom> <movie.clmv> [interval]`: plays the movie twice and compares
+/// the state every `interval` frames (60 by default). Exits with 0 when both runs match,
+/// 3 when they diverge.
+fn verify_movie(args: &[String]) -> ! {
+    let (Some(rom_path), Some(movie_path)) = (args.first(), args.get(1)) else {
+        eprintln!("usage: clementine --verify-movie <rom> <movie.clmv> [interval]");
+        std::process::exit(1)
+    };
+    let interval = args.get(2).map_or(60, |interval| {
+        interval
+            .parse()
+            .ok()
+            .filter(|interval| *interval > 0)
+            .unwrap_or_else(|| {
+                eprintln!("invalid interval: {interval}");
+                std::process::exit(1)
+            })
+    });
+
+    let movie = Movie::read_file(movie_path.as_ref()).unwrap_or_else(|e| {
+        eprintln!("can't read {movie_path}: {e}");
+        std::process::exit(2)
+    });
+    let mut gba = load_rom(rom_path);
+
+    match determinism::verify_movie(&mut gba, &movie, interval) {
+        Ok(None) => {
+            println!("{} frames, deterministic", movie.frames.len());
+            std::process::exit(0)
+        }
+        Ok(Some(divergence)) => {
+            println!("{divergence}");
+            std::process::exit(3)
+        }
+        Err(e) => {
+            eprintln!("can't play {movie_path}: {e}");
+            std::process::exit(2)
+        }
+    }
+}
+
+/// The emulator with the ROM at `rom_path` and the BIOS of [`load_bios`], exits if the
+/// ROM can't be read.
+fn load_rom(rom_path: &str) -> Gba {
+    let rom = std::fs::read(rom_path).unwrap_or_else(|e| {
+        eprintln!("can't read {rom_path}: {e}");
+        std::process::exit(2)
+    });
+    let header = CartridgeHeader::new(&rom).unwrap_or_else(|e| {
+        eprintln!("invalid cartridge {rom_path}: {e}");
+        std::process::exit(2)
+    });
+    let (bios, config) = load_bios();
+
+    Gba::with_config(header, bios, rom, config)
+}
+
+/// `gba_bios.bin` from the working directory, or the replacement BIOS with the
+/// configuration it needs, see [`EmuConfig::load_bios`].
+fn load_bios() -> ([u8; 0x4000], EmuConfig) {
+    let mut config = EmuConfig {
+        bios_path: Some("gba_bios.bin".into()),
+        ..EmuConfig::default()
+    };
+    let bios = config.load_bios().unwrap_or_else(|e| {
+        eprintln!("gba_bios.bin: {e}");
+        std::process::exit(2)
+    });
+    if config.bios_path.is_none() {
+        eprintln!("gba_bios.bin not found, using the replacement BIOS");
+    }
+
+    (bios, config)
+}
@@ -1,12 +1,20 @@
 #[cfg(feature = "disassembler")]
 use crate::disassembler::Disassembler;
-use emu::{cartridge_header::CartridgeHeader, gba::Gba};
+use emu::{
+    cartridge_header::CartridgeHeader,
+    config::EmuConfig,
+    cpu::hardware::serial::{link_cable::LinkTransport, uart::UartHost, SerialPeripheral},
+    events::{Event, EventKind},
+    gba::Gba,
+};
 use logger::log;
 use std::io::Read;
 
 use super::cpu_registers::CpuRegisters;
 use crate::{
-    about, cpu_handler::CpuHandler, gba_display::GbaDisplay, savegame::SaveGame, ui_traits::UiTool,
+    about, cheats::Cheats, cpu_handler::CpuHandler, game_properties::GameProperties,
+    gba_display::GbaDisplay, notifications::Toasts, savegame::SaveGame, solar_sensor::SolarSensor,
+    ui_traits::UiTool,
 };
 
 use std::{
@@ -15,18 +23,30 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+/// What the serial port is connected to from the start.
+pub enum SerialCable {
+    /// A link cable to another console.
+    Link(Box<dyn LinkTransport>),
+    /// The UART, to a host pipe.
+    Uart(Box<dyn UartHost>),
+    /// The wireless adapter, alone.
+    WirelessAdapter,
+}
+
 pub struct App {
     tools: Vec<Box<dyn UiTool>>,
     open: BTreeSet<String>,
+    toasts: Toasts,
 }
 
 impl App {
-    /// Create a new `ClementineApp` instance
+    /// Create a new `ClementineApp` instance, repainting `ctx` whenever the emulation
+    /// draws a frame, with `serial` plugged in the serial port.
     ///
     /// # Panics
-    /// It panics if the cartridge can't be opened.
+    /// It panics if the working directory can't be read or the BIOS is too short.
     #[must_use]
-    pub fn new(cartridge_name: String) -> Self {
+    pub fn new(cartridge_name: String, ctx: &egui::Context, serial: Option<SerialCable>) -> Self {
         let data = match read_file(cartridge_name) {
             Ok(d) => d,
             Err(e) => {
@@ -35,43 +55,74 @@ impl App {
             }
         };
 
-        let bios_file = env::current_dir().unwrap().join("gba_bios.bin");
-        let bios = match std::fs::read(bios_file) {
-            Ok(f) => f,
+        let mut config = EmuConfig {
+            bios_path: Some(env::current_dir().unwrap().join("gba_bios.bin")),
+            ..EmuConfig::default()
+        };
+        let bios = match config.load_bios() {
+            Ok(bios) => bios,
             Err(e) => {
-                eprintln!("can't open bios file: {e}");
+                eprintln!("{e}");
                 std::process::exit(3);
             }
         };
+        if config.bios_path.is_none() {
+            log("gba_bios.bin not found, using the replacement BIOS");
+        }
 
-        let cartridge_header =
-            CartridgeHeader::new(data.as_slice()).expect("Cartridge must be opened");
-        let arc_gba = Arc::new(Mutex::new(Gba::new(
-            cartridge_header,
-            bios[0..0x0000_4000].try_into().unwrap(),
-            data,
-        )));
+        if matches!(serial, Some(SerialCable::WirelessAdapter)) {
+            config.serial_peripheral = SerialPeripheral::WirelessAdapter;
+        }
+
+        // Battery saves go to the default profile of the game, next to the BIOS.
+        config.save_directory = Some(env::current_dir().unwrap().join("saves"));
+
+        let cartridge_header = match CartridgeHeader::new(data.as_slice()) {
+            Ok(header) => header,
+            Err(e) => {
+                eprintln!("can't open cartridge: {e}");
+                std::process::exit(4);
+            }
+        };
+        let mut gba = Gba::with_config(cartridge_header, bios, data, config);
+        // Homebrew debug prints, next to the log.
+        gba.cpu
+            .bus
+            .set_debug_output(Some(Box::new(|message| eprintln!("{message}"))));
+        match serial {
+            Some(SerialCable::Link(link)) => gba.cpu.bus.connect_link_cable(link),
+            Some(SerialCable::Uart(host)) => gba.cpu.bus.connect_uart(host),
+            Some(SerialCable::WirelessAdapter) | None => {}
+        }
+        let ctx = ctx.clone();
+        gba.subscribe(EventKind::FrameComplete, move |event| {
+            if matches!(event, Event::FrameComplete { drawn: true, .. }) {
+                ctx.request_repaint();
+            }
+        });
+        let arc_gba = Arc::new(Mutex::new(gba));
 
         #[cfg(feature = "disassembler")]
         let disassembler = Disassembler::new(Arc::clone(&arc_gba));
 
-        let tools: Vec<Box<dyn UiTool>> = vec![
+        let mut tools: Vec<Box<dyn UiTool>> = vec![
             Box::<about::About>::default(),
             Box::new(CpuRegisters::new(Arc::clone(&arc_gba))),
             Box::new(CpuHandler::new(Arc::clone(&arc_gba))),
-            Box::new(GbaDisplay::new(Arc::clone(&arc_gba))),
+            Box::new(GbaDisplay::new(&arc_gba.lock().unwrap())),
             Box::new(SaveGame::new(Arc::clone(&arc_gba))),
         ];
 
-        #[cfg(feature = "disassembler")]
-        let mut tools = tools;
         #[cfg(feature = "disassembler")]
         tools.push(Box::new(disassembler));
+        tools.push(Box::new(GameProperties::new(Arc::clone(&arc_gba))));
+        tools.push(Box::new(Cheats::new(Arc::clone(&arc_gba))));
+        tools.push(Box::new(SolarSensor::new(&arc_gba.lock().unwrap())));
 
-        Self::from_tools(tools)
+        Self::from_tools(tools, Toasts::new(arc_gba))
     }
 
-    fn from_tools(tools: Vec<Box<dyn UiTool>>) -> Self {
+    fn from_tools(tools: Vec<Box<dyn UiTool>>, toasts: Toasts) -> Self {
         let mut open = BTreeSet::new();
 
         open.insert(tools[1].name().to_owned());
@@ -81,11 +132,15 @@ impl App {
         #[cfg(feature = "disassembler")]
         open.insert(tools[5].name().to_owned());
 
-        Self { tools, open }
+        Self {
+            tools,
+            open,
+            toasts,
+        }
     }
 
     pub fn checkboxes(&mut self, ui: &mut egui::Ui) {
-        let Self { tools, open } = self;
+        let Self { tools, open, .. } = self;
         for tool in tools {
             let mut is_open = open.contains(tool.name());
             ui.toggle_value(&mut is_open, tool.name());
@@ -94,7 +149,7 @@ impl App {
     }
 
     fn windows(&mut self, ctx: &egui::Context) {
-        let Self { tools, open } = self;
+        let Self { tools, open, .. } = self;
         for tool in tools {
             let mut is_open = open.contains(tool.name());
             tool.show(ctx, &mut is_open);
@@ -105,8 +160,6 @@ impl App {
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        ctx.request_repaint();
-
         egui::SidePanel::right("Clementine Tools")
             .resizable(false)
             .default_width(200.0)
@@ -128,6 +181,14 @@ impl eframe::App for App {
             });
 
         self.windows(ctx);
+
+        self.toasts.show(ctx);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        for tool in &mut self.tools {
+            tool.on_exit();
+        }
     }
 }
 
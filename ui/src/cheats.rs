@@ -0,0 +1,93 @@
+use std::sync::{Arc, Mutex};
+
+use emu::cheats::codebreaker;
+use emu::gba::Gba;
+
+use crate::ui_traits::UiTool;
+
+pub struct Cheats {
+    gba: Arc<Mutex<Gba>>,
+    description: String,
+    codes: String,
+    /// Why the codes entered couldn't be added.
+    error: Option<String>,
+}
+
+impl Cheats {
+    pub const fn new(gba: Arc<Mutex<Gba>>) -> Self {
+        Self {
+            gba,
+            description: String::new(),
+            codes: String::new(),
+            error: None,
+        }
+    }
+
+    fn add(&mut self) {
+        match codebreaker::parse(self.description.trim(), &self.codes) {
+            Ok(cheat) => {
+                self.gba.lock().unwrap().add_cheat(cheat);
+                self.description.clear();
+                self.codes.clear();
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+}
+
+impl UiTool for Cheats {
+    fn name(&self) -> &'static str {
+        "Cheats"
+    }
+
+    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+        egui::Window::new(self.name())
+            .default_width(280.0)
+            .open(open)
+            .show(ctx, |ui| self.ui(ui));
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let mut gba = self.gba.lock().unwrap();
+        let mut toggled = None;
+        let mut removed = None;
+        for (index, cheat) in gba.cheats().iter().enumerate() {
+            ui.horizontal(|ui| {
+                let mut enabled = cheat.enabled;
+                if ui.checkbox(&mut enabled, &cheat.description).changed() {
+                    toggled = Some((index, enabled));
+                }
+                if ui.small_button("🗑").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some((index, enabled)) = toggled {
+            gba.set_cheat_enabled(index, enabled);
+        }
+        if let Some(index) = removed {
+            gba.remove_cheat(index);
+        }
+        drop(gba);
+
+        ui.separator();
+        ui.label("CodeBreaker codes");
+        ui.text_edit_singleline(&mut self.description)
+            .on_hover_text("Description");
+        ui.add(
+            egui::TextEdit::multiline(&mut self.codes)
+                .code_editor()
+                .hint_text("XXXXXXXX YYYY"),
+        );
+        if ui
+            .add_enabled(!self.codes.trim().is_empty(), egui::Button::new("Add"))
+            .clicked()
+        {
+            self.add();
+        }
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+    }
+}
@@ -3,42 +3,314 @@ use std::ops::{Deref, DerefMut, Range};
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use egui::text_selection::text_cursor_state::byte_index_from_char_index;
 use egui::{TextBuffer, TextEdit};
 
-use emu::gba::Gba;
+use emu::clock::{Pacer, PacingStrategy};
+use emu::config::{MemoryProfile, Overclock};
+use emu::cpu::breakpoint_condition::Condition;
+use emu::cpu::breakpoints::{self, StateMask, StepResult};
+use emu::cpu::execution_trap::ExecutionTrap;
+use emu::fast_forward::FastForward;
+use emu::gba::{ExecutionState, Gba, ResetKind};
+use emu::notifications::{Notification, NotificationKind};
+use emu::rewind::RewindBuffer;
+use emu::step_history::StepHistory;
 
 use crate::ui_traits::UiTool;
 
 pub struct CpuHandler {
     gba: Arc<Mutex<Gba>>,
     play: Arc<AtomicBool>,
+    /// Set while the rewind key is held, the emulation thread waits meanwhile.
+    rewinding: Arc<AtomicBool>,
     thread_handle: Option<thread::JoinHandle<()>>,
     breakpoints: Arc<Mutex<BTreeSet<Breakpoint>>>,
+    execution_trap: Arc<Mutex<Option<ExecutionTrap>>>,
+    running_time: Arc<Mutex<RunningTime>>,
+    pacer: Arc<Mutex<Pacer>>,
+    /// Whether the fast-forward button is on, [`FAST_FORWARD_KEY`] also runs it while held.
+    fast_forward_on: bool,
+    fast_forwarding: bool,
+    /// Speed of the pacer to go back to when the fast-forward stops.
+    speed_before_fast_forward: Option<f64>,
     b_address: UpperHexString,
     breakpoint_combo: BreakpointType,
+    /// Condition of the next exact breakpoint, empty for none.
+    b_condition: String,
+    condition_error: Option<String>,
+    step_back_error: Option<String>,
     cycle_to_skip_custom_value: u64,
 }
 
 impl CpuHandler {
     pub fn new(gba: Arc<Mutex<Gba>>) -> Self {
+        gba.lock()
+            .unwrap()
+            .set_rewind(Some(RewindBuffer::default()));
+
         Self {
             gba,
             play: Arc::new(AtomicBool::new(false)),
+            rewinding: Arc::new(AtomicBool::new(false)),
             thread_handle: None,
             breakpoints: Arc::new(Mutex::new(BTreeSet::new())),
+            execution_trap: Arc::new(Mutex::new(None)),
+            running_time: Arc::new(Mutex::new(RunningTime::default())),
+            pacer: Arc::new(Mutex::new(Pacer::default())),
+            fast_forward_on: false,
+            fast_forwarding: false,
+            speed_before_fast_forward: None,
             b_address: UpperHexString::default(),
             breakpoint_combo: BreakpointType::Equal,
+            b_condition: String::new(),
+            condition_error: None,
+            step_back_error: None,
             cycle_to_skip_custom_value: 5000,
         }
     }
+
+    fn speed_combo(&self, ui: &mut egui::Ui) {
+        let current = self.pacer.lock().unwrap().speed();
+        let mut selected = current;
+
+        egui::ComboBox::from_id_source("Speed")
+            .selected_text(speed_label(current))
+            .show_ui(ui, |ui| {
+                for speed in SPEEDS {
+                    ui.selectable_value(&mut selected, speed, speed_label(speed));
+                }
+            });
+
+        if selected != current {
+            self.pacer.lock().unwrap().set_speed(selected);
+            self.gba.lock().unwrap().notify(Notification::info(
+                NotificationKind::SpeedChanged,
+                format!("Speed: {}", speed_label(selected)),
+            ));
+        }
+    }
+
+    fn strategy_combo(&self, ui: &mut egui::Ui) {
+        let current = self.pacer.lock().unwrap().strategy();
+        let mut selected = current;
+
+        egui::ComboBox::from_label("Sync to")
+            .selected_text(selected.to_string())
+            .show_ui(ui, |ui| {
+                for strategy in STRATEGIES {
+                    ui.selectable_value(&mut selected, strategy, strategy.to_string());
+                }
+            });
+
+        if selected != current {
+            self.pacer.lock().unwrap().set_strategy(selected);
+        }
+    }
+
+    /// Runs the emulation in a thread as long as the tool lives, if not done yet. The
+    /// thread idles while the [`Gba`] is paused, it pauses it on breakpoints and errors.
+    fn start_emulation_thread(&mut self) {
+        if self.play.swap(true, std::sync::atomic::Ordering::Relaxed) {
+            return;
+        }
+
+        let gba_clone = Arc::clone(&self.gba);
+        let play_clone = Arc::clone(&self.play);
+        let rewinding_clone = Arc::clone(&self.rewinding);
+        let breakpoints_clone = Arc::clone(&self.breakpoints);
+        let execution_trap_clone = Arc::clone(&self.execution_trap);
+        let running_time_clone = Arc::clone(&self.running_time);
+        let pacer_clone = Arc::clone(&self.pacer);
+
+        self.thread_handle = Some(thread::spawn(move || {
+            let mut running = false;
+            for step in 1_u32.. {
+                if !play_clone.load(std::sync::atomic::Ordering::Relaxed) {
+                    break;
+                }
+
+                let paused = gba_clone.lock().unwrap().execution_state() == ExecutionState::Paused;
+                if paused == running {
+                    running = !paused;
+                    if running {
+                        running_time_clone.lock().unwrap().resume();
+                        pacer_clone.lock().unwrap().restart();
+                    } else {
+                        running_time_clone.lock().unwrap().pause();
+                    }
+                }
+                // The UI runs the frames while rewinding.
+                if paused || rewinding_clone.load(std::sync::atomic::Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(1));
+                    continue;
+                }
+
+                // Exact addresses are checked by the CPU itself.
+                breakpoints_clone
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|b| b.kind == BreakpointType::Greater)
+                    .for_each(|b| {
+                        let mut gba = gba_clone.lock().unwrap();
+                        let pc = u32::try_from(gba.cpu.registers.program_counter())
+                            .expect("Failed to convert u16 to u32");
+                        if pc > b.address {
+                            gba.pause();
+                        }
+                    });
+
+                let mut gba = gba_clone.lock().unwrap();
+                if let StepResult::BreakpointHit(_) = gba.step() {
+                    gba.pause();
+                }
+
+                if let Some(trap) = gba.cpu.take_execution_trap() {
+                    *execution_trap_clone.lock().unwrap() = Some(trap);
+                    gba.pause();
+                }
+
+                if let Some(misaligned) = gba.cpu.take_misaligned_pc() {
+                    gba.notify(Notification::error(
+                        NotificationKind::MisalignedPc,
+                        format!("Stopped: {misaligned}"),
+                    ));
+                    gba.pause();
+                }
+
+                // Already notified, with the last instructions.
+                if gba.take_frame_overrun().is_some() {
+                    gba.pause();
+                }
+
+                // Sleeping without the lock keeps the other tools live.
+                if step.is_multiple_of(PACING_STEPS) {
+                    let now = gba.clock_sample();
+                    drop(gba);
+                    thread::sleep(pacer_clone.lock().unwrap().delay(now));
+                }
+            }
+
+            if running {
+                running_time_clone.lock().unwrap().pause();
+            }
+        }));
+    }
+
+    /// Runs `count` steps, stopping early on a breakpoint.
+    fn step(&self, count: u64) {
+        if let Ok(mut gba) = self.gba.lock() {
+            for _ in 0..count {
+                if let StepResult::BreakpointHit(_) = gba.step() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Goes back while [`REWIND_KEY`] is held, a snapshot per UI frame. A frame is run
+    /// after each one to show it.
+    fn rewind_hotkey(&self, ctx: &egui::Context) {
+        let held = !ctx.wants_keyboard_input() && ctx.input(|i| i.key_down(REWIND_KEY));
+        self.rewinding
+            .store(held, std::sync::atomic::Ordering::Relaxed);
+        if !held {
+            return;
+        }
+
+        let mut gba = self.gba.lock().unwrap();
+        if gba.rewind().is_ok() {
+            let _ = gba.run_frame();
+        }
+    }
+
+    /// Starts or stops the fast-forward: the pacer runs at its speed and the core skips
+    /// the frames the display can't show.
+    fn set_fast_forward(&mut self, enabled: bool) {
+        if enabled == self.fast_forwarding {
+            return;
+        }
+        self.fast_forwarding = enabled;
+
+        let fast_forward = enabled.then(FastForward::default);
+        let mut pacer = self.pacer.lock().unwrap();
+        if let Some(fast_forward) = &fast_forward {
+            self.speed_before_fast_forward = pacer.speed();
+            pacer.set_speed(Some(fast_forward.speed));
+        } else {
+            pacer.set_speed(self.speed_before_fast_forward);
+        }
+        drop(pacer);
+
+        self.gba.lock().unwrap().set_fast_forward(fast_forward);
+    }
+
+    fn run_report(&self) -> String {
+        let running_time = self.running_time.lock().unwrap().elapsed();
+
+        self.gba
+            .lock()
+            .unwrap()
+            .run_report(running_time)
+            .to_string()
+    }
 }
 
-#[derive(Clone, Copy, Eq, Hash, PartialEq, Ord, PartialOrd)]
+/// Held to go back in time, see [`Gba::rewind`].
+const REWIND_KEY: egui::Key = egui::Key::Backspace;
+
+/// Held to fast-forward, see [`FastForward`].
+const FAST_FORWARD_KEY: egui::Key = egui::Key::Tab;
+
+/// Steps run between two checks of the pacing.
+const PACING_STEPS: u32 = 4096;
+
+/// Speeds offered to watch the game in slow motion or to skip ahead.
+const SPEEDS: [Option<f64>; 6] = [Some(0.1), Some(0.25), Some(0.5), Some(1.0), Some(2.0), None];
+
+/// Pacing strategies offered, the UI doesn't play the audio.
+const STRATEGIES: [PacingStrategy; 3] = [
+    PacingStrategy::Timer,
+    PacingStrategy::Display { refresh_rate: 60.0 },
+    PacingStrategy::FreeRun,
+];
+
+fn speed_label(speed: Option<f64>) -> String {
+    speed.map_or_else(|| "Unlimited".to_string(), |speed| format!("{speed}x"))
+}
+
+/// Time spent with the emulation playing, for the run report.
+#[derive(Default)]
+struct RunningTime {
+    total: Duration,
+    resumed_at: Option<Instant>,
+}
+
+impl RunningTime {
+    fn resume(&mut self) {
+        self.resumed_at = Some(Instant::now());
+    }
+
+    fn pause(&mut self) {
+        if let Some(resumed_at) = self.resumed_at.take() {
+            self.total += resumed_at.elapsed();
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.total + self.resumed_at.map_or(Duration::ZERO, |at| at.elapsed())
+    }
+}
+
+#[derive(Clone, Eq, Hash, PartialEq, Ord, PartialOrd)]
 struct Breakpoint {
     address: u32,
     kind: BreakpointType,
+    /// Only for [`BreakpointType::Equal`], evaluated by the CPU.
+    condition: Option<String>,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd)]
@@ -103,6 +375,11 @@ impl UiTool for CpuHandler {
     }
 
     fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+        self.rewind_hotkey(ctx);
+        let fast_forward_held =
+            !ctx.wants_keyboard_input() && ctx.input(|i| i.key_down(FAST_FORWARD_KEY));
+        self.set_fast_forward(self.fast_forward_on || fast_forward_held);
+
         egui::Window::new(self.name())
             .default_width(320.0)
             .open(open)
@@ -121,111 +398,254 @@ impl UiTool for CpuHandler {
             }
             ui.text_edit_singleline(&mut cartridge_name);
 
+            let paused = self.gba.lock().unwrap().execution_state() == ExecutionState::Paused;
+            let thread_running = self.play.load(std::sync::atomic::Ordering::Relaxed);
             if ui
-                .add_enabled(
-                    !self.play.load(std::sync::atomic::Ordering::Relaxed),
-                    egui::Button::new("▶"),
-                )
+                .add_enabled(!thread_running || paused, egui::Button::new("▶"))
                 .clicked()
             {
-                if self.play.load(std::sync::atomic::Ordering::Relaxed) {
-                    return;
-                }
-
-                let gba_clone = Arc::clone(&self.gba);
-                let play_clone = Arc::clone(&self.play);
-                let breakpoints_clone = Arc::clone(&self.breakpoints);
-
-                self.play.swap(true, std::sync::atomic::Ordering::Relaxed);
-
-                self.thread_handle = Some(thread::spawn(move || {
-                    while play_clone.load(std::sync::atomic::Ordering::Relaxed) {
-                        breakpoints_clone.lock().unwrap().iter().for_each(|&b| {
-                            let pc = u32::try_from(
-                                gba_clone.lock().unwrap().cpu.registers.program_counter(),
-                            )
-                            .expect("Failed to convert u16 to u32");
-                            match b.kind {
-                                BreakpointType::Equal => {
-                                    if pc == b.address {
-                                        play_clone
-                                            .swap(false, std::sync::atomic::Ordering::Relaxed);
-                                    }
-                                }
-                                BreakpointType::Greater => {
-                                    if pc > b.address {
-                                        play_clone
-                                            .swap(false, std::sync::atomic::Ordering::Relaxed);
-                                    }
-                                }
-                            }
-                        });
+                self.gba.lock().unwrap().resume();
+                self.start_emulation_thread();
+            }
 
-                        gba_clone.lock().unwrap().step();
-                    }
-                }));
+            if ui
+                .add_enabled(thread_running && !paused, egui::Button::new("⏸ "))
+                .clicked()
+            {
+                self.gba.lock().unwrap().pause();
             }
 
             if ui
-                .add_enabled(
-                    self.play.load(std::sync::atomic::Ordering::Relaxed),
-                    egui::Button::new("⏸ "),
-                )
+                .button("⏯")
+                .on_hover_text("Runs to the end of the frame, then pauses")
                 .clicked()
             {
-                self.play.swap(false, std::sync::atomic::Ordering::Relaxed);
-                self.thread_handle = None;
+                self.gba.lock().unwrap().frame_advance();
+                self.start_emulation_thread();
             }
+
+            ui.menu_button("⟲", |ui| {
+                let kinds = [
+                    (
+                        ResetKind::Soft,
+                        "Soft reset",
+                        "Like A+B+Select+Start in game",
+                    ),
+                    (
+                        ResetKind::Restart,
+                        "Reset",
+                        "Reboots, keeping the work RAM and the save",
+                    ),
+                    (
+                        ResetKind::Hard,
+                        "Hard reset",
+                        "Reboots, clearing the work RAM and erasing the save",
+                    ),
+                ];
+                for (kind, label, hover) in kinds {
+                    if ui.button(label).on_hover_text(hover).clicked() {
+                        if let Ok(mut gba) = self.gba.lock() {
+                            gba.reset(kind);
+                        }
+                        ui.close_menu();
+                    }
+                }
+            });
+
+            ui.toggle_value(&mut self.fast_forward_on, "⏩")
+                .on_hover_text(format!(
+                    "Fast-forward at {}x, also while {} is held",
+                    emu::fast_forward::DEFAULT_SPEED,
+                    FAST_FORWARD_KEY.name()
+                ));
+            ui.add_enabled_ui(!self.fast_forwarding, |ui| {
+                self.speed_combo(ui);
+            });
         });
 
+        let execution_trap = *self.execution_trap.lock().unwrap();
+        if let Some(trap) = execution_trap {
+            ui.horizontal(|ui| {
+                ui.colored_label(egui::Color32::RED, format!("Stopped: {trap}"));
+                if ui.button("X").clicked() {
+                    *self.execution_trap.lock().unwrap() = None;
+                }
+            });
+        }
+
+        if ui
+            .button("📋 Copy run report")
+            .on_hover_text("Summary of the session to paste in a GitHub issue")
+            .clicked()
+        {
+            let report = self.run_report();
+            ui.output_mut(|output| output.copied_text = report);
+        }
+
         ui.collapsing("CPU Advanced controls", |ui| {
-            ui.label(format!(
-                "Current CPU cycle: {}",
-                &mut self.gba.lock().unwrap().cpu.current_cycle
-            ));
+            if let Ok(gba) = self.gba.lock() {
+                ui.label(format!("Current CPU cycle: {}", gba.cpu.current_cycle));
+                ui.label(format!(
+                    "Bus cycle: {} ({:.3}s emulated)",
+                    gba.cycles(),
+                    gba.emulated_time().as_secs_f64()
+                ));
+
+                let target = self.pacer.lock().unwrap().frame_duration();
+                let target = target.map_or_else(
+                    || "unlimited".to_string(),
+                    |target| format!("{:.3} ms", target.as_secs_f64() * 1000.0),
+                );
+                let measured = gba.frame_pacing().map_or_else(
+                    || "not running".to_string(),
+                    |stats| {
+                        format!(
+                            "{:.3} ms ± {:.3} ms",
+                            stats.mean.as_secs_f64() * 1000.0,
+                            stats.jitter.as_secs_f64() * 1000.0
+                        )
+                    },
+                );
+                ui.label(format!("Frame time: {measured} (target {target})"));
+
+                let masters = gba.bus_masters();
+                ui.label(format!(
+                    "Bus: CPU {} cycles, DMA0-3 {:?}, longest stall {}",
+                    masters.cpu, masters.dma, masters.longest_stall
+                ));
+
+                let telemetry = self.pacer.lock().unwrap().telemetry();
+                ui.label(format!(
+                    "Pacing: {}, waited {:.1}s, {} resyncs",
+                    telemetry.strategy,
+                    telemetry.waited.as_secs_f64(),
+                    telemetry.resyncs
+                ));
+            }
 
-            ui.horizontal(|ui| {
-                ui.label("Step CPU cycles:");
+            self.strategy_combo(ui);
 
-                if ui.button("⏭x1").clicked() {
-                    if let Ok(mut gba) = self.gba.lock() {
-                        gba.step();
-                    }
+            if let Ok(mut gba) = self.gba.lock() {
+                let mut ppu_write_log = gba.ppu_write_log();
+                if ui
+                    .checkbox(&mut ppu_write_log, "Log PPU register writes")
+                    .changed()
+                {
+                    gba.set_ppu_write_log(ppu_write_log);
                 }
 
-                if ui.button("⏭x10").clicked() {
-                    if let Ok(mut gba) = self.gba.lock() {
-                        (0..10).for_each(|_| gba.step());
-                    }
+                let mut bios_hle = gba.config().bios_hle;
+                if ui
+                    .checkbox(&mut bios_hle, "Emulate BIOS functions")
+                    .on_hover_text("Off, every SWI runs the code of the BIOS file")
+                    .changed()
+                {
+                    gba.set_bios_hle(bios_hle);
                 }
 
-                if ui.button("⏭x100").clicked() {
-                    if let Ok(mut gba) = self.gba.lock() {
-                        (0..100).for_each(|_| gba.step());
-                    }
+                let mut overclock = gba.config().overclock;
+                egui::ComboBox::from_label("CPU overclock")
+                    .selected_text(format!("x{}", overclock.multiplier()))
+                    .show_ui(ui, |ui| {
+                        for option in Overclock::ALL {
+                            ui.selectable_value(
+                                &mut overclock,
+                                option,
+                                format!("x{}", option.multiplier()),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text("Removes slowdown, the video and the sound keep their speed");
+                if overclock != gba.config().overclock {
+                    gba.set_overclock(overclock);
                 }
 
-                if ui.button("⏭x500").clicked() {
-                    if let Ok(mut gba) = self.gba.lock() {
-                        (0..500).for_each(|_| gba.step());
+                let mut trap_on_misaligned_pc = gba.cpu.trap_on_misaligned_pc();
+                if ui
+                    .checkbox(&mut trap_on_misaligned_pc, "Stop on misaligned PC")
+                    .changed()
+                {
+                    gba.cpu.set_trap_on_misaligned_pc(trap_on_misaligned_pc);
+                }
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Step CPU cycles:");
+
+                for count in [1, 10, 100, 500, 1000] {
+                    if ui.button(format!("⏭x{count}")).clicked() {
+                        self.step(count);
                     }
                 }
+            });
 
-                if ui.button("⏭x1000").clicked() {
-                    if let Ok(mut gba) = self.gba.lock() {
-                        (0..1000).for_each(|_| gba.step());
+            ui.horizontal(|ui| {
+                let (mut recording, bounded) = {
+                    let gba = self.gba.lock().unwrap();
+                    (
+                        gba.step_history().is_some(),
+                        gba.config().memory_profile == MemoryProfile::Bounded,
+                    )
+                };
+                if ui
+                    .add_enabled(!bounded, egui::Checkbox::new(&mut recording, "Step back:"))
+                    .on_hover_text("Records save-states while running, to step backwards")
+                    .on_disabled_hover_text("Not available with the bounded memory profile")
+                    .changed()
+                {
+                    self.gba
+                        .lock()
+                        .unwrap()
+                        .set_step_history(recording.then(StepHistory::default));
+                }
+
+                for count in [1, 10, 100, 1000] {
+                    if ui
+                        .add_enabled(recording, egui::Button::new(format!("⏮x{count}")))
+                        .clicked()
+                    {
+                        let result = self.gba.lock().unwrap().step_back(count);
+                        self.step_back_error = result.err().map(|e| e.to_string());
                     }
                 }
             });
 
+            let (mut rewind, bounded) = {
+                let gba = self.gba.lock().unwrap();
+                (
+                    gba.rewind_buffer().is_some(),
+                    gba.config().memory_profile == MemoryProfile::Bounded,
+                )
+            };
+            if ui
+                .add_enabled(
+                    !bounded,
+                    egui::Checkbox::new(
+                        &mut rewind,
+                        format!("Rewind (hold {})", REWIND_KEY.name()),
+                    ),
+                )
+                .on_hover_text("Takes a save-state every few frames to go back in time")
+                .on_disabled_hover_text("Not available with the bounded memory profile")
+                .changed()
+            {
+                self.gba
+                    .lock()
+                    .unwrap()
+                    .set_rewind(rewind.then(RewindBuffer::default));
+            }
+
+            if let Some(error) = &self.step_back_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+
             ui.horizontal(|ui| {
                 ui.label("Step (custom) CPU cycles:");
                 ui.add(egui::DragValue::new(&mut self.cycle_to_skip_custom_value).speed(100));
 
                 if ui.button("Step").clicked() {
-                    if let Ok(mut gba) = self.gba.lock() {
-                        (0..self.cycle_to_skip_custom_value).for_each(|_| gba.step());
-                    }
+                    self.step(self.cycle_to_skip_custom_value);
                 }
             })
         });
@@ -273,17 +693,58 @@ impl UiTool for CpuHandler {
                     };
 
                     let address = u32::from_str_radix(&a, 16).unwrap();
+                    let condition = if self.breakpoint_combo == BreakpointType::Equal
+                        && !self.b_condition.trim().is_empty()
+                    {
+                        match self.b_condition.parse::<Condition>() {
+                            Ok(condition) => Some(condition),
+                            Err(e) => {
+                                self.condition_error = Some(e.to_string());
+                                return;
+                            }
+                        }
+                    } else {
+                        None
+                    };
                     let b = Breakpoint {
                         address,
                         kind: self.breakpoint_combo,
+                        condition: condition.as_ref().map(ToString::to_string),
                     };
 
+                    if b.kind == BreakpointType::Equal {
+                        let mut breakpoint = breakpoints::Breakpoint::new(address, StateMask::ANY);
+                        breakpoint.condition = condition;
+                        if let Ok(mut gba) = self.gba.lock() {
+                            gba.cpu.add_breakpoint(breakpoint);
+                        }
+
+                        // The CPU keeps a single breakpoint per address.
+                        self.breakpoints.lock().unwrap().retain(|other| {
+                            other.kind != BreakpointType::Equal || other.address != address
+                        });
+                    }
                     self.breakpoints.lock().unwrap().insert(b);
 
                     self.b_address.clear();
+                    self.b_condition.clear();
+                    self.condition_error = None;
                 }
             });
 
+            ui.horizontal(|ui| {
+                ui.label("Condition:");
+                ui.add_enabled(
+                    self.breakpoint_combo == BreakpointType::Equal,
+                    TextEdit::singleline(&mut self.b_condition)
+                        .hint_text("r0 == 0x40 && [0x03001234] != 0"),
+                );
+            });
+
+            if let Some(error) = &self.condition_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+
             egui::containers::ScrollArea::new([false, true]).show(ui, |ui| {
                 ui.label("Active breakpoints:");
                 let breakpoints = self.breakpoints.lock().unwrap().clone();
@@ -291,7 +752,15 @@ impl UiTool for CpuHandler {
                 for b in &breakpoints {
                     ui.horizontal(|ui| {
                         ui.label(format!("0x{:08X}", b.address));
+                        if let Some(condition) = &b.condition {
+                            ui.label(format!("if {condition}"));
+                        }
                         if ui.button("X").clicked() {
+                            if b.kind == BreakpointType::Equal {
+                                if let Ok(mut gba) = self.gba.lock() {
+                                    gba.cpu.remove_breakpoint(b.address);
+                                }
+                            }
                             self.breakpoints.lock().unwrap().remove(b);
                         }
                     });
@@ -299,4 +768,13 @@ impl UiTool for CpuHandler {
             });
         });
     }
+
+    fn on_exit(&mut self) {
+        self.play.swap(false, std::sync::atomic::Ordering::Relaxed);
+        if let Some(handle) = self.thread_handle.take() {
+            handle.join().ok();
+        }
+
+        println!("{}", self.run_report());
+    }
 }
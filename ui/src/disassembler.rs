@@ -5,11 +5,39 @@ use std::sync::{Arc, Mutex};
 
 pub struct Disassembler {
     gba: Arc<Mutex<Gba>>,
+    patch_address: String,
+    patch_source: String,
+    patch_thumb: bool,
+    /// Outcome of the last patch, shown until the next one.
+    patch_status: Option<Result<String, String>>,
 }
 
 impl Disassembler {
     pub(crate) fn new(arc_gba: Arc<Mutex<Gba>>) -> Self {
-        Self { gba: arc_gba }
+        Self {
+            gba: arc_gba,
+            patch_address: String::new(),
+            patch_source: String::new(),
+            patch_thumb: false,
+            patch_status: None,
+        }
+    }
+
+    fn patch(&self) -> Result<String, String> {
+        let address = self.patch_address.trim();
+        let address = address.strip_prefix("0x").unwrap_or(address);
+        let address =
+            u32::from_str_radix(address, 16).map_err(|_| format!("invalid address `{address}`"))?;
+
+        let written = self
+            .gba
+            .lock()
+            .unwrap()
+            .cpu
+            .patch_instruction(address, &self.patch_source, self.patch_thumb)
+            .map_err(|err| err.to_string())?;
+
+        Ok(format!("{written} bytes written at 0x{address:08X}"))
     }
 }
 
@@ -28,6 +56,39 @@ impl UiTool for Disassembler {
     }
 
     fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Patch instruction", |ui| {
+            ui.horizontal(|ui| {
+                ui.label("address (HEX):");
+                ui.add(
+                    TextEdit::singleline(&mut self.patch_address)
+                        .desired_width(100.0)
+                        .char_limit(10),
+                );
+
+                ui.add(
+                    TextEdit::singleline(&mut self.patch_source)
+                        .hint_text("MOV R0, #1")
+                        .font(TextStyle::Monospace),
+                );
+
+                ui.checkbox(&mut self.patch_thumb, "Thumb");
+
+                if ui.button("Patch").clicked() {
+                    self.patch_status = Some(self.patch());
+                }
+            });
+
+            match &self.patch_status {
+                Some(Ok(message)) => {
+                    ui.label(message);
+                }
+                Some(Err(message)) => {
+                    ui.colored_label(egui::Color32::RED, message);
+                }
+                None => {}
+            }
+        });
+
         let mut s = self.gba.lock().unwrap().cpu.disassembler_buffer.join("\n");
 
         ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui| {
@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex};
+
+use emu::cartridge_info::CartridgeInfo;
+use emu::gba::Gba;
+
+use crate::ui_traits::UiTool;
+
+pub struct GameProperties {
+    gba: Arc<Mutex<Gba>>,
+    /// Detecting the save type scans the whole ROM, it's only done again on request.
+    info: CartridgeInfo,
+}
+
+impl GameProperties {
+    pub fn new(gba: Arc<Mutex<Gba>>) -> Self {
+        let info = gba.lock().unwrap().cartridge_info();
+
+        Self { gba, info }
+    }
+}
+
+fn list<T: ToString>(items: &[T]) -> String {
+    if items.is_empty() {
+        return "none".to_string();
+    }
+
+    items
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl UiTool for GameProperties {
+    fn name(&self) -> &'static str {
+        "Game Properties"
+    }
+
+    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+        egui::Window::new(self.name())
+            .default_width(320.0)
+            .open(open)
+            .show(ctx, |ui| self.ui(ui));
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let info = &self.info;
+        let header = &info.header;
+        let check = |valid| if valid { "✔" } else { "✘" };
+
+        egui::Grid::new("game-properties").show(ui, |ui| {
+            ui.label("Title:");
+            ui.label(header.game_title.trim_end_matches('\0'));
+            ui.end_row();
+
+            ui.label("Game code:");
+            ui.label(format!("AGB-{}", header.game_code));
+            ui.end_row();
+
+            ui.label("Maker code:");
+            ui.label(&header.marker_code);
+            ui.end_row();
+
+            ui.label("Version:");
+            ui.label(header.software_version[0].to_string());
+            ui.end_row();
+
+            ui.label("ROM size:");
+            ui.label(format!("{} KB", info.rom_size / 1024));
+            ui.end_row();
+
+            ui.label("Header checksum:");
+            ui.label(format!(
+                "0x{:02X} {}",
+                header.complement_check,
+                check(info.header_checksum_valid)
+            ));
+            ui.end_row();
+
+            ui.label("Fixed value:");
+            ui.label(check(info.fixed_value_valid));
+            ui.end_row();
+
+            ui.label("Save type:");
+            ui.label(info.save_type.to_string());
+            ui.end_row();
+
+            ui.label("Hardware:");
+            ui.label(list(&info.hardware));
+            ui.end_row();
+
+            ui.label("Emulated peripherals:");
+            ui.label(list(&info.peripherals));
+            ui.end_row();
+        });
+
+        if ui.button("Refresh").clicked() {
+            self.info = self.gba.lock().unwrap().cartridge_info();
+        }
+    }
+}
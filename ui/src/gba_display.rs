@@ -2,9 +2,9 @@ use egui::{self, ColorImage, ImageSource, Ui};
 
 use eframe::epaint::textures::TextureOptions;
 use egui::load::SizedTexture;
-use std::sync::{Arc, Mutex};
-
 use emu::{
+    config::ColorCorrection,
+    cpu::hardware::lcd::FrameOutput,
     gba::Gba,
     render::{LCD_HEIGHT, LCD_WIDTH},
 };
@@ -12,33 +12,27 @@ use emu::{
 use crate::ui_traits::UiTool;
 
 pub struct GbaDisplay {
-    gba: Arc<Mutex<Gba>>,
+    frame_output: FrameOutput,
+    color_correction: ColorCorrection,
 }
 
 impl GbaDisplay {
-    pub(crate) const fn new(gba: Arc<Mutex<Gba>>) -> Self {
-        Self { gba }
+    pub(crate) fn new(gba: &Gba) -> Self {
+        Self {
+            frame_output: gba.frame_output(),
+            color_correction: gba.config().color_correction,
+        }
     }
 
     #[allow(clippy::needless_pass_by_ref_mut)]
     fn ui(&mut self, ui: &mut Ui) {
-        //TODO: Fix this .lock().unwrap() repeated two times
         let rgb_data = self
-            .gba
-            .lock()
-            .unwrap()
-            .cpu
-            .bus
-            .lcd
-            .buffer
+            .frame_output
+            .load()
             .iter()
             .flat_map(|row| {
-                row.iter().flat_map(|pixel| {
-                    let red = (pixel.red() << 3) | (pixel.red() >> 2);
-                    let green = (pixel.green() << 3) | (pixel.green() >> 2);
-                    let blue = (pixel.blue() << 3) | (pixel.blue() >> 2);
-                    [red, green, blue]
-                })
+                row.iter()
+                    .flat_map(|pixel| pixel.to_rgb8(self.color_correction))
             })
             .collect::<Vec<_>>();
 
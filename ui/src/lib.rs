@@ -1,10 +1,14 @@
 mod about;
 pub mod app;
+mod cheats;
 mod cpu_handler;
 mod cpu_registers;
 #[cfg(feature = "disassembler")]
 mod disassembler;
+mod game_properties;
 mod gba_color;
 mod gba_display;
+mod notifications;
 mod savegame;
+mod solar_sensor;
 mod ui_traits;
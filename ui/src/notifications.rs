@@ -0,0 +1,95 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use emu::{
+    gba::{ExecutionState, Gba},
+    notifications::{Notification, Severity},
+};
+
+/// How often the notifications are polled while the emulation is paused: the window is
+/// only repainted on the frames drawn otherwise.
+const PAUSED_POLL: Duration = Duration::from_millis(200);
+
+/// Shows the notifications of the emulator as toasts in the bottom left corner.
+pub struct Toasts {
+    gba: Arc<Mutex<Gba>>,
+    shown: Vec<(Notification, Instant)>,
+}
+
+impl Toasts {
+    pub const fn new(gba: Arc<Mutex<Gba>>) -> Self {
+        Self {
+            gba,
+            shown: Vec::new(),
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let now = Instant::now();
+
+        let (notifications, state) = {
+            let mut gba = self.gba.lock().unwrap();
+            (gba.take_notifications(), gba.execution_state())
+        };
+        if state != ExecutionState::Running {
+            ctx.request_repaint_after(PAUSED_POLL);
+        }
+        for notification in notifications {
+            self.shown
+                .retain(|(shown, _)| shown.kind != notification.kind);
+            self.shown.push((notification, now));
+        }
+
+        self.shown.retain(|(notification, since)| {
+            notification
+                .timeout
+                .is_none_or(|timeout| now.duration_since(*since) < timeout)
+        });
+
+        if self.shown.is_empty() {
+            return;
+        }
+        if let Some(left) = self
+            .shown
+            .iter()
+            .filter_map(|(notification, since)| {
+                notification
+                    .timeout
+                    .map(|timeout| timeout.saturating_sub(now.duration_since(*since)))
+            })
+            .min()
+        {
+            ctx.request_repaint_after(left);
+        }
+
+        let mut dismissed = None;
+        egui::Area::new(egui::Id::new("Notifications"))
+            .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(10.0, -10.0))
+            .show(ctx, |ui| {
+                for (index, (notification, _)) in self.shown.iter().enumerate() {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.colored_label(color(notification.severity), &notification.message);
+                            if notification.timeout.is_none() && ui.small_button("✖").clicked() {
+                                dismissed = Some(index);
+                            }
+                        });
+                    });
+                }
+            });
+
+        if let Some(index) = dismissed {
+            self.shown.remove(index);
+        }
+    }
+}
+
+const fn color(severity: Severity) -> egui::Color32 {
+    match severity {
+        Severity::Info => egui::Color32::LIGHT_GRAY,
+        Severity::Warning => egui::Color32::YELLOW,
+        Severity::Error => egui::Color32::LIGHT_RED,
+    }
+}
@@ -1,23 +1,30 @@
 use std::{
     error::Error,
-    io::{Read, Write},
     sync::{Arc, Mutex},
 };
 
+use emu::atomic_file::{self, DEFAULT_BACKUPS};
+use emu::battery_save::SaveLayout;
 use emu::gba::Gba;
+use emu::movie::{Movie, MovieStart, MovieStatus};
 
 use crate::ui_traits::UiTool;
-use emu::cpu::arm7tdmi::Arm7tdmi;
+use emu::save_state::LoadReport;
 use native_dialog::{FileDialog, MessageDialog};
 use std::fs;
 
 pub struct SaveGame {
     gba: Arc<Mutex<Gba>>,
+    /// Layout of the exported battery saves.
+    export_layout: SaveLayout,
 }
 
 impl SaveGame {
     pub const fn new(gba: Arc<Mutex<Gba>>) -> Self {
-        Self { gba }
+        Self {
+            gba,
+            export_layout: SaveLayout::Raw,
+        }
     }
 
     fn save_state(&self) -> Result<(), Box<dyn Error>> {
@@ -28,39 +35,93 @@ impl SaveGame {
 
         let path = path.ok_or("No file selected")?;
 
-        let cpu = &self.gba.lock().unwrap().cpu;
+        self.gba.lock().unwrap().save_state_to_file(&path)?;
+
+        Ok(())
+    }
+
+    fn load_state(&self) -> Result<LoadReport, Box<dyn Error>> {
+        let path = FileDialog::new()
+            .set_location("~")
+            .add_filter("Clementine save file", &["clm"])
+            .show_open_single_file()?;
+
+        let path = path.ok_or("No file selected")?;
+
+        let report = self.gba.lock().unwrap().load_state_from_file(&path)?;
 
-        let encoded = bincode::serialize(cpu)?;
-        let mut file = fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open(path)?;
+        Ok(report)
+    }
+
+    fn save_movie(&self) -> Result<(), Box<dyn Error>> {
+        let movie = self.gba.lock().unwrap().stop_recording()?;
+
+        let path = FileDialog::new()
+            .set_location("~")
+            .add_filter("Clementine movie", &["clmv"])
+            .show_save_single_file()?;
 
-        file.write_all(&encoded)?;
+        let path = path.ok_or("No file selected")?;
+        movie.write_file(&path)?;
 
         Ok(())
     }
 
-    fn load_state(&self) -> Result<(), Box<dyn Error>> {
+    fn play_movie(&self) -> Result<(), Box<dyn Error>> {
         let path = FileDialog::new()
             .set_location("~")
-            .add_filter("Clementine save file", &["clm"])
+            .add_filter("Clementine movie", &["clmv"])
+            .show_open_single_file()?;
+
+        let path = path.ok_or("No file selected")?;
+        let movie = Movie::read_file(&path)?;
+        self.gba.lock().unwrap().play_movie(&movie)?;
+
+        Ok(())
+    }
+
+    fn import_battery_save(&self) -> Result<(), Box<dyn Error>> {
+        let path = FileDialog::new()
+            .set_location("~")
+            .add_filter("Battery save", &["sav"])
             .show_open_single_file()?;
 
         let path = path.ok_or("No file selected")?;
+        let file = fs::read(path)?;
+
+        self.gba.lock().unwrap().import_save(&file)?;
 
-        let mut file = fs::OpenOptions::new().read(true).open(path)?;
-        let mut encoded = Vec::new();
-        file.read_to_end(&mut encoded)?;
+        Ok(())
+    }
+
+    fn export_battery_save(&self) -> Result<(), Box<dyn Error>> {
+        let file = self
+            .gba
+            .lock()
+            .unwrap()
+            .export_save(self.export_layout)
+            .ok_or("The cartridge has no backup memory")?;
+
+        let path = FileDialog::new()
+            .set_location("~")
+            .add_filter("Battery save", &["sav"])
+            .show_save_single_file()?;
 
-        let cpu = &mut self.gba.lock().unwrap().cpu;
-        let decoded: Arm7tdmi = bincode::deserialize(&encoded)?;
-        *cpu = decoded;
+        let path = path.ok_or("No file selected")?;
+        atomic_file::write(&path, &file, DEFAULT_BACKUPS)?;
 
         Ok(())
     }
 }
 
+fn show_error(err: &dyn Error) {
+    MessageDialog::new()
+        .set_title("Clementine")
+        .set_text(err.to_string().as_str())
+        .show_alert()
+        .unwrap();
+}
+
 impl UiTool for SaveGame {
     fn name(&self) -> &'static str {
         "Save Game"
@@ -86,13 +147,79 @@ impl UiTool for SaveGame {
         }
 
         if ui.button("Load").clicked() {
-            self.load_state().unwrap_or_else(|err| {
+            // An incomplete load is reported by the emulator's notifications.
+            if let Err(err) = self.load_state() {
                 MessageDialog::new()
                     .set_title("Clementine")
                     .set_text(err.to_string().as_str())
                     .show_alert()
                     .unwrap();
-            });
+            }
+        }
+
+        ui.separator();
+
+        let status = self.gba.lock().unwrap().movie_status();
+        match status {
+            MovieStatus::Idle => {
+                ui.horizontal(|ui| {
+                    ui.label("Record movie from");
+                    for (start, label) in
+                        [(MovieStart::PowerOn, "power-on"), (MovieStart::Now, "now")]
+                    {
+                        if ui.button(label).clicked() {
+                            let result = self.gba.lock().unwrap().start_recording(start);
+                            if let Err(err) = result {
+                                show_error(&err);
+                            }
+                        }
+                    }
+                });
+                if ui.button("Play movie").clicked() {
+                    if let Err(err) = self.play_movie() {
+                        show_error(&*err);
+                    }
+                }
+            }
+            MovieStatus::Recording { frames } => {
+                ui.label(format!("Recording: {frames} frames"));
+                if ui.button("Stop and save movie").clicked() {
+                    if let Err(err) = self.save_movie() {
+                        show_error(&*err);
+                    }
+                }
+            }
+            MovieStatus::Playing { frame, frames } => {
+                ui.label(format!("Playing: frame {frame} of {frames}"));
+                if ui.button("Stop movie").clicked() {
+                    self.gba.lock().unwrap().stop_movie();
+                }
+            }
+        }
+
+        ui.separator();
+
+        if ui.button("Import .sav").clicked() {
+            // A successful import is reported by the emulator's notifications.
+            if let Err(err) = self.import_battery_save() {
+                show_error(&*err);
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Export as");
+            ui.radio_value(&mut self.export_layout, SaveLayout::Raw, "Raw");
+            ui.radio_value(
+                &mut self.export_layout,
+                SaveLayout::Padded { len: 0x2_0000 },
+                "Padded to 128K",
+            );
+        });
+
+        if ui.button("Export .sav").clicked() {
+            if let Err(err) = self.export_battery_save() {
+                show_error(&*err);
+            }
         }
     }
 }
@@ -0,0 +1,45 @@
+use emu::cpu::hardware::solar::SolarLight;
+use emu::gba::Gba;
+
+use crate::ui_traits::UiTool;
+
+pub struct SolarSensor {
+    /// `None` when the cartridge has no sensor.
+    light: Option<SolarLight>,
+}
+
+impl SolarSensor {
+    pub fn new(gba: &Gba) -> Self {
+        Self {
+            light: gba.solar_light().cloned(),
+        }
+    }
+}
+
+impl UiTool for SolarSensor {
+    fn name(&self) -> &'static str {
+        "Solar Sensor"
+    }
+
+    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+        egui::Window::new(self.name())
+            .default_width(240.0)
+            .open(open)
+            .show(ctx, |ui| self.ui(ui));
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let Some(light) = &self.light else {
+            ui.label("The cartridge has no solar sensor.");
+            return;
+        };
+
+        let mut level = light.level();
+        if ui
+            .add(egui::Slider::new(&mut level, 0..=SolarLight::MAX).text("Sunlight"))
+            .changed()
+        {
+            light.set_level(level);
+        }
+    }
+}
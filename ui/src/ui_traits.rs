@@ -6,4 +6,7 @@ pub trait UiTool {
     fn show(&mut self, ctx: &egui::Context, open: &mut bool);
 
     fn ui(&mut self, ui: &mut egui::Ui);
+
+    /// Called once when the application is closing.
+    fn on_exit(&mut self) {}
 }